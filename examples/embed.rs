@@ -0,0 +1,35 @@
+//! Embedding pmr as a library: start a process, inspect it, read its logs and delete it,
+//! all inside a throwaway directory.
+//!
+//! Run with `cargo run --example embed`.
+
+use pmr::prelude::*;
+use std::collections::HashMap;
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let dir = tempfile::TempDir::new()?;
+    let manager = ProcessManager::in_directory(dir.path()).await?;
+
+    let mut env = HashMap::new();
+    env.insert("GREETING".to_string(), "hello from pmr".to_string());
+    let name: ProcessName = "greeter".parse()?;
+    let started: StartOutcome = manager
+        .start(&name, "sh", vec!["-c".to_string(), "echo $GREETING; sleep 30".to_string()], env, None, None)
+        .await?;
+    println!("started {} with pid {:?}", started.name, started.pid);
+
+    let record: ProcessRecord = manager.get_process_status(&name).await?;
+    println!("{} is {} (pid {:?})", record.name, record.status, record.pid);
+    assert_eq!(record.status, ProcessStatus::Running);
+
+    // Give the shell a moment to write its first line
+    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+    let logs = manager.get_process_logs(&name, Some(10)).await?;
+    println!("logs:\n{}", logs);
+
+    let stopped = manager.stop(&name).await?;
+    println!("stopped after {}ms", stopped.waited_ms);
+    manager.delete(&name).await?;
+    Ok(())
+}
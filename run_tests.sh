@@ -177,6 +177,20 @@ else
     print_error "HTTP API build failed"
 fi
 
+# Test the library alone, without the CLI or HTTP API dependencies
+if cargo build --release --lib --no-default-features > /dev/null 2>&1; then
+    print_success "Library-only build successful"
+else
+    print_error "Library-only build failed"
+fi
+
+# Check every supported feature combination, tests and examples included
+if cargo test --test feature_matrix_tests -- --ignored > /dev/null 2>&1; then
+    print_success "Feature combinations check successful"
+else
+    print_error "Feature combinations check failed"
+fi
+
 echo ""
 echo "📊 Test Summary"
 echo "==============="
@@ -0,0 +1,165 @@
+//! CPU pinning for started processes, written like taskset's list syntax (`0,2-3`).
+
+use crate::{Error, Result};
+use std::collections::BTreeSet;
+use std::fmt;
+use std::str::FromStr;
+
+/// Highest CPU count a cpu_set_t can describe
+const MAX_CPUS: usize = 1024;
+
+/// A non-empty set of CPU indices
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CpuSet {
+    cpus: BTreeSet<usize>,
+}
+
+impl CpuSet {
+    pub fn cpus(&self) -> impl Iterator<Item = usize> + '_ {
+        self.cpus.iter().copied()
+    }
+
+    /// Reject CPUs the machine does not have
+    pub fn validate(&self, available: usize) -> Result<()> {
+        match self.cpus.iter().find(|&&cpu| cpu >= available) {
+            Some(cpu) => Err(Error::InvalidCpuAffinity(format!(
+                "CPU {} does not exist (this machine has {} CPUs: 0-{})",
+                cpu,
+                available,
+                available.saturating_sub(1)
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    /// Make a command pin its child to this set before it executes
+    #[cfg(target_os = "linux")]
+    pub fn apply_to(&self, command: &mut tokio::process::Command) -> Result<()> {
+        // SAFETY: cpu_set_t is plain data, and CPU_ZERO/CPU_SET only write into it
+        let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+        unsafe { libc::CPU_ZERO(&mut set) };
+        for cpu in &self.cpus {
+            unsafe { libc::CPU_SET(*cpu, &mut set) };
+        }
+
+        // SAFETY: the hook only calls sched_setaffinity, which is async-signal-safe
+        unsafe {
+            command.pre_exec(move || {
+                if libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set) != 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+        Ok(())
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn apply_to(&self, _command: &mut tokio::process::Command) -> Result<()> {
+        Err(Error::InvalidCpuAffinity("CPU affinity is only supported on Linux".to_string()))
+    }
+}
+
+/// Number of CPUs configured on this machine
+pub fn available_cpus() -> usize {
+    let configured = unsafe { libc::sysconf(libc::_SC_NPROCESSORS_CONF) };
+    if configured > 0 {
+        configured as usize
+    } else {
+        std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1)
+    }
+}
+
+impl FromStr for CpuSet {
+    type Err = Error;
+
+    fn from_str(list: &str) -> Result<Self> {
+        let invalid = |reason: String| Error::InvalidCpuAffinity(format!("'{}': {}", list, reason));
+        let parse_cpu = |value: &str| match value.trim().parse::<usize>() {
+            Ok(cpu) if cpu < MAX_CPUS => Ok(cpu),
+            Ok(cpu) => Err(invalid(format!("CPU {} is beyond the supported {} CPUs", cpu, MAX_CPUS))),
+            Err(_) => Err(invalid(format!("'{}' is not a CPU number", value.trim()))),
+        };
+
+        let mut cpus = BTreeSet::new();
+        if list.trim().is_empty() {
+            return Err(invalid("the CPU list is empty".to_string()));
+        }
+        for part in list.split(',') {
+            match part.split_once('-') {
+                Some((start, end)) => {
+                    let (start, end) = (parse_cpu(start)?, parse_cpu(end)?);
+                    if start > end {
+                        return Err(invalid(format!("range {}-{} is reversed", start, end)));
+                    }
+                    cpus.extend(start..=end);
+                }
+                None if part.trim().is_empty() => return Err(invalid("empty entry in the CPU list".to_string())),
+                None => {
+                    cpus.insert(parse_cpu(part)?);
+                }
+            }
+        }
+        Ok(Self { cpus })
+    }
+}
+
+/// Canonical list form with consecutive CPUs collapsed into ranges
+impl fmt::Display for CpuSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut ranges: Vec<(usize, usize)> = Vec::new();
+        for &cpu in &self.cpus {
+            match ranges.last_mut() {
+                Some((_, end)) if *end + 1 == cpu => *end = cpu,
+                _ => ranges.push((cpu, cpu)),
+            }
+        }
+        let parts: Vec<String> = ranges
+            .into_iter()
+            .map(|(start, end)| if start == end { start.to_string() } else { format!("{}-{}", start, end) })
+            .collect();
+        write!(f, "{}", parts.join(","))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(list: &str) -> Vec<usize> {
+        list.parse::<CpuSet>().unwrap().cpus().collect()
+    }
+
+    #[test]
+    fn test_parse_lists_and_ranges() {
+        assert_eq!(parse("0"), vec![0]);
+        assert_eq!(parse("3,1"), vec![1, 3]);
+        assert_eq!(parse("0,2-3"), vec![0, 2, 3]);
+        assert_eq!(parse("4-4"), vec![4]);
+        assert_eq!(parse(" 1 , 5 - 7 "), vec![1, 5, 6, 7]);
+        assert_eq!(parse("2-3,3-4,2"), vec![2, 3, 4]);
+    }
+
+    #[test]
+    fn test_parse_rejects_malformed_lists() {
+        for list in ["", " ", ",", "1,", ",1", "1,,2", "a", "1-", "-1", "3-1", "1-2-3", "1.5", "-", "0-4096"] {
+            let err = list.parse::<CpuSet>().unwrap_err();
+            assert!(matches!(err, Error::InvalidCpuAffinity(_)), "{:?} gave {:?}", list, err);
+        }
+    }
+
+    #[test]
+    fn test_display_collapses_ranges() {
+        for (list, canonical) in [("0", "0"), ("0,1,2", "0-2"), ("0,2-3,5", "0,2-3,5"), ("7,6,1", "1,6-7"), ("1-3,2-5", "1-5")] {
+            assert_eq!(list.parse::<CpuSet>().unwrap().to_string(), canonical);
+        }
+    }
+
+    #[test]
+    fn test_validate_against_available_cpus() {
+        let set: CpuSet = "0,3".parse().unwrap();
+        assert!(set.validate(4).is_ok());
+        let err = set.validate(2).unwrap_err().to_string();
+        assert!(err.contains("CPU 3 does not exist"), "{}", err);
+    }
+}
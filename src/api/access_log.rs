@@ -0,0 +1,139 @@
+//! The API server's access log: one line per request, appended to
+//! [`Config::access_log_path`](crate::config::Config::access_log_path) and rotated by the
+//! same [`LogRotator`] settings as process logs.
+//!
+//! A line reads `<timestamp> <token id> <method> <path> <status> <latency>ms <remote addr>`,
+//! naming the token by its public id (`pmr_ab12cd34`), with `-` for a request without a
+//! valid token or a server without peer addresses.
+//! The values of query parameters named like `token` are written as `[REDACTED]`.
+
+#[cfg(feature = "http-api")]
+use crate::{api::auth::AuthManager, log_rotation::LogRotator, storage::StorageBackend, Result};
+#[cfg(feature = "http-api")]
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{header, Uri},
+    middleware::Next,
+    response::Response,
+};
+#[cfg(feature = "http-api")]
+use chrono::{SecondsFormat, Utc};
+#[cfg(feature = "http-api")]
+use std::fs::{File, OpenOptions};
+#[cfg(feature = "http-api")]
+use std::io::Write;
+#[cfg(feature = "http-api")]
+use std::net::SocketAddr;
+#[cfg(feature = "http-api")]
+use std::path::{Path, PathBuf};
+#[cfg(feature = "http-api")]
+use std::sync::Arc;
+#[cfg(feature = "http-api")]
+use std::time::Instant;
+#[cfg(feature = "http-api")]
+use tokio::sync::Mutex;
+
+/// Written in place of secret query values
+#[cfg(feature = "http-api")]
+pub const REDACTED: &str = "[REDACTED]";
+
+/// Appends access lines to one file; cheap to clone, clones share the file
+#[cfg(feature = "http-api")]
+#[derive(Clone)]
+pub struct AccessLog {
+    path: PathBuf,
+    rotator: Arc<LogRotator>,
+    auth_manager: Arc<AuthManager>,
+    /// Opened on the first write and again after each rotation
+    file: Arc<Mutex<Option<File>>>,
+}
+
+#[cfg(feature = "http-api")]
+impl AccessLog {
+    pub fn new(path: PathBuf, rotator: Arc<LogRotator>, database: Arc<dyn StorageBackend>) -> Self {
+        Self {
+            path,
+            rotator,
+            auth_manager: Arc::new(AuthManager::new(database)),
+            file: Arc::default(),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Append one line, then rotate the file once it is past the size limit
+    pub async fn write_line(&self, line: &str) -> Result<()> {
+        let mut file = self.file.lock().await;
+        if file.is_none() {
+            if let Some(parent) = self.path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            *file = Some(OpenOptions::new().create(true).append(true).open(&self.path)?);
+        }
+        if let Some(handle) = file.as_mut() {
+            writeln!(handle, "{}", line)?;
+        }
+        if self.rotator.rotate_if_needed(&self.path).await?.is_some() {
+            // The open handle now points at the archive
+            *file = None;
+        }
+        Ok(())
+    }
+}
+
+/// Middleware writing an access line for every request it passes on
+#[cfg(feature = "http-api")]
+pub async fn log_access(State(access_log): State<AccessLog>, request: Request, next: Next) -> Response {
+    let started = Instant::now();
+    let timestamp = Utc::now();
+    let method = request.method().clone();
+    let path = redact_uri(request.uri());
+    let remote = request.extensions().get::<ConnectInfo<SocketAddr>>().map(|info| info.0.to_string());
+    let bearer = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::to_string);
+    let token = match bearer {
+        Some(bearer) => access_log.auth_manager.authenticate(&bearer).await.map(|token| token.public_id),
+        None => None,
+    };
+
+    let response = next.run(request).await;
+
+    let line = format!(
+        "{} {} {} {} {} {}ms {}",
+        timestamp.to_rfc3339_opts(SecondsFormat::Millis, true),
+        token.as_deref().unwrap_or("-"),
+        method,
+        path,
+        response.status().as_u16(),
+        started.elapsed().as_millis(),
+        remote.as_deref().unwrap_or("-"),
+    );
+    // A full disk must not take the API down with it
+    if let Err(e) = access_log.write_line(&line).await {
+        tracing::warn!(path = %access_log.path().display(), error = %e, "failed to write access log");
+    }
+    response
+}
+
+/// Path and query of `uri`, with the values of token-like query parameters redacted
+#[cfg(feature = "http-api")]
+pub fn redact_uri(uri: &Uri) -> String {
+    let Some(query) = uri.query() else {
+        return uri.path().to_string();
+    };
+    let query = query
+        .split('&')
+        .map(|pair| match pair.split_once('=') {
+            Some((key, _)) if key.to_ascii_lowercase().ends_with("token") => format!("{}={}", key, REDACTED),
+            _ => pair.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("&");
+    format!("{}?{}", uri.path(), query)
+}
@@ -1,7 +1,9 @@
 #[cfg(feature = "http-api")]
-use crate::{Error, database::Database};
+use crate::{Error, storage::StorageBackend};
 #[cfg(feature = "http-api")]
-use chrono::Utc;
+use crate::duration::HumanDuration;
+#[cfg(feature = "http-api")]
+use chrono::{DateTime, Utc};
 #[cfg(feature = "http-api")]
 use std::sync::Arc;
 #[cfg(feature = "http-api")]
@@ -9,84 +11,131 @@ use uuid::Uuid;
 
 // Re-export ApiToken from database module
 #[cfg(feature = "http-api")]
-pub use crate::database::ApiToken;
+pub use crate::database::{ApiToken, TOKEN_ID_LENGTH, TOKEN_ID_PREFIX};
+
+/// Tries at a token whose public id is not taken before giving up
+#[cfg(feature = "http-api")]
+const GENERATE_ATTEMPTS: usize = 5;
 
+/// Split a token into its public id and secret; None for tokens issued before public ids
 #[cfg(feature = "http-api")]
+pub fn split_token(token: &str) -> Option<(&str, &str)> {
+    let id_length = TOKEN_ID_PREFIX.len() + TOKEN_ID_LENGTH;
+    let public_id = token.get(..id_length)?;
+    let secret = token.get(id_length..)?.strip_prefix('_')?;
+    (is_token_id(public_id) && !secret.is_empty()).then_some((public_id, secret))
+}
+
+/// Whether `value` has the form of a public token id, e.g. `pmr_ab12cd34`
+#[cfg(feature = "http-api")]
+pub fn is_token_id(value: &str) -> bool {
+    value.strip_prefix(TOKEN_ID_PREFIX).is_some_and(|rest| {
+        rest.len() == TOKEN_ID_LENGTH && rest.bytes().all(|byte| byte.is_ascii_lowercase() || byte.is_ascii_digit())
+    })
+}
+
+#[cfg(feature = "http-api")]
+#[derive(Clone)]
 pub struct AuthManager {
-    database: Arc<Database>,
+    database: Arc<dyn StorageBackend>,
 }
 
 #[cfg(feature = "http-api")]
 impl AuthManager {
-    pub fn new(database: Arc<Database>) -> Self {
+    pub fn new(database: Arc<dyn StorageBackend>) -> Self {
         Self { database }
     }
 
-    /// Generate a new API token
-    pub async fn generate_token(&self, name: String, expires_in_days: Option<u32>) -> crate::Result<ApiToken> {
-        let id = Uuid::new_v4().to_string();
-        let token = self.generate_secure_token();
+    /// Generate a new API token, expiring `expires_in` after creation when given
+    pub async fn generate_token(&self, name: String, expires_in: Option<HumanDuration>) -> crate::Result<ApiToken> {
+        self.generate_scoped_token(name, expires_in, false).await
+    }
+
+    /// Generate a token that can also use admin-only endpoints, see [`ApiToken::admin`]
+    pub async fn generate_admin_token(&self, name: String, expires_in: Option<HumanDuration>) -> crate::Result<ApiToken> {
+        self.generate_scoped_token(name, expires_in, true).await
+    }
+
+    async fn generate_scoped_token(&self, name: String, expires_in: Option<HumanDuration>, admin: bool) -> crate::Result<ApiToken> {
         let created_at = Utc::now();
-        let expires_at = expires_in_days.map(|days| {
-            created_at + chrono::Duration::days(days as i64)
-        });
-
-        let api_token = ApiToken {
-            id: id.clone(),
-            token: token.clone(),
-            name,
-            created_at,
-            expires_at,
-            is_active: true,
-        };
+        let expires_at = expires_in.map(|duration| created_at + duration.as_duration());
+        self.insert_new_token(name, created_at, expires_at, admin, std::iter::repeat_with(generate_token_id)).await
+    }
 
-        self.database.insert_api_token(&api_token).await?;
-        Ok(api_token)
+    /// Store a token under the first of `public_ids` not already in use
+    async fn insert_new_token(
+        &self,
+        name: String,
+        created_at: DateTime<Utc>,
+        expires_at: Option<DateTime<Utc>>,
+        admin: bool,
+        public_ids: impl Iterator<Item = String>,
+    ) -> crate::Result<ApiToken> {
+        for public_id in public_ids.take(GENERATE_ATTEMPTS) {
+            let api_token = ApiToken {
+                id: Uuid::new_v4().to_string(),
+                token: format!("{}_{}", public_id, self.generate_secure_token()),
+                public_id,
+                name: name.clone(),
+                created_at,
+                expires_at,
+                is_active: true,
+                admin,
+            };
+            match self.database.insert_api_token(&api_token).await {
+                Ok(()) => return Ok(api_token),
+                Err(Error::Database(sqlx::Error::Database(e))) if e.is_unique_violation() => {
+                    tracing::warn!(public_id = %api_token.public_id, "generated token id is taken, trying another");
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Err(Error::Other(format!("Could not generate a token with an unused id in {} attempts", GENERATE_ATTEMPTS)))
     }
 
     /// Validate a token (blocking version for use in handlers)
     pub fn validate_token_sync(&self, token: &str) -> bool {
+        self.token_name_sync(token).is_some()
+    }
+
+    /// Name of the token if it is active and unexpired (blocking version for use in handlers)
+    pub fn token_name_sync(&self, token: &str) -> Option<String> {
         // Use tokio's block_in_place to run async code in sync context
-        tokio::task::block_in_place(|| {
-            tokio::runtime::Handle::current().block_on(async {
-                match self.database.get_api_token_by_token(token).await {
-                    Ok(Some(api_token)) => {
-                        if !api_token.is_active {
-                            return false;
-                        }
-
-                        if let Some(expires_at) = api_token.expires_at {
-                            if Utc::now() > expires_at {
-                                return false;
-                            }
-                        }
-
-                        true
-                    }
-                    _ => false,
-                }
-            })
-        })
+        tokio::task::block_in_place(|| tokio::runtime::Handle::current().block_on(self.token_name(token)))
     }
 
     /// Validate a token (async version)
     pub async fn validate_token(&self, token: &str) -> bool {
-        match self.database.get_api_token_by_token(token).await {
-            Ok(Some(api_token)) => {
-                if !api_token.is_active {
-                    return false;
-                }
+        self.token_name(token).await.is_some()
+    }
 
-                if let Some(expires_at) = api_token.expires_at {
-                    if Utc::now() > expires_at {
-                        return false;
-                    }
-                }
+    /// Name of the token if it is active and unexpired (async version)
+    pub async fn token_name(&self, token: &str) -> Option<String> {
+        self.authenticate(token).await.map(|api_token| api_token.name)
+    }
 
-                true
+    /// The token's record if it is active and unexpired
+    pub async fn authenticate(&self, token: &str) -> Option<ApiToken> {
+        let api_token = self.lookup(token).await.ok()??;
+        if !api_token.is_active {
+            return None;
+        }
+        if let Some(expires_at) = api_token.expires_at {
+            if Utc::now() > expires_at {
+                return None;
             }
-            _ => false,
         }
+        Some(api_token)
+    }
+
+    /// The record whose full value is `token`. The public id finds the row; the value is
+    /// then compared in constant time. Tokens without an id are looked up whole.
+    async fn lookup(&self, token: &str) -> crate::Result<Option<ApiToken>> {
+        let found = match split_token(token) {
+            Some((public_id, _)) => self.database.get_api_token_by_public_id(public_id).await?,
+            None => self.database.get_api_token_by_token(token).await?,
+        };
+        Ok(found.filter(|api_token| constant_time_eq(api_token.token.as_bytes(), token.as_bytes())))
     }
 
     /// List all tokens
@@ -94,14 +143,38 @@ impl AuthManager {
         self.database.get_all_api_tokens().await
     }
 
-    /// Revoke a token
+    /// Find one token by exact name or public id, or by a prefix of the token string
+    pub async fn find_token(&self, query: &str) -> crate::Result<ApiToken> {
+        let tokens = self.database.get_all_api_tokens().await?;
+        if let Some(token) = tokens.iter().find(|token| token.name == query || token.public_id == query) {
+            return Ok(token.clone());
+        }
+
+        let mut matches: Vec<&ApiToken> = tokens.iter().filter(|token| token.token.starts_with(query)).collect();
+        match matches.len() {
+            0 => Err(Error::Other(format!("No token named or starting with '{}'", query))),
+            1 => Ok(matches.remove(0).clone()),
+            count => Err(Error::Other(format!(
+                "'{}' matches {} tokens ({}); use a longer prefix",
+                query,
+                count,
+                matches.iter().map(|token| token.name.as_str()).collect::<Vec<_>>().join(", ")
+            ))),
+        }
+    }
+
+    /// Revoke a token, given in full or by its public id
     pub async fn revoke_token(&self, token: &str) -> crate::Result<()> {
-        let updated = self.database.update_api_token_status(token, false).await?;
-        if updated {
-            Ok(())
+        let found = if is_token_id(token) {
+            self.database.get_api_token_by_public_id(token).await?
         } else {
-            Err(Error::Other("Token not found".to_string()))
-        }
+            self.lookup(token).await?
+        };
+        let Some(found) = found else {
+            return Err(Error::Other("Token not found".to_string()));
+        };
+        self.database.update_api_token_status(&found.token, false).await?;
+        Ok(())
     }
 
     /// Generate a secure random token
@@ -114,3 +187,60 @@ impl AuthManager {
         base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(random_bytes)
     }
 }
+
+/// Random public id for a new token
+#[cfg(feature = "http-api")]
+fn generate_token_id() -> String {
+    use rand::Rng;
+    const ALPHABET: &[u8] = b"abcdefghijklmnopqrstuvwxyz0123456789";
+    let mut rng = rand::thread_rng();
+    let suffix: String = (0..TOKEN_ID_LENGTH).map(|_| ALPHABET[rng.gen_range(0..ALPHABET.len())] as char).collect();
+    format!("{}{}", TOKEN_ID_PREFIX, suffix)
+}
+
+/// Compare without returning early at the first difference, so timing does not reveal how
+/// much of a guessed token was right
+#[cfg(feature = "http-api")]
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |difference, (x, y)| difference | (x ^ y)) == 0
+}
+
+#[cfg(all(test, feature = "http-api"))]
+mod tests {
+    use super::*;
+    use crate::database::Database;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_split_token() {
+        assert_eq!(split_token("pmr_ab12cd34_s3cr_et"), Some(("pmr_ab12cd34", "s3cr_et")));
+        assert_eq!(split_token("pmr_ab12cd34_"), None);
+        assert_eq!(split_token("pmr_AB12CD34_secret"), None);
+        assert_eq!(split_token("pmr_ab12cd3_secret"), None);
+        assert_eq!(split_token("pmr_ab12cd34"), None);
+        assert_eq!(split_token("N2mVqk0r-legacy-token"), None);
+        assert!(is_token_id("pmr_ab12cd34"));
+        assert!(!is_token_id("pmr_ab12cd345"));
+        assert!(is_token_id(&generate_token_id()));
+    }
+
+    #[tokio::test]
+    async fn test_generation_retries_a_taken_id() {
+        let temp_dir = TempDir::new().unwrap();
+        let url = format!("sqlite:{}?mode=rwc", temp_dir.path().join("test.db").display());
+        let auth_manager = AuthManager::new(Arc::new(Database::new(&url).await.unwrap()));
+        let ids = |ids: &[&str]| ids.iter().map(|id| id.to_string()).collect::<Vec<_>>().into_iter();
+
+        let first = auth_manager.insert_new_token("first".to_string(), Utc::now(), None, false, ids(&["pmr_aaaaaaaa"])).await.unwrap();
+        assert!(first.token.starts_with("pmr_aaaaaaaa_"));
+        let second = auth_manager
+            .insert_new_token("second".to_string(), Utc::now(), None, false, ids(&["pmr_aaaaaaaa", "pmr_bbbbbbbb"]))
+            .await
+            .unwrap();
+        assert_eq!(second.public_id, "pmr_bbbbbbbb");
+        assert_eq!(auth_manager.token_name(&second.token).await.as_deref(), Some("second"));
+
+        let error = auth_manager.insert_new_token("third".to_string(), Utc::now(), None, false, ids(&["pmr_aaaaaaaa"; 6])).await.unwrap_err();
+        assert!(error.to_string().contains("unused id"), "{}", error);
+    }
+}
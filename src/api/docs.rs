@@ -3,8 +3,23 @@ use utoipa::OpenApi;
 
 #[cfg(feature = "http-api")]
 use crate::{
-    api::handlers::{ProcessListResponse, ProcessResponse, MessageResponse, StartProcessRequest, LogsQuery},
-    database::{ProcessRecord, ProcessStatus},
+    api::handlers::{
+        ProcessListResponse, ProcessResponse, MessageResponse, StartProcessRequest, StartQuery, LogsQuery, StopQuery, RestartQuery, DeleteQuery, StatusQuery, ListQuery,
+        BulkStartItemResult, BulkStartResponse, MetricsResponse, AddNoteRequest, NoteResponse, NoteListResponse,
+        StartResponse, StopResponse, RestartResponse, DeleteResponse, ReloadResponse, RotatedLogsResponse,
+        RotatedLogsContentResponse, InspectResponse, LogsResponse, HealthResponse, NameCheckResponse, ReservationResponse, ReserveQuery,
+    },
+    api::log_broker::LogSubscriberCount,
+    config::ConfigPaths,
+    database::{LogDirMode, ProcessCounts, ProcessKind, ProcessNote, ProcessRecord, ProcessRun, ProcessStatus, Reservation},
+    file_pool::FilePoolStats,
+    file_watch::WatchConfig,
+    flapping::FailureHistory,
+    inspect::{InspectEnvironment, InspectHistory, InspectLogs, InspectReport, Liveness},
+    logs_quota::{DeletedArchive, LogDirUsage, LogsQuotaStats, LogsUsage, QuotaEnforcement},
+    process::{ManagerStats, RotatedLog, RotatedLogsContent, StatusGauges},
+    readiness::{ReadinessCheck, ReadinessReport},
+    outcome::{DeleteOutcome, NameCheck, NotRunning, ReloadOutcome, RestartOutcome, RestartSkip, StartExit, StartOutcome, StopOutcome},
 };
 
 #[cfg(feature = "http-api")]
@@ -12,22 +27,99 @@ use crate::{
 #[openapi(
     paths(
         crate::api::handlers::list_processes,
+        crate::api::handlers::list_process_names,
         crate::api::handlers::get_process_status,
+        crate::api::handlers::check_process_name,
+        crate::api::handlers::reserve_process_name,
         crate::api::handlers::start_process,
+        crate::api::handlers::bulk_start_processes,
         crate::api::handlers::stop_process,
         crate::api::handlers::restart_process,
         crate::api::handlers::delete_process,
         crate::api::handlers::get_process_logs,
+        crate::api::handlers::get_process_logs_archive,
+        crate::api::handlers::list_rotated_logs,
+        crate::api::handlers::add_process_note,
+        crate::api::handlers::list_process_notes,
+        crate::api::handlers::delete_process_note,
+        crate::api::handlers::inspect_process,
+        crate::api::handlers::follow_process_logs,
+        crate::api::handlers::get_metrics,
+        crate::api::handlers::get_stats,
+        crate::api::handlers::reload_config,
+        crate::api::handlers::health_check,
+        crate::api::handlers::readiness_check,
     ),
     components(
         schemas(
             ProcessRecord,
+            ProcessRun,
             ProcessStatus,
+            ProcessKind,
+            LogDirMode,
             ProcessListResponse,
             ProcessResponse,
             MessageResponse,
+            LogsResponse,
+            HealthResponse,
+            ReadinessReport,
+            ReadinessCheck,
+            StartResponse,
+            StopResponse,
+            RestartResponse,
+            DeleteResponse,
+            ReloadResponse,
+            RotatedLogsResponse,
+            RotatedLogsContentResponse,
+            RotatedLog,
+            RotatedLogsContent,
+            StartOutcome,
+            StartExit,
+            StopOutcome,
+            NotRunning,
+            RestartOutcome,
+            RestartSkip,
+            DeleteOutcome,
+            ReloadOutcome,
             StartProcessRequest,
+            StartQuery,
             LogsQuery,
+            StopQuery,
+            RestartQuery,
+            DeleteQuery,
+            StatusQuery,
+            ListQuery,
+            BulkStartItemResult,
+            BulkStartResponse,
+            MetricsResponse,
+            StatusGauges,
+            ProcessCounts,
+            ManagerStats,
+            ConfigPaths,
+            LogsQuotaStats,
+            LogsUsage,
+            LogDirUsage,
+            QuotaEnforcement,
+            DeletedArchive,
+            LogSubscriberCount,
+            FilePoolStats,
+            ProcessNote,
+            AddNoteRequest,
+            NoteResponse,
+            NoteListResponse,
+            WatchConfig,
+            InspectResponse,
+            InspectReport,
+            Liveness,
+            InspectLogs,
+            InspectHistory,
+            InspectEnvironment,
+            FailureHistory,
+            NameCheck,
+            NameCheckResponse,
+            Reservation,
+            ReservationResponse,
+            ReserveQuery,
         )
     ),
     tags(
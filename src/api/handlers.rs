@@ -1,15 +1,40 @@
 #[cfg(feature = "http-api")]
 use crate::{
-    api::auth::AuthManager,
-    database::ProcessRecord,
-    process::ProcessManager,
+    affinity::{available_cpus, CpuSet},
+    api::{
+        auth::AuthManager,
+        log_broker::{LogBroker, LogSubscriberCount, LogSubscription},
+    },
+    archive::{archive_file_name, write_tar_archive},
+    config::StartLimitsConfig,
+    database::{ApiToken, LogDirMode, ProcessKind, ProcessNote, ProcessRecord, Reservation},
+    duration::HumanDuration,
+    log_index::LogSince,
+    file_pool::FilePoolStats,
+    file_watch::WatchConfig,
+    messages::start_message,
+    inspect::InspectReport,
+    limits::{self, check_start_size},
+    name::ProcessName,
+    outcome::{DeleteOutcome, NameCheck, ReloadOutcome, RestartOutcome, StartOutcome, StopOutcome},
+    env::{self, validate_env_vars, ReservedEnvPolicy},
+    process::{
+        parse_signal, DeleteOptions, LogOptions, DEFAULT_EPHEMERAL_KEEP, DEFAULT_RESERVATION_TTL, RECENT_RUNS, ManagerStats, ProcessLogs, ProcessManager, RestartOptions, RotatedLog,
+        RotatedLogsContent, StartOptions, StatusGauges, StatusRefresh, StopOptions,
+    },
+    readiness::{self, ReadinessReport},
+    umask::Umask,
     Error,
 };
 #[cfg(feature = "http-api")]
 use axum::{
-    extract::{Path, Query, State},
-    http::{HeaderMap, StatusCode},
-    response::Json,
+    body::{Body, Bytes},
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        Path, Query, State,
+    },
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Json, Response},
 };
 #[cfg(feature = "http-api")]
 use std::sync::{Arc, Mutex};
@@ -20,25 +45,40 @@ use std::collections::HashMap;
 #[cfg(feature = "http-api")]
 use utoipa::ToSchema;
 
-// Helper function to validate authentication
+/// The token a request was authenticated with by [`require_token`], which every `/api`
+/// route sits behind. Handlers take it for the token's identity and scopes; without the
+/// middleware in front it rejects the request, so a route can never slip through unchecked.
 #[cfg(feature = "http-api")]
-fn validate_auth(headers: &HeaderMap, auth_manager: &Arc<Mutex<AuthManager>>) -> Result<(), StatusCode> {
-    let auth_header = headers
-        .get("Authorization")
-        .and_then(|h| h.to_str().ok())
-        .ok_or(StatusCode::UNAUTHORIZED)?;
+#[derive(Debug, Clone)]
+pub struct Authenticated(pub ApiToken);
 
-    if !auth_header.starts_with("Bearer ") {
-        return Err(StatusCode::UNAUTHORIZED);
-    }
+#[cfg(feature = "http-api")]
+#[axum::async_trait]
+impl<S: Send + Sync> axum::extract::FromRequestParts<S> for Authenticated {
+    type Rejection = StatusCode;
 
-    let token = &auth_header[7..];
-    let auth_manager = auth_manager.lock().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-    if !auth_manager.validate_token_sync(token) {
-        return Err(StatusCode::UNAUTHORIZED);
+    async fn from_request_parts(parts: &mut axum::http::request::Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts.extensions.get::<Authenticated>().cloned().ok_or(StatusCode::UNAUTHORIZED)
     }
+}
+
+/// The token of a `Bearer` Authorization header
+#[cfg(feature = "http-api")]
+fn bearer_token(headers: &HeaderMap) -> Result<&str, StatusCode> {
+    headers
+        .get("Authorization")
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "))
+        .ok_or(StatusCode::UNAUTHORIZED)
+}
 
-    Ok(())
+/// The token the request was made with
+#[cfg(feature = "http-api")]
+async fn authenticate_token(headers: &HeaderMap, auth_manager: &Arc<Mutex<AuthManager>>) -> Result<ApiToken, StatusCode> {
+    let token = bearer_token(headers)?;
+    // Cloned out so the lock is not held, and requests do not queue, while the token is looked up
+    let auth_manager = auth_manager.lock().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?.clone();
+    auth_manager.authenticate(token).await.ok_or(StatusCode::UNAUTHORIZED)
 }
 
 #[cfg(feature = "http-api")]
@@ -76,7 +116,7 @@ pub struct ProcessResponse {
 }
 
 #[cfg(feature = "http-api")]
-#[derive(Serialize, ToSchema)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct MessageResponse {
     /// Whether the request was successful
     pub success: bool,
@@ -86,6 +126,56 @@ pub struct MessageResponse {
     pub error: Option<String>,
 }
 
+#[cfg(feature = "http-api")]
+#[derive(Debug, Serialize, ToSchema)]
+pub struct StartResponse {
+    /// Whether the request was successful
+    pub success: bool,
+    /// What the start did (present on success)
+    pub data: Option<StartOutcome>,
+    /// The process as stored once the start window is over (with `return=record`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub record: Option<ProcessRecord>,
+    /// What the start did, as the CLI words it (with `return=record`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    /// Error message (present on failure)
+    pub error: Option<String>,
+}
+
+#[cfg(feature = "http-api")]
+#[derive(Debug, Serialize, ToSchema)]
+pub struct StopResponse {
+    /// Whether the request was successful
+    pub success: bool,
+    /// What the stop did (present on success)
+    pub data: Option<StopOutcome>,
+    /// Error message (present on failure)
+    pub error: Option<String>,
+}
+
+#[cfg(feature = "http-api")]
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RestartResponse {
+    /// Whether the request was successful
+    pub success: bool,
+    /// What the restart did (present on success)
+    pub data: Option<RestartOutcome>,
+    /// Error message (present on failure)
+    pub error: Option<String>,
+}
+
+#[cfg(feature = "http-api")]
+#[derive(Debug, Serialize, ToSchema)]
+pub struct DeleteResponse {
+    /// Whether the request was successful
+    pub success: bool,
+    /// What the delete did (present on success)
+    pub data: Option<DeleteOutcome>,
+    /// Error message (present on failure)
+    pub error: Option<String>,
+}
+
 #[cfg(feature = "http-api")]
 impl<T> ApiResponse<T> {
     pub fn success(data: T) -> Self {
@@ -164,265 +254,1389 @@ impl MessageResponse {
 }
 
 #[cfg(feature = "http-api")]
-#[derive(Deserialize, ToSchema)]
-pub struct StartProcessRequest {
-    /// Process name (must be unique)
-    pub name: String,
-    /// Command to execute
-    pub command: String,
-    /// Command arguments
-    #[serde(default)]
-    pub args: Vec<String>,
-    /// Environment variables
-    pub env_vars: Option<HashMap<String, String>>,
-    /// Working directory (defaults to current directory)
-    pub working_dir: Option<String>,
-    /// Log directory (defaults to ./logs)
-    pub log_dir: Option<String>,
+impl StartResponse {
+    pub fn success(data: StartOutcome) -> Self {
+        Self {
+            success: true,
+            data: Some(data),
+            record: None,
+            message: None,
+            error: None,
+        }
+    }
+
+    pub fn with_record(data: StartOutcome, record: ProcessRecord) -> Self {
+        Self {
+            message: Some(start_message(&data)),
+            record: Some(record),
+            ..Self::success(data)
+        }
+    }
+
+    pub fn error(message: String) -> Self {
+        Self {
+            success: false,
+            data: None,
+            record: None,
+            message: None,
+            error: Some(message),
+        }
+    }
 }
 
 #[cfg(feature = "http-api")]
-#[derive(Deserialize, ToSchema)]
-pub struct LogsQuery {
-    /// Number of lines to return (default: all)
-    pub lines: Option<usize>,
-    /// Whether to return rotated log files
-    pub rotated: Option<bool>,
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ReloadResponse {
+    /// Whether the request was successful
+    pub success: bool,
+    /// What the reload changed (present on success)
+    pub data: Option<ReloadOutcome>,
+    /// Error message (present on failure)
+    pub error: Option<String>,
 }
 
 #[cfg(feature = "http-api")]
-#[utoipa::path(
-    get,
-    path = "/api/processes",
-    responses(
-        (status = 200, description = "List of all processes", body = ProcessListResponse),
-        (status = 401, description = "Unauthorized")
-    ),
-    security(
-        ("bearer_auth" = [])
-    )
-)]
-pub async fn list_processes(
-    State((process_manager, auth_manager)): State<(Arc<ProcessManager>, Arc<Mutex<AuthManager>>)>,
-    headers: HeaderMap,
-) -> std::result::Result<Json<ProcessListResponse>, StatusCode> {
-    validate_auth(&headers, &auth_manager)?;
-    match process_manager.list_processes().await {
-        Ok(processes) => Ok(Json(ProcessListResponse::success(processes))),
-        Err(e) => {
-            eprintln!("Error listing processes: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
-        }
-    }
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RotatedLogsResponse {
+    /// Whether the request was successful
+    pub success: bool,
+    /// Rotated log files, newest first (present on success)
+    pub data: Option<Vec<RotatedLog>>,
+    /// Error message (present on failure)
+    pub error: Option<String>,
 }
 
 #[cfg(feature = "http-api")]
-#[utoipa::path(
-    get,
-    path = "/api/processes/{name}",
-    responses(
-        (status = 200, description = "Process status", body = ProcessResponse),
-        (status = 401, description = "Unauthorized"),
-        (status = 404, description = "Process not found")
-    ),
-    params(
-        ("name" = String, Path, description = "Process name")
-    ),
-    security(
-        ("bearer_auth" = [])
-    )
-)]
-pub async fn get_process_status(
-    State((process_manager, auth_manager)): State<(Arc<ProcessManager>, Arc<Mutex<AuthManager>>)>,
-    headers: HeaderMap,
-    Path(name): Path<String>,
-) -> std::result::Result<Json<ProcessResponse>, StatusCode> {
-    validate_auth(&headers, &auth_manager)?;
-    match process_manager.get_process_status(&name).await {
-        Ok(process) => Ok(Json(ProcessResponse::success(process))),
-        Err(Error::ProcessNotFound(_)) => Err(StatusCode::NOT_FOUND),
-        Err(e) => {
-            eprintln!("Error getting process status: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+#[derive(Debug, Serialize, ToSchema)]
+pub struct LogsResponse {
+    /// Whether the request was successful
+    pub success: bool,
+    /// Log lines (present on success)
+    pub data: Option<String>,
+    /// Some bytes of the log were not UTF-8 and show as U+FFFD in `data`
+    pub contains_invalid_utf8: bool,
+    /// Byte offset in the log file `data` starts at (present with `tail_bytes`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start_offset: Option<u64>,
+    /// Error message (present on failure)
+    pub error: Option<String>,
+}
+
+#[cfg(feature = "http-api")]
+impl LogsResponse {
+    pub fn success(logs: ProcessLogs) -> Self {
+        Self {
+            success: true,
+            data: Some(logs.content),
+            contains_invalid_utf8: logs.contains_invalid_utf8,
+            start_offset: logs.start_offset,
+            error: None,
         }
     }
 }
 
 #[cfg(feature = "http-api")]
-#[utoipa::path(
-    post,
-    path = "/api/processes",
-    request_body = StartProcessRequest,
-    responses(
-        (status = 200, description = "Process started successfully", body = MessageResponse),
-        (status = 401, description = "Unauthorized"),
-        (status = 409, description = "Process already exists")
-    ),
-    security(
-        ("bearer_auth" = [])
-    )
-)]
-pub async fn start_process(
-    State((process_manager, auth_manager)): State<(Arc<ProcessManager>, Arc<Mutex<AuthManager>>)>,
-    headers: HeaderMap,
-    Json(request): Json<StartProcessRequest>,
-) -> std::result::Result<Json<MessageResponse>, StatusCode> {
-    validate_auth(&headers, &auth_manager)?;
-    let env_vars = request.env_vars.unwrap_or_default();
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RotatedLogsContentResponse {
+    /// Whether the request was successful
+    pub success: bool,
+    /// Rotated log content up to `max_logs_response_bytes` (present on success)
+    pub data: Option<RotatedLogsContent>,
+    /// Where to download everything, when the content was truncated
+    pub hint: Option<String>,
+    /// Error message (present on failure)
+    pub error: Option<String>,
+}
 
-    match process_manager
-        .start_process(
-            &request.name,
-            &request.command,
-            request.args,
-            env_vars,
-            request.working_dir,
-            request.log_dir,
-        )
-        .await
-    {
-        Ok(message) => Ok(Json(MessageResponse::success(message))),
-        Err(Error::ProcessAlreadyExists(_)) => Err(StatusCode::CONFLICT),
-        Err(e) => {
-            eprintln!("Error starting process: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+#[cfg(feature = "http-api")]
+impl RotatedLogsContentResponse {
+    pub fn success(name: &str, data: RotatedLogsContent) -> Self {
+        let hint = data.truncated.then(|| {
+            format!("Content truncated at {} bytes; download all logs from /api/processes/{}/logs/archive", data.bytes, name)
+        });
+        Self {
+            success: true,
+            data: Some(data),
+            hint,
+            error: None,
         }
     }
 }
 
 #[cfg(feature = "http-api")]
-#[utoipa::path(
-    put,
-    path = "/api/processes/{name}/stop",
-    responses(
-        (status = 200, description = "Process stopped successfully", body = MessageResponse),
-        (status = 401, description = "Unauthorized"),
-        (status = 404, description = "Process not found")
-    ),
-    params(
-        ("name" = String, Path, description = "Process name")
-    ),
-    security(
-        ("bearer_auth" = [])
-    )
-)]
-pub async fn stop_process(
-    State((process_manager, auth_manager)): State<(Arc<ProcessManager>, Arc<Mutex<AuthManager>>)>,
-    headers: HeaderMap,
-    Path(name): Path<String>,
-) -> std::result::Result<Json<MessageResponse>, StatusCode> {
-    validate_auth(&headers, &auth_manager)?;
-    match process_manager.stop_process(&name).await {
-        Ok(message) => Ok(Json(MessageResponse::success(message))),
-        Err(Error::ProcessNotFound(_)) => Err(StatusCode::NOT_FOUND),
-        Err(e) => {
-            eprintln!("Error stopping process: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+impl RotatedLogsResponse {
+    pub fn success(data: Vec<RotatedLog>) -> Self {
+        Self {
+            success: true,
+            data: Some(data),
+            error: None,
         }
     }
 }
 
 #[cfg(feature = "http-api")]
-#[utoipa::path(
-    put,
-    path = "/api/processes/{name}/restart",
-    responses(
-        (status = 200, description = "Process restarted successfully", body = MessageResponse),
-        (status = 401, description = "Unauthorized"),
-        (status = 404, description = "Process not found")
-    ),
-    params(
-        ("name" = String, Path, description = "Process name")
-    ),
-    security(
-        ("bearer_auth" = [])
-    )
-)]
-pub async fn restart_process(
-    State((process_manager, auth_manager)): State<(Arc<ProcessManager>, Arc<Mutex<AuthManager>>)>,
-    headers: HeaderMap,
-    Path(name): Path<String>,
-) -> std::result::Result<Json<MessageResponse>, StatusCode> {
-    validate_auth(&headers, &auth_manager)?;
-    match process_manager.restart_process(&name).await {
-        Ok(message) => Ok(Json(MessageResponse::success(message))),
-        Err(Error::ProcessNotFound(_)) => Err(StatusCode::NOT_FOUND),
-        Err(e) => {
-            eprintln!("Error restarting process: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+impl ReloadResponse {
+    pub fn success(data: ReloadOutcome) -> Self {
+        Self {
+            success: true,
+            data: Some(data),
+            error: None,
+        }
+    }
+
+    pub fn error(message: String) -> Self {
+        Self {
+            success: false,
+            data: None,
+            error: Some(message),
         }
     }
 }
 
 #[cfg(feature = "http-api")]
-#[utoipa::path(
-    delete,
-    path = "/api/processes/{name}",
-    responses(
-        (status = 200, description = "Process deleted successfully", body = MessageResponse),
-        (status = 401, description = "Unauthorized"),
-        (status = 404, description = "Process not found")
-    ),
-    params(
-        ("name" = String, Path, description = "Process name")
-    ),
-    security(
-        ("bearer_auth" = [])
-    )
-)]
-pub async fn delete_process(
-    State((process_manager, auth_manager)): State<(Arc<ProcessManager>, Arc<Mutex<AuthManager>>)>,
-    headers: HeaderMap,
-    Path(name): Path<String>,
-) -> std::result::Result<Json<MessageResponse>, StatusCode> {
-    validate_auth(&headers, &auth_manager)?;
-    match process_manager.delete_process(&name).await {
-        Ok(message) => Ok(Json(MessageResponse::success(message))),
-        Err(Error::ProcessNotFound(_)) => Err(StatusCode::NOT_FOUND),
-        Err(e) => {
-            eprintln!("Error deleting process: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+impl StopResponse {
+    pub fn success(data: StopOutcome) -> Self {
+        Self {
+            success: true,
+            data: Some(data),
+            error: None,
         }
     }
 }
 
 #[cfg(feature = "http-api")]
-#[utoipa::path(
-    get,
-    path = "/api/processes/{name}/logs",
-    responses(
-        (status = 200, description = "Process logs", body = MessageResponse),
-        (status = 401, description = "Unauthorized"),
-        (status = 404, description = "Process not found")
+impl RestartResponse {
+    pub fn success(data: RestartOutcome) -> Self {
+        Self {
+            success: true,
+            data: Some(data),
+            error: None,
+        }
+    }
+}
+
+#[cfg(feature = "http-api")]
+impl DeleteResponse {
+    pub fn success(data: DeleteOutcome) -> Self {
+        Self {
+            success: true,
+            data: Some(data),
+            error: None,
+        }
+    }
+}
+
+#[cfg(feature = "http-api")]
+#[derive(Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct StartProcessRequest {
+    /// Process name (must be unique)
+    pub name: String,
+    /// Command to execute
+    pub command: String,
+    /// Command arguments
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Environment variables; a key given twice is refused
+    #[serde(default, deserialize_with = "env::deserialize_unique_env")]
+    pub env_vars: Option<HashMap<String, String>>,
+    /// Working directory (defaults to current directory)
+    pub working_dir: Option<String>,
+    /// Log directory (defaults to ./logs)
+    pub log_dir: Option<String>,
+    /// `workdir` logs to `<working_dir>/logs`, following the working directory on restart;
+    /// `log_dir` wins over it (defaults to the configured mode)
+    pub log_dir_mode: Option<LogDirMode>,
+    /// CPUs to pin the process to, e.g. "0,2-3" (Linux only)
+    pub cpu_affinity: Option<String>,
+    /// Umask of the process in octal, e.g. "027" (defaults to the server's own umask)
+    pub umask: Option<String>,
+    /// What the process is for, shown by `pmr status` and matched by `search` on the list
+    /// endpoint; at most 256 characters on one line
+    pub description: Option<String>,
+    /// Succeed if an identical process is already running; start it if it is stopped
+    #[serde(default)]
+    pub idempotent: bool,
+    /// Record uid/gid, cwd, RSS and environment from /proc right after the process starts
+    #[serde(default)]
+    pub snapshot: bool,
+    /// Fail and leave nothing behind if the process exits within the start window
+    #[serde(default)]
+    pub strict: bool,
+    /// `service` (default) or `job`: a job runs once and ends succeeded or failed
+    #[serde(default)]
+    pub kind: ProcessKind,
+    /// Run a job that already ran under this name again, archiving its previous log
+    #[serde(default)]
+    pub rerun: bool,
+    /// Signal stops send first unless they name their own, e.g. "INT" (defaults to TERM)
+    pub stop_signal: Option<String>,
+    /// Delete the process and its logs once it has exited and the grace period is over
+    #[serde(default)]
+    pub ephemeral: bool,
+    /// Grace period of an ephemeral process, e.g. "30s" or "2h" (defaults to 10m)
+    pub ephemeral_keep: Option<String>,
+    /// Restart the process when these files or directories change; relative paths are
+    /// taken from the working directory
+    #[serde(default)]
+    pub watch: Vec<String>,
+    /// Globs for watched paths to leave out, besides ".git/" and "*.log"
+    #[serde(default)]
+    pub watch_ignore: Vec<String>,
+    /// Milliseconds of changes collected into one restart (defaults to 500)
+    pub watch_debounce_ms: Option<u64>,
+    /// Wait in the start queue and retry when the system is out of processes or memory,
+    /// instead of failing (also on for every start with `[start_queue] enabled`)
+    #[serde(default)]
+    pub queue_on_pressure: bool,
+    /// Rotate an oversized log left by an earlier run before starting (true) or keep
+    /// appending to it (false); defaults to `rotate_logs_on_start` in the config
+    pub rotate_on_start: Option<bool>,
+}
+
+#[cfg(feature = "http-api")]
+impl StartProcessRequest {
+    /// Start options described by the request; fails on an invalid CPU list, umask,
+    /// description, stop signal or grace period
+    fn start_options(&mut self) -> crate::Result<StartOptions> {
+        let cpu_affinity = match &self.cpu_affinity {
+            Some(list) => Some(list.parse::<CpuSet>()?),
+            None => None,
+        };
+        let umask = self.umask.as_deref().map(str::parse::<Umask>).transpose()?;
+        let description = match self.description.take() {
+            Some(description) => limits::clean_description(&description)?,
+            None => None,
+        };
+        let stop_signal = self.stop_signal.as_deref().map(parse_signal).transpose()?;
+        let ephemeral_keep = match (self.ephemeral, &self.ephemeral_keep) {
+            (false, None) => None,
+            (false, Some(_)) => return Err(Error::Other("ephemeral_keep requires ephemeral".to_string())),
+            (true, None) => Some(DEFAULT_EPHEMERAL_KEEP),
+            (true, Some(keep)) => Some(
+                keep.parse::<HumanDuration>()?
+                    .as_duration()
+                    .to_std()
+                    .map_err(|e| Error::InvalidDuration(format!("'{}': {}", keep, e)))?,
+            ),
+        };
+        let watch = match (self.watch.is_empty(), self.watch_ignore.is_empty() && self.watch_debounce_ms.is_none()) {
+            (true, true) => None,
+            (true, false) => return Err(Error::Other("watch_ignore and watch_debounce_ms require watch".to_string())),
+            (false, _) => Some(WatchConfig::new(
+                std::mem::take(&mut self.watch),
+                std::mem::take(&mut self.watch_ignore),
+                self.watch_debounce_ms,
+            )),
+        };
+        Ok(StartOptions {
+            working_dir: self.working_dir.take(),
+            log_dir: self.log_dir.take(),
+            log_dir_mode: self.log_dir_mode,
+            cpu_affinity,
+            umask,
+            description,
+            idempotent: self.idempotent,
+            capture_snapshot: self.snapshot,
+            strict: self.strict,
+            kind: self.kind,
+            rerun: self.rerun,
+            stop_signal,
+            ephemeral_keep,
+            watch,
+            queue_on_pressure: self.queue_on_pressure,
+            rotate_on_start: self.rotate_on_start,
+            ..Default::default()
+        })
+    }
+}
+
+#[cfg(feature = "http-api")]
+#[derive(Serialize, ToSchema)]
+pub struct BulkStartItemResult {
+    /// Process name from the request item
+    pub name: String,
+    /// Whether this item was started successfully
+    pub success: bool,
+    /// What the start did (present on success)
+    pub outcome: Option<StartOutcome>,
+    /// Error message (present on failure)
+    pub error: Option<String>,
+    /// HTTP status code this item would have received as a single request
+    pub status_code: u16,
+}
+
+#[cfg(feature = "http-api")]
+#[derive(Serialize, ToSchema)]
+pub struct BulkStartResponse {
+    /// Whether the batch was accepted and processed
+    pub success: bool,
+    /// Per-item results in request order
+    pub data: Option<Vec<BulkStartItemResult>>,
+    /// Error message (present on failure)
+    pub error: Option<String>,
+}
+
+#[cfg(feature = "http-api")]
+impl BulkStartItemResult {
+    fn started(name: String, outcome: StartOutcome) -> Self {
+        Self {
+            name,
+            success: true,
+            outcome: Some(outcome),
+            error: None,
+            status_code: StatusCode::OK.as_u16(),
+        }
+    }
+
+    fn failed(name: String, error: String, status_code: StatusCode) -> Self {
+        Self {
+            name,
+            success: false,
+            outcome: None,
+            error: Some(error),
+            status_code: status_code.as_u16(),
+        }
+    }
+}
+
+#[cfg(feature = "http-api")]
+impl BulkStartResponse {
+    pub fn success(data: Vec<BulkStartItemResult>) -> Self {
+        Self {
+            success: true,
+            data: Some(data),
+            error: None,
+        }
+    }
+
+    pub fn error(message: String, data: Option<Vec<BulkStartItemResult>>) -> Self {
+        Self {
+            success: false,
+            data,
+            error: Some(message),
+        }
+    }
+}
+
+#[cfg(feature = "http-api")]
+#[derive(Deserialize, ToSchema)]
+pub struct StatusQuery {
+    /// Include the /proc snapshot recorded at start
+    pub snapshot: Option<bool>,
+    /// Include every note attached to the process
+    pub notes: Option<bool>,
+    /// `true` probes the PID, `false` returns the stored status, `stale` (default) probes
+    /// only if the status was not verified within `status_stale_after`
+    pub refresh: Option<String>,
+    /// Cut long commands, arguments and environment values, listing them in `truncated_fields`
+    pub truncate: Option<bool>,
+}
+
+#[cfg(feature = "http-api")]
+#[derive(Deserialize, ToSchema)]
+pub struct ListQuery {
+    /// `true` probes every PID, `false` returns the stored statuses, `stale` (default)
+    /// probes only records not verified within `status_stale_after`
+    pub refresh: Option<String>,
+    /// Cut long commands, arguments and environment values, listing them in `truncated_fields`
+    pub truncate: Option<bool>,
+    /// Only processes whose name or description contains this, ignoring case
+    pub search: Option<String>,
+}
+
+/// The refresh mode a query asks for, `stale` when it names none
+#[cfg(feature = "http-api")]
+fn refresh_mode(refresh: Option<&str>) -> std::result::Result<StatusRefresh, StatusCode> {
+    refresh.map_or(Ok(StatusRefresh::Stale), |mode| mode.parse().map_err(|_| StatusCode::BAD_REQUEST))
+}
+
+#[cfg(feature = "http-api")]
+#[derive(Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct AddNoteRequest {
+    /// Note text, at most 4096 bytes
+    pub text: String,
+}
+
+#[cfg(feature = "http-api")]
+#[derive(Debug, Serialize, ToSchema)]
+pub struct NoteResponse {
+    /// Whether the request was successful
+    pub success: bool,
+    /// The note that was added (present on success)
+    pub data: Option<ProcessNote>,
+    /// Error message (present on failure)
+    pub error: Option<String>,
+}
+
+#[cfg(feature = "http-api")]
+#[derive(Serialize, ToSchema)]
+pub struct NoteListResponse {
+    /// Whether the request was successful
+    pub success: bool,
+    /// Notes on the process, oldest first (present on success)
+    pub data: Option<Vec<ProcessNote>>,
+    /// Error message (present on failure)
+    pub error: Option<String>,
+}
+
+#[cfg(feature = "http-api")]
+#[derive(Serialize, ToSchema)]
+pub struct InspectResponse {
+    /// Whether the request was successful
+    pub success: bool,
+    /// Everything known about the process (present on success)
+    pub data: Option<InspectReport>,
+    /// Error message (present on failure)
+    pub error: Option<String>,
+}
+
+#[cfg(feature = "http-api")]
+impl InspectResponse {
+    pub fn success(data: InspectReport) -> Self {
+        Self {
+            success: true,
+            data: Some(data),
+            error: None,
+        }
+    }
+}
+
+#[cfg(feature = "http-api")]
+impl NoteResponse {
+    pub fn success(data: ProcessNote) -> Self {
+        Self {
+            success: true,
+            data: Some(data),
+            error: None,
+        }
+    }
+
+    pub fn error(message: String) -> Self {
+        Self {
+            success: false,
+            data: None,
+            error: Some(message),
+        }
+    }
+}
+
+#[cfg(feature = "http-api")]
+impl NoteListResponse {
+    pub fn success(data: Vec<ProcessNote>) -> Self {
+        Self {
+            success: true,
+            data: Some(data),
+            error: None,
+        }
+    }
+}
+
+#[cfg(feature = "http-api")]
+#[derive(Deserialize, ToSchema)]
+pub struct StartQuery {
+    /// `outcome` (default) answers with what the start did, `record` adds the stored process
+    #[serde(rename = "return")]
+    pub return_value: Option<String>,
+}
+
+#[cfg(feature = "http-api")]
+#[derive(Deserialize, ToSchema)]
+pub struct ReserveQuery {
+    /// How long the reservation lasts, e.g. "30s" or "2h" (defaults to 5m, at most 1d)
+    pub ttl: Option<String>,
+}
+
+#[cfg(feature = "http-api")]
+#[derive(Debug, Serialize, ToSchema)]
+pub struct NameCheckResponse {
+    /// Whether the request was successful
+    pub success: bool,
+    /// What holds the name (present on success)
+    pub data: Option<NameCheck>,
+    /// Error message (present on failure)
+    pub error: Option<String>,
+}
+
+#[cfg(feature = "http-api")]
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ReservationResponse {
+    /// Whether the request was successful
+    pub success: bool,
+    /// The reservation that was made or renewed (present on success)
+    pub data: Option<Reservation>,
+    /// Error message (present on failure)
+    pub error: Option<String>,
+}
+
+#[cfg(feature = "http-api")]
+impl ReservationResponse {
+    pub fn success(data: Reservation) -> Self {
+        Self {
+            success: true,
+            data: Some(data),
+            error: None,
+        }
+    }
+
+    pub fn error(message: String) -> Self {
+        Self {
+            success: false,
+            data: None,
+            error: Some(message),
+        }
+    }
+}
+
+#[cfg(feature = "http-api")]
+#[derive(Deserialize, ToSchema)]
+pub struct StopQuery {
+    /// Signal to send first, e.g. "INT"; defaults to the process's stop signal
+    pub signal: Option<String>,
+}
+
+#[cfg(feature = "http-api")]
+#[derive(Deserialize, ToSchema)]
+pub struct RestartQuery {
+    /// Restart even if the process is flapping (failing repeatedly)
+    pub force: Option<bool>,
+}
+
+#[cfg(feature = "http-api")]
+#[derive(Deserialize, ToSchema)]
+pub struct DeleteQuery {
+    /// Remove the record and its log file permanently instead of soft-deleting it
+    pub hard: Option<bool>,
+}
+
+#[cfg(feature = "http-api")]
+#[derive(Deserialize, ToSchema)]
+pub struct LogsQuery {
+    /// Number of lines to return (default: all, 0 returns nothing)
+    pub lines: Option<usize>,
+    /// Return the content of the rotated log files, cut off at `max_logs_response_bytes`
+    pub rotated: Option<bool>,
+    /// Read the rotated files and the live log as one stream, oldest first
+    pub merge_rotated: Option<bool>,
+    /// Show runs of identical consecutive lines once, with a repeat count
+    pub collapse_repeats: Option<bool>,
+    /// Return the last this many bytes of the live log instead of lines
+    pub tail_bytes: Option<u64>,
+    /// Keep the partial line a `tail_bytes` cut lands in
+    pub allow_partial: Option<bool>,
+    /// Return the lines of the live log timed at or after this RFC 3339 time or duration ago
+    pub since: Option<String>,
+}
+
+#[cfg(feature = "http-api")]
+#[utoipa::path(
+    get,
+    path = "/api/processes",
+    responses(
+        (status = 200, description = "List of all processes", body = ProcessListResponse),
+        (status = 400, description = "Unknown refresh mode"),
+        (status = 401, description = "Unauthorized")
+    ),
+    params(
+        ("refresh" = Option<String>, Query, description = "true probes every PID, false returns stored statuses, stale (default) probes only records not verified recently"),
+        ("truncate" = Option<bool>, Query, description = "Cut long commands, arguments and environment values for display, listing them in truncated_fields"),
+        ("search" = Option<String>, Query, description = "Only processes whose name or description contains this text, ignoring case")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn list_processes(
+    State((process_manager, _auth_manager)): State<(Arc<ProcessManager>, Arc<Mutex<AuthManager>>)>,
+    _: Authenticated,
+    Query(params): Query<ListQuery>,
+) -> std::result::Result<Json<ProcessListResponse>, StatusCode> {
+    let refresh = refresh_mode(params.refresh.as_deref())?;
+    let listed = match params.search.as_deref() {
+        Some(text) => process_manager.search_processes(text, refresh).await,
+        None => process_manager.list_processes_with_refresh(refresh).await,
+    };
+    match listed {
+        Ok(mut processes) => {
+            if params.truncate.unwrap_or(false) {
+                processes.iter_mut().for_each(limits::truncate_for_display);
+            }
+            Ok(Json(ProcessListResponse::success(processes)))
+        }
+        Err(e) => {
+            eprintln!("Error listing processes: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+#[cfg(feature = "http-api")]
+#[derive(Deserialize, ToSchema)]
+pub struct NamesQuery {
+    /// Only names starting with this
+    pub prefix: Option<String>,
+}
+
+#[cfg(feature = "http-api")]
+#[utoipa::path(
+    get,
+    path = "/api/processes/names",
+    responses(
+        (status = 200, description = "Sorted process names, read without checking any status", body = Vec<String>),
+        (status = 401, description = "Unauthorized")
+    ),
+    params(
+        ("prefix" = Option<String>, Query, description = "Only names starting with this, for autocompletion")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn list_process_names(
+    State((process_manager, _auth_manager)): State<(Arc<ProcessManager>, Arc<Mutex<AuthManager>>)>,
+    _: Authenticated,
+    Query(params): Query<NamesQuery>,
+) -> std::result::Result<Json<Vec<String>>, StatusCode> {
+    match process_manager.process_names(params.prefix.as_deref()).await {
+        Ok(names) => Ok(Json(names)),
+        Err(e) => {
+            eprintln!("Error listing process names: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+#[cfg(feature = "http-api")]
+#[utoipa::path(
+    get,
+    path = "/api/processes/{name}",
+    responses(
+        (status = 200, description = "Process status", body = ProcessResponse),
+        (status = 400, description = "Unknown refresh mode"),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Process not found")
+    ),
+    params(
+        ("name" = String, Path, description = "Process name"),
+        ("snapshot" = Option<bool>, Query, description = "Include the /proc snapshot recorded at start"),
+        ("notes" = Option<bool>, Query, description = "Include every note attached to the process"),
+        ("refresh" = Option<String>, Query, description = "true probes the PID, false returns the stored status, stale (default) probes only if not verified recently"),
+        ("truncate" = Option<bool>, Query, description = "Cut long commands, arguments and environment values for display, listing them in truncated_fields")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn get_process_status(
+    State((process_manager, _auth_manager)): State<(Arc<ProcessManager>, Arc<Mutex<AuthManager>>)>,
+    _: Authenticated,
+    Path(name): Path<ProcessName>,
+    Query(params): Query<StatusQuery>,
+) -> std::result::Result<Json<ProcessResponse>, StatusCode> {
+    let refresh = refresh_mode(params.refresh.as_deref())?;
+    match process_manager.get_process_status_with_refresh(&name, refresh).await {
+        // pmr's own processes are not served, as if they did not exist
+        Ok(process) if process.internal => Err(StatusCode::NOT_FOUND),
+        Ok(mut process) => {
+            if !params.snapshot.unwrap_or(false) {
+                process.start_snapshot = None;
+            }
+            if params.notes.unwrap_or(false) {
+                process.notes = process_manager.get_process_notes(&name).await.map_err(|e| {
+                    eprintln!("Error getting process notes: {}", e);
+                    StatusCode::INTERNAL_SERVER_ERROR
+                })?;
+            }
+            process.recent_runs = process_manager.process_runs(&name, RECENT_RUNS).await.map_err(|e| {
+                eprintln!("Error getting process runs: {}", e);
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?;
+            if params.truncate.unwrap_or(false) {
+                limits::truncate_for_display(&mut process);
+            }
+            Ok(Json(ProcessResponse::success(process)))
+        }
+        Err(Error::ProcessNotFound(_)) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            eprintln!("Error getting process status: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+#[cfg(feature = "http-api")]
+#[utoipa::path(
+    get,
+    path = "/api/processes/{name}/exists",
+    responses(
+        (status = 200, description = "A process or a reservation holds the name; read from the database only, so the status is the stored one (HEAD gets the status code alone)", body = NameCheckResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "The name is free", body = NameCheckResponse)
+    ),
+    params(
+        ("name" = String, Path, description = "Process name")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn check_process_name(
+    State((process_manager, _auth_manager)): State<(Arc<ProcessManager>, Arc<Mutex<AuthManager>>)>,
+    _: Authenticated,
+    Path(name): Path<ProcessName>,
+) -> std::result::Result<(StatusCode, Json<NameCheckResponse>), StatusCode> {
+    match process_manager.check_name(&name).await {
+        Ok(check) => {
+            let status = if check.taken() { StatusCode::OK } else { StatusCode::NOT_FOUND };
+            Ok((status, Json(NameCheckResponse { success: true, data: Some(check), error: None })))
+        }
+        Err(e) => {
+            eprintln!("Error checking process name: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+#[cfg(feature = "http-api")]
+#[utoipa::path(
+    post,
+    path = "/api/processes/{name}/reserve",
+    responses(
+        (status = 200, description = "Name reserved for the token used, or its reservation renewed; nothing is spawned or logged, and only a start with this token may use the name until it expires", body = ReservationResponse),
+        (status = 400, description = "Invalid or reserved name, or a ttl outside 1s to 1d", body = ReservationResponse),
+        (status = 401, description = "Unauthorized", body = ReservationResponse),
+        (status = 409, description = "A process exists under the name, or another token reserved it", body = ReservationResponse)
+    ),
+    params(
+        ("name" = String, Path, description = "Process name"),
+        ("ttl" = Option<String>, Query, description = "How long the reservation lasts, e.g. 30s or 2h (defaults to 5m, at most 1d)")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn reserve_process_name(
+    State((process_manager, _auth_manager)): State<(Arc<ProcessManager>, Arc<Mutex<AuthManager>>)>,
+    Authenticated(token): Authenticated,
+    Path(name): Path<ProcessName>,
+    Query(params): Query<ReserveQuery>,
+) -> std::result::Result<Json<ReservationResponse>, (StatusCode, Json<ReservationResponse>)> {
+    let failed = |status: StatusCode, message: String| (status, Json(ReservationResponse::error(message)));
+    let ttl = match params.ttl.as_deref().map(str::parse::<HumanDuration>).transpose() {
+        Ok(Some(ttl)) => ttl.as_duration().to_std().map_err(|e| failed(StatusCode::BAD_REQUEST, e.to_string()))?,
+        Ok(None) => DEFAULT_RESERVATION_TTL,
+        Err(e) => return Err(failed(StatusCode::BAD_REQUEST, e.to_string())),
+    };
+    match process_manager.reserve_name(&name, &token.public_id, ttl).await {
+        Ok(reservation) => Ok(Json(ReservationResponse::success(reservation))),
+        Err(e @ (Error::ProcessAlreadyExists(_) | Error::NameReserved(_, _))) => Err(failed(StatusCode::CONFLICT, e.to_string())),
+        Err(e @ (Error::InvalidDuration(_) | Error::ReservedName(_))) => Err(failed(StatusCode::BAD_REQUEST, e.to_string())),
+        Err(e) => {
+            eprintln!("Error reserving process name: {}", e);
+            Err(failed(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+        }
+    }
+}
+
+#[cfg(feature = "http-api")]
+#[utoipa::path(
+    post,
+    path = "/api/processes",
+    request_body = StartProcessRequest,
+    responses(
+        (status = 200, description = "Process started successfully, or already running unchanged (idempotent); with return=record also the stored record and message", body = StartResponse),
+        (status = 400, description = "An invalid or reserved name, invalid environment variables or CPU affinity, or an unknown return value", body = StartResponse),
+        (status = 401, description = "Unauthorized", body = StartResponse),
+        (status = 409, description = "Process already exists, another token reserved the name, or the job already succeeded and rerun was not set", body = StartResponse),
+        (status = 422, description = "Idempotent start found the process with a different definition, or a strict start exited within the start window", body = StartResponse),
+        (status = 413, description = "The arguments or environment are over a start_limits size limit", body = StartResponse),
+        (status = 429, description = "The max_processes limit is reached", body = StartResponse),
+        (status = 500, description = "The process could not be spawned", body = StartResponse)
+    ),
+    params(
+        ("return" = Option<String>, Query, description = "outcome (default) returns the StartOutcome; record adds the stored ProcessRecord and the start message, saving a GET")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn start_process(
+    State((process_manager, _auth_manager)): State<(Arc<ProcessManager>, Arc<Mutex<AuthManager>>)>,
+    Authenticated(token): Authenticated,
+    Query(params): Query<StartQuery>,
+    Json(mut request): Json<StartProcessRequest>,
+) -> std::result::Result<Json<StartResponse>, (StatusCode, Json<StartResponse>)> {
+    let failed = |status: StatusCode, message: String| (status, Json(StartResponse::error(message)));
+    let return_record = match params.return_value.as_deref() {
+        None | Some("outcome") => false,
+        Some("record") => true,
+        Some(other) => {
+            return Err(failed(
+                StatusCode::BAD_REQUEST,
+                format!("Unknown return value '{}'; expected 'outcome' or 'record'", other),
+            ))
+        }
+    };
+    let name = ProcessName::new(request.name.as_str()).map_err(|e| failed(StatusCode::BAD_REQUEST, e.to_string()))?;
+    let options = request
+        .start_options()
+        .map_err(|e| failed(StatusCode::BAD_REQUEST, e.to_string()))?;
+    let options = StartOptions { reservation_token: Some(token.public_id), ..options };
+    let env_vars = request.env_vars.unwrap_or_default();
+
+    match process_manager
+        .start_with_options(&name, &request.command, request.args, env_vars, options)
+        .await
+    {
+        Ok(outcome) if return_record => {
+            // Read back without probing: the record as the start left it in the database
+            let record = process_manager
+                .get_process_status_with_refresh(&name, StatusRefresh::Stored)
+                .await
+                .map_err(|e| failed(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+            Ok(Json(StartResponse::with_record(outcome, record)))
+        }
+        Ok(outcome) => Ok(Json(StartResponse::success(outcome))),
+        Err(e @ (Error::ProcessAlreadyExists(_) | Error::JobCompleted(_) | Error::NameReserved(_, _))) => {
+            Err(failed(StatusCode::CONFLICT, e.to_string()))
+        }
+        Err(e @ (Error::DefinitionMismatch(_, _) | Error::StartExited(_, _, _))) => {
+            Err(failed(start_exit_status(&e), e.to_string()))
+        }
+        Err(e @ (Error::InvalidEnvVars(_) | Error::InvalidCpuAffinity(_) | Error::InvalidDescription(_) | Error::ReservedName(_))) => {
+            Err(failed(StatusCode::BAD_REQUEST, e.to_string()))
+        }
+        Err(e @ Error::ProcessLimitReached(_, _)) => Err(failed(StatusCode::TOO_MANY_REQUESTS, e.to_string())),
+        Err(e @ Error::StartTooLarge(_)) => Err(failed(StatusCode::PAYLOAD_TOO_LARGE, e.to_string())),
+        Err(e) => {
+            eprintln!("Error starting process: {}", e);
+            Err(failed(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+        }
+    }
+}
+
+// Status for a start refused as unprocessable. The command runs through setsid, so a
+// binary that cannot be executed shows up as a quick exit with 126 or 127: that is a
+// spawn failure, not the process failing at runtime.
+#[cfg(feature = "http-api")]
+fn start_exit_status(error: &Error) -> StatusCode {
+    match error {
+        Error::StartExited(_, Some(126 | 127), _) => StatusCode::INTERNAL_SERVER_ERROR,
+        _ => StatusCode::UNPROCESSABLE_ENTITY,
+    }
+}
+
+// Validate every bulk item up front, returning one optional error per item
+#[cfg(feature = "http-api")]
+fn validate_bulk_start_requests(requests: &[StartProcessRequest], env_policy: ReservedEnvPolicy, start_limits: &StartLimitsConfig) -> Vec<Option<String>> {
+    let mut seen_names = std::collections::HashSet::new();
+    requests
+        .iter()
+        .map(|request| {
+            if let Err(error) = ProcessName::new(request.name.as_str()) {
+                Some(error.to_string())
+            } else if request.command.trim().is_empty() {
+                Some(format!("Command for process '{}' must not be empty", request.name))
+            } else if !seen_names.insert(request.name.as_str()) {
+                Some(format!("Duplicate process name '{}' in batch", request.name))
+            } else if let Some(Err(errors)) = request
+                .env_vars
+                .as_ref()
+                .map(|env_vars| validate_env_vars(env_vars, env_policy))
+            {
+                Some(Error::InvalidEnvVars(errors).to_string())
+            } else if let Some(Err(error)) = request
+                .cpu_affinity
+                .as_ref()
+                .map(|list| list.parse::<CpuSet>().and_then(|cpus| cpus.validate(available_cpus())))
+            {
+                Some(error.to_string())
+            } else if let Some(Err(error)) = request.description.as_deref().map(limits::clean_description) {
+                Some(error.to_string())
+            } else if let Err(error) = check_start_size(start_limits, &request.args, request.env_vars.as_ref().unwrap_or(&HashMap::new())) {
+                Some(error.to_string())
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+#[cfg(feature = "http-api")]
+#[utoipa::path(
+    post,
+    path = "/api/processes/bulk",
+    request_body = Vec<StartProcessRequest>,
+    responses(
+        (status = 200, description = "Batch processed; per-item results describe each start", body = BulkStartResponse),
+        (status = 400, description = "Batch is empty, too large, or failed validation (nothing was started)", body = BulkStartResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 429, description = "The batch would exceed the max_processes limit (nothing was started)", body = BulkStartResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn bulk_start_processes(
+    State((process_manager, _auth_manager)): State<(Arc<ProcessManager>, Arc<Mutex<AuthManager>>)>,
+    Authenticated(token): Authenticated,
+    Json(requests): Json<Vec<StartProcessRequest>>,
+) -> std::result::Result<(StatusCode, Json<BulkStartResponse>), StatusCode> {
+    let api_config = &process_manager.config().api;
+
+    if requests.is_empty() {
+        return Ok((
+            StatusCode::BAD_REQUEST,
+            Json(BulkStartResponse::error("Batch must contain at least one process".to_string(), None)),
+        ));
+    }
+    if requests.len() > api_config.max_bulk_size {
+        return Ok((
+            StatusCode::BAD_REQUEST,
+            Json(BulkStartResponse::error(
+                format!("Batch contains {} processes, maximum is {}", requests.len(), api_config.max_bulk_size),
+                None,
+            )),
+        ));
+    }
+
+    // Nothing is started unless every item passes validation
+    let validation_errors = validate_bulk_start_requests(&requests, process_manager.config().reserved_env_policy, &process_manager.config().start_limits);
+    if validation_errors.iter().any(Option::is_some) {
+        let results = requests
+            .into_iter()
+            .zip(validation_errors)
+            .map(|(request, error)| match error {
+                Some(error) => BulkStartItemResult::failed(request.name, error, StatusCode::BAD_REQUEST),
+                None => BulkStartItemResult::failed(
+                    request.name,
+                    "Not started: batch validation failed".to_string(),
+                    StatusCode::FAILED_DEPENDENCY,
+                ),
+            })
+            .collect();
+        return Ok((
+            StatusCode::BAD_REQUEST,
+            Json(BulkStartResponse::error("Batch validation failed".to_string(), Some(results))),
+        ));
+    }
+
+    // The whole batch must fit under max_processes before anything starts
+    match process_manager.check_capacity(requests.len()).await {
+        Ok(()) => {}
+        Err(e @ Error::ProcessLimitReached(_, _)) => {
+            return Ok((StatusCode::TOO_MANY_REQUESTS, Json(BulkStartResponse::error(e.to_string(), None))));
+        }
+        Err(e) => {
+            eprintln!("Error checking process capacity: {}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    }
+
+    let semaphore = Arc::new(tokio::sync::Semaphore::new(api_config.bulk_concurrency.max(1)));
+    // Items running the same command resolve and hash it once for the whole request
+    let resolution = process_manager.resolution_cache();
+    let mut handles = Vec::with_capacity(requests.len());
+    for mut request in requests {
+        let name = request.name.clone();
+        let process_manager = process_manager.clone();
+        let semaphore = semaphore.clone();
+        let resolution = resolution.clone();
+        let reservation_token = Some(token.public_id.clone());
+        let handle = tokio::spawn(async move {
+            let permit = semaphore.acquire().await;
+            let started = match (ProcessName::new(request.name.as_str()), request.start_options()) {
+                // Start windows are watched in the background so they overlap across the batch
+                (Ok(name), Ok(options)) => {
+                    process_manager
+                        .start_with_options(
+                            &name,
+                            &request.command,
+                            request.args,
+                            request.env_vars.unwrap_or_default(),
+                            StartOptions { check_in_background: true, resolution: Some(resolution), reservation_token, ..options },
+                        )
+                        .await
+                }
+                (Err(e), _) | (_, Err(e)) => Err(e),
+            };
+            // Waiting for the start window takes no spawning capacity
+            drop(permit);
+            let started = match started {
+                Ok(outcome) => Ok(process_manager.finish_start_check(outcome).await),
+                Err(e) => Err(e),
+            };
+            match started {
+                Ok(outcome) => BulkStartItemResult::started(request.name, outcome),
+                Err(e @ (Error::ProcessAlreadyExists(_) | Error::JobCompleted(_) | Error::NameReserved(_, _))) => {
+                    BulkStartItemResult::failed(request.name, e.to_string(), StatusCode::CONFLICT)
+                }
+                Err(e @ (Error::DefinitionMismatch(_, _) | Error::StartExited(_, _, _))) => {
+                    let status = start_exit_status(&e);
+                    BulkStartItemResult::failed(request.name, e.to_string(), status)
+                }
+                Err(e @ (Error::InvalidEnvVars(_) | Error::InvalidCpuAffinity(_) | Error::InvalidDescription(_) | Error::ReservedName(_))) => {
+                    BulkStartItemResult::failed(request.name, e.to_string(), StatusCode::BAD_REQUEST)
+                }
+                Err(e @ Error::ProcessLimitReached(_, _)) => {
+                    BulkStartItemResult::failed(request.name, e.to_string(), StatusCode::TOO_MANY_REQUESTS)
+                }
+                Err(e @ Error::StartTooLarge(_)) => {
+                    BulkStartItemResult::failed(request.name, e.to_string(), StatusCode::PAYLOAD_TOO_LARGE)
+                }
+                Err(e) => {
+                    eprintln!("Error starting process in bulk: {}", e);
+                    BulkStartItemResult::failed(request.name, e.to_string(), StatusCode::INTERNAL_SERVER_ERROR)
+                }
+            }
+        });
+        handles.push((name, handle));
+    }
+
+    // Await in spawn order so results line up with the request array
+    let mut results = Vec::with_capacity(handles.len());
+    for (name, handle) in handles {
+        let result = handle.await.unwrap_or_else(|e| {
+            BulkStartItemResult::failed(name, format!("Start task failed: {}", e), StatusCode::INTERNAL_SERVER_ERROR)
+        });
+        results.push(result);
+    }
+
+    Ok((StatusCode::OK, Json(BulkStartResponse::success(results))))
+}
+
+#[cfg(feature = "http-api")]
+#[utoipa::path(
+    put,
+    path = "/api/processes/{name}/stop",
+    responses(
+        (status = 200, description = "Process stopped, or found already stopped", body = StopResponse),
+        (status = 400, description = "Unknown signal"),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Process not found"),
+        (status = 409, description = "Process is still running after the stop grace period")
+    ),
+    params(
+        ("name" = String, Path, description = "Process name"),
+        ("signal" = Option<String>, Query, description = "Signal to send first, overriding the process's stop signal")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn stop_process(
+    State((process_manager, _auth_manager)): State<(Arc<ProcessManager>, Arc<Mutex<AuthManager>>)>,
+    _: Authenticated,
+    Path(name): Path<ProcessName>,
+    Query(params): Query<StopQuery>,
+) -> std::result::Result<Json<StopResponse>, StatusCode> {
+    let options = StopOptions {
+        signal: params.signal.as_deref().map(parse_signal).transpose().map_err(|_| StatusCode::BAD_REQUEST)?,
+        ..Default::default()
+    };
+    match process_manager.stop_with_options(&name, options).await {
+        Ok(outcome) => Ok(Json(StopResponse::success(outcome))),
+        Err(Error::ProcessNotFound(_)) => Err(StatusCode::NOT_FOUND),
+        Err(Error::StopTimedOut(_, _)) => Err(StatusCode::CONFLICT),
+        Err(e) => {
+            eprintln!("Error stopping process: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+#[cfg(feature = "http-api")]
+#[utoipa::path(
+    put,
+    path = "/api/processes/{name}/restart",
+    responses(
+        (status = 200, description = "Process restarted successfully", body = RestartResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Process not found"),
+        (status = 409, description = "Process is flapping; pass force=true to restart it anyway")
+    ),
+    params(
+        ("name" = String, Path, description = "Process name"),
+        ("force" = Option<bool>, Query, description = "Restart even if the process is flapping")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn restart_process(
+    State((process_manager, _auth_manager)): State<(Arc<ProcessManager>, Arc<Mutex<AuthManager>>)>,
+    _: Authenticated,
+    Path(name): Path<ProcessName>,
+    Query(params): Query<RestartQuery>,
+) -> std::result::Result<Json<RestartResponse>, StatusCode> {
+    let options = RestartOptions {
+        force: params.force.unwrap_or(false),
+        ..Default::default()
+    };
+    match process_manager.restart_with_options(&name, options).await {
+        Ok(outcome) => Ok(Json(RestartResponse::success(outcome))),
+        Err(Error::ProcessNotFound(_)) => Err(StatusCode::NOT_FOUND),
+        Err(Error::Flapping(_, _, _)) => Err(StatusCode::CONFLICT),
+        Err(e) => {
+            eprintln!("Error restarting process: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+#[cfg(feature = "http-api")]
+#[utoipa::path(
+    delete,
+    path = "/api/processes/{name}",
+    responses(
+        (status = 200, description = "Process deleted successfully", body = DeleteResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Process not found")
+    ),
+    params(
+        ("name" = String, Path, description = "Process name"),
+        ("hard" = Option<bool>, Query, description = "Delete permanently instead of soft-deleting")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn delete_process(
+    State((process_manager, _auth_manager)): State<(Arc<ProcessManager>, Arc<Mutex<AuthManager>>)>,
+    _: Authenticated,
+    Path(name): Path<ProcessName>,
+    Query(params): Query<DeleteQuery>,
+) -> std::result::Result<Json<DeleteResponse>, StatusCode> {
+    let options = DeleteOptions {
+        hard: params.hard.unwrap_or(false),
+    };
+    match process_manager.delete_with_options(&name, options).await {
+        Ok(outcome) => Ok(Json(DeleteResponse::success(outcome))),
+        Err(Error::ProcessNotFound(_)) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            eprintln!("Error deleting process: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+#[cfg(feature = "http-api")]
+#[utoipa::path(
+    post,
+    path = "/api/processes/{name}/notes",
+    request_body = AddNoteRequest,
+    responses(
+        (status = 200, description = "Note added; the author is the name of the token used", body = NoteResponse),
+        (status = 400, description = "Note is empty or longer than 4096 bytes", body = NoteResponse),
+        (status = 401, description = "Unauthorized", body = NoteResponse),
+        (status = 404, description = "Process not found", body = NoteResponse),
+        (status = 429, description = "The process already has 100 notes", body = NoteResponse)
+    ),
+    params(
+        ("name" = String, Path, description = "Process name")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn add_process_note(
+    State((process_manager, _auth_manager)): State<(Arc<ProcessManager>, Arc<Mutex<AuthManager>>)>,
+    Authenticated(token): Authenticated,
+    Path(name): Path<ProcessName>,
+    Json(request): Json<AddNoteRequest>,
+) -> std::result::Result<Json<NoteResponse>, (StatusCode, Json<NoteResponse>)> {
+    let failed = |status: StatusCode, message: String| (status, Json(NoteResponse::error(message)));
+    match process_manager.annotate_process(&name, &token.name, &request.text).await {
+        Ok(note) => Ok(Json(NoteResponse::success(note))),
+        Err(e @ Error::ProcessNotFound(_)) => Err(failed(StatusCode::NOT_FOUND, e.to_string())),
+        Err(e @ Error::InvalidNote(_)) => Err(failed(StatusCode::BAD_REQUEST, e.to_string())),
+        Err(e @ Error::NoteLimitReached(_, _)) => Err(failed(StatusCode::TOO_MANY_REQUESTS, e.to_string())),
+        Err(e) => {
+            eprintln!("Error adding process note: {}", e);
+            Err(failed(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+        }
+    }
+}
+
+#[cfg(feature = "http-api")]
+#[utoipa::path(
+    get,
+    path = "/api/processes/{name}/notes",
+    responses(
+        (status = 200, description = "Notes on the process, oldest first", body = NoteListResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Process not found")
+    ),
+    params(
+        ("name" = String, Path, description = "Process name")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn list_process_notes(
+    State((process_manager, _auth_manager)): State<(Arc<ProcessManager>, Arc<Mutex<AuthManager>>)>,
+    _: Authenticated,
+    Path(name): Path<ProcessName>,
+) -> std::result::Result<Json<NoteListResponse>, StatusCode> {
+    match process_manager.get_process_notes(&name).await {
+        Ok(notes) => Ok(Json(NoteListResponse::success(notes))),
+        Err(Error::ProcessNotFound(_)) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            eprintln!("Error getting process notes: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+#[cfg(feature = "http-api")]
+#[utoipa::path(
+    get,
+    path = "/api/processes/{name}/inspect",
+    responses(
+        (status = 200, description = "The stored record with its unredacted environment, liveness, resources, logs, histories, notes and the pmr installation", body = InspectResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "The token is not an admin token"),
+        (status = 404, description = "Process not found")
+    ),
+    params(
+        ("name" = String, Path, description = "Process name")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn inspect_process(
+    State((process_manager, _auth_manager)): State<(Arc<ProcessManager>, Arc<Mutex<AuthManager>>)>,
+    Authenticated(token): Authenticated,
+    Path(name): Path<ProcessName>,
+) -> std::result::Result<Json<InspectResponse>, StatusCode> {
+    // Unredacted data is for admin tokens only
+    if !token.admin {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    match process_manager.inspect(&name).await {
+        // pmr's own processes are not served, as if they did not exist
+        Ok(report) if report.record.internal => Err(StatusCode::NOT_FOUND),
+        Ok(report) => Ok(Json(InspectResponse::success(report))),
+        Err(Error::ProcessNotFound(_)) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            eprintln!("Error inspecting process: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+#[cfg(feature = "http-api")]
+#[utoipa::path(
+    delete,
+    path = "/api/processes/{name}/notes/{note_id}",
+    responses(
+        (status = 200, description = "Note deleted", body = MessageResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Process or note not found")
+    ),
+    params(
+        ("name" = String, Path, description = "Process name"),
+        ("note_id" = i64, Path, description = "Note id")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn delete_process_note(
+    State((process_manager, _auth_manager)): State<(Arc<ProcessManager>, Arc<Mutex<AuthManager>>)>,
+    _: Authenticated,
+    Path((name, note_id)): Path<(ProcessName, i64)>,
+) -> std::result::Result<Json<MessageResponse>, StatusCode> {
+    match process_manager.delete_process_note(&name, note_id).await {
+        Ok(()) => Ok(Json(MessageResponse::success(format!("Deleted note {} from process '{}'", note_id, name)))),
+        Err(Error::ProcessNotFound(_) | Error::NoteNotFound(_, _)) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            eprintln!("Error deleting process note: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+#[cfg(feature = "http-api")]
+#[utoipa::path(
+    get,
+    path = "/api/processes/{name}/logs",
+    responses(
+        (status = 200, description = "Process logs; with rotated=true a RotatedLogsContentResponse", body = LogsResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 400, description = "tail_bytes combined with lines, rotated or merge_rotated, or since invalid or combined with tail_bytes, rotated or merge_rotated"),
+        (status = 404, description = "Process not found"),
+        (status = 422, description = "lines or tail_bytes exceeds the configured maximum")
     ),
     params(
         ("name" = String, Path, description = "Process name"),
-        ("lines" = Option<usize>, Query, description = "Number of lines to return"),
-        ("rotated" = Option<bool>, Query, description = "Whether to return rotated log files")
+        ("lines" = Option<usize>, Query, description = "Number of lines to return (0 returns nothing; at most `max_log_lines`, default 10000)"),
+        ("rotated" = Option<bool>, Query, description = "Return the content of the rotated log files, cut off at `max_logs_response_bytes`"),
+        ("merge_rotated" = Option<bool>, Query, description = "Tail across rotated files and the live log as one stream"),
+        ("collapse_repeats" = Option<bool>, Query, description = "Replace runs of identical lines with one line and `[repeated N times]`; `lines` counts collapsed entries"),
+        ("tail_bytes" = Option<u64>, Query, description = "Return the last N bytes of the live log (at most `max_logs_response_bytes`), starting at the next whole line; `start_offset` tells where in the file"),
+        ("allow_partial" = Option<bool>, Query, description = "With tail_bytes, keep the partial line the cut lands in"),
+        ("since" = Option<String>, Query, description = "Only lines of the live log timed at or after this RFC 3339 time or duration ago (e.g. 30m), found with the log index when it has one")
     ),
     security(
         ("bearer_auth" = [])
     )
 )]
 pub async fn get_process_logs(
-    State((process_manager, auth_manager)): State<(Arc<ProcessManager>, Arc<Mutex<AuthManager>>)>,
-    headers: HeaderMap,
-    Path(name): Path<String>,
+    State((process_manager, _auth_manager)): State<(Arc<ProcessManager>, Arc<Mutex<AuthManager>>)>,
+    _: Authenticated,
+    Path(name): Path<ProcessName>,
     Query(params): Query<LogsQuery>,
-) -> std::result::Result<Json<MessageResponse>, StatusCode> {
-    validate_auth(&headers, &auth_manager)?;
+) -> std::result::Result<Response, StatusCode> {
+    if params.lines.is_some_and(|lines| lines > process_manager.config().api.max_log_lines) {
+        return Err(StatusCode::UNPROCESSABLE_ENTITY);
+    }
+    if params.tail_bytes.is_some_and(|bytes| bytes > process_manager.config().api.max_logs_response_bytes as u64) {
+        return Err(StatusCode::UNPROCESSABLE_ENTITY);
+    }
+    if params.tail_bytes.is_some()
+        && (params.lines.is_some() || params.rotated.unwrap_or(false) || params.merge_rotated.unwrap_or(false))
+    {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let since = params.since.as_deref().map(str::parse::<LogSince>).transpose().map_err(|_| StatusCode::BAD_REQUEST)?;
+    if since.is_some()
+        && (params.tail_bytes.is_some() || params.rotated.unwrap_or(false) || params.merge_rotated.unwrap_or(false))
+    {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let options = LogOptions {
+        lines: params.lines,
+        collapse_repeats: params.collapse_repeats.unwrap_or(false),
+        tail_bytes: params.tail_bytes,
+        allow_partial: params.allow_partial.unwrap_or(false),
+        since,
+    };
     if params.rotated.unwrap_or(false) {
-        match process_manager.get_rotated_logs(&name).await {
-            Ok(logs) => Ok(Json(MessageResponse::success(logs.join("\n")))),
+        let max_bytes = process_manager.config().api.max_logs_response_bytes;
+        match process_manager.read_rotated_logs(&name, max_bytes).await {
+            Ok(content) => Ok(Json(RotatedLogsContentResponse::success(&name, content)).into_response()),
             Err(Error::ProcessNotFound(_)) => Err(StatusCode::NOT_FOUND),
             Err(e) => {
                 eprintln!("Error getting rotated logs: {}", e);
                 Err(StatusCode::INTERNAL_SERVER_ERROR)
             }
         }
+    } else if params.merge_rotated.unwrap_or(false) {
+        match process_manager.read_merged_process_logs(&name, &options).await {
+            Ok(logs) => Ok(Json(LogsResponse::success(logs)).into_response()),
+            Err(Error::ProcessNotFound(_)) => Err(StatusCode::NOT_FOUND),
+            Err(e) => {
+                eprintln!("Error getting merged logs: {}", e);
+                Err(StatusCode::INTERNAL_SERVER_ERROR)
+            }
+        }
     } else {
-        match process_manager.get_process_logs(&name, params.lines).await {
-            Ok(logs) => Ok(Json(MessageResponse::success(logs))),
+        match process_manager.read_process_logs(&name, &options).await {
+            Ok(logs) => Ok(Json(LogsResponse::success(logs)).into_response()),
             Err(Error::ProcessNotFound(_)) => Err(StatusCode::NOT_FOUND),
             Err(e) => {
                 eprintln!("Error getting process logs: {}", e);
@@ -431,3 +1645,334 @@ pub async fn get_process_logs(
         }
     }
 }
+
+#[cfg(feature = "http-api")]
+#[utoipa::path(
+    get,
+    path = "/api/processes/{name}/logs/rotated",
+    responses(
+        (status = 200, description = "Rotated log files of the process, newest first, without their content", body = RotatedLogsResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Process not found")
+    ),
+    params(
+        ("name" = String, Path, description = "Process name")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn list_rotated_logs(
+    State((process_manager, _auth_manager)): State<(Arc<ProcessManager>, Arc<Mutex<AuthManager>>)>,
+    _: Authenticated,
+    Path(name): Path<ProcessName>,
+) -> std::result::Result<Json<RotatedLogsResponse>, StatusCode> {
+    match process_manager.rotated_logs(&name).await {
+        Ok(rotated) => Ok(Json(RotatedLogsResponse::success(rotated))),
+        Err(Error::ProcessNotFound(_)) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            eprintln!("Error listing rotated logs: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Size of the in-memory pipe between the archive writer and the response body
+#[cfg(feature = "http-api")]
+const ARCHIVE_PIPE_SIZE: usize = 64 * 1024;
+
+#[cfg(feature = "http-api")]
+#[utoipa::path(
+    get,
+    path = "/api/processes/{name}/logs/archive",
+    responses(
+        (status = 200, description = "Tar archive of the live and rotated logs", content_type = "application/x-tar", body = Vec<u8>),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Process not found")
+    ),
+    params(
+        ("name" = String, Path, description = "Process name")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn get_process_logs_archive(
+    State((process_manager, _auth_manager)): State<(Arc<ProcessManager>, Arc<Mutex<AuthManager>>)>,
+    _: Authenticated,
+    Path(name): Path<ProcessName>,
+) -> std::result::Result<Response, StatusCode> {
+    let entries = match process_manager.log_archive_entries(&name).await {
+        Ok(entries) => entries,
+        Err(Error::ProcessNotFound(_)) => return Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            eprintln!("Error collecting log files: {}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    // The archive is written into a bounded pipe and streamed out as it is produced
+    let (mut writer, reader) = tokio::io::duplex(ARCHIVE_PIPE_SIZE);
+    let manifest_dir = name.clone();
+    tokio::spawn(async move {
+        if let Err(e) = write_tar_archive(&mut writer, &manifest_dir, &entries).await {
+            eprintln!("Error streaming log archive: {}", e);
+        }
+    });
+
+    let stream = futures_util::stream::unfold(reader, |mut reader| async move {
+        use tokio::io::AsyncReadExt;
+        let mut buffer = vec![0u8; ARCHIVE_PIPE_SIZE];
+        match reader.read(&mut buffer).await {
+            Ok(0) => None,
+            Ok(read) => {
+                buffer.truncate(read);
+                Some((Ok::<Bytes, std::io::Error>(Bytes::from(buffer)), reader))
+            }
+            Err(e) => Some((Err(e), reader)),
+        }
+    });
+
+    let file_name = archive_file_name(&name, chrono::Utc::now());
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/x-tar".to_string()),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", file_name)),
+        ],
+        Body::from_stream(stream),
+    )
+        .into_response())
+}
+
+#[cfg(feature = "http-api")]
+#[utoipa::path(
+    get,
+    path = "/api/processes/{name}/logs/follow",
+    responses(
+        (status = 101, description = "WebSocket carrying one text frame per new log line, and \"[N lines skipped]\" when the client falls behind"),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Process not found")
+    ),
+    params(
+        ("name" = String, Path, description = "Process name")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn follow_process_logs(
+    State((process_manager, _auth_manager, broker)): State<(Arc<ProcessManager>, Arc<Mutex<AuthManager>>, LogBroker)>,
+    _: Authenticated,
+    Path(name): Path<ProcessName>,
+    ws: WebSocketUpgrade,
+) -> std::result::Result<Response, StatusCode> {
+    let process = match process_manager.get_process_status(&name).await {
+        Ok(process) => process,
+        Err(Error::ProcessNotFound(_)) => return Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            eprintln!("Error getting process status: {}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    let subscription = broker.subscribe(&name, std::path::Path::new(&process.log_path));
+    Ok(ws.on_upgrade(move |socket| stream_log_frames(socket, subscription)))
+}
+
+/// Forward frames until the client goes away; the subscription is dropped with it
+#[cfg(feature = "http-api")]
+async fn stream_log_frames(mut socket: WebSocket, mut subscription: LogSubscription) {
+    loop {
+        tokio::select! {
+            frame = subscription.next() => {
+                let Some(frame) = frame else { break };
+                if socket.send(Message::Text(frame.to_string())).await.is_err() {
+                    break;
+                }
+            }
+            incoming = socket.recv() => {
+                if matches!(incoming, None | Some(Err(_)) | Some(Ok(Message::Close(_)))) {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(feature = "http-api")]
+#[derive(Debug, Serialize, ToSchema)]
+pub struct MetricsResponse {
+    /// Clients following each log through `/logs/follow`
+    pub log_subscribers: Vec<LogSubscriberCount>,
+    pub total_log_subscribers: usize,
+    /// File operations of sweeps over every process, like measuring the logs
+    pub file_pool: FilePoolStats,
+    /// Processes per status and how long the oldest failed and stopped ones have been so
+    pub processes: StatusGauges,
+}
+
+#[cfg(feature = "http-api")]
+#[utoipa::path(
+    get,
+    path = "/api/metrics",
+    responses(
+        (status = 200, description = "Server metrics", body = MetricsResponse),
+        (status = 401, description = "Unauthorized")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn get_metrics(
+    State((process_manager, _auth_manager, broker)): State<(Arc<ProcessManager>, Arc<Mutex<AuthManager>>, LogBroker)>,
+    _: Authenticated,
+) -> std::result::Result<Json<MetricsResponse>, StatusCode> {
+    let processes = process_manager.status_gauges().await.map_err(|e| {
+        eprintln!("Error reading process gauges: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    let log_subscribers = broker.subscriber_counts();
+    Ok(Json(MetricsResponse {
+        total_log_subscribers: log_subscribers.iter().map(|count| count.subscribers).sum(),
+        log_subscribers,
+        file_pool: process_manager.file_pool_stats(),
+        processes,
+    }))
+}
+
+#[cfg(feature = "http-api")]
+#[utoipa::path(
+    get,
+    path = "/api/stats",
+    responses(
+        (status = 200, description = "What `pmr stats` shows: counts per status, the ages of the oldest failed and stopped processes, paths and log usage", body = ManagerStats),
+        (status = 401, description = "Unauthorized")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn get_stats(
+    State((process_manager, _auth_manager)): State<(Arc<ProcessManager>, Arc<Mutex<AuthManager>>)>,
+    _: Authenticated,
+) -> std::result::Result<Json<ManagerStats>, StatusCode> {
+    match process_manager.stats().await {
+        Ok(stats) => Ok(Json(stats)),
+        Err(e) => {
+            eprintln!("Error reading stats: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+#[cfg(feature = "http-api")]
+#[utoipa::path(
+    post,
+    path = "/api/admin/reload",
+    responses(
+        (status = 200, description = "Config file applied; lists the sections that changed", body = ReloadResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "The token is not an admin token", body = ReloadResponse),
+        (status = 422, description = "The config file is invalid; the running configuration is unchanged", body = ReloadResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn reload_config(
+    State((process_manager, _auth_manager)): State<(Arc<ProcessManager>, Arc<Mutex<AuthManager>>)>,
+    Authenticated(token): Authenticated,
+) -> std::result::Result<Json<ReloadResponse>, (StatusCode, Json<ReloadResponse>)> {
+    let failed = |status: StatusCode, message: String| (status, Json(ReloadResponse::error(message)));
+    // Reloading changes the configuration for every client, so only admin tokens may
+    if !token.admin {
+        return Err(failed(StatusCode::FORBIDDEN, "Reloading the configuration needs an admin token".to_string()));
+    }
+    match process_manager.reload_config().await {
+        Ok(outcome) => Ok(Json(ReloadResponse::success(outcome))),
+        Err(e @ (Error::InvalidConfig(_) | Error::Config(_))) => {
+            Err(failed(StatusCode::UNPROCESSABLE_ENTITY, e.to_string()))
+        }
+        Err(e) => {
+            eprintln!("Error reloading configuration: {}", e);
+            Err(failed(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+        }
+    }
+}
+
+#[cfg(feature = "http-api")]
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct HealthResponse {
+    /// Always "ok" while the server answers
+    pub status: String,
+    /// Whether the server only serves read requests
+    pub read_only: bool,
+    /// pmr version of the server
+    pub version: String,
+    /// Seconds since the server was created
+    pub uptime_secs: u64,
+}
+
+/// Liveness probe; needs no token so load balancers and status pages can call it. Checks
+/// nothing beyond the server answering, see `/readyz` for that.
+#[cfg(feature = "http-api")]
+#[utoipa::path(
+    get,
+    path = "/healthz",
+    responses(
+        (status = 200, description = "The server is up", body = HealthResponse)
+    )
+)]
+pub async fn health_check(read_only: bool, started: std::time::Instant) -> Json<HealthResponse> {
+    Json(HealthResponse {
+        status: "ok".to_string(),
+        read_only,
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        uptime_secs: started.elapsed().as_secs(),
+    })
+}
+
+/// Readiness probe; needs no token either. 503 when the database or the log directory
+/// cannot be used, naming the failed checks.
+#[cfg(feature = "http-api")]
+#[utoipa::path(
+    get,
+    path = "/readyz",
+    responses(
+        (status = 200, description = "Every dependency works", body = ReadinessReport),
+        (status = 503, description = "Some dependency failed; `failed` names the checks", body = ReadinessReport)
+    )
+)]
+pub async fn readiness_check(State(process_manager): State<Arc<ProcessManager>>) -> (StatusCode, Json<ReadinessReport>) {
+    let report = readiness::check(&process_manager).await;
+    let status = if report.ready { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    (status, Json(report))
+}
+
+/// Let only requests with a valid token through to the routes it is layered on, handing
+/// the token to them as [`Authenticated`]
+#[cfg(feature = "http-api")]
+pub async fn require_token(
+    State(auth_manager): State<Arc<Mutex<AuthManager>>>,
+    headers: HeaderMap,
+    mut request: axum::extract::Request,
+    next: axum::middleware::Next,
+) -> std::result::Result<Response, (StatusCode, Json<MessageResponse>)> {
+    let token = authenticate_token(&headers, &auth_manager)
+        .await
+        .map_err(|status| (status, Json(MessageResponse::error("Unauthorized".to_string()))))?;
+    request.extensions_mut().insert(Authenticated(token));
+    Ok(next.run(request).await)
+}
+
+/// Answer for every non-GET request on a read-only server
+#[cfg(feature = "http-api")]
+pub async fn reject_read_only() -> (StatusCode, [(header::HeaderName, &'static str); 1], Json<MessageResponse>) {
+    (
+        StatusCode::METHOD_NOT_ALLOWED,
+        [(header::ALLOW, "GET, HEAD")],
+        Json(MessageResponse::error(
+            "This pmr API server is read-only; only GET requests are accepted".to_string(),
+        )),
+    )
+}
@@ -0,0 +1,217 @@
+//! Fan-out of followed logs: one tailer per (process, file), shared by every client
+//! streaming that log, so extra dashboard tabs cost a channel receiver and no extra IO.
+
+#[cfg(feature = "http-api")]
+use serde::Serialize;
+#[cfg(feature = "http-api")]
+use std::collections::HashMap;
+#[cfg(feature = "http-api")]
+use std::fmt;
+#[cfg(feature = "http-api")]
+use std::path::{Path, PathBuf};
+#[cfg(feature = "http-api")]
+use std::sync::{Arc, Mutex};
+#[cfg(feature = "http-api")]
+use std::time::Duration;
+#[cfg(feature = "http-api")]
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+#[cfg(feature = "http-api")]
+use tokio::sync::broadcast;
+#[cfg(feature = "http-api")]
+use utoipa::ToSchema;
+
+/// Lines buffered per followed log before the slowest subscriber starts losing them
+#[cfg(feature = "http-api")]
+pub const DEFAULT_CHANNEL_CAPACITY: usize = 1024;
+
+/// How often a tailer checks its file for new output
+#[cfg(feature = "http-api")]
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// What a subscriber receives
+#[cfg(feature = "http-api")]
+#[derive(Debug, Clone, PartialEq)]
+pub enum LogFrame {
+    Line(String),
+    /// The subscriber fell this many lines behind and they were dropped
+    Skipped(u64),
+}
+
+#[cfg(feature = "http-api")]
+impl fmt::Display for LogFrame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Line(line) => write!(f, "{}", line),
+            Self::Skipped(count) => write!(f, "[{} lines skipped]", count),
+        }
+    }
+}
+
+/// Clients currently following one log file
+#[cfg(feature = "http-api")]
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct LogSubscriberCount {
+    pub process: String,
+    pub path: String,
+    pub subscribers: usize,
+}
+
+#[cfg(feature = "http-api")]
+type TailKey = (String, PathBuf);
+
+#[cfg(feature = "http-api")]
+struct Tailer {
+    sender: broadcast::Sender<String>,
+    subscribers: usize,
+    task: tokio::task::JoinHandle<()>,
+}
+
+#[cfg(feature = "http-api")]
+struct BrokerState {
+    tailers: Mutex<HashMap<TailKey, Tailer>>,
+    capacity: usize,
+    poll_interval: Duration,
+}
+
+/// Hands out subscriptions to followed logs; cheap to clone, all clones share the tailers
+#[cfg(feature = "http-api")]
+#[derive(Clone)]
+pub struct LogBroker {
+    state: Arc<BrokerState>,
+}
+
+#[cfg(feature = "http-api")]
+impl Default for LogBroker {
+    fn default() -> Self {
+        Self::new(DEFAULT_CHANNEL_CAPACITY, DEFAULT_POLL_INTERVAL)
+    }
+}
+
+#[cfg(feature = "http-api")]
+impl LogBroker {
+    /// `capacity` lines are kept per log for subscribers that fall behind
+    pub fn new(capacity: usize, poll_interval: Duration) -> Self {
+        Self {
+            state: Arc::new(BrokerState {
+                tailers: Mutex::new(HashMap::new()),
+                capacity: capacity.max(1),
+                poll_interval,
+            }),
+        }
+    }
+
+    /// Follow `path` for `process` from its current end. The first subscriber starts the
+    /// tailer; dropping the last subscription stops it.
+    pub fn subscribe(&self, process: &str, path: &Path) -> LogSubscription {
+        let key = (process.to_string(), path.to_path_buf());
+        let mut tailers = self.state.tailers.lock().unwrap();
+        let tailer = tailers.entry(key.clone()).or_insert_with(|| {
+            let (sender, _) = broadcast::channel(self.state.capacity);
+            let task = tokio::spawn(tail_file(path.to_path_buf(), sender.clone(), self.state.poll_interval));
+            Tailer { sender, subscribers: 0, task }
+        });
+        tailer.subscribers += 1;
+        LogSubscription {
+            receiver: tailer.sender.subscribe(),
+            key,
+            state: self.state.clone(),
+        }
+    }
+
+    /// Number of tailer tasks currently running
+    pub fn tailer_count(&self) -> usize {
+        self.state.tailers.lock().unwrap().len()
+    }
+
+    /// Subscribers per followed log, sorted by process and path
+    pub fn subscriber_counts(&self) -> Vec<LogSubscriberCount> {
+        let tailers = self.state.tailers.lock().unwrap();
+        let mut counts: Vec<LogSubscriberCount> = tailers
+            .iter()
+            .map(|((process, path), tailer)| LogSubscriberCount {
+                process: process.clone(),
+                path: path.to_string_lossy().to_string(),
+                subscribers: tailer.subscribers,
+            })
+            .collect();
+        counts.sort_by(|a, b| (&a.process, &a.path).cmp(&(&b.process, &b.path)));
+        counts
+    }
+}
+
+/// One client's view of a followed log
+#[cfg(feature = "http-api")]
+pub struct LogSubscription {
+    receiver: broadcast::Receiver<String>,
+    key: TailKey,
+    state: Arc<BrokerState>,
+}
+
+#[cfg(feature = "http-api")]
+impl LogSubscription {
+    /// Next line, or a notice of how many were skipped because this subscriber lagged.
+    /// None once the tailer is gone.
+    pub async fn next(&mut self) -> Option<LogFrame> {
+        match self.receiver.recv().await {
+            Ok(line) => Some(LogFrame::Line(line)),
+            Err(broadcast::error::RecvError::Lagged(count)) => Some(LogFrame::Skipped(count)),
+            Err(broadcast::error::RecvError::Closed) => None,
+        }
+    }
+}
+
+#[cfg(feature = "http-api")]
+impl Drop for LogSubscription {
+    fn drop(&mut self) {
+        let mut tailers = self.state.tailers.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(tailer) = tailers.get_mut(&self.key) {
+            tailer.subscribers -= 1;
+            if tailer.subscribers == 0 {
+                tailer.task.abort();
+                tailers.remove(&self.key);
+            }
+        }
+    }
+}
+
+/// Publish complete lines appended to `path`, starting from its current end. A file that
+/// shrinks (truncated or rotated away) is read again from the start.
+#[cfg(feature = "http-api")]
+async fn tail_file(path: PathBuf, sender: broadcast::Sender<String>, poll_interval: Duration) {
+    let mut offset = tokio::fs::metadata(&path).await.map(|m| m.len()).unwrap_or(0);
+    let mut partial = Vec::new();
+    loop {
+        tokio::time::sleep(poll_interval).await;
+        let Ok(len) = tokio::fs::metadata(&path).await.map(|m| m.len()) else {
+            continue;
+        };
+        if len < offset {
+            offset = 0;
+            partial.clear();
+        }
+        if len == offset {
+            continue;
+        }
+
+        let Ok(mut file) = tokio::fs::File::open(&path).await else {
+            continue;
+        };
+        if file.seek(std::io::SeekFrom::Start(offset)).await.is_err() {
+            continue;
+        }
+        let mut chunk = Vec::new();
+        let Ok(read) = file.take(len - offset).read_to_end(&mut chunk).await else {
+            continue;
+        };
+        offset += read as u64;
+        partial.extend_from_slice(&chunk);
+
+        // Only whole lines are published; the rest waits for its newline
+        while let Some(end) = partial.iter().position(|byte| *byte == b'\n') {
+            let line: Vec<u8> = partial.drain(..=end).collect();
+            let line = String::from_utf8_lossy(&line[..end]).trim_end_matches('\r').to_string();
+            // Nobody listening between the last unsubscribe and the abort is fine
+            let _ = sender.send(line);
+        }
+    }
+}
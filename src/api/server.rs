@@ -1,42 +1,124 @@
 #[cfg(feature = "http-api")]
 use crate::{
-    api::{auth::AuthManager, handlers::*, docs::ApiDoc},
+    api::{access_log::{log_access, AccessLog}, auth::AuthManager, handlers::*, docs::ApiDoc, log_broker::LogBroker},
+    cancel::Cancellation,
+    file_watch,
     process::ProcessManager,
     Error, Result,
 };
 #[cfg(feature = "http-api")]
 use axum::{
-    routing::{delete, get, post, put},
+    http::HeaderValue,
+    middleware,
+    response::Response,
+    routing::{any, delete, get, post, put, MethodRouter},
     Router,
 };
 #[cfg(feature = "http-api")]
+use std::net::SocketAddr;
+#[cfg(feature = "http-api")]
 use std::sync::{Arc, Mutex};
 #[cfg(feature = "http-api")]
+use std::time::Instant;
+#[cfg(feature = "http-api")]
 use tower::ServiceBuilder;
 #[cfg(feature = "http-api")]
 use tower_http::{cors::CorsLayer, trace::TraceLayer};
 #[cfg(feature = "http-api")]
 use utoipa_swagger_ui::SwaggerUi;
 
+/// Response header naming the server's database, see [`crate::config::ApiConfig::database_header`]
+#[cfg(feature = "http-api")]
+pub const DATABASE_HEADER: &str = "x-pmr-database";
+
+/// Paths served without a token, besides the API docs when they do not require one
+#[cfg(feature = "http-api")]
+pub const PUBLIC_PATHS: [&str; 2] = ["/healthz", "/readyz"];
+
+/// Paths of the Swagger UI and the OpenAPI document, when the docs are enabled
+#[cfg(feature = "http-api")]
+const DOCS_PATHS: [&str; 2] = ["/swagger-ui/", "/api-docs/openapi.json"];
+
+/// A path the server's router serves, as registered (`:name` for path parameters)
+#[cfg(feature = "http-api")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegisteredRoute {
+    pub path: String,
+    /// Served without a token
+    pub public: bool,
+}
+
+/// A router that remembers the paths registered on it, so they can be listed
+#[cfg(feature = "http-api")]
+struct RouteRegistry<S> {
+    router: Router<S>,
+    paths: Vec<&'static str>,
+}
+
+#[cfg(feature = "http-api")]
+impl<S: Clone + Send + Sync + 'static> RouteRegistry<S> {
+    fn new() -> Self {
+        Self { router: Router::new(), paths: Vec::new() }
+    }
+
+    fn route(mut self, path: &'static str, method_router: MethodRouter<S>) -> Self {
+        self.router = self.router.route(path, method_router);
+        if !self.paths.contains(&path) {
+            self.paths.push(path);
+        }
+        self
+    }
+}
+
 #[cfg(feature = "http-api")]
 pub struct ApiServer {
     process_manager: Arc<ProcessManager>,
     auth_manager: Arc<Mutex<AuthManager>>,
     port: u16,
+    /// Only GET routes are served; everything else gets 405
+    read_only: bool,
+    /// Shares one tailer per followed log among all streaming clients
+    log_broker: LogBroker,
+    access_log: AccessLog,
+    /// When the server was created, for the uptime `/healthz` reports
+    started: Instant,
 }
 
 #[cfg(feature = "http-api")]
 impl ApiServer {
+    /// Server for `process_manager`; a read-only manager gets a read-only server
     pub fn new(process_manager: ProcessManager, port: u16) -> Result<Self> {
+        let read_only = process_manager.is_read_only();
+        Self::build(process_manager, port, read_only)
+    }
+
+    /// Server that registers only the GET routes, whatever the manager allows.
+    /// Pair it with [`ProcessManager::read_only`] so nothing can write to the database.
+    pub fn new_read_only(process_manager: ProcessManager, port: u16) -> Result<Self> {
+        Self::build(process_manager, port, true)
+    }
+
+    fn build(process_manager: ProcessManager, port: u16, read_only: bool) -> Result<Self> {
         let database = process_manager.get_database();
+        let config = process_manager.config();
+        // Shares the manager's rotator, so a config reload applies to the access log too
+        let access_log = AccessLog::new(config.access_log_path(), process_manager.log_rotator(), database.clone());
         let auth_manager = AuthManager::new(database);
         Ok(Self {
             process_manager: Arc::new(process_manager),
             auth_manager: Arc::new(Mutex::new(auth_manager)),
             port,
+            read_only,
+            log_broker: LogBroker::default(),
+            access_log,
+            started: Instant::now(),
         })
     }
 
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
     pub fn get_auth_manager(&self) -> Arc<Mutex<AuthManager>> {
         self.auth_manager.clone()
     }
@@ -49,46 +131,240 @@ impl ApiServer {
             .map_err(|e| Error::Other(format!("Failed to bind to port {}: {}", self.port, e)))?;
 
         println!("PMR HTTP API server starting on port {}", self.port);
+        if self.read_only {
+            println!("Read-only mode: only GET requests are served, all others get 405");
+        }
         println!("API endpoints:");
         println!("  GET    /api/processes           - List all processes");
-        println!("  POST   /api/processes           - Start a new process");
+        if !self.read_only {
+            println!("  POST   /api/processes           - Start a new process");
+            println!("  POST   /api/processes/bulk      - Start multiple processes");
+        }
         println!("  GET    /api/processes/{{name}}   - Get process status");
-        println!("  PUT    /api/processes/{{name}}/stop    - Stop a process");
-        println!("  PUT    /api/processes/{{name}}/restart - Restart a process");
-        println!("  DELETE /api/processes/{{name}}   - Delete a process");
+        if !self.read_only {
+            println!("  PUT    /api/processes/{{name}}/stop    - Stop a process");
+            println!("  PUT    /api/processes/{{name}}/restart - Restart a process");
+            println!("  DELETE /api/processes/{{name}}   - Delete a process");
+        }
         println!("  GET    /api/processes/{{name}}/logs    - Get process logs");
+        println!("  GET    /api/processes/{{name}}/logs/rotated - List rotated log files");
+        println!("  GET    /api/processes/{{name}}/logs/archive - Download logs as a tar archive");
+        println!("  GET    /api/processes/{{name}}/logs/follow  - Stream new log lines (WebSocket)");
+        println!("  GET    /api/processes/{{name}}/inspect - Everything about a process (admin tokens only)");
+        println!("  GET    /api/metrics             - Server metrics");
+        if !self.read_only {
+            println!("  POST   /api/admin/reload        - Re-read the config file (also on SIGHUP)");
+        }
+        println!("  GET    /healthz                 - Health check (no token needed)");
+        println!("  GET    /readyz                  - Readiness: database and log directory checks (no token needed)");
         println!();
-        println!("API Documentation:");
-        println!("  Swagger UI: http://localhost:{}/swagger-ui/", self.port);
-        println!("  OpenAPI JSON: http://localhost:{}/api-docs/openapi.json", self.port);
+        println!("Access log: {}", self.access_log.path().display());
+        let api = &self.process_manager.config().api;
+        if api.docs_enabled {
+            println!();
+            println!("API Documentation{}:", if api.docs_require_auth { " (token required)" } else { "" });
+            println!("  Swagger UI: http://localhost:{}/swagger-ui/", self.port);
+            println!("  OpenAPI JSON: http://localhost:{}/api-docs/openapi.json", self.port);
+        }
 
-        axum::serve(listener, app)
+        let reloader = (!self.read_only).then(|| tokio::spawn(reload_on_sighup(self.process_manager.clone())));
+        // Processes started with --watch restart on changes for as long as the server runs
+        let watching = Cancellation::new();
+        let watcher = (!self.read_only).then(|| tokio::spawn(file_watch::supervise(self.process_manager.clone(), watching.clone())));
+        let served = axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
+            .with_graceful_shutdown(shutdown_signal())
             .await
-            .map_err(|e| Error::Other(format!("Server error: {}", e)))?;
+            .map_err(|e| Error::Other(format!("Server error: {}", e)));
+        if let Some(reloader) = reloader {
+            reloader.abort();
+        }
+        // Let a restart under way finish rather than leave its record deleted
+        watching.cancel();
+        if let Some(watcher) = watcher {
+            let _ = watcher.await;
+        }
+        served?;
 
+        // Requests have drained; stop the manager's background work before exiting
+        self.process_manager.shutdown().await;
         Ok(())
     }
 
-    fn create_router(&self) -> Router {
-        let api_routes = Router::new()
-            .route("/processes", get(list_processes))
-            .route("/processes", post(start_process))
-            .route("/processes/:name", get(get_process_status))
-            .route("/processes/:name/stop", put(stop_process))
-            .route("/processes/:name/restart", put(restart_process))
-            .route("/processes/:name", delete(delete_process))
-            .route("/processes/:name/logs", get(get_process_logs))
-            .with_state((self.process_manager.clone(), self.auth_manager.clone()));
-
-        Router::new()
+    pub fn create_router(&self) -> Router {
+        self.build_router().0
+    }
+
+    /// Every path the router of [`Self::create_router`] serves, so audits can walk them all:
+    /// only those marked public may answer without a token
+    pub fn routes(&self) -> Vec<RegisteredRoute> {
+        self.build_router().1
+    }
+
+    /// The router, with the paths registered on it
+    fn build_router(&self) -> (Router, Vec<RegisteredRoute>) {
+        let api_routes = if self.read_only {
+            RouteRegistry::new()
+                .route("/processes", get(list_processes).fallback(reject_read_only))
+                .route("/processes/names", get(list_process_names).fallback(reject_read_only))
+                .route("/processes/bulk", any(reject_read_only))
+                .route("/processes/:name", get(get_process_status).fallback(reject_read_only))
+                .route("/processes/:name/exists", get(check_process_name).fallback(reject_read_only))
+                .route("/processes/:name/reserve", any(reject_read_only))
+                .route("/processes/:name/stop", any(reject_read_only))
+                .route("/processes/:name/restart", any(reject_read_only))
+                .route("/processes/:name/logs", get(get_process_logs).fallback(reject_read_only))
+                .route("/processes/:name/logs/rotated", get(list_rotated_logs).fallback(reject_read_only))
+                .route("/processes/:name/logs/archive", get(get_process_logs_archive).fallback(reject_read_only))
+                .route("/processes/:name/notes", get(list_process_notes).fallback(reject_read_only))
+                .route("/processes/:name/notes/:note_id", any(reject_read_only))
+                .route("/processes/:name/inspect", get(inspect_process).fallback(reject_read_only))
+                .route("/stats", get(get_stats).fallback(reject_read_only))
+                .route("/admin/reload", any(reject_read_only))
+        } else {
+            RouteRegistry::new()
+                .route("/processes", get(list_processes))
+                .route("/processes", post(start_process))
+                .route("/processes/names", get(list_process_names))
+                .route("/processes/bulk", post(bulk_start_processes))
+                .route("/processes/:name", get(get_process_status))
+                .route("/processes/:name/exists", get(check_process_name))
+                .route("/processes/:name/reserve", post(reserve_process_name))
+                .route("/processes/:name/stop", put(stop_process))
+                .route("/processes/:name/restart", put(restart_process))
+                .route("/processes/:name", delete(delete_process))
+                .route("/processes/:name/logs", get(get_process_logs))
+                .route("/processes/:name/logs/rotated", get(list_rotated_logs))
+                .route("/processes/:name/logs/archive", get(get_process_logs_archive))
+                .route("/processes/:name/notes", get(list_process_notes).post(add_process_note))
+                .route("/processes/:name/notes/:note_id", delete(delete_process_note))
+                .route("/processes/:name/inspect", get(inspect_process))
+                .route("/stats", get(get_stats))
+                .route("/admin/reload", post(reload_config))
+        };
+
+        // Streaming routes also need the broker; both are reads, so read-only servers keep them
+        let stream_routes = RouteRegistry::new()
+            .route("/processes/:name/logs/follow", get(follow_process_logs))
+            .route("/metrics", get(get_metrics));
+        let mut routes: Vec<RegisteredRoute> = api_routes.paths.iter().chain(&stream_routes.paths)
+            .map(|path| RegisteredRoute { path: format!("/api{}", path), public: false })
+            .collect();
+        let stream_router = if self.read_only { stream_routes.router.fallback(reject_read_only) } else { stream_routes.router }
+            .with_state((self.process_manager.clone(), self.auth_manager.clone(), self.log_broker.clone()));
+        // Deny by default: nothing under /api reaches a handler without a valid token, so a
+        // new route cannot forget to check it. Handlers still check scopes such as admin.
+        let api_routes = api_routes.router
+            .with_state((self.process_manager.clone(), self.auth_manager.clone()))
+            .merge(stream_router)
+            .layer(middleware::from_fn_with_state(self.auth_manager.clone(), require_token));
+        routes.extend(PUBLIC_PATHS.iter().map(|path| RegisteredRoute { path: path.to_string(), public: true }));
+        let api = &self.process_manager.config().api;
+        if api.docs_enabled {
+            routes.extend(DOCS_PATHS.iter().map(|path| RegisteredRoute { path: path.to_string(), public: !api.docs_require_auth }));
+        }
+
+        let (read_only, started) = (self.read_only, self.started);
+        let router = Router::new()
             .nest("/api", api_routes)
-            .merge(SwaggerUi::new("/swagger-ui").url("/api-docs/openapi.json", ApiDoc::get_openapi()))
+            .route("/healthz", get(move || health_check(read_only, started)))
+            .route("/readyz", get(readiness_check).with_state(self.process_manager.clone()))
+            .merge(self.docs_routes())
             .layer(
                 ServiceBuilder::new()
                     .layer(TraceLayer::new_for_http())
+                    .layer(middleware::from_fn_with_state(self.access_log.clone(), log_access))
                     .layer(CorsLayer::permissive()),
-            )
+            );
+        let router = match self.database_header() {
+            Some(database) => router.layer(middleware::map_response(move |mut response: Response| {
+                let database = database.clone();
+                async move {
+                    response.headers_mut().insert(DATABASE_HEADER, database);
+                    response
+                }
+            })),
+            None => router,
+        };
+        (router, routes)
+    }
+
+    /// The Swagger UI and OpenAPI document as [`ApiConfig::docs_enabled`] and
+    /// [`ApiConfig::docs_require_auth`] ask; nothing at all when the docs are off, so their
+    /// paths fall through to the usual 404
+    ///
+    /// [`ApiConfig::docs_enabled`]: crate::config::ApiConfig::docs_enabled
+    /// [`ApiConfig::docs_require_auth`]: crate::config::ApiConfig::docs_require_auth
+    fn docs_routes(&self) -> Router {
+        let api = &self.process_manager.config().api;
+        if !api.docs_enabled {
+            return Router::new();
+        }
+        let docs = Router::from(SwaggerUi::new("/swagger-ui").url("/api-docs/openapi.json", ApiDoc::get_openapi()));
+        if api.docs_require_auth {
+            docs.route_layer(middleware::from_fn_with_state(self.auth_manager.clone(), require_token))
+        } else {
+            docs
+        }
+    }
+
+    /// Value of the `X-PMR-Database` header, when [`ApiConfig::database_header`] asks for it
+    ///
+    /// [`ApiConfig::database_header`]: crate::config::ApiConfig::database_header
+    fn database_header(&self) -> Option<HeaderValue> {
+        let config = self.process_manager.config();
+        if !config.api.database_header {
+            return None;
+        }
+        let database = config.paths().database;
+        match HeaderValue::from_str(&database.to_string_lossy()) {
+            Ok(value) => Some(value),
+            Err(_) => {
+                tracing::warn!(path = %database.display(), "database path cannot be sent in a header");
+                None
+            }
+        }
+    }
+}
+
+/// Reload the config file on every SIGHUP, like `POST /api/admin/reload`
+#[cfg(feature = "http-api")]
+async fn reload_on_sighup(process_manager: Arc<ProcessManager>) {
+    let mut sighup = match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup()) {
+        Ok(sighup) => sighup,
+        Err(e) => {
+            eprintln!("Warning: cannot listen for SIGHUP, config reload only via the API: {}", e);
+            return;
+        }
+    };
+    while sighup.recv().await.is_some() {
+        match process_manager.reload_config().await {
+            Ok(outcome) => {
+                let changed = if outcome.changed.is_empty() { "nothing".to_string() } else { outcome.changed.join(", ") };
+                println!("Reloaded {}: changed {}", outcome.config_file.display(), changed);
+                for warning in outcome.warnings {
+                    eprintln!("Warning: config {}", warning);
+                }
+            }
+            Err(e) => eprintln!("Config reload failed, keeping the current configuration: {}", e),
+        }
     }
 }
 
+/// Resolve on Ctrl+C or SIGTERM
+#[cfg(feature = "http-api")]
+async fn shutdown_signal() {
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                sigterm.recv().await;
+            }
+            Err(_) => std::future::pending::<()>().await,
+        }
+    };
 
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {}
+        _ = terminate => {}
+    }
+    println!("Shutting down PMR HTTP API server...");
+}
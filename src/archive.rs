@@ -0,0 +1,234 @@
+use crate::{Error, Result};
+use std::path::{Path, PathBuf};
+use tokio::io::{AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+const BLOCK_SIZE: usize = 512;
+const COPY_BUFFER_SIZE: usize = 64 * 1024;
+
+/// A file to include in a log archive
+#[derive(Debug, Clone)]
+pub struct ArchiveEntry {
+    /// Path of the entry inside the archive
+    pub archive_name: String,
+    /// Path of the file on disk
+    pub source: PathBuf,
+}
+
+/// Build the default archive file name, e.g. `web-logs-20250101T120000Z.tar`
+pub fn archive_file_name(process_name: &str, timestamp: chrono::DateTime<chrono::Utc>) -> String {
+    format!("{}-logs-{}.tar", process_name, timestamp.format("%Y%m%dT%H%M%SZ"))
+}
+
+/// Stream the given files into `writer` as an uncompressed tar archive.
+///
+/// Files are copied in fixed-size chunks so memory use does not depend on log size.
+/// Files that cannot be opened are skipped; a `MANIFEST.txt` entry written last
+/// lists what was included and what was skipped.
+pub async fn write_tar_archive<W>(writer: &mut W, manifest_dir: &str, entries: &[ArchiveEntry]) -> Result<()>
+where
+    W: AsyncWrite + Unpin,
+{
+    let mut manifest = String::new();
+    let mut buffer = vec![0u8; COPY_BUFFER_SIZE];
+
+    for entry in entries {
+        let mut file = match tokio::fs::File::open(&entry.source).await {
+            Ok(file) => file,
+            Err(e) => {
+                manifest.push_str(&format!("skipped {} ({})\n", entry.archive_name, e));
+                continue;
+            }
+        };
+        let metadata = file.metadata().await?;
+        let size = metadata.len();
+        let mtime = metadata
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+
+        writer.write_all(&tar_header(&entry.archive_name, size, mtime)?).await?;
+
+        // The file may grow or shrink while it is copied; write exactly `size` bytes
+        let mut remaining = size;
+        while remaining > 0 {
+            let want = remaining.min(buffer.len() as u64) as usize;
+            let read = file.read(&mut buffer[..want]).await?;
+            if read == 0 {
+                break;
+            }
+            writer.write_all(&buffer[..read]).await?;
+            remaining -= read as u64;
+        }
+        write_zeros(writer, remaining).await?;
+        write_zeros(writer, padding(size)).await?;
+
+        manifest.push_str(&format!("included {} ({} bytes)\n", entry.archive_name, size));
+    }
+
+    let manifest_name = format!("{}/MANIFEST.txt", manifest_dir);
+    let now = chrono::Utc::now().timestamp().max(0) as u64;
+    writer.write_all(&tar_header(&manifest_name, manifest.len() as u64, now)?).await?;
+    writer.write_all(manifest.as_bytes()).await?;
+    write_zeros(writer, padding(manifest.len() as u64)).await?;
+
+    // End of archive: two empty blocks
+    write_zeros(writer, (BLOCK_SIZE * 2) as u64).await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+/// Write a tar archive to a file on disk, readable by the owner only when it is created:
+/// logs can hold secrets
+pub async fn write_tar_archive_to_path(path: &Path, manifest_dir: &str, entries: &[ArchiveEntry]) -> Result<()> {
+    let options = crate::permissions::private_file_options();
+    let mut file = tokio::fs::OpenOptions::from(options).write(true).create(true).truncate(true).open(path).await?;
+    write_tar_archive(&mut file, manifest_dir, entries).await
+}
+
+fn padding(size: u64) -> u64 {
+    let remainder = size % BLOCK_SIZE as u64;
+    if remainder == 0 {
+        0
+    } else {
+        BLOCK_SIZE as u64 - remainder
+    }
+}
+
+async fn write_zeros<W: AsyncWrite + Unpin>(writer: &mut W, mut count: u64) -> Result<()> {
+    let zeros = [0u8; BLOCK_SIZE];
+    while count > 0 {
+        let chunk = count.min(BLOCK_SIZE as u64) as usize;
+        writer.write_all(&zeros[..chunk]).await?;
+        count -= chunk as u64;
+    }
+    Ok(())
+}
+
+/// Build a ustar header block for a regular file
+fn tar_header(name: &str, size: u64, mtime: u64) -> Result<[u8; BLOCK_SIZE]> {
+    let mut header = [0u8; BLOCK_SIZE];
+
+    // Long names are split into the prefix (155 bytes) and name (100 bytes) fields
+    let (prefix, file_name) = if name.len() <= 100 {
+        ("", name)
+    } else {
+        match name.rsplit_once('/') {
+            Some((prefix, file_name)) if prefix.len() <= 155 && file_name.len() <= 100 => (prefix, file_name),
+            _ => return Err(Error::Other(format!("Archive entry name too long: {}", name))),
+        }
+    };
+
+    header[..file_name.len()].copy_from_slice(file_name.as_bytes());
+    write_octal(&mut header[100..108], 0o644);
+    write_octal(&mut header[108..116], 0);
+    write_octal(&mut header[116..124], 0);
+    write_octal(&mut header[124..136], size);
+    write_octal(&mut header[136..148], mtime);
+    header[156] = b'0';
+    header[257..263].copy_from_slice(b"ustar\0");
+    header[263..265].copy_from_slice(b"00");
+    header[345..345 + prefix.len()].copy_from_slice(prefix.as_bytes());
+
+    // The checksum is computed with the checksum field filled with spaces
+    header[148..156].copy_from_slice(b"        ");
+    let checksum: u32 = header.iter().map(|b| *b as u32).sum();
+    let checksum = format!("{:06o}\0 ", checksum);
+    header[148..156].copy_from_slice(checksum.as_bytes());
+
+    Ok(header)
+}
+
+/// Write a zero-padded, NUL-terminated octal number filling the field
+fn write_octal(field: &mut [u8], value: u64) {
+    let digits = field.len() - 1;
+    let formatted = format!("{:0width$o}", value, width = digits);
+    field[..digits].copy_from_slice(&formatted.as_bytes()[formatted.len() - digits..]);
+    field[digits] = 0;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    /// Minimal reader returning (name, content) pairs from a tar archive
+    fn read_entries(archive: &[u8]) -> Vec<(String, Vec<u8>)> {
+        let mut entries = Vec::new();
+        let mut offset = 0;
+        while offset + BLOCK_SIZE <= archive.len() {
+            let header = &archive[offset..offset + BLOCK_SIZE];
+            if header.iter().all(|b| *b == 0) {
+                break;
+            }
+            let field = |range: std::ops::Range<usize>| {
+                String::from_utf8_lossy(&header[range]).trim_end_matches('\0').to_string()
+            };
+            let prefix = field(345..500);
+            let name = field(0..100);
+            let name = if prefix.is_empty() { name } else { format!("{}/{}", prefix, name) };
+            let size = u64::from_str_radix(field(124..135).trim(), 8).unwrap() as usize;
+            offset += BLOCK_SIZE;
+            entries.push((name, archive[offset..offset + size].to_vec()));
+            offset += size + padding(size as u64) as usize;
+        }
+        entries
+    }
+
+    #[tokio::test]
+    async fn test_archive_contains_files_and_manifest() {
+        let temp_dir = TempDir::new().unwrap();
+        let live = temp_dir.path().join("web.log");
+        let rotated = temp_dir.path().join("web.1.log");
+        std::fs::write(&live, "live output\n").unwrap();
+        std::fs::write(&rotated, vec![b'x'; 1000]).unwrap();
+
+        let entries = vec![
+            ArchiveEntry { archive_name: "web/web.log".to_string(), source: live },
+            ArchiveEntry { archive_name: "web/web.1.log".to_string(), source: rotated },
+            ArchiveEntry { archive_name: "web/web.2.log".to_string(), source: temp_dir.path().join("web.2.log") },
+        ];
+
+        let mut archive = Vec::new();
+        write_tar_archive(&mut archive, "web", &entries).await.unwrap();
+        assert_eq!(archive.len() % BLOCK_SIZE, 0);
+
+        let read = read_entries(&archive);
+        assert_eq!(read.len(), 3);
+        assert_eq!(read[0].0, "web/web.log");
+        assert_eq!(read[0].1, b"live output\n");
+        assert_eq!(read[1].1.len(), 1000);
+
+        let manifest = String::from_utf8(read[2].1.clone()).unwrap();
+        assert_eq!(read[2].0, "web/MANIFEST.txt");
+        assert!(manifest.contains("included web/web.log (12 bytes)"));
+        assert!(manifest.contains("skipped web/web.2.log"));
+
+        // Written to disk, only its owner can read it
+        let path = temp_dir.path().join("web-logs.tar");
+        write_tar_archive_to_path(&path, "web", &entries).await.unwrap();
+        let mode = std::os::unix::fs::PermissionsExt::mode(&std::fs::metadata(&path).unwrap().permissions());
+        assert_eq!((mode & 0o777, std::fs::read(&path).unwrap().len()), (0o600, archive.len()));
+    }
+
+    #[test]
+    fn test_header_checksum_and_long_names() {
+        let header = tar_header("proc/proc.log", 5, 0).unwrap();
+        let stored = u32::from_str_radix(String::from_utf8_lossy(&header[148..154]).as_ref(), 8).unwrap();
+        let mut copy = header;
+        copy[148..156].copy_from_slice(b"        ");
+        assert_eq!(stored, copy.iter().map(|b| *b as u32).sum::<u32>());
+
+        let long_name = format!("{}/{}", "d".repeat(120), "file.log");
+        let header = tar_header(&long_name, 0, 0).unwrap();
+        assert_eq!(&header[..8], b"file.log");
+        assert!(tar_header(&"x".repeat(300), 0, 0).is_err());
+    }
+
+    #[test]
+    fn test_archive_file_name() {
+        let timestamp = chrono::DateTime::parse_from_rfc3339("2025-01-02T03:04:05Z").unwrap().with_timezone(&chrono::Utc);
+        assert_eq!(archive_file_name("web", timestamp), "web-logs-20250102T030405Z.tar");
+    }
+}
@@ -0,0 +1,177 @@
+//! `pmr batch`: many pmr commands in one invocation, sharing one database connection.
+//!
+//! Input is either a JSON array of argv arrays (`[["start", "web", "sleep", "60"], ["list"]]`)
+//! or one command per line, split the way a shell splits words: single quotes, double quotes
+//! and backslash escapes, without any expansion. Blank lines and lines starting with `#` are
+//! skipped, and a leading `pmr` is optional. Every command produces one [`BatchResult`].
+
+use crate::{Error, Result};
+use serde::{Deserialize, Serialize};
+
+/// One command of a batch, before clap has parsed it
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatchCommand {
+    /// Line number of the command, or its 1-based position in the JSON array
+    pub index: usize,
+    /// Arguments after the program name; Err when the line could not be split
+    pub argv: std::result::Result<Vec<String>, String>,
+}
+
+/// What one command of a batch did, printed as one line of JSON
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BatchResult {
+    /// [`BatchCommand::index`] of the command
+    pub index: usize,
+    /// Arguments after the program name; empty when the line could not be split
+    pub command: Vec<String>,
+    pub success: bool,
+    /// Exit status the command would have had as a pmr invocation of its own
+    pub exit_code: i32,
+    /// What the command printed to stdout
+    pub output: String,
+    /// What it printed to stderr, including the error that made it fail
+    pub stderr: String,
+}
+
+/// Split batch input into commands
+pub fn parse_batch(input: &str) -> Result<Vec<BatchCommand>> {
+    if input.trim_start().starts_with('[') {
+        let commands: Vec<Vec<String>> = serde_json::from_str(input)
+            .map_err(|e| Error::Other(format!("Batch input is not a JSON array of argv arrays: {}", e)))?;
+        return Ok(commands
+            .into_iter()
+            .enumerate()
+            .map(|(i, argv)| BatchCommand { index: i + 1, argv: Ok(strip_program(argv)) })
+            .collect());
+    }
+
+    Ok(input
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| {
+            let line = line.trim_start();
+            !line.is_empty() && !line.starts_with('#')
+        })
+        .map(|(i, line)| BatchCommand {
+            index: i + 1,
+            argv: split_command_line(line).map(strip_program),
+        })
+        .collect())
+}
+
+fn strip_program(mut argv: Vec<String>) -> Vec<String> {
+    if argv.first().is_some_and(|program| program == "pmr") {
+        argv.remove(0);
+    }
+    argv
+}
+
+/// Split a command line into words like a POSIX shell, without expanding anything
+pub fn split_command_line(line: &str) -> std::result::Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    // Distinguishes an empty quoted word ("") from no word at all
+    let mut in_word = false;
+    let mut chars = line.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            '\\' => match chars.next() {
+                Some(escaped) => {
+                    word.push(escaped);
+                    in_word = true;
+                }
+                None => return Err("trailing backslash".to_string()),
+            },
+            '\'' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => return Err("unterminated single quote".to_string()),
+                    }
+                }
+            }
+            '"' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        // Inside double quotes a backslash only escapes what would end or
+                        // expand the string
+                        Some('\\') => match chars.next() {
+                            Some(escaped @ ('"' | '\\' | '$' | '`')) => word.push(escaped),
+                            Some(other) => {
+                                word.push('\\');
+                                word.push(other);
+                            }
+                            None => return Err("unterminated double quote".to_string()),
+                        },
+                        Some(c) => word.push(c),
+                        None => return Err("unterminated double quote".to_string()),
+                    }
+                }
+            }
+            c => {
+                word.push(c);
+                in_word = true;
+            }
+        }
+    }
+    if in_word {
+        words.push(word);
+    }
+    Ok(words)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_command_line() {
+        assert_eq!(split_command_line("start web sleep 60").unwrap(), ["start", "web", "sleep", "60"]);
+        assert_eq!(split_command_line("  start   'my app'  \"a \\\"b\\\"\" ").unwrap(), ["start", "my app", "a \"b\""]);
+        assert_eq!(split_command_line(r#"start x echo "" a\ b "c\d""#).unwrap(), ["start", "x", "echo", "", "a b", "c\\d"]);
+        assert_eq!(split_command_line("logs 'it''s'").unwrap(), ["logs", "its"]);
+        assert!(split_command_line("").unwrap().is_empty());
+
+        assert_eq!(split_command_line("start 'x").unwrap_err(), "unterminated single quote");
+        assert_eq!(split_command_line("start \"x").unwrap_err(), "unterminated double quote");
+        assert_eq!(split_command_line("start x\\").unwrap_err(), "trailing backslash");
+    }
+
+    #[test]
+    fn test_parse_batch_lines() {
+        let commands = parse_batch("# setup\nstart web sleep 60\n\npmr status web\nlogs 'web\n").unwrap();
+        assert_eq!(
+            commands,
+            vec![
+                BatchCommand { index: 2, argv: Ok(vec!["start".into(), "web".into(), "sleep".into(), "60".into()]) },
+                BatchCommand { index: 4, argv: Ok(vec!["status".into(), "web".into()]) },
+                BatchCommand { index: 5, argv: Err("unterminated single quote".into()) },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_batch_json() {
+        let commands = parse_batch(r#" [["pmr", "list"], ["status", "my app"]]"#).unwrap();
+        assert_eq!(
+            commands,
+            vec![
+                BatchCommand { index: 1, argv: Ok(vec!["list".into()]) },
+                BatchCommand { index: 2, argv: Ok(vec!["status".into(), "my app".into()]) },
+            ]
+        );
+
+        assert!(parse_batch(r#"[["list"], "status"]"#).is_err());
+    }
+}
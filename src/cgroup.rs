@@ -0,0 +1,282 @@
+use crate::{Error, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Default mount point of the unified (v2) cgroup hierarchy
+pub const CGROUP_MOUNT: &str = "/sys/fs/cgroup";
+
+/// Sub-directory under the delegated hierarchy that holds all pmr cgroups
+const PMR_CGROUP_DIR: &str = "pmr";
+
+/// Scheduling period written to cpu.max, in microseconds
+const CPU_PERIOD_US: u64 = 100_000;
+
+/// Resource caps enforced through cgroup v2
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CgroupLimits {
+    /// Value for memory.max, in bytes
+    pub memory_max: Option<u64>,
+    /// CPU cap as a percentage of one core (200 = two cores)
+    pub cpu_max_percent: Option<u32>,
+}
+
+impl CgroupLimits {
+    pub fn is_empty(&self) -> bool {
+        self.memory_max.is_none() && self.cpu_max_percent.is_none()
+    }
+}
+
+/// Live usage read back from a process cgroup
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "http-api", derive(utoipa::ToSchema))]
+pub struct CgroupStats {
+    pub path: String,
+    pub memory_current: Option<u64>,
+    pub cpu_pressure: Option<String>,
+}
+
+/// A per-process cgroup located at `<root>/pmr/<name>`
+pub struct Cgroup {
+    root: PathBuf,
+    path: PathBuf,
+}
+
+impl Cgroup {
+    pub fn new(root: &Path, name: &str) -> Self {
+        Self {
+            root: root.to_path_buf(),
+            path: root.join(PMR_CGROUP_DIR).join(name),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Check whether `root` is a cgroup v2 hierarchy
+    pub fn is_available(root: &Path) -> bool {
+        root.join("cgroup.controllers").is_file()
+    }
+
+    /// Create the cgroup and write the requested limits
+    pub fn create(&self, limits: &CgroupLimits) -> Result<()> {
+        if !Self::is_available(&self.root) {
+            return Err(Error::Other(format!(
+                "cgroup v2 is not available at '{}'; cannot apply cgroup limits",
+                self.root.display()
+            )));
+        }
+
+        let mut controllers = Vec::new();
+        if limits.memory_max.is_some() {
+            controllers.push("+memory");
+        }
+        if limits.cpu_max_percent.is_some() {
+            controllers.push("+cpu");
+        }
+        let controllers = controllers.join(" ");
+
+        let pmr_dir = self.root.join(PMR_CGROUP_DIR);
+        fs::create_dir_all(&self.path).map_err(|e| {
+            Error::Other(format!("Failed to create cgroup '{}': {}", self.path.display(), e))
+        })?;
+
+        // Controllers must be enabled on every ancestor before the leaf can use them
+        if !controllers.is_empty() {
+            for dir in [&self.root, &pmr_dir] {
+                write_cgroup_file(&dir.join("cgroup.subtree_control"), &controllers)?;
+            }
+        }
+
+        if let Some(memory_max) = limits.memory_max {
+            write_cgroup_file(&self.path.join("memory.max"), &memory_max.to_string())?;
+        }
+        if let Some(percent) = limits.cpu_max_percent {
+            write_cgroup_file(&self.path.join("cpu.max"), &cpu_max_value(percent)?)?;
+        }
+
+        Ok(())
+    }
+
+    /// Move a process into this cgroup
+    pub fn add_process(&self, pid: u32) -> Result<()> {
+        write_cgroup_file(&self.path.join("cgroup.procs"), &pid.to_string())
+    }
+
+    /// Remove the cgroup directory if it exists
+    pub fn remove(&self) -> Result<()> {
+        match fs::remove_dir(&self.path) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(Error::Other(format!(
+                "Failed to remove cgroup '{}': {}",
+                self.path.display(),
+                e
+            ))),
+        }
+    }
+
+    /// Read current memory usage and CPU pressure
+    pub fn stats(&self) -> CgroupStats {
+        let memory_current = fs::read_to_string(self.path.join("memory.current"))
+            .ok()
+            .and_then(|s| s.trim().parse().ok());
+        let cpu_pressure = fs::read_to_string(self.path.join("cpu.pressure"))
+            .ok()
+            .and_then(|s| s.lines().next().map(|line| line.trim().to_string()));
+
+        CgroupStats {
+            path: self.path.to_string_lossy().to_string(),
+            memory_current,
+            cpu_pressure,
+        }
+    }
+}
+
+/// Build the cpu.max value ("<quota> <period>") for a percentage of one core
+pub fn cpu_max_value(percent: u32) -> Result<String> {
+    if percent == 0 {
+        return Err(Error::Other("CPU cap must be greater than 0%".to_string()));
+    }
+    let quota = CPU_PERIOD_US * percent as u64 / 100;
+    Ok(format!("{} {}", quota, CPU_PERIOD_US))
+}
+
+/// Locate the cgroup hierarchy pmr may write to.
+///
+/// Root can use the top of the hierarchy; other users need a delegated
+/// `user@<uid>.service` subtree, taken from the current process cgroup.
+pub fn default_root() -> PathBuf {
+    let mount = PathBuf::from(CGROUP_MOUNT);
+    if unsafe { libc::geteuid() } == 0 {
+        return mount;
+    }
+
+    fs::read_to_string("/proc/self/cgroup")
+        .ok()
+        .and_then(|content| delegated_root(&mount, &content))
+        .unwrap_or(mount)
+}
+
+/// Find the delegated user service subtree in a /proc/<pid>/cgroup listing
+fn delegated_root(mount: &Path, proc_cgroup: &str) -> Option<PathBuf> {
+    let unified = proc_cgroup
+        .lines()
+        .find_map(|line| line.strip_prefix("0::"))?;
+
+    let mut root = mount.to_path_buf();
+    for component in unified.split('/').filter(|c| !c.is_empty()) {
+        root.push(component);
+        if component.starts_with("user@") && component.ends_with(".service") {
+            return Some(root);
+        }
+    }
+    None
+}
+
+fn write_cgroup_file(path: &Path, value: &str) -> Result<()> {
+    fs::write(path, value).map_err(|e| {
+        Error::Other(format!("Failed to write '{}' to {}: {}", value, path.display(), e))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn fake_hierarchy() -> TempDir {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("cgroup.controllers"), "cpu memory pids").unwrap();
+        temp_dir
+    }
+
+    #[test]
+    fn test_cgroup_path_construction() {
+        let cgroup = Cgroup::new(Path::new("/sys/fs/cgroup"), "web");
+        assert_eq!(cgroup.path(), Path::new("/sys/fs/cgroup/pmr/web"));
+    }
+
+    #[test]
+    fn test_cpu_max_value() {
+        assert_eq!(cpu_max_value(50).unwrap(), "50000 100000");
+        assert_eq!(cpu_max_value(200).unwrap(), "200000 100000");
+        assert!(cpu_max_value(0).is_err());
+    }
+
+    #[test]
+    fn test_delegated_root() {
+        let mount = Path::new("/sys/fs/cgroup");
+        let listing = "0::/user.slice/user-1000.slice/user@1000.service/app.slice/term.scope\n";
+        assert_eq!(
+            delegated_root(mount, listing),
+            Some(PathBuf::from("/sys/fs/cgroup/user.slice/user-1000.slice/user@1000.service"))
+        );
+        assert_eq!(delegated_root(mount, "0::/system.slice/foo.service\n"), None);
+        assert_eq!(delegated_root(mount, "4:memory:/foo\n"), None);
+    }
+
+    #[test]
+    fn test_create_writes_limits() {
+        let root = fake_hierarchy();
+        let cgroup = Cgroup::new(root.path(), "capped");
+        let limits = CgroupLimits {
+            memory_max: Some(64 * 1024 * 1024),
+            cpu_max_percent: Some(25),
+        };
+
+        cgroup.create(&limits).unwrap();
+        cgroup.add_process(4242).unwrap();
+
+        let read = |file: &str| fs::read_to_string(cgroup.path().join(file)).unwrap();
+        assert_eq!(read("memory.max"), "67108864");
+        assert_eq!(read("cpu.max"), "25000 100000");
+        assert_eq!(read("cgroup.procs"), "4242");
+        assert_eq!(
+            fs::read_to_string(root.path().join("pmr/cgroup.subtree_control")).unwrap(),
+            "+memory +cpu"
+        );
+    }
+
+    #[test]
+    fn test_create_fails_without_cgroup_v2() {
+        let temp_dir = TempDir::new().unwrap();
+        let cgroup = Cgroup::new(temp_dir.path(), "capped");
+        let limits = CgroupLimits {
+            memory_max: Some(1024),
+            cpu_max_percent: None,
+        };
+
+        let err = cgroup.create(&limits).unwrap_err();
+        assert!(err.to_string().contains("cgroup v2 is not available"));
+        assert!(!cgroup.path().exists());
+    }
+
+    #[test]
+    fn test_stats_reads_usage_files() {
+        let root = fake_hierarchy();
+        let cgroup = Cgroup::new(root.path(), "usage");
+        fs::create_dir_all(cgroup.path()).unwrap();
+        fs::write(cgroup.path().join("memory.current"), "123456\n").unwrap();
+        fs::write(
+            cgroup.path().join("cpu.pressure"),
+            "some avg10=1.50 avg60=0.20 avg300=0.05 total=1234\nfull avg10=0.00 avg60=0.00 avg300=0.00 total=0\n",
+        )
+        .unwrap();
+
+        let stats = cgroup.stats();
+        assert_eq!(stats.memory_current, Some(123456));
+        assert_eq!(
+            stats.cpu_pressure.as_deref(),
+            Some("some avg10=1.50 avg60=0.20 avg300=0.05 total=1234")
+        );
+    }
+
+    #[test]
+    fn test_remove_missing_cgroup_is_ok() {
+        let root = fake_hierarchy();
+        let cgroup = Cgroup::new(root.path(), "missing");
+        assert!(cgroup.remove().is_ok());
+    }
+}
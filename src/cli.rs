@@ -1,19 +1,26 @@
+use crate::affinity::CpuSet;
+use crate::database::{LogDirMode, ProcessKind};
+use crate::duration::HumanDuration;
+use crate::log_index::LogSince;
+use crate::format::HumanBytes;
+use crate::env::{check_key, EnvVarError, EnvVarProblem};
+use crate::name::ProcessName;
+use crate::process::StatusRefresh;
+use crate::umask::Umask;
 use clap::{Parser, Subcommand, ValueEnum};
 use std::collections::HashMap;
+use std::path::PathBuf;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, ValueEnum, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, ValueEnum, Serialize, Deserialize)]
 pub enum OutputFormat {
     /// Plain text output (default)
+    #[default]
     Text,
     /// JSON formatted output
     Json,
-}
-
-impl Default for OutputFormat {
-    fn default() -> Self {
-        OutputFormat::Text
-    }
+    /// Stable tab-separated output of list and status for scripts
+    Porcelain,
 }
 
 #[cfg(feature = "http-api")]
@@ -23,15 +30,23 @@ pub enum AuthCommands {
     Generate {
         /// Token name/description
         name: String,
-        /// Token expiration in days (optional)
+        /// Token lifetime, e.g. 30m, 12h, 7d, 2w; a bare number means days (optional)
+        #[arg(long, value_name = "DURATION")]
+        expires_in: Option<HumanDuration>,
+        /// Also allow admin-only endpoints, such as inspect, which return unredacted environments
         #[arg(long)]
-        expires_in: Option<u32>,
+        admin: bool,
     },
     /// List all API tokens
     List,
+    /// Show one token's details, including time until it expires
+    Show {
+        /// Token name, public id (pmr_...) or a prefix of the token string
+        token: String,
+    },
     /// Revoke an API token
     Revoke {
-        /// Token to revoke
+        /// Token to revoke, or its public id as shown by `pmr auth list` (e.g. pmr_ab12cd34)
         token: String,
     },
 }
@@ -42,28 +57,131 @@ pub enum AuthCommands {
 #[command(version = "0.3.0")]
 pub struct Cli {
     /// Output format
+    ///
+    /// `porcelain` is a stable contract for scripts (version 1), unlike the text tables.
+    /// `list` prints one line per process: name, status, pid, exit_code, created_at
+    /// (epoch seconds), log_path, separated by tabs. `status` prints `key<TAB>value`
+    /// lines for name, status, pid, exit_code, command, working_dir, created_at,
+    /// updated_at and log_path. Missing values are empty, and backslash, tab, newline
+    /// and carriage return in values are written as \\, \t, \n and \r. Other commands
+    /// print their text output.
     #[arg(long, value_enum, default_value_t = OutputFormat::default())]
     pub format: OutputFormat,
 
+    /// Only print process names in `list` text output
+    #[arg(short, long, global = true)]
+    pub quiet: bool,
+
+    /// Leave out header and separator rows of text tables
+    #[arg(long, global = true)]
+    pub no_header: bool,
+
+    /// Print how long each internal operation took (to stderr) when the command finishes
+    #[arg(long, global = true)]
+    pub timings: bool,
+
+    /// Run database migrations that rebuild tables without asking (or set PMR_AUTO_MIGRATE=1)
+    #[arg(long, global = true)]
+    pub migrate: bool,
+
+    /// Go ahead with destructive commands (delete, clear) without asking (or set
+    /// PMR_ASSUME_YES=1). Nothing is asked when stdin is not a terminal either.
+    #[arg(short = 'y', long, global = true)]
+    pub yes: bool,
+
     #[command(subcommand)]
     pub command: Commands,
 }
 
 #[derive(Subcommand)]
+// Parsed once per run, so the size of `Start` costs nothing worth boxing it for
+#[allow(clippy::large_enum_variant)]
 pub enum Commands {
     /// Start a new process
     Start {
         /// Process name
-        name: String,
+        name: ProcessName,
         /// Environment variables (key=value format)
         #[arg(short, long)]
         env: Vec<String>,
+        /// Let a later `-e` for the same key replace the earlier one instead of failing
+        #[arg(long)]
+        env_allow_duplicates: bool,
         /// Working directory
         #[arg(short, long)]
         workdir: Option<String>,
         /// Log directory for this process (default: ./logs)
         #[arg(long)]
         log_dir: Option<String>,
+        /// `workdir` logs to <workdir>/logs, following the workdir on restart; `--log-dir`
+        /// wins over it (default: the configured mode)
+        #[arg(long, value_name = "MODE")]
+        log_dir_mode: Option<LogDirMode>,
+        /// cgroup v2 memory limit (memory.max): bytes, or a size like 512M or 2GB
+        #[arg(long, value_name = "SIZE")]
+        cgroup_memory_max: Option<HumanBytes>,
+        /// cgroup v2 CPU limit as a percentage of one core (cpu.max)
+        #[arg(long)]
+        cgroup_cpu_max: Option<u32>,
+        /// Pin the process to these CPUs, e.g. 0,2-3 (Linux only)
+        #[arg(long)]
+        cpu_affinity: Option<CpuSet>,
+        /// Umask of the process in octal, e.g. 027 or 0002 (default: inherit pmr's umask)
+        #[arg(long, value_name = "OCTAL")]
+        umask: Option<Umask>,
+        /// What the process is for, shown by `status` and `list --wide` (at most 256 characters)
+        #[arg(long, value_name = "TEXT")]
+        description: Option<String>,
+        /// Succeed if an identical process is already running; start it if it is stopped
+        #[arg(long)]
+        idempotent: bool,
+        /// Record the child's uid/gid, cwd, RSS and environment from /proc right after it starts
+        #[arg(long)]
+        snapshot: bool,
+        /// Report each phase of the start (validate, log, queue, spawn, wait, record) with its timing
+        #[arg(short, long)]
+        verbose: bool,
+        /// Fail if the process exits within the start window, leaving no record or log behind
+        #[arg(long)]
+        strict: bool,
+        /// `service` keeps running; a `job` runs once and ends succeeded (exit code 0) or failed
+        #[arg(long, value_name = "KIND", default_value = "service")]
+        kind: ProcessKind,
+        /// Run a job that already ran under this name again, archiving its previous log
+        #[arg(long)]
+        rerun: bool,
+        /// Signal `pmr stop` sends first unless given one (e.g. INT, QUIT or a number; default TERM)
+        #[arg(long, value_name = "SIG")]
+        stop_signal: Option<String>,
+        /// Delete the process and its logs once it has exited and the grace period is over
+        #[arg(long)]
+        ephemeral: bool,
+        /// Grace period before an exited ephemeral process is deleted, e.g. 30s or 2h (default: 10m)
+        #[arg(long, value_name = "DURATION", requires = "ephemeral")]
+        ephemeral_keep: Option<HumanDuration>,
+        /// Restart the process when this file or directory (watched recursively) changes; may
+        /// be repeated. `pmr serve` or `pmr watch` does the watching.
+        #[arg(long, value_name = "PATH")]
+        watch: Vec<String>,
+        /// Leave out paths matching this glob, besides .git/ and *.log; a trailing / matches
+        /// directories. May be repeated.
+        #[arg(long, value_name = "GLOB", requires = "watch")]
+        watch_ignore: Vec<String>,
+        /// Collect changes for this long into one restart (default: 500)
+        #[arg(long, value_name = "MS", requires = "watch")]
+        watch_debounce_ms: Option<u64>,
+        /// When the system is out of processes or memory (EAGAIN/ENOMEM), wait in the start
+        /// queue and retry instead of failing; see `[start_queue]` in the config file
+        #[arg(long)]
+        queue_on_pressure: bool,
+        /// Rotate an oversized log left by an earlier run before starting, even if
+        /// `rotate_logs_on_start` is off; restarts keep the choice
+        #[arg(long, conflicts_with = "no_rotate_on_start")]
+        rotate_on_start: bool,
+        /// Keep appending to the existing log at start, so followers and shippers holding it
+        /// keep the same file; rotation waits for `pmr rotate` or the size check
+        #[arg(long)]
+        no_rotate_on_start: bool,
         /// Command to execute
         command: String,
         /// Command arguments
@@ -73,44 +191,259 @@ pub enum Commands {
     /// Stop a running process
     Stop {
         /// Process name
-        name: String,
+        name: ProcessName,
+        /// Signal to send (e.g. TERM, INT, KILL or a number; default: the process's stop signal)
+        #[arg(short, long)]
+        signal: Option<String>,
+        /// Send SIGKILL if the process is still running after the grace period
+        #[arg(long)]
+        escalate: bool,
     },
     /// Restart a process
     Restart {
         /// Process name
-        name: String,
+        name: ProcessName,
+        /// Rotate the current log so the new run starts with an empty file
+        #[arg(long)]
+        reset_logs: bool,
+        /// Restart only if the binary changed since the process was started
+        #[arg(long)]
+        only_if_changed: bool,
+        /// Restart even if the process is flapping (failing repeatedly, see `doctor`)
+        #[arg(long)]
+        force: bool,
+    },
+    /// Change how a process is started from its next start (restart) on
+    Update {
+        /// Process name
+        name: ProcessName,
+        /// New working directory; in workdir log mode the log moves along with it
+        #[arg(short, long, required_unless_present = "description")]
+        workdir: Option<String>,
+        /// New description, taking effect at once; an empty one clears it
+        #[arg(long, value_name = "TEXT")]
+        description: Option<String>,
     },
-    /// Delete a process
+    /// Delete a process (it can be restored with `undelete` until it is purged)
     Delete {
         /// Process name
-        name: String,
+        name: ProcessName,
+        /// Remove the record and its log file permanently
+        #[arg(long)]
+        hard: bool,
+    },
+    /// Restore the most recently deleted process with this name
+    Undelete {
+        /// Process name
+        name: ProcessName,
     },
-    /// Clear stopped/failed processes or all processes
+    /// Check whether a process or an API reservation holds a name, reading only the
+    /// database; exits 0 if the name is taken and 2 if it is free
+    Exists {
+        /// Process name
+        name: ProcessName,
+    },
+    /// Clear stopped/failed processes and jobs that succeeded over a week ago, or all processes
     Clear {
         /// Clear all processes regardless of status
         #[arg(long)]
         all: bool,
+        /// Permanently remove deleted processes older than the retention period
+        #[arg(long, conflicts_with = "all")]
+        purge_deleted: bool,
     },
     /// List all processes
-    List,
+    List {
+        /// Print only the per-status summary
+        #[arg(long)]
+        counts_only: bool,
+        /// List only jobs, with their run count, last run, duration and result
+        #[arg(long, conflicts_with = "counts_only")]
+        jobs: bool,
+        /// `true` probes every PID, `false` shows stored statuses, `stale` probes only
+        /// those not verified in the last 2 seconds
+        #[arg(long, value_name = "MODE", default_value = "true", conflicts_with = "counts_only")]
+        refresh: StatusRefresh,
+        /// Print only the names, one per line, without checking any status (for completion)
+        #[arg(long, conflicts_with_all = ["counts_only", "jobs"])]
+        names_only: bool,
+        /// With --names-only, only the names starting with this
+        #[arg(long, value_name = "PREFIX", requires = "names_only")]
+        prefix: Option<String>,
+        /// Show long commands, arguments and environment values whole instead of cut off
+        #[arg(long)]
+        full: bool,
+        /// Only the processes whose name or description contains this, ignoring case
+        #[arg(long, value_name = "TEXT", conflicts_with_all = ["counts_only", "names_only"])]
+        search: Option<String>,
+        /// Add a DESCRIPTION column to the table
+        #[arg(long)]
+        wide: bool,
+        /// List the processes of every profile (`~/.pmr` and `~/.pmr/profiles/*`), each
+        /// database opened read-only and statuses shown as stored
+        #[arg(long, conflicts_with_all = ["counts_only", "jobs", "names_only", "search"])]
+        all_profiles: bool,
+        /// List the processes of every host sharing the Postgres storage (`[storage]`),
+        /// statuses as each host last stored them; this host's are refreshed as usual
+        #[arg(long, conflicts_with_all = ["counts_only", "jobs", "names_only", "search", "all_profiles"])]
+        all_hosts: bool,
+    },
+    /// Show how many processes are managed, the configured limit and where pmr keeps its files
+    Stats {
+        /// Print only the running, stopped and failed counts on one line, e.g.
+        /// `pmr: 12▲ 2■ 1✖`, for status bars; stored statuses, so it stays cheap
+        #[arg(long)]
+        oneline: bool,
+        /// Write the one-line summary in plain ASCII (`pmr: 12R 2S 1F`), which is also
+        /// the default when stdout is not a terminal
+        #[arg(long, requires = "oneline", conflicts_with = "unicode")]
+        ascii: bool,
+        /// Write the one-line summary with symbols even when stdout is not a terminal
+        #[arg(long, requires = "oneline")]
+        unicode: bool,
+        /// Keep refreshing every N seconds (default: 2) until Ctrl+C; with `--oneline` a
+        /// new line is printed each time
+        #[arg(long, value_name = "SECONDS", num_args = 0..=1, default_missing_value = "2")]
+        watch: Option<u64>,
+    },
+    /// Wait until the given processes are running, exiting non-zero on timeout.
+    /// Ctrl+C stops waiting and leaves every process running.
+    Wait {
+        /// Process names
+        #[arg(required = true)]
+        names: Vec<ProcessName>,
+        /// Give up after this many seconds
+        #[arg(long, default_value_t = 120)]
+        timeout: u64,
+    },
+    /// Restart a process started with `--watch` whenever its watched paths change, until
+    /// Ctrl+C. `pmr serve` does this for every such process.
+    Watch {
+        /// Process name
+        name: ProcessName,
+        /// List past restarts made because watched files changed, and exit
+        #[arg(long)]
+        history: bool,
+    },
     /// Show process status
     Status {
         /// Process name
-        name: String,
+        name: ProcessName,
+        /// Keep refreshing the view every N seconds (default: 2) until Ctrl+C
+        #[arg(long, value_name = "SECONDS", num_args = 0..=1, default_missing_value = "2")]
+        watch: Option<u64>,
+        /// Also compare the binary and working directory with those recorded at start
+        #[arg(long, conflicts_with = "watch")]
+        drift: bool,
+        /// Also show the /proc snapshot recorded at start (see `start --snapshot`)
+        #[arg(long, conflicts_with = "watch")]
+        snapshot: bool,
+        /// Show every note instead of only the latest
+        #[arg(long, conflicts_with = "watch")]
+        notes: bool,
+        /// `true` probes the PID, `false` shows the stored status, `stale` probes only if it
+        /// was not verified in the last 2 seconds
+        #[arg(long, value_name = "MODE", default_value = "true", conflicts_with = "watch")]
+        refresh: StatusRefresh,
+        /// Show long commands, arguments and environment values whole instead of cut off
+        #[arg(long)]
+        full: bool,
+    },
+    /// Print everything pmr knows about a process as one JSON document, for support
+    /// tickets: the stored record with its unredacted environment, liveness, resource
+    /// usage, logs, histories, notes and the pmr installation. Always JSON.
+    Inspect {
+        /// Process name
+        name: ProcessName,
+    },
+    /// Report processes that need attention: those flapping, i.e. failing repeatedly within
+    /// the configured window, and those whose PID is a zombie its parent has not reaped.
+    /// Zombies reparented to init are listed apart. Also lists the database, its directory
+    /// and the config file when other local users can read them. Exits 1 when anything
+    /// needs attention.
+    Doctor {
+        /// Restrict the files listed as readable by other users to their owner (0600, and
+        /// 0700 for directories)
+        #[arg(long)]
+        fix_permissions: bool,
+    },
+    /// Report processes whose binary or working directory changed since they were started
+    Drift {
+        /// Process name (default: all processes)
+        name: Option<ProcessName>,
     },
     /// Show process logs
     Logs {
         /// Process name
-        name: String,
-        /// Number of lines to show (default: all)
+        name: ProcessName,
+        /// Number of lines to show (default: all, 0 shows nothing, at most 10000)
         #[arg(short = 'n', long)]
         lines: Option<usize>,
         /// Show rotated log files
         #[arg(long)]
         rotated: bool,
+        /// Read the rotated files and the live log as one stream, oldest first
+        #[arg(long)]
+        merge_rotated: bool,
         /// Manually rotate log file
         #[arg(long)]
         rotate: bool,
+        /// List past rotations of the log, with sizes and deleted archives
+        #[arg(long)]
+        rotation_history: bool,
+        /// Write the live and rotated logs to a tar archive at this path
+        #[arg(long, value_name = "PATH")]
+        archive: Option<PathBuf>,
+        /// Show runs of identical consecutive lines once, with a repeat count (counted once by -n)
+        #[arg(long)]
+        collapse_repeats: bool,
+        /// Show the last SIZE bytes of the live log, e.g. 64K, starting at the next whole line
+        #[arg(long, value_name = "SIZE", conflicts_with_all = ["lines", "merge_rotated", "rotated", "rotate", "rotation_history", "archive"])]
+        tail_bytes: Option<HumanBytes>,
+        /// Keep the partial line a --tail-bytes cut lands in
+        #[arg(long, requires = "tail_bytes")]
+        allow_partial: bool,
+        /// Show the lines of the live log timed at or after TIME (RFC 3339) or within a duration such as 30m
+        #[arg(long, value_name = "TIME", conflicts_with_all = ["tail_bytes", "merge_rotated", "rotated", "rotate", "rotation_history", "archive"])]
+        since: Option<LogSince>,
+        /// Rebuild the index of the live log, which --since then seeks with
+        #[arg(long, conflicts_with_all = ["lines", "since", "tail_bytes", "merge_rotated", "rotated", "rotate", "rotation_history", "archive", "collapse_repeats"])]
+        reindex: bool,
+    },
+    /// Attach a timestamped note to a process (kept across restarts)
+    Annotate {
+        /// Process name
+        name: ProcessName,
+        /// Note text, at most 4096 bytes
+        text: String,
+    },
+    /// List the notes attached to a process
+    Notes {
+        /// Process name
+        name: ProcessName,
+        /// Delete the note with this id instead of listing
+        #[arg(long, value_name = "NOTE_ID")]
+        delete: Option<i64>,
+    },
+    /// Show the past runs of a process with how long each took, oldest first
+    History {
+        /// Process name
+        name: ProcessName,
+        /// List finished runs with their durations (the default, and so far the only history)
+        #[arg(long)]
+        runs: bool,
+    },
+    /// Run pmr commands read from stdin over one database connection
+    ///
+    /// Reads one command per line (shell-style quoting, `#` comments, optional leading
+    /// `pmr`) or a JSON array of argv arrays, runs them in order and prints one JSON
+    /// object per command: index, command, success, exit_code, output and stderr.
+    /// Exits 1 when any command failed. Commands that never finish on their own
+    /// (`serve`, `status --watch`, `stats --watch`, `serve-logs --follow`) are rejected.
+    Batch {
+        /// Stop at the first command that fails instead of running the rest
+        #[arg(long)]
+        fail_fast: bool,
     },
     #[cfg(feature = "http-api")]
     /// Start HTTP API server
@@ -121,6 +454,12 @@ pub enum Commands {
         /// Run server in daemon mode (background)
         #[arg(short, long)]
         daemon: bool,
+        /// Run the server in this terminal until Ctrl+C or SIGTERM, which is also what
+        /// happens without `--daemon`; for scripts that want to say so
+        #[arg(long, conflicts_with = "daemon")]
+        foreground: bool,
+        #[command(flatten)]
+        flags: ServeFlags,
     },
     #[cfg(feature = "http-api")]
     /// Show HTTP API server status
@@ -136,21 +475,327 @@ pub enum Commands {
         port: u16,
     },
     #[cfg(feature = "http-api")]
+    /// Show the HTTP API server's access log
+    ServeLogs {
+        /// Number of lines to show (default: all, 0 shows nothing, at most 10000)
+        #[arg(short = 'n', long)]
+        lines: Option<usize>,
+        /// Keep printing requests as they are logged, until Ctrl+C
+        #[arg(short, long)]
+        follow: bool,
+    },
+    #[cfg(feature = "http-api")]
     /// Manage API authentication tokens
     Auth {
         #[command(subcommand)]
         command: AuthCommands,
     },
+    /// Inspect the pmr configuration
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommands,
+    },
+    /// Inspect the pmr database
+    Db {
+        #[command(subcommand)]
+        command: DbCommands,
+    },
+    /// Generate files that integrate pmr with other tools
+    Generate {
+        #[command(subcommand)]
+        command: GenerateCommands,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum GenerateCommands {
+    /// Print a systemd unit running the HTTP API server (--serve) or owning the lifecycle
+    /// of one stored process (--process), using this pmr's database and config file
+    Systemd {
+        /// Unit running `pmr serve` in the foreground
+        #[arg(long, required_unless_present = "process")]
+        serve: bool,
+        /// Port of the API server (default: 8080)
+        #[arg(short, long, default_value = "8080", requires = "serve")]
+        port: u16,
+        /// Unit starting this process's stored definition with `pmr start --idempotent`,
+        /// waiting for it with `pmr wait` and stopping it with `pmr stop`
+        #[arg(long, value_name = "NAME", conflicts_with = "serve")]
+        process: Option<ProcessName>,
+        /// Write a user unit (`systemctl --user`) instead of a system unit run as the current user
+        #[arg(long)]
+        user: bool,
+        /// Write the unit to this file instead of printing it
+        #[arg(short, long, value_name = "PATH")]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum DbCommands {
+    /// Show the database's schema version, the version this pmr supports and pending migrations
+    Info {
+        /// Exit 1 when this pmr cannot use the database because a newer release wrote it
+        #[arg(long)]
+        check_compat: bool,
+    },
+    /// Move a damaged database aside to <path>.corrupt-<timestamp> and rebuild it at <path>
+    /// from every row that can still be read
+    Salvage {
+        /// Database file to salvage
+        #[arg(value_name = "PATH")]
+        path: PathBuf,
+    },
+}
+
+/// How `pmr serve` serves, passed on to the server `--daemon` and `serve-restart` spawn
+#[cfg(feature = "http-api")]
+#[derive(clap::Args, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ServeFlags {
+    /// Serve only GET endpoints from a database opened read-only (for dashboards)
+    #[arg(long)]
+    pub read_only: bool,
+    /// Send the database path in an X-PMR-Database header on every response
+    #[arg(long)]
+    pub database_header: bool,
+    /// Leave out the Swagger UI and OpenAPI document (also `docs = false` under [api])
+    #[arg(long)]
+    pub no_docs: bool,
+    /// Ask for a token on the Swagger UI and OpenAPI document (also `docs_require_auth = true` under [api])
+    #[arg(long, conflicts_with = "no_docs")]
+    pub docs_require_auth: bool,
+}
+
+#[cfg(feature = "http-api")]
+impl ServeFlags {
+    /// Arguments of `pmr serve` giving these flags
+    pub fn to_args(self) -> Vec<String> {
+        [
+            (self.read_only, "--read-only"),
+            (self.database_header, "--database-header"),
+            (self.no_docs, "--no-docs"),
+            (self.docs_require_auth, "--docs-require-auth"),
+        ]
+        .into_iter()
+        .filter(|(set, _)| *set)
+        .map(|(_, flag)| flag.to_string())
+        .collect()
+    }
+
+    /// The flags of a `pmr serve` command line (without the program name); defaults
+    /// when it is not one
+    pub fn from_args(args: &[String]) -> Self {
+        match Cli::try_parse_from(std::iter::once("pmr").chain(args.iter().map(String::as_str))) {
+            Ok(Cli { command: Commands::Serve { flags, .. }, .. }) => flags,
+            _ => Self::default(),
+        }
+    }
+}
+
+#[derive(Subcommand)]
+pub enum ConfigCommands {
+    /// Check the configuration for contradictory or unusable settings; exits 1 on errors
+    Validate {
+        /// Config file to check (default: $PMR_CONFIG or config.toml in $PMR_HOME or ~/.pmr)
+        #[arg(long, value_name = "PATH")]
+        config: Option<PathBuf>,
+    },
+    /// Show the database, default log directory and config file pmr uses, as absolute paths
+    Show,
 }
 
 impl Commands {
-    pub fn parse_env_vars(env_strings: Vec<String>) -> HashMap<String, String> {
-        let mut env_vars = HashMap::new();
+    /// Parse `KEY=VALUE` strings, reporting every malformed entry at once. A key given twice
+    /// is an error, listing both values, unless `allow_duplicates` lets the last one win.
+    pub fn parse_env_vars(env_strings: Vec<String>, allow_duplicates: bool) -> Result<HashMap<String, String>, Vec<EnvVarError>> {
+        let mut env_vars: HashMap<String, String> = HashMap::new();
+        let mut errors = Vec::new();
         for env_str in env_strings {
-            if let Some((key, value)) = env_str.split_once('=') {
-                env_vars.insert(key.to_string(), value.to_string());
+            match env_str.split_once('=') {
+                Some((key, value)) => match check_key(key) {
+                    Some(problem) => errors.push(EnvVarError { key: key.to_string(), problem }),
+                    None => match env_vars.get(key) {
+                        Some(first) if !allow_duplicates => errors.push(EnvVarError {
+                            key: key.to_string(),
+                            problem: EnvVarProblem::Duplicate { first: first.clone(), second: value.to_string() },
+                        }),
+                        _ => {
+                            env_vars.insert(key.to_string(), value.to_string());
+                        }
+                    },
+                },
+                None => errors.push(EnvVarError {
+                    key: env_str,
+                    problem: EnvVarProblem::MissingSeparator,
+                }),
             }
         }
-        env_vars
+
+        if errors.is_empty() {
+            Ok(env_vars)
+        } else {
+            Err(errors)
+        }
+    }
+    /// Clamp a `--lines` value to `max`, returning a warning when it had to be reduced
+    pub fn clamp_log_lines(lines: Option<usize>, max: usize) -> (Option<usize>, Option<String>) {
+        match lines {
+            Some(requested) if requested > max => (
+                Some(max),
+                Some(format!("--lines {} exceeds the maximum of {}; showing the last {} lines", requested, max, max)),
+            ),
+            _ => (lines, None),
+        }
+    }
+
+    /// Clamp a `--tail-bytes` value to `max`, returning a warning when it had to be reduced
+    pub fn clamp_tail_bytes(bytes: Option<HumanBytes>, max: u64) -> (Option<u64>, Option<String>) {
+        match bytes.map(|bytes| bytes.as_u64()) {
+            Some(requested) if requested > max => (
+                Some(max),
+                Some(format!("--tail-bytes {} exceeds the maximum of {}; showing the last {} bytes", requested, max, max)),
+            ),
+            bytes => (bytes, None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Commands, clap::Error> {
+        Cli::try_parse_from(std::iter::once("pmr").chain(args.iter().copied())).map(|cli| cli.command)
+    }
+
+    #[cfg(feature = "http-api")]
+    #[test]
+    fn test_serve_commands_parse() {
+        assert!(matches!(
+            parse(&["serve"]).unwrap(),
+            Commands::Serve { port: 8080, daemon: false, foreground: false, flags } if flags == ServeFlags::default()
+        ));
+        assert!(matches!(parse(&["serve", "--daemon", "-p", "3000"]).unwrap(), Commands::Serve { port: 3000, daemon: true, .. }));
+        assert!(matches!(parse(&["serve", "--foreground"]).unwrap(), Commands::Serve { daemon: false, foreground: true, .. }));
+        let conflict = parse(&["serve", "--foreground", "--daemon"]).err().unwrap();
+        assert_eq!(conflict.kind(), clap::error::ErrorKind::ArgumentConflict);
+
+        assert!(matches!(parse(&["serve-status"]).unwrap(), Commands::ServeStatus));
+        assert!(matches!(parse(&["serve-stop"]).unwrap(), Commands::ServeStop));
+        assert!(matches!(parse(&["serve-restart"]).unwrap(), Commands::ServeRestart { port: 8080 }));
+        assert!(matches!(parse(&["serve-restart", "--port", "9000"]).unwrap(), Commands::ServeRestart { port: 9000 }));
+        assert!(parse(&["serve-stop", "--port", "9000"]).is_err());
+    }
+
+    #[test]
+    fn test_stats_parse() {
+        assert!(matches!(parse(&["stats"]).unwrap(), Commands::Stats { oneline: false, ascii: false, unicode: false, watch: None }));
+        assert!(matches!(parse(&["stats", "--oneline", "--ascii"]).unwrap(), Commands::Stats { oneline: true, ascii: true, .. }));
+        assert!(matches!(parse(&["stats", "--watch"]).unwrap(), Commands::Stats { watch: Some(2), .. }));
+        assert!(matches!(parse(&["stats", "--oneline", "--watch", "5"]).unwrap(), Commands::Stats { oneline: true, watch: Some(5), .. }));
+        assert_eq!(parse(&["stats", "--ascii"]).err().unwrap().kind(), clap::error::ErrorKind::MissingRequiredArgument);
+        let conflict = parse(&["stats", "--oneline", "--ascii", "--unicode"]).err().unwrap();
+        assert_eq!(conflict.kind(), clap::error::ErrorKind::ArgumentConflict);
+    }
+
+    #[test]
+    fn test_list_all_hosts_parse() {
+        assert!(matches!(parse(&["list", "--all-hosts"]).unwrap(), Commands::List { all_hosts: true, all_profiles: false, .. }));
+        for other in ["--all-profiles", "--counts-only", "--names-only", "--jobs"] {
+            let conflict = parse(&["list", "--all-hosts", other]).err().unwrap();
+            assert_eq!(conflict.kind(), clap::error::ErrorKind::ArgumentConflict, "{}", other);
+        }
+    }
+
+    #[test]
+    fn test_rotate_on_start_parse() {
+        assert!(matches!(
+            parse(&["start", "web", "sleep"]).unwrap(),
+            Commands::Start { rotate_on_start: false, no_rotate_on_start: false, .. }
+        ));
+        assert!(matches!(parse(&["start", "--no-rotate-on-start", "web", "sleep"]).unwrap(), Commands::Start { no_rotate_on_start: true, .. }));
+        let conflict = parse(&["start", "--rotate-on-start", "--no-rotate-on-start", "web", "sleep"]).err().unwrap();
+        assert_eq!(conflict.kind(), clap::error::ErrorKind::ArgumentConflict);
+    }
+
+    #[cfg(feature = "http-api")]
+    #[test]
+    fn test_serve_flags_round_trip() {
+        let Commands::Serve { flags, .. } = parse(&["serve", "--read-only", "--docs-require-auth"]).unwrap() else { panic!() };
+        assert_eq!(flags, ServeFlags { read_only: true, docs_require_auth: true, ..Default::default() });
+        let mut args = vec!["serve".to_string(), "--port".to_string(), "9000".to_string()];
+        args.extend(flags.to_args());
+        assert_eq!(ServeFlags::from_args(&args), flags);
+
+        let flags = ServeFlags { no_docs: true, database_header: true, ..Default::default() };
+        assert_eq!(ServeFlags::from_args(&[["serve".to_string()].as_slice(), &flags.to_args()].concat()), flags);
+        assert_eq!(ServeFlags::from_args(&["list".to_string()]), ServeFlags::default());
+        let conflict = parse(&["serve", "--no-docs", "--docs-require-auth"]).err().unwrap();
+        assert_eq!(conflict.kind(), clap::error::ErrorKind::ArgumentConflict);
+    }
+
+    #[test]
+    fn test_logs_tail_bytes_parse() {
+        assert!(matches!(
+            parse(&["logs", "web", "--tail-bytes", "64K", "--allow-partial"]).unwrap(),
+            Commands::Logs { tail_bytes: Some(bytes), allow_partial: true, lines: None, .. } if bytes.as_u64() == 64 * 1024
+        ));
+        let conflict = parse(&["logs", "web", "--tail-bytes", "100", "-n", "5"]).err().unwrap();
+        assert_eq!(conflict.kind(), clap::error::ErrorKind::ArgumentConflict);
+        let conflict = parse(&["logs", "web", "--tail-bytes", "100", "--merge-rotated"]).err().unwrap();
+        assert_eq!(conflict.kind(), clap::error::ErrorKind::ArgumentConflict);
+        let missing = parse(&["logs", "web", "--allow-partial"]).err().unwrap();
+        assert_eq!(missing.kind(), clap::error::ErrorKind::MissingRequiredArgument);
+        assert_eq!(
+            Commands::clamp_tail_bytes(Some(HumanBytes::from(10_000)), 4096),
+            (Some(4096), Some("--tail-bytes 10000 exceeds the maximum of 4096; showing the last 4096 bytes".to_string()))
+        );
+        assert_eq!(Commands::clamp_tail_bytes(Some(HumanBytes::from(10)), 4096), (Some(10), None));
+    }
+
+    #[test]
+    fn test_logs_since_and_reindex_parse() {
+        assert!(matches!(
+            parse(&["logs", "web", "--since", "30m", "-n", "5"]).unwrap(),
+            Commands::Logs { since: Some(LogSince::Ago(_)), lines: Some(5), reindex: false, .. }
+        ));
+        assert!(matches!(
+            parse(&["logs", "web", "--since", "2024-05-01T12:00:00Z"]).unwrap(),
+            Commands::Logs { since: Some(LogSince::At(_)), .. }
+        ));
+        assert!(matches!(parse(&["logs", "web", "--reindex"]).unwrap(), Commands::Logs { reindex: true, since: None, .. }));
+        let invalid = parse(&["logs", "web", "--since", "yesterday"]).err().unwrap();
+        assert_eq!(invalid.kind(), clap::error::ErrorKind::ValueValidation);
+        for extra in [&["--since", "1h", "--tail-bytes", "1K"][..], &["--since", "1h", "--merge-rotated"], &["--reindex", "--since", "1h"]] {
+            let args = [&["logs", "web"][..], extra].concat();
+            assert_eq!(parse(&args).err().unwrap().kind(), clap::error::ErrorKind::ArgumentConflict, "{:?}", args);
+        }
+    }
+
+    #[test]
+    fn test_description_and_search_parse() {
+        assert!(matches!(
+            parse(&["update", "web", "--description", "Public API"]).unwrap(),
+            Commands::Update { workdir: None, description: Some(text), .. } if text == "Public API"
+        ));
+        assert!(matches!(parse(&["update", "web", "-w", "/srv"]).unwrap(), Commands::Update { description: None, .. }));
+        let missing = parse(&["update", "web"]).err().unwrap();
+        assert_eq!(missing.kind(), clap::error::ErrorKind::MissingRequiredArgument);
+
+        assert!(matches!(
+            parse(&["list", "--search", "api", "--wide"]).unwrap(),
+            Commands::List { search: Some(text), wide: true, .. } if text == "api"
+        ));
+        let conflict = parse(&["list", "--search", "api", "--names-only"]).err().unwrap();
+        assert_eq!(conflict.kind(), clap::error::ErrorKind::ArgumentConflict);
+    }
+
+    #[cfg(not(feature = "http-api"))]
+    #[test]
+    fn test_serve_commands_need_the_http_api_feature() {
+        for command in ["serve", "serve-status", "serve-stop", "serve-restart"] {
+            let error = parse(&[command]).err().unwrap();
+            assert_eq!(error.kind(), clap::error::ErrorKind::InvalidSubcommand, "{}", command);
+        }
     }
 }
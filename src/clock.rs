@@ -0,0 +1,112 @@
+//! Time as seen by [`ProcessManager`](crate::process::ProcessManager): record timestamps,
+//! retention cut-offs, and the deadlines and pauses of start, stop and wait polling.
+//! Tests swap in a [`ManualClock`] so none of that needs real sleeps.
+
+use chrono::{DateTime, Utc};
+use std::future::Future;
+use std::pin::Pin;
+use std::time::Duration;
+
+/// Future returned by [`Clock::sleep`]
+pub type Sleep = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+pub trait Clock: Send + Sync {
+    /// Wall-clock time, for timestamps stored with records
+    fn now(&self) -> DateTime<Utc>;
+
+    /// Monotonic time since an arbitrary origin, for deadlines
+    fn elapsed(&self) -> Duration;
+
+    /// Wait for `duration` to pass on this clock
+    fn sleep(&self, duration: Duration) -> Sleep;
+}
+
+/// The real clocks: `Utc::now()` and tokio's timer
+#[derive(Debug, Clone, Copy)]
+pub struct SystemClock {
+    origin: tokio::time::Instant,
+}
+
+impl SystemClock {
+    pub fn new() -> Self {
+        Self { origin: tokio::time::Instant::now() }
+    }
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+
+    fn elapsed(&self) -> Duration {
+        self.origin.elapsed()
+    }
+
+    fn sleep(&self, duration: Duration) -> Sleep {
+        Box::pin(tokio::time::sleep(duration))
+    }
+}
+
+/// Clock that only moves when told to. Sleeping advances it by the requested amount and
+/// yields once, so polling loops run to their deadlines without waiting.
+#[cfg(any(test, feature = "test-util"))]
+#[derive(Debug)]
+pub struct ManualClock {
+    start: DateTime<Utc>,
+    elapsed: std::sync::Mutex<Duration>,
+}
+
+#[cfg(any(test, feature = "test-util"))]
+impl ManualClock {
+    pub fn new(start: DateTime<Utc>) -> Self {
+        Self { start, elapsed: std::sync::Mutex::new(Duration::ZERO) }
+    }
+
+    /// Clock starting at the current wall-clock time
+    pub fn starting_now() -> Self {
+        Self::new(Utc::now())
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        *self.elapsed.lock().unwrap_or_else(|e| e.into_inner()) += duration;
+    }
+}
+
+#[cfg(any(test, feature = "test-util"))]
+impl Clock for ManualClock {
+    fn now(&self) -> DateTime<Utc> {
+        self.start + chrono::Duration::from_std(self.elapsed()).unwrap_or(chrono::Duration::MAX)
+    }
+
+    fn elapsed(&self) -> Duration {
+        *self.elapsed.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn sleep(&self, duration: Duration) -> Sleep {
+        self.advance(duration);
+        Box::pin(tokio::task::yield_now())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_manual_clock_moves_only_when_asked() {
+        let clock = ManualClock::starting_now();
+        let start = clock.now();
+        assert_eq!(clock.now(), start);
+
+        clock.sleep(Duration::from_secs(90)).await;
+        clock.advance(Duration::from_secs(30));
+        assert_eq!(clock.elapsed(), Duration::from_secs(120));
+        assert_eq!(clock.now() - start, chrono::Duration::seconds(120));
+    }
+}
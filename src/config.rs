@@ -1,20 +1,121 @@
-use std::path::PathBuf;
+use crate::database::LogDirMode;
+use crate::env::{check_key, is_reserved, EnvDefaults, EnvVarError, ReservedEnvPolicy};
+use crate::format::{deserialize_bytes, deserialize_optional_bytes};
+use crate::notify::{check_webhook_url, EVENT_STATUSES};
+use crate::suggest::explain_unknown_field;
+use crate::Error;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
 use std::env;
+use std::time::Duration;
+
+/// Largest number of log lines a single request may ask for
+pub const DEFAULT_MAX_LOG_LINES: usize = 10_000;
+
+/// Largest amount of log content a single request may ask for (5MB)
+pub const DEFAULT_MAX_LOGS_RESPONSE_BYTES: usize = 5 * 1024 * 1024;
+
+/// Environment variable overriding the config file location
+pub const CONFIG_PATH_ENV: &str = "PMR_CONFIG";
+
+/// Environment variable overriding the `~/.pmr` directory holding the database and config file
+pub const HOME_ENV: &str = "PMR_HOME";
+
+/// File name of the database inside a pmr home
+pub const DATABASE_FILE_NAME: &str = "processes.db";
+
+/// Environment variable allowing database migrations that rebuild tables when set to `1`
+pub const AUTO_MIGRATE_ENV: &str = "PMR_AUTO_MIGRATE";
 
 #[derive(Debug, Clone)]
 pub struct Config {
     pub database_path: PathBuf,
     pub default_log_dir: PathBuf,
+    /// Where logs go for processes started with a working directory but no `--log-dir`
+    pub log_dir_mode: LogDirMode,
     pub log_rotation: LogRotationConfig,
+    /// Whether a start rotates a previous log that is over `log_rotation.max_file_size`
+    /// before the process writes to it, for processes that do not choose for themselves
+    /// (`--rotate-on-start` / `--no-rotate-on-start`). Off, a start always appends, so
+    /// `pmr logs -f` and log shippers keep following the same file.
+    pub rotate_logs_on_start: bool,
+    /// Most bytes the live and rotated logs of all processes may take together; past it the
+    /// oldest archives are deleted. 0 means no quota.
+    pub logs_quota_bytes: u64,
+    pub cgroup_root: PathBuf,
+    pub stop_grace_period: Duration,
+    /// How long start watches a new process before reporting it as running
+    pub start_check_window: Duration,
+    /// Most processes pmr manages at once (deleted ones excluded); 0 means no limit
+    pub max_processes: usize,
+    /// Size limits on the arguments and environment a start may store
+    pub start_limits: StartLimitsConfig,
+    /// Whether and for how long starts wait out resource exhaustion instead of failing
+    pub start_queue: StartQueueConfig,
+    /// How many file operations sweeps over every process run at once, and how long each may take
+    pub file_ops: FileOpsConfig,
+    /// How often exited children are reaped and their records updated. Starts also reap
+    /// whatever exited since the last pass, so this mostly bounds how late exits are recorded.
+    pub reaper_interval: Duration,
+    /// How long a verified status counts as fresh when statuses are refreshed in `stale` mode
+    pub status_stale_after: Duration,
+    /// How recently the log of a process owned by another user must have been written for
+    /// it to count as running when its PID can neither be signalled nor looked up in /proc.
+    /// Zero turns the inference off, so such processes show as stopped.
+    pub inferred_liveness_window: Duration,
+    pub reserved_env_policy: ReservedEnvPolicy,
+    /// Variables every started process gets unless it sets them itself
+    pub env_defaults: EnvDefaults,
+    /// How long soft-deleted processes are kept before they can be purged
+    pub deleted_retention: Duration,
+    /// How long succeeded jobs are kept before `clear` removes them
+    pub job_retention: Duration,
+    pub notifications: NotificationConfig,
+    pub flapping: FlappingConfig,
+    /// Config file the settings were read from, if any
+    pub config_file: Option<PathBuf>,
+    /// Run database migrations that rebuild tables instead of refusing to open the database
+    pub allow_destructive_migrations: bool,
+    /// Where process records are kept: the SQLite file at `database_path`, or a database
+    /// shared with other hosts
+    pub storage: StorageConfig,
     #[cfg(feature = "http-api")]
     pub api: ApiConfig,
 }
 
+/// Where pmr keeps its files, as absolute paths
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "http-api", derive(utoipa::ToSchema))]
+pub struct ConfigPaths {
+    pub database: PathBuf,
+    /// Log directory of processes started without `--log-dir`
+    pub default_log_dir: PathBuf,
+    /// Config file the settings were read from; None when defaults are used
+    pub config_file: Option<PathBuf>,
+}
+
 #[cfg(feature = "http-api")]
 #[derive(Debug, Clone)]
 pub struct ApiConfig {
     pub enabled: bool,
     pub port: u16,
+    pub max_bulk_size: usize,     // max number of items accepted by bulk endpoints
+    pub bulk_concurrency: usize,  // number of bulk items processed at the same time
+    pub max_log_lines: usize,     // largest `lines` value accepted by the logs endpoint
+    /// Most bytes of rotated log content one logs response carries; the rest is cut off
+    /// and marked truncated, and the archive endpoint serves it instead
+    pub max_logs_response_bytes: usize,
+    /// Where the server logs requests; None means `api-access.log` next to the database
+    pub access_log_path: Option<PathBuf>,
+    /// Send the database path in an `X-PMR-Database` header on every response, to tell
+    /// which database a server uses; off by default as it reveals a local path
+    pub database_header: bool,
+    /// Serve the Swagger UI and the OpenAPI document; when off their paths answer 404
+    pub docs_enabled: bool,
+    /// Ask for a valid token on the documentation routes like on the API itself
+    pub docs_require_auth: bool,
 }
 
 #[cfg(feature = "http-api")]
@@ -23,13 +124,256 @@ impl Default for ApiConfig {
         Self {
             enabled: false,
             port: 8080,
+            max_bulk_size: 100,
+            bulk_concurrency: 8,
+            max_log_lines: DEFAULT_MAX_LOG_LINES,
+            max_logs_response_bytes: DEFAULT_MAX_LOGS_RESPONSE_BYTES,
+            access_log_path: None,
+            database_header: false,
+            docs_enabled: true,
+            docs_require_auth: false,
         }
     }
 }
 
-#[derive(Debug, Clone)]
+/// Global webhook receiving process state change events
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct NotificationConfig {
+    /// http(s) URL events are POSTed to; notifications are disabled when unset
+    pub webhook_url: Option<String>,
+    /// Sent as `Authorization: Bearer <token>`
+    pub bearer_token: Option<String>,
+    /// New statuses to notify about ("running", "stopped", "failed", "deleted", "flapping"); empty means all
+    pub events: Vec<String>,
+    /// Timeout of a single delivery attempt, in milliseconds
+    pub timeout_ms: u64,
+    /// Extra attempts after a failed delivery
+    pub max_retries: u32,
+}
+
+impl Default for NotificationConfig {
+    fn default() -> Self {
+        Self {
+            webhook_url: None,
+            bearer_token: None,
+            events: Vec::new(),
+            timeout_ms: 2000,
+            max_retries: 2,
+        }
+    }
+}
+
+/// Largest arguments and environment a start may store, in bytes; 0 turns a limit off.
+/// Checked by [`crate::limits::check_start_size`]
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct StartLimitsConfig {
+    /// All arguments together, as stored (a JSON array)
+    #[serde(deserialize_with = "deserialize_bytes")]
+    pub max_args_bytes: u64,
+    /// Any single environment variable value
+    #[serde(deserialize_with = "deserialize_bytes")]
+    pub max_env_value_bytes: u64,
+    /// The whole environment, as stored (a JSON object)
+    #[serde(deserialize_with = "deserialize_bytes")]
+    pub max_env_bytes: u64,
+}
+
+impl Default for StartLimitsConfig {
+    fn default() -> Self {
+        Self {
+            max_args_bytes: 64 * 1024,
+            max_env_value_bytes: 32 * 1024,
+            max_env_bytes: 256 * 1024,
+        }
+    }
+}
+
+/// Queue for starts whose spawn fails because the system is out of processes or memory,
+/// see [`crate::start_queue`]
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct StartQueueConfig {
+    /// Queue every start under pressure, as if each passed `--queue-on-pressure`
+    pub enabled: bool,
+    /// Most starts waiting at once; further ones fail right away
+    pub depth: usize,
+    /// How long a queued start keeps retrying before it fails, in seconds
+    pub max_wait_secs: u64,
+}
+
+impl Default for StartQueueConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            depth: 16,
+            max_wait_secs: 30,
+        }
+    }
+}
+
+impl StartQueueConfig {
+    pub fn max_wait(&self) -> Duration {
+        Duration::from_secs(self.max_wait_secs)
+    }
+}
+
+/// Bounds on the file operations of sweeps over every process, see [`crate::file_pool`].
+/// Read when the manager is created; a reload leaves them as they are.
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct FileOpsConfig {
+    /// Most operations running at once
+    pub concurrency: usize,
+    /// How long one operation may take before it is reported as unknown, in milliseconds
+    pub timeout_ms: u64,
+}
+
+impl Default for FileOpsConfig {
+    fn default() -> Self {
+        Self {
+            concurrency: 64,
+            timeout_ms: 5000,
+        }
+    }
+}
+
+impl FileOpsConfig {
+    pub fn timeout(&self) -> Duration {
+        Duration::from_millis(self.timeout_ms)
+    }
+}
+
+/// When repeated failures mark a process as flapping, see [`crate::flapping`]
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct FlappingConfig {
+    /// Failures within the window that make a process flapping; 0 turns detection off
+    pub failures: usize,
+    /// Length of the window, in seconds
+    pub window_secs: u64,
+}
+
+impl FlappingConfig {
+    pub fn enabled(&self) -> bool {
+        self.failures > 0
+    }
+}
+
+impl Default for FlappingConfig {
+    fn default() -> Self {
+        Self { failures: 5, window_secs: 600 }
+    }
+}
+
+/// Database kind process records are kept in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageKind {
+    /// The file at [`Config::database_path`], private to this host
+    #[default]
+    Sqlite,
+    /// A Postgres database other hosts may share; needs the `postgres` feature
+    Postgres,
+}
+
+/// The `[storage]` section, see [`crate::storage`]. Read when the manager is created; a
+/// reload leaves it as it is.
+#[derive(Debug, Clone, Default, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct StorageConfig {
+    pub backend: StorageKind,
+    /// Connection URL of a Postgres backend, e.g. `postgres://pmr@db.internal/pmr`
+    pub url: Option<String>,
+    /// Name this host's rows are kept under in a shared database; the machine's host
+    /// name when unset. Two pmr instances with the same name manage the same processes.
+    pub host: Option<String>,
+}
+
+/// How serious a configuration finding is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// pmr cannot work with this setting and refuses to start
+    Error,
+    /// Probably not what was meant, but usable
+    Warning,
+}
+
+/// A problem found by [`Config::validate`]
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ConfigIssue {
+    pub severity: Severity,
+    /// Path of the offending setting, e.g. `log_rotation.max_files` or `env_overrides.web-*.PORT`
+    pub key: String,
+    pub message: String,
+}
+
+impl ConfigIssue {
+    pub fn error(key: impl Into<String>, message: impl Into<String>) -> Self {
+        Self { severity: Severity::Error, key: key.into(), message: message.into() }
+    }
+
+    pub fn warning(key: impl Into<String>, message: impl Into<String>) -> Self {
+        Self { severity: Severity::Warning, key: key.into(), message: message.into() }
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+impl fmt::Display for ConfigIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        write!(f, "{}: {}: {}", severity, self.key, self.message)
+    }
+}
+
+/// Contents of the TOML config file; every section is optional
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ConfigFile {
+    log_rotation: Option<LogRotationConfig>,
+    rotate_logs_on_start: Option<bool>,
+    notifications: Option<NotificationConfig>,
+    flapping: Option<FlappingConfig>,
+    max_processes: Option<usize>,
+    start_limits: Option<StartLimitsConfig>,
+    start_queue: Option<StartQueueConfig>,
+    file_ops: Option<FileOpsConfig>,
+    #[serde(deserialize_with = "deserialize_optional_bytes")]
+    logs_quota_bytes: Option<u64>,
+    inferred_liveness_window_secs: Option<u64>,
+    reaper_interval_ms: Option<u64>,
+    log_dir_mode: Option<LogDirMode>,
+    env: Option<BTreeMap<String, String>>,
+    env_overrides: Option<BTreeMap<String, BTreeMap<String, String>>>,
+    api: Option<ApiFileConfig>,
+    storage: Option<StorageConfig>,
+}
+
+/// The `[api]` section: API server options read when the server starts. Accepted without
+/// the `http-api` feature too, so one config file serves every build.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+#[cfg_attr(not(feature = "http-api"), allow(dead_code))]
+struct ApiFileConfig {
+    /// See [`ApiConfig::docs_enabled`]
+    docs: Option<bool>,
+    /// See [`ApiConfig::docs_require_auth`]
+    docs_require_auth: Option<bool>,
+}
+
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
 pub struct LogRotationConfig {
-    pub max_file_size: u64,  // in bytes
+    #[serde(deserialize_with = "deserialize_bytes")]
+    pub max_file_size: u64,  // in bytes, or a size like "50MB" in the file
     pub max_files: usize,    // number of rotated files to keep
     pub enabled: bool,
 }
@@ -44,10 +388,15 @@ impl Default for LogRotationConfig {
     }
 }
 
+impl Default for Config {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Config {
     pub fn new() -> Self {
-        let home_dir = env::var("HOME").unwrap_or_else(|_| "/tmp".to_string());
-        let pmr_dir = PathBuf::from(home_dir).join(".pmr");
+        let pmr_dir = Self::home_dir();
 
         // Default log directory is in the current working directory
         let default_log_dir = std::env::current_dir()
@@ -55,45 +404,555 @@ impl Config {
             .join("logs");
 
         Self {
-            database_path: pmr_dir.join("processes.db"),
+            database_path: pmr_dir.join(DATABASE_FILE_NAME),
             default_log_dir,
+            log_dir_mode: LogDirMode::default(),
             log_rotation: LogRotationConfig::default(),
+            rotate_logs_on_start: true,
+            logs_quota_bytes: 0,
+            cgroup_root: crate::cgroup::default_root(),
+            stop_grace_period: Duration::from_secs(10),
+            start_check_window: Duration::from_millis(200),
+            max_processes: 1000,
+            start_limits: StartLimitsConfig::default(),
+            start_queue: StartQueueConfig::default(),
+            file_ops: FileOpsConfig::default(),
+            reaper_interval: Duration::from_secs(1),
+            status_stale_after: Duration::from_secs(2),
+            inferred_liveness_window: Duration::from_secs(60),
+            reserved_env_policy: ReservedEnvPolicy::default(),
+            env_defaults: EnvDefaults::default(),
+            deleted_retention: Duration::from_secs(7 * 24 * 60 * 60),
+            job_retention: Duration::from_secs(7 * 24 * 60 * 60),
+            notifications: NotificationConfig::default(),
+            flapping: FlappingConfig::default(),
+            config_file: None,
+            allow_destructive_migrations: env::var(AUTO_MIGRATE_ENV).is_ok_and(|value| value == "1"),
+            storage: StorageConfig::default(),
             #[cfg(feature = "http-api")]
             api: ApiConfig::default(),
         }
     }
 
+    /// Directory holding the database and config file: `$PMR_HOME`, or `~/.pmr`
+    pub fn home_dir() -> PathBuf {
+        match env::var_os(HOME_ENV).filter(|dir| !dir.is_empty()) {
+            Some(dir) => absolute(Path::new(&dir)),
+            None => {
+                let home_dir = env::var("HOME").unwrap_or_else(|_| "/tmp".to_string());
+                PathBuf::from(home_dir).join(".pmr")
+            }
+        }
+    }
+
+    /// Configuration keeping the database and logs inside `dir`
+    /// (`dir/processes.db` and `dir/logs`), independent of `HOME` and the current directory
+    pub fn in_directory(dir: impl AsRef<Path>) -> Self {
+        let dir = dir.as_ref();
+        Self::new()
+            .with_database_path(dir.join(DATABASE_FILE_NAME))
+            .with_log_dir(dir.join("logs"))
+    }
+
+    /// Default config file location: `$PMR_CONFIG`, or `config.toml` in [`Config::home_dir`]
+    pub fn default_file_path() -> PathBuf {
+        match env::var_os(CONFIG_PATH_ENV) {
+            Some(path) => PathBuf::from(path),
+            None => Self::home_dir().join("config.toml"),
+        }
+    }
+
+    /// Build the default configuration, applying the config file if one exists
+    pub fn load() -> crate::Result<Self> {
+        let path = Self::default_file_path();
+        if path.exists() {
+            Self::new().with_file(&path)
+        } else {
+            Ok(Self::new())
+        }
+    }
+
+    /// Apply the settings of a TOML config file on top of this configuration
+    pub fn with_file(self, path: &Path) -> crate::Result<Self> {
+        let content = std::fs::read_to_string(path).map_err(|e| {
+            Error::Config(format!("Failed to read config file '{}': {}", path.display(), e))
+        })?;
+        let mut config = self.with_toml(&content).map_err(|e| {
+            Error::Config(format!("Invalid config file '{}': {}", path.display(), explain_unknown_field(&e.to_string())))
+        })?;
+        config.config_file = Some(absolute(path));
+        Ok(config)
+    }
+
+    fn with_toml(mut self, content: &str) -> std::result::Result<Self, toml::de::Error> {
+        let file: ConfigFile = toml::from_str(content)?;
+        if let Some(log_rotation) = file.log_rotation {
+            self.log_rotation = log_rotation;
+        }
+        if let Some(rotate_logs_on_start) = file.rotate_logs_on_start {
+            self.rotate_logs_on_start = rotate_logs_on_start;
+        }
+        if let Some(notifications) = file.notifications {
+            self.notifications = notifications;
+        }
+        if let Some(flapping) = file.flapping {
+            self.flapping = flapping;
+        }
+        if let Some(max_processes) = file.max_processes {
+            self.max_processes = max_processes;
+        }
+        if let Some(start_limits) = file.start_limits {
+            self.start_limits = start_limits;
+        }
+        if let Some(start_queue) = file.start_queue {
+            self.start_queue = start_queue;
+        }
+        if let Some(file_ops) = file.file_ops {
+            self.file_ops = file_ops;
+        }
+        if let Some(logs_quota_bytes) = file.logs_quota_bytes {
+            self.logs_quota_bytes = logs_quota_bytes;
+        }
+        if let Some(secs) = file.inferred_liveness_window_secs {
+            self.inferred_liveness_window = Duration::from_secs(secs);
+        }
+        if let Some(ms) = file.reaper_interval_ms {
+            self.reaper_interval = Duration::from_millis(ms);
+        }
+        if let Some(log_dir_mode) = file.log_dir_mode {
+            self.log_dir_mode = log_dir_mode;
+        }
+        if let Some(env) = file.env {
+            self.env_defaults.global = env;
+        }
+        if let Some(env_overrides) = file.env_overrides {
+            self.env_defaults.overrides = env_overrides;
+        }
+        if let Some(storage) = file.storage {
+            self.storage = storage;
+        }
+        #[cfg(feature = "http-api")]
+        if let Some(api) = file.api {
+            self.api.docs_enabled = api.docs.unwrap_or(self.api.docs_enabled);
+            self.api.docs_require_auth = api.docs_require_auth.unwrap_or(self.api.docs_require_auth);
+        }
+        Ok(self)
+    }
+
+    /// This configuration with the settings the config file controls read from it again,
+    /// and the keys of those that changed. Settings the file leaves out go back to their
+    /// defaults; everything else (paths, timings, `[file_ops]`, `[api]`, `[storage]`) is kept.
+    pub fn reloaded(&self) -> crate::Result<(Self, Vec<String>)> {
+        let path = self.config_file.clone().unwrap_or_else(Self::default_file_path);
+        let file = if path.exists() { Self::new().with_file(&path)? } else { Self::new() };
+
+        let mut changed = Vec::new();
+        let mut note = |key: &str, differs: bool| {
+            if differs {
+                changed.push(key.to_string());
+            }
+        };
+        note("log_rotation", file.log_rotation != self.log_rotation);
+        note("rotate_logs_on_start", file.rotate_logs_on_start != self.rotate_logs_on_start);
+        note("notifications", file.notifications != self.notifications);
+        note("flapping", file.flapping != self.flapping);
+        note("max_processes", file.max_processes != self.max_processes);
+        note("start_limits", file.start_limits != self.start_limits);
+        note("start_queue", file.start_queue != self.start_queue);
+        note("logs_quota_bytes", file.logs_quota_bytes != self.logs_quota_bytes);
+        note("inferred_liveness_window_secs", file.inferred_liveness_window != self.inferred_liveness_window);
+        note("log_dir_mode", file.log_dir_mode != self.log_dir_mode);
+        note("env", file.env_defaults.global != self.env_defaults.global);
+        note("env_overrides", file.env_defaults.overrides != self.env_defaults.overrides);
+
+        let reloaded = Self {
+            log_rotation: file.log_rotation,
+            rotate_logs_on_start: file.rotate_logs_on_start,
+            notifications: file.notifications,
+            flapping: file.flapping,
+            max_processes: file.max_processes,
+            start_limits: file.start_limits,
+            start_queue: file.start_queue,
+            logs_quota_bytes: file.logs_quota_bytes,
+            inferred_liveness_window: file.inferred_liveness_window,
+            log_dir_mode: file.log_dir_mode,
+            env_defaults: file.env_defaults,
+            config_file: file.config_file,
+            ..self.clone()
+        };
+        Ok((reloaded, changed))
+    }
+
+    pub fn with_notifications(mut self, notifications: NotificationConfig) -> Self {
+        self.notifications = notifications;
+        self
+    }
+
+    pub fn with_flapping(mut self, flapping: FlappingConfig) -> Self {
+        self.flapping = flapping;
+        self
+    }
+
     pub fn with_log_dir(mut self, log_dir: PathBuf) -> Self {
         self.default_log_dir = log_dir;
         self
     }
 
+    /// Put the logs of processes started with a working directory in its `logs/` subdirectory
+    pub fn with_log_dir_mode(mut self, log_dir_mode: LogDirMode) -> Self {
+        self.log_dir_mode = log_dir_mode;
+        self
+    }
+
+    /// Allow migrations that rebuild tables, as `--migrate` does
+    pub fn with_destructive_migrations(mut self, allow: bool) -> Self {
+        self.allow_destructive_migrations = allow;
+        self
+    }
+
     pub fn with_log_rotation(mut self, config: LogRotationConfig) -> Self {
         self.log_rotation = config;
         self
     }
 
+    /// Whether starts rotate an oversized previous log by default, see [`Config::rotate_logs_on_start`]
+    pub fn with_rotate_logs_on_start(mut self, rotate_logs_on_start: bool) -> Self {
+        self.rotate_logs_on_start = rotate_logs_on_start;
+        self
+    }
+
     pub fn with_database_path(mut self, database_path: PathBuf) -> Self {
         self.database_path = database_path;
         self
     }
 
+    /// Keep process records in `storage` instead of the SQLite file at the database path
+    pub fn with_storage(mut self, storage: StorageConfig) -> Self {
+        self.storage = storage;
+        self
+    }
+
+    /// Cap the number of managed processes; 0 removes the limit
+    pub fn with_logs_quota_bytes(mut self, logs_quota_bytes: u64) -> Self {
+        self.logs_quota_bytes = logs_quota_bytes;
+        self
+    }
+
+    pub fn with_max_processes(mut self, max_processes: usize) -> Self {
+        self.max_processes = max_processes;
+        self
+    }
+
+    pub fn with_start_limits(mut self, start_limits: StartLimitsConfig) -> Self {
+        self.start_limits = start_limits;
+        self
+    }
+
+    pub fn with_start_queue(mut self, start_queue: StartQueueConfig) -> Self {
+        self.start_queue = start_queue;
+        self
+    }
+
+    pub fn with_file_ops(mut self, file_ops: FileOpsConfig) -> Self {
+        self.file_ops = file_ops;
+        self
+    }
+
+    /// Processes exiting within this window after start are reported as completed or failed
+    /// rather than running; raise it on slow machines
+    pub fn with_start_check_window(mut self, start_check_window: Duration) -> Self {
+        self.start_check_window = start_check_window;
+        self
+    }
+
+    pub fn with_reaper_interval(mut self, reaper_interval: Duration) -> Self {
+        self.reaper_interval = reaper_interval;
+        self
+    }
+
+    pub fn with_status_stale_after(mut self, status_stale_after: Duration) -> Self {
+        self.status_stale_after = status_stale_after;
+        self
+    }
+
+    pub fn with_inferred_liveness_window(mut self, inferred_liveness_window: Duration) -> Self {
+        self.inferred_liveness_window = inferred_liveness_window;
+        self
+    }
+
+    pub fn with_stop_grace_period(mut self, stop_grace_period: Duration) -> Self {
+        self.stop_grace_period = stop_grace_period;
+        self
+    }
 
+    pub fn with_deleted_retention(mut self, deleted_retention: Duration) -> Self {
+        self.deleted_retention = deleted_retention;
+        self
+    }
 
+    pub fn with_job_retention(mut self, job_retention: Duration) -> Self {
+        self.job_retention = job_retention;
+        self
+    }
+
+    pub fn with_env_defaults(mut self, env_defaults: EnvDefaults) -> Self {
+        self.env_defaults = env_defaults;
+        self
+    }
+
+    pub fn with_reserved_env_policy(mut self, policy: ReservedEnvPolicy) -> Self {
+        self.reserved_env_policy = policy;
+        self
+    }
+
+    #[cfg(feature = "http-api")]
+    pub fn with_access_log_path(mut self, access_log_path: PathBuf) -> Self {
+        self.api.access_log_path = Some(access_log_path);
+        self
+    }
+
+    /// The API server's access log: the configured path, or `api-access.log` next to the
+    /// database (`~/.pmr/api-access.log` by default)
+    #[cfg(feature = "http-api")]
+    pub fn access_log_path(&self) -> PathBuf {
+        self.api.access_log_path.clone().unwrap_or_else(|| {
+            self.database_path.parent().unwrap_or(Path::new(".")).join("api-access.log")
+        })
+    }
+
+    #[cfg(feature = "http-api")]
+    pub fn with_database_header(mut self, database_header: bool) -> Self {
+        self.api.database_header = database_header;
+        self
+    }
+
+    /// Mount the Swagger UI and OpenAPI document or not
+    #[cfg(feature = "http-api")]
+    pub fn with_docs_enabled(mut self, docs_enabled: bool) -> Self {
+        self.api.docs_enabled = docs_enabled;
+        self
+    }
+
+    #[cfg(feature = "http-api")]
+    pub fn with_docs_require_auth(mut self, docs_require_auth: bool) -> Self {
+        self.api.docs_require_auth = docs_require_auth;
+        self
+    }
+
+    /// The database, default log directory and config file in use, as absolute paths
+    pub fn paths(&self) -> ConfigPaths {
+        ConfigPaths {
+            database: absolute(&self.database_path),
+            default_log_dir: absolute(&self.default_log_dir),
+            config_file: self.config_file.clone(),
+        }
+    }
+
+    pub fn with_cgroup_root(mut self, cgroup_root: PathBuf) -> Self {
+        self.cgroup_root = cgroup_root;
+        self
+    }
+
+
+
+    /// Check the settings for contradictions and values pmr cannot work with. Errors make
+    /// [`crate::process::ProcessManager::new`] refuse to start; warnings are only reported.
+    pub fn validate(&self) -> Vec<ConfigIssue> {
+        let mut issues = Vec::new();
+
+        let rotation = &self.log_rotation;
+        if rotation.enabled && rotation.max_files == 0 {
+            issues.push(ConfigIssue::error("log_rotation.max_files", "rotation is enabled but keeps no rotated files; set at least 1 or disable rotation"));
+        }
+        if rotation.enabled && rotation.max_file_size == 0 {
+            issues.push(ConfigIssue::error("log_rotation.max_file_size", "rotation is enabled with a 0 byte limit, so every non-empty log would rotate"));
+        }
+
+        // Named by absolute path: a different HOME or working directory (sudo, systemd)
+        // is the usual reason these point somewhere unexpected
+        let paths = self.paths();
+        if let Some(problem) = unwritable_dir(&paths.default_log_dir) {
+            issues.push(ConfigIssue::error(
+                "default_log_dir",
+                format!(
+                    "cannot create process logs in {}: {}; run pmr from a writable directory or start processes with --log-dir",
+                    paths.default_log_dir.display(),
+                    problem
+                ),
+            ));
+        }
+        if let Some(problem) = paths.database.parent().and_then(unwritable_dir) {
+            issues.push(ConfigIssue::error(
+                "database_path",
+                format!(
+                    "cannot create the database {}: {}; set {} to a writable directory to keep pmr's files elsewhere",
+                    paths.database.display(),
+                    problem,
+                    HOME_ENV
+                ),
+            ));
+        }
+
+        if self.start_check_window > Duration::from_secs(60) {
+            issues.push(ConfigIssue::warning(
+                "start_check_window",
+                format!("every start waits up to {}s before returning", self.start_check_window.as_secs()),
+            ));
+        }
+        if self.stop_grace_period.is_zero() {
+            issues.push(ConfigIssue::warning("stop_grace_period", "stopped processes get no time to exit before being killed"));
+        }
+        if self.reaper_interval.is_zero() {
+            issues.push(ConfigIssue::warning("reaper_interval", "0 is treated as 1ms, which keeps the reaper busy"));
+        }
+        if self.deleted_retention.is_zero() {
+            issues.push(ConfigIssue::warning("deleted_retention", "soft-deleted processes can be purged right away, so undelete has no window"));
+        }
+        if self.flapping.enabled() && self.flapping.window_secs == 0 {
+            issues.push(ConfigIssue::error("flapping.window_secs", "a 0s window holds no failures, so no process could flap; set failures = 0 to turn detection off"));
+        }
+
+        self.validate_notifications(&mut issues);
+        self.validate_env_defaults(&mut issues);
+        self.validate_storage(&mut issues);
+        #[cfg(feature = "http-api")]
+        self.validate_api(&mut issues);
+
+        issues
+    }
+
+    fn validate_notifications(&self, issues: &mut Vec<ConfigIssue>) {
+        let notifications = &self.notifications;
+        match &notifications.webhook_url {
+            // The manager still starts with a bad URL, only without notifications
+            Some(url) => {
+                if let Err(Error::Config(problem)) = check_webhook_url(url) {
+                    issues.push(ConfigIssue::warning("notifications.webhook_url", format!("{}; notifications will be disabled", problem)));
+                }
+            }
+            None if notifications.bearer_token.is_some() => {
+                issues.push(ConfigIssue::warning("notifications.bearer_token", "set without a webhook_url, so it is never sent"));
+            }
+            None => {}
+        }
+        for (index, event) in notifications.events.iter().enumerate() {
+            if !EVENT_STATUSES.contains(&event.as_str()) {
+                issues.push(ConfigIssue::error(
+                    format!("notifications.events[{}]", index),
+                    format!("unknown status '{}'; expected one of {}", event, EVENT_STATUSES.join(", ")),
+                ));
+            }
+        }
+        if notifications.webhook_url.is_some() && notifications.timeout_ms == 0 {
+            issues.push(ConfigIssue::error("notifications.timeout_ms", "a 0ms timeout fails every delivery"));
+        }
+    }
+
+    fn validate_env_defaults(&self, issues: &mut Vec<ConfigIssue>) {
+        fn check(prefix: &str, vars: &BTreeMap<String, String>, issues: &mut Vec<ConfigIssue>) {
+            for key in vars.keys() {
+                let path = format!("{}.{}", prefix, key);
+                if let Some(problem) = check_key(key) {
+                    issues.push(ConfigIssue::error(path, EnvVarError { key: key.clone(), problem }.to_string()));
+                } else if is_reserved(key) {
+                    issues.push(ConfigIssue::warning(path, "the PMR_ prefix is reserved for variables pmr injects"));
+                }
+            }
+        }
+
+        check("env", &self.env_defaults.global, issues);
+        for (pattern, vars) in &self.env_defaults.overrides {
+            if pattern.is_empty() {
+                issues.push(ConfigIssue::error("env_overrides", "an empty pattern matches no process name"));
+            }
+            check(&format!("env_overrides.{}", pattern), vars, issues);
+        }
+    }
+
+    fn validate_storage(&self, issues: &mut Vec<ConfigIssue>) {
+        let storage = &self.storage;
+        match storage.backend {
+            StorageKind::Sqlite => {
+                if storage.url.is_some() {
+                    issues.push(ConfigIssue::warning("storage.url", "only used by the postgres backend; records stay in the SQLite file"));
+                }
+                if storage.host.is_some() {
+                    issues.push(ConfigIssue::warning("storage.host", "only used by the postgres backend, where hosts share one database"));
+                }
+            }
+            StorageKind::Postgres => {
+                if !cfg!(feature = "postgres") {
+                    issues.push(ConfigIssue::error("storage.backend", "this pmr was built without the postgres feature"));
+                }
+                if storage.url.as_deref().is_none_or(str::is_empty) {
+                    issues.push(ConfigIssue::error("storage.url", "the postgres backend needs a connection URL"));
+                }
+            }
+        }
+        if storage.host.as_deref().is_some_and(|host| host.trim().is_empty()) {
+            issues.push(ConfigIssue::error("storage.host", "an empty host name cannot tell this host's rows apart"));
+        }
+    }
+
+    #[cfg(feature = "http-api")]
+    fn validate_api(&self, issues: &mut Vec<ConfigIssue>) {
+        if self.api.max_log_lines == 0 {
+            issues.push(ConfigIssue::error("api.max_log_lines", "the logs endpoint could never return a line"));
+        }
+        if self.api.max_logs_response_bytes == 0 {
+            issues.push(ConfigIssue::error("api.max_logs_response_bytes", "rotated log content could never be returned"));
+        }
+        if self.api.max_bulk_size == 0 {
+            issues.push(ConfigIssue::warning("api.max_bulk_size", "bulk endpoints reject every non-empty batch"));
+        }
+        if self.api.bulk_concurrency == 0 {
+            issues.push(ConfigIssue::warning("api.bulk_concurrency", "0 is treated as 1"));
+        }
+    }
+
+    /// Create the database directory, readable by the owner only when pmr creates it, and the
+    /// default log directory
     pub fn ensure_directories(&self) -> crate::Result<()> {
         if let Some(parent) = self.database_path.parent() {
-            std::fs::create_dir_all(parent)?;
+            crate::permissions::create_private_dir_all(parent)?;
         }
         std::fs::create_dir_all(&self.default_log_dir)?;
         Ok(())
     }
 
-    pub fn ensure_log_directory(&self, log_dir: &PathBuf) -> crate::Result<()> {
-        std::fs::create_dir_all(log_dir)?;
+    pub fn ensure_log_directory(&self, log_dir: &Path) -> crate::Result<()> {
+        self.ensure_log_directory_with(log_dir, &crate::retry_fs::RetryingFs::default())
+    }
+
+    /// [`Self::ensure_log_directory`] through `files`, whose retries a start reports
+    pub fn ensure_log_directory_with(&self, log_dir: &Path, files: &crate::retry_fs::RetryingFs) -> crate::Result<()> {
+        files.create_dir_all(log_dir)?;
         Ok(())
     }
 }
 
+/// `path` made absolute against the current directory, without resolving symlinks
+fn absolute(path: &Path) -> PathBuf {
+    std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Why files cannot be created in `dir` (or in the directories that would be created for
+/// it), judged from its nearest existing ancestor; None when they can
+fn unwritable_dir(dir: &Path) -> Option<String> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let existing = dir.ancestors().find(|ancestor| ancestor.exists())?;
+    if !existing.is_dir() {
+        return Some(format!("{} is not a directory", existing.display()));
+    }
+    let path = std::ffi::CString::new(existing.as_os_str().as_bytes()).ok()?;
+    // SAFETY: access only reads the NUL-terminated path
+    if unsafe { libc::access(path.as_ptr(), libc::W_OK | libc::X_OK) } != 0 {
+        return Some(format!("{} is not writable: {}", existing.display(), std::io::Error::last_os_error()));
+    }
+    None
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -159,6 +1018,383 @@ mod tests {
         assert!(log_dir.is_dir());
     }
 
+    #[test]
+    fn test_config_file_notifications() {
+        let config = Config::new()
+            .with_toml(
+                r#"
+                [notifications]
+                webhook_url = "https://hooks.example.com/pmr"
+                bearer_token = "secret"
+                events = ["failed", "stopped"]
+                "#,
+            )
+            .unwrap();
+
+        assert_eq!(config.notifications.webhook_url.as_deref(), Some("https://hooks.example.com/pmr"));
+        assert_eq!(config.notifications.bearer_token.as_deref(), Some("secret"));
+        assert_eq!(config.notifications.events, vec!["failed", "stopped"]);
+        assert_eq!(config.notifications.max_retries, 2);
+
+        // An empty file keeps the defaults
+        let config = Config::new().with_toml("").unwrap();
+        assert_eq!(config.notifications, NotificationConfig::default());
+    }
+
+    #[test]
+    fn test_config_file_rotate_logs_on_start() {
+        assert!(Config::new().rotate_logs_on_start);
+        assert!(!Config::new().with_toml("rotate_logs_on_start = false\n").unwrap().rotate_logs_on_start);
+        assert!(Config::new().with_toml("rotate_logs_on_start = \"no\"\n").is_err());
+    }
+
+    #[test]
+    fn test_config_file_reaper_interval() {
+        assert_eq!(Config::new().reaper_interval, Duration::from_secs(1));
+        assert_eq!(Config::new().with_toml("reaper_interval_ms = 250\n").unwrap().reaper_interval, Duration::from_millis(250));
+        assert!(Config::new().with_toml("reaper_interval_ms = \"fast\"\n").is_err());
+    }
+
+    #[test]
+    fn test_config_file_inferred_liveness_window() {
+        assert_eq!(Config::new().inferred_liveness_window, Duration::from_secs(60));
+        let config = Config::new().with_toml("inferred_liveness_window_secs = 0\n").unwrap();
+        assert_eq!(config.inferred_liveness_window, Duration::ZERO);
+        assert!(Config::new().with_toml("inferred_liveness_window_secs = -1\n").is_err());
+    }
+
+    #[test]
+    fn test_config_file_max_processes() {
+        assert_eq!(Config::new().max_processes, 1000);
+        assert_eq!(Config::new().with_toml("max_processes = 50\n").unwrap().max_processes, 50);
+        assert_eq!(Config::new().with_toml("max_processes = 0\n").unwrap().max_processes, 0);
+        assert!(Config::new().with_toml("max_processes = -1\n").is_err());
+        assert_eq!(Config::new().logs_quota_bytes, 0);
+        assert_eq!(Config::new().with_toml("logs_quota_bytes = 1073741824\n").unwrap().logs_quota_bytes, 1 << 30);
+        assert_eq!(Config::new().with_toml("logs_quota_bytes = \"5GB\"\n").unwrap().logs_quota_bytes, 5_000_000_000);
+        let rotation = Config::new().with_toml("[log_rotation]\nmax_file_size = \"50MiB\"\n").unwrap().log_rotation;
+        assert_eq!(rotation.max_file_size, 50 << 20);
+        assert!(Config::new().with_toml("logs_quota_bytes = \"5 parsecs\"\n").is_err());
+    }
+
+    #[test]
+    fn test_config_file_start_queue() {
+        assert_eq!(Config::new().start_queue, StartQueueConfig { enabled: false, depth: 16, max_wait_secs: 30 });
+        let queue = Config::new().with_toml("[start_queue]\nenabled = true\nmax_wait_secs = 5\n").unwrap().start_queue;
+        assert!(queue.enabled);
+        assert_eq!(queue.depth, 16);
+        assert_eq!(queue.max_wait(), Duration::from_secs(5));
+        assert!(Config::new().with_toml("[start_queue]\nmax_wait = 5\n").is_err());
+    }
+
+    #[test]
+    fn test_config_file_file_ops() {
+        assert_eq!(Config::new().file_ops, FileOpsConfig { concurrency: 64, timeout_ms: 5000 });
+        let file_ops = Config::new().with_toml("[file_ops]\nconcurrency = 8\n").unwrap().file_ops;
+        assert_eq!(file_ops, FileOpsConfig { concurrency: 8, timeout_ms: 5000 });
+        assert_eq!(Config::new().with_toml("[file_ops]\ntimeout_ms = 250\n").unwrap().file_ops.timeout(), Duration::from_millis(250));
+        assert!(Config::new().with_toml("[file_ops]\ntimeout = 5\n").is_err());
+    }
+
+    #[test]
+    fn test_config_file_start_limits() {
+        assert_eq!(Config::new().start_limits.max_args_bytes, 64 * 1024);
+        let limits = Config::new().with_toml("[start_limits]\nmax_env_value_bytes = \"1MiB\"\nmax_args_bytes = 0\n").unwrap().start_limits;
+        assert_eq!(limits, StartLimitsConfig { max_args_bytes: 0, max_env_value_bytes: 1 << 20, max_env_bytes: 256 * 1024 });
+        assert!(Config::new().with_toml("[start_limits]\nmax_env_size = 10\n").is_err());
+    }
+
+    #[test]
+    fn test_config_file_log_dir_mode() {
+        assert_eq!(Config::new().log_dir_mode, LogDirMode::Default);
+        assert_eq!(Config::new().with_toml("log_dir_mode = \"workdir\"\n").unwrap().log_dir_mode, LogDirMode::Workdir);
+        assert!(Config::new().with_toml("log_dir_mode = \"cwd\"\n").is_err());
+    }
+
+    #[test]
+    fn test_config_file_flapping() {
+        assert_eq!(Config::new().flapping, FlappingConfig { failures: 5, window_secs: 600 });
+        let config = Config::new().with_toml("[flapping]\nfailures = 3\n").unwrap();
+        assert_eq!(config.flapping, FlappingConfig { failures: 3, window_secs: 600 });
+        assert!(Config::new().with_toml("[flapping]\nwindow = 60\n").is_err());
+
+        let dir = TempDir::new().unwrap();
+        let flapping = |failures, window_secs| valid_config(&dir).with_flapping(FlappingConfig { failures, window_secs });
+        assert_eq!(findings(&flapping(3, 0)), vec![(Severity::Error, "flapping.window_secs".to_string())]);
+        assert!(findings(&flapping(0, 0)).is_empty());
+    }
+
+    #[test]
+    fn test_config_file_storage() {
+        assert_eq!(Config::new().storage, StorageConfig::default());
+        let config = Config::new()
+            .with_toml("[storage]\nbackend = \"postgres\"\nurl = \"postgres://pmr@db/pmr\"\nhost = \"web-1\"\n")
+            .unwrap();
+        assert_eq!(config.storage.backend, StorageKind::Postgres);
+        assert_eq!(config.storage.host.as_deref(), Some("web-1"));
+        assert!(Config::new().with_toml("[storage]\nbackend = \"mysql\"\n").is_err());
+
+        let dir = TempDir::new().unwrap();
+        let storage = |backend, url: Option<&str>, host: Option<&str>| {
+            valid_config(&dir).with_storage(StorageConfig { backend, url: url.map(str::to_string), host: host.map(str::to_string) })
+        };
+        let mut no_url = vec![(Severity::Error, "storage.url".to_string())];
+        if !cfg!(feature = "postgres") {
+            no_url.insert(0, (Severity::Error, "storage.backend".to_string()));
+        }
+        assert_eq!(findings(&storage(StorageKind::Postgres, None, None)), no_url);
+        assert_eq!(
+            findings(&storage(StorageKind::Sqlite, Some("postgres://db/pmr"), Some(" "))),
+            vec![
+                (Severity::Warning, "storage.url".to_string()),
+                (Severity::Warning, "storage.host".to_string()),
+                (Severity::Error, "storage.host".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_reloaded_replaces_only_file_settings() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(&path, "max_processes = 3\n").unwrap();
+        let config = Config::in_directory(dir.path())
+            .with_stop_grace_period(Duration::from_secs(1))
+            .with_file(&path)
+            .unwrap();
+
+        let (same, changed) = config.reloaded().unwrap();
+        assert!(changed.is_empty());
+        assert_eq!(same.max_processes, 3);
+
+        std::fs::write(&path, "[log_rotation]\nmax_file_size = 100\n\n[flapping]\nfailures = 2\n").unwrap();
+        let (reloaded, changed) = config.reloaded().unwrap();
+        assert_eq!(changed, vec!["log_rotation", "flapping", "max_processes"]);
+        assert_eq!(reloaded.log_rotation.max_file_size, 100);
+        assert_eq!(reloaded.log_rotation.max_files, LogRotationConfig::default().max_files);
+        assert_eq!(reloaded.max_processes, Config::new().max_processes);
+        // Settings the file cannot hold keep their running values
+        assert_eq!(reloaded.stop_grace_period, Duration::from_secs(1));
+        assert_eq!(reloaded.database_path, config.database_path);
+
+        std::fs::write(&path, "[log_rotation]\nmax_size = 100\n").unwrap();
+        assert!(config.reloaded().is_err());
+    }
+
+    #[test]
+    fn test_config_file_env_defaults() {
+        let config = Config::new()
+            .with_toml("[env]\nTZ = \"UTC\"\n\n[env_overrides.\"web-*\"]\nPORT = \"8080\"\n")
+            .unwrap();
+        assert_eq!(config.env_defaults.global.get("TZ").map(String::as_str), Some("UTC"));
+        assert_eq!(config.env_defaults.overrides["web-*"].get("PORT").map(String::as_str), Some("8080"));
+        // Values must be strings
+        assert!(Config::new().with_toml("[env]\nPORT = 8080\n").is_err());
+    }
+
+    #[test]
+    fn test_config_file_errors() {
+        assert!(Config::new().with_toml("[notifications]\nwebhook = \"typo\"\n").is_err());
+
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("config.toml");
+        std::fs::write(&path, "not toml").unwrap();
+        let err = Config::new().with_file(&path).unwrap_err();
+        assert!(err.to_string().contains("Invalid config file"));
+    }
+
+    #[test]
+    fn test_config_file_errors_suggest_fields() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("config.toml");
+        let error_for = |content: &str| {
+            std::fs::write(&path, content).unwrap();
+            Config::new().with_file(&path).unwrap_err().to_string()
+        };
+
+        // A typo names the field it was probably meant to be, with its position
+        let typo = error_for("[notifications]\nwebhok_url = \"http://localhost/\"\n");
+        assert!(typo.contains("line 2, column 1"), "{}", typo);
+        assert!(typo.contains("unknown field `webhok_url`"), "{}", typo);
+        assert!(typo.contains("did you mean `webhook_url`?"), "{}", typo);
+        let top_level = error_for("max_process = 10\n");
+        assert!(top_level.contains("did you mean `max_processes`?"), "{}", top_level);
+
+        // Settings nested a level too deep are unknown in the section that holds them
+        let nested = error_for("[notifications.retry]\nmax_retries = 3\n");
+        assert!(nested.contains("unknown field `retry`"), "{}", nested);
+        assert!(!nested.contains("did you mean"), "{}", nested);
+
+        let wrong_type = error_for("[notifications]\ntimeout_ms = \"fast\"\n");
+        assert!(wrong_type.contains("invalid type: string \"fast\", expected u64"), "{}", wrong_type);
+        assert!(!wrong_type.contains("did you mean"), "{}", wrong_type);
+    }
+
+    /// Config rooted in a fresh directory, so only the settings under test produce issues
+    fn valid_config(dir: &TempDir) -> Config {
+        Config::in_directory(dir.path())
+    }
+
+    /// (severity, key) of every issue found
+    fn findings(config: &Config) -> Vec<(Severity, String)> {
+        config.validate().into_iter().map(|issue| (issue.severity, issue.key)).collect()
+    }
+
+    #[test]
+    fn test_validate_defaults_are_clean() {
+        let dir = TempDir::new().unwrap();
+        assert_eq!(valid_config(&dir).validate(), vec![]);
+    }
+
+    #[test]
+    fn test_validate_rotation_limits() {
+        let dir = TempDir::new().unwrap();
+        let rotation = |max_file_size, max_files, enabled| {
+            valid_config(&dir).with_log_rotation(LogRotationConfig { max_file_size, max_files, enabled })
+        };
+        assert_eq!(findings(&rotation(1024, 0, true)), vec![(Severity::Error, "log_rotation.max_files".to_string())]);
+        assert_eq!(findings(&rotation(0, 3, true)), vec![(Severity::Error, "log_rotation.max_file_size".to_string())]);
+        // Nothing rotates when disabled, so the limits do not matter
+        assert!(findings(&rotation(0, 0, false)).is_empty());
+    }
+
+    #[test]
+    fn test_validate_unwritable_directories() {
+        let dir = TempDir::new().unwrap();
+        let file = dir.path().join("plain-file");
+        std::fs::write(&file, "").unwrap();
+
+        let config = valid_config(&dir).with_log_dir(file.join("logs")).with_database_path(file.join("db/processes.db"));
+        let issues = config.validate();
+        assert_eq!(
+            issues.iter().map(|issue| issue.key.as_str()).collect::<Vec<_>>(),
+            vec!["default_log_dir", "database_path"]
+        );
+        assert!(issues[0].message.contains("is not a directory"), "{}", issues[0].message);
+        let expected = format!("cannot create the database {}: ", file.join("db/processes.db").display());
+        assert!(issues[1].message.starts_with(&expected), "{}", issues[1].message);
+        assert!(issues[1].message.ends_with("set PMR_HOME to a writable directory to keep pmr's files elsewhere"));
+
+        // Directories that do not exist yet are fine when they can be created
+        let config = valid_config(&dir).with_log_dir(dir.path().join("a/b/c"));
+        assert!(config.validate().is_empty());
+    }
+
+    #[test]
+    fn test_validate_timing_warnings() {
+        let dir = TempDir::new().unwrap();
+        let config = valid_config(&dir)
+            .with_start_check_window(Duration::from_secs(120))
+            .with_stop_grace_period(Duration::ZERO)
+            .with_reaper_interval(Duration::ZERO)
+            .with_deleted_retention(Duration::ZERO);
+        let keys: Vec<(Severity, String)> = ["start_check_window", "stop_grace_period", "reaper_interval", "deleted_retention"]
+            .iter()
+            .map(|key| (Severity::Warning, key.to_string()))
+            .collect();
+        assert_eq!(findings(&config), keys);
+    }
+
+    #[test]
+    fn test_validate_notifications() {
+        let dir = TempDir::new().unwrap();
+        let config = valid_config(&dir).with_notifications(NotificationConfig {
+            webhook_url: Some("ftp://hooks.example.com".to_string()),
+            events: vec!["failed".to_string(), "crashed".to_string()],
+            timeout_ms: 0,
+            ..Default::default()
+        });
+        assert_eq!(
+            findings(&config),
+            vec![
+                (Severity::Warning, "notifications.webhook_url".to_string()),
+                (Severity::Error, "notifications.events[1]".to_string()),
+                (Severity::Error, "notifications.timeout_ms".to_string()),
+            ]
+        );
+
+        let config = valid_config(&dir).with_notifications(NotificationConfig {
+            bearer_token: Some("secret".to_string()),
+            ..Default::default()
+        });
+        assert_eq!(findings(&config), vec![(Severity::Warning, "notifications.bearer_token".to_string())]);
+    }
+
+    #[test]
+    fn test_validate_env_defaults() {
+        let dir = TempDir::new().unwrap();
+        let config = valid_config(&dir)
+            .with_toml("[env]\n\"1X\" = \"a\"\nPMR_MODE = \"b\"\nOK = \"c\"\n[env_overrides.\"web-*\"]\n\"A=B\" = \"d\"\n[env_overrides.\"\"]\nX = \"e\"\n")
+            .unwrap();
+        let issues = config.validate();
+        assert_eq!(
+            issues.iter().map(|issue| (issue.severity, issue.key.as_str())).collect::<Vec<_>>(),
+            vec![
+                (Severity::Error, "env.1X"),
+                (Severity::Warning, "env.PMR_MODE"),
+                (Severity::Error, "env_overrides"),
+                (Severity::Error, "env_overrides.web-*.A=B"),
+            ]
+        );
+        assert_eq!(issues[0].to_string(), "error: env.1X: key '1X' must not start with a digit");
+    }
+
+    #[cfg(feature = "http-api")]
+    #[test]
+    fn test_validate_api_limits() {
+        let dir = TempDir::new().unwrap();
+        let mut config = valid_config(&dir);
+        config.api.max_log_lines = 0;
+        config.api.max_logs_response_bytes = 0;
+        config.api.max_bulk_size = 0;
+        config.api.bulk_concurrency = 0;
+        assert_eq!(
+            findings(&config),
+            vec![
+                (Severity::Error, "api.max_log_lines".to_string()),
+                (Severity::Error, "api.max_logs_response_bytes".to_string()),
+                (Severity::Warning, "api.max_bulk_size".to_string()),
+                (Severity::Warning, "api.bulk_concurrency".to_string()),
+            ]
+        );
+    }
+
+    #[cfg(feature = "http-api")]
+    #[test]
+    fn test_access_log_path() {
+        let config = Config::new().with_database_path(PathBuf::from("/srv/pmr/processes.db"));
+        assert_eq!(config.access_log_path(), PathBuf::from("/srv/pmr/api-access.log"));
+        let config = config.with_access_log_path(PathBuf::from("/var/log/pmr-access.log"));
+        assert_eq!(config.access_log_path(), PathBuf::from("/var/log/pmr-access.log"));
+    }
+
+    #[cfg(feature = "http-api")]
+    #[test]
+    fn test_api_section() {
+        assert!(Config::new().api.docs_enabled);
+        let config = Config::new().with_toml("[api]\ndocs = false\n").unwrap();
+        assert!(!config.api.docs_enabled && !config.api.docs_require_auth);
+        // A section leaving a key out keeps its current value
+        let config = config.with_toml("[api]\ndocs_require_auth = true\n").unwrap();
+        assert!(!config.api.docs_enabled && config.api.docs_require_auth);
+        assert!(Config::new().with_toml("[api]\nport = 80\n").is_err());
+    }
+
+    #[test]
+    fn test_paths_are_absolute() {
+        let dir = TempDir::new().unwrap();
+        let config = Config::new().with_database_path(PathBuf::from("state/processes.db"));
+        let paths = config.paths();
+        assert_eq!(paths.database, std::env::current_dir().unwrap().join("state/processes.db"));
+        assert!(paths.default_log_dir.is_absolute());
+        assert_eq!(paths.config_file, None);
+
+        let file = dir.path().join("pmr.toml");
+        std::fs::write(&file, "max_processes = 3\n").unwrap();
+        let config = Config::new().with_file(&file).unwrap();
+        assert_eq!(config.paths().config_file, Some(file));
+    }
+
     #[test]
     fn test_log_rotation_config_default() {
         let config = LogRotationConfig::default();
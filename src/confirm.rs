@@ -0,0 +1,209 @@
+//! Asking before destructive commands: `pmr delete` of a running process or with `--hard`,
+//! and `pmr clear`, show what they are about to remove and wait for an answer. `--yes`,
+//! [`ASSUME_YES_ENV`] or a stdin that is not a terminal skip the question, so scripts never
+//! hang on it.
+
+use crate::database::{ProcessRecord, ProcessStatus};
+use std::io::{self, BufRead, Write};
+
+/// Environment variable answering every question with yes when set to `1`, like `--yes`
+pub const ASSUME_YES_ENV: &str = "PMR_ASSUME_YES";
+
+/// `clear --all` over more processes than this asks for their number to be typed
+pub const CLEAR_ALL_TYPED_THRESHOLD: usize = 5;
+
+/// Processes named in a clear summary before the rest are only counted
+const SUMMARY_MAX_NAMES: usize = 20;
+
+/// The answer that lets a destructive command go ahead
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expected {
+    /// `y` or `yes`
+    Yes,
+    /// This exact text, for the commands that are hardest to take back
+    Typed(String),
+}
+
+/// A question asked before a destructive command
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Confirmation {
+    /// What the command will affect, shown before the question
+    pub summary: String,
+    pub question: String,
+    pub expected: Expected,
+}
+
+impl Confirmation {
+    /// Deleting `process`: a running one has its name typed, since deleting stops it; a hard
+    /// delete asks for `y`. None for soft-deleting a process that is not running, which
+    /// `pmr undelete` takes back.
+    pub fn delete(process: &ProcessRecord, hard: bool) -> Option<Self> {
+        let running = process.status == ProcessStatus::Running;
+        if !running && !hard {
+            return None;
+        }
+        let mut summary = Vec::new();
+        if running {
+            let pid = process.pid.map(|pid| format!(" (PID {})", pid)).unwrap_or_default();
+            summary.push(format!("Process '{}'{} is running; deleting it stops it first.", process.name, pid));
+        }
+        summary.push(if hard {
+            format!("The record and the log files of '{}' are removed for good.", process.name)
+        } else {
+            format!("'pmr undelete {}' restores the record until it is purged.", process.name)
+        });
+        Some(Self {
+            summary: summary.join("\n"),
+            question: format!("Delete process '{}'?", process.name),
+            expected: if running { Expected::Typed(process.name.to_string()) } else { Expected::Yes },
+        })
+    }
+
+    /// Clearing `processes`; `--all` over more than [`CLEAR_ALL_TYPED_THRESHOLD`] of them has
+    /// their number typed. None when there is nothing to clear.
+    pub fn clear(processes: &[ProcessRecord], all: bool) -> Option<Self> {
+        if processes.is_empty() {
+            return None;
+        }
+        let mut summary = vec![format!("This removes {} process(es) and their logs for good:", processes.len())];
+        summary.extend(
+            processes
+                .iter()
+                .take(SUMMARY_MAX_NAMES)
+                .map(|process| format!("  {} ({})", process.name, process.status)),
+        );
+        if processes.len() > SUMMARY_MAX_NAMES {
+            summary.push(format!("  ... and {} more", processes.len() - SUMMARY_MAX_NAMES));
+        }
+        let running = processes.iter().filter(|process| process.status == ProcessStatus::Running).count();
+        if running > 0 {
+            summary.push(format!("{} of them are running and will be stopped.", running));
+        }
+        Some(Self {
+            summary: summary.join("\n"),
+            question: "Clear these processes?".to_string(),
+            expected: if all && processes.len() > CLEAR_ALL_TYPED_THRESHOLD {
+                Expected::Typed(processes.len().to_string())
+            } else {
+                Expected::Yes
+            },
+        })
+    }
+
+    /// Show the summary and the question on `output`, read one line from `input` and tell
+    /// whether it was the expected answer. Anything else, including end of input, declines.
+    pub fn ask(&self, input: &mut impl BufRead, output: &mut impl Write) -> io::Result<bool> {
+        if !self.summary.is_empty() {
+            writeln!(output, "{}", self.summary)?;
+        }
+        match &self.expected {
+            Expected::Yes => write!(output, "{} [y/N] ", self.question)?,
+            Expected::Typed(text) => write!(output, "{} Type '{}' to confirm: ", self.question, text)?,
+        }
+        output.flush()?;
+
+        let mut answer = String::new();
+        input.read_line(&mut answer)?;
+        let answer = answer.trim();
+        Ok(match &self.expected {
+            Expected::Yes => matches!(answer, "y" | "Y" | "yes"),
+            Expected::Typed(text) => answer == text,
+        })
+    }
+}
+
+/// Whether [`ASSUME_YES_ENV`] answers every question
+pub fn assume_yes_from_env() -> bool {
+    std::env::var(ASSUME_YES_ENV).is_ok_and(|value| value == "1")
+}
+
+/// Whether to ask at all: only when someone at a terminal can answer and nothing said yes
+/// beforehand
+pub fn should_ask(assume_yes: bool, interactive: bool) -> bool {
+    interactive && !assume_yes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(name: &str, status: ProcessStatus) -> ProcessRecord {
+        serde_json::from_value(serde_json::json!({
+            "id": name,
+            "name": name,
+            "command": "server",
+            "args": [],
+            "env_vars": {},
+            "working_dir": "/srv",
+            "pid": (status == ProcessStatus::Running).then_some(4242),
+            "status": status,
+            "created_at": "2026-10-18T09:30:00Z",
+            "updated_at": "2026-10-18T09:30:00Z",
+            "log_path": format!("/srv/{}.log", name),
+        }))
+        .unwrap()
+    }
+
+    /// The answer to `confirmation` given `typed`, and what was shown
+    fn answer(confirmation: &Confirmation, typed: &str) -> (bool, String) {
+        let mut shown = Vec::new();
+        let accepted = confirmation.ask(&mut typed.as_bytes(), &mut shown).unwrap();
+        (accepted, String::from_utf8(shown).unwrap())
+    }
+
+    #[test]
+    fn test_deleting_a_running_process_needs_its_name() {
+        let confirmation = Confirmation::delete(&record("web", ProcessStatus::Running), false).unwrap();
+        let (accepted, shown) = answer(&confirmation, "y\n");
+        assert!(!accepted);
+        assert!(shown.contains("Process 'web' (PID 4242) is running; deleting it stops it first."), "{}", shown);
+        assert!(shown.ends_with("Delete process 'web'? Type 'web' to confirm: "), "{}", shown);
+        assert!(answer(&confirmation, "web\n").0);
+        assert!(!answer(&confirmation, "we\n").0);
+        assert!(!answer(&confirmation, "").0);
+    }
+
+    #[test]
+    fn test_hard_delete_asks_and_soft_delete_of_a_stopped_process_does_not() {
+        assert!(Confirmation::delete(&record("old", ProcessStatus::Stopped), false).is_none());
+
+        let confirmation = Confirmation::delete(&record("old", ProcessStatus::Stopped), true).unwrap();
+        assert_eq!(confirmation.expected, Expected::Yes);
+        let (accepted, shown) = answer(&confirmation, "yes\n");
+        assert!(accepted);
+        assert!(shown.contains("removed for good"), "{}", shown);
+        assert!(shown.ends_with("[y/N] "), "{}", shown);
+        assert!(!answer(&confirmation, "\n").0);
+        assert!(!answer(&confirmation, "n\n").0);
+    }
+
+    #[test]
+    fn test_clear_lists_what_goes_and_all_over_the_threshold_needs_the_count() {
+        assert!(Confirmation::clear(&[], true).is_none());
+
+        let few = vec![record("a", ProcessStatus::Stopped), record("b", ProcessStatus::Running)];
+        let confirmation = Confirmation::clear(&few, true).unwrap();
+        assert_eq!(confirmation.expected, Expected::Yes);
+        let (accepted, shown) = answer(&confirmation, "Y\n");
+        assert!(accepted);
+        assert!(shown.contains("This removes 2 process(es)"), "{}", shown);
+        assert!(shown.contains("  b (running)\n1 of them are running and will be stopped."), "{}", shown);
+
+        let many: Vec<ProcessRecord> = (0..25).map(|i| record(&format!("p{}", i), ProcessStatus::Failed)).collect();
+        let confirmation = Confirmation::clear(&many, true).unwrap();
+        assert_eq!(confirmation.expected, Expected::Typed("25".to_string()));
+        let (accepted, shown) = answer(&confirmation, "y\n");
+        assert!(!accepted);
+        assert!(shown.contains("  ... and 5 more"), "{}", shown);
+        assert!(answer(&confirmation, "25\n").0);
+        // Without --all the usual clear of finished processes asks for y
+        assert_eq!(Confirmation::clear(&many, false).unwrap().expected, Expected::Yes);
+    }
+
+    #[test]
+    fn test_should_ask_only_interactively_without_yes() {
+        assert!(should_ask(false, true));
+        assert!(!should_ask(true, true));
+        assert!(!should_ask(false, false));
+    }
+}
@@ -2,24 +2,244 @@ use sqlx::{SqlitePool, Row, sqlite::SqlitePoolOptions};
 use serde::{Deserialize, Serialize};
 use chrono::{DateTime, Utc};
 use std::collections::HashMap;
-use crate::{Error, Result};
+use futures_util::stream::{BoxStream, StreamExt};
+use crate::{affinity::CpuSet, cgroup::{CgroupLimits, CgroupStats}, drift::DriftReport, file_watch::WatchConfig, flapping::FailureHistory, logs_quota::QuotaEnforcement, name::ProcessName, snapshot::StartSnapshot, umask::Umask, Error, Result};
+
+/// Bound parameters per statement; the compile-time default of SQLite builds before 3.32
+const MAX_BIND_PARAMETERS: usize = 999;
+
+/// Schema version this build creates and understands. Raise it with every change older
+/// releases cannot work with; added columns they simply do not read need no new version.
+pub const SCHEMA_VERSION: i64 = 1;
+
+/// Oldest pmr release that reads a database at [`SCHEMA_VERSION`]. Stored with the
+/// version, so an older binary can name the release to upgrade to.
+pub const MIN_BINARY_VERSION: &str = "0.3.0";
+
+/// Name of the record `pmr serve --daemon` runs the HTTP server under. Records are marked
+/// internal by [`ProcessRecord::internal`]; the name only identifies rows from before that
+/// column, and stays reserved so a user process cannot take over `serve-stop` and friends.
+pub const HTTP_SERVER_PROCESS_NAME: &str = "__pmr_http_server__";
+
+/// [`HTTP_SERVER_PROCESS_NAME`] as a [`ProcessName`]
+pub fn http_server_process_name() -> ProcessName {
+    ProcessName::stored(HTTP_SERVER_PROCESS_NAME.to_string())
+}
+
+/// How a database's schema compares with what this binary supports
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SchemaInfo {
+    pub database: std::path::PathBuf,
+    /// Version recorded in the database; 0 when it has none (new, or created before
+    /// versioning)
+    pub schema_version: i64,
+    /// Oldest pmr release the database works with, as recorded with its version
+    pub min_binary_version: Option<String>,
+    /// Schema version this binary supports
+    pub supported_version: i64,
+    pub binary_version: String,
+    /// Migrations opening the database would run that rebuild tables, and so need
+    /// `--migrate` or `PMR_AUTO_MIGRATE=1`
+    pub destructive_migrations: Vec<String>,
+}
+
+impl SchemaInfo {
+    /// Whether this binary can open the database, possibly after migrating it
+    pub fn is_compatible(&self) -> bool {
+        self.schema_version <= self.supported_version
+    }
+
+    /// Whether opening the database would change its schema
+    pub fn migrations_pending(&self) -> bool {
+        self.schema_version < self.supported_version
+    }
+}
+
+/// SQLite result codes for a damaged file and for one that is not a database at all
+const SQLITE_CORRUPT: i32 = 11;
+const SQLITE_NOTADB: i32 = 26;
+
+/// SQLite result codes for a write the storage refused: a read-only file, a disk I/O
+/// error and a full disk
+const SQLITE_READONLY: i32 = 8;
+const SQLITE_IOERR: i32 = 10;
+const SQLITE_FULL: i32 = 13;
+
+/// Rowids a salvage probes one by one in a table it cannot read in full
+const SALVAGE_MAX_ROWIDS: i64 = 1_000_000;
+
+/// Problems of the damaged file kept in a [`SalvageReport`]
+const SALVAGE_INTEGRITY_LINES: u32 = 20;
+
+/// Whether SQLite reported the database file as damaged or as not a database
+pub fn is_corruption(error: &sqlx::Error) -> bool {
+    let sqlx::Error::Database(e) = error else { return false };
+    // Extended result codes keep the primary code in the low byte
+    e.code()
+        .and_then(|code| code.parse::<i32>().ok())
+        .is_some_and(|code| matches!(code & 0xff, SQLITE_CORRUPT | SQLITE_NOTADB))
+}
+
+/// Whether a write failed because the storage under the database is read-only, full or
+/// failing, rather than because of what was written
+pub fn is_storage_failure(error: &Error) -> bool {
+    let io_failure = |e: &std::io::Error| {
+        use std::io::ErrorKind;
+        matches!(e.kind(), ErrorKind::ReadOnlyFilesystem | ErrorKind::StorageFull | ErrorKind::QuotaExceeded)
+            || e.raw_os_error() == Some(libc::EIO)
+    };
+    match error {
+        Error::Database(sqlx::Error::Database(e)) => e
+            .code()
+            .and_then(|code| code.parse::<i32>().ok())
+            .is_some_and(|code| matches!(code & 0xff, SQLITE_READONLY | SQLITE_IOERR | SQLITE_FULL)),
+        Error::Database(sqlx::Error::Io(e)) | Error::Io(e) => io_failure(e),
+        _ => false,
+    }
+}
+
+/// A value of a column both databases store with the same type, see [`StoredRow`]
+#[cfg(not(feature = "postgres"))]
+pub(crate) trait StoredValue: for<'r> sqlx::Decode<'r, sqlx::Sqlite> + sqlx::Type<sqlx::Sqlite> {}
+
+#[cfg(not(feature = "postgres"))]
+impl<T> StoredValue for T where T: for<'r> sqlx::Decode<'r, sqlx::Sqlite> + sqlx::Type<sqlx::Sqlite> {}
+
+/// A value of a column both databases store with the same type, see [`StoredRow`]
+#[cfg(feature = "postgres")]
+pub(crate) trait StoredValue:
+    for<'r> sqlx::Decode<'r, sqlx::Sqlite> + sqlx::Type<sqlx::Sqlite> + for<'r> sqlx::Decode<'r, sqlx::Postgres> + sqlx::Type<sqlx::Postgres>
+{
+}
+
+#[cfg(feature = "postgres")]
+impl<T> StoredValue for T where
+    T: for<'r> sqlx::Decode<'r, sqlx::Sqlite> + sqlx::Type<sqlx::Sqlite> + for<'r> sqlx::Decode<'r, sqlx::Postgres> + sqlx::Type<sqlx::Postgres>
+{
+}
+
+/// A row read from any storage backend. The Postgres tables mirror the SQLite columns,
+/// timestamps and JSON included, so the `row_to_*` decoders of [`Database`] read both.
+pub(crate) trait StoredRow {
+    /// The value of `column`; panics when it is missing or of another type, like [`Row::get`]
+    fn column<T: StoredValue>(&self, column: &str) -> T;
+
+    /// The value of `column`, or the error when it is missing or of another type
+    #[cfg_attr(not(feature = "http-api"), allow(dead_code))]
+    fn try_column<T: StoredValue>(&self, column: &str) -> std::result::Result<T, sqlx::Error>;
+}
+
+impl StoredRow for sqlx::sqlite::SqliteRow {
+    fn column<T: StoredValue>(&self, column: &str) -> T {
+        self.get(column)
+    }
+
+    fn try_column<T: StoredValue>(&self, column: &str) -> std::result::Result<T, sqlx::Error> {
+        self.try_get(column)
+    }
+}
+
+#[cfg(feature = "postgres")]
+impl StoredRow for sqlx::postgres::PgRow {
+    fn column<T: StoredValue>(&self, column: &str) -> T {
+        self.get(column)
+    }
+
+    fn try_column<T: StoredValue>(&self, column: &str) -> std::result::Result<T, sqlx::Error> {
+        self.try_get(column)
+    }
+}
+
+/// An error opening the database, told apart when the file is damaged
+fn open_error(error: Error) -> Error {
+    match error {
+        Error::Database(e) if is_corruption(&e) => Error::DatabaseCorrupt(e.to_string()),
+        error => error,
+    }
+}
+
+/// A failed connect, described by `context` unless the file is damaged
+fn connect_error(error: sqlx::Error, context: impl FnOnce() -> String) -> Error {
+    if is_corruption(&error) {
+        Error::DatabaseCorrupt(error.to_string())
+    } else {
+        Error::Other(format!("{}: {}", context(), error))
+    }
+}
+
+/// What [`Database::salvage`] got out of a damaged database
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SalvageReport {
+    /// The rebuilt database, at the path of the damaged one
+    pub database: std::path::PathBuf,
+    /// Where the damaged file was moved
+    pub backup: std::path::PathBuf,
+    pub tables: Vec<TableSalvage>,
+    /// What `PRAGMA integrity_check` found in the damaged file, or why it could not run
+    pub integrity_check: Vec<String>,
+}
+
+/// Rows of one table copied into the rebuilt database
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TableSalvage {
+    pub table: String,
+    pub recovered: usize,
+    /// Rows that could not be read; None when the table could not be read at all, so
+    /// how many it held is unknown
+    pub lost: Option<usize>,
+}
+
+impl SalvageReport {
+    pub fn recovered(&self) -> usize {
+        self.tables.iter().map(|table| table.recovered).sum()
+    }
+
+    /// Rows known to be lost; tables that could not be read at all add nothing
+    pub fn lost(&self) -> usize {
+        self.tables.iter().filter_map(|table| table.lost).sum()
+    }
+
+    /// Whether any table could not be read at all
+    pub fn has_unreadable_tables(&self) -> bool {
+        self.tables.iter().any(|table| table.lost.is_none())
+    }
+}
+
+/// Start of a token's public id, which is followed by [`TOKEN_ID_LENGTH`] lowercase
+/// letters and digits
+#[cfg(feature = "http-api")]
+pub const TOKEN_ID_PREFIX: &str = "pmr_";
+
+/// Characters after [`TOKEN_ID_PREFIX`] in a token's public id
+#[cfg(feature = "http-api")]
+pub const TOKEN_ID_LENGTH: usize = 8;
 
 #[cfg(feature = "http-api")]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ApiToken {
     pub id: String,
+    /// Names the token in listings and logs without revealing it, e.g. `pmr_ab12cd34`.
+    /// Tokens issued since ids exist start with it, followed by `_` and the secret.
+    pub public_id: String,
+    /// The full value clients send
     pub token: String,
     pub name: String,
     pub created_at: DateTime<Utc>,
     pub expires_at: Option<DateTime<Utc>>,
     pub is_active: bool,
+    /// Admin tokens can also use endpoints that expose unredacted data, such as inspect
+    #[serde(default)]
+    pub admin: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[cfg_attr(feature = "http-api", derive(utoipa::ToSchema))]
 pub struct ProcessRecord {
     pub id: String,
-    pub name: String,
+    pub name: ProcessName,
+    /// What the process is for, in the user's words; matched by `pmr list --search`
+    #[serde(default)]
+    pub description: Option<String>,
     pub command: String,
     pub args: Vec<String>,
     pub env_vars: HashMap<String, String>,
@@ -29,6 +249,262 @@ pub struct ProcessRecord {
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
     pub log_path: String,
+    /// Log directory requested at start (None means the configured default)
+    #[serde(default)]
+    pub log_dir: Option<String>,
+    /// memory.max applied through cgroup v2, in bytes
+    #[serde(default)]
+    pub cgroup_memory_max: Option<u64>,
+    /// cpu.max applied through cgroup v2, as a percentage of one core
+    #[serde(default)]
+    pub cgroup_cpu_max: Option<u32>,
+    /// Live cgroup usage, filled in by status queries (not persisted)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cgroup: Option<CgroupStats>,
+    /// Set when the process was soft-deleted; such records are hidden from lookups
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<DateTime<Utc>>,
+    /// Executable the command resolved to at start
+    #[serde(default)]
+    pub binary_path: Option<String>,
+    /// SHA-256 of the executable at start
+    #[serde(default)]
+    pub binary_sha256: Option<String>,
+    /// Working directory with symlinks resolved at start
+    #[serde(default)]
+    pub canonical_workdir: Option<String>,
+    /// CPUs the process is pinned to, in list form (`0,2-3`)
+    #[serde(default)]
+    pub cpu_affinity: Option<String>,
+    /// Umask set for the process, in octal (`0027`); None inherits the umask of pmr
+    #[serde(default)]
+    pub umask: Option<String>,
+    /// Exit code of the last run, when pmr saw the process exit
+    #[serde(default)]
+    pub exit_code: Option<i32>,
+    /// Why the last run's exit code is missing although its exit was seen
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exit_reason: Option<String>,
+    /// What /proc showed right after spawn, when the start asked for a snapshot
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start_snapshot: Option<StartSnapshot>,
+    /// Keys of `env_vars` that came from the config file defaults rather than the start request
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub default_env_keys: Vec<String>,
+    /// Drift against the recorded start state, filled in on request (not persisted)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub drift: Option<DriftReport>,
+    /// Notes attached with `pmr annotate`, filled in on request (stored separately)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub notes: Vec<ProcessNote>,
+    /// Latest finished runs, oldest first, filled in by status queries (stored separately)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub recent_runs: Vec<ProcessRun>,
+    /// Whether the process failed repeatedly within the flapping window, filled in by
+    /// list and status queries (stored separately)
+    #[serde(default)]
+    pub flapping: bool,
+    /// Whether the manager could not store the statuses it refreshed because the database
+    /// storage is read-only, full or failing; the status is the probed one all the same.
+    /// Filled in by list and status queries
+    #[serde(default)]
+    pub degraded: bool,
+    /// Whether a running status was inferred from recent log writes: the PID belongs to
+    /// another user and /proc hides it, so it could not be confirmed as this process.
+    /// Filled in by queries that probe the PID
+    #[serde(default)]
+    pub inferred: bool,
+    /// Fields shortened for display because they are too long, e.g. `args[2]` or
+    /// `env_vars.BLOB`; filled in by list and status unless full output is asked for
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub truncated_fields: Vec<String>,
+    /// Long-running service or oneshot job; see [`ProcessKind`]
+    #[serde(default)]
+    pub kind: ProcessKind,
+    /// Times the process was run under this name: 1, plus one for every `--rerun` of a job
+    #[serde(default = "default_runs")]
+    pub runs: u32,
+    /// Signal sent first when the process is stopped, unless a stop names its own
+    #[serde(default = "default_stop_signal")]
+    pub stop_signal: i32,
+    /// How the log directory was chosen; with `workdir` and no `log_dir` it follows the
+    /// working directory on restart
+    #[serde(default)]
+    pub log_dir_mode: LogDirMode,
+    /// When the status was last checked against the PID; None if it never was
+    #[serde(default)]
+    pub status_verified_at: Option<DateTime<Utc>>,
+    /// Started by pmr for itself, like the daemonized HTTP server; left out of listings,
+    /// counts and clears
+    #[serde(default)]
+    pub internal: bool,
+    /// Seconds an ephemeral process is kept after it exits before it is deleted with its
+    /// logs; None for ordinary processes
+    #[serde(default)]
+    pub ephemeral_keep_secs: Option<u64>,
+    /// When an exited ephemeral process is due for deletion; None while it runs
+    #[serde(default)]
+    pub delete_after: Option<DateTime<Utc>>,
+    /// Last lines of the log, captured when the process failed or stopped with a non-zero
+    /// exit code; empty otherwise, and for every new start
+    #[serde(default)]
+    pub last_output: Vec<String>,
+    /// The log held bytes that are not UTF-8, which `last_output` shows replaced
+    #[serde(default)]
+    pub last_output_lossy: bool,
+    /// Paths whose changes restart the process; None unless started with `--watch`
+    #[serde(default)]
+    pub watch: Option<WatchConfig>,
+    /// Whether a start rotates an oversized previous log first; None follows
+    /// [`Config::rotate_logs_on_start`](crate::config::Config::rotate_logs_on_start)
+    #[serde(default)]
+    pub rotate_on_start: Option<bool>,
+}
+
+fn default_runs() -> u32 {
+    1
+}
+
+fn default_stop_signal() -> i32 {
+    libc::SIGTERM
+}
+
+impl ProcessRecord {
+    pub fn cgroup_limits(&self) -> CgroupLimits {
+        CgroupLimits {
+            memory_max: self.cgroup_memory_max,
+            cpu_max_percent: self.cgroup_cpu_max,
+        }
+    }
+
+    /// Fields of the stored definition (command, args, env, workdir) that differ from a
+    /// requested one, in that order
+    pub fn definition_differences(
+        &self,
+        command: &str,
+        args: &[String],
+        env_vars: &HashMap<String, String>,
+        working_dir: &str,
+    ) -> Vec<String> {
+        let mut differences = Vec::new();
+        if self.command != command {
+            differences.push("command".to_string());
+        }
+        if self.args != args {
+            differences.push("args".to_string());
+        }
+        if &self.env_vars != env_vars {
+            differences.push("env".to_string());
+        }
+        if self.working_dir != working_dir {
+            differences.push("workdir".to_string());
+        }
+        differences
+    }
+
+    /// Variables given explicitly at start, without the ones filled in from defaults
+    pub fn explicit_env_vars(&self) -> HashMap<String, String> {
+        self.env_vars
+            .iter()
+            .filter(|(key, _)| !self.default_env_keys.contains(key))
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect()
+    }
+
+    /// Stored CPU affinity; a value that no longer parses is treated as unpinned
+    pub fn cpu_set(&self) -> Option<CpuSet> {
+        self.cpu_affinity.as_deref().and_then(|list| list.parse().ok())
+    }
+
+    /// Stored umask; a value that no longer parses is treated as inherited
+    pub fn umask_value(&self) -> Option<Umask> {
+        self.umask.as_deref().and_then(|umask| umask.parse().ok())
+    }
+
+    /// How long the last run took: until the exit for a finished job, until now otherwise
+    pub fn run_duration(&self, now: DateTime<Utc>) -> chrono::Duration {
+        let end = if self.status == ProcessStatus::Running { now } else { self.updated_at };
+        (end - self.created_at).max(chrono::Duration::zero())
+    }
+}
+
+/// What a process is expected to do: keep running, or run once to completion
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "http-api", derive(utoipa::ToSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum ProcessKind {
+    /// Exiting ends the process, cleanly (stopped) or not (failed)
+    #[default]
+    Service,
+    /// A oneshot job: exit code 0 means it succeeded, anything else that it failed, and a
+    /// succeeded job is only run again on request
+    Job,
+}
+
+impl ProcessKind {
+    /// Status of a process of this kind after it exited with `exit_code`, where a service
+    /// would get `service_status`. Jobs killed by a signal are treated like services.
+    pub fn exit_status(self, service_status: ProcessStatus, exit_code: Option<i32>) -> ProcessStatus {
+        match (self, exit_code) {
+            (ProcessKind::Job, Some(0)) => ProcessStatus::Succeeded,
+            (ProcessKind::Job, Some(_)) => ProcessStatus::Failed,
+            _ => service_status,
+        }
+    }
+}
+
+impl std::fmt::Display for ProcessKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProcessKind::Service => write!(f, "service"),
+            ProcessKind::Job => write!(f, "job"),
+        }
+    }
+}
+
+impl std::str::FromStr for ProcessKind {
+    type Err = Error;
+
+    fn from_str(kind: &str) -> Result<Self> {
+        match kind {
+            "service" => Ok(ProcessKind::Service),
+            "job" => Ok(ProcessKind::Job),
+            _ => Err(Error::Other(format!("Unknown process kind '{}': expected service or job", kind))),
+        }
+    }
+}
+
+/// Where a process's log goes when no log directory is given for it
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "http-api", derive(utoipa::ToSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum LogDirMode {
+    /// The configured default log directory
+    #[default]
+    Default,
+    /// `logs/` inside the process's working directory, worked out again on every start
+    Workdir,
+}
+
+impl std::fmt::Display for LogDirMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LogDirMode::Default => write!(f, "default"),
+            LogDirMode::Workdir => write!(f, "workdir"),
+        }
+    }
+}
+
+impl std::str::FromStr for LogDirMode {
+    type Err = Error;
+
+    fn from_str(mode: &str) -> Result<Self> {
+        match mode {
+            "default" => Ok(LogDirMode::Default),
+            "workdir" => Ok(LogDirMode::Workdir),
+            _ => Err(Error::Other(format!("Unknown log directory mode '{}': expected default or workdir", mode))),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -38,26 +514,244 @@ pub enum ProcessStatus {
     Stopped,
     Failed,
     Unknown,
+    /// A job that exited with code 0
+    Succeeded,
 }
 
 impl std::fmt::Display for ProcessStatus {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            ProcessStatus::Running => write!(f, "running"),
-            ProcessStatus::Stopped => write!(f, "stopped"),
-            ProcessStatus::Failed => write!(f, "failed"),
-            ProcessStatus::Unknown => write!(f, "unknown"),
+        // pad() so table columns like `{:<10}` line up
+        f.pad(match self {
+            ProcessStatus::Running => "running",
+            ProcessStatus::Stopped => "stopped",
+            ProcessStatus::Failed => "failed",
+            ProcessStatus::Unknown => "unknown",
+            ProcessStatus::Succeeded => "succeeded",
+        })
+    }
+}
+
+/// Number of processes in each status
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "http-api", derive(utoipa::ToSchema))]
+pub struct ProcessCounts {
+    pub running: usize,
+    pub stopped: usize,
+    pub failed: usize,
+    pub unknown: usize,
+    /// Jobs that completed successfully
+    #[serde(default)]
+    pub succeeded: usize,
+    pub total: usize,
+}
+
+impl ProcessCounts {
+    pub fn from_processes(processes: &[ProcessRecord]) -> Self {
+        let mut counts = Self::default();
+        for process in processes {
+            counts.add(&process.status, 1);
+        }
+        counts
+    }
+
+    pub(crate) fn add(&mut self, status: &ProcessStatus, count: usize) {
+        match status {
+            ProcessStatus::Running => self.running += count,
+            ProcessStatus::Stopped => self.stopped += count,
+            ProcessStatus::Failed => self.failed += count,
+            ProcessStatus::Unknown => self.unknown += count,
+            ProcessStatus::Succeeded => self.succeeded += count,
+        }
+        self.total += count;
+    }
+}
+
+impl std::fmt::Display for ProcessCounts {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} running, {} stopped, {} failed", self.running, self.stopped, self.failed)?;
+        if self.succeeded > 0 {
+            write!(f, ", {} succeeded", self.succeeded)?;
+        }
+        if self.unknown > 0 {
+            write!(f, ", {} unknown", self.unknown)?;
         }
+        write!(f, " ({} total)", self.total)
     }
 }
 
+/// Live process counts per status, with the oldest `updated_at` among the stopped and among
+/// the failed processes. Every status change bumps updated_at, so it stands for when a process
+/// entered the status it is in.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StatusSummary {
+    pub counts: ProcessCounts,
+    pub oldest_stopped: Option<DateTime<Utc>>,
+    pub oldest_failed: Option<DateTime<Utc>>,
+}
+
+impl StatusSummary {
+    /// Add one row of the aggregate query; `oldest` is the stored RFC 3339 text
+    pub(crate) fn add(&mut self, status: &ProcessStatus, count: usize, oldest: &str) -> Result<()> {
+        self.counts.add(status, count);
+        let oldest = DateTime::parse_from_rfc3339(oldest)
+            .map_err(|e| Error::Other(format!("Failed to parse updated_at: {}", e)))?
+            .with_timezone(&Utc);
+        match status {
+            ProcessStatus::Stopped => self.oldest_stopped = Some(oldest),
+            ProcessStatus::Failed => self.oldest_failed = Some(oldest),
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+/// One past rotation of a process's log
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "http-api", derive(utoipa::ToSchema))]
+pub struct LogRotationEntry {
+    pub process_name: String,
+    pub rotated_at: DateTime<Utc>,
+    /// What asked for the rotation: `size` at start, `manual`, `restart`, or `rerun`
+    pub trigger: String,
+    pub rotated_from_size: u64,
+    pub archives_shifted: usize,
+    /// Paths of archives deleted by this rotation
+    pub archives_deleted: Vec<String>,
+}
+
+/// One restart made because files a process watches changed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "http-api", derive(utoipa::ToSchema))]
+pub struct WatchRestartEntry {
+    pub process_name: String,
+    pub restarted_at: DateTime<Utc>,
+    /// The first file that changed
+    pub path: String,
+    /// Files that changed within the debounce window
+    pub changes: usize,
+}
+
+/// One run of a process, from its start until pmr saw it exit or stopped it; kept by name
+/// so the history survives restarts
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "http-api", derive(utoipa::ToSchema))]
+pub struct ProcessRun {
+    pub process_name: String,
+    pub started_at: DateTime<Utc>,
+    pub ended_at: DateTime<Utc>,
+    /// Status the run ended with
+    pub status: ProcessStatus,
+    /// None when the exit code was not seen, as for stops and processes pmr adopted
+    pub exit_code: Option<i32>,
+    pub duration_ms: u64,
+}
+
+impl ProcessRun {
+    pub fn new(process_name: &str, started_at: DateTime<Utc>, ended_at: DateTime<Utc>, status: ProcessStatus, exit_code: Option<i32>) -> Self {
+        Self {
+            process_name: process_name.to_string(),
+            started_at,
+            ended_at,
+            status,
+            exit_code,
+            duration_ms: (ended_at - started_at).num_milliseconds().max(0) as u64,
+        }
+    }
+
+    /// Mean duration of `runs`; None when there are none
+    pub fn average_duration_ms(runs: &[ProcessRun]) -> Option<u64> {
+        let total: u64 = runs.iter().map(|run| run.duration_ms).sum();
+        (!runs.is_empty()).then(|| total / runs.len() as u64)
+    }
+}
+
+/// Free-form note attached to a process; kept by name so it survives restarts
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "http-api", derive(utoipa::ToSchema))]
+pub struct ProcessNote {
+    pub id: i64,
+    pub process_name: String,
+    pub created_at: DateTime<Utc>,
+    /// Unix user for notes added from the CLI, token name for notes added over the API
+    pub author: String,
+    pub text: String,
+}
+
+/// Claim on a process name that nothing runs under yet, so provisioning tools can settle
+/// who creates a process before any start has side effects. Until it expires only a start
+/// by the same API token may use the name.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "http-api", derive(utoipa::ToSchema))]
+pub struct Reservation {
+    pub name: String,
+    /// Public id of the API token holding the name, e.g. `pmr_ab12cd34`
+    pub token: String,
+    pub reserved_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
 #[derive(Clone)]
 pub struct Database {
     pool: SqlitePool,
+    /// Set by [`Database::fail_status_writes`]; shared by clones
+    #[cfg(any(test, feature = "test-util"))]
+    failing_status_writes: std::sync::Arc<std::sync::atomic::AtomicBool>,
 }
 
 impl Database {
+    fn from_pool(pool: SqlitePool) -> Self {
+        Self {
+            pool,
+            #[cfg(any(test, feature = "test-util"))]
+            failing_status_writes: Default::default(),
+        }
+    }
+
+    /// Make the status writes of refreshes, exits and failure histories fail as they do on a
+    /// read-only filesystem, until called again with `false`
+    #[cfg(any(test, feature = "test-util"))]
+    pub fn fail_status_writes(&self, fail: bool) {
+        self.failing_status_writes.store(fail, std::sync::atomic::Ordering::SeqCst);
+    }
+
+    /// The error of a status write while [`Database::fail_status_writes`] is in effect
+    fn injected_write_failure(&self) -> Result<()> {
+        #[cfg(any(test, feature = "test-util"))]
+        if self.failing_status_writes.load(std::sync::atomic::Ordering::SeqCst) {
+            let error = std::io::Error::new(std::io::ErrorKind::ReadOnlyFilesystem, "injected status write failure");
+            return Err(Error::Database(sqlx::Error::Io(error)));
+        }
+        Ok(())
+    }
+
+    /// Open the database, running every pending migration
     pub async fn new(database_url: &str) -> Result<Self> {
+        Self::open(database_url, true).await
+    }
+
+    /// Open the database, migrating it to [`SCHEMA_VERSION`]. Fails with
+    /// [`Error::SchemaTooNew`] when a newer pmr wrote it, and with
+    /// [`Error::MigrationRequired`] when migrating would rebuild tables and that is not allowed.
+    /// A damaged file fails with [`Error::DatabaseCorrupt`]; see [`Self::salvage`].
+    #[tracing::instrument(name = "db.open", skip_all)]
+    pub async fn open(database_url: &str, allow_destructive_migrations: bool) -> Result<Self> {
+        Self::open_migrated(database_url, allow_destructive_migrations).await.map_err(open_error)
+    }
+
+    async fn open_migrated(database_url: &str, allow_destructive_migrations: bool) -> Result<Self> {
+        // Run migrations over a single short-lived connection first. Pooled connections
+        // that saw the schema before an ALTER TABLE would otherwise keep stale column
+        // metadata for `SELECT *` statements.
+        let migration_pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect(database_url).await
+            .map_err(|e| connect_error(e, || format!("Failed to connect to database at '{}'", database_url)))?;
+        let migration_db = Self::from_pool(migration_pool);
+        migration_db.check_schema(allow_destructive_migrations).await?;
+        migration_db.migrate().await?;
+        migration_db.record_schema_version().await?;
+        migration_db.pool.close().await;
+
         // Add more detailed error context for database connection
         // Configure connection pool for better concurrent performance
         let pool = SqlitePoolOptions::new()
@@ -66,13 +760,284 @@ impl Database {
             .acquire_timeout(std::time::Duration::from_secs(30)) // Longer timeout for high load
             .idle_timeout(std::time::Duration::from_secs(600))   // Keep connections alive longer
             .connect(database_url).await
-            .map_err(|e| Error::Other(format!("Failed to connect to database at '{}': {}", database_url, e)))?;
-        let db = Self { pool };
+            .map_err(|e| connect_error(e, || format!("Failed to connect to database at '{}'", database_url)))?;
+        let db = Self::from_pool(pool);
         db.configure_for_concurrency().await?;
-        db.migrate().await?;
         Ok(db)
     }
 
+    /// Open an existing database without migrating it; every write fails in SQLite itself
+    #[tracing::instrument(name = "db.open", skip_all)]
+    pub async fn new_read_only(database_path: &std::path::Path) -> Result<Self> {
+        Self::open_read_only(database_path).await.map_err(open_error)
+    }
+
+    async fn open_read_only(database_path: &std::path::Path) -> Result<Self> {
+        let database_url = format!("sqlite:{}?mode=ro", database_path.display());
+        let pool = SqlitePoolOptions::new()
+            .max_connections(100)
+            .acquire_timeout(std::time::Duration::from_secs(30))
+            .connect(&database_url).await
+            .map_err(|e| connect_error(e, || format!("Failed to open database at '{}' read-only", database_path.display())))?;
+        let db = Self::from_pool(pool);
+        let (version, min_binary_version) = db.stored_schema_version().await?;
+        if version > SCHEMA_VERSION {
+            return Err(Error::SchemaTooNew(version, SCHEMA_VERSION, min_binary_version.unwrap_or_default()));
+        }
+        Ok(db)
+    }
+
+    /// Compare the schema of the database at `database_path` with this binary, without
+    /// changing it; a missing file reads as an empty database
+    pub async fn inspect(database_path: &std::path::Path) -> Result<SchemaInfo> {
+        let mut info = SchemaInfo {
+            database: database_path.to_path_buf(),
+            schema_version: 0,
+            min_binary_version: None,
+            supported_version: SCHEMA_VERSION,
+            binary_version: env!("CARGO_PKG_VERSION").to_string(),
+            destructive_migrations: Vec::new(),
+        };
+        if !database_path.exists() {
+            return Ok(info);
+        }
+
+        let database_url = format!("sqlite:{}?mode=ro", database_path.display());
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect(&database_url).await
+            .map_err(|e| connect_error(e, || format!("Failed to open database at '{}' read-only", database_path.display())))?;
+        let db = Self::from_pool(pool);
+        (info.schema_version, info.min_binary_version) = db.stored_schema_version().await.map_err(open_error)?;
+        if info.migrations_pending() {
+            info.destructive_migrations = db.destructive_migrations().await.map_err(open_error)?;
+        }
+        db.pool.close().await;
+        Ok(info)
+    }
+
+    /// Schema version and minimum release recorded in the database; version 0 when none is
+    async fn stored_schema_version(&self) -> Result<(i64, Option<String>)> {
+        let table = sqlx::query("SELECT name FROM sqlite_master WHERE type = 'table' AND name = 'schema_version'")
+            .fetch_optional(&self.pool)
+            .await?;
+        if table.is_none() {
+            return Ok((0, None));
+        }
+
+        let row = sqlx::query("SELECT version, min_binary_version FROM schema_version WHERE id = 1")
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(row.map_or((0, None), |row| (row.get("version"), row.get("min_binary_version"))))
+    }
+
+    /// Refuse a database written by a newer pmr, and migrations that rebuild tables
+    /// unless they are allowed
+    async fn check_schema(&self, allow_destructive_migrations: bool) -> Result<()> {
+        let (version, min_binary_version) = self.stored_schema_version().await?;
+        if version > SCHEMA_VERSION {
+            return Err(Error::SchemaTooNew(version, SCHEMA_VERSION, min_binary_version.unwrap_or_default()));
+        }
+        if version < SCHEMA_VERSION && !allow_destructive_migrations {
+            let destructive = self.destructive_migrations().await?;
+            if !destructive.is_empty() {
+                return Err(Error::MigrationRequired(version, destructive));
+            }
+        }
+        Ok(())
+    }
+
+    /// Pending migrations that recreate a table rather than only adding to it
+    async fn destructive_migrations(&self) -> Result<Vec<String>> {
+        let table_info = sqlx::query("PRAGMA table_info(processes)")
+            .fetch_all(&self.pool)
+            .await?;
+        let has_column = |column: &str| table_info.iter().any(|row| row.get::<String, _>("name") == column);
+
+        let mut migrations = Vec::new();
+        if table_info.is_empty() {
+            return Ok(migrations);
+        }
+        if (has_column("stdout_path") || has_column("stderr_path")) && !has_column("log_path") {
+            migrations.push("rebuild processes to merge stdout_path and stderr_path into log_path".to_string());
+        }
+        if !has_column("deleted_at") {
+            migrations.push("rebuild processes for soft delete, replacing the unique name constraint".to_string());
+        }
+        Ok(migrations)
+    }
+
+    /// Record that the schema is at [`SCHEMA_VERSION`], once migrations have run
+    async fn record_schema_version(&self) -> Result<()> {
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS schema_version (
+                id INTEGER PRIMARY KEY CHECK (id = 1),
+                version INTEGER NOT NULL,
+                min_binary_version TEXT NOT NULL,
+                updated_at TEXT NOT NULL
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO schema_version (id, version, min_binary_version, updated_at) VALUES (1, ?, ?, ?)
+            ON CONFLICT(id) DO UPDATE SET
+                version = excluded.version,
+                min_binary_version = excluded.min_binary_version,
+                updated_at = excluded.updated_at
+            WHERE version < excluded.version
+            "#,
+        )
+        .bind(SCHEMA_VERSION)
+        .bind(MIN_BINARY_VERSION)
+        .bind(Utc::now().to_rfc3339())
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Close every connection, which checkpoints the write-ahead log into the database file
+    pub async fn close(&self) {
+        self.pool.close().await;
+    }
+
+    /// Move the damaged database at `database_path` aside to
+    /// `<name>.corrupt-<timestamp>`, create a fresh one in its place and copy into it every
+    /// row that can still be read. Tables that fail to copy in one go are copied a row at a
+    /// time, so one bad page loses only the rows on it.
+    pub async fn salvage(database_path: &std::path::Path) -> Result<SalvageReport> {
+        if !database_path.exists() {
+            return Err(Error::Io(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("No database at '{}'", database_path.display()),
+            )));
+        }
+        let mut backup = database_path.as_os_str().to_owned();
+        backup.push(format!(".corrupt-{}", Utc::now().format("%Y%m%dT%H%M%SZ")));
+        let backup = std::path::PathBuf::from(backup);
+        std::fs::rename(database_path, &backup)?;
+        // The write-ahead log holds the latest changes, so it goes along with the file
+        for suffix in ["-wal", "-shm"] {
+            let mut from = database_path.as_os_str().to_owned();
+            from.push(suffix);
+            let mut to = backup.as_os_str().to_owned();
+            to.push(suffix);
+            if std::path::Path::new(&from).exists() {
+                std::fs::rename(&from, &to)?;
+            }
+        }
+
+        crate::permissions::create_private_file_if_missing(database_path)?;
+        let fresh = Self::new(&format!("sqlite:{}?mode=rwc", database_path.display())).await?;
+        let mut conn = fresh.pool.acquire().await?;
+        // Once the damaged file is attached every statement reads its schema, and fails if
+        // that is what is damaged, so the fresh schema is read first. The schema version is
+        // this binary's, recorded when the fresh database was created.
+        let mut tables = Vec::new();
+        for table in sqlx::query_scalar::<_, String>(
+            "SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%' AND name != 'schema_version' ORDER BY name",
+        )
+        .fetch_all(&mut *conn)
+        .await?
+        {
+            let columns: Vec<String> = sqlx::query_scalar("SELECT name FROM pragma_table_info(?)")
+                .bind(&table)
+                .fetch_all(&mut *conn)
+                .await?;
+            tables.push((table, columns));
+        }
+
+        let attached = sqlx::query("ATTACH DATABASE ? AS damaged")
+            .bind(backup.to_string_lossy().to_string())
+            .execute(&mut *conn)
+            .await;
+        // A file too damaged to attach has nothing readable, and the reason stands in for
+        // the integrity check
+        let (integrity_check, present) = match attached {
+            Ok(_) => {
+                let integrity_check = sqlx::query_scalar::<_, String>(&format!("PRAGMA damaged.integrity_check({})", SALVAGE_INTEGRITY_LINES))
+                    .fetch_all(&mut *conn)
+                    .await
+                    .unwrap_or_else(|e| vec![e.to_string()]);
+                let present = sqlx::query_scalar::<_, String>("SELECT name FROM damaged.sqlite_master WHERE type = 'table'")
+                    .fetch_all(&mut *conn)
+                    .await;
+                (integrity_check, present)
+            }
+            Err(e) => (vec![e.to_string()], Err(e)),
+        };
+        let mut report = SalvageReport { database: database_path.to_path_buf(), backup, tables: Vec::new(), integrity_check };
+        match present {
+            Ok(present) => {
+                for (table, columns) in tables.into_iter().filter(|(table, _)| present.contains(table)) {
+                    report.tables.push(Self::salvage_table(&mut conn, table, &columns).await);
+                }
+            }
+            Err(_) => {
+                report.tables = tables
+                    .into_iter()
+                    .map(|(table, _)| TableSalvage { table, recovered: 0, lost: None })
+                    .collect();
+            }
+        }
+
+        // Closing the connection rather than detaching, which needs a readable schema too
+        conn.close().await?;
+        fresh.close().await;
+        Ok(report)
+    }
+
+    /// Copy the readable rows of `table` from the attached damaged database, in one go when
+    /// the table reads cleanly and a row at a time when it does not
+    async fn salvage_table(conn: &mut sqlx::SqliteConnection, table: String, columns: &[String]) -> TableSalvage {
+        let mut salvaged = TableSalvage { table, recovered: 0, lost: None };
+        let Ok(present) = sqlx::query_scalar::<_, String>("SELECT name FROM pragma_table_info(?, 'damaged')")
+            .bind(&salvaged.table)
+            .fetch_all(&mut *conn)
+            .await
+        else {
+            return salvaged;
+        };
+        // Columns added since the damaged file was written keep their defaults
+        let shared = columns
+            .iter()
+            .filter(|column| present.contains(column))
+            .map(|column| format!("\"{}\"", column))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let table = &salvaged.table;
+        // The salvaged database keeps the instance id of the damaged one
+        let insert = if table == "meta" { "INSERT OR REPLACE" } else { "INSERT OR IGNORE" };
+        let copy = format!("{insert} INTO main.\"{table}\" ({shared}) SELECT {shared} FROM damaged.\"{table}\"");
+
+        if let Ok(done) = sqlx::query(&copy).execute(&mut *conn).await {
+            salvaged.recovered = done.rows_affected() as usize;
+            salvaged.lost = Some(0);
+            return salvaged;
+        }
+        let Ok(max_rowid) = sqlx::query_scalar::<_, Option<i64>>(&format!("SELECT max(rowid) FROM damaged.\"{}\"", table))
+            .fetch_one(&mut *conn)
+            .await
+        else {
+            return salvaged;
+        };
+        // Each row commits on its own: SQLite may roll back a whole transaction on corruption
+        let copy_row = format!("{} WHERE rowid = ?", copy);
+        let mut lost = 0;
+        for rowid in 1..=max_rowid.unwrap_or(0).min(SALVAGE_MAX_ROWIDS) {
+            match sqlx::query(&copy_row).bind(rowid).execute(&mut *conn).await {
+                Ok(done) => salvaged.recovered += done.rows_affected() as usize,
+                Err(_) => lost += 1,
+            }
+        }
+        salvaged.lost = Some(lost);
+        salvaged
+    }
+
     async fn configure_for_concurrency(&self) -> Result<()> {
         // Configure SQLite for better concurrent performance
         sqlx::query("PRAGMA journal_mode = WAL").execute(&self.pool).await?;
@@ -88,6 +1053,22 @@ impl Database {
         // Migrate processes table
         self.migrate_processes_table().await?;
 
+        self.migrate_log_rotations_table().await?;
+
+        self.migrate_process_notes_table().await?;
+
+        self.migrate_process_failures_table().await?;
+
+        self.migrate_watch_restarts_table().await?;
+
+        self.migrate_process_runs_table().await?;
+
+        self.migrate_logs_quota_table().await?;
+
+        self.migrate_reservations_table().await?;
+
+        self.migrate_meta_table().await?;
+
         // Migrate API tokens table (if http-api feature is enabled)
         #[cfg(feature = "http-api")]
         self.migrate_api_tokens_table().await?;
@@ -95,85 +1076,387 @@ impl Database {
         Ok(())
     }
 
-    async fn migrate_processes_table(&self) -> Result<()> {
-        // Check if the table exists and what columns it has
-        let table_info = sqlx::query("PRAGMA table_info(processes)")
-            .fetch_all(&self.pool)
-            .await
-            .unwrap_or_default();
-
-        let has_old_columns = table_info.iter().any(|row| {
-            let column_name: String = row.get("name");
-            column_name == "stdout_path" || column_name == "stderr_path"
-        });
-
-        let has_new_column = table_info.iter().any(|row| {
-            let column_name: String = row.get("name");
-            column_name == "log_path"
-        });
+    async fn migrate_processes_table(&self) -> Result<()> {
+        // Check if the table exists and what columns it has
+        let table_info = sqlx::query("PRAGMA table_info(processes)")
+            .fetch_all(&self.pool)
+            .await
+            .unwrap_or_default();
+
+        let has_old_columns = table_info.iter().any(|row| {
+            let column_name: String = row.get("name");
+            column_name == "stdout_path" || column_name == "stderr_path"
+        });
+
+        let has_new_column = table_info.iter().any(|row| {
+            let column_name: String = row.get("name");
+            column_name == "log_path"
+        });
+
+        if has_old_columns && !has_new_column {
+            // Need to migrate from old schema to new schema
+            // Create new table
+            sqlx::query(
+                r#"
+                CREATE TABLE processes_new (
+                    id TEXT PRIMARY KEY,
+                    name TEXT UNIQUE NOT NULL,
+                    command TEXT NOT NULL,
+                    args TEXT NOT NULL,
+                    env_vars TEXT NOT NULL,
+                    working_dir TEXT NOT NULL,
+                    pid INTEGER,
+                    status TEXT NOT NULL,
+                    created_at TEXT NOT NULL,
+                    updated_at TEXT NOT NULL,
+                    log_path TEXT NOT NULL
+                )
+                "#,
+            )
+            .execute(&self.pool)
+            .await?;
+
+            // Copy data from old table, using stdout_path as log_path
+            sqlx::query(
+                r#"
+                INSERT INTO processes_new
+                SELECT id, name, command, args, env_vars, working_dir, pid, status,
+                       created_at, updated_at, stdout_path as log_path
+                FROM processes
+                "#,
+            )
+            .execute(&self.pool)
+            .await?;
+
+            // Drop old table and rename new one
+            sqlx::query("DROP TABLE processes").execute(&self.pool).await?;
+            sqlx::query("ALTER TABLE processes_new RENAME TO processes").execute(&self.pool).await?;
+        } else if table_info.is_empty() {
+            // Create new table from scratch
+            sqlx::query(
+                r#"
+                CREATE TABLE processes (
+                    id TEXT PRIMARY KEY,
+                    name TEXT UNIQUE NOT NULL,
+                    command TEXT NOT NULL,
+                    args TEXT NOT NULL,
+                    env_vars TEXT NOT NULL,
+                    working_dir TEXT NOT NULL,
+                    pid INTEGER,
+                    status TEXT NOT NULL,
+                    created_at TEXT NOT NULL,
+                    updated_at TEXT NOT NULL,
+                    log_path TEXT NOT NULL
+                )
+                "#,
+            )
+            .execute(&self.pool)
+            .await?;
+        }
+        // If has_new_column is true, table is already in the correct format
+
+        // Columns added after the log_path schema
+        self.ensure_processes_column("cgroup_memory_max", "INTEGER").await?;
+        self.ensure_processes_column("cgroup_cpu_max", "INTEGER").await?;
+        if self.ensure_processes_column("log_dir", "TEXT").await? {
+            self.backfill_log_dirs().await?;
+        }
+        if !self.has_processes_column("deleted_at").await? {
+            self.migrate_to_soft_delete().await?;
+        }
+        self.ensure_processes_column("binary_path", "TEXT").await?;
+        self.ensure_processes_column("binary_sha256", "TEXT").await?;
+        self.ensure_processes_column("canonical_workdir", "TEXT").await?;
+        self.ensure_processes_column("exit_code", "INTEGER").await?;
+        self.ensure_processes_column("exit_reason", "TEXT").await?;
+        self.ensure_processes_column("cpu_affinity", "TEXT").await?;
+        self.ensure_processes_column("start_snapshot", "TEXT").await?;
+        self.ensure_processes_column("default_env_keys", "TEXT").await?;
+        self.ensure_processes_column("kind", "TEXT").await?;
+        self.ensure_processes_column("runs", "INTEGER").await?;
+        self.ensure_processes_column("stop_signal", "INTEGER").await?;
+        self.ensure_processes_column("log_dir_mode", "TEXT").await?;
+        self.ensure_processes_column("status_verified_at", "TEXT").await?;
+        if self.ensure_processes_column("internal", "INTEGER NOT NULL DEFAULT 0").await? {
+            // Before the column the HTTP server was told apart by its name only
+            sqlx::query("UPDATE processes SET internal = 1 WHERE name = ?")
+                .bind(HTTP_SERVER_PROCESS_NAME)
+                .execute(&self.pool)
+                .await?;
+        }
+        self.ensure_processes_column("ephemeral_keep_secs", "INTEGER").await?;
+        self.ensure_processes_column("delete_after", "TEXT").await?;
+        self.ensure_processes_column("last_output", "TEXT").await?;
+        self.ensure_processes_column("last_output_lossy", "INTEGER NOT NULL DEFAULT 0").await?;
+        self.ensure_processes_column("watch", "TEXT").await?;
+        self.ensure_processes_column("umask", "TEXT").await?;
+        self.ensure_processes_column("description", "TEXT").await?;
+        self.ensure_processes_column("rotate_on_start", "INTEGER").await?;
+        self.ensure_processes_indexes().await?;
+
+        Ok(())
+    }
+
+    /// Indexes for the status, time and name+status filters. Live-record indexes are
+    /// partial so they stay small and the planner does not mistake `deleted_at IS NULL`
+    /// for a selective condition.
+    async fn ensure_processes_indexes(&self) -> Result<()> {
+        const INDEXES: [&str; 6] = [
+            "CREATE INDEX IF NOT EXISTS idx_processes_live_status ON processes(status, created_at) WHERE deleted_at IS NULL",
+            "CREATE INDEX IF NOT EXISTS idx_processes_live_created_at ON processes(created_at) WHERE deleted_at IS NULL",
+            "CREATE INDEX IF NOT EXISTS idx_processes_updated_at ON processes(updated_at)",
+            "CREATE INDEX IF NOT EXISTS idx_processes_name_status ON processes(name, status)",
+            "CREATE INDEX IF NOT EXISTS idx_processes_deleted_at ON processes(deleted_at) WHERE deleted_at IS NOT NULL",
+            "CREATE INDEX IF NOT EXISTS idx_processes_delete_after ON processes(delete_after) WHERE delete_after IS NOT NULL",
+        ];
+        for index in INDEXES {
+            sqlx::query(index).execute(&self.pool).await?;
+        }
+        Ok(())
+    }
+
+    async fn has_processes_column(&self, column: &str) -> Result<bool> {
+        let table_info = sqlx::query("PRAGMA table_info(processes)")
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(table_info.iter().any(|row| {
+            let column_name: String = row.get("name");
+            column_name == column
+        }))
+    }
+
+    /// Rebuild the processes table with a `deleted_at` column. Names only have to be
+    /// unique among live records, so the UNIQUE constraint on `name` is replaced by a
+    /// partial index, which SQLite can only do by recreating the table.
+    async fn migrate_to_soft_delete(&self) -> Result<()> {
+        const COLUMNS: &str = "id, name, command, args, env_vars, working_dir, pid, status, \
+            created_at, updated_at, log_path, cgroup_memory_max, cgroup_cpu_max, log_dir";
+
+        let mut tx = self.pool.begin().await?;
+        sqlx::query(
+            r#"
+            CREATE TABLE processes_new (
+                id TEXT PRIMARY KEY,
+                name TEXT NOT NULL,
+                command TEXT NOT NULL,
+                args TEXT NOT NULL,
+                env_vars TEXT NOT NULL,
+                working_dir TEXT NOT NULL,
+                pid INTEGER,
+                status TEXT NOT NULL,
+                created_at TEXT NOT NULL,
+                updated_at TEXT NOT NULL,
+                log_path TEXT NOT NULL,
+                cgroup_memory_max INTEGER,
+                cgroup_cpu_max INTEGER,
+                log_dir TEXT,
+                deleted_at TEXT
+            )
+            "#,
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query(&format!(
+            "INSERT INTO processes_new ({columns}) SELECT {columns} FROM processes",
+            columns = COLUMNS
+        ))
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query("DROP TABLE processes").execute(&mut *tx).await?;
+        sqlx::query("ALTER TABLE processes_new RENAME TO processes").execute(&mut *tx).await?;
+        sqlx::query(
+            "CREATE UNIQUE INDEX idx_processes_live_name ON processes(name) WHERE deleted_at IS NULL"
+        )
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Add a column to the processes table if it is missing, returning whether it was added
+    async fn ensure_processes_column(&self, column: &str, definition: &str) -> Result<bool> {
+        if self.has_processes_column(column).await? {
+            return Ok(false);
+        }
+
+        sqlx::query(&format!("ALTER TABLE processes ADD COLUMN {} {}", column, definition))
+            .execute(&self.pool)
+            .await?;
+
+        Ok(true)
+    }
+
+    /// Records created before log_dir was stored keep using the directory of their log file
+    async fn backfill_log_dirs(&self) -> Result<()> {
+        let rows = sqlx::query("SELECT id, log_path FROM processes")
+            .fetch_all(&self.pool)
+            .await?;
+
+        for row in rows {
+            let id: String = row.get("id");
+            let log_path: String = row.get("log_path");
+            let log_dir = std::path::Path::new(&log_path)
+                .parent()
+                .map(|p| p.to_string_lossy().to_string());
+
+            sqlx::query("UPDATE processes SET log_dir = ? WHERE id = ?")
+                .bind(log_dir)
+                .bind(id)
+                .execute(&self.pool)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn migrate_log_rotations_table(&self) -> Result<()> {
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS log_rotations (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                process_name TEXT NOT NULL,
+                rotated_at TEXT NOT NULL,
+                trigger TEXT NOT NULL,
+                rotated_from_size INTEGER NOT NULL,
+                archives_shifted INTEGER NOT NULL,
+                archives_deleted TEXT NOT NULL
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_log_rotations_process ON log_rotations(process_name)")
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn migrate_process_notes_table(&self) -> Result<()> {
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS process_notes (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                process_name TEXT NOT NULL,
+                created_at TEXT NOT NULL,
+                author TEXT NOT NULL,
+                text TEXT NOT NULL
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_process_notes_process ON process_notes(process_name)")
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn migrate_process_failures_table(&self) -> Result<()> {
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS process_failures (
+                process_name TEXT PRIMARY KEY,
+                failures TEXT NOT NULL,
+                flapping_since TEXT
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
 
-        if has_old_columns && !has_new_column {
-            // Need to migrate from old schema to new schema
-            // Create new table
-            sqlx::query(
-                r#"
-                CREATE TABLE processes_new (
-                    id TEXT PRIMARY KEY,
-                    name TEXT UNIQUE NOT NULL,
-                    command TEXT NOT NULL,
-                    args TEXT NOT NULL,
-                    env_vars TEXT NOT NULL,
-                    working_dir TEXT NOT NULL,
-                    pid INTEGER,
-                    status TEXT NOT NULL,
-                    created_at TEXT NOT NULL,
-                    updated_at TEXT NOT NULL,
-                    log_path TEXT NOT NULL
-                )
-                "#,
+    async fn migrate_watch_restarts_table(&self) -> Result<()> {
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS watch_restarts (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                process_name TEXT NOT NULL,
+                restarted_at TEXT NOT NULL,
+                path TEXT NOT NULL,
+                changes INTEGER NOT NULL
             )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_watch_restarts_process ON watch_restarts(process_name)")
             .execute(&self.pool)
             .await?;
+        Ok(())
+    }
 
-            // Copy data from old table, using stdout_path as log_path
-            sqlx::query(
-                r#"
-                INSERT INTO processes_new
-                SELECT id, name, command, args, env_vars, working_dir, pid, status,
-                       created_at, updated_at, stdout_path as log_path
-                FROM processes
-                "#,
+    async fn migrate_process_runs_table(&self) -> Result<()> {
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS process_runs (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                process_name TEXT NOT NULL,
+                started_at TEXT NOT NULL,
+                ended_at TEXT NOT NULL,
+                status TEXT NOT NULL,
+                exit_code INTEGER,
+                duration_ms INTEGER NOT NULL
             )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+        // The reaper and a status refresh can both see the same exit; the run is kept once
+        sqlx::query("CREATE UNIQUE INDEX IF NOT EXISTS idx_process_runs_process ON process_runs(process_name, started_at)")
             .execute(&self.pool)
             .await?;
+        Ok(())
+    }
 
-            // Drop old table and rename new one
-            sqlx::query("DROP TABLE processes").execute(&self.pool).await?;
-            sqlx::query("ALTER TABLE processes_new RENAME TO processes").execute(&self.pool).await?;
-        } else if table_info.is_empty() {
-            // Create new table from scratch
-            sqlx::query(
-                r#"
-                CREATE TABLE processes (
-                    id TEXT PRIMARY KEY,
-                    name TEXT UNIQUE NOT NULL,
-                    command TEXT NOT NULL,
-                    args TEXT NOT NULL,
-                    env_vars TEXT NOT NULL,
-                    working_dir TEXT NOT NULL,
-                    pid INTEGER,
-                    status TEXT NOT NULL,
-                    created_at TEXT NOT NULL,
-                    updated_at TEXT NOT NULL,
-                    log_path TEXT NOT NULL
-                )
-                "#,
-            )
+    /// Settings of the database itself. Its instance id is drawn once, when the table is
+    /// created, so it tells databases apart however they are named or moved.
+    async fn migrate_meta_table(&self) -> Result<()> {
+        sqlx::query("CREATE TABLE IF NOT EXISTS meta (key TEXT PRIMARY KEY, value TEXT NOT NULL)")
             .execute(&self.pool)
             .await?;
-        }
-        // If has_new_column is true, table is already in the correct format
+        sqlx::query("INSERT OR IGNORE INTO meta (key, value) VALUES ('instance_id', ?)")
+            .bind(uuid::Uuid::new_v4().to_string())
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn migrate_logs_quota_table(&self) -> Result<()> {
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS logs_quota_enforcements (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                enforced_at TEXT NOT NULL,
+                quota_bytes INTEGER NOT NULL,
+                used_before INTEGER NOT NULL,
+                used_after INTEGER NOT NULL,
+                deleted TEXT NOT NULL
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
 
+    async fn migrate_reservations_table(&self) -> Result<()> {
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS reservations (
+                name TEXT PRIMARY KEY,
+                token TEXT NOT NULL,
+                reserved_at TEXT NOT NULL,
+                expires_at TEXT NOT NULL
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
         Ok(())
     }
 
@@ -204,25 +1487,76 @@ impl Database {
             .execute(&self.pool)
             .await?;
 
-            // Create index on token for faster lookups
-            sqlx::query("CREATE INDEX idx_api_tokens_token ON api_tokens(token)")
-                .execute(&self.pool)
-                .await?;
         }
 
+        let has_public_id = sqlx::query("PRAGMA table_info(api_tokens)")
+            .fetch_all(&self.pool)
+            .await?
+            .iter()
+            .any(|row| row.get::<String, _>("name") == "public_id");
+        if !has_public_id {
+            sqlx::query("ALTER TABLE api_tokens ADD COLUMN public_id TEXT").execute(&self.pool).await?;
+        }
+
+        // Tokens from before scopes are not admin tokens
+        let has_admin = sqlx::query("PRAGMA table_info(api_tokens)")
+            .fetch_all(&self.pool)
+            .await?
+            .iter()
+            .any(|row| row.get::<String, _>("name") == "admin");
+        if !has_admin {
+            sqlx::query("ALTER TABLE api_tokens ADD COLUMN admin INTEGER NOT NULL DEFAULT 0").execute(&self.pool).await?;
+        }
+
+        // Tokens from before public ids get one derived from their value; they keep working
+        // as they are, only listings and logs name them by it
+        let unnamed: Vec<(String, String)> = sqlx::query_as("SELECT id, token FROM api_tokens WHERE public_id IS NULL")
+            .fetch_all(&self.pool)
+            .await?;
+        if !unnamed.is_empty() {
+            let mut taken: std::collections::HashSet<String> = sqlx::query_scalar("SELECT public_id FROM api_tokens WHERE public_id IS NOT NULL")
+                .fetch_all(&self.pool)
+                .await?
+                .into_iter()
+                .collect();
+            for (id, token) in unnamed {
+                let public_id = derive_token_id(&token, &taken);
+                sqlx::query("UPDATE api_tokens SET public_id = ? WHERE id = ?")
+                    .bind(&public_id)
+                    .bind(&id)
+                    .execute(&self.pool)
+                    .await?;
+                taken.insert(public_id);
+            }
+        }
+
+        // Lookups go by public id; the full value is only compared once the row is found
+        sqlx::query("CREATE UNIQUE INDEX IF NOT EXISTS idx_api_tokens_public_id ON api_tokens(public_id)")
+            .execute(&self.pool)
+            .await?;
+        sqlx::query("DROP INDEX IF EXISTS idx_api_tokens_token").execute(&self.pool).await?;
+
         Ok(())
     }
 
+    #[tracing::instrument(name = "db.query", skip_all)]
     pub async fn insert_process(&self, process: &ProcessRecord) -> Result<()> {
         let args_json = serde_json::to_string(&process.args)?;
         let env_vars_json = serde_json::to_string(&process.env_vars)?;
+        let start_snapshot_json = process.start_snapshot.as_ref().map(serde_json::to_string).transpose()?;
+        let default_env_keys_json = serde_json::to_string(&process.default_env_keys)?;
+        let last_output_json = (!process.last_output.is_empty()).then(|| serde_json::to_string(&process.last_output)).transpose()?;
+        let watch_json = process.watch.as_ref().map(serde_json::to_string).transpose()?;
 
         sqlx::query(
             r#"
             INSERT INTO processes (
                 id, name, command, args, env_vars, working_dir, pid, status,
-                created_at, updated_at, log_path
-            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+                created_at, updated_at, log_path, log_dir, cgroup_memory_max, cgroup_cpu_max,
+                binary_path, binary_sha256, canonical_workdir, exit_code, exit_reason, cpu_affinity,
+                start_snapshot, default_env_keys, kind, runs, stop_signal, log_dir_mode, status_verified_at, internal,
+                ephemeral_keep_secs, delete_after, last_output, last_output_lossy, watch, umask, description, rotate_on_start
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
             "#,
         )
         .bind(&process.id)
@@ -236,44 +1570,420 @@ impl Database {
         .bind(process.created_at.to_rfc3339())
         .bind(process.updated_at.to_rfc3339())
         .bind(&process.log_path)
+        .bind(&process.log_dir)
+        .bind(process.cgroup_memory_max.map(|m| m as i64))
+        .bind(process.cgroup_cpu_max.map(|c| c as i64))
+        .bind(&process.binary_path)
+        .bind(&process.binary_sha256)
+        .bind(&process.canonical_workdir)
+        .bind(process.exit_code)
+        .bind(&process.exit_reason)
+        .bind(&process.cpu_affinity)
+        .bind(start_snapshot_json)
+        .bind(default_env_keys_json)
+        .bind(process.kind.to_string())
+        .bind(process.runs as i64)
+        .bind(process.stop_signal)
+        .bind(process.log_dir_mode.to_string())
+        .bind(process.status_verified_at.map(|at| at.to_rfc3339()))
+        .bind(process.internal)
+        .bind(process.ephemeral_keep_secs.map(|secs| secs as i64))
+        .bind(process.delete_after.map(|at| at.to_rfc3339()))
+        .bind(last_output_json)
+        .bind(process.last_output_lossy)
+        .bind(watch_json)
+        .bind(&process.umask)
+        .bind(&process.description)
+        .bind(process.rotate_on_start)
         .execute(&self.pool)
         .await?;
 
         Ok(())
     }
 
-    pub async fn get_process_by_name(&self, name: &str) -> Result<Option<ProcessRecord>> {
-        let row = sqlx::query("SELECT * FROM processes WHERE name = ?")
+    #[tracing::instrument(name = "db.query", skip_all)]
+    pub async fn get_process_by_name(&self, name: &ProcessName) -> Result<Option<ProcessRecord>> {
+        let row = sqlx::query("SELECT * FROM processes WHERE name = ? AND deleted_at IS NULL")
             .bind(name)
             .fetch_optional(&self.pool)
             .await?;
 
         if let Some(row) = row {
-            Ok(Some(self.row_to_process_record(row)?))
+            Ok(Some(Self::row_to_process_record(&row)?))
         } else {
             Ok(None)
         }
     }
 
+    #[tracing::instrument(name = "db.query", skip_all)]
     pub async fn get_all_processes(&self) -> Result<Vec<ProcessRecord>> {
-        let rows = sqlx::query("SELECT * FROM processes ORDER BY created_at DESC")
+        let rows = sqlx::query("SELECT * FROM processes WHERE deleted_at IS NULL ORDER BY created_at DESC")
             .fetch_all(&self.pool)
             .await?;
 
         let mut processes = Vec::new();
         for row in rows {
-            processes.push(self.row_to_process_record(row)?);
+            processes.push(Self::row_to_process_record(&row)?);
         }
         Ok(processes)
     }
 
-    pub async fn update_process_status(&self, name: &str, status: ProcessStatus, pid: Option<u32>) -> Result<()> {
+    /// Live processes whose name or description contains `text`, ignoring ASCII case, in
+    /// the order of [`Self::get_all_processes`]
+    #[tracing::instrument(name = "db.query", skip_all)]
+    pub async fn search_processes(&self, text: &str) -> Result<Vec<ProcessRecord>> {
+        // instr rather than LIKE, so `%` and `_` in the text match themselves
+        let rows = sqlx::query(
+            "SELECT * FROM processes WHERE deleted_at IS NULL \
+             AND (instr(lower(name), lower(?1)) > 0 OR instr(lower(coalesce(description, '')), lower(?1)) > 0) \
+             ORDER BY created_at DESC",
+        )
+        .bind(text)
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.into_iter().map(|row| Self::row_to_process_record(&row)).collect()
+    }
+
+    /// Names of the live processes that are not internal, sorted, optionally only those
+    /// starting with `prefix`. Reads the name column alone, for completion.
+    #[tracing::instrument(name = "db.query", skip_all)]
+    pub async fn get_process_names(&self, prefix: Option<&str>) -> Result<Vec<String>> {
+        // Gathered into one JSON array: fetching a row per name costs more than the query
+        let names: String = sqlx::query_scalar(
+            "SELECT json_group_array(name) FROM (SELECT name FROM processes \
+             WHERE deleted_at IS NULL AND internal = 0 AND substr(name, 1, length(?1)) = ?1 ORDER BY name)",
+        )
+        .bind(prefix.unwrap_or(""))
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(serde_json::from_str(&names)?)
+    }
+
+    /// Live processes in the order of [`Self::get_all_processes`], converted a row at a time
+    /// as the stream is polled, so memory does not grow with the table
+    pub fn stream_processes(&self) -> BoxStream<'_, Result<ProcessRecord>> {
+        self.stream_records(sqlx::query("SELECT * FROM processes WHERE deleted_at IS NULL ORDER BY created_at DESC"))
+    }
+
+    /// [`Self::get_deleted_processes_before`] as a stream
+    pub fn stream_deleted_processes_before(&self, cutoff: DateTime<Utc>) -> BoxStream<'_, Result<ProcessRecord>> {
+        self.stream_records(
+            sqlx::query("SELECT * FROM processes WHERE deleted_at IS NOT NULL AND deleted_at <= ? ORDER BY deleted_at ASC")
+                .bind(cutoff.to_rfc3339()),
+        )
+    }
+
+    /// Exited ephemeral processes whose deletion was due by `now`, oldest deadline first
+    pub fn stream_expired_ephemeral_processes(&self, now: DateTime<Utc>) -> BoxStream<'_, Result<ProcessRecord>> {
+        self.stream_records(
+            sqlx::query(
+                "SELECT * FROM processes WHERE deleted_at IS NULL AND delete_after IS NOT NULL AND delete_after <= ? AND status != ? ORDER BY delete_after ASC",
+            )
+            .bind(now.to_rfc3339())
+            .bind(ProcessStatus::Running.to_string()),
+        )
+    }
+
+    /// Name and live log path of every record, soft-deleted and internal ones included,
+    /// since their logs take up space all the same
+    #[tracing::instrument(name = "db.query", skip_all)]
+    pub async fn get_log_paths(&self) -> Result<Vec<(String, String)>> {
+        let rows = sqlx::query("SELECT name, log_path FROM processes ORDER BY name")
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(rows.iter().map(|row| (row.get("name"), row.get("log_path"))).collect())
+    }
+
+    /// Number of live records, internal ones included, whose log is at `log_path`; before
+    /// a log file is removed, to leave alone one another process still writes to
+    #[tracing::instrument(name = "db.query", skip_all)]
+    pub async fn count_processes_with_log_path(&self, log_path: &str) -> Result<usize> {
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM processes WHERE deleted_at IS NULL AND log_path = ?")
+            .bind(log_path)
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(count as usize)
+    }
+
+    /// Running processes that restart when watched paths change
+    #[tracing::instrument(name = "db.query", skip_all)]
+    pub async fn get_watched_processes(&self) -> Result<Vec<ProcessRecord>> {
+        let rows = sqlx::query("SELECT * FROM processes WHERE deleted_at IS NULL AND watch IS NOT NULL AND status = ? ORDER BY name")
+            .bind(ProcessStatus::Running.to_string())
+            .fetch_all(&self.pool)
+            .await?;
+        rows.into_iter().map(|row| Self::row_to_process_record(&row)).collect()
+    }
+
+    fn stream_records<'a>(&'a self, query: sqlx::query::Query<'a, sqlx::Sqlite, sqlx::sqlite::SqliteArguments<'a>>) -> BoxStream<'a, Result<ProcessRecord>> {
+        query.fetch(&self.pool).map(move |row| Self::row_to_process_record(&row?)).boxed()
+    }
+
+    /// Number of processes that are neither soft-deleted nor internal
+    #[tracing::instrument(name = "db.query", skip_all)]
+    pub async fn count_processes(&self) -> Result<usize> {
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM processes WHERE deleted_at IS NULL AND internal = 0")
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(count as usize)
+    }
+
+    /// Random id drawn when the database was created; None for a database opened read-only
+    /// that no pmr with instance ids has opened for writing yet
+    #[tracing::instrument(name = "db.query", skip_all)]
+    pub async fn instance_id(&self) -> Result<Option<String>> {
+        let table_exists = sqlx::query("SELECT name FROM sqlite_master WHERE type='table' AND name='meta'")
+            .fetch_optional(&self.pool)
+            .await?
+            .is_some();
+        if !table_exists {
+            return Ok(None);
+        }
+        Ok(sqlx::query_scalar("SELECT value FROM meta WHERE key = 'instance_id'").fetch_optional(&self.pool).await?)
+    }
+
+    /// Run a trivial query, to tell whether the database answers at all
+    #[tracing::instrument(name = "db.query", skip_all)]
+    pub async fn ping(&self) -> Result<()> {
+        sqlx::query("SELECT 1").execute(&self.pool).await?;
+        Ok(())
+    }
+
+    /// Count live processes per stored status without loading the records; internal
+    /// processes are not counted
+    #[tracing::instrument(name = "db.query", skip_all)]
+    pub async fn count_processes_by_status(&self) -> Result<ProcessCounts> {
+        let rows = sqlx::query(
+            "SELECT status, COUNT(*) AS count FROM processes WHERE deleted_at IS NULL AND internal = 0 GROUP BY status"
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut counts = ProcessCounts::default();
+        for row in rows {
+            let status: String = row.get("status");
+            let count: i64 = row.get("count");
+            counts.add(&Self::parse_status(&status), count as usize);
+        }
+        Ok(counts)
+    }
+
+    /// [`Self::count_processes_by_status`] together with when the longest-standing stopped
+    /// and failed processes entered their status, in one aggregate query
+    #[tracing::instrument(name = "db.query", skip_all)]
+    pub async fn summarize_processes_by_status(&self) -> Result<StatusSummary> {
+        let rows = sqlx::query(
+            "SELECT status, COUNT(*) AS count, MIN(updated_at) AS oldest FROM processes \
+             WHERE deleted_at IS NULL AND internal = 0 GROUP BY status"
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut summary = StatusSummary::default();
+        for row in rows {
+            let status: String = row.get("status");
+            let count: i64 = row.get("count");
+            let oldest: String = row.get("oldest");
+            summary.add(&Self::parse_status(&status), count as usize, &oldest)?;
+        }
+        Ok(summary)
+    }
+
+    /// Set the status and PID of a live process, stamping updated_at with `at`
+    #[tracing::instrument(name = "db.query", skip_all)]
+    pub async fn update_process_status(&self, name: &ProcessName, status: ProcessStatus, pid: Option<u32>, at: DateTime<Utc>) -> Result<()> {
+        self.injected_write_failure()?;
+        sqlx::query(
+            "UPDATE processes SET status = ?, pid = ?, updated_at = ? WHERE name = ? AND deleted_at IS NULL"
+        )
+        .bind(status.to_string())
+        .bind(pid.map(|p| p as i64))
+        .bind(at.to_rfc3339())
+        .bind(name)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Reset a live record for a restart in one transaction: read its definition as stored
+    /// right now and mark it stopped without a PID, keeping the row. None when there is no
+    /// such process. updated_at is stamped with `at`.
+    #[tracing::instrument(name = "db.query", skip_all)]
+    pub async fn reset_for_restart(&self, name: &ProcessName, at: DateTime<Utc>) -> Result<Option<ProcessRecord>> {
+        self.injected_write_failure()?;
+        let mut tx = self.pool.begin().await?;
+        let Some(row) = sqlx::query("SELECT * FROM processes WHERE name = ? AND deleted_at IS NULL")
+            .bind(name)
+            .fetch_optional(&mut *tx)
+            .await?
+        else {
+            return Ok(None);
+        };
+        let mut process = Self::row_to_process_record(&row)?;
+        process.status = ProcessStatus::Stopped;
+        process.pid = None;
+        process.updated_at = at;
+        sqlx::query("UPDATE processes SET status = ?, pid = NULL, updated_at = ? WHERE id = ?")
+            .bind(process.status.to_string())
+            .bind(process.updated_at.to_rfc3339())
+            .bind(&process.id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(Some(process))
+    }
+
+    /// Overwrite the live record named `process.name` with `process`, id included, in place;
+    /// false when there is no such process
+    #[tracing::instrument(name = "db.query", skip_all)]
+    pub async fn replace_process(&self, process: &ProcessRecord) -> Result<bool> {
+        let args_json = serde_json::to_string(&process.args)?;
+        let env_vars_json = serde_json::to_string(&process.env_vars)?;
+        let start_snapshot_json = process.start_snapshot.as_ref().map(serde_json::to_string).transpose()?;
+        let default_env_keys_json = serde_json::to_string(&process.default_env_keys)?;
+        let last_output_json = (!process.last_output.is_empty()).then(|| serde_json::to_string(&process.last_output)).transpose()?;
+        let watch_json = process.watch.as_ref().map(serde_json::to_string).transpose()?;
+
+        let result = sqlx::query(
+            r#"
+            UPDATE processes SET
+                id = ?, command = ?, args = ?, env_vars = ?, working_dir = ?, pid = ?, status = ?,
+                created_at = ?, updated_at = ?, log_path = ?, log_dir = ?, cgroup_memory_max = ?, cgroup_cpu_max = ?,
+                binary_path = ?, binary_sha256 = ?, canonical_workdir = ?, exit_code = ?, exit_reason = ?, cpu_affinity = ?,
+                start_snapshot = ?, default_env_keys = ?, kind = ?, runs = ?, stop_signal = ?, log_dir_mode = ?, status_verified_at = ?, internal = ?,
+                ephemeral_keep_secs = ?, delete_after = ?, last_output = ?, last_output_lossy = ?, watch = ?, umask = ?, description = ?,
+                rotate_on_start = ?
+            WHERE name = ? AND deleted_at IS NULL
+            "#,
+        )
+        .bind(&process.id)
+        .bind(&process.command)
+        .bind(&args_json)
+        .bind(&env_vars_json)
+        .bind(&process.working_dir)
+        .bind(process.pid.map(|p| p as i64))
+        .bind(process.status.to_string())
+        .bind(process.created_at.to_rfc3339())
+        .bind(process.updated_at.to_rfc3339())
+        .bind(&process.log_path)
+        .bind(&process.log_dir)
+        .bind(process.cgroup_memory_max.map(|m| m as i64))
+        .bind(process.cgroup_cpu_max.map(|c| c as i64))
+        .bind(&process.binary_path)
+        .bind(&process.binary_sha256)
+        .bind(&process.canonical_workdir)
+        .bind(process.exit_code)
+        .bind(&process.exit_reason)
+        .bind(&process.cpu_affinity)
+        .bind(start_snapshot_json)
+        .bind(default_env_keys_json)
+        .bind(process.kind.to_string())
+        .bind(process.runs as i64)
+        .bind(process.stop_signal)
+        .bind(process.log_dir_mode.to_string())
+        .bind(process.status_verified_at.map(|at| at.to_rfc3339()))
+        .bind(process.internal)
+        .bind(process.ephemeral_keep_secs.map(|secs| secs as i64))
+        .bind(process.delete_after.map(|at| at.to_rfc3339()))
+        .bind(last_output_json)
+        .bind(process.last_output_lossy)
+        .bind(watch_json)
+        .bind(&process.umask)
+        .bind(&process.description)
+        .bind(process.rotate_on_start)
+        .bind(&process.name)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Change the working directory a process is started in from its next start on;
+    /// false when there is no such process. updated_at is left alone, as the status is.
+    #[tracing::instrument(name = "db.query", skip_all)]
+    pub async fn update_working_dir(&self, name: &ProcessName, working_dir: &str) -> Result<bool> {
+        let result = sqlx::query(
+            "UPDATE processes SET working_dir = ? WHERE name = ? AND deleted_at IS NULL"
+        )
+        .bind(working_dir)
+        .bind(name)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Set or, with None, clear the description of a process; false when there is no such
+    /// process. updated_at is left alone, as the status is.
+    #[tracing::instrument(name = "db.query", skip_all)]
+    pub async fn update_description(&self, name: &ProcessName, description: Option<&str>) -> Result<bool> {
+        let result = sqlx::query(
+            "UPDATE processes SET description = ? WHERE name = ? AND deleted_at IS NULL"
+        )
+        .bind(description)
+        .bind(name)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Record that the statuses of these records (by id) were checked against their PIDs at `at`
+    #[tracing::instrument(name = "db.query", skip_all)]
+    pub async fn mark_status_verified(&self, ids: &[String], at: DateTime<Utc>) -> Result<()> {
+        self.injected_write_failure()?;
+        let at = at.to_rfc3339();
+        for chunk in ids.chunks(MAX_BIND_PARAMETERS - 1) {
+            let placeholders = chunk.iter().map(|_| "?").collect::<Vec<_>>().join(",");
+            let query = format!("UPDATE processes SET status_verified_at = ? WHERE id IN ({})", placeholders);
+
+            let mut query_builder = sqlx::query(&query).bind(&at);
+            for id in chunk {
+                query_builder = query_builder.bind(id);
+            }
+            query_builder.execute(&self.pool).await?;
+        }
+        Ok(())
+    }
+
+    /// Set when an ephemeral process (by id) is deleted, unless a deadline is already set
+    #[tracing::instrument(name = "db.query", skip_all)]
+    pub async fn schedule_deletion(&self, id: &str, at: DateTime<Utc>) -> Result<()> {
+        self.injected_write_failure()?;
+        sqlx::query("UPDATE processes SET delete_after = ? WHERE id = ? AND delete_after IS NULL")
+            .bind(at.to_rfc3339())
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Store the output captured when a process (by id) failed
+    #[tracing::instrument(name = "db.query", skip_all)]
+    pub async fn set_last_output(&self, id: &str, lines: &[String], lossy: bool) -> Result<()> {
+        self.injected_write_failure()?;
+        sqlx::query("UPDATE processes SET last_output = ?, last_output_lossy = ? WHERE id = ?")
+            .bind(serde_json::to_string(lines)?)
+            .bind(lossy)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Record that a process exited at `at`, with its exit code when it has one
+    #[tracing::instrument(name = "db.query", skip_all)]
+    pub async fn record_exit(&self, name: &ProcessName, status: ProcessStatus, exit_code: Option<i32>, exit_reason: Option<&str>, at: DateTime<Utc>) -> Result<()> {
+        self.injected_write_failure()?;
         sqlx::query(
-            "UPDATE processes SET status = ?, pid = ?, updated_at = ? WHERE name = ?"
+            "UPDATE processes SET status = ?, exit_code = ?, exit_reason = ?, updated_at = ? WHERE name = ? AND deleted_at IS NULL"
         )
         .bind(status.to_string())
-        .bind(pid.map(|p| p as i64))
-        .bind(Utc::now().to_rfc3339())
+        .bind(exit_code)
+        .bind(exit_reason)
+        .bind(at.to_rfc3339())
         .bind(name)
         .execute(&self.pool)
         .await?;
@@ -281,8 +1991,9 @@ impl Database {
         Ok(())
     }
 
-    pub async fn delete_process(&self, name: &str) -> Result<bool> {
-        let result = sqlx::query("DELETE FROM processes WHERE name = ?")
+    #[tracing::instrument(name = "db.query", skip_all)]
+    pub async fn delete_process(&self, name: &ProcessName) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM processes WHERE name = ? AND deleted_at IS NULL")
             .bind(name)
             .execute(&self.pool)
             .await?;
@@ -290,6 +2001,7 @@ impl Database {
         Ok(result.rows_affected() > 0)
     }
 
+    #[tracing::instrument(name = "db.query", skip_all)]
     pub async fn delete_process_by_id(&self, id: &str) -> Result<bool> {
         let result = sqlx::query("DELETE FROM processes WHERE id = ?")
             .bind(id)
@@ -299,6 +2011,7 @@ impl Database {
         Ok(result.rows_affected() > 0)
     }
 
+    #[tracing::instrument(name = "db.query", skip_all)]
     pub async fn get_processes_by_status(&self, statuses: &[ProcessStatus]) -> Result<Vec<ProcessRecord>> {
         if statuses.is_empty() {
             return Ok(Vec::new());
@@ -306,7 +2019,7 @@ impl Database {
 
         let status_strings: Vec<String> = statuses.iter().map(|s| s.to_string()).collect();
         let placeholders = status_strings.iter().map(|_| "?").collect::<Vec<_>>().join(",");
-        let query = format!("SELECT * FROM processes WHERE status IN ({}) ORDER BY created_at DESC", placeholders);
+        let query = format!("SELECT * FROM processes WHERE status IN ({}) AND deleted_at IS NULL ORDER BY created_at DESC", placeholders);
 
         let mut query_builder = sqlx::query(&query);
         for status in &status_strings {
@@ -317,35 +2030,136 @@ impl Database {
 
         let mut processes = Vec::new();
         for row in rows {
-            processes.push(self.row_to_process_record(row)?);
+            processes.push(Self::row_to_process_record(&row)?);
         }
         Ok(processes)
     }
 
-    pub async fn delete_processes_by_names(&self, names: &[String]) -> Result<usize> {
+    #[tracing::instrument(name = "db.query", skip_all)]
+    pub async fn delete_processes_by_names(&self, names: &[ProcessName]) -> Result<usize> {
         if names.is_empty() {
             return Ok(0);
         }
 
-        let placeholders = names.iter().map(|_| "?").collect::<Vec<_>>().join(",");
-        let query = format!("DELETE FROM processes WHERE name IN ({})", placeholders);
+        // Large batches are split to stay under SQLite's bound-parameter limit, in one transaction
+        let mut tx = self.pool.begin().await?;
+        let mut deleted = 0;
+        for chunk in names.chunks(MAX_BIND_PARAMETERS) {
+            let placeholders = chunk.iter().map(|_| "?").collect::<Vec<_>>().join(",");
+            let query = format!("DELETE FROM processes WHERE name IN ({}) AND deleted_at IS NULL", placeholders);
 
-        let mut query_builder = sqlx::query(&query);
-        for name in names {
-            query_builder = query_builder.bind(name);
+            let mut query_builder = sqlx::query(&query);
+            for name in chunk {
+                query_builder = query_builder.bind(name);
+            }
+
+            deleted += query_builder.execute(&mut *tx).await?.rows_affected() as usize;
+        }
+        tx.commit().await?;
+        Ok(deleted)
+    }
+
+    /// Mark the live record with this name as deleted, returning whether one existed
+    pub async fn soft_delete_process(&self, name: &ProcessName) -> Result<bool> {
+        self.soft_delete_process_at(name, Utc::now()).await
+    }
+
+    /// [`Self::soft_delete_process`] with the deletion time given by the caller
+    #[tracing::instrument(name = "db.query", skip_all)]
+    pub async fn soft_delete_process_at(&self, name: &ProcessName, deleted_at: DateTime<Utc>) -> Result<bool> {
+        let now = deleted_at.to_rfc3339();
+        let result = sqlx::query(
+            "UPDATE processes SET deleted_at = ?, updated_at = ? WHERE name = ? AND deleted_at IS NULL"
+        )
+        .bind(&now)
+        .bind(&now)
+        .bind(name)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Most recently soft-deleted record with this name
+    #[tracing::instrument(name = "db.query", skip_all)]
+    pub async fn get_deleted_process_by_name(&self, name: &ProcessName) -> Result<Option<ProcessRecord>> {
+        let row = sqlx::query(
+            "SELECT * FROM processes WHERE name = ? AND deleted_at IS NOT NULL ORDER BY deleted_at DESC LIMIT 1"
+        )
+        .bind(name)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        if let Some(row) = row {
+            Ok(Some(Self::row_to_process_record(&row)?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Soft-deleted records deleted before `cutoff`, oldest first
+    #[tracing::instrument(name = "db.query", skip_all)]
+    pub async fn get_deleted_processes_before(&self, cutoff: DateTime<Utc>) -> Result<Vec<ProcessRecord>> {
+        let rows = sqlx::query(
+            "SELECT * FROM processes WHERE deleted_at IS NOT NULL AND deleted_at <= ? ORDER BY deleted_at ASC"
+        )
+        .bind(cutoff.to_rfc3339())
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut processes = Vec::new();
+        for row in rows {
+            processes.push(Self::row_to_process_record(&row)?);
         }
+        Ok(processes)
+    }
+
+    /// Clear the deleted marker of a soft-deleted record
+    #[tracing::instrument(name = "db.query", skip_all)]
+    pub async fn restore_process(&self, id: &str) -> Result<bool> {
+        let result = sqlx::query(
+            "UPDATE processes SET deleted_at = NULL, updated_at = ? WHERE id = ? AND deleted_at IS NOT NULL"
+        )
+        .bind(Utc::now().to_rfc3339())
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
 
-        let result = query_builder.execute(&self.pool).await?;
-        Ok(result.rows_affected() as usize)
+    pub(crate) fn parse_status(status: &str) -> ProcessStatus {
+        match status {
+            "running" => ProcessStatus::Running,
+            "stopped" => ProcessStatus::Stopped,
+            "failed" => ProcessStatus::Failed,
+            "succeeded" => ProcessStatus::Succeeded,
+            _ => ProcessStatus::Unknown,
+        }
     }
 
-    fn row_to_process_record(&self, row: sqlx::sqlite::SqliteRow) -> Result<ProcessRecord> {
-        let args_json: String = row.get("args");
-        let env_vars_json: String = row.get("env_vars");
-        let created_at_str: String = row.get("created_at");
-        let updated_at_str: String = row.get("updated_at");
-        let status_str: String = row.get("status");
-        let pid_i64: Option<i64> = row.get("pid");
+    /// Decode a process row of either database, see [`StoredRow`]
+    pub(crate) fn row_to_process_record(row: &impl StoredRow) -> Result<ProcessRecord> {
+        let args_json: String = row.column("args");
+        let env_vars_json: String = row.column("env_vars");
+        let created_at_str: String = row.column("created_at");
+        let updated_at_str: String = row.column("updated_at");
+        let status_str: String = row.column("status");
+        let pid_i64: Option<i64> = row.column("pid");
+        let cgroup_memory_max_i64: Option<i64> = row.column("cgroup_memory_max");
+        let cgroup_cpu_max_i64: Option<i64> = row.column("cgroup_cpu_max");
+        let deleted_at_str: Option<String> = row.column("deleted_at");
+        let start_snapshot_json: Option<String> = row.column("start_snapshot");
+        let default_env_keys_json: Option<String> = row.column("default_env_keys");
+        let kind_str: Option<String> = row.column("kind");
+        let runs_i64: Option<i64> = row.column("runs");
+        let stop_signal: Option<i32> = row.column("stop_signal");
+        let log_dir_mode: Option<String> = row.column("log_dir_mode");
+        let status_verified_at: Option<String> = row.column("status_verified_at");
+        let ephemeral_keep_secs: Option<i64> = row.column("ephemeral_keep_secs");
+        let delete_after: Option<String> = row.column("delete_after");
+        let last_output_json: Option<String> = row.column("last_output");
+        let watch_json: Option<String> = row.column("watch");
 
         let args: Vec<String> = serde_json::from_str(&args_json)?;
         let env_vars: HashMap<String, String> = serde_json::from_str(&env_vars_json)?;
@@ -356,43 +2170,565 @@ impl Database {
             .map_err(|e| Error::Other(format!("Failed to parse updated_at: {}", e)))?
             .with_timezone(&Utc);
 
-        let status = match status_str.as_str() {
-            "running" => ProcessStatus::Running,
-            "stopped" => ProcessStatus::Stopped,
-            "failed" => ProcessStatus::Failed,
-            _ => ProcessStatus::Unknown,
+        let deleted_at = match deleted_at_str {
+            Some(deleted_at_str) => Some(
+                DateTime::parse_from_rfc3339(&deleted_at_str)
+                    .map_err(|e| Error::Other(format!("Failed to parse deleted_at: {}", e)))?
+                    .with_timezone(&Utc),
+            ),
+            None => None,
+        };
+
+        let status = Self::parse_status(&status_str);
+        // A snapshot is informational; one that no longer parses is dropped rather than hiding the record
+        let start_snapshot = start_snapshot_json.and_then(|json| serde_json::from_str(&json).ok());
+        // Records from before defaults existed have no such keys
+        let default_env_keys = match default_env_keys_json {
+            Some(json) => serde_json::from_str(&json)?,
+            None => Vec::new(),
         };
 
         Ok(ProcessRecord {
-            id: row.get("id"),
-            name: row.get("name"),
-            command: row.get("command"),
+            id: row.column("id"),
+            name: row.column("name"),
+            command: row.column("command"),
             args,
             env_vars,
-            working_dir: row.get("working_dir"),
+            working_dir: row.column("working_dir"),
             pid: pid_i64.map(|p| p as u32),
             status,
             created_at,
             updated_at,
-            log_path: row.get("log_path"),
+            log_path: row.column("log_path"),
+            log_dir: row.column("log_dir"),
+            cgroup_memory_max: cgroup_memory_max_i64.map(|m| m as u64),
+            cgroup_cpu_max: cgroup_cpu_max_i64.map(|c| c as u32),
+            cgroup: None,
+            deleted_at,
+            binary_path: row.column("binary_path"),
+            binary_sha256: row.column("binary_sha256"),
+            canonical_workdir: row.column("canonical_workdir"),
+            exit_code: row.column("exit_code"),
+            exit_reason: row.column("exit_reason"),
+            cpu_affinity: row.column("cpu_affinity"),
+            umask: row.column("umask"),
+            description: row.column("description"),
+            start_snapshot,
+            default_env_keys,
+            drift: None,
+            notes: Vec::new(),
+            recent_runs: Vec::new(),
+            flapping: false,
+            degraded: false,
+            inferred: false,
+            truncated_fields: Vec::new(),
+            // Records from before kinds existed are services
+            kind: kind_str.and_then(|kind| kind.parse().ok()).unwrap_or_default(),
+            runs: runs_i64.map_or(1, |runs| runs as u32),
+            stop_signal: stop_signal.unwrap_or_else(default_stop_signal),
+            log_dir_mode: log_dir_mode.and_then(|mode| mode.parse().ok()).unwrap_or_default(),
+            status_verified_at: status_verified_at
+                .and_then(|at| DateTime::parse_from_rfc3339(&at).ok())
+                .map(|at| at.with_timezone(&Utc)),
+            internal: row.column("internal"),
+            ephemeral_keep_secs: ephemeral_keep_secs.map(|secs| secs as u64),
+            delete_after: delete_after
+                .and_then(|at| DateTime::parse_from_rfc3339(&at).ok())
+                .map(|at| at.with_timezone(&Utc)),
+            // Like the snapshot, captured output is informational
+            last_output: last_output_json.and_then(|json| serde_json::from_str(&json).ok()).unwrap_or_default(),
+            last_output_lossy: row.column("last_output_lossy"),
+            watch: watch_json.map(|json| serde_json::from_str(&json)).transpose()?,
+            rotate_on_start: row.column("rotate_on_start"),
+        })
+    }
+
+    #[tracing::instrument(name = "db.query", skip_all)]
+    pub async fn insert_log_rotation(&self, entry: &LogRotationEntry) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO log_rotations (process_name, rotated_at, trigger, rotated_from_size, archives_shifted, archives_deleted)
+            VALUES (?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(&entry.process_name)
+        .bind(entry.rotated_at.to_rfc3339())
+        .bind(&entry.trigger)
+        .bind(entry.rotated_from_size as i64)
+        .bind(entry.archives_shifted as i64)
+        .bind(serde_json::to_string(&entry.archives_deleted)?)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Rotations of a process's log, oldest first. Databases opened read-only from
+    /// before rotations were recorded have no history.
+    #[tracing::instrument(name = "db.query", skip_all)]
+    pub async fn get_log_rotations(&self, process_name: &ProcessName) -> Result<Vec<LogRotationEntry>> {
+        let table_exists = sqlx::query("SELECT name FROM sqlite_master WHERE type='table' AND name='log_rotations'")
+            .fetch_optional(&self.pool)
+            .await?
+            .is_some();
+        if !table_exists {
+            return Ok(Vec::new());
+        }
+
+        let rows = sqlx::query("SELECT * FROM log_rotations WHERE process_name = ? ORDER BY id")
+            .bind(process_name)
+            .fetch_all(&self.pool)
+            .await?;
+
+        rows.iter().map(Self::row_to_log_rotation).collect()
+    }
+
+    pub(crate) fn row_to_log_rotation(row: &impl StoredRow) -> Result<LogRotationEntry> {
+        let rotated_at_str: String = row.column("rotated_at");
+        let archives_deleted_json: String = row.column("archives_deleted");
+        Ok(LogRotationEntry {
+            process_name: row.column("process_name"),
+            rotated_at: DateTime::parse_from_rfc3339(&rotated_at_str)
+                .map_err(|e| Error::Other(format!("Failed to parse rotated_at: {}", e)))?
+                .with_timezone(&Utc),
+            trigger: row.column("trigger"),
+            rotated_from_size: row.column::<i64>("rotated_from_size") as u64,
+            archives_shifted: row.column::<i64>("archives_shifted") as usize,
+            archives_deleted: serde_json::from_str(&archives_deleted_json)?,
+        })
+    }
+
+    #[tracing::instrument(name = "db.query", skip_all)]
+    pub async fn delete_log_rotations(&self, process_name: &ProcessName) -> Result<u64> {
+        let result = sqlx::query("DELETE FROM log_rotations WHERE process_name = ?")
+            .bind(process_name)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    /// Store a restart made by watching files, keeping the latest `keep` per process
+    #[tracing::instrument(name = "db.query", skip_all)]
+    pub async fn insert_watch_restart(&self, entry: &WatchRestartEntry, keep: usize) -> Result<()> {
+        sqlx::query("INSERT INTO watch_restarts (process_name, restarted_at, path, changes) VALUES (?, ?, ?, ?)")
+            .bind(&entry.process_name)
+            .bind(entry.restarted_at.to_rfc3339())
+            .bind(&entry.path)
+            .bind(entry.changes as i64)
+            .execute(&self.pool)
+            .await?;
+        sqlx::query(
+            "DELETE FROM watch_restarts WHERE process_name = ? AND id NOT IN (SELECT id FROM watch_restarts WHERE process_name = ? ORDER BY id DESC LIMIT ?)",
+        )
+        .bind(&entry.process_name)
+        .bind(&entry.process_name)
+        .bind(keep as i64)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Restarts of a process made by watching files, oldest first. Databases opened
+    /// read-only from before watching existed have none.
+    #[tracing::instrument(name = "db.query", skip_all)]
+    pub async fn get_watch_restarts(&self, process_name: &ProcessName) -> Result<Vec<WatchRestartEntry>> {
+        let table_exists = sqlx::query("SELECT name FROM sqlite_master WHERE type='table' AND name='watch_restarts'")
+            .fetch_optional(&self.pool)
+            .await?
+            .is_some();
+        if !table_exists {
+            return Ok(Vec::new());
+        }
+
+        let rows = sqlx::query("SELECT * FROM watch_restarts WHERE process_name = ? ORDER BY id")
+            .bind(process_name)
+            .fetch_all(&self.pool)
+            .await?;
+
+        rows.iter().map(Self::row_to_watch_restart).collect()
+    }
+
+    pub(crate) fn row_to_watch_restart(row: &impl StoredRow) -> Result<WatchRestartEntry> {
+        let restarted_at_str: String = row.column("restarted_at");
+        Ok(WatchRestartEntry {
+            process_name: row.column("process_name"),
+            restarted_at: DateTime::parse_from_rfc3339(&restarted_at_str)
+                .map_err(|e| Error::Other(format!("Failed to parse restarted_at: {}", e)))?
+                .with_timezone(&Utc),
+            path: row.column("path"),
+            changes: row.column::<i64>("changes") as usize,
+        })
+    }
+
+    /// Store a finished run, keeping the latest `keep` per process
+    #[tracing::instrument(name = "db.query", skip_all)]
+    pub async fn insert_process_run(&self, run: &ProcessRun, keep: usize) -> Result<()> {
+        sqlx::query("INSERT OR IGNORE INTO process_runs (process_name, started_at, ended_at, status, exit_code, duration_ms) VALUES (?, ?, ?, ?, ?, ?)")
+            .bind(&run.process_name)
+            .bind(run.started_at.to_rfc3339())
+            .bind(run.ended_at.to_rfc3339())
+            .bind(run.status.to_string())
+            .bind(run.exit_code)
+            .bind(run.duration_ms as i64)
+            .execute(&self.pool)
+            .await?;
+        sqlx::query(
+            "DELETE FROM process_runs WHERE process_name = ? AND id NOT IN (SELECT id FROM process_runs WHERE process_name = ? ORDER BY id DESC LIMIT ?)",
+        )
+        .bind(&run.process_name)
+        .bind(&run.process_name)
+        .bind(keep as i64)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// The latest `limit` finished runs of a process, oldest first. Databases opened
+    /// read-only from before runs were recorded have none.
+    #[tracing::instrument(name = "db.query", skip_all)]
+    pub async fn get_process_runs(&self, process_name: &ProcessName, limit: usize) -> Result<Vec<ProcessRun>> {
+        let table_exists = sqlx::query("SELECT name FROM sqlite_master WHERE type='table' AND name='process_runs'")
+            .fetch_optional(&self.pool)
+            .await?
+            .is_some();
+        if !table_exists {
+            return Ok(Vec::new());
+        }
+
+        let rows = sqlx::query("SELECT * FROM (SELECT * FROM process_runs WHERE process_name = ? ORDER BY id DESC LIMIT ?) ORDER BY id")
+            .bind(process_name)
+            .bind(limit as i64)
+            .fetch_all(&self.pool)
+            .await?;
+
+        rows.iter().map(Self::row_to_process_run).collect()
+    }
+
+    pub(crate) fn row_to_process_run(row: &impl StoredRow) -> Result<ProcessRun> {
+        let parse_time = |column: &str| -> Result<DateTime<Utc>> {
+            let value: String = row.column(column);
+            Ok(DateTime::parse_from_rfc3339(&value)
+                .map_err(|e| Error::Other(format!("Failed to parse {}: {}", column, e)))?
+                .with_timezone(&Utc))
+        };
+        let status: String = row.column("status");
+        Ok(ProcessRun {
+            process_name: row.column("process_name"),
+            started_at: parse_time("started_at")?,
+            ended_at: parse_time("ended_at")?,
+            status: Self::parse_status(&status),
+            exit_code: row.column("exit_code"),
+            duration_ms: row.column::<i64>("duration_ms") as u64,
+        })
+    }
+
+    #[tracing::instrument(name = "db.query", skip_all)]
+    pub async fn delete_process_runs(&self, process_name: &ProcessName) -> Result<u64> {
+        let result = sqlx::query("DELETE FROM process_runs WHERE process_name = ?")
+            .bind(process_name)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    #[tracing::instrument(name = "db.query", skip_all)]
+    pub async fn delete_watch_restarts(&self, process_name: &ProcessName) -> Result<u64> {
+        let result = sqlx::query("DELETE FROM watch_restarts WHERE process_name = ?")
+            .bind(process_name)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    /// Store a pass of the logs quota, keeping the latest `keep`
+    #[tracing::instrument(name = "db.query", skip_all)]
+    pub async fn insert_quota_enforcement(&self, enforcement: &QuotaEnforcement, keep: usize) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO logs_quota_enforcements (enforced_at, quota_bytes, used_before, used_after, deleted) VALUES (?, ?, ?, ?, ?)",
+        )
+        .bind(enforcement.enforced_at.to_rfc3339())
+        .bind(enforcement.quota_bytes as i64)
+        .bind(enforcement.used_before as i64)
+        .bind(enforcement.used_after as i64)
+        .bind(serde_json::to_string(&enforcement.deleted)?)
+        .execute(&self.pool)
+        .await?;
+        sqlx::query("DELETE FROM logs_quota_enforcements WHERE id NOT IN (SELECT id FROM logs_quota_enforcements ORDER BY id DESC LIMIT ?)")
+            .bind(keep as i64)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// The latest pass of the logs quota; None when there was none, or when a database
+    /// opened read-only is from before the quota existed
+    #[tracing::instrument(name = "db.query", skip_all)]
+    pub async fn get_last_quota_enforcement(&self) -> Result<Option<QuotaEnforcement>> {
+        let table_exists = sqlx::query("SELECT name FROM sqlite_master WHERE type='table' AND name='logs_quota_enforcements'")
+            .fetch_optional(&self.pool)
+            .await?
+            .is_some();
+        if !table_exists {
+            return Ok(None);
+        }
+
+        let row = sqlx::query("SELECT * FROM logs_quota_enforcements ORDER BY id DESC LIMIT 1")
+            .fetch_optional(&self.pool)
+            .await?;
+        row.as_ref().map(Self::row_to_quota_enforcement).transpose()
+    }
+
+    pub(crate) fn row_to_quota_enforcement(row: &impl StoredRow) -> Result<QuotaEnforcement> {
+        let enforced_at: String = row.column("enforced_at");
+        let deleted: String = row.column("deleted");
+        Ok(QuotaEnforcement {
+            enforced_at: DateTime::parse_from_rfc3339(&enforced_at)
+                .map_err(|e| Error::Other(format!("Failed to parse enforced_at: {}", e)))?
+                .with_timezone(&Utc),
+            quota_bytes: row.column::<i64>("quota_bytes") as u64,
+            used_before: row.column::<i64>("used_before") as u64,
+            used_after: row.column::<i64>("used_after") as u64,
+            deleted: serde_json::from_str(&deleted)?,
+        })
+    }
+
+    #[tracing::instrument(name = "db.query", skip_all)]
+    pub async fn insert_process_note(&self, process_name: &ProcessName, author: &str, text: &str) -> Result<ProcessNote> {
+        let created_at = Utc::now();
+        let result = sqlx::query("INSERT INTO process_notes (process_name, created_at, author, text) VALUES (?, ?, ?, ?)")
+            .bind(process_name)
+            .bind(created_at.to_rfc3339())
+            .bind(author)
+            .bind(text)
+            .execute(&self.pool)
+            .await?;
+        Ok(ProcessNote {
+            id: result.last_insert_rowid(),
+            process_name: process_name.to_string(),
+            created_at,
+            author: author.to_string(),
+            text: text.to_string(),
+        })
+    }
+
+    /// Notes on a process, oldest first. Databases opened read-only from before notes
+    /// existed have none.
+    #[tracing::instrument(name = "db.query", skip_all)]
+    pub async fn get_process_notes(&self, process_name: &ProcessName) -> Result<Vec<ProcessNote>> {
+        let table_exists = sqlx::query("SELECT name FROM sqlite_master WHERE type='table' AND name='process_notes'")
+            .fetch_optional(&self.pool)
+            .await?
+            .is_some();
+        if !table_exists {
+            return Ok(Vec::new());
+        }
+
+        let rows = sqlx::query("SELECT * FROM process_notes WHERE process_name = ? ORDER BY id")
+            .bind(process_name)
+            .fetch_all(&self.pool)
+            .await?;
+
+        rows.iter().map(Self::row_to_process_note).collect()
+    }
+
+    pub(crate) fn row_to_process_note(row: &impl StoredRow) -> Result<ProcessNote> {
+        let created_at_str: String = row.column("created_at");
+        Ok(ProcessNote {
+            id: row.column("id"),
+            process_name: row.column("process_name"),
+            created_at: DateTime::parse_from_rfc3339(&created_at_str)
+                .map_err(|e| Error::Other(format!("Failed to parse created_at: {}", e)))?
+                .with_timezone(&Utc),
+            author: row.column("author"),
+            text: row.column("text"),
+        })
+    }
+
+    /// Deletes one note of a process; returns whether it existed
+    #[tracing::instrument(name = "db.query", skip_all)]
+    pub async fn delete_process_note(&self, process_name: &ProcessName, note_id: i64) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM process_notes WHERE process_name = ? AND id = ?")
+            .bind(process_name)
+            .bind(note_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    #[tracing::instrument(name = "db.query", skip_all)]
+    pub async fn delete_process_notes(&self, process_name: &ProcessName) -> Result<u64> {
+        let result = sqlx::query("DELETE FROM process_notes WHERE process_name = ?")
+            .bind(process_name)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    /// Store a reservation unless another token holds the name and its reservation has
+    /// not expired by `now`; the same token renews its own. Returns whether it was stored.
+    #[tracing::instrument(name = "db.query", skip_all)]
+    pub async fn claim_reservation(&self, reservation: &Reservation, now: DateTime<Utc>) -> Result<bool> {
+        let result = sqlx::query(
+            "INSERT INTO reservations (name, token, reserved_at, expires_at) VALUES (?, ?, ?, ?) \
+             ON CONFLICT(name) DO UPDATE SET token = excluded.token, reserved_at = excluded.reserved_at, expires_at = excluded.expires_at \
+             WHERE reservations.expires_at <= ? OR reservations.token = excluded.token",
+        )
+        .bind(&reservation.name)
+        .bind(&reservation.token)
+        .bind(reservation.reserved_at.to_rfc3339())
+        .bind(reservation.expires_at.to_rfc3339())
+        .bind(now.to_rfc3339())
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Reservation of a name that has not expired by `now`. Databases opened read-only
+    /// from before reservations existed have none.
+    #[tracing::instrument(name = "db.query", skip_all)]
+    pub async fn get_reservation(&self, name: &ProcessName, now: DateTime<Utc>) -> Result<Option<Reservation>> {
+        let table_exists = sqlx::query("SELECT name FROM sqlite_master WHERE type='table' AND name='reservations'")
+            .fetch_optional(&self.pool)
+            .await?
+            .is_some();
+        if !table_exists {
+            return Ok(None);
+        }
+
+        let row = sqlx::query("SELECT * FROM reservations WHERE name = ? AND expires_at > ?")
+            .bind(name)
+            .bind(now.to_rfc3339())
+            .fetch_optional(&self.pool)
+            .await?;
+        row.as_ref().map(Self::row_to_reservation).transpose()
+    }
+
+    pub(crate) fn row_to_reservation(row: &impl StoredRow) -> Result<Reservation> {
+        let parse = |column: &str| -> Result<DateTime<Utc>> {
+            let value: String = row.column(column);
+            Ok(DateTime::parse_from_rfc3339(&value)
+                .map_err(|e| Error::Other(format!("Failed to parse {}: {}", column, e)))?
+                .with_timezone(&Utc))
+        };
+        Ok(Reservation {
+            name: row.column("name"),
+            token: row.column("token"),
+            reserved_at: parse("reserved_at")?,
+            expires_at: parse("expires_at")?,
         })
     }
 
+    /// Drops the reservation of a name, expired or not; returns whether there was one
+    #[tracing::instrument(name = "db.query", skip_all)]
+    pub async fn delete_reservation(&self, name: &ProcessName) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM reservations WHERE name = ?")
+            .bind(name)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Drops the reservations that expired by `now`; returns how many
+    #[tracing::instrument(name = "db.query", skip_all)]
+    pub async fn delete_expired_reservations(&self, now: DateTime<Utc>) -> Result<u64> {
+        let result = sqlx::query("DELETE FROM reservations WHERE expires_at <= ?")
+            .bind(now.to_rfc3339())
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    /// Recent failures of a process; empty when it has none, or when a database opened
+    /// read-only is from before failures were recorded
+    #[tracing::instrument(name = "db.query", skip_all)]
+    pub async fn get_failure_history(&self, process_name: &ProcessName) -> Result<FailureHistory> {
+        if !self.has_failures_table().await? {
+            return Ok(FailureHistory::default());
+        }
+        let row = sqlx::query("SELECT * FROM process_failures WHERE process_name = ?")
+            .bind(process_name)
+            .fetch_optional(&self.pool)
+            .await?;
+        match row {
+            Some(row) => Ok(Self::row_to_failure_history(&row)?.1),
+            None => Ok(FailureHistory::default()),
+        }
+    }
+
+    /// Failure histories of the processes that are currently flapping, by process name
+    #[tracing::instrument(name = "db.query", skip_all)]
+    pub async fn get_flapping_histories(&self) -> Result<Vec<(ProcessName, FailureHistory)>> {
+        if !self.has_failures_table().await? {
+            return Ok(Vec::new());
+        }
+        let rows = sqlx::query("SELECT * FROM process_failures WHERE flapping_since IS NOT NULL ORDER BY process_name")
+            .fetch_all(&self.pool)
+            .await?;
+        rows.iter().map(Self::row_to_failure_history).collect()
+    }
+
+    #[tracing::instrument(name = "db.query", skip_all)]
+    pub async fn save_failure_history(&self, process_name: &ProcessName, history: &FailureHistory) -> Result<()> {
+        self.injected_write_failure()?;
+        let failures: Vec<String> = history.failures.iter().map(|at| at.to_rfc3339()).collect();
+        sqlx::query("INSERT OR REPLACE INTO process_failures (process_name, failures, flapping_since) VALUES (?, ?, ?)")
+            .bind(process_name)
+            .bind(serde_json::to_string(&failures)?)
+            .bind(history.flapping_since.map(|at| at.to_rfc3339()))
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "db.query", skip_all)]
+    pub async fn delete_failure_history(&self, process_name: &ProcessName) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM process_failures WHERE process_name = ?")
+            .bind(process_name)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn has_failures_table(&self) -> Result<bool> {
+        Ok(sqlx::query("SELECT name FROM sqlite_master WHERE type='table' AND name='process_failures'")
+            .fetch_optional(&self.pool)
+            .await?
+            .is_some())
+    }
+
+    pub(crate) fn row_to_failure_history(row: &impl StoredRow) -> Result<(ProcessName, FailureHistory)> {
+        let parse = |value: &str| {
+            DateTime::parse_from_rfc3339(value)
+                .map(|at| at.with_timezone(&Utc))
+                .map_err(|e| Error::Other(format!("Failed to parse failure time: {}", e)))
+        };
+        let failures_json: String = row.column("failures");
+        let failures: Vec<String> = serde_json::from_str(&failures_json)?;
+        let flapping_since: Option<String> = row.column("flapping_since");
+        let history = FailureHistory {
+            failures: failures.iter().map(|at| parse(at)).collect::<Result<_>>()?,
+            flapping_since: flapping_since.as_deref().map(parse).transpose()?,
+        };
+        Ok((row.column("process_name"), history))
+    }
+
     // API Token methods (only available with http-api feature)
     #[cfg(feature = "http-api")]
+    #[tracing::instrument(name = "db.query", skip_all)]
     pub async fn insert_api_token(&self, token: &ApiToken) -> Result<()> {
         sqlx::query(
             r#"
-            INSERT INTO api_tokens (id, token, name, created_at, expires_at, is_active)
-            VALUES (?, ?, ?, ?, ?, ?)
+            INSERT INTO api_tokens (id, public_id, token, name, created_at, expires_at, is_active, admin)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?)
             "#,
         )
         .bind(&token.id)
+        .bind(&token.public_id)
         .bind(&token.token)
         .bind(&token.name)
         .bind(token.created_at.to_rfc3339())
         .bind(token.expires_at.map(|e| e.to_rfc3339()))
         .bind(if token.is_active { 1 } else { 0 })
+        .bind(token.admin)
         .execute(&self.pool)
         .await?;
 
@@ -400,6 +2736,7 @@ impl Database {
     }
 
     #[cfg(feature = "http-api")]
+    #[tracing::instrument(name = "db.query", skip_all)]
     pub async fn get_api_token_by_token(&self, token: &str) -> Result<Option<ApiToken>> {
         let row = sqlx::query("SELECT * FROM api_tokens WHERE token = ?")
             .bind(token)
@@ -407,13 +2744,25 @@ impl Database {
             .await?;
 
         if let Some(row) = row {
-            Ok(Some(self.row_to_api_token(row)?))
+            Ok(Some(Self::row_to_api_token(&row)?))
         } else {
             Ok(None)
         }
     }
 
     #[cfg(feature = "http-api")]
+    #[tracing::instrument(name = "db.query", skip_all)]
+    pub async fn get_api_token_by_public_id(&self, public_id: &str) -> Result<Option<ApiToken>> {
+        let row = sqlx::query("SELECT * FROM api_tokens WHERE public_id = ?")
+            .bind(public_id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        row.map(|row| Self::row_to_api_token(&row)).transpose()
+    }
+
+    #[cfg(feature = "http-api")]
+    #[tracing::instrument(name = "db.query", skip_all)]
     pub async fn get_all_api_tokens(&self) -> Result<Vec<ApiToken>> {
         let rows = sqlx::query("SELECT * FROM api_tokens ORDER BY created_at DESC")
             .fetch_all(&self.pool)
@@ -421,12 +2770,13 @@ impl Database {
 
         let mut tokens = Vec::new();
         for row in rows {
-            tokens.push(self.row_to_api_token(row)?);
+            tokens.push(Self::row_to_api_token(&row)?);
         }
         Ok(tokens)
     }
 
     #[cfg(feature = "http-api")]
+    #[tracing::instrument(name = "db.query", skip_all)]
     pub async fn update_api_token_status(&self, token: &str, is_active: bool) -> Result<bool> {
         let result = sqlx::query("UPDATE api_tokens SET is_active = ? WHERE token = ?")
             .bind(if is_active { 1 } else { 0 })
@@ -438,6 +2788,7 @@ impl Database {
     }
 
     #[cfg(feature = "http-api")]
+    #[tracing::instrument(name = "db.query", skip_all)]
     pub async fn delete_api_token(&self, token: &str) -> Result<bool> {
         let result = sqlx::query("DELETE FROM api_tokens WHERE token = ?")
             .bind(token)
@@ -448,10 +2799,9 @@ impl Database {
     }
 
     #[cfg(feature = "http-api")]
-    fn row_to_api_token(&self, row: sqlx::sqlite::SqliteRow) -> Result<ApiToken> {
-        let created_at_str: String = row.get("created_at");
-        let expires_at_str: Option<String> = row.get("expires_at");
-        let is_active_i64: i64 = row.get("is_active");
+    pub(crate) fn row_to_api_token(row: &impl StoredRow) -> Result<ApiToken> {
+        let created_at_str: String = row.column("created_at");
+        let expires_at_str: Option<String> = row.column("expires_at");
 
         let created_at = DateTime::parse_from_rfc3339(&created_at_str)
             .map_err(|e| Error::Other(format!("Failed to parse created_at: {}", e)))?
@@ -466,12 +2816,34 @@ impl Database {
         };
 
         Ok(ApiToken {
-            id: row.get("id"),
-            token: row.get("token"),
-            name: row.get("name"),
+            id: row.column("id"),
+            public_id: row.column("public_id"),
+            token: row.column("token"),
+            name: row.column("name"),
             created_at,
             expires_at,
-            is_active: is_active_i64 != 0,
+            is_active: row.column("is_active"),
+            // Read-only servers may open a database from before scopes
+            admin: row.try_column("admin").unwrap_or(false),
         })
     }
 }
+
+/// Public id for a token issued before ids existed: the first [`TOKEN_ID_LENGTH`] hex
+/// digits of its SHA-256 not in `taken`, trying later digits on a clash
+#[cfg(feature = "http-api")]
+fn derive_token_id(token: &str, taken: &std::collections::HashSet<String>) -> String {
+    use sha2::{Digest, Sha256};
+    let hex = |bytes: &[u8]| -> String { Sha256::digest(bytes).iter().map(|byte| format!("{:02x}", byte)).collect() };
+    let mut seed = hex(token.as_bytes());
+    loop {
+        if let Some(id) = (0..=seed.len() - TOKEN_ID_LENGTH)
+            .map(|start| format!("{}{}", TOKEN_ID_PREFIX, &seed[start..start + TOKEN_ID_LENGTH]))
+            .find(|id| !taken.contains(id))
+        {
+            return id;
+        }
+        // Every window of this digest is taken; hash it again
+        seed = hex(seed.as_bytes());
+    }
+}
@@ -0,0 +1,220 @@
+//! Detect when the binary or working directory of a process no longer matches
+//! what was recorded when it was started.
+
+use crate::database::ProcessRecord;
+use crate::resolution::ResolutionCache;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// Suffix the kernel appends to `/proc/<pid>/exe` once the running image is unlinked
+const DELETED_SUFFIX: &str = " (deleted)";
+
+/// What was captured about the executable and working directory at start
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StartFingerprint {
+    pub binary_path: Option<String>,
+    pub binary_sha256: Option<String>,
+    pub canonical_workdir: Option<String>,
+}
+
+impl StartFingerprint {
+    /// Resolve and hash the executable and canonicalize the working directory.
+    /// Anything that cannot be resolved is left empty rather than failing the start.
+    pub fn capture(command: &str, working_dir: &str, path_var: Option<&str>) -> Self {
+        Self::capture_with(&ResolutionCache::default(), command, working_dir, path_var)
+    }
+
+    /// [`Self::capture`], reusing what `cache` already resolved for an earlier start
+    pub fn capture_with(cache: &ResolutionCache, command: &str, working_dir: &str, path_var: Option<&str>) -> Self {
+        let binary_path = cache.executable(command, Path::new(working_dir), path_var);
+        let binary_sha256 = binary_path.as_deref().and_then(|path| cache.sha256(path));
+        Self {
+            binary_path: binary_path.map(|path| path.to_string_lossy().to_string()),
+            binary_sha256,
+            canonical_workdir: cache
+                .canonical_dir(Path::new(working_dir))
+                .map(|path| path.to_string_lossy().to_string()),
+        }
+    }
+}
+
+/// A single difference between the recorded start state and the system now
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "http-api", derive(utoipa::ToSchema))]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum DriftIssue {
+    /// The file at the recorded binary path has a different hash
+    BinaryChanged { path: String },
+    /// The recorded binary path no longer exists
+    BinaryMissing { path: String },
+    /// The image the process is running was deleted or replaced on disk
+    RunningBinaryReplaced { exe: String },
+    /// The working directory no longer exists
+    WorkdirMissing { path: String },
+    /// The working directory now resolves to a different location (e.g. a moved symlink)
+    WorkdirMoved { recorded: String, current: String },
+}
+
+impl std::fmt::Display for DriftIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DriftIssue::BinaryChanged { path } => write!(f, "binary changed on disk: {}", path),
+            DriftIssue::BinaryMissing { path } => write!(f, "binary missing: {}", path),
+            DriftIssue::RunningBinaryReplaced { exe } => write!(f, "running binary deleted or replaced: {}", exe),
+            DriftIssue::WorkdirMissing { path } => write!(f, "working directory missing: {}", path),
+            DriftIssue::WorkdirMoved { recorded, current } => {
+                write!(f, "working directory now resolves to {} (was {})", current, recorded)
+            }
+        }
+    }
+}
+
+/// Drift of one process
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "http-api", derive(utoipa::ToSchema))]
+pub struct DriftReport {
+    pub process: String,
+    /// False when nothing was recorded at start, so the binary could not be compared
+    pub fingerprinted: bool,
+    pub issues: Vec<DriftIssue>,
+}
+
+impl DriftReport {
+    pub fn has_drift(&self) -> bool {
+        !self.issues.is_empty()
+    }
+
+    /// Whether the binary on disk is no longer the one the process was started from
+    pub fn binary_changed(&self) -> bool {
+        self.issues.iter().any(|issue| {
+            matches!(
+                issue,
+                DriftIssue::BinaryChanged { .. }
+                    | DriftIssue::BinaryMissing { .. }
+                    | DriftIssue::RunningBinaryReplaced { .. }
+            )
+        })
+    }
+}
+
+/// Compare a record against the filesystem and, if it is alive, its running image.
+/// Processes checked with one `cache` share the hash of a binary they all run.
+pub fn check_drift(process: &ProcessRecord, running_pid: Option<u32>, cache: &ResolutionCache) -> DriftReport {
+    let mut issues = Vec::new();
+
+    if let (Some(path), Some(recorded_hash)) = (&process.binary_path, &process.binary_sha256) {
+        match cache.sha256(Path::new(path)) {
+            Some(hash) if &hash != recorded_hash => issues.push(DriftIssue::BinaryChanged { path: path.clone() }),
+            Some(_) => {}
+            None => issues.push(DriftIssue::BinaryMissing { path: path.clone() }),
+        }
+    }
+
+    if let Some(pid) = running_pid {
+        if let Ok(exe) = std::fs::read_link(format!("/proc/{}/exe", pid)) {
+            let exe = exe.to_string_lossy().to_string();
+            if exe.ends_with(DELETED_SUFFIX) {
+                issues.push(DriftIssue::RunningBinaryReplaced { exe });
+            }
+        }
+    }
+
+    match cache.canonical_dir(Path::new(&process.working_dir)) {
+        None => issues.push(DriftIssue::WorkdirMissing { path: process.working_dir.clone() }),
+        Some(current) => {
+            let current = current.to_string_lossy().to_string();
+            if let Some(recorded) = &process.canonical_workdir {
+                if recorded != &current {
+                    issues.push(DriftIssue::WorkdirMoved { recorded: recorded.clone(), current });
+                }
+            }
+        }
+    }
+
+    DriftReport {
+        process: process.name.to_string(),
+        fingerprinted: process.binary_sha256.is_some(),
+        issues,
+    }
+}
+
+/// Resolve a command the way `execvp` would: paths containing `/` are taken relative to
+/// the working directory, bare names are looked up in `PATH`
+pub fn resolve_executable(command: &str, working_dir: &Path, path_var: Option<&str>) -> Option<PathBuf> {
+    if command.contains('/') {
+        let candidate = working_dir.join(command);
+        return std::fs::canonicalize(candidate).ok().filter(|path| path.is_file());
+    }
+
+    let path_var = match path_var {
+        Some(path_var) => path_var.to_string(),
+        None => std::env::var("PATH").ok()?,
+    };
+    std::env::split_paths(&path_var)
+        .map(|dir| working_dir.join(dir).join(command))
+        .find(|candidate| is_executable(candidate))
+        .and_then(|candidate| std::fs::canonicalize(candidate).ok())
+}
+
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::metadata(path)
+        .map(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+/// Hex-encoded SHA-256 of a file, read in chunks
+pub fn sha256_file(path: &Path) -> std::io::Result<String> {
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; 64 * 1024];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hasher.finalize().iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_sha256_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("data");
+        std::fs::write(&path, "abc").unwrap();
+        assert_eq!(
+            sha256_file(&path).unwrap(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn test_resolve_executable() {
+        let temp_dir = TempDir::new().unwrap();
+        let bin_dir = temp_dir.path().join("bin");
+        std::fs::create_dir(&bin_dir).unwrap();
+        let tool = bin_dir.join("tool");
+        std::fs::write(&tool, "#!/bin/sh\n").unwrap();
+        let canonical = std::fs::canonicalize(&tool).unwrap();
+
+        // Not executable yet, so PATH lookup skips it
+        assert_eq!(resolve_executable("tool", temp_dir.path(), Some(bin_dir.to_str().unwrap())), None);
+
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&tool, std::fs::Permissions::from_mode(0o755)).unwrap();
+        assert_eq!(
+            resolve_executable("tool", temp_dir.path(), Some(bin_dir.to_str().unwrap())),
+            Some(canonical.clone())
+        );
+        assert_eq!(resolve_executable("./bin/tool", temp_dir.path(), None), Some(canonical));
+        assert_eq!(resolve_executable("./bin/missing", temp_dir.path(), None), None);
+    }
+}
@@ -0,0 +1,124 @@
+//! Durations written on the command line: `30m`, `12h`, `7d`, `2w`. A bare integer means
+//! days, which is what `--expires-in` accepted before units existed.
+
+use crate::{Error, Result};
+use chrono::Duration;
+use std::fmt;
+use std::str::FromStr;
+
+/// Longest duration accepted, about ten years
+pub const MAX_DURATION_SECS: i64 = 10 * 366 * 24 * 60 * 60;
+
+/// Suffixes and how many seconds one of each is
+const UNITS: [(&str, i64); 5] = [("s", 1), ("m", 60), ("h", 60 * 60), ("d", 24 * 60 * 60), ("w", 7 * 24 * 60 * 60)];
+
+/// A positive duration of at most [`MAX_DURATION_SECS`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HumanDuration(Duration);
+
+impl HumanDuration {
+    pub fn as_duration(&self) -> Duration {
+        self.0
+    }
+}
+
+impl FromStr for HumanDuration {
+    type Err = Error;
+
+    fn from_str(input: &str) -> Result<Self> {
+        let invalid = |reason: &str| Error::InvalidDuration(format!("'{}': {}", input, reason));
+        let trimmed = input.trim();
+        if trimmed.starts_with('-') {
+            return Err(invalid("must be positive"));
+        }
+
+        let split = trimmed.find(|c: char| !c.is_ascii_digit()).unwrap_or(trimmed.len());
+        let (amount, unit) = trimmed.split_at(split);
+        if amount.is_empty() {
+            return Err(invalid("expected a number followed by s, m, h, d or w (e.g. 12h, 7d)"));
+        }
+        let unit_secs = match unit {
+            "" => 24 * 60 * 60,
+            unit => UNITS
+                .iter()
+                .find(|(suffix, _)| *suffix == unit)
+                .map(|(_, secs)| *secs)
+                .ok_or_else(|| invalid(&format!("unknown unit '{}'; use s, m, h, d or w", unit)))?,
+        };
+
+        let secs = amount
+            .parse::<i64>()
+            .ok()
+            .and_then(|amount| amount.checked_mul(unit_secs))
+            .filter(|secs| *secs <= MAX_DURATION_SECS)
+            .ok_or_else(|| invalid("longer than the 10 year maximum"))?;
+        if secs == 0 {
+            return Err(invalid("must be greater than zero"));
+        }
+        Ok(Self(Duration::seconds(secs)))
+    }
+}
+
+impl fmt::Display for HumanDuration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", format_duration(self.0))
+    }
+}
+
+/// Largest two units of `duration`, e.g. `6d 23h` or `45m 10s`; negative values are shown as positive
+pub fn format_duration(duration: Duration) -> String {
+    let mut remaining = duration.num_seconds().abs();
+    if remaining == 0 {
+        return "0s".to_string();
+    }
+
+    let mut parts = Vec::new();
+    for (suffix, secs) in UNITS.iter().rev().skip(1) {
+        if remaining >= *secs {
+            parts.push(format!("{}{}", remaining / secs, suffix));
+            remaining %= secs;
+        }
+        if parts.len() == 2 || remaining == 0 {
+            break;
+        }
+    }
+    parts.join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secs(input: &str) -> i64 {
+        input.parse::<HumanDuration>().unwrap().as_duration().num_seconds()
+    }
+
+    #[test]
+    fn test_parse_units_and_bare_days() {
+        assert_eq!(secs("30m"), 30 * 60);
+        assert_eq!(secs("12h"), 12 * 3600);
+        assert_eq!(secs("7d"), 7 * 86400);
+        assert_eq!(secs("2w"), 14 * 86400);
+        assert_eq!(secs("90s"), 90);
+        assert_eq!(secs("30"), 30 * 86400);
+        assert_eq!(secs(" 1h "), 3600);
+    }
+
+    #[test]
+    fn test_parse_rejects_bad_values() {
+        for input in ["0", "0h", "-1d", "", "h", "7x", "1.5h", "3650w", "99999999999999999999d"] {
+            let err = input.parse::<HumanDuration>().unwrap_err();
+            assert!(matches!(err, Error::InvalidDuration(_)), "{} gave {}", input, err);
+        }
+        assert!("3650d".parse::<HumanDuration>().is_ok());
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(Duration::seconds(0)), "0s");
+        assert_eq!(format_duration(Duration::seconds(45)), "45s");
+        assert_eq!(format_duration(Duration::seconds(6 * 86400 + 23 * 3600 + 59)), "6d 23h");
+        assert_eq!(format_duration(Duration::seconds(-(2 * 3600 + 5 * 60))), "2h 5m");
+        assert_eq!(format_duration(Duration::days(3)), "3d");
+    }
+}
@@ -0,0 +1,375 @@
+//! Validation of user-supplied environment variables.
+//!
+//! Precedence when a process is spawned: the inherited environment of pmr is
+//! overridden by the config file defaults (see [`layer_env`]), then by user variables
+//! (`-e KEY=VALUE` / `env_vars`), which are in turn overridden by the variables pmr
+//! injects itself (see [`INJECTED_VARS`]).
+
+use serde::{de, Deserialize, Deserializer, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+/// Prefix reserved for variables injected by pmr
+pub const RESERVED_PREFIX: &str = "PMR_";
+
+/// Name of the managed process
+pub const PMR_PROCESS_NAME: &str = "PMR_PROCESS_NAME";
+/// Unique id of the process record
+pub const PMR_INSTANCE_ID: &str = "PMR_INSTANCE_ID";
+
+/// Variables pmr sets on every spawned process; they always win over user values
+pub const INJECTED_VARS: [&str; 2] = [PMR_PROCESS_NAME, PMR_INSTANCE_ID];
+
+/// What to do with user keys in the reserved `PMR_` namespace
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum ReservedEnvPolicy {
+    /// Accept the key but print a warning
+    #[default]
+    Warn,
+    /// Refuse to start the process
+    Reject,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum EnvVarProblem {
+    /// `-e` value without a `=` separator
+    MissingSeparator,
+    EmptyKey,
+    ContainsEquals,
+    ContainsNul,
+    LeadingDigit,
+    Reserved,
+    /// The same key given twice in one source, with the first and the later value
+    Duplicate { first: String, second: String },
+}
+
+/// A single invalid environment variable
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EnvVarError {
+    pub key: String,
+    pub problem: EnvVarProblem,
+}
+
+impl fmt::Display for EnvVarError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.problem {
+            EnvVarProblem::MissingSeparator => write!(f, "'{}' is not in KEY=VALUE format", self.key),
+            EnvVarProblem::EmptyKey => write!(f, "environment variable key must not be empty"),
+            EnvVarProblem::ContainsEquals => write!(f, "key '{}' must not contain '='", self.key),
+            EnvVarProblem::ContainsNul => write!(f, "key '{}' must not contain NUL bytes", self.key.escape_default()),
+            EnvVarProblem::LeadingDigit => write!(f, "key '{}' must not start with a digit", self.key),
+            EnvVarProblem::Reserved => write!(
+                f,
+                "key '{}' uses the reserved '{}' prefix",
+                self.key, RESERVED_PREFIX
+            ),
+            EnvVarProblem::Duplicate { first, second } => write!(
+                f,
+                "key '{}' is given more than once ('{}' and '{}')",
+                self.key, first, second
+            ),
+        }
+    }
+}
+
+/// Check the syntax of a single key (the reserved prefix is checked separately)
+pub fn check_key(key: &str) -> Option<EnvVarProblem> {
+    if key.is_empty() {
+        Some(EnvVarProblem::EmptyKey)
+    } else if key.contains('=') {
+        Some(EnvVarProblem::ContainsEquals)
+    } else if key.contains('\0') {
+        Some(EnvVarProblem::ContainsNul)
+    } else if key.starts_with(|c: char| c.is_ascii_digit()) {
+        Some(EnvVarProblem::LeadingDigit)
+    } else {
+        None
+    }
+}
+
+pub fn is_reserved(key: &str) -> bool {
+    key.starts_with(RESERVED_PREFIX)
+}
+
+/// Validate a set of variables, collecting every problem instead of stopping at the first.
+/// On success, returns warnings for reserved keys accepted under [`ReservedEnvPolicy::Warn`].
+pub fn validate_env_vars(
+    env_vars: &HashMap<String, String>,
+    policy: ReservedEnvPolicy,
+) -> std::result::Result<Vec<String>, Vec<EnvVarError>> {
+    let mut keys: Vec<&String> = env_vars.keys().collect();
+    keys.sort();
+
+    let mut errors = Vec::new();
+    let mut warnings = Vec::new();
+    for key in keys {
+        if let Some(problem) = check_key(key) {
+            errors.push(EnvVarError { key: key.clone(), problem });
+        } else if is_reserved(key) {
+            let error = EnvVarError { key: key.clone(), problem: EnvVarProblem::Reserved };
+            match policy {
+                ReservedEnvPolicy::Reject => errors.push(error),
+                ReservedEnvPolicy::Warn => warnings.push(format!(
+                    "{}; pmr-injected variables ({}) take precedence",
+                    error,
+                    INJECTED_VARS.join(", ")
+                )),
+            }
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(warnings)
+    } else {
+        Err(errors)
+    }
+}
+
+/// Placeholder stored instead of the value of a sensitive variable
+pub const REDACTED: &str = "[REDACTED]";
+
+/// Name fragments that mark a variable as holding a secret
+const SENSITIVE_KEY_PARTS: [&str; 7] = ["SECRET", "PASSWORD", "PASSWD", "TOKEN", "CREDENTIAL", "PRIVATE_KEY", "API_KEY"];
+
+/// Whether a variable name suggests a secret value (`DB_PASSWORD`, `GITHUB_TOKEN`, `AWS_SECRET_ACCESS_KEY`, `API_KEY`)
+pub fn is_sensitive_key(key: &str) -> bool {
+    let upper = key.to_ascii_uppercase();
+    SENSITIVE_KEY_PARTS.iter().any(|part| upper.contains(part))
+}
+
+/// The value to show for a variable: [`REDACTED`] for sensitive keys, otherwise the value itself
+pub fn redact_value(key: &str, value: &str) -> String {
+    if is_sensitive_key(key) {
+        REDACTED.to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+/// Environment defaults from the `[env]` and `[env_overrides."<glob>"]` sections of the config file
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EnvDefaults {
+    /// Applied to every started process
+    pub global: BTreeMap<String, String>,
+    /// Applied to processes whose name matches the glob (`*` and `?` wildcards)
+    pub overrides: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+/// Environment a process starts with, after defaults were applied
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EffectiveEnv {
+    pub vars: HashMap<String, String>,
+    /// Keys of `vars` whose value came from defaults, sorted
+    pub default_keys: Vec<String>,
+}
+
+/// Compute the environment a process starts with.
+///
+/// Layers, each overriding the ones before it:
+/// 1. `[env]`
+/// 2. every `[env_overrides]` section whose glob matches `name`, least specific first
+///    (fewer literal characters in the pattern; ties go by pattern text)
+/// 3. `explicit`, the values given with `-e` or in the API request
+pub fn layer_env(
+    defaults: &EnvDefaults,
+    name: &str,
+    explicit: HashMap<String, String>,
+) -> EffectiveEnv {
+    let mut matching: Vec<(&String, &BTreeMap<String, String>)> = defaults
+        .overrides
+        .iter()
+        .filter(|(pattern, _)| glob_matches(pattern, name))
+        .collect();
+    matching.sort_by_key(|(pattern, _)| (literal_len(pattern), *pattern));
+
+    let mut vars = HashMap::new();
+    for layer in std::iter::once(&defaults.global).chain(matching.into_iter().map(|(_, vars)| vars)) {
+        vars.extend(layer.iter().map(|(key, value)| (key.clone(), value.clone())));
+    }
+    let mut default_keys: Vec<String> = vars.keys().filter(|key| !explicit.contains_key(*key)).cloned().collect();
+    default_keys.sort();
+
+    vars.extend(explicit);
+    EffectiveEnv { vars, default_keys }
+}
+
+/// Match `text` against a glob where `*` is any run of characters and `?` any one character
+pub fn glob_matches(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // Position of the last `*` and the text position it was tried at, for backtracking
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                Some((star_p, star_t)) => {
+                    p = star_p + 1;
+                    t = star_t + 1;
+                    star = Some((star_p, star_t + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+fn literal_len(pattern: &str) -> usize {
+    pattern.chars().filter(|c| *c != '*' && *c != '?').count()
+}
+
+/// Deserialize an optional map of environment variables, refusing a key that appears twice
+/// where a plain map would keep the last value without a word
+pub fn deserialize_unique_env<'de, D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Option<HashMap<String, String>>, D::Error> {
+    Option::<UniqueEnv>::deserialize(deserializer).map(|env_vars| env_vars.map(|UniqueEnv(env_vars)| env_vars))
+}
+
+/// Environment variables read by [`deserialize_unique_env`]
+struct UniqueEnv(HashMap<String, String>);
+
+impl<'de> Deserialize<'de> for UniqueEnv {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        struct Visitor;
+
+        impl<'de> de::Visitor<'de> for Visitor {
+            type Value = UniqueEnv;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a map of environment variables")
+            }
+
+            fn visit_map<A: de::MapAccess<'de>>(self, mut map: A) -> std::result::Result<UniqueEnv, A::Error> {
+                let mut env_vars: HashMap<String, String> = HashMap::new();
+                while let Some((key, value)) = map.next_entry::<String, String>()? {
+                    if let Some(first) = env_vars.get(&key) {
+                        let problem = EnvVarProblem::Duplicate { first: first.clone(), second: value };
+                        return Err(de::Error::custom(EnvVarError { key, problem }));
+                    }
+                    env_vars.insert(key, value);
+                }
+                Ok(UniqueEnv(env_vars))
+            }
+        }
+
+        deserializer.deserialize_map(Visitor)
+    }
+}
+
+/// Render a list of problems, one per line
+pub fn describe_errors(errors: &[EnvVarError]) -> String {
+    errors
+        .iter()
+        .map(|e| format!("  - {}", e))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn test_check_key_classes() {
+        assert_eq!(check_key("PATH"), None);
+        assert_eq!(check_key("_private1"), None);
+        assert_eq!(check_key(""), Some(EnvVarProblem::EmptyKey));
+        assert_eq!(check_key("A=B"), Some(EnvVarProblem::ContainsEquals));
+        assert_eq!(check_key("A\0B"), Some(EnvVarProblem::ContainsNul));
+        assert_eq!(check_key("1ABC"), Some(EnvVarProblem::LeadingDigit));
+    }
+
+    #[test]
+    fn test_all_problems_are_reported() {
+        let env = vars(&[("", "x"), ("9LIVES", "x"), ("OK", "x"), ("BAD\0KEY", "x")]);
+        let errors = validate_env_vars(&env, ReservedEnvPolicy::Warn).unwrap_err();
+        assert_eq!(errors.len(), 3);
+    }
+
+    #[test]
+    fn test_sensitive_keys_are_redacted() {
+        for key in ["DB_PASSWORD", "github_token", "AWS_SECRET_ACCESS_KEY", "API_KEY", "STRIPE_API_KEY"] {
+            assert_eq!(redact_value(key, "value"), REDACTED, "{} should be redacted", key);
+        }
+        for key in ["PATH", "HOME", "KEYBOARD", "PMR_PROCESS_NAME"] {
+            assert_eq!(redact_value(key, "value"), "value", "{} should be kept", key);
+        }
+    }
+
+    #[test]
+    fn test_glob_matching() {
+        assert!(glob_matches("web-*", "web-frontend"));
+        assert!(glob_matches("web-*", "web-"));
+        assert!(!glob_matches("web-*", "worker"));
+        assert!(glob_matches("*-worker", "mail-worker"));
+        assert!(glob_matches("db?", "db1"));
+        assert!(!glob_matches("db?", "db12"));
+        assert!(glob_matches("*a*b*", "xaxxbx"));
+        assert!(!glob_matches("*a*b", "xaxxbx"));
+        assert!(glob_matches("api", "api"));
+        assert!(!glob_matches("api", "api2"));
+        assert!(glob_matches("*", ""));
+    }
+
+    #[test]
+    fn test_layer_env_precedence() {
+        let section = |pairs: &[(&str, &str)]| pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        let defaults = EnvDefaults {
+            global: section(&[("TZ", "UTC"), ("HTTP_PROXY", "http://proxy:3128"), ("LEVEL", "global")]),
+            overrides: [
+                ("*".to_string(), section(&[("LEVEL", "any"), ("ANY", "1")])),
+                ("web-*".to_string(), section(&[("LEVEL", "web"), ("PORT", "8080")])),
+                ("web-api".to_string(), section(&[("PORT", "9090")])),
+                ("db-*".to_string(), section(&[("LEVEL", "db")])),
+            ]
+            .into_iter()
+            .collect(),
+        };
+
+        let env = layer_env(&defaults, "web-api", vars(&[("TZ", "Asia/Shanghai"), ("APP", "1")]));
+        assert_eq!(env.vars, vars(&[
+            ("TZ", "Asia/Shanghai"),
+            ("HTTP_PROXY", "http://proxy:3128"),
+            ("LEVEL", "web"),
+            ("ANY", "1"),
+            ("PORT", "9090"),
+            ("APP", "1"),
+        ]));
+        assert_eq!(env.default_keys, ["ANY", "HTTP_PROXY", "LEVEL", "PORT"]);
+
+        let env = layer_env(&defaults, "worker", HashMap::new());
+        assert_eq!(env.vars.get("LEVEL").map(String::as_str), Some("any"));
+        assert!(!env.vars.contains_key("PORT"));
+        assert_eq!(env.default_keys.len(), env.vars.len());
+
+        let env = layer_env(&EnvDefaults::default(), "worker", vars(&[("A", "1")]));
+        assert_eq!(env, EffectiveEnv { vars: vars(&[("A", "1")]), default_keys: Vec::new() });
+    }
+
+    #[test]
+    fn test_reserved_prefix_policy() {
+        let env = vars(&[("PMR_PROCESS_NAME", "spoofed"), ("APP", "1")]);
+
+        let warnings = validate_env_vars(&env, ReservedEnvPolicy::Warn).unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("PMR_PROCESS_NAME"));
+
+        let errors = validate_env_vars(&env, ReservedEnvPolicy::Reject).unwrap_err();
+        assert_eq!(errors, vec![EnvVarError {
+            key: "PMR_PROCESS_NAME".to_string(),
+            problem: EnvVarProblem::Reserved,
+        }]);
+    }
+}
@@ -1,3 +1,5 @@
+use crate::config::ConfigIssue;
+use crate::env::{describe_errors, EnvVarError};
 use std::fmt;
 
 #[derive(Debug)]
@@ -6,8 +8,61 @@ pub enum Error {
     Io(std::io::Error),
     ProcessNotFound(String),
     ProcessAlreadyExists(String),
+    /// A process name broke the naming rules; holds the name and the rule
+    InvalidProcessName(String),
+    /// The name belongs to a process pmr runs for itself
+    ReservedName(String),
+    /// A job with this name already succeeded; running it again needs a rerun
+    JobCompleted(String),
+    /// Another API token holds a reservation of the name; holds the name and the public id
+    /// of that token
+    NameReserved(String, String),
+    /// An idempotent start found the process with a different definition; holds the differing fields
+    DefinitionMismatch(String, Vec<String>),
     InvalidProcessState(String),
+    StopTimedOut(String, u32),
+    /// A restart was refused because the process keeps failing; holds the name, its recent
+    /// failures and the window in seconds
+    Flapping(String, usize, u64),
+    /// Starting would exceed `max_processes`; holds the current count and the limit
+    ProcessLimitReached(usize, usize),
+    /// A mutating operation was called on a read-only manager; holds the operation
+    ReadOnly(String),
+    InvalidEnvVars(Vec<EnvVarError>),
+    InvalidCpuAffinity(String),
+    InvalidUmask(String),
+    InvalidDuration(String),
+    InvalidSize(String),
+    /// The arguments or environment of a start are over a `start_limits` size limit; holds
+    /// what is too large
+    StartTooLarge(String),
+    /// The log file of a process could not be read; holds its path and why
+    LogUnreadable(String, std::io::Error),
+    /// A note was empty or over the size limit; holds the reason
+    InvalidNote(String),
+    /// A process description was too long or held control characters; holds the reason
+    InvalidDescription(String),
+    /// The process already has the maximum number of notes; holds the name and the limit
+    NoteLimitReached(String, usize),
+    /// No note with this id on the process; holds the name and the id
+    NoteNotFound(String, i64),
     SerializationError(serde_json::Error),
+    Config(String),
+    /// Config::validate found settings pmr cannot work with; holds the errors
+    InvalidConfig(Vec<ConfigIssue>),
+    /// A strict start saw the process exit within the start window; holds the name, the
+    /// exit code (None when killed by a signal) and its last log lines
+    StartExited(String, Option<i32>, Vec<String>),
+    /// A start failed and undoing part of what it had done failed too
+    StartRolledBack(Box<Error>, Vec<String>),
+    /// The database was written by a newer pmr; holds its schema version, the version
+    /// this binary supports and the oldest release that reads it
+    SchemaTooNew(i64, i64, String),
+    /// Migrating the database would rebuild tables and that was not allowed; holds its
+    /// schema version and the rebuilding migrations
+    MigrationRequired(i64, Vec<String>),
+    /// SQLite found the database file damaged or not a database; holds its message
+    DatabaseCorrupt(String),
     Other(String),
 }
 
@@ -18,14 +73,173 @@ impl fmt::Display for Error {
             Error::Io(e) => write!(f, "IO error: {}", e),
             Error::ProcessNotFound(name) => write!(f, "Process '{}' not found", name),
             Error::ProcessAlreadyExists(name) => write!(f, "Process '{}' already exists", name),
+            Error::InvalidProcessName(msg) => write!(f, "Invalid process name {}", msg),
+            Error::ReservedName(name) => write!(f, "Process name '{}' is reserved for pmr's own HTTP server", name),
+            Error::JobCompleted(name) => write!(f, "Job '{}' already completed (use --rerun)", name),
+            Error::NameReserved(name, token) => write!(f, "Process name '{}' is reserved by token {}", name, token),
+            Error::DefinitionMismatch(name, fields) => write!(
+                f,
+                "Process '{}' already exists with a different definition (differs in: {})",
+                name,
+                fields.join(", ")
+            ),
             Error::InvalidProcessState(msg) => write!(f, "Invalid process state: {}", msg),
+            Error::StopTimedOut(name, pid) => write!(
+                f,
+                "Process '{}' (PID {}) is still running after the stop grace period; retry with `--signal KILL` or `--escalate`",
+                name, pid
+            ),
+            Error::Flapping(name, failures, window_secs) => write!(
+                f,
+                "Process '{}' is flapping: it failed {} times in the last {}; restart it anyway with `--force`",
+                name,
+                failures,
+                crate::format::human_duration(std::time::Duration::from_secs(*window_secs))
+            ),
+            Error::ProcessLimitReached(current, max) => write!(
+                f,
+                "Process limit reached: {} of {} allowed processes are managed; delete some or raise max_processes",
+                current, max
+            ),
+            Error::ReadOnly(operation) => write!(f, "Cannot {}: pmr was opened in read-only mode", operation),
+            Error::InvalidEnvVars(errors) => {
+                write!(f, "Invalid environment variables:\n{}", describe_errors(errors))
+            }
+            Error::InvalidCpuAffinity(msg) => write!(f, "Invalid CPU affinity {}", msg),
+            Error::InvalidUmask(msg) => write!(f, "Invalid umask {}", msg),
+            Error::InvalidDuration(msg) => write!(f, "Invalid duration {}", msg),
+            Error::InvalidSize(msg) => write!(f, "Invalid size {}", msg),
+            Error::StartTooLarge(msg) => write!(
+                f,
+                "Cannot start: {}; put large data in a file and pass the process its path instead",
+                msg
+            ),
+            Error::InvalidNote(msg) => write!(f, "Invalid note: {}", msg),
+            Error::InvalidDescription(msg) => write!(f, "Invalid description: {}", msg),
+            Error::NoteLimitReached(name, max) => write!(
+                f,
+                "Process '{}' already has {} notes; delete some with `pmr notes {} --delete <id>`",
+                name, max, name
+            ),
+            Error::NoteNotFound(name, id) => write!(f, "Process '{}' has no note {}", name, id),
             Error::SerializationError(e) => write!(f, "Serialization error: {}", e),
+            Error::Config(msg) => write!(f, "Configuration error: {}", msg),
+            Error::InvalidConfig(issues) => {
+                write!(f, "Invalid configuration:")?;
+                for issue in issues {
+                    write!(f, "\n  - {}: {}", issue.key, issue.message)?;
+                }
+                Ok(())
+            }
+            Error::StartExited(name, code, output) => {
+                match code {
+                    Some(code) => write!(f, "Process '{}' exited with code {} during the start window", name, code)?,
+                    None => write!(f, "Process '{}' was killed by a signal during the start window", name)?,
+                }
+                write!(f, " (strict start, nothing was kept)")?;
+                if !output.is_empty() {
+                    write!(f, "\nLast log lines:")?;
+                    for line in output {
+                        write!(f, "\n  {}", line)?;
+                    }
+                }
+                Ok(())
+            }
+            Error::StartRolledBack(error, problems) => {
+                write!(f, "{}\nWarning: rollback was incomplete:", error)?;
+                for problem in problems {
+                    write!(f, "\n  - {}", problem)?;
+                }
+                Ok(())
+            }
+            Error::SchemaTooNew(version, supported, min_binary_version) => write!(
+                f,
+                "Database schema version {} is newer than this pmr {} supports (schema version {}); install pmr {} or later",
+                version,
+                env!("CARGO_PKG_VERSION"),
+                supported,
+                if min_binary_version.is_empty() { "a newer release" } else { min_binary_version }
+            ),
+            Error::MigrationRequired(version, migrations) => {
+                write!(
+                    f,
+                    "Database schema version {} must be migrated to version {} by rebuilding tables:",
+                    version,
+                    crate::database::SCHEMA_VERSION
+                )?;
+                for migration in migrations {
+                    write!(f, "\n  - {}", migration)?;
+                }
+                write!(f, "\nBack up the database, then rerun with --migrate or set PMR_AUTO_MIGRATE=1")
+            }
+            Error::DatabaseCorrupt(msg) => write!(
+                f,
+                "The database is damaged ({}); recover what is readable with `pmr db salvage <path>`",
+                msg
+            ),
+            Error::LogUnreadable(path, e) => match e.kind() {
+                std::io::ErrorKind::NotFound => write!(f, "Log file '{}' does not exist; it was removed or the process never started", path),
+                std::io::ErrorKind::PermissionDenied => write!(f, "Permission denied reading log file '{}'", path),
+                _ => write!(f, "Failed to read log file '{}': {}", path, e),
+            },
             Error::Other(msg) => write!(f, "{}", msg),
         }
     }
 }
 
-impl std::error::Error for Error {}
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Database(e) => Some(e),
+            Error::Io(e) | Error::LogUnreadable(_, e) => Some(e),
+            Error::SerializationError(e) => Some(e),
+            Error::StartRolledBack(e, _) => Some(e.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+/// Lets pmr calls be used with `?` in code returning `std::io::Result`
+impl From<Error> for std::io::Error {
+    fn from(err: Error) -> Self {
+        use std::io::ErrorKind;
+
+        let kind = match err {
+            Error::Io(e) => return e,
+            Error::LogUnreadable(_, ref e) => e.kind(),
+            Error::ProcessNotFound(_) | Error::NoteNotFound(_, _) => ErrorKind::NotFound,
+            Error::ProcessAlreadyExists(_) | Error::JobCompleted(_) | Error::NameReserved(_, _) | Error::DefinitionMismatch(_, _) => {
+                ErrorKind::AlreadyExists
+            }
+            Error::InvalidProcessState(_)
+            | Error::ReservedName(_)
+            | Error::InvalidProcessName(_)
+            | Error::InvalidEnvVars(_)
+            | Error::InvalidCpuAffinity(_)
+            | Error::InvalidUmask(_)
+            | Error::InvalidDuration(_)
+            | Error::InvalidSize(_)
+            | Error::StartTooLarge(_)
+            | Error::InvalidNote(_)
+            | Error::InvalidDescription(_)
+            | Error::Config(_)
+            | Error::InvalidConfig(_) => ErrorKind::InvalidInput,
+            Error::StopTimedOut(_, _) => ErrorKind::TimedOut,
+            Error::ReadOnly(_) => ErrorKind::PermissionDenied,
+            Error::SerializationError(_) | Error::DatabaseCorrupt(_) => ErrorKind::InvalidData,
+            Error::SchemaTooNew(_, _, _) => ErrorKind::Unsupported,
+            Error::Database(_)
+            | Error::ProcessLimitReached(_, _)
+            | Error::Flapping(_, _, _)
+            | Error::NoteLimitReached(_, _)
+            | Error::StartExited(_, _, _)
+            | Error::StartRolledBack(_, _)
+            | Error::MigrationRequired(_, _)
+            | Error::Other(_) => ErrorKind::Other,
+        };
+        std::io::Error::new(kind, err)
+    }
+}
 
 impl From<sqlx::Error> for Error {
     fn from(err: sqlx::Error) -> Self {
@@ -46,3 +260,47 @@ impl From<serde_json::Error> for Error {
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::ErrorKind;
+
+    #[test]
+    fn test_into_io_error() {
+        let io: std::io::Error = Error::ProcessNotFound("web".to_string()).into();
+        assert_eq!(io.kind(), ErrorKind::NotFound);
+        assert_eq!(io.to_string(), "Process 'web' not found");
+
+        let original = std::io::Error::new(ErrorKind::PermissionDenied, "denied");
+        let io: std::io::Error = Error::Io(original).into();
+        assert_eq!(io.kind(), ErrorKind::PermissionDenied);
+        assert_eq!(io.to_string(), "denied");
+
+        let io: std::io::Error = Error::StopTimedOut("web".to_string(), 42).into();
+        assert_eq!(io.kind(), ErrorKind::TimedOut);
+    }
+
+    #[test]
+    fn test_unreadable_log_says_why() {
+        let unreadable = |kind| Error::LogUnreadable("/srv/web.log".to_string(), std::io::Error::from(kind));
+        let missing = unreadable(ErrorKind::NotFound);
+        assert_eq!(missing.to_string(), "Log file '/srv/web.log' does not exist; it was removed or the process never started");
+        let denied = unreadable(ErrorKind::PermissionDenied);
+        assert_eq!(denied.to_string(), "Permission denied reading log file '/srv/web.log'");
+        assert!(unreadable(ErrorKind::InvalidData).to_string().starts_with("Failed to read log file '/srv/web.log': "));
+
+        let io: std::io::Error = denied.into();
+        assert_eq!(io.kind(), ErrorKind::PermissionDenied);
+        assert_eq!(std::io::Error::from(missing).kind(), ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn test_source_is_the_wrapped_error() {
+        use std::error::Error as _;
+
+        let err = Error::Io(std::io::Error::other("disk full"));
+        assert_eq!(err.source().unwrap().to_string(), "disk full");
+        assert!(Error::Other("plain".to_string()).source().is_none());
+    }
+}
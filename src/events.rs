@@ -0,0 +1,202 @@
+//! Process lifecycle events, published by [`ProcessManager`](crate::process::ProcessManager)
+//! on a tokio broadcast channel; see [`ProcessManager::subscribe`](crate::process::ProcessManager::subscribe).
+//!
+//! Publishing never blocks and never fails the operation that caused the event. Delivery
+//! is lossy under lag: each subscriber has room for [`EVENT_CAPACITY`] unread events, and
+//! one that falls further behind skips the oldest and gets `RecvError::Lagged(n)` telling
+//! it how many it missed. Events published before a receiver subscribed are not replayed.
+//! The webhook notifier is itself a subscriber.
+
+use crate::database::ProcessStatus;
+use crate::flapping::FlappingTransition;
+use crate::notify::{DELETED_STATUS, FLAPPING_STATUS, LOGS_OVER_QUOTA_STATUS};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::sync::broadcast;
+
+/// Unread events kept per subscriber before the oldest are dropped for it
+pub const EVENT_CAPACITY: usize = 1024;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProcessEvent {
+    pub process: String,
+    pub timestamp: DateTime<Utc>,
+    #[serde(flatten)]
+    pub kind: ProcessEventKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum ProcessEventKind {
+    /// A start left the process running
+    Started { pid: Option<u32> },
+    /// The process stopped, through `stop` or by exiting; `from` is None when it exited
+    /// cleanly within the start window
+    Stopped { from: Option<ProcessStatus>, exit_code: Option<i32> },
+    /// The process failed; `from` is None when it failed within the start window
+    Failed { from: Option<ProcessStatus>, exit_code: Option<i32> },
+    /// A job exited with code 0; `from` is None when it did so within the start window
+    Succeeded { from: Option<ProcessStatus> },
+    /// `restart` brought up a new instance; follows that instance's own start event
+    Restarted { pid: Option<u32>, previous_pid: Option<u32> },
+    /// The record was soft-deleted, or removed for good when `hard`
+    Deleted { from: ProcessStatus, hard: bool },
+    /// A soft-deleted record was brought back
+    Restored { status: ProcessStatus },
+    /// The log was rotated; `trigger` is `size`, `manual` or `restart`
+    LogRotated { trigger: String, rotated_from_size: u64 },
+    /// A status refresh found a change not covered by the variants above
+    StatusChanged { from: ProcessStatus, to: ProcessStatus, exit_code: Option<i32> },
+    /// The process failed `failures` times within `window_secs` and is now flapping
+    FlappingEntered { status: ProcessStatus, failures: usize, window_secs: u64 },
+    /// Enough failures left the window that the process is no longer flapping
+    FlappingCleared { status: ProcessStatus },
+    /// All logs together stay over the quota with every archive deleted; published for the
+    /// process with the largest live log
+    LogsOverQuota { used_bytes: u64, quota_bytes: u64 },
+    /// The spawn failed for lack of processes or memory and the start joined the start
+    /// queue; `queue_depth` counts the starts waiting, this one included
+    StartQueued { error: String, queue_depth: usize },
+}
+
+impl ProcessEventKind {
+    /// The event for a change of recorded status
+    pub fn transition(from: Option<ProcessStatus>, to: ProcessStatus, exit_code: Option<i32>) -> Self {
+        match (from, to) {
+            (from, ProcessStatus::Stopped) => Self::Stopped { from, exit_code },
+            (from, ProcessStatus::Failed) => Self::Failed { from, exit_code },
+            (from, ProcessStatus::Succeeded) => Self::Succeeded { from },
+            (None, ProcessStatus::Running) => Self::Started { pid: None },
+            (Some(from), to) => Self::StatusChanged { from, to, exit_code },
+            (None, to) => Self::StatusChanged { from: ProcessStatus::Unknown, to, exit_code },
+        }
+    }
+
+    /// The event for a change of flapping state of a process with this status
+    pub fn flapping(transition: FlappingTransition, status: ProcessStatus, failures: usize, window_secs: u64) -> Self {
+        match transition {
+            FlappingTransition::Entered => Self::FlappingEntered { status, failures, window_secs },
+            FlappingTransition::Cleared => Self::FlappingCleared { status },
+        }
+    }
+}
+
+impl ProcessEvent {
+    /// Status change this event stands for, as (old status, new status, exit code), with
+    /// deletion reported as the `deleted` status and flapping as the `flapping` status. None
+    /// for events that change no status.
+    pub fn status_change(&self) -> Option<(Option<String>, String, Option<i32>)> {
+        let name = |status: &ProcessStatus| status.to_string();
+        match &self.kind {
+            ProcessEventKind::Started { .. } => Some((None, name(&ProcessStatus::Running), None)),
+            ProcessEventKind::Stopped { from, exit_code } => {
+                Some((from.as_ref().map(name), name(&ProcessStatus::Stopped), *exit_code))
+            }
+            ProcessEventKind::Failed { from, exit_code } => {
+                Some((from.as_ref().map(name), name(&ProcessStatus::Failed), *exit_code))
+            }
+            ProcessEventKind::Succeeded { from } => Some((from.as_ref().map(name), name(&ProcessStatus::Succeeded), Some(0))),
+            ProcessEventKind::Deleted { from, .. } => Some((Some(name(from)), DELETED_STATUS.to_string(), None)),
+            ProcessEventKind::Restored { status } => Some((Some(DELETED_STATUS.to_string()), name(status), None)),
+            ProcessEventKind::StatusChanged { from, to, exit_code } => Some((Some(name(from)), name(to), *exit_code)),
+            ProcessEventKind::FlappingEntered { status, .. } => Some((Some(name(status)), FLAPPING_STATUS.to_string(), None)),
+            ProcessEventKind::FlappingCleared { status } => Some((Some(FLAPPING_STATUS.to_string()), name(status), None)),
+            ProcessEventKind::LogsOverQuota { .. } => Some((None, LOGS_OVER_QUOTA_STATUS.to_string(), None)),
+            ProcessEventKind::Restarted { .. } | ProcessEventKind::LogRotated { .. } | ProcessEventKind::StartQueued { .. } => None,
+        }
+    }
+}
+
+/// Sending side of the event stream, shared by a manager and its background tasks
+#[derive(Debug, Clone)]
+pub struct EventBus {
+    sender: broadcast::Sender<ProcessEvent>,
+    published: Arc<AtomicU64>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(EVENT_CAPACITY);
+        Self { sender, published: Arc::default() }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<ProcessEvent> {
+        self.sender.subscribe()
+    }
+
+    /// Send to current subscribers without waiting; having none is not an error
+    pub fn publish(&self, event: ProcessEvent) {
+        tracing::debug!(process = %event.process, event = ?event.kind, "process event");
+        self.published.fetch_add(1, Ordering::SeqCst);
+        let _ = self.sender.send(event);
+    }
+
+    /// Events published so far
+    pub fn published(&self) -> u64 {
+        self.published.load(Ordering::SeqCst)
+    }
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(kind: ProcessEventKind) -> ProcessEvent {
+        ProcessEvent { process: "web".to_string(), timestamp: Utc::now(), kind }
+    }
+
+    #[test]
+    fn test_transition_kinds_and_status_changes() {
+        use ProcessStatus::*;
+        assert_eq!(ProcessEventKind::transition(Some(Running), Stopped, Some(3)), ProcessEventKind::Stopped { from: Some(Running), exit_code: Some(3) });
+        assert_eq!(ProcessEventKind::transition(None, Failed, Some(1)), ProcessEventKind::Failed { from: None, exit_code: Some(1) });
+        assert_eq!(ProcessEventKind::transition(Some(Stopped), Running, None), ProcessEventKind::StatusChanged { from: Stopped, to: Running, exit_code: None });
+
+        let change = |kind| event(kind).status_change();
+        assert_eq!(change(ProcessEventKind::Started { pid: Some(7) }), Some((None, "running".to_string(), None)));
+        assert_eq!(
+            change(ProcessEventKind::Deleted { from: Stopped, hard: true }),
+            Some((Some("stopped".to_string()), "deleted".to_string(), None))
+        );
+        assert_eq!(
+            change(ProcessEventKind::Restored { status: Stopped }),
+            Some((Some("deleted".to_string()), "stopped".to_string(), None))
+        );
+        assert_eq!(
+            change(ProcessEventKind::flapping(FlappingTransition::Entered, Failed, 5, 600)),
+            Some((Some("failed".to_string()), "flapping".to_string(), None))
+        );
+        assert_eq!(
+            change(ProcessEventKind::flapping(FlappingTransition::Cleared, Stopped, 5, 600)),
+            Some((Some("flapping".to_string()), "stopped".to_string(), None))
+        );
+        assert_eq!(change(ProcessEventKind::LogRotated { trigger: "manual".to_string(), rotated_from_size: 10 }), None);
+    }
+
+    #[tokio::test]
+    async fn test_slow_subscriber_lags_without_blocking() {
+        let bus = EventBus::new();
+        bus.publish(event(ProcessEventKind::Started { pid: None }));
+        let mut receiver = bus.subscribe();
+        for pid in 0..EVENT_CAPACITY as u32 + 5 {
+            bus.publish(event(ProcessEventKind::Started { pid: Some(pid) }));
+        }
+        assert_eq!(bus.published(), EVENT_CAPACITY as u64 + 6);
+
+        assert!(matches!(receiver.recv().await, Err(broadcast::error::RecvError::Lagged(5))));
+        let next = receiver.recv().await.unwrap();
+        assert_eq!(next.kind, ProcessEventKind::Started { pid: Some(5) });
+
+        let json = serde_json::to_value(&next).unwrap();
+        assert_eq!(json["event"], "started");
+        assert_eq!(json["process"], "web");
+    }
+}
@@ -0,0 +1,214 @@
+//! Bounded pool for file operations that sweep every process, like measuring all the logs
+//! for `pmr stats` and the logs quota. Operations run on blocking threads, at most
+//! [`FileOpsConfig::concurrency`] at once so a sweep over thousands of processes neither
+//! runs one file at a time nor opens thousands of descriptors together.
+//!
+//! Each operation has a timeout: one that takes longer, say on a hung network mount, is
+//! reported as unknown (None) and the sweep goes on without it. The operation itself
+//! cannot be interrupted, so it keeps its place in the pool until it returns. Waits for a
+//! place show in `--timings` as `file_pool.wait`; [`FilePool::stats`] counts them.
+
+use crate::config::FileOpsConfig;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// How busy the pool is and has been
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "http-api", derive(utoipa::ToSchema))]
+pub struct FilePoolStats {
+    /// Most operations running at once
+    pub limit: usize,
+    /// Operations running now, including timed-out ones that have not returned
+    pub in_flight: usize,
+    /// Most operations that ever ran at once
+    pub peak_in_flight: usize,
+    /// Operations that had to wait for a place because the pool was full
+    pub waited: u64,
+    /// Operations reported as unknown because they took longer than the timeout
+    pub timed_out: u64,
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    in_flight: AtomicUsize,
+    peak_in_flight: AtomicUsize,
+    waited: AtomicU64,
+    timed_out: AtomicU64,
+}
+
+/// Shared by all sweeps of one manager; cheap to clone
+#[derive(Debug, Clone)]
+pub struct FilePool {
+    permits: Arc<Semaphore>,
+    limit: usize,
+    timeout: Duration,
+    counters: Arc<Counters>,
+}
+
+/// Counts an operation as running until its blocking thread is done with it
+struct InFlight(Arc<Counters>);
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.0.in_flight.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl FilePool {
+    pub fn new(config: &FileOpsConfig) -> Self {
+        let limit = config.concurrency.max(1);
+        Self {
+            permits: Arc::new(Semaphore::new(limit)),
+            limit,
+            timeout: config.timeout(),
+            counters: Arc::default(),
+        }
+    }
+
+    /// Run `op` on a blocking thread once the pool has room. None when it took longer
+    /// than the timeout or panicked.
+    pub async fn run<T, F>(&self, op: F) -> Option<T>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let permit = match self.permits.clone().try_acquire_owned() {
+            Ok(permit) => permit,
+            Err(_) => {
+                self.counters.waited.fetch_add(1, Ordering::Relaxed);
+                self.wait_for_room().await?
+            }
+        };
+        let in_flight = self.counters.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+        self.counters.peak_in_flight.fetch_max(in_flight, Ordering::SeqCst);
+        let guard = InFlight(self.counters.clone());
+        let task = tokio::task::spawn_blocking(move || {
+            let _permit = permit;
+            let _guard = guard;
+            op()
+        });
+        match tokio::time::timeout(self.timeout, task).await {
+            Ok(result) => result.ok(),
+            Err(_) => {
+                self.counters.timed_out.fetch_add(1, Ordering::Relaxed);
+                tracing::warn!(timeout = ?self.timeout, "file operation timed out; reported as unknown");
+                None
+            }
+        }
+    }
+
+    /// [`Self::run`] `op` on every item, all of them queued at once; the results are in
+    /// the order of the items
+    pub async fn run_all<I, T, F>(&self, items: impl IntoIterator<Item = I>, op: F) -> Vec<Option<T>>
+    where
+        I: Send + 'static,
+        T: Send + 'static,
+        F: Fn(I) -> T + Send + Sync + 'static,
+    {
+        let op = Arc::new(op);
+        futures_util::future::join_all(items.into_iter().map(|item| {
+            let op = op.clone();
+            self.run(move || op(item))
+        }))
+        .await
+    }
+
+    #[tracing::instrument(name = "file_pool.wait", skip_all)]
+    async fn wait_for_room(&self) -> Option<OwnedSemaphorePermit> {
+        // The semaphore is never closed
+        self.permits.clone().acquire_owned().await.ok()
+    }
+
+    pub fn stats(&self) -> FilePoolStats {
+        FilePoolStats {
+            limit: self.limit,
+            in_flight: self.counters.in_flight.load(Ordering::SeqCst),
+            peak_in_flight: self.counters.peak_in_flight.load(Ordering::SeqCst),
+            waited: self.counters.waited.load(Ordering::Relaxed),
+            timed_out: self.counters.timed_out.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::{Path, PathBuf};
+    use std::time::Instant;
+
+    /// Stats `path` like a sweep would, tracking how many calls overlap; the file named
+    /// `slow` stands in for one on a hung mount
+    #[derive(Default)]
+    struct StatShim {
+        running: AtomicUsize,
+        most_running: AtomicUsize,
+    }
+
+    impl StatShim {
+        fn stat(&self, path: &Path) -> Option<u64> {
+            let running = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+            self.most_running.fetch_max(running, Ordering::SeqCst);
+            if path.ends_with("slow") {
+                std::thread::sleep(Duration::from_secs(1));
+            } else {
+                std::thread::sleep(Duration::from_millis(1));
+            }
+            let size = std::fs::metadata(path).ok().map(|metadata| metadata.len());
+            self.running.fetch_sub(1, Ordering::SeqCst);
+            size
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_bulk_stat_stays_at_the_cap() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let paths: Vec<PathBuf> = (0..3000)
+            .map(|i| {
+                let path = dir.path().join(format!("{}.log", i));
+                std::fs::write(&path, vec![b'x'; i % 7]).unwrap();
+                path
+            })
+            .collect();
+        let pool = FilePool::new(&FileOpsConfig { concurrency: 16, timeout_ms: 10_000 });
+        let shim = Arc::new(StatShim::default());
+
+        let stat = shim.clone();
+        let sizes = pool.run_all(paths, move |path| stat.stat(&path)).await;
+
+        assert_eq!(sizes.len(), 3000);
+        assert!(sizes.iter().enumerate().all(|(i, size)| *size == Some(Some((i % 7) as u64))));
+        assert_eq!(shim.most_running.load(Ordering::SeqCst), 16);
+        let stats = pool.stats();
+        assert_eq!(stats.peak_in_flight, 16);
+        assert_eq!(stats.in_flight, 0);
+        assert!(stats.waited > 0);
+        assert_eq!(stats.timed_out, 0);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_slow_file_is_unknown_without_holding_up_the_rest() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let mut paths: Vec<PathBuf> = (0..200).map(|i| dir.path().join(format!("{}.log", i))).collect();
+        paths.insert(100, dir.path().join("slow"));
+        for path in &paths {
+            std::fs::write(path, b"log").unwrap();
+        }
+        let timeout = Duration::from_millis(300);
+        let pool = FilePool::new(&FileOpsConfig { concurrency: 8, timeout_ms: timeout.as_millis() as u64 });
+        let shim = Arc::new(StatShim::default());
+
+        let started = Instant::now();
+        let stat = shim.clone();
+        let sizes = pool.run_all(paths, move |path| stat.stat(&path)).await;
+
+        assert!(started.elapsed() < timeout + Duration::from_millis(700), "took {:?}", started.elapsed());
+        assert_eq!(sizes[100], None);
+        assert_eq!(sizes.iter().filter(|size| size.is_none()).count(), 1);
+        assert_eq!(pool.stats().timed_out, 1);
+        // The slow stat still holds its place until it returns
+        assert_eq!(pool.stats().in_flight, 1);
+    }
+}
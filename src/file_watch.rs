@@ -0,0 +1,433 @@
+//! Restarting a process when files it was started to watch (`pmr start --watch`) change.
+//!
+//! Watched paths are followed with the platform's file-notification API (inotify, FSEvents,
+//! kqueue or ReadDirectoryChangesW, through the `notify` crate), so a large tree costs
+//! nothing between changes. Changes seen within the debounce window after the first one
+//! lead to a single restart. The API server watches every running process that has a
+//! watch configuration; `pmr watch <name>` does the same for one process in the foreground.
+
+use crate::{
+    cancel::Cancellation,
+    database::{ProcessRecord, ProcessStatus},
+    env::glob_matches,
+    name::ProcessName,
+    outcome::RestartOutcome,
+    process::ProcessManager,
+    Error, Result,
+};
+use notify::{EventKind, RecursiveMode, Watcher};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+/// Changes within this long after the first one are collected into one restart
+pub const DEFAULT_WATCH_DEBOUNCE_MS: u64 = 500;
+
+/// Always ignored: version control data and logs, which a process in a watched directory
+/// may well be writing itself
+pub const DEFAULT_WATCH_IGNORE: [&str; 2] = [".git/", "*.log"];
+
+/// Restarts kept in a process's watch history
+pub const WATCH_HISTORY_LIMIT: usize = 100;
+
+/// How often a watcher re-reads its process, to follow a changed watch configuration or a
+/// deletion, and looks again for watched paths that did not exist yet
+const WATCH_RECHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How often the API server looks for running processes that have no watcher yet
+const SUPERVISE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// What a process started with `--watch` watches
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "http-api", derive(utoipa::ToSchema))]
+pub struct WatchConfig {
+    /// Files and directories (watched recursively), absolute once the process is started
+    pub paths: Vec<String>,
+    /// Globs for paths to leave out. One ending in `/` matches directories by name; one
+    /// containing `/` matches the path relative to the watched directory; others match
+    /// file names.
+    pub ignore: Vec<String>,
+    pub debounce_ms: u64,
+}
+
+impl WatchConfig {
+    /// Watch `paths`, ignoring [`DEFAULT_WATCH_IGNORE`] and `ignore`
+    pub fn new(paths: Vec<String>, ignore: Vec<String>, debounce_ms: Option<u64>) -> Self {
+        let mut all_ignored: Vec<String> = DEFAULT_WATCH_IGNORE.iter().map(|pattern| pattern.to_string()).collect();
+        for pattern in ignore {
+            if !all_ignored.contains(&pattern) {
+                all_ignored.push(pattern);
+            }
+        }
+        Self { paths, ignore: all_ignored, debounce_ms: debounce_ms.unwrap_or(DEFAULT_WATCH_DEBOUNCE_MS) }
+    }
+
+    /// The same configuration with relative paths taken from `working_dir`
+    pub fn resolved(mut self, working_dir: &str) -> Self {
+        for path in &mut self.paths {
+            if Path::new(path.as_str()).is_relative() {
+                *path = Path::new(working_dir).join(path.as_str()).to_string_lossy().to_string();
+            }
+        }
+        self
+    }
+
+    pub fn debounce(&self) -> Duration {
+        Duration::from_millis(self.debounce_ms)
+    }
+
+    /// Whether `relative`, a path below a watched directory, is left out
+    pub fn is_ignored(&self, relative: &Path, is_dir: bool) -> bool {
+        let relative_text = relative.to_string_lossy();
+        let file_name = relative.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+        self.ignore.iter().any(|pattern| match pattern.strip_suffix('/') {
+            Some(directory) => is_dir && glob_matches(directory, &file_name),
+            None if pattern.contains('/') => glob_matches(pattern, &relative_text),
+            None => glob_matches(pattern, &file_name),
+        })
+    }
+
+    /// Whether `relative`, a path below a watched directory, is left out itself or lies in
+    /// a directory that is
+    fn is_ignored_below(&self, relative: &Path, is_dir: bool) -> bool {
+        relative.ancestors().skip(1).filter(|ancestor| !ancestor.as_os_str().is_empty()).any(|ancestor| self.is_ignored(ancestor, true))
+            || self.is_ignored(relative, is_dir)
+    }
+
+    /// Whether a notification about `path` is a change of the watched files
+    fn is_watched(&self, path: &Path) -> bool {
+        self.paths.iter().map(Path::new).any(|root| {
+            if path == root {
+                return true;
+            }
+            match path.strip_prefix(root) {
+                Ok(relative) => !self.is_ignored_below(relative, path.is_dir()),
+                Err(_) => false,
+            }
+        })
+    }
+}
+
+/// Follows the watched paths and collects the files that change
+pub struct PathWatcher {
+    config: WatchConfig,
+    watcher: notify::RecommendedWatcher,
+    changed: mpsc::UnboundedReceiver<PathBuf>,
+    /// Watched paths that did not exist yet when last tried
+    missing: Vec<PathBuf>,
+}
+
+impl PathWatcher {
+    /// Watch from the current state of the paths on
+    pub fn new(config: WatchConfig) -> Result<Self> {
+        let (sender, changed) = mpsc::unbounded_channel();
+        let filter = config.clone();
+        let handler = move |event: notify::Result<notify::Event>| {
+            let event = match event {
+                Ok(event) => event,
+                Err(e) => {
+                    tracing::warn!(error = %e, "file notification failed");
+                    return;
+                }
+            };
+            // Reading a file changes nothing
+            if matches!(event.kind, EventKind::Access(_)) {
+                return;
+            }
+            // Notifications were lost, so anything under the watched paths may have changed
+            if event.need_rescan() {
+                for root in &filter.paths {
+                    let _ = sender.send(PathBuf::from(root));
+                }
+                return;
+            }
+            for path in event.paths {
+                if filter.is_watched(&path) {
+                    let _ = sender.send(path);
+                }
+            }
+        };
+        // Symbolic links are not followed, so a link back up the tree cannot loop
+        let notify_config = notify::Config::default().with_follow_symlinks(false);
+        let watcher = notify::RecommendedWatcher::new(handler, notify_config).map_err(watch_error)?;
+        let mut path_watcher = Self { config, watcher, changed, missing: Vec::new() };
+        let roots: Vec<PathBuf> = path_watcher.config.paths.iter().map(PathBuf::from).collect();
+        for root in roots {
+            if !path_watcher.watch_root(&root)? {
+                path_watcher.missing.push(root);
+            }
+        }
+        Ok(path_watcher)
+    }
+
+    pub fn config(&self) -> &WatchConfig {
+        &self.config
+    }
+
+    /// Start watching `root`; false if it does not exist yet. A file is followed through its
+    /// directory, so editors that save by replacing the file are still seen.
+    fn watch_root(&mut self, root: &Path) -> Result<bool> {
+        let Ok(metadata) = std::fs::symlink_metadata(root) else { return Ok(false) };
+        let (path, mode) = match root.parent() {
+            Some(parent) if !metadata.is_dir() => (parent, RecursiveMode::NonRecursive),
+            _ => (root, RecursiveMode::Recursive),
+        };
+        match self.watcher.watch(path, mode) {
+            Ok(()) => Ok(true),
+            // Removed again in the meantime
+            Err(notify::Error { kind: notify::ErrorKind::PathNotFound, .. }) => Ok(false),
+            Err(e) => Err(watch_error(e)),
+        }
+    }
+
+    /// Watch the paths that have appeared since they were last tried; each counts as changed
+    pub fn watch_new_paths(&mut self) -> Result<Vec<PathBuf>> {
+        let mut appeared = Vec::new();
+        for root in std::mem::take(&mut self.missing) {
+            if self.watch_root(&root)? {
+                appeared.push(root);
+            } else {
+                self.missing.push(root);
+            }
+        }
+        Ok(appeared)
+    }
+
+    /// Wait for the next changed file; None once notifications can no longer arrive
+    pub async fn next_change(&mut self) -> Option<PathBuf> {
+        self.changed.recv().await
+    }
+
+    /// Files changed since the previous call that have not been taken yet, in path order
+    pub fn changes(&mut self) -> Vec<PathBuf> {
+        let mut changed = BTreeSet::new();
+        while let Ok(path) = self.changed.try_recv() {
+            changed.insert(path);
+        }
+        changed.into_iter().collect()
+    }
+}
+
+fn watch_error(e: notify::Error) -> Error {
+    Error::Other(format!("Could not watch files: {}", e))
+}
+
+/// A restart made because watched files changed
+#[derive(Debug, Clone, Serialize)]
+pub struct WatchRestart {
+    /// The first file that changed
+    pub path: String,
+    /// Files that changed within the debounce window
+    pub changes: usize,
+    pub outcome: RestartOutcome,
+}
+
+/// Whether a change restarts the process: it is running, or failed, where an edit is likely
+/// the fix. A process stopped on purpose stays stopped.
+fn restarts_on_change(process: &ProcessRecord) -> bool {
+    match process.status {
+        ProcessStatus::Running | ProcessStatus::Failed => true,
+        ProcessStatus::Stopped => process.exit_code.is_some_and(|code| code != 0),
+        _ => false,
+    }
+}
+
+/// Restart `name` whenever its watched paths change, until `cancellation` fires or the
+/// process is deleted or loses its watch configuration. `on_restart` hears about every
+/// restart attempt.
+pub async fn watch_process(
+    process_manager: &ProcessManager,
+    name: &ProcessName,
+    cancellation: &Cancellation,
+    mut on_restart: impl FnMut(std::result::Result<&WatchRestart, (&str, &Error)>),
+) -> Result<()> {
+    let mut watcher: Option<PathWatcher> = None;
+    loop {
+        let process = match process_manager.get_process_status(name).await {
+            Ok(process) => process,
+            Err(Error::ProcessNotFound(_)) => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        let Some(config) = process.watch.clone() else { return Ok(()) };
+        // The configuration comes with the record, so a restart with changed paths is followed
+        if watcher.as_ref().is_none_or(|watcher| *watcher.config() != config) {
+            watcher = Some(PathWatcher::new(config)?);
+        }
+        let Some(watcher) = watcher.as_mut() else { continue };
+
+        let first = tokio::select! {
+            _ = cancellation.cancelled() => return Ok(()),
+            first = watcher.next_change() => match first {
+                Some(path) => Some(path),
+                None => return Err(Error::Other("File notifications stopped".to_string())),
+            },
+            _ = tokio::time::sleep(WATCH_RECHECK_INTERVAL) => None,
+        };
+        let mut changed = watcher.watch_new_paths()?;
+        changed.extend(first);
+        let Some(first) = changed.first() else { continue };
+        let path = first.to_string_lossy().to_string();
+
+        // Collect the rest of a burst, e.g. a save touching several files, into one restart
+        tokio::select! {
+            _ = cancellation.cancelled() => return Ok(()),
+            _ = tokio::time::sleep(watcher.config().debounce()) => {}
+        }
+        changed.extend(watcher.changes());
+        changed.sort();
+        changed.dedup();
+        let changes = changed.len();
+
+        match process_manager.get_process_status(name).await {
+            Ok(process) if restarts_on_change(&process) => {}
+            Ok(_) => continue,
+            Err(Error::ProcessNotFound(_)) => return Ok(()),
+            Err(e) => return Err(e),
+        }
+        tracing::info!(process = %name, path = %path, changes, "watched files changed, restarting");
+        match process_manager.restart_for_change(name, &path, changes).await {
+            // Stopped on purpose since the status was read
+            Ok(outcome) if outcome.skipped.is_some() => continue,
+            Ok(outcome) => on_restart(Ok(&WatchRestart { path, changes, outcome })),
+            Err(e) => on_restart(Err((&path, &e))),
+        }
+    }
+}
+
+/// Keep a watcher running for every running process with a watch configuration, until
+/// `cancellation` fires. Used by the API server.
+pub async fn supervise(process_manager: Arc<ProcessManager>, cancellation: Cancellation) {
+    let mut watchers: HashMap<ProcessName, tokio::task::JoinHandle<()>> = HashMap::new();
+    loop {
+        watchers.retain(|_, watcher| !watcher.is_finished());
+        match process_manager.watched_processes().await {
+            Ok(processes) => {
+                for process in processes {
+                    if watchers.contains_key(&process.name) {
+                        continue;
+                    }
+                    let process_manager = process_manager.clone();
+                    let cancellation = cancellation.clone();
+                    let name = process.name.clone();
+                    let watcher = tokio::spawn(async move {
+                        let logged = |restart: std::result::Result<&WatchRestart, (&str, &Error)>| match restart {
+                            Ok(restart) => tracing::info!(process = %name, path = %restart.path, "restarted after a watched file changed"),
+                            Err((path, e)) => tracing::warn!(process = %name, path, error = %e, "restart after a watched file changed failed"),
+                        };
+                        if let Err(e) = watch_process(&process_manager, &name, &cancellation, logged).await {
+                            tracing::warn!(process = %name, error = %e, "stopped watching files");
+                        }
+                    });
+                    watchers.insert(process.name, watcher);
+                }
+            }
+            Err(e) => tracing::warn!(error = %e, "failed to list watched processes"),
+        }
+        tokio::select! {
+            _ = cancellation.cancelled() => break,
+            _ = tokio::time::sleep(SUPERVISE_INTERVAL) => {}
+        }
+    }
+    for (_, watcher) in watchers {
+        let _ = watcher.await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_ignore_patterns() {
+        let config = WatchConfig::new(vec!["/src".to_string()], vec!["target/".to_string(), "docs/*.md".to_string()], None);
+        assert_eq!(config.ignore, vec![".git/", "*.log", "target/", "docs/*.md"]);
+        assert_eq!(config.debounce(), Duration::from_millis(DEFAULT_WATCH_DEBOUNCE_MS));
+
+        assert!(config.is_ignored(Path::new(".git"), true));
+        assert!(config.is_ignored(Path::new("nested/.git"), true));
+        assert!(!config.is_ignored(Path::new(".git"), false));
+        assert!(config.is_ignored(Path::new("logs/app.log"), false));
+        assert!(config.is_ignored(Path::new("target"), true));
+        assert!(config.is_ignored(Path::new("docs/guide.md"), false));
+        assert!(!config.is_ignored(Path::new("README.md"), false));
+        assert!(!config.is_ignored(Path::new("src/main.rs"), false));
+    }
+
+    #[test]
+    fn test_relative_paths_resolve_against_working_dir() {
+        let config = WatchConfig::new(vec!["src".to_string(), "/etc/app.toml".to_string()], Vec::new(), Some(100)).resolved("/work");
+        assert_eq!(config.paths, vec!["/work/src", "/etc/app.toml"]);
+    }
+
+    /// Changes reported within `wait`, after the first one arrives
+    async fn changes_within(watcher: &mut PathWatcher, wait: Duration) -> BTreeSet<PathBuf> {
+        let mut changed = BTreeSet::new();
+        if let Ok(Some(first)) = tokio::time::timeout(wait, watcher.next_change()).await {
+            changed.insert(first);
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            changed.extend(watcher.changes());
+        }
+        changed
+    }
+
+    #[tokio::test]
+    async fn test_changes_are_notified() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::create_dir_all(root.join(".git/objects")).unwrap();
+        std::fs::write(root.join("src/main.rs"), "fn main() {}").unwrap();
+        let config = WatchConfig::new(vec![root.to_string_lossy().to_string()], Vec::new(), None);
+        let mut watcher = PathWatcher::new(config).unwrap();
+        assert!(watcher.changes().is_empty());
+
+        // Ignored paths never count, nor does anything inside an ignored directory
+        std::fs::write(root.join(".git/index"), "x").unwrap();
+        std::fs::write(root.join(".git/objects/ab"), "x").unwrap();
+        std::fs::write(root.join("server.log"), "x").unwrap();
+        assert!(changes_within(&mut watcher, Duration::from_millis(300)).await.is_empty());
+
+        std::fs::write(root.join("src/main.rs"), "fn main() { run() }").unwrap();
+        std::fs::write(root.join("src/lib.rs"), "").unwrap();
+        let changed = changes_within(&mut watcher, Duration::from_secs(5)).await;
+        assert_eq!(changed, BTreeSet::from([root.join("src/lib.rs"), root.join("src/main.rs")]));
+
+        std::fs::remove_file(root.join("src/lib.rs")).unwrap();
+        assert_eq!(changes_within(&mut watcher, Duration::from_secs(5)).await, BTreeSet::from([root.join("src/lib.rs")]));
+        assert!(watcher.changes().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_watched_file_replaced_and_path_created_later() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let settings = root.join("app.toml");
+        let later = root.join("later");
+        std::fs::write(&settings, "a = 1").unwrap();
+        std::fs::write(root.join("other.toml"), "").unwrap();
+        let paths = vec![settings.to_string_lossy().to_string(), later.to_string_lossy().to_string()];
+        let mut watcher = PathWatcher::new(WatchConfig::new(paths, Vec::new(), None)).unwrap();
+        assert!(watcher.watch_new_paths().unwrap().is_empty());
+
+        // Files next to a watched file are not watched
+        std::fs::write(root.join("other.toml"), "b = 2").unwrap();
+        assert!(changes_within(&mut watcher, Duration::from_millis(300)).await.is_empty());
+
+        // Saved the way editors do: written aside, then moved over the watched file
+        std::fs::write(root.join("app.toml.tmp"), "a = 2").unwrap();
+        std::fs::rename(root.join("app.toml.tmp"), &settings).unwrap();
+        assert!(changes_within(&mut watcher, Duration::from_secs(5)).await.contains(&settings));
+        std::fs::write(&settings, "a = 3").unwrap();
+        assert!(changes_within(&mut watcher, Duration::from_secs(5)).await.contains(&settings));
+
+        // A path that did not exist is watched, and counts as changed, once it does
+        std::fs::create_dir(&later).unwrap();
+        assert_eq!(watcher.watch_new_paths().unwrap(), vec![later.clone()]);
+        std::fs::write(later.join("file"), "").unwrap();
+        assert!(changes_within(&mut watcher, Duration::from_secs(5)).await.contains(&later.join("file")));
+    }
+}
@@ -0,0 +1,160 @@
+//! Flapping detection: a process that keeps failing needs a person to look at it, however
+//! often it gets started again.
+//!
+//! Every failed exit (a non-zero exit code) is added to the process's [`FailureHistory`],
+//! which is stored by process name so it survives restarts. A process is flapping while at
+//! least [`FlappingConfig::failures`] of its failures happened within the last
+//! [`FlappingConfig::window_secs`]; it stops flapping once enough of them have aged out.
+
+use crate::config::FlappingConfig;
+use crate::database::ProcessStatus;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+/// A change of a process's flapping state
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlappingTransition {
+    Entered,
+    Cleared,
+}
+
+/// Whether an exit with this code counts as a failure. Exits by signal do not, as pmr's
+/// own stop signals end processes that way.
+pub fn is_failure(exit_code: Option<i32>) -> bool {
+    matches!(exit_code, Some(code) if code != 0)
+}
+
+/// Recent failures of one process and whether it is flapping
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "http-api", derive(utoipa::ToSchema))]
+pub struct FailureHistory {
+    /// Times of the most recent failures, oldest first; no more than the configured
+    /// threshold are kept, as older ones can never matter
+    pub failures: Vec<DateTime<Utc>>,
+    /// When the process started flapping; None while it is not
+    pub flapping_since: Option<DateTime<Utc>>,
+}
+
+impl FailureHistory {
+    pub fn is_flapping(&self) -> bool {
+        self.flapping_since.is_some()
+    }
+
+    /// Failures within the window ending at `now`
+    pub fn recent_failures(&self, config: &FlappingConfig, now: DateTime<Utc>) -> usize {
+        let window = Duration::seconds(config.window_secs as i64);
+        self.failures.iter().filter(|at| now - **at < window).count()
+    }
+
+    /// Add a failure at `at`, reporting [`FlappingTransition::Entered`] when it is the one
+    /// that reaches the threshold
+    pub fn record_failure(&mut self, config: &FlappingConfig, at: DateTime<Utc>) -> Option<FlappingTransition> {
+        if !config.enabled() {
+            return self.refresh(config, at);
+        }
+        self.failures.push(at);
+        let excess = self.failures.len().saturating_sub(config.failures);
+        self.failures.drain(..excess);
+        self.refresh(config, at)
+    }
+
+    /// Re-evaluate the flapping state at `now`, as failures age out of the window
+    pub fn refresh(&mut self, config: &FlappingConfig, now: DateTime<Utc>) -> Option<FlappingTransition> {
+        let flapping = config.enabled() && self.recent_failures(config, now) >= config.failures;
+        match (self.flapping_since, flapping) {
+            (None, true) => {
+                self.flapping_since = Some(now);
+                Some(FlappingTransition::Entered)
+            }
+            (Some(_), false) => {
+                self.flapping_since = None;
+                Some(FlappingTransition::Cleared)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// A process that is flapping now, as listed by `pmr doctor`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FlappingProcess {
+    pub name: String,
+    pub status: ProcessStatus,
+    /// Failures within the last `window_secs`
+    pub recent_failures: usize,
+    pub window_secs: u64,
+    pub flapping_since: DateTime<Utc>,
+    pub last_failure: DateTime<Utc>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn config(failures: usize, window_secs: u64) -> FlappingConfig {
+        FlappingConfig { failures, window_secs }
+    }
+
+    fn at(secs: i64) -> DateTime<Utc> {
+        Utc.timestamp_opt(1_700_000_000 + secs, 0).unwrap()
+    }
+
+    #[test]
+    fn test_enters_on_the_threshold_failure() {
+        let config = config(3, 60);
+        let mut history = FailureHistory::default();
+        assert_eq!(history.record_failure(&config, at(0)), None);
+        assert_eq!(history.record_failure(&config, at(10)), None);
+        assert_eq!(history.record_failure(&config, at(59)), Some(FlappingTransition::Entered));
+        assert_eq!(history.flapping_since, Some(at(59)));
+
+        // Further failures keep it flapping without announcing it again
+        assert_eq!(history.record_failure(&config, at(65)), None);
+        assert_eq!(history.failures, vec![at(10), at(59), at(65)]);
+    }
+
+    #[test]
+    fn test_failures_spread_wider_than_the_window_do_not_flap() {
+        let config = config(3, 60);
+        let mut history = FailureHistory::default();
+        history.record_failure(&config, at(0));
+        history.record_failure(&config, at(30));
+        // The first failure is exactly one window old, so it no longer counts
+        assert_eq!(history.record_failure(&config, at(60)), None);
+        assert_eq!(history.recent_failures(&config, at(60)), 2);
+        assert!(!history.is_flapping());
+    }
+
+    #[test]
+    fn test_clears_when_a_failure_ages_out() {
+        let config = config(2, 60);
+        let mut history = FailureHistory::default();
+        history.record_failure(&config, at(0));
+        assert_eq!(history.record_failure(&config, at(20)), Some(FlappingTransition::Entered));
+
+        assert_eq!(history.refresh(&config, at(59)), None);
+        assert_eq!(history.refresh(&config, at(60)), Some(FlappingTransition::Cleared));
+        assert_eq!(history.refresh(&config, at(61)), None);
+
+        // One more failure within the window of the last one flaps again
+        assert_eq!(history.record_failure(&config, at(79)), Some(FlappingTransition::Entered));
+    }
+
+    #[test]
+    fn test_disabled_detection_never_flaps_and_clears() {
+        let mut history = FailureHistory { failures: vec![at(0), at(1)], flapping_since: Some(at(1)) };
+        let disabled = config(0, 60);
+        assert_eq!(history.record_failure(&disabled, at(2)), Some(FlappingTransition::Cleared));
+        assert_eq!(history.record_failure(&disabled, at(3)), None);
+        assert_eq!(history.failures, vec![at(0), at(1)]);
+    }
+
+    #[test]
+    fn test_signal_exits_are_not_failures() {
+        assert!(is_failure(Some(1)));
+        assert!(is_failure(Some(-1)));
+        assert!(!is_failure(Some(0)));
+        assert!(!is_failure(None));
+    }
+}
@@ -0,0 +1,305 @@
+//! Sizes and durations for people: `human_bytes` and `human_duration` for text output, and
+//! [`HumanBytes`] and [`HumanDuration`] for reading them back from the command line and the
+//! config file. JSON output keeps the raw numbers.
+//!
+//! Sizes are shown in binary units (KiB, MiB, ...). On input both kinds are accepted:
+//! `KB`, `MB`, `GB`, ... are powers of 1000 and `K`, `KiB`, `M`, `MiB`, ... are powers of
+//! 1024, so `50MB` is 50,000,000 bytes and `50M` and `50MiB` are 52,428,800. Suffixes are
+//! case-insensitive and a plain integer is a number of bytes, as the flags took before.
+
+pub use crate::duration::{format_duration, HumanDuration};
+
+use crate::{Error, Result};
+use serde::{Deserialize, Deserializer};
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+const KIB: u64 = 1024;
+
+/// Binary units shown by [`human_bytes`], each 1024 times the previous one
+const BINARY_UNITS: [&str; 6] = ["KiB", "MiB", "GiB", "TiB", "PiB", "EiB"];
+
+/// Suffixes accepted by [`HumanBytes`] (lowercase) and how many bytes one of each is
+const SUFFIXES: [(&str, u64); 20] = [
+    ("b", 1),
+    ("k", KIB),
+    ("kib", KIB),
+    ("kb", 1000),
+    ("m", KIB.pow(2)),
+    ("mib", KIB.pow(2)),
+    ("mb", 1000u64.pow(2)),
+    ("g", KIB.pow(3)),
+    ("gib", KIB.pow(3)),
+    ("gb", 1000u64.pow(3)),
+    ("t", KIB.pow(4)),
+    ("tib", KIB.pow(4)),
+    ("tb", 1000u64.pow(4)),
+    ("p", KIB.pow(5)),
+    ("pib", KIB.pow(5)),
+    ("pb", 1000u64.pow(5)),
+    ("e", KIB.pow(6)),
+    ("eib", KIB.pow(6)),
+    ("eb", 1000u64.pow(6)),
+    ("", 1),
+];
+
+/// `bytes` in the largest binary unit that keeps it at least 1, with one decimal:
+/// `512 B`, `1.0 KiB`, `1.4 MiB`
+pub fn human_bytes(bytes: u64) -> String {
+    if bytes < KIB {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64 / KIB as f64;
+    let mut unit = 0;
+    // Move up a unit when rounding to one decimal would show 1024.0
+    while value >= 1023.95 && unit + 1 < BINARY_UNITS.len() {
+        value /= KIB as f64;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, BINARY_UNITS[unit])
+}
+
+/// Largest two units of `duration` like [`format_duration`], e.g. `2h 13m`; under a second
+/// it is shown in milliseconds
+pub fn human_duration(duration: Duration) -> String {
+    if duration < Duration::from_secs(1) {
+        return format!("{}ms", duration.as_millis());
+    }
+    format_duration(chrono::Duration::from_std(duration).unwrap_or(chrono::Duration::MAX))
+}
+
+/// A byte count written as `1048576`, `512K`, `50MB`, `1.5GiB`, ...; see the module docs
+/// for the suffixes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct HumanBytes(u64);
+
+impl HumanBytes {
+    pub fn as_u64(&self) -> u64 {
+        self.0
+    }
+}
+
+impl From<u64> for HumanBytes {
+    fn from(bytes: u64) -> Self {
+        Self(bytes)
+    }
+}
+
+impl FromStr for HumanBytes {
+    type Err = Error;
+
+    fn from_str(input: &str) -> Result<Self> {
+        let invalid = |reason: &str| Error::InvalidSize(format!("'{}': {}", input, reason));
+        let trimmed = input.trim();
+        if trimmed.starts_with('-') {
+            return Err(invalid("must not be negative"));
+        }
+
+        let split = trimmed.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(trimmed.len());
+        let (amount, unit) = trimmed.split_at(split);
+        if amount.is_empty() || amount.starts_with('.') || amount.ends_with('.') || amount.matches('.').count() > 1 {
+            return Err(invalid("expected a number optionally followed by a unit (e.g. 1048576, 512K, 50MB, 1.5GiB)"));
+        }
+        let unit = unit.trim_start().to_ascii_lowercase();
+        let multiplier = SUFFIXES
+            .iter()
+            .find(|(suffix, _)| *suffix == unit)
+            .map(|(_, bytes)| *bytes)
+            .ok_or_else(|| invalid(&format!("unknown unit '{}'; use B, K/KiB, KB, M/MiB, MB, G/GiB, GB, T/TiB, TB, P/PiB, PB, E/EiB or EB", unit)))?;
+
+        let too_large = || invalid("larger than the maximum of 16 EiB");
+        let (whole, fraction) = amount.split_once('.').unwrap_or((amount, ""));
+        if !fraction.is_empty() && multiplier == 1 {
+            return Err(invalid("a number of bytes must be whole"));
+        }
+        let whole = whole.parse::<u64>().map_err(|_| too_large())?;
+        let mut bytes = whole.checked_mul(multiplier).ok_or_else(too_large)?;
+        if !fraction.is_empty() {
+            // Digits past the precision of the unit cannot add a whole byte
+            let digits = &fraction[..fraction.len().min(18)];
+            let scale = 10u128.pow(digits.len() as u32);
+            let extra = digits.parse::<u128>().map_err(|_| too_large())? * multiplier as u128 / scale;
+            bytes = bytes.checked_add(extra as u64).ok_or_else(too_large)?;
+        }
+        Ok(Self(bytes))
+    }
+}
+
+impl fmt::Display for HumanBytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", human_bytes(self.0))
+    }
+}
+
+/// Read a byte count from the config file as either an integer or a string like `"50MB"`
+pub fn deserialize_bytes<'de, D: Deserializer<'de>>(deserializer: D) -> std::result::Result<u64, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Bytes {
+        Count(u64),
+        Text(String),
+    }
+
+    match Bytes::deserialize(deserializer)? {
+        Bytes::Count(bytes) => Ok(bytes),
+        Bytes::Text(text) => text.parse::<HumanBytes>().map(|bytes| bytes.as_u64()).map_err(serde::de::Error::custom),
+    }
+}
+
+/// [`deserialize_bytes`] for optional settings
+pub fn deserialize_optional_bytes<'de, D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Option<u64>, D::Error> {
+    deserialize_bytes(deserializer).map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bytes(input: &str) -> u64 {
+        input.parse::<HumanBytes>().unwrap_or_else(|e| panic!("{}: {}", input, e)).as_u64()
+    }
+
+    #[test]
+    fn test_human_bytes_below_one_kib() {
+        assert_eq!(human_bytes(0), "0 B");
+        assert_eq!(human_bytes(1), "1 B");
+        assert_eq!(human_bytes(1023), "1023 B");
+    }
+
+    #[test]
+    fn test_human_bytes_units() {
+        assert_eq!(human_bytes(1024), "1.0 KiB");
+        assert_eq!(human_bytes(1536), "1.5 KiB");
+        assert_eq!(human_bytes(1_468_006), "1.4 MiB");
+        assert_eq!(human_bytes(10 * 1024 * 1024), "10.0 MiB");
+        assert_eq!(human_bytes(1 << 30), "1.0 GiB");
+        assert_eq!(human_bytes(1 << 40), "1.0 TiB");
+        assert_eq!(human_bytes(1 << 50), "1.0 PiB");
+        assert_eq!(human_bytes(1 << 60), "1.0 EiB");
+        assert_eq!(human_bytes(u64::MAX), "16.0 EiB");
+    }
+
+    #[test]
+    fn test_human_bytes_boundaries_never_show_1024() {
+        assert_eq!(human_bytes(1024 * 1024 - 1), "1.0 MiB");
+        assert_eq!(human_bytes(1023 * 1024), "1023.0 KiB");
+        assert_eq!(human_bytes((1 << 30) - 1), "1.0 GiB");
+        for shift in [20, 30, 40, 50, 60] {
+            assert!(!human_bytes((1u64 << shift) - 1).starts_with("1024"), "{}", shift);
+        }
+    }
+
+    #[test]
+    fn test_parse_plain_integers_are_bytes() {
+        assert_eq!(bytes("0"), 0);
+        assert_eq!(bytes("1048576"), 1_048_576);
+        assert_eq!(bytes(" 42 "), 42);
+        assert_eq!(bytes("18446744073709551615"), u64::MAX);
+    }
+
+    #[test]
+    fn test_parse_binary_and_si_suffixes() {
+        assert_eq!(bytes("10B"), 10);
+        assert_eq!(bytes("512K"), 512 * 1024);
+        assert_eq!(bytes("512KiB"), 512 * 1024);
+        assert_eq!(bytes("512KB"), 512_000);
+        assert_eq!(bytes("50M"), 50 * 1024 * 1024);
+        assert_eq!(bytes("50MiB"), 50 * 1024 * 1024);
+        assert_eq!(bytes("50MB"), 50_000_000);
+        assert_eq!(bytes("2G"), 2 << 30);
+        assert_eq!(bytes("2GB"), 2_000_000_000);
+        assert_eq!(bytes("1T"), 1 << 40);
+        assert_eq!(bytes("1TB"), 1_000_000_000_000);
+        assert_eq!(bytes("1PiB"), 1 << 50);
+        assert_eq!(bytes("1PB"), 1_000_000_000_000_000);
+        assert_eq!(bytes("1EiB"), 1 << 60);
+        assert_eq!(bytes("1EB"), 1_000_000_000_000_000_000);
+    }
+
+    #[test]
+    fn test_parse_is_case_insensitive_and_allows_a_space() {
+        assert_eq!(bytes("50mb"), 50_000_000);
+        assert_eq!(bytes("50 MiB"), 50 * 1024 * 1024);
+        assert_eq!(bytes("1 gib"), 1 << 30);
+        assert_eq!(bytes("3k"), 3072);
+    }
+
+    #[test]
+    fn test_parse_decimals_are_rounded_down_to_whole_bytes() {
+        assert_eq!(bytes("1.5K"), 1536);
+        assert_eq!(bytes("1.5GiB"), 3 << 29);
+        assert_eq!(bytes("0.5MB"), 500_000);
+        assert_eq!(bytes("1.0001K"), 1024);
+        assert_eq!(bytes("0.000000000000000000001E"), 0);
+        assert_eq!(bytes("15.5EiB"), 31 << 59);
+    }
+
+    #[test]
+    fn test_parse_rejects_bad_values() {
+        for input in ["", "-1", "-1K", "K", "1X", "1KX", ".5K", "1.K", "1.2.3M", "1.5", "1.5B", "16EiB", "18446744073709551616", "99999999999999999999K", "19EB"] {
+            let err = input.parse::<HumanBytes>().unwrap_err();
+            assert!(matches!(err, Error::InvalidSize(_)), "{} gave {}", input, err);
+        }
+    }
+
+    #[test]
+    fn test_parse_largest_values() {
+        assert_eq!(bytes("15EiB"), 15 << 60);
+        assert_eq!(bytes("18EB"), 18_000_000_000_000_000_000);
+        assert_eq!(bytes("16383PiB"), 16383 << 50);
+    }
+
+    #[test]
+    fn test_display_round_trips_within_display_precision() {
+        for value in [0u64, 1, 1023, 1024, 1536, 1 << 20, 10 * 1024 * 1024, 3 << 29, 1 << 40, 5 << 50, 1 << 60] {
+            let shown = HumanBytes::from(value).to_string();
+            let parsed = bytes(&shown);
+            // One decimal of the shown unit is the most that can be lost
+            let unit = if value < 1024 { 1 } else { 1u64 << (10 * value.ilog(1024)) };
+            assert!(parsed.abs_diff(value) <= unit / 10, "{} -> {} -> {}", value, shown, parsed);
+        }
+        for value in [0u64, 7, 1 << 10, 1 << 20, 3 << 30, 1 << 60] {
+            assert_eq!(bytes(&HumanBytes::from(value).to_string()), value);
+        }
+    }
+
+    #[test]
+    fn test_deserialize_integer_or_string() {
+        #[derive(Deserialize)]
+        struct Settings {
+            #[serde(deserialize_with = "deserialize_bytes")]
+            size: u64,
+        }
+        let size = |toml_text: &str| toml::from_str::<Settings>(toml_text).map(|settings| settings.size);
+        assert_eq!(size("size = 1048576").unwrap(), 1_048_576);
+        assert_eq!(size("size = \"50MB\"").unwrap(), 50_000_000);
+        assert_eq!(size("size = \"10MiB\"").unwrap(), 10 << 20);
+        assert!(size("size = \"ten\"").unwrap_err().to_string().contains("Invalid size"));
+        assert!(size("size = -1").is_err());
+    }
+
+    #[test]
+    fn test_human_duration() {
+        assert_eq!(human_duration(Duration::ZERO), "0ms");
+        assert_eq!(human_duration(Duration::from_millis(250)), "250ms");
+        assert_eq!(human_duration(Duration::from_millis(999)), "999ms");
+        assert_eq!(human_duration(Duration::from_secs(1)), "1s");
+        assert_eq!(human_duration(Duration::from_millis(1999)), "1s");
+        assert_eq!(human_duration(Duration::from_secs(59)), "59s");
+        assert_eq!(human_duration(Duration::from_secs(60)), "1m");
+        assert_eq!(human_duration(Duration::from_secs(2 * 3600 + 13 * 60 + 5)), "2h 13m");
+        assert_eq!(human_duration(Duration::from_secs(86400)), "1d");
+        assert_eq!(human_duration(Duration::from_secs(6 * 86400 + 23 * 3600 + 59 * 60)), "6d 23h");
+        assert_eq!(human_duration(Duration::from_secs(400 * 86400)), "400d");
+        assert_eq!(human_duration(Duration::MAX), format_duration(chrono::Duration::MAX));
+    }
+
+    #[test]
+    fn test_human_duration_round_trips_through_human_duration_parsing() {
+        for secs in [1i64, 59, 60, 3600, 86400, 7 * 86400] {
+            let shown = human_duration(Duration::from_secs(secs as u64));
+            assert_eq!(shown.parse::<HumanDuration>().unwrap().as_duration().num_seconds(), secs, "{}", shown);
+        }
+    }
+}
@@ -1,18 +1,65 @@
 use crate::{
     cli::OutputFormat,
-    database::ProcessRecord,
-    process::ClearResult,
+    config::{ConfigIssue, ConfigPaths},
+    database::{LogDirMode, LogRotationEntry, ProcessCounts, ProcessKind, ProcessNote, ProcessRecord, ProcessRun, ProcessStatus, SalvageReport, SchemaInfo, WatchRestartEntry},
+    drift::DriftReport,
+    duration::format_duration,
+    format::{human_bytes, human_duration},
+    file_watch::WatchRestart,
+    flapping::FlappingProcess,
+    inspect::InspectReport,
+    logs_quota::{LogsQuotaStats, QuotaEnforcement},
+    outcome::{DeleteOutcome, NameCheck, RestartOutcome, StartOutcome, StopOutcome, UndeleteOutcome},
+    permissions::LoosePermissions,
+    pid_probe::ZombieProcess,
+    porcelain,
+    process::{ClearResult, ManagerStats, ProcessLogs, RotatedLog, StartPhaseReport},
+    profiles::{short_id, ProfileListing},
+    snapshot::StartSnapshot,
+    storage::HostListing,
+    text_width::{fit, pad},
 };
 use serde::{Deserialize, Serialize};
+use unicode_segmentation::UnicodeSegmentation;
+
+pub use crate::messages::{delete_message, name_check_message, restart_message, start_message, stop_message, undelete_message, watch_restart_message};
 
 /// Formatter for different output formats
 pub struct Formatter {
     format: OutputFormat,
+    quiet: bool,
+    no_header: bool,
+    color: bool,
+    wide: bool,
 }
 
 impl Formatter {
     pub fn new(format: OutputFormat) -> Self {
-        Self { format }
+        Self { format, quiet: false, no_header: false, color: false, wide: false }
+    }
+
+    /// Use terminal styling in text output, e.g. dimmed captured output (off by default)
+    pub fn with_color(mut self, color: bool) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Print only process names in text lists (ignored for JSON and porcelain)
+    pub fn with_quiet(mut self, quiet: bool) -> Self {
+        self.quiet = quiet;
+        self
+    }
+
+    /// Leave out header, separator and summary rows of text tables (ignored for JSON and porcelain)
+    pub fn with_no_header(mut self, no_header: bool) -> Self {
+        self.no_header = no_header;
+        self
+    }
+
+    /// Add a DESCRIPTION column to the text process list (ignored for JSON and porcelain)
+    pub fn with_wide(mut self, wide: bool) -> Self {
+        self.wide = wide;
+        self
     }
 
     /// Format process list output
@@ -20,6 +67,90 @@ impl Formatter {
         match self.format {
             OutputFormat::Text => self.format_process_list_text(processes),
             OutputFormat::Json => self.format_process_list_json(processes),
+            OutputFormat::Porcelain => porcelain::format_list(processes),
+        }
+    }
+
+    /// Format process names alone, one per line; JSON is an array of them
+    pub fn format_process_names(&self, names: &[String]) -> String {
+        match self.format {
+            OutputFormat::Text | OutputFormat::Porcelain => names.join("\n"),
+            OutputFormat::Json => serde_json::to_string_pretty(names).unwrap_or_else(|_| "[]".to_string()),
+        }
+    }
+
+    /// Whether the process list is one self-contained line per process (porcelain, and
+    /// quiet text), so each line can be printed as soon as its record is read
+    pub fn lists_line_by_line(&self) -> bool {
+        match self.format {
+            OutputFormat::Porcelain => true,
+            OutputFormat::Text => self.quiet,
+            OutputFormat::Json => false,
+        }
+    }
+
+    /// The line of one process in a list printed line by line, see [`Self::lists_line_by_line`]
+    pub fn format_process_list_line(&self, process: &ProcessRecord) -> String {
+        match self.format {
+            OutputFormat::Porcelain => porcelain::format_list_line(process),
+            OutputFormat::Text | OutputFormat::Json => process.name.to_string(),
+        }
+    }
+
+    /// Format jobs with their last run: when it started, how long it took and its result.
+    /// Porcelain output has no job format and prints the text table.
+    pub fn format_job_list(&self, jobs: &[ProcessRecord], now: chrono::DateTime<chrono::Utc>) -> String {
+        match self.format {
+            OutputFormat::Text | OutputFormat::Porcelain => self.format_job_list_text(jobs, now),
+            OutputFormat::Json => {
+                let output = JobListOutput { jobs: jobs.iter().map(|job| JobRun::new(job, now)).collect() };
+                serde_json::to_string_pretty(&output).unwrap_or_else(|_| "{}".to_string())
+            }
+        }
+    }
+
+    /// Format only the per-status summary of the process list
+    pub fn format_process_counts(&self, counts: &ProcessCounts) -> String {
+        match self.format {
+            OutputFormat::Text | OutputFormat::Porcelain => counts.to_string(),
+            OutputFormat::Json => {
+                let output = ProcessCountsOutput { summary: counts.clone() };
+                serde_json::to_string_pretty(&output).unwrap_or_else(|_| "{}".to_string())
+            }
+        }
+    }
+
+    /// The `pmr stats --oneline` summary: `pmr: ` and the running, stopped and failed
+    /// counts, each followed by its symbol and separated by spaces. This layout is stable,
+    /// like `--format porcelain`, whatever the output format.
+    pub fn format_stats_oneline(counts: &ProcessCounts, symbols: OnelineSymbols) -> String {
+        let [running, stopped, failed] = match symbols {
+            OnelineSymbols::Unicode => ["▲", "■", "✖"],
+            OnelineSymbols::Ascii => ["R", "S", "F"],
+        };
+        format!("pmr: {}{} {}{} {}{}", counts.running, running, counts.stopped, stopped, counts.failed, failed)
+    }
+
+    /// Format managed process count against the configured limit
+    pub fn format_stats(&self, stats: &ManagerStats) -> String {
+        match self.format {
+            OutputFormat::Text | OutputFormat::Porcelain => {
+                let limit = if stats.max_processes == 0 {
+                    "no limit".to_string()
+                } else {
+                    format!("max {}", stats.max_processes)
+                };
+                format!(
+                    "Processes: {} ({})\n{}{}\n{}\n{}",
+                    stats.managed,
+                    limit,
+                    stats.counts,
+                    status_ages_text(stats),
+                    config_paths_text(&stats.paths),
+                    logs_quota_text(&stats.logs)
+                )
+            }
+            OutputFormat::Json => serde_json::to_string_pretty(stats).unwrap_or_else(|_| "{}".to_string()),
         }
     }
 
@@ -28,17 +159,20 @@ impl Formatter {
         match self.format {
             OutputFormat::Text => self.format_process_status_text(process),
             OutputFormat::Json => self.format_process_status_json(process),
+            OutputFormat::Porcelain => porcelain::format_status(process),
         }
     }
 
     /// Format process logs output
-    pub fn format_process_logs(&self, logs: &str, process_name: &str) -> String {
+    pub fn format_process_logs(&self, logs: &ProcessLogs, process_name: &str) -> String {
         match self.format {
-            OutputFormat::Text => logs.to_string(),
+            OutputFormat::Text | OutputFormat::Porcelain => logs.content.clone(),
             OutputFormat::Json => {
                 let log_output = LogOutput {
                     process_name: process_name.to_string(),
-                    logs: logs.to_string(),
+                    logs: logs.content.clone(),
+                    contains_invalid_utf8: logs.contains_invalid_utf8,
+                    start_offset: logs.start_offset,
                 };
                 serde_json::to_string_pretty(&log_output).unwrap_or_else(|_| "{}".to_string())
             }
@@ -46,13 +180,14 @@ impl Formatter {
     }
 
     /// Format rotated logs list output
-    pub fn format_rotated_logs(&self, logs: &[String], process_name: &str) -> String {
+    /// Format the list of rotated log files of a process; the text form has one line per file
+    pub fn format_rotated_logs(&self, logs: &[RotatedLog], process_name: &str) -> String {
         match self.format {
-            OutputFormat::Text => {
+            OutputFormat::Text | OutputFormat::Porcelain => {
                 if logs.is_empty() {
                     format!("No rotated log files found for process '{}'", process_name)
                 } else {
-                    logs.join("\n")
+                    logs.iter().map(rotated_log_header).collect::<Vec<_>>().join("\n")
                 }
             }
             OutputFormat::Json => {
@@ -65,10 +200,377 @@ impl Formatter {
         }
     }
 
+    /// Format the rotation history of a process, oldest first
+    pub fn format_rotation_history(&self, entries: &[LogRotationEntry], process_name: &str) -> String {
+        match self.format {
+            OutputFormat::Text | OutputFormat::Porcelain => {
+                if entries.is_empty() {
+                    return format!("No log rotations recorded for process '{}'", process_name);
+                }
+                let mut output = format!("{:<24} {:<8} {:>12} {:>8}  {}\n", "ROTATED AT", "TRIGGER", "SIZE", "SHIFTED", "DELETED");
+                output.push_str(&"-".repeat(70));
+                for entry in entries {
+                    let deleted = if entry.archives_deleted.is_empty() {
+                        "-".to_string()
+                    } else {
+                        entry.archives_deleted.join(", ")
+                    };
+                    output.push_str(&format!(
+                        "\n{:<24} {:<8} {:>12} {:>8}  {}",
+                        entry.rotated_at.format("%Y-%m-%d %H:%M:%S UTC"),
+                        entry.trigger,
+                        entry.rotated_from_size,
+                        entry.archives_shifted,
+                        deleted
+                    ));
+                }
+                output
+            }
+            OutputFormat::Json => {
+                let output = RotationHistoryOutput {
+                    process_name: process_name.to_string(),
+                    rotations: entries.to_vec(),
+                };
+                serde_json::to_string_pretty(&output).unwrap_or_else(|_| "{}".to_string())
+            }
+        }
+    }
+
+    /// Format the restarts a process had because watched files changed, oldest first
+    pub fn format_watch_history(&self, entries: &[WatchRestartEntry], process_name: &str) -> String {
+        match self.format {
+            OutputFormat::Text | OutputFormat::Porcelain => {
+                if entries.is_empty() {
+                    return format!("No watch restarts recorded for process '{}'", process_name);
+                }
+                let mut output = format!("{:<24} {:>8}  {}\n", "RESTARTED AT", "CHANGES", "PATH");
+                output.push_str(&"-".repeat(70));
+                for entry in entries {
+                    output.push_str(&format!(
+                        "\n{:<24} {:>8}  {}",
+                        entry.restarted_at.format("%Y-%m-%d %H:%M:%S UTC"),
+                        entry.changes,
+                        entry.path
+                    ));
+                }
+                output
+            }
+            OutputFormat::Json => {
+                let output = WatchHistoryOutput {
+                    process_name: process_name.to_string(),
+                    restarts: entries.to_vec(),
+                };
+                serde_json::to_string_pretty(&output).unwrap_or_else(|_| "{}".to_string())
+            }
+        }
+    }
+
+    /// Format the processes of every profile, each line naming its profile, followed by
+    /// the database each profile was read from. Porcelain output prints the text table.
+    pub fn format_profile_listings(&self, listings: &[ProfileListing]) -> String {
+        if let OutputFormat::Json = self.format {
+            let output = ProfileListingsOutput { profiles: listings.to_vec() };
+            return serde_json::to_string_pretty(&output).unwrap_or_else(|_| "{}".to_string());
+        }
+        let rows = listings.iter().flat_map(|listing| listing.processes.iter().map(move |process| (listing, process)));
+        if self.quiet {
+            return rows.map(|(listing, process)| format!("{}/{}", listing.profile, process.name)).collect::<Vec<_>>().join("\n");
+        }
+        if listings.is_empty() {
+            return "No profiles found.".to_string();
+        }
+        let mut lines = Vec::new();
+        if !self.no_header {
+            lines.push(format!("{:<16} {:<20} {:<10} {:<10} {:<30}", "PROFILE", "NAME", "STATUS", "PID", "COMMAND"));
+            lines.push("-".repeat(90));
+        }
+        for (listing, process) in rows {
+            let pid = process.pid.map(|pid| pid.to_string()).unwrap_or_else(|| "-".to_string());
+            lines.push(format!(
+                "{} {} {:<10} {:<10} {}",
+                pad(&listing.profile, 16),
+                pad(process.name.as_str(), 20),
+                process.status,
+                pid,
+                pad(&format!("{} {}", process.command, process.args.join(" ")), 30)
+            ));
+        }
+        lines.push(String::new());
+        for listing in listings {
+            let id = listing.instance_id.as_deref().map(|id| format!(" (id {})", short_id(id))).unwrap_or_default();
+            let current = if listing.current { ", current" } else { "" };
+            lines.push(match &listing.error {
+                Some(error) => format!("{}: {}{} could not be read: {}", listing.profile, listing.database, current, error),
+                None => format!("{}: {}{}, {} process(es){}", listing.profile, listing.database, id, listing.processes.len(), current),
+            });
+        }
+        lines.join("\n")
+    }
+
+    /// Format the processes of every host sharing the storage, grouped by host
+    pub fn format_host_listings(&self, listings: &[HostListing]) -> String {
+        if let OutputFormat::Json = self.format {
+            let output = HostListingsOutput { hosts: listings.to_vec() };
+            return serde_json::to_string_pretty(&output).unwrap_or_else(|_| "{}".to_string());
+        }
+        let rows = listings.iter().flat_map(|listing| listing.processes.iter().map(move |process| (listing, process)));
+        if self.quiet {
+            return rows.map(|(listing, process)| format!("{}/{}", listing.host, process.name)).collect::<Vec<_>>().join("\n");
+        }
+        let mut lines = Vec::new();
+        if !self.no_header {
+            lines.push(format!("{:<16} {:<20} {:<10} {:<10} {:<30}", "HOST", "NAME", "STATUS", "PID", "COMMAND"));
+            lines.push("-".repeat(90));
+        }
+        for (listing, process) in rows {
+            let pid = process.pid.map(|pid| pid.to_string()).unwrap_or_else(|| "-".to_string());
+            lines.push(format!(
+                "{} {} {:<10} {:<10} {}",
+                pad(&listing.host, 16),
+                pad(process.name.as_str(), 20),
+                process.status,
+                pid,
+                pad(&format!("{} {}", process.command, process.args.join(" ")), 30)
+            ));
+        }
+        lines.push(String::new());
+        for listing in listings {
+            let current = if listing.current { ", current" } else { "" };
+            lines.push(format!("{}: {} process(es){}", listing.host, listing.processes.len(), current));
+        }
+        lines.join("\n")
+    }
+
+    /// Format the finished runs of a process, oldest first
+    pub fn format_run_history(&self, runs: &[ProcessRun], process_name: &str) -> String {
+        match self.format {
+            OutputFormat::Text | OutputFormat::Porcelain => {
+                if runs.is_empty() {
+                    return format!("No finished runs recorded for process '{}'", process_name);
+                }
+                let mut output = format!("{:<24} {:<24} {:>10}  {}\n", "STARTED AT", "ENDED AT", "DURATION", "RESULT");
+                output.push_str(&"-".repeat(80));
+                for run in runs {
+                    output.push_str(&format!(
+                        "\n{:<24} {:<24} {:>10}  {}",
+                        run.started_at.format("%Y-%m-%d %H:%M:%S UTC"),
+                        run.ended_at.format("%Y-%m-%d %H:%M:%S UTC"),
+                        human_duration(std::time::Duration::from_millis(run.duration_ms)),
+                        run_result(run)
+                    ));
+                }
+                if let Some(average) = ProcessRun::average_duration_ms(runs) {
+                    output.push_str(&format!("\nAverage: {} over {} run(s)", human_duration(std::time::Duration::from_millis(average)), runs.len()));
+                }
+                output
+            }
+            OutputFormat::Json => {
+                let output = RunHistoryOutput {
+                    process_name: process_name.to_string(),
+                    average_duration_ms: ProcessRun::average_duration_ms(runs),
+                    runs: runs.to_vec(),
+                };
+                serde_json::to_string_pretty(&output).unwrap_or_else(|_| "{}".to_string())
+            }
+        }
+    }
+
+    /// Format the notes on a process, oldest first
+    pub fn format_notes(&self, notes: &[ProcessNote], process_name: &str) -> String {
+        match self.format {
+            OutputFormat::Text | OutputFormat::Porcelain => {
+                if notes.is_empty() {
+                    return format!("No notes on process '{}'", process_name);
+                }
+                let mut output = format!("{:<6} {:<24} {:<16} {}
+", "ID", "CREATED AT", "AUTHOR", "NOTE");
+                output.push_str(&"-".repeat(70));
+                for note in notes {
+                    output.push_str(&format!(
+                        "\n{:<6} {:<24} {} {}",
+                        note.id,
+                        note.created_at.format("%Y-%m-%d %H:%M:%S UTC"),
+                        fit(&note.author, 16),
+                        note.text
+                    ));
+                }
+                output
+            }
+            OutputFormat::Json => {
+                let output = NotesOutput {
+                    process_name: process_name.to_string(),
+                    notes: notes.to_vec(),
+                };
+                serde_json::to_string_pretty(&output).unwrap_or_else(|_| "{}".to_string())
+            }
+        }
+    }
+
+    /// Format the findings of a config validation
+    pub fn format_config_issues(&self, path: &std::path::Path, issues: &[ConfigIssue]) -> String {
+        let errors = issues.iter().filter(|issue| issue.is_error()).count();
+        let warnings = issues.len() - errors;
+        match self.format {
+            OutputFormat::Text | OutputFormat::Porcelain => {
+                let mut output: Vec<String> = issues.iter().map(ConfigIssue::to_string).collect();
+                output.push(if issues.is_empty() {
+                    format!("{}: configuration is valid", path.display())
+                } else {
+                    format!("{}: {} error(s), {} warning(s)", path.display(), errors, warnings)
+                });
+                output.join("\n")
+            }
+            OutputFormat::Json => {
+                let output = ConfigValidationOutput {
+                    config_path: path.display().to_string(),
+                    valid: errors == 0,
+                    errors,
+                    warnings,
+                    issues: issues.to_vec(),
+                };
+                serde_json::to_string_pretty(&output).unwrap_or_else(|_| "{}".to_string())
+            }
+        }
+    }
+
+    /// Format where pmr keeps its files, for `pmr config show`
+    pub fn format_config_paths(&self, paths: &ConfigPaths) -> String {
+        match self.format {
+            OutputFormat::Text | OutputFormat::Porcelain => config_paths_text(paths),
+            OutputFormat::Json => serde_json::to_string_pretty(paths).unwrap_or_else(|_| "{}".to_string()),
+        }
+    }
+
+    /// Format how the database schema compares with this binary (`pmr db info`)
+    pub fn format_schema_info(&self, info: &SchemaInfo) -> String {
+        match self.format {
+            OutputFormat::Text | OutputFormat::Porcelain => schema_info_text(info),
+            OutputFormat::Json => {
+                let mut value = serde_json::to_value(info).unwrap_or_default();
+                value["compatible"] = info.is_compatible().into();
+                value["migrations_pending"] = info.migrations_pending().into();
+                serde_json::to_string_pretty(&value).unwrap_or_else(|_| "{}".to_string())
+            }
+        }
+    }
+
+    /// Format what was recovered from a damaged database; the text form is a banner meant
+    /// to be noticed among a command's normal output
+    pub fn format_salvage_report(&self, report: &SalvageReport) -> String {
+        match self.format {
+            OutputFormat::Text | OutputFormat::Porcelain => salvage_report_text(report),
+            OutputFormat::Json => {
+                let mut value = serde_json::to_value(report).unwrap_or_default();
+                value["recovered"] = report.recovered().into();
+                value["lost"] = report.lost().into();
+                value["unreadable_tables"] = report.has_unreadable_tables().into();
+                serde_json::to_string_pretty(&value).unwrap_or_else(|_| "{}".to_string())
+            }
+        }
+    }
+
+    /// Format drift reports, one block per process
+    pub fn format_drift_reports(&self, reports: &[DriftReport]) -> String {
+        match self.format {
+            OutputFormat::Text | OutputFormat::Porcelain => {
+                if reports.is_empty() {
+                    return "No processes found.".to_string();
+                }
+                reports
+                    .iter()
+                    .map(|report| format!("{}: {}", report.process, drift_summary(report)))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            }
+            OutputFormat::Json => {
+                let output = DriftOutput { reports: reports.to_vec() };
+                serde_json::to_string_pretty(&output).unwrap_or_else(|_| "{}".to_string())
+            }
+        }
+    }
+
+    /// Format an inspect report; it is JSON in every format, as it is meant to be pasted whole
+    pub fn format_inspect_report(&self, report: &InspectReport) -> String {
+        serde_json::to_string_pretty(report).unwrap_or_else(|_| "{}".to_string())
+    }
+
+    /// Format what `pmr doctor` found; `fixed` are the paths `--fix-permissions` restricted
+    pub fn format_doctor_report(
+        &self,
+        flapping: &[FlappingProcess],
+        zombies: &[ZombieProcess],
+        loose: &[LoosePermissions],
+        fixed: &[LoosePermissions],
+    ) -> String {
+        match self.format {
+            OutputFormat::Text | OutputFormat::Porcelain => {
+                if flapping.is_empty() && zombies.is_empty() && loose.is_empty() && fixed.is_empty() {
+                    return "No problems found.".to_string();
+                }
+                let local = |at: &chrono::DateTime<chrono::Utc>| at.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M:%S").to_string();
+                let mut sections = Vec::new();
+                if !flapping.is_empty() {
+                    sections.push(format!("Flapping processes ({}):", flapping.len()));
+                }
+                for process in flapping {
+                    let output = sections.last_mut().unwrap();
+                    output.push_str(&format!(
+                        "\n  {} ({}): {} failures in the last {}, flapping since {}, last failure at {}",
+                        process.name,
+                        process.status,
+                        process.recent_failures,
+                        human_duration(std::time::Duration::from_secs(process.window_secs)),
+                        local(&process.flapping_since),
+                        local(&process.last_failure)
+                    ));
+                }
+                let (reparented, unreaped): (Vec<&ZombieProcess>, Vec<&ZombieProcess>) = zombies.iter().partition(|zombie| zombie.reparented);
+                if !unreaped.is_empty() {
+                    let mut output = format!("Zombie processes whose parent has not reaped them ({}):", unreaped.len());
+                    for zombie in unreaped {
+                        output.push_str(&format!("\n  {} (pid {}): parent {} is alive", zombie.name, zombie.pid, zombie.parent_pid));
+                    }
+                    sections.push(output);
+                }
+                if !reparented.is_empty() {
+                    let mut output = format!("Zombie processes left to init ({}):", reparented.len());
+                    for zombie in reparented {
+                        output.push_str(&format!("\n  {} (pid {})", zombie.name, zombie.pid));
+                    }
+                    sections.push(output);
+                }
+                if !loose.is_empty() {
+                    let mut output = format!("Secrets readable by other users ({}), run 'pmr doctor --fix-permissions':", loose.len());
+                    for path in loose {
+                        output.push_str(&format!("\n  {}: mode {}, expected {}", path.path.display(), path.mode, path.expected_mode));
+                    }
+                    sections.push(output);
+                }
+                if !fixed.is_empty() {
+                    let mut output = format!("Restricted to their owner ({}):", fixed.len());
+                    for path in fixed {
+                        output.push_str(&format!("\n  {}: mode {} -> {}", path.path.display(), path.mode, path.expected_mode));
+                    }
+                    sections.push(output);
+                }
+                sections.join("\n\n")
+            }
+            OutputFormat::Json => {
+                let output = DoctorOutput {
+                    flapping: flapping.to_vec(),
+                    zombies: zombies.to_vec(),
+                    loose_permissions: loose.to_vec(),
+                    fixed_permissions: fixed.to_vec(),
+                };
+                serde_json::to_string_pretty(&output).unwrap_or_else(|_| "{}".to_string())
+            }
+        }
+    }
+
     /// Format clear result output
     pub fn format_clear_result(&self, result: &ClearResult) -> String {
         match self.format {
-            OutputFormat::Text => self.format_clear_result_text(result),
+            OutputFormat::Text | OutputFormat::Porcelain => self.format_clear_result_text(result),
             OutputFormat::Json => self.format_clear_result_json(result),
         }
     }
@@ -76,7 +578,7 @@ impl Formatter {
     /// Format simple success message
     pub fn format_success_message(&self, message: &str) -> String {
         match self.format {
-            OutputFormat::Text => message.to_string(),
+            OutputFormat::Text | OutputFormat::Porcelain => message.to_string(),
             OutputFormat::Json => {
                 let response = SimpleResponse {
                     success: true,
@@ -87,10 +589,77 @@ impl Formatter {
         }
     }
 
+    /// Format a completed start; JSON carries the outcome and the phases of a verbose start,
+    /// text shows only the message since the phases were printed as they finished
+    pub fn format_start_outcome(&self, outcome: &StartOutcome, phases: &[StartPhaseReport]) -> String {
+        match self.format {
+            OutputFormat::Text | OutputFormat::Porcelain => start_message(outcome),
+            OutputFormat::Json => {
+                let response = StartOutcomeResponse {
+                    success: true,
+                    outcome,
+                    phases: phases.to_vec(),
+                };
+                serde_json::to_string_pretty(&response).unwrap_or_else(|_| "{}".to_string())
+            }
+        }
+    }
+
+    /// Format a failed start, with the phases of a verbose start in JSON like
+    /// [`Formatter::format_start_outcome`]
+    pub fn format_start_failure(&self, message: &str, phases: &[StartPhaseReport]) -> String {
+        match self.format {
+            OutputFormat::Text | OutputFormat::Porcelain => message.to_string(),
+            OutputFormat::Json => {
+                let response = StartResultResponse {
+                    success: false,
+                    message: message.to_string(),
+                    phases: phases.to_vec(),
+                };
+                serde_json::to_string_pretty(&response).unwrap_or_else(|_| "{}".to_string())
+            }
+        }
+    }
+
+    pub fn format_stop_outcome(&self, outcome: &StopOutcome) -> String {
+        self.format_outcome(outcome, stop_message)
+    }
+
+    pub fn format_restart_outcome(&self, outcome: &RestartOutcome) -> String {
+        self.format_outcome(outcome, restart_message)
+    }
+
+    pub fn format_watch_restart(&self, restart: &WatchRestart) -> String {
+        self.format_outcome(restart, watch_restart_message)
+    }
+
+    pub fn format_delete_outcome(&self, outcome: &DeleteOutcome) -> String {
+        self.format_outcome(outcome, delete_message)
+    }
+
+    pub fn format_undelete_outcome(&self, outcome: &UndeleteOutcome) -> String {
+        self.format_outcome(outcome, undelete_message)
+    }
+
+    pub fn format_name_check(&self, check: &NameCheck) -> String {
+        self.format_outcome(check, name_check_message)
+    }
+
+    /// The sentence in text output, the outcome's fields next to `success` in JSON
+    fn format_outcome<T: Serialize>(&self, outcome: &T, message: fn(&T) -> String) -> String {
+        match self.format {
+            OutputFormat::Text | OutputFormat::Porcelain => message(outcome),
+            OutputFormat::Json => {
+                let response = OutcomeResponse { success: true, outcome };
+                serde_json::to_string_pretty(&response).unwrap_or_else(|_| "{}".to_string())
+            }
+        }
+    }
+
     /// Format error message
     pub fn format_error_message(&self, message: &str) -> String {
         match self.format {
-            OutputFormat::Text => message.to_string(),
+            OutputFormat::Text | OutputFormat::Porcelain => message.to_string(),
             OutputFormat::Json => {
                 let response = SimpleResponse {
                     success: false,
@@ -104,6 +673,8 @@ impl Formatter {
     /// Format empty list message
     pub fn format_empty_list_message(&self, message: &str) -> String {
         match self.format {
+            OutputFormat::Text if self.quiet => String::new(),
+            OutputFormat::Porcelain => String::new(),
             OutputFormat::Text => message.to_string(),
             OutputFormat::Json => {
                 let response = EmptyListResponse {
@@ -117,54 +688,232 @@ impl Formatter {
 
     // Private methods for text formatting
     fn format_process_list_text(&self, processes: &[ProcessRecord]) -> String {
+        if self.quiet {
+            return processes.iter().map(|process| process.name.as_str()).collect::<Vec<_>>().join("\n");
+        }
+
         let mut output = String::new();
-        output.push_str(&format!("{:<20} {:<10} {:<10} {:<30} {:<20}", "NAME", "STATUS", "PID", "COMMAND", "CREATED"));
-        output.push('\n');
-        output.push_str(&"-".repeat(90));
-        output.push('\n');
-        
+        let width = if self.wide { 121 } else { 90 };
+        if !self.no_header {
+            if processes.iter().any(|process| process.degraded) {
+                output.push_str(DEGRADED_BANNER);
+                output.push('\n');
+            }
+            output.push_str(&format!("{:<20} {:<10} {:<10} {:<30} {:<20}", "NAME", "STATUS", "PID", "COMMAND", "CREATED"));
+            if self.wide {
+                output.push_str(&format!(" {:<30}", "DESCRIPTION"));
+            }
+            output.push('\n');
+            output.push_str(&"-".repeat(width));
+            output.push('\n');
+        }
+
         for process in processes {
             let pid_str = process.pid.map(|p| p.to_string()).unwrap_or_else(|| "-".to_string());
             let created_str = process.created_at.format("%Y-%m-%d %H:%M:%S").to_string();
+            // Columns are measured in terminal cells; names and commands are never cut, long
+            // commands having been shortened already unless --full asked for them whole
             output.push_str(&format!(
-                "{:<20} {:<10} {:<10} {:<30} {:<20}",
-                process.name,
+                "{} {:<10} {:<10} {} {:<20}",
+                pad(process.name.as_str(), 20),
                 process.status,
                 pid_str,
-                format!("{} {}", process.command, process.args.join(" ")),
+                pad(&format!("{} {}", process.command, process.args.join(" ")), 30),
                 created_str
             ));
+            if self.wide {
+                output.push_str(&format!(" {}", fit(process.description.as_deref().unwrap_or("-"), 30)));
+            }
+            if process.flapping {
+                output.push_str(" FLAPPING");
+            }
+            if process.ephemeral_keep_secs.is_some() {
+                output.push_str(" EPHEMERAL");
+            }
+            if process.inferred {
+                output.push_str(" INFERRED");
+            }
             output.push('\n');
         }
-        
+
+        if !self.no_header {
+            output.push_str(&"-".repeat(width));
+            output.push('\n');
+            output.push_str(&ProcessCounts::from_processes(processes).to_string());
+            output.push('\n');
+        }
+
+        // The caller prints with println!, which adds the final newline
+        output.truncate(output.trim_end_matches('\n').len());
+        output
+    }
+
+    fn format_job_list_text(&self, jobs: &[ProcessRecord], now: chrono::DateTime<chrono::Utc>) -> String {
+        if self.quiet {
+            return jobs.iter().map(|job| job.name.as_str()).collect::<Vec<_>>().join("\n");
+        }
+
+        let mut output = String::new();
+        if !self.no_header {
+            output.push_str(&format!("{:<20} {:<10} {:<5} {:<20} {:<10} {:<10} {:<20}", "NAME", "STATUS", "RUNS", "LAST RUN", "DURATION", "AVERAGE", "RESULT"));
+            output.push('\n');
+            output.push_str(&"-".repeat(101));
+            output.push('\n');
+        }
+        for job in jobs {
+            let run = JobRun::new(job, now);
+            output.push_str(&format!(
+                "{} {:<10} {:<5} {:<20} {:<10} {:<10} {:<20}",
+                pad(&run.name, 20),
+                run.status,
+                run.runs,
+                run.last_run.format("%Y-%m-%d %H:%M:%S"),
+                human_duration(std::time::Duration::from_millis(run.duration_ms)),
+                run.average_ms.map(|average| human_duration(std::time::Duration::from_millis(average))).unwrap_or_else(|| "-".to_string()),
+                run.result
+            ));
+            output.push('\n');
+        }
+        output.truncate(output.trim_end_matches('\n').len());
         output
     }
 
     fn format_process_list_json(&self, processes: &[ProcessRecord]) -> String {
         let process_list = ProcessListOutput {
             processes: processes.to_vec(),
+            summary: ProcessCounts::from_processes(processes),
+            degraded: processes.iter().any(|process| process.degraded),
         };
         serde_json::to_string_pretty(&process_list).unwrap_or_else(|_| "{}".to_string())
     }
 
     fn format_process_status_text(&self, process: &ProcessRecord) -> String {
         let mut output = String::new();
+        if process.degraded {
+            output.push_str(&format!("{}\n", DEGRADED_BANNER));
+        }
         output.push_str(&format!("Process: {}\n", process.name));
-        output.push_str(&format!("Status: {}\n", process.status));
+        if let Some(description) = &process.description {
+            output.push_str(&format!("Description: {}\n", description));
+        }
+        if process.inferred {
+            // Another user's PID that /proc hides: running going by its log, not confirmed
+            output.push_str(&format!("Status: {} (inferred)\n", process.status));
+        } else {
+            output.push_str(&format!("Status: {}\n", process.status));
+        }
+        if process.kind == ProcessKind::Job {
+            output.push_str(&format!("Kind: job (run {} time{})\n", process.runs, if process.runs == 1 { "" } else { "s" }));
+        }
+        if process.flapping {
+            output.push_str("Flapping: yes, failing repeatedly (see 'pmr doctor')\n");
+        }
+        if let Some(keep) = process.ephemeral_keep_secs {
+            let keep = format_duration(chrono::Duration::seconds(keep as i64));
+            match process.delete_after {
+                Some(at) => output.push_str(&format!(
+                    "Ephemeral: yes, deleted with its logs at {}\n",
+                    at.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M:%S")
+                )),
+                None => output.push_str(&format!("Ephemeral: yes, deleted with its logs {} after it exits\n", keep)),
+            }
+        }
+        if let Some(watch) = &process.watch {
+            output.push_str(&format!(
+                "Watch: {} (debounce {}ms; ignoring {})\n",
+                watch.paths.join(", "),
+                watch.debounce_ms,
+                watch.ignore.join(", ")
+            ));
+        }
         output.push_str(&format!("PID: {}\n", process.pid.map(|p| p.to_string()).unwrap_or_else(|| "N/A".to_string())));
+        if let Some(exit_code) = process.exit_code {
+            output.push_str(&format!("Exit Code: {}\n", exit_code));
+        } else if let Some(reason) = &process.exit_reason {
+            output.push_str(&format!("Exit Code: unknown ({})\n", reason));
+        }
+        if let Some(last) = process.recent_runs.last() {
+            let average = ProcessRun::average_duration_ms(&process.recent_runs).unwrap_or_default();
+            output.push_str(&format!(
+                "Last Run: {} ({}), average {} over the last {} run(s)\n",
+                human_duration(std::time::Duration::from_millis(last.duration_ms)),
+                run_result(last),
+                human_duration(std::time::Duration::from_millis(average)),
+                process.recent_runs.len()
+            ));
+        }
         output.push_str(&format!("Command: {} {}\n", process.command, process.args.join(" ")));
+        if !process.truncated_fields.is_empty() {
+            output.push_str(&format!("Truncated: {} (use --full to show everything)\n", process.truncated_fields.join(", ")));
+        }
         output.push_str(&format!("Working Directory: {}\n", process.working_dir));
         output.push_str(&format!("Created: {}\n", process.created_at.format("%Y-%m-%d %H:%M:%S")));
         output.push_str(&format!("Updated: {}\n", process.updated_at.format("%Y-%m-%d %H:%M:%S")));
-        output.push_str(&format!("Log File: {}\n", process.log_path));
+        if let Some(verified_at) = process.status_verified_at {
+            output.push_str(&format!("Status Verified: {}\n", verified_at.format("%Y-%m-%d %H:%M:%S")));
+        }
+        let log_mode = match process.log_dir_mode {
+            LogDirMode::Workdir => " (workdir mode, follows the working directory)",
+            LogDirMode::Default => "",
+        };
+        output.push_str(&format!("Log File: {}{}\n", process.log_path, log_mode));
+        output.push_str(&format!("Stop Signal: {}\n", crate::process::signal_name(process.stop_signal)));
+        output.push_str(&format!("Umask: {}\n", process.umask.as_deref().unwrap_or("inherited from pmr")));
+
+        if let Some(memory_max) = process.cgroup_memory_max {
+            output.push_str(&format!("Memory Limit: {}\n", human_bytes(memory_max)));
+        }
+        if let Some(cpu_max) = process.cgroup_cpu_max {
+            output.push_str(&format!("CPU Limit: {}%\n", cpu_max));
+        }
+        if let Some(cpu_affinity) = &process.cpu_affinity {
+            output.push_str(&format!("CPU Affinity: {}\n", cpu_affinity));
+        }
+        if let Some(cgroup) = &process.cgroup {
+            output.push_str(&format!("Cgroup: {}\n", cgroup.path));
+            output.push_str(&format!("Memory Current: {}\n", cgroup.memory_current.map(human_bytes).unwrap_or_else(|| "N/A".to_string())));
+            output.push_str(&format!("CPU Pressure: {}\n", cgroup.cpu_pressure.as_deref().unwrap_or("N/A")));
+        }
+        if let Some(drift) = &process.drift {
+            output.push_str(&format!("Drift: {}\n", drift_summary(drift)));
+        }
+        if let Some(snapshot) = &process.start_snapshot {
+            output.push_str(&snapshot_text(snapshot));
+        }
+        if let Some(note) = process.notes.last() {
+            output.push_str(&format!(
+                "Latest Note: [{}] {}: {}\n",
+                note.created_at.format("%Y-%m-%d %H:%M"),
+                note.author,
+                truncate_note(&note.text)
+            ));
+        }
         
         if !process.env_vars.is_empty() {
             output.push_str("Environment Variables:\n");
-            for (key, value) in &process.env_vars {
-                output.push_str(&format!("  {}={}\n", key, value));
+            let mut keys: Vec<&String> = process.env_vars.keys().collect();
+            keys.sort();
+            for key in keys {
+                let origin = if process.default_env_keys.contains(key) { " (default)" } else { "" };
+                output.push_str(&format!("  {}={}{}\n", key, process.env_vars[key], origin));
             }
         }
-        
+
+        if !process.last_output.is_empty() {
+            output.push_str(if process.last_output_lossy {
+                "Last Output (not valid UTF-8, shown lossily):\n"
+            } else {
+                "Last Output:\n"
+            });
+            for line in &process.last_output {
+                if self.color {
+                    output.push_str(&format!("  \x1b[2m{}\x1b[0m\n", line));
+                } else {
+                    output.push_str(&format!("  {}\n", line));
+                }
+            }
+        }
+
         output
     }
 
@@ -173,22 +922,157 @@ impl Formatter {
     }
 }
 
+/// Shown above listings and in statuses while the manager is degraded
+const DEGRADED_BANNER: &str =
+    "WARNING: statuses cannot be stored: the database storage is read-only, full or failing; showing the statuses probed just now";
+
+/// How a finished run ended: its status, with the exit code when it was not 0
+fn run_result(run: &ProcessRun) -> String {
+    match run.exit_code {
+        Some(code) if code != 0 => format!("{} (exit {})", run.status, code),
+        _ => run.status.to_string(),
+    }
+}
+
+/// The last run of a job, as listed by `pmr list --jobs`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JobRun {
+    pub name: String,
+    pub status: ProcessStatus,
+    pub runs: u32,
+    /// When the last run started
+    pub last_run: chrono::DateTime<chrono::Utc>,
+    /// How long the last run took, or has taken so far while it is running
+    pub duration_ms: u64,
+    /// Average duration of the recent finished runs, when any were recorded
+    #[serde(default)]
+    pub average_ms: Option<u64>,
+    /// `succeeded`, `failed (exit N)`, `running`, or what else the status says
+    pub result: String,
+}
+
+impl JobRun {
+    pub fn new(job: &ProcessRecord, now: chrono::DateTime<chrono::Utc>) -> Self {
+        let result = match (&job.status, job.exit_code) {
+            (ProcessStatus::Failed, Some(code)) => format!("failed (exit {})", code),
+            // A job that outlived the manager that started it exits unobserved
+            (ProcessStatus::Stopped, None) => "unknown (exit not seen)".to_string(),
+            (status, _) => status.to_string(),
+        };
+        Self {
+            name: job.name.to_string(),
+            status: job.status.clone(),
+            runs: job.runs,
+            last_run: job.created_at,
+            duration_ms: job.run_duration(now).num_milliseconds().max(0) as u64,
+            average_ms: ProcessRun::average_duration_ms(&job.recent_runs),
+            result,
+        }
+    }
+}
+
+/// Line introducing one rotated log file in `pmr logs --rotated`
+pub fn rotated_log_header(log: &RotatedLog) -> String {
+    let modified = log.modified.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M:%S");
+    if log.compressed {
+        format!("=== {} ({}, modified {}, compressed: not shown) ===", log.path, human_bytes(log.size_bytes), modified)
+    } else {
+        format!("=== {} ({}, modified {}) ===", log.path, human_bytes(log.size_bytes), modified)
+    }
+}
+
 // Helper structs for JSON output
+#[derive(Serialize, Deserialize)]
+struct JobListOutput {
+    jobs: Vec<JobRun>,
+}
+
 #[derive(Serialize, Deserialize)]
 struct ProcessListOutput {
     processes: Vec<ProcessRecord>,
+    summary: ProcessCounts,
+    /// The statuses were probed but could not be stored
+    #[serde(default)]
+    degraded: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ProcessCountsOutput {
+    summary: ProcessCounts,
 }
 
 #[derive(Serialize, Deserialize)]
 struct LogOutput {
     process_name: String,
     logs: String,
+    contains_invalid_utf8: bool,
+    /// Where `logs` starts in the log file, for `--tail-bytes`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    start_offset: Option<u64>,
 }
 
 #[derive(Serialize, Deserialize)]
 struct RotatedLogsOutput {
     process_name: String,
-    rotated_logs: Vec<String>,
+    rotated_logs: Vec<RotatedLog>,
+}
+
+#[derive(Serialize)]
+struct ConfigValidationOutput {
+    config_path: String,
+    valid: bool,
+    errors: usize,
+    warnings: usize,
+    issues: Vec<ConfigIssue>,
+}
+
+#[derive(Serialize)]
+struct RunHistoryOutput {
+    process_name: String,
+    average_duration_ms: Option<u64>,
+    runs: Vec<ProcessRun>,
+}
+
+#[derive(Serialize)]
+struct ProfileListingsOutput {
+    profiles: Vec<ProfileListing>,
+}
+
+#[derive(Serialize)]
+struct HostListingsOutput {
+    hosts: Vec<HostListing>,
+}
+
+#[derive(Serialize)]
+struct WatchHistoryOutput {
+    process_name: String,
+    restarts: Vec<WatchRestartEntry>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RotationHistoryOutput {
+    process_name: String,
+    rotations: Vec<LogRotationEntry>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct NotesOutput {
+    process_name: String,
+    notes: Vec<ProcessNote>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct DoctorOutput {
+    flapping: Vec<FlappingProcess>,
+    zombies: Vec<ZombieProcess>,
+    loose_permissions: Vec<LoosePermissions>,
+    /// Paths `--fix-permissions` restricted, with the modes they had
+    fixed_permissions: Vec<LoosePermissions>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct DriftOutput {
+    reports: Vec<DriftReport>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -197,12 +1081,238 @@ struct SimpleResponse {
     message: String,
 }
 
+#[derive(Serialize)]
+struct OutcomeResponse<'a, T> {
+    success: bool,
+    #[serde(flatten)]
+    outcome: &'a T,
+}
+
+#[derive(Serialize)]
+struct StartOutcomeResponse<'a> {
+    success: bool,
+    #[serde(flatten)]
+    outcome: &'a StartOutcome,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    phases: Vec<StartPhaseReport>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct StartResultResponse {
+    success: bool,
+    message: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    phases: Vec<StartPhaseReport>,
+}
+
 #[derive(Serialize, Deserialize)]
 struct EmptyListResponse {
     processes: Vec<ProcessRecord>,
     message: String,
 }
 
+/// Indented block describing a start snapshot; unknown values show as N/A
+/// Characters of a note shown on the status page
+const NOTE_PREVIEW_CHARS: usize = 60;
+
+/// First line of a note, cut to [`NOTE_PREVIEW_CHARS`]
+fn truncate_note(text: &str) -> String {
+    let first_line = text.lines().next().unwrap_or_default();
+    let mut preview: String = first_line.graphemes(true).take(NOTE_PREVIEW_CHARS).collect();
+    if preview.len() < text.len() {
+        preview.push_str("...");
+    }
+    preview
+}
+
+fn snapshot_text(snapshot: &StartSnapshot) -> String {
+    let known = |value: Option<String>| value.unwrap_or_else(|| "N/A".to_string());
+    let mut output = format!("Start Snapshot: {}\n", snapshot.captured_at.format("%Y-%m-%d %H:%M:%S%.3f"));
+    output.push_str(&format!("  PID: {} (parent {})\n", snapshot.pid, known(snapshot.ppid.map(|p| p.to_string()))));
+    output.push_str(&format!(
+        "  UID/GID: {}/{}\n",
+        known(snapshot.uid.map(|u| u.to_string())),
+        known(snapshot.gid.map(|g| g.to_string()))
+    ));
+    output.push_str(&format!("  CWD: {}\n", known(snapshot.cwd.clone())));
+    output.push_str(&format!("  RSS: {}\n", known(snapshot.rss_kb.map(|rss| human_bytes(rss.saturating_mul(1024))))));
+    if let Some(environ) = &snapshot.environ {
+        output.push_str(&format!("  Environment ({}):\n", environ.len()));
+        for (key, value) in environ {
+            output.push_str(&format!("    {}={}\n", key, value));
+        }
+    }
+    for note in &snapshot.notes {
+        output.push_str(&format!("  Note: {}\n", note));
+    }
+    output
+}
+
+fn schema_info_text(info: &SchemaInfo) -> String {
+    let version = match info.schema_version {
+        0 => "0 (none recorded)".to_string(),
+        version => version.to_string(),
+    };
+    let pending = if !info.migrations_pending() {
+        "none".to_string()
+    } else if info.destructive_migrations.is_empty() {
+        "yes, applied on the next command".to_string()
+    } else {
+        let mut pending = "yes, rebuilding tables (needs --migrate or PMR_AUTO_MIGRATE=1):".to_string();
+        for migration in &info.destructive_migrations {
+            pending.push_str(&format!("\n  - {}", migration));
+        }
+        pending
+    };
+    let compatible = if info.is_compatible() {
+        "yes".to_string()
+    } else {
+        format!("no, install pmr {} or later", info.min_binary_version.as_deref().unwrap_or("(unknown)"))
+    };
+    format!(
+        "Database: {}\nSchema version: {}\nSupported schema version: {} (pmr {})\nMinimum pmr version: {}\nCompatible: {}\nPending migrations: {}",
+        info.database.display(),
+        version,
+        info.supported_version,
+        info.binary_version,
+        info.min_binary_version.as_deref().unwrap_or("-"),
+        compatible,
+        pending
+    )
+}
+
+fn salvage_report_text(report: &SalvageReport) -> String {
+    let rule = "=".repeat(72);
+    let mut output = format!(
+        "{rule}\nDATABASE DAMAGED: salvaged what was readable\n{rule}\nRebuilt database: {}\nDamaged file kept at: {}\nRows recovered: {}\nRows lost: {}",
+        report.database.display(),
+        report.backup.display(),
+        report.recovered(),
+        report.lost()
+    );
+    for table in &report.tables {
+        let lost = match table.lost {
+            Some(lost) => format!("{} lost", lost),
+            None => "unreadable, row count unknown".to_string(),
+        };
+        output.push_str(&format!("\n  {}: {} recovered, {}", table.table, table.recovered, lost));
+    }
+    if report.integrity_check.iter().any(|line| line != "ok") {
+        output.push_str("\nIntegrity check of the damaged file:");
+        for line in &report.integrity_check {
+            output.push_str(&format!("\n  {}", line));
+        }
+    }
+    output.push_str(&format!("\n{rule}"));
+    output
+}
+
+/// How long the oldest failed and stopped processes have been so, as a line of its own;
+/// empty when there are none
+fn status_ages_text(stats: &ManagerStats) -> String {
+    let ages = [("failed", stats.oldest_failed_age_seconds), ("stopped", stats.oldest_stopped_age_seconds)]
+        .into_iter()
+        .filter_map(|(status, age)| Some(format!("{} {} ago", status, human_duration(std::time::Duration::from_secs(age?)))))
+        .collect::<Vec<_>>();
+    if ages.is_empty() {
+        String::new()
+    } else {
+        format!("\nOldest: {}", ages.join(", "))
+    }
+}
+
+fn config_paths_text(paths: &ConfigPaths) -> String {
+    let config_file = match &paths.config_file {
+        Some(path) => path.display().to_string(),
+        None => "(none, using defaults)".to_string(),
+    };
+    format!(
+        "Database: {}\nDefault log directory: {}\nConfig file: {}",
+        paths.database.display(),
+        paths.default_log_dir.display(),
+        config_file
+    )
+}
+
+/// Disk usage of the logs by directory, the quota and what it last deleted
+fn logs_quota_text(logs: &LogsQuotaStats) -> String {
+    let usage = &logs.usage;
+    let quota = match logs.quota_bytes {
+        0 => "no quota".to_string(),
+        quota => format!("quota {}", human_bytes(quota)),
+    };
+    let mut text = format!(
+        "Logs: {} ({} live, {} rotated; {})",
+        human_bytes(usage.total_bytes()),
+        human_bytes(usage.live_bytes()),
+        human_bytes(usage.rotated_bytes()),
+        quota
+    );
+    for dir in &usage.directories {
+        text.push_str(&format!(
+            "\n  {}: {} live, {} rotated ({} process{}",
+            dir.log_dir,
+            human_bytes(dir.live_bytes),
+            human_bytes(dir.rotated_bytes),
+            dir.processes,
+            if dir.processes == 1 { "" } else { "es" }
+        ));
+        if dir.unknown > 0 {
+            text.push_str(&format!(", {} not measured in time", dir.unknown));
+        }
+        text.push(')');
+    }
+    if let Some(enforcement) = &logs.last_enforcement {
+        text.push_str(&format!(
+            "\nLast quota enforcement: {}, deleted {} archive{} ({}), {} left",
+            enforcement.enforced_at.format("%Y-%m-%d %H:%M:%S UTC"),
+            enforcement.deleted.len(),
+            if enforcement.deleted.len() == 1 { "" } else { "s" },
+            human_bytes(enforcement.freed_bytes()),
+            human_bytes(enforcement.used_after)
+        ));
+    }
+    let archives_exhausted = usage.rotated_bytes() == 0 && logs.quota_bytes > 0 && usage.total_bytes() > logs.quota_bytes;
+    if archives_exhausted || logs.last_enforcement.as_ref().is_some_and(QuotaEnforcement::over_quota) {
+        text.push_str("\nWARNING: logs stay over the quota even with the rotated archives deleted");
+    }
+    text
+}
+
+/// Symbols of the `pmr stats --oneline` summary
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OnelineSymbols {
+    Unicode,
+    Ascii,
+}
+
+impl OnelineSymbols {
+    /// `--ascii` or `--unicode` when given, otherwise symbols only on a terminal
+    pub fn select(ascii: bool, unicode: bool, stdout_is_terminal: bool) -> Self {
+        match (ascii, unicode) {
+            (true, _) => Self::Ascii,
+            (false, true) => Self::Unicode,
+            (false, false) if stdout_is_terminal => Self::Unicode,
+            (false, false) => Self::Ascii,
+        }
+    }
+}
+
+/// One-line description of a drift report; issues are listed on indented lines
+fn drift_summary(report: &DriftReport) -> String {
+    if report.has_drift() {
+        let mut summary = format!("{} issue(s)", report.issues.len());
+        for issue in &report.issues {
+            summary.push_str(&format!("\n  - {}", issue));
+        }
+        summary
+    } else if report.fingerprinted {
+        "no drift".to_string()
+    } else {
+        "no drift (binary not recorded at start)".to_string()
+    }
+}
+
 impl Formatter {
     // Private methods for clear result formatting
     fn format_clear_result_text(&self, result: &ClearResult) -> String {
@@ -231,6 +1341,20 @@ impl Formatter {
             for process_name in &result.failed_processes {
                 output.push_str(&format!("  - {}", process_name));
                 output.push('\n');
+                if let Some(reason) = result.details.get(process_name) {
+                    output.push_str(&format!("      {}", reason));
+                    output.push('\n');
+                }
+            }
+        }
+
+        if !result.skipped_processes.is_empty() {
+            output.push('\n');
+            output.push_str(&format!("Skipped {} processes (cancelled):", result.skipped_processes.len()));
+            output.push('\n');
+            for process_name in &result.skipped_processes {
+                output.push_str(&format!("  - {}", process_name));
+                output.push('\n');
             }
         }
 
@@ -243,3 +1367,713 @@ impl Formatter {
 }
 
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::drift::DriftIssue;
+    use crate::logs_quota::{DeletedArchive, LogDirUsage, LogsUsage};
+    use crate::outcome::RestartSkip;
+    use chrono::Utc;
+    use std::collections::HashMap;
+
+    fn record(name: &str, status: ProcessStatus) -> ProcessRecord {
+        ProcessRecord {
+            id: name.to_string(),
+            name: name.parse().unwrap(),
+            command: "sleep".to_string(),
+            args: vec!["60".to_string()],
+            env_vars: HashMap::new(),
+            working_dir: "/tmp".to_string(),
+            pid: Some(42),
+            status,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            log_path: format!("/tmp/{}.log", name),
+            log_dir: None,
+            cgroup_memory_max: None,
+            cgroup_cpu_max: None,
+            cgroup: None,
+            deleted_at: None,
+            binary_path: None,
+            binary_sha256: None,
+            canonical_workdir: None,
+            exit_code: None,
+            exit_reason: None,
+            cpu_affinity: None,
+            umask: None,
+            description: None,
+            start_snapshot: None,
+            default_env_keys: Vec::new(),
+            drift: None,
+            notes: Vec::new(),
+            recent_runs: Vec::new(),
+            flapping: false,
+            degraded: false,
+            inferred: false,
+            truncated_fields: Vec::new(),
+            kind: ProcessKind::Service,
+            runs: 1,
+            stop_signal: libc::SIGTERM,
+            log_dir_mode: LogDirMode::Default,
+            status_verified_at: None,
+            internal: false,
+            ephemeral_keep_secs: None,
+            delete_after: None,
+            last_output: Vec::new(),
+            last_output_lossy: false,
+            watch: None,
+            rotate_on_start: None,
+        }
+    }
+
+    fn sample() -> Vec<ProcessRecord> {
+        vec![
+            record("web", ProcessStatus::Running),
+            record("worker", ProcessStatus::Running),
+            record("cron", ProcessStatus::Stopped),
+            record("broken", ProcessStatus::Failed),
+        ]
+    }
+
+    #[test]
+    fn test_stats_oneline() {
+        let counts = ProcessCounts { running: 12, stopped: 2, failed: 1, unknown: 3, succeeded: 4, total: 22 };
+        assert_eq!(Formatter::format_stats_oneline(&counts, OnelineSymbols::Unicode), "pmr: 12▲ 2■ 1✖");
+        assert_eq!(Formatter::format_stats_oneline(&counts, OnelineSymbols::Ascii), "pmr: 12R 2S 1F");
+        assert_eq!(Formatter::format_stats_oneline(&ProcessCounts::default(), OnelineSymbols::Ascii), "pmr: 0R 0S 0F");
+
+        assert_eq!(OnelineSymbols::select(false, false, true), OnelineSymbols::Unicode);
+        assert_eq!(OnelineSymbols::select(false, false, false), OnelineSymbols::Ascii);
+        assert_eq!(OnelineSymbols::select(true, false, true), OnelineSymbols::Ascii);
+        assert_eq!(OnelineSymbols::select(false, true, false), OnelineSymbols::Unicode);
+    }
+
+    #[test]
+    fn test_list_text_has_summary_footer() {
+        let output = Formatter::new(OutputFormat::Text).format_process_list(&sample());
+        assert_eq!(output.lines().last(), Some("2 running, 1 stopped, 1 failed (4 total)"));
+    }
+
+    #[test]
+    fn test_description_in_wide_list_status_and_json() {
+        let mut processes = sample();
+        processes[0].description = Some("Public HTTP API".to_string());
+
+        let narrow = Formatter::new(OutputFormat::Text).format_process_list(&processes);
+        assert!(!narrow.contains("DESCRIPTION") && !narrow.contains("Public HTTP API"));
+        let wide = Formatter::new(OutputFormat::Text).with_wide(true).format_process_list(&processes);
+        let lines: Vec<&str> = wide.lines().collect();
+        assert!(lines[0].trim_end().ends_with("DESCRIPTION"), "{}", lines[0]);
+        assert_eq!(lines[1].len(), 121);
+        assert!(lines[2].starts_with("web ") && lines[2].trim_end().ends_with("Public HTTP API"), "{}", lines[2]);
+        assert!(lines[3].trim_end().ends_with(" -"), "{}", lines[3]);
+
+        let status = Formatter::new(OutputFormat::Text).format_process_status(&processes[0]);
+        assert!(status.contains("Process: web\nDescription: Public HTTP API\nStatus:"), "{}", status);
+        assert!(!Formatter::new(OutputFormat::Text).format_process_status(&processes[1]).contains("Description:"));
+
+        let json: serde_json::Value = serde_json::from_str(&Formatter::new(OutputFormat::Json).format_process_list(&processes)).unwrap();
+        assert_eq!(json["processes"][0]["description"], "Public HTTP API");
+        assert!(json["processes"][1]["description"].is_null());
+    }
+
+    #[test]
+    fn test_wide_characters_keep_columns_aligned() {
+        use crate::text_width::width;
+
+        let mut processes = vec![
+            record("数据同步服务", ProcessStatus::Running),
+            record("deploy 🚀", ProcessStatus::Stopped),
+            record("cafe\u{301}", ProcessStatus::Running),
+            record("\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467} family", ProcessStatus::Failed),
+            record("非常非常非常非常长的进程名字", ProcessStatus::Running),
+        ];
+        processes[0].command = "同步工具".to_string();
+        processes[1].args = vec!["🚀".repeat(5)];
+        processes[2].description = Some("crème brûlée e\u{301}".repeat(5));
+        processes[3].description = Some("家族".to_string());
+
+        for formatter in [Formatter::new(OutputFormat::Text), Formatter::new(OutputFormat::Text).with_wide(true)] {
+            let list = formatter.format_process_list(&processes);
+            let lines: Vec<&str> = list.lines().collect();
+            // Columns start where the header's do, counted in terminal cells
+            let column_start = |line: &str, text: &str| width(&line[..line.find(text).unwrap()]);
+            let header = lines[0];
+            let starts = ["STATUS", "PID", "COMMAND", "CREATED"].map(|column| column_start(header, column));
+            assert_eq!(starts, [21, 32, 43, 74]);
+            for (row, process) in lines[2..6].iter().zip(&processes) {
+                assert_eq!(column_start(row, &process.status.to_string()), starts[0], "{}", row);
+                assert_eq!(column_start(row, "42 "), starts[1], "{}", row);
+                assert_eq!(column_start(row, &process.created_at.format("%Y-%m-%d").to_string()), starts[3], "{}", row);
+            }
+            // A name wider than its column is shown whole and pushes the rest of its row
+            assert!(lines[6].starts_with("非常非常非常非常长的进程名字 running"), "{}", lines[6]);
+        }
+
+        let wide = Formatter::new(OutputFormat::Text).with_wide(true).format_process_list(&processes);
+        let lines: Vec<&str> = wide.lines().collect();
+        let description_start = width(&lines[0][..lines[0].find("DESCRIPTION").unwrap()]);
+        assert!(lines[4].ends_with("…"), "{}", lines[4]);
+        assert_eq!(width(lines[4]), description_start + 30, "{}", lines[4]);
+        assert!(lines[5].starts_with("\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467} family            failed"), "{}", lines[5]);
+        assert!(lines[5].trim_end().ends_with(" 家族"), "{}", lines[5]);
+    }
+
+    #[test]
+    fn test_quiet_text_lists_names_only() {
+        let output = Formatter::new(OutputFormat::Text).with_quiet(true).format_process_list(&sample());
+        assert_eq!(output, "web\nworker\ncron\nbroken");
+
+        // --no-header changes nothing once only names are printed
+        let both = Formatter::new(OutputFormat::Text)
+            .with_quiet(true)
+            .with_no_header(true)
+            .format_process_list(&sample());
+        assert_eq!(both, output);
+
+        let empty = Formatter::new(OutputFormat::Text).with_quiet(true).format_empty_list_message("No processes found.");
+        assert_eq!(empty, "");
+    }
+
+    #[test]
+    fn test_host_listings_group_rows_by_host() {
+        let listings = vec![
+            HostListing { host: "db-1".to_string(), current: false, processes: vec![record("web", ProcessStatus::Running)] },
+            HostListing { host: "db-2".to_string(), current: true, processes: vec![record("web", ProcessStatus::Stopped)] },
+        ];
+        let text = Formatter::new(OutputFormat::Text).format_host_listings(&listings);
+        assert!(text.starts_with("HOST"), "{}", text);
+        assert!(text.lines().any(|line| line.starts_with("db-1") && line.contains(" web ") && line.contains("running")), "{}", text);
+        assert!(text.ends_with("db-1: 1 process(es)\ndb-2: 1 process(es), current"), "{}", text);
+
+        let quiet = Formatter::new(OutputFormat::Text).with_quiet(true).format_host_listings(&listings);
+        assert_eq!(quiet, "db-1/web\ndb-2/web");
+
+        let json: serde_json::Value = serde_json::from_str(&Formatter::new(OutputFormat::Json).format_host_listings(&listings)).unwrap();
+        assert_eq!(json["hosts"][1]["host"], "db-2");
+        assert_eq!(json["hosts"][1]["current"], true);
+        assert_eq!(json["hosts"][0]["processes"][0]["name"], "web");
+    }
+
+    #[test]
+    fn test_no_header_keeps_rows_aligned() {
+        let full = Formatter::new(OutputFormat::Text).format_process_list(&sample());
+        let rows = Formatter::new(OutputFormat::Text).with_no_header(true).format_process_list(&sample());
+
+        assert!(!rows.contains("NAME"));
+        assert!(!rows.contains("---"));
+        assert!(!rows.contains("total"));
+        // Data rows are byte-for-byte the ones of the full table, so columns still line up
+        let full_rows: Vec<&str> = full.lines().skip(2).take(4).collect();
+        assert_eq!(rows.lines().collect::<Vec<_>>(), full_rows);
+        assert_eq!(
+            Formatter::new(OutputFormat::Text).with_no_header(true).format_empty_list_message("No processes found."),
+            "No processes found."
+        );
+    }
+
+    #[test]
+    fn test_quiet_and_no_header_ignored_for_json() {
+        let processes = sample();
+        let plain = Formatter::new(OutputFormat::Json).format_process_list(&processes);
+        for formatter in [
+            Formatter::new(OutputFormat::Json).with_quiet(true),
+            Formatter::new(OutputFormat::Json).with_no_header(true),
+            Formatter::new(OutputFormat::Json).with_quiet(true).with_no_header(true),
+        ] {
+            assert_eq!(formatter.format_process_list(&processes), plain);
+            assert!(formatter.format_empty_list_message("No processes found.").contains("No processes found."));
+        }
+    }
+
+    #[test]
+    fn test_summary_mentions_unknown_only_when_present() {
+        let mut processes = sample();
+        processes.push(record("odd", ProcessStatus::Unknown));
+        assert_eq!(
+            ProcessCounts::from_processes(&processes).to_string(),
+            "2 running, 1 stopped, 1 failed, 1 unknown (5 total)"
+        );
+    }
+
+    #[test]
+    fn test_list_json_includes_summary() {
+        let output = Formatter::new(OutputFormat::Json).format_process_list(&sample());
+        let json: serde_json::Value = serde_json::from_str(&output).unwrap();
+        assert_eq!(json["processes"].as_array().unwrap().len(), 4);
+        assert_eq!(json["summary"]["running"], 2);
+        assert_eq!(json["summary"]["total"], 4);
+    }
+
+    #[test]
+    fn test_counts_only_output() {
+        let counts = ProcessCounts::from_processes(&sample());
+
+        let text = Formatter::new(OutputFormat::Text).format_process_counts(&counts);
+        assert_eq!(text, "2 running, 1 stopped, 1 failed (4 total)");
+
+        let json = Formatter::new(OutputFormat::Json).format_process_counts(&counts);
+        let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(json, serde_json::json!({
+            "summary": { "running": 2, "stopped": 1, "failed": 1, "unknown": 0, "succeeded": 0, "total": 4 }
+        }));
+    }
+
+    #[test]
+    fn test_stats_shows_current_and_max() {
+        let counts = ProcessCounts::from_processes(&sample());
+        let paths = ConfigPaths {
+            database: "/home/ops/.pmr/processes.db".into(),
+            default_log_dir: "/srv/app/logs".into(),
+            config_file: None,
+        };
+        let usage = LogsUsage {
+            directories: vec![LogDirUsage { log_dir: "/srv/app/logs".to_string(), processes: 4, live_bytes: 700, rotated_bytes: 300, unknown: 0 }],
+            measured_at: Utc::now(),
+        };
+        let logs = LogsQuotaStats { quota_bytes: 0, usage, last_enforcement: None };
+        let stats = ManagerStats {
+            managed: counts.total,
+            max_processes: 10,
+            counts,
+            oldest_failed_age_seconds: Some(7800),
+            oldest_stopped_age_seconds: Some(45),
+            paths,
+            logs,
+        };
+
+        let text = Formatter::new(OutputFormat::Text).format_stats(&stats);
+        assert_eq!(
+            text,
+            "Processes: 4 (max 10)\n2 running, 1 stopped, 1 failed (4 total)\n\
+             Oldest: failed 2h 10m ago, stopped 45s ago\n\
+             Database: /home/ops/.pmr/processes.db\n\
+             Default log directory: /srv/app/logs\n\
+             Config file: (none, using defaults)\n\
+             Logs: 1000 B (700 B live, 300 B rotated; no quota)\n  \
+             /srv/app/logs: 700 B live, 300 B rotated (4 processes)"
+        );
+
+        let deleted = DeletedArchive { process_name: "web".to_string(), path: "/srv/app/logs/web.3.log".to_string(), size: 250 };
+        let enforcement = QuotaEnforcement {
+            enforced_at: "2026-10-18T09:30:00Z".parse().unwrap(),
+            quota_bytes: 1000,
+            used_before: 1250,
+            used_after: 1000,
+            deleted: vec![deleted],
+        };
+        let mut enforced = stats.clone();
+        enforced.logs.quota_bytes = 1000;
+        enforced.logs.last_enforcement = Some(enforcement.clone());
+        let text = Formatter::new(OutputFormat::Text).format_stats(&enforced);
+        assert!(text.contains("(700 B live, 300 B rotated; quota 1000 B)"), "{}", text);
+        assert!(text.ends_with("\nLast quota enforcement: 2026-10-18 09:30:00 UTC, deleted 1 archive (250 B), 1000 B left"), "{}", text);
+        enforced.logs.last_enforcement = Some(QuotaEnforcement { used_after: 1200, ..enforcement });
+        assert!(Formatter::new(OutputFormat::Text).format_stats(&enforced).ends_with("\nWARNING: logs stay over the quota even with the rotated archives deleted"));
+
+        let mut slow = stats.clone();
+        slow.logs.usage.directories[0].unknown = 1;
+        let text = Formatter::new(OutputFormat::Text).format_stats(&slow);
+        assert!(text.ends_with("/srv/app/logs: 700 B live, 300 B rotated (4 processes, 1 not measured in time)"), "{}", text);
+
+        let unlimited = ManagerStats { max_processes: 0, oldest_failed_age_seconds: None, ..stats.clone() };
+        assert!(Formatter::new(OutputFormat::Text).format_stats(&unlimited).starts_with("Processes: 4 (no limit)\n2 running, 1 stopped, 1 failed (4 total)\nOldest: stopped 45s ago\nDatabase:"));
+        let settled = ManagerStats { oldest_failed_age_seconds: None, oldest_stopped_age_seconds: None, ..stats.clone() };
+        assert!(Formatter::new(OutputFormat::Text).format_stats(&settled).contains("(4 total)\nDatabase:"));
+
+        let json: serde_json::Value = serde_json::from_str(&Formatter::new(OutputFormat::Json).format_stats(&stats)).unwrap();
+        assert_eq!(json["managed"], 4);
+        assert_eq!(json["max_processes"], 10);
+        assert_eq!(json["oldest_failed_age_seconds"], 7800);
+        assert_eq!(json["oldest_stopped_age_seconds"], 45);
+        assert_eq!(json["paths"]["database"], "/home/ops/.pmr/processes.db");
+        assert_eq!(json["paths"]["config_file"], serde_json::Value::Null);
+        assert_eq!(json["logs"]["usage"]["directories"][0]["rotated_bytes"], 300);
+    }
+
+    #[test]
+    fn test_status_includes_start_snapshot() {
+        let mut process = record("web", ProcessStatus::Running);
+        let text = Formatter::new(OutputFormat::Text).format_process_status(&process);
+        assert!(!text.contains("Start Snapshot"));
+
+        process.start_snapshot = Some(StartSnapshot {
+            pid: 42,
+            ppid: Some(1),
+            uid: Some(1000),
+            gid: Some(1000),
+            cwd: Some("/srv/web".to_string()),
+            environ: Some([("PMR_PROCESS_NAME".to_string(), "web".to_string())].into_iter().collect()),
+            notes: vec!["environ truncated to 16384 bytes".to_string()],
+            ..Default::default()
+        });
+        let text = Formatter::new(OutputFormat::Text).format_process_status(&process);
+        assert!(text.contains("  PID: 42 (parent 1)\n  UID/GID: 1000/1000\n  CWD: /srv/web\n  RSS: N/A\n"));
+        assert!(text.contains("  Environment (1):\n    PMR_PROCESS_NAME=web\n"));
+        assert!(text.contains("  Note: environ truncated"));
+    }
+
+    #[test]
+    fn test_start_result_includes_phases_in_json() {
+        use crate::process::StartPhase;
+        let phases = vec![
+            StartPhaseReport { phase: StartPhase::Validate, detail: "validated inputs".to_string(), elapsed_ms: 2, failed: false },
+            StartPhaseReport { phase: StartPhase::Log, detail: "Permission denied".to_string(), elapsed_ms: 1, failed: true },
+        ];
+        assert_eq!(phases[0].to_string(), "validate: validated inputs (2ms)");
+        assert_eq!(phases[1].to_string(), "log failed: Permission denied (1ms)");
+
+        let text = Formatter::new(OutputFormat::Text).format_start_failure("Permission denied", &phases);
+        assert_eq!(text, "Permission denied");
+
+        let json = Formatter::new(OutputFormat::Json).format_start_failure("Permission denied", &phases);
+        let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(json["success"], false);
+        assert_eq!(json["phases"][0]["phase"], "validate");
+        assert_eq!(json["phases"][1]["failed"], true);
+
+        // A completed start serializes its outcome; phases only appear for a verbose start
+        let outcome = StartOutcome {
+            name: "web".to_string(),
+            pid: Some(42),
+            status: ProcessStatus::Running,
+            unchanged: false,
+            early_exit: None,
+            rotated_log_size: None,
+        };
+        let json = Formatter::new(OutputFormat::Json).format_start_outcome(&outcome, &[]);
+        let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(json["success"], true);
+        assert_eq!(json["pid"], 42);
+        assert_eq!(json["status"], "Running");
+        assert!(json.get("phases").is_none());
+        assert_eq!(Formatter::new(OutputFormat::Text).format_start_outcome(&outcome, &phases), "Process 'web' started with PID 42");
+    }
+
+    #[test]
+    fn test_outcome_messages() {
+        use crate::outcome::{NotRunning, StartExit};
+        let start = |early_exit| StartOutcome {
+            name: "job".to_string(),
+            pid: Some(7),
+            status: ProcessStatus::Running,
+            unchanged: false,
+            early_exit,
+            rotated_log_size: None,
+        };
+        let exit = |exit_code, log_tail: &[&str]| StartExit {
+            exit_code,
+            elapsed_ms: 12,
+            log_tail: log_tail.iter().map(|line| line.to_string()).collect(),
+        };
+        assert_eq!(start_message(&start(Some(exit(Some(0), &[])))), "Process 'job' completed successfully in 12ms");
+        assert_eq!(
+            start_message(&start(Some(exit(Some(3), &["boom"])))),
+            "Process 'job' failed: exited with code 3 after 12ms\nLast log lines:\n  boom"
+        );
+        assert_eq!(start_message(&start(Some(exit(None, &[])))), "Process 'job' failed: was killed by a signal after 12ms");
+        assert_eq!(
+            start_message(&StartOutcome { unchanged: true, ..start(None) }),
+            "Process 'job' already running, unchanged"
+        );
+        assert_eq!(
+            start_message(&StartOutcome { pid: None, rotated_log_size: Some(12 * 1024 * 1024), ..start(None) }),
+            "Process 'job' started\nRotated the previous log (12.0 MiB) before starting; `pmr logs job --rotated` lists it"
+        );
+
+        let stop = |not_running| StopOutcome { name: "job".to_string(), pid: None, waited_ms: 0, escalated: false, not_running };
+        assert_eq!(stop_message(&StopOutcome { pid: Some(7), ..stop(None) }), "Process 'job' stopped");
+        let never = NotRunning { status: ProcessStatus::Failed, exit_code: None, since: None };
+        assert_eq!(stop_message(&stop(Some(never))), "Process 'job' was not running (it never started)");
+        let exited = NotRunning { status: ProcessStatus::Failed, exit_code: Some(3), since: Some(Utc::now()) };
+        let message = stop_message(&stop(Some(exited)));
+        assert!(message.starts_with("Process 'job' was not running (exited at "), "{}", message);
+        assert!(message.ends_with(" with code 3)"), "{}", message);
+
+        let restart = RestartOutcome {
+            name: "job".to_string(),
+            previous_pid: Some(6),
+            skipped: None,
+            stop: None,
+            start: Some(start(None)),
+        };
+        assert_eq!(restart_message(&restart), "Process 'job' restarted. Process 'job' started with PID 7");
+        let watched = |changes| WatchRestart { path: "/src/main.rs".to_string(), changes, outcome: restart.clone() };
+        assert_eq!(watch_restart_message(&watched(1)), "/src/main.rs changed. Process 'job' restarted. Process 'job' started with PID 7");
+        assert!(watch_restart_message(&watched(3)).starts_with("/src/main.rs and 2 other files changed. "));
+        let skipped = RestartOutcome { skipped: Some(RestartSkip::BinaryUnchanged), start: None, ..restart };
+        assert_eq!(restart_message(&skipped), "Process 'job' binary unchanged; not restarted");
+
+        let delete = DeleteOutcome { name: "job".to_string(), hard: false, previous_status: ProcessStatus::Stopped, stop: None };
+        assert_eq!(delete_message(&delete), "Process 'job' deleted (restore it with 'pmr undelete job')");
+        assert_eq!(delete_message(&DeleteOutcome { hard: true, ..delete }), "Process 'job' deleted");
+
+        // JSON carries the fields instead of the sentence
+        let json = Formatter::new(OutputFormat::Json).format_stop_outcome(&stop(None));
+        let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(json["success"], true);
+        assert_eq!(json["escalated"], false);
+        assert!(json.get("message").is_none());
+    }
+
+    #[test]
+    fn test_status_shows_latest_note_truncated() {
+        let mut process = record("web", ProcessStatus::Running);
+        let note = |id: i64, text: &str| ProcessNote {
+            id,
+            process_name: "web".to_string(),
+            created_at: Utc::now(),
+            author: "alice".to_string(),
+            text: text.to_string(),
+        };
+        process.notes = vec![note(1, "old"), note(2, &format!("{}\nsecond line", "x".repeat(80)))];
+
+        let text = Formatter::new(OutputFormat::Text).format_process_status(&process);
+        assert!(!text.contains("old"));
+        assert!(text.contains(&format!("alice: {}...\n", "x".repeat(NOTE_PREVIEW_CHARS))));
+        assert_eq!(truncate_note("short"), "short");
+        assert_eq!(truncate_note("two\nlines"), "two...");
+        let accented = "e\u{301}".repeat(NOTE_PREVIEW_CHARS + 1);
+        assert_eq!(truncate_note(&accented), format!("{}...", "e\u{301}".repeat(NOTE_PREVIEW_CHARS)));
+
+        let json: serde_json::Value = serde_json::from_str(&Formatter::new(OutputFormat::Json).format_process_status(&process)).unwrap();
+        assert_eq!(json["notes"][1]["text"].as_str().unwrap().len(), 92);
+        process.notes.clear();
+        let json: serde_json::Value = serde_json::from_str(&Formatter::new(OutputFormat::Json).format_process_status(&process)).unwrap();
+        assert!(json.get("notes").is_none());
+    }
+
+    #[test]
+    fn test_status_explains_missing_exit_code() {
+        let mut process = record("web", ProcessStatus::Stopped);
+        process.exit_reason = Some("not a child".to_string());
+        let text = Formatter::new(OutputFormat::Text).format_process_status(&process);
+        assert!(text.contains("Exit Code: unknown (not a child)\n"));
+
+        process.exit_code = Some(0);
+        let text = Formatter::new(OutputFormat::Text).format_process_status(&process);
+        assert!(text.contains("Exit Code: 0\n") && !text.contains("not a child"));
+    }
+
+    #[test]
+    fn test_flapping_marked_in_list_and_status() {
+        let mut processes = sample();
+        processes[3].flapping = true;
+        let list = Formatter::new(OutputFormat::Text).format_process_list(&processes);
+        let flagged: Vec<&str> = list.lines().filter(|line| line.ends_with(" FLAPPING")).collect();
+        assert_eq!(flagged.len(), 1);
+        assert!(flagged[0].starts_with("broken "));
+
+        let status = Formatter::new(OutputFormat::Text).format_process_status(&processes[3]);
+        assert!(status.contains("Status: failed\nFlapping: yes"), "{}", status);
+        assert!(!Formatter::new(OutputFormat::Text).format_process_status(&processes[0]).contains("Flapping"));
+
+        let report = Formatter::new(OutputFormat::Text).format_doctor_report(&[], &[], &[], &[]);
+        assert_eq!(report, "No problems found.");
+    }
+
+    #[test]
+    fn test_doctor_report_separates_zombies_by_parent() {
+        let zombies = [
+            ZombieProcess { name: "orphan".to_string(), pid: 12, parent_pid: 1, reparented: true },
+            ZombieProcess { name: "worker".to_string(), pid: 10, parent_pid: 9, reparented: false },
+        ];
+        let report = Formatter::new(OutputFormat::Text).format_doctor_report(&[], &zombies, &[], &[]);
+        assert_eq!(
+            report,
+            "Zombie processes whose parent has not reaped them (1):\n  worker (pid 10): parent 9 is alive\n\n\
+             Zombie processes left to init (1):\n  orphan (pid 12)"
+        );
+
+        let json: serde_json::Value = serde_json::from_str(&Formatter::new(OutputFormat::Json).format_doctor_report(&[], &zombies, &[], &[])).unwrap();
+        assert_eq!(json["flapping"], serde_json::json!([]));
+        assert_eq!(json["zombies"][1]["parent_pid"], 9);
+        assert_eq!(json["zombies"][1]["reparented"], false);
+    }
+
+    #[test]
+    fn test_doctor_report_lists_loose_and_fixed_permissions() {
+        let loose = |path: &str, is_dir: bool, mode: &str| LoosePermissions {
+            path: path.into(),
+            is_dir,
+            mode: mode.to_string(),
+            expected_mode: if is_dir { "0700" } else { "0600" }.to_string(),
+        };
+        let report = Formatter::new(OutputFormat::Text).format_doctor_report(
+            &[],
+            &[],
+            &[loose("/home/ops/.pmr", true, "0755")],
+            &[loose("/home/ops/.pmr/processes.db", false, "0644")],
+        );
+        assert_eq!(
+            report,
+            "Secrets readable by other users (1), run 'pmr doctor --fix-permissions':\n  /home/ops/.pmr: mode 0755, expected 0700\n\n\
+             Restricted to their owner (1):\n  /home/ops/.pmr/processes.db: mode 0644 -> 0600"
+        );
+
+        let json = Formatter::new(OutputFormat::Json).format_doctor_report(&[], &[], &[loose("/home/ops/.pmr", true, "0755")], &[]);
+        let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(json["loose_permissions"][0]["mode"], "0755");
+        assert_eq!(json["fixed_permissions"], serde_json::json!([]));
+    }
+
+    #[test]
+    fn test_degraded_banner_in_text_and_flag_in_json() {
+        let mut processes = sample();
+        for process in &mut processes {
+            process.degraded = true;
+        }
+        let list = Formatter::new(OutputFormat::Text).format_process_list(&processes);
+        assert!(list.starts_with("WARNING: statuses cannot be stored"), "{}", list);
+        assert!(!Formatter::new(OutputFormat::Text).with_no_header(true).format_process_list(&processes).contains("WARNING"));
+        let status = Formatter::new(OutputFormat::Text).format_process_status(&processes[0]);
+        assert!(status.starts_with("WARNING: statuses cannot be stored"), "{}", status);
+
+        let json: serde_json::Value = serde_json::from_str(&Formatter::new(OutputFormat::Json).format_process_list(&processes)).unwrap();
+        assert_eq!(json["degraded"], true);
+        let json: serde_json::Value = serde_json::from_str(&Formatter::new(OutputFormat::Json).format_process_list(&sample())).unwrap();
+        assert_eq!(json["degraded"], false);
+    }
+
+    #[test]
+    fn test_last_output_under_status() {
+        let mut process = sample().remove(3);
+        process.last_output = vec!["Traceback (most recent call last):".to_string(), "KeyError: 'PORT'".to_string()];
+        let text = Formatter::new(OutputFormat::Text).format_process_status(&process);
+        assert!(text.ends_with("Last Output:\n  Traceback (most recent call last):\n  KeyError: 'PORT'\n"), "{}", text);
+
+        process.last_output_lossy = true;
+        let text = Formatter::new(OutputFormat::Text).with_color(true).format_process_status(&process);
+        assert!(text.contains("Last Output (not valid UTF-8, shown lossily):\n  \x1b[2mTraceback (most recent call last):\x1b[0m\n"), "{}", text);
+
+        let json: serde_json::Value = serde_json::from_str(&Formatter::new(OutputFormat::Json).format_process_status(&process)).unwrap();
+        assert_eq!(json["last_output"][1], "KeyError: 'PORT'");
+        assert!(!Formatter::new(OutputFormat::Text).format_process_status(&sample()[0]).contains("Last Output"));
+    }
+
+    #[test]
+    fn test_ephemeral_marked_in_list_and_status() {
+        let mut processes = sample();
+        processes[1].ephemeral_keep_secs = Some(600);
+        let list = Formatter::new(OutputFormat::Text).format_process_list(&processes);
+        let marked: Vec<&str> = list.lines().filter(|line| line.ends_with(" EPHEMERAL")).collect();
+        assert_eq!(marked.len(), 1);
+
+        let status = Formatter::new(OutputFormat::Text).format_process_status(&processes[1]);
+        assert!(status.contains("Ephemeral: yes, deleted with its logs 10m after it exits\n"), "{}", status);
+        processes[1].delete_after = Some(processes[1].updated_at);
+        let status = Formatter::new(OutputFormat::Text).format_process_status(&processes[1]);
+        assert!(status.contains("Ephemeral: yes, deleted with its logs at "), "{}", status);
+        assert!(!Formatter::new(OutputFormat::Text).format_process_status(&processes[0]).contains("Ephemeral"));
+    }
+
+    #[test]
+    fn test_inferred_status_annotated_in_text_and_flagged_in_json() {
+        let mut process = record("web", ProcessStatus::Running);
+        process.inferred = true;
+        let status = Formatter::new(OutputFormat::Text).format_process_status(&process);
+        assert!(status.contains("Status: running (inferred)\n"), "{}", status);
+        let list = Formatter::new(OutputFormat::Text).format_process_list(std::slice::from_ref(&process));
+        assert_eq!(list.lines().filter(|line| line.ends_with(" INFERRED")).count(), 1);
+        let json: serde_json::Value = serde_json::from_str(&Formatter::new(OutputFormat::Json).format_process_status(&process)).unwrap();
+        assert_eq!(json["inferred"], true);
+
+        process.inferred = false;
+        assert!(Formatter::new(OutputFormat::Text).format_process_status(&process).contains("Status: running\n"));
+    }
+
+    #[test]
+    fn test_status_marks_default_env_vars() {
+        let mut process = record("web", ProcessStatus::Running);
+        process.env_vars = [("TZ", "UTC"), ("APP", "1")].into_iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        process.default_env_keys = vec!["TZ".to_string()];
+
+        let text = Formatter::new(OutputFormat::Text).format_process_status(&process);
+        assert!(text.contains("Environment Variables:\n  APP=1\n  TZ=UTC (default)\n"));
+
+        let json: serde_json::Value = serde_json::from_str(&Formatter::new(OutputFormat::Json).format_process_status(&process)).unwrap();
+        assert_eq!(json["default_env_keys"], serde_json::json!(["TZ"]));
+    }
+
+    #[test]
+    fn test_status_includes_drift() {
+        let mut process = record("web", ProcessStatus::Running);
+        process.drift = Some(DriftReport {
+            process: "web".to_string(),
+            fingerprinted: true,
+            issues: vec![DriftIssue::WorkdirMissing { path: "/srv/web".to_string() }],
+        });
+
+        let text = Formatter::new(OutputFormat::Text).format_process_status(&process);
+        assert!(text.contains("Drift: 1 issue(s)\n  - working directory missing: /srv/web\n"));
+
+        let json = Formatter::new(OutputFormat::Json).format_drift_reports(&[process.drift.clone().unwrap()]);
+        let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(json["reports"][0]["issues"][0]["kind"], "workdir_missing");
+    }
+
+    #[test]
+    fn test_job_list_shows_runs_duration_and_result() {
+        let now = Utc::now();
+        let mut done = record("backup", ProcessStatus::Succeeded);
+        done.kind = ProcessKind::Job;
+        done.runs = 3;
+        done.created_at = now - chrono::Duration::seconds(90);
+        done.updated_at = now - chrono::Duration::seconds(30);
+        let mut broken = record("migrate", ProcessStatus::Failed);
+        broken.kind = ProcessKind::Job;
+        broken.exit_code = Some(2);
+        let run = |seconds: i64| {
+            let ended_at = now - chrono::Duration::seconds(100);
+            ProcessRun::new("backup", ended_at - chrono::Duration::seconds(seconds), ended_at, ProcessStatus::Succeeded, Some(0))
+        };
+        done.recent_runs = vec![run(10), run(20)];
+
+        let text = Formatter::new(OutputFormat::Text).format_job_list(&[done.clone(), broken.clone()], now);
+        let rows: Vec<&str> = text.lines().collect();
+        assert!(rows[0].starts_with("NAME"));
+        assert!(rows[2].starts_with("backup"));
+        assert!(rows[2].contains(" 3 ") && rows[2].contains(" 1m ") && rows[2].trim_end().ends_with("succeeded"));
+        assert!(rows[2].contains(" 15s "), "{}", rows[2]);
+        assert!(rows[3].trim_end().ends_with("failed (exit 2)") && rows[3].contains(" - "), "{}", rows[3]);
+        assert_eq!(Formatter::new(OutputFormat::Text).with_quiet(true).format_job_list(&[done.clone()], now), "backup");
+
+        let json = Formatter::new(OutputFormat::Json).format_job_list(&[done], now);
+        let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(json["jobs"][0]["runs"], 3);
+        assert_eq!(json["jobs"][0]["duration_ms"], 60_000);
+        assert_eq!(json["jobs"][0]["status"], "Succeeded");
+        assert_eq!(json["jobs"][0]["average_ms"], 15_000);
+    }
+
+    #[test]
+    fn test_run_history_lists_durations_and_status_shows_the_last_run() {
+        let now = Utc::now();
+        let run = |seconds: i64, status: ProcessStatus, exit_code: Option<i32>| {
+            ProcessRun::new("backup", now - chrono::Duration::seconds(seconds), now, status, exit_code)
+        };
+        let runs = vec![run(30, ProcessStatus::Succeeded, Some(0)), run(90, ProcessStatus::Failed, Some(3))];
+
+        let text = Formatter::new(OutputFormat::Text).format_run_history(&runs, "backup");
+        let rows: Vec<&str> = text.lines().collect();
+        assert!(rows[0].starts_with("STARTED AT"));
+        assert!(rows[2].contains(" 30s ") && rows[2].ends_with("succeeded"), "{}", rows[2]);
+        assert!(rows[3].contains(" 1m 30s ") && rows[3].ends_with("failed (exit 3)"), "{}", rows[3]);
+        assert_eq!(rows[4], "Average: 1m over 2 run(s)");
+        assert_eq!(
+            Formatter::new(OutputFormat::Text).format_run_history(&[], "backup"),
+            "No finished runs recorded for process 'backup'"
+        );
+
+        let json = Formatter::new(OutputFormat::Json).format_run_history(&runs, "backup");
+        let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(json["average_duration_ms"], 60_000);
+        assert_eq!(json["runs"][1]["duration_ms"], 90_000);
+
+        let mut process = record("backup", ProcessStatus::Failed);
+        process.recent_runs = runs;
+        let status = Formatter::new(OutputFormat::Text).format_process_status(&process);
+        assert!(status.contains("Last Run: 1m 30s (failed (exit 3)), average 1m over the last 2 run(s)"), "{}", status);
+    }
+}
@@ -0,0 +1,96 @@
+//! `pmr inspect`: everything pmr knows about one process in a single JSON document, for
+//! support tickets. The record is included as stored, with its environment unredacted, so
+//! the API serves the report to admin tokens only.
+
+use crate::cgroup::CgroupStats;
+use crate::config::ConfigPaths;
+use crate::database::{LogRotationEntry, ProcessNote, ProcessRecord, ProcessRun, ProcessStatus, WatchRestartEntry, SCHEMA_VERSION};
+use crate::drift::DriftReport;
+use crate::flapping::FailureHistory;
+use crate::process::{ResourceUsage, RotatedLog};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// The full state of one process, see [`crate::process::ProcessManager::inspect`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "http-api", derive(utoipa::ToSchema))]
+pub struct InspectReport {
+    pub generated_at: DateTime<Utc>,
+    /// The record as stored before this report probed anything, every field included
+    pub record: ProcessRecord,
+    pub liveness: Liveness,
+    /// Memory and threads of the live process; None when it is not running
+    pub resources: Option<ResourceUsage>,
+    /// Usage of the process's cgroup; None without cgroup limits
+    pub cgroup: Option<CgroupStats>,
+    pub drift: DriftReport,
+    pub logs: InspectLogs,
+    pub history: InspectHistory,
+    pub notes: Vec<ProcessNote>,
+    pub environment: InspectEnvironment,
+}
+
+/// What probing the PID found
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "http-api", derive(utoipa::ToSchema))]
+pub struct Liveness {
+    pub pid: Option<u32>,
+    pub stored_status: ProcessStatus,
+    /// The status after refreshing it against the PID, as `pmr status` shows it
+    pub status: ProcessStatus,
+    /// A process with the PID exists
+    pub pid_alive: bool,
+    /// The process under the PID started no earlier than the record, so it is not a
+    /// recycled PID
+    pub same_process: bool,
+    pub flapping: bool,
+}
+
+/// The live log and its archives
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "http-api", derive(utoipa::ToSchema))]
+pub struct InspectLogs {
+    pub log_path: String,
+    /// None when the live log does not exist
+    pub live_bytes: Option<u64>,
+    pub rotated_bytes: u64,
+    /// Newest first
+    pub rotated: Vec<RotatedLog>,
+    /// Oldest first
+    pub rotations: Vec<LogRotationEntry>,
+}
+
+/// Stored histories of the process
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "http-api", derive(utoipa::ToSchema))]
+pub struct InspectHistory {
+    pub failures: FailureHistory,
+    /// Oldest first
+    pub watch_restarts: Vec<WatchRestartEntry>,
+    /// Finished runs kept, oldest first
+    #[serde(default)]
+    pub runs: Vec<ProcessRun>,
+}
+
+/// The pmr installation that produced the report
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "http-api", derive(utoipa::ToSchema))]
+pub struct InspectEnvironment {
+    pub pmr_version: String,
+    pub schema_version: i64,
+    pub paths: ConfigPaths,
+    pub read_only: bool,
+    pub degraded: bool,
+}
+
+impl InspectEnvironment {
+    pub fn new(paths: ConfigPaths, read_only: bool, degraded: bool) -> Self {
+        Self {
+            pmr_version: env!("CARGO_PKG_VERSION").to_string(),
+            schema_version: SCHEMA_VERSION,
+            paths,
+            read_only,
+            degraded,
+        }
+    }
+}
@@ -1,16 +1,65 @@
+pub mod affinity;
+pub mod archive;
+#[cfg(feature = "cli")]
+pub mod batch;
+pub mod cancel;
+pub mod cgroup;
+#[cfg(feature = "cli")]
 pub mod cli;
+pub mod clock;
 pub mod config;
+#[cfg(feature = "cli")]
+pub mod confirm;
 pub mod database;
+pub mod drift;
+pub mod duration;
+pub mod env;
 pub mod error;
+pub mod events;
+pub mod file_pool;
+pub mod file_watch;
+pub mod flapping;
+pub mod format;
+#[cfg(feature = "cli")]
 pub mod formatter;
+pub mod inspect;
+pub mod limits;
+pub mod log_index;
 pub mod log_rotation;
+pub mod logs_quota;
+pub mod messages;
+pub mod name;
+pub mod notify;
+pub mod outcome;
+pub mod permissions;
+pub mod pid_probe;
+#[cfg(feature = "cli")]
+pub mod porcelain;
+pub mod prelude;
 pub mod process;
+pub mod profiles;
+pub mod readiness;
+pub mod resolution;
+pub mod retry_fs;
+pub mod snapshot;
+pub mod start_queue;
+pub mod storage;
+pub mod suggest;
+pub mod systemd;
+#[cfg(feature = "cli")]
+pub mod text_width;
+pub mod timings;
+pub mod umask;
+#[cfg(feature = "cli")]
+pub mod watch;
 
 #[cfg(feature = "http-api")]
 pub mod api {
+    pub mod access_log;
     pub mod auth;
     pub mod docs;
     pub mod handlers;
+    pub mod log_broker;
     pub mod server;
 
     pub use auth::AuthManager;
@@ -0,0 +1,195 @@
+//! Keeping huge arguments and environments out of pmr: starts over the configured
+//! [`StartLimitsConfig`] are refused, and records stored before the limits existed have
+//! their long values shortened for display so `pmr list` and `pmr status` stay readable.
+//! Descriptions have a fixed cap of their own, [`MAX_DESCRIPTION_CHARS`].
+
+use crate::config::StartLimitsConfig;
+use crate::database::ProcessRecord;
+use crate::format::human_bytes;
+use crate::{Error, Result};
+use std::collections::HashMap;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Characters of a value shown before it is cut off for display; a character with its
+/// combining marks counts once and is never split
+pub const DISPLAY_VALUE_MAX_CHARS: usize = 200;
+
+/// Longest description of a process, in characters
+pub const MAX_DESCRIPTION_CHARS: usize = 256;
+
+/// A description as it is stored: trimmed, and None when blank, which clears it. Refused
+/// over [`MAX_DESCRIPTION_CHARS`] or with line breaks or other control characters, which
+/// would break the rows of `pmr list --wide`.
+pub fn clean_description(description: &str) -> Result<Option<String>> {
+    let description = description.trim();
+    let chars = description.chars().count();
+    if chars > MAX_DESCRIPTION_CHARS {
+        return Err(Error::InvalidDescription(format!(
+            "it is {} characters long, over the limit of {}",
+            chars, MAX_DESCRIPTION_CHARS
+        )));
+    }
+    if description.chars().any(char::is_control) {
+        return Err(Error::InvalidDescription("line breaks and other control characters are not allowed".to_string()));
+    }
+    Ok((!description.is_empty()).then(|| description.to_string()))
+}
+
+/// Refuse arguments or an environment over `limits`, measured as the database stores them
+pub fn check_start_size(limits: &StartLimitsConfig, args: &[String], env_vars: &HashMap<String, String>) -> Result<()> {
+    let over = |limit: u64, size: usize| limit > 0 && size as u64 > limit;
+    let too_large = |what: String, size: usize, limit: u64, key: &str| {
+        Error::StartTooLarge(format!(
+            "{} {}, over the {} limit (start_limits.{})",
+            what,
+            human_bytes(size as u64),
+            human_bytes(limit),
+            key
+        ))
+    };
+
+    let args_size = serde_json::to_string(args)?.len();
+    if over(limits.max_args_bytes, args_size) {
+        return Err(too_large("the arguments take".to_string(), args_size, limits.max_args_bytes, "max_args_bytes"));
+    }
+
+    let mut keys: Vec<&String> = env_vars.keys().collect();
+    keys.sort();
+    for key in keys {
+        let size = env_vars[key].len();
+        if over(limits.max_env_value_bytes, size) {
+            return Err(too_large(format!("environment variable '{}' is", key), size, limits.max_env_value_bytes, "max_env_value_bytes"));
+        }
+    }
+
+    let env_size = serde_json::to_string(env_vars)?.len();
+    if over(limits.max_env_bytes, env_size) {
+        return Err(too_large("the environment takes".to_string(), env_size, limits.max_env_bytes, "max_env_bytes"));
+    }
+    Ok(())
+}
+
+/// Shorten the command, arguments and environment values of `process` that are longer than
+/// [`DISPLAY_VALUE_MAX_CHARS`], listing what was shortened in `truncated_fields`
+pub fn truncate_for_display(process: &mut ProcessRecord) {
+    let mut truncated = Vec::new();
+    if let Some(short) = shortened(&process.command) {
+        process.command = short;
+        truncated.push("command".to_string());
+    }
+    for (index, arg) in process.args.iter_mut().enumerate() {
+        if let Some(short) = shortened(arg) {
+            *arg = short;
+            truncated.push(format!("args[{}]", index));
+        }
+    }
+    let mut env_keys = Vec::new();
+    for (key, value) in process.env_vars.iter_mut() {
+        if let Some(short) = shortened(value) {
+            *value = short;
+            env_keys.push(format!("env_vars.{}", key));
+        }
+    }
+    env_keys.sort();
+    truncated.extend(env_keys);
+    process.truncated_fields = truncated;
+}
+
+/// `value` cut to [`DISPLAY_VALUE_MAX_CHARS`] with an ellipsis and its full size, or None
+/// when it is short enough to show whole
+fn shortened(value: &str) -> Option<String> {
+    let (cut, _) = value.grapheme_indices(true).nth(DISPLAY_VALUE_MAX_CHARS)?;
+    Some(format!("{}… ({} in full)", &value[..cut], human_bytes(value.len() as u64)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits(max_args_bytes: u64, max_env_value_bytes: u64, max_env_bytes: u64) -> StartLimitsConfig {
+        StartLimitsConfig { max_args_bytes, max_env_value_bytes, max_env_bytes }
+    }
+
+    fn env(pairs: &[(&str, String)]) -> HashMap<String, String> {
+        pairs.iter().map(|(key, value)| (key.to_string(), value.clone())).collect()
+    }
+
+    #[test]
+    fn test_check_start_size_limits() {
+        let defaults = StartLimitsConfig::default();
+        assert!(check_start_size(&defaults, &["--port".to_string(), "80".to_string()], &env(&[("A", "b".to_string())])).is_ok());
+
+        // ["xxxx"] is the stored form, two quotes and two brackets over the argument
+        let args = vec!["x".repeat(96)];
+        assert!(check_start_size(&limits(100, 0, 0), &args, &HashMap::new()).is_ok());
+        let err = check_start_size(&limits(99, 0, 0), &args, &HashMap::new()).unwrap_err();
+        assert!(matches!(err, Error::StartTooLarge(_)));
+        assert!(err.to_string().contains("the arguments take 100 B, over the 99 B limit (start_limits.max_args_bytes)"), "{}", err);
+
+        let blob = env(&[("SMALL", "1".to_string()), ("BLOB", "b".repeat(2 << 20))]);
+        let err = check_start_size(&defaults, &[], &blob).unwrap_err();
+        assert!(err.to_string().contains("environment variable 'BLOB' is 2.0 MiB, over the 32.0 KiB limit"), "{}", err);
+        assert!(err.to_string().contains("put large data in a file"), "{}", err);
+
+        let many = env(&[("A", "a".repeat(20_000)), ("B", "b".repeat(20_000))]);
+        assert!(check_start_size(&limits(0, 32 * 1024, 0), &[], &many).is_ok());
+        let err = check_start_size(&limits(0, 32 * 1024, 30_000), &[], &many).unwrap_err();
+        assert!(err.to_string().contains("(start_limits.max_env_bytes)"), "{}", err);
+
+        // 0 turns every limit off
+        assert!(check_start_size(&limits(0, 0, 0), &[], &blob).is_ok());
+    }
+
+    #[test]
+    fn test_clean_description() {
+        assert_eq!(clean_description("  Serves the public API  ").unwrap().as_deref(), Some("Serves the public API"));
+        assert_eq!(clean_description(" ").unwrap(), None);
+        let longest = "é".repeat(MAX_DESCRIPTION_CHARS);
+        assert_eq!(clean_description(&longest).unwrap(), Some(longest));
+
+        let err = clean_description(&"x".repeat(MAX_DESCRIPTION_CHARS + 1)).unwrap_err();
+        assert!(matches!(err, Error::InvalidDescription(_)));
+        assert_eq!(err.to_string(), "Invalid description: it is 257 characters long, over the limit of 256");
+        let err = clean_description("first line\nsecond line").unwrap_err();
+        assert!(err.to_string().contains("control characters"), "{}", err);
+    }
+
+    #[test]
+    fn test_truncate_for_display() {
+        let mut process: ProcessRecord = serde_json::from_value(serde_json::json!({
+            "id": "blob",
+            "name": "blob",
+            "command": "server",
+            "args": ["--short", "é".repeat(300)],
+            "env_vars": { "BLOB": "b".repeat(2 << 20), "PORT": "80" },
+            "working_dir": "/srv",
+            "pid": null,
+            "status": "Stopped",
+            "created_at": "2026-10-18T09:30:00Z",
+            "updated_at": "2026-10-18T09:30:00Z",
+            "log_path": "/srv/blob.log",
+        }))
+        .unwrap();
+        truncate_for_display(&mut process);
+
+        assert_eq!(process.truncated_fields, ["args[1]", "env_vars.BLOB"]);
+        assert_eq!(process.args[0], "--short");
+        assert_eq!(process.args[1], format!("{}… (600 B in full)", "é".repeat(DISPLAY_VALUE_MAX_CHARS)));
+        assert_eq!(process.env_vars["BLOB"], format!("{}… (2.0 MiB in full)", "b".repeat(DISPLAY_VALUE_MAX_CHARS)));
+        assert_eq!(process.env_vars["PORT"], "80");
+        assert_eq!(process.command, "server");
+
+        // Combining marks stay with their letter
+        let mut combining = process.clone();
+        combining.args = vec!["e\u{301}".repeat(DISPLAY_VALUE_MAX_CHARS + 1)];
+        truncate_for_display(&mut combining);
+        assert!(combining.args[0].starts_with(&format!("{}…", "e\u{301}".repeat(DISPLAY_VALUE_MAX_CHARS))), "{}", combining.args[0]);
+
+        // A value of exactly the limit is shown whole
+        let mut exact = process.clone();
+        exact.args = vec!["y".repeat(DISPLAY_VALUE_MAX_CHARS)];
+        exact.env_vars.clear();
+        truncate_for_display(&mut exact);
+        assert!(exact.truncated_fields.is_empty());
+    }
+}
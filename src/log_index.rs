@@ -0,0 +1,482 @@
+//! Optional index of a live log, kept next to it as `<name>.log.idx`, so reading the lines
+//! since a time seeks close to them instead of scanning the file from its start. A log has
+//! one once `pmr logs <name> --reindex` builds it; reads keep it up to date after that.
+//!
+//! The index is advisory: one that is missing, corrupt, of another version or for a log that
+//! was truncated or replaced is ignored, and the log is scanned as if there were none.
+//!
+//! Times come from the lines themselves: a line whose first word, optionally in brackets, is
+//! an RFC 3339 time has that time, and a line without one has the time of the line before
+//! it. Seeking assumes the times in a log do not go backwards.
+//!
+//! Format, little-endian: the magic `PMRIDX` and a version byte; the stride, the indexed
+//! length of the log, its line count, a hash of its first bytes and the time of its last
+//! timestamped line; the entries, each a byte offset and the time carried into that line;
+//! and a checksum of everything before it.
+
+use crate::duration::HumanDuration;
+use crate::{Error, Result};
+use chrono::{DateTime, Utc};
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// Version written after the magic bytes; indexes of other versions are ignored
+pub const INDEX_VERSION: u8 = 1;
+
+/// Lines from one index entry to the next
+pub const DEFAULT_STRIDE: u32 = 256;
+
+const MAGIC: &[u8; 6] = b"PMRIDX";
+
+/// Bytes at the start of the log hashed into the index, to notice a log replaced by another
+const HEAD_BYTES: u64 = 256;
+
+/// Stands for "no time yet" in the file
+const NO_TIME: i64 = i64::MIN;
+
+const HEADER_LEN: usize = MAGIC.len() + 1 + 4 + 8 * 5;
+const ENTRY_LEN: usize = 16;
+
+/// Path of the index of the log at `log_path`
+pub fn index_path(log_path: &Path) -> PathBuf {
+    let mut path = log_path.as_os_str().to_owned();
+    path.push(".idx");
+    PathBuf::from(path)
+}
+
+/// A line of the log reading can start at
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct IndexEntry {
+    /// Byte offset of the line
+    pub offset: u64,
+    /// Time of the last timestamped line before it, in milliseconds since the epoch
+    pub carried_ms: Option<i64>,
+}
+
+/// Offsets of every `stride`th line of a log, as far as it has been indexed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogIndex {
+    stride: u32,
+    indexed_len: u64,
+    lines: u64,
+    head_hash: u64,
+    last_ms: Option<i64>,
+    entries: Vec<IndexEntry>,
+}
+
+impl LogIndex {
+    /// An index of nothing yet, with an entry every `stride` lines
+    pub fn empty(stride: u32) -> Self {
+        Self {
+            stride: stride.max(1),
+            indexed_len: 0,
+            lines: 0,
+            head_hash: fnv1a(&[]),
+            last_ms: None,
+            entries: Vec::new(),
+        }
+    }
+
+    /// Index the whole log at `log_path`
+    pub fn build(log_path: &Path, stride: u32) -> io::Result<Self> {
+        let mut index = Self::empty(stride);
+        index.extend(log_path)?;
+        Ok(index)
+    }
+
+    pub fn stride(&self) -> u32 {
+        self.stride
+    }
+
+    /// Bytes of the log covered, always ending at a line break
+    pub fn indexed_len(&self) -> u64 {
+        self.indexed_len
+    }
+
+    pub fn lines(&self) -> u64 {
+        self.lines
+    }
+
+    pub fn entries(&self) -> &[IndexEntry] {
+        &self.entries
+    }
+
+    /// Index the whole lines written to the log since it was last indexed; false when
+    /// there were none
+    pub fn extend(&mut self, log_path: &Path) -> io::Result<bool> {
+        let mut file = fs::File::open(log_path)?;
+        let len = file.metadata()?.len();
+        if len <= self.indexed_len {
+            return Ok(false);
+        }
+        file.seek(SeekFrom::Start(self.indexed_len))?;
+        // Bounded by the length seen, however much is appended meanwhile
+        let mut reader = BufReader::new(file.take(len - self.indexed_len));
+        let mut line = Vec::new();
+        let grown = self.indexed_len;
+        loop {
+            line.clear();
+            let read = reader.read_until(b'\n', &mut line)?;
+            // A line still being written is indexed once it is finished
+            if read == 0 || line.last() != Some(&b'\n') {
+                break;
+            }
+            if self.lines.is_multiple_of(self.stride as u64) {
+                self.entries.push(IndexEntry { offset: self.indexed_len, carried_ms: self.last_ms });
+            }
+            if let Some(ms) = line_time_ms(&line) {
+                self.last_ms = Some(ms);
+            }
+            self.indexed_len += read as u64;
+            self.lines += 1;
+        }
+        if grown < HEAD_BYTES {
+            self.head_hash = fnv1a(&read_head(log_path, self.indexed_len)?);
+        }
+        Ok(self.indexed_len > grown)
+    }
+
+    /// The index of the log at `log_path`, unless there is none or it cannot be trusted:
+    /// unreadable, corrupt, of another version, or for a log that has since been truncated
+    /// or replaced
+    pub fn load(log_path: &Path) -> Option<Self> {
+        let index = Self::decode(&fs::read(index_path(log_path)).ok()?)?;
+        let len = fs::metadata(log_path).ok()?.len();
+        if len < index.indexed_len || fnv1a(&read_head(log_path, index.indexed_len).ok()?) != index.head_hash {
+            return None;
+        }
+        Some(index)
+    }
+
+    /// Write the index next to the log, replacing the previous one in a single rename
+    pub fn write(&self, log_path: &Path) -> io::Result<()> {
+        let path = index_path(log_path);
+        let mut temp = path.clone().into_os_string();
+        temp.push(".tmp");
+        fs::write(&temp, self.encode())?;
+        fs::rename(&temp, &path)
+    }
+
+    /// Start the index of a log over after the log was rotated or truncated, keeping its
+    /// stride. Logs without an index are left without one.
+    pub fn restart(log_path: &Path) -> io::Result<()> {
+        let path = index_path(log_path);
+        let stride = match fs::read(&path) {
+            Ok(bytes) => Self::decode(&bytes).map_or(DEFAULT_STRIDE, |index| index.stride),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        Self::empty(stride).write(log_path)
+    }
+
+    /// Where to start reading the lines timed at or after `since_ms`: the last entry all
+    /// lines before which are older
+    pub fn seek(&self, since_ms: i64) -> IndexEntry {
+        let older = self.entries.partition_point(|entry| entry.carried_ms.is_none_or(|ms| ms < since_ms));
+        older.checked_sub(1).map(|i| self.entries[i]).unwrap_or_default()
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_LEN + self.entries.len() * ENTRY_LEN + 8);
+        bytes.extend_from_slice(MAGIC);
+        bytes.push(INDEX_VERSION);
+        bytes.extend_from_slice(&self.stride.to_le_bytes());
+        bytes.extend_from_slice(&self.indexed_len.to_le_bytes());
+        bytes.extend_from_slice(&self.lines.to_le_bytes());
+        bytes.extend_from_slice(&self.head_hash.to_le_bytes());
+        bytes.extend_from_slice(&self.last_ms.unwrap_or(NO_TIME).to_le_bytes());
+        bytes.extend_from_slice(&(self.entries.len() as u64).to_le_bytes());
+        for entry in &self.entries {
+            bytes.extend_from_slice(&entry.offset.to_le_bytes());
+            bytes.extend_from_slice(&entry.carried_ms.unwrap_or(NO_TIME).to_le_bytes());
+        }
+        let checksum = fnv1a(&bytes);
+        bytes.extend_from_slice(&checksum.to_le_bytes());
+        bytes
+    }
+
+    /// Parse an index, None for anything that is not a well-formed index of this version
+    fn decode(bytes: &[u8]) -> Option<Self> {
+        let (body, checksum) = bytes.split_at_checked(bytes.len().checked_sub(8)?)?;
+        if bytes.len() < HEADER_LEN + 8 || !body.starts_with(MAGIC) || body[MAGIC.len()] != INDEX_VERSION {
+            return None;
+        }
+        if fnv1a(body) != u64::from_le_bytes(checksum.try_into().ok()?) {
+            return None;
+        }
+        let mut fields = Fields { bytes: body, at: MAGIC.len() + 1 };
+        let stride = u32::from_le_bytes(fields.take()?);
+        let indexed_len = fields.u64()?;
+        let lines = fields.u64()?;
+        let head_hash = fields.u64()?;
+        let last_ms = time(fields.u64()? as i64);
+        let count = fields.u64()?;
+        if stride == 0 || (body.len() - HEADER_LEN) as u64 != count.checked_mul(ENTRY_LEN as u64)? {
+            return None;
+        }
+        let mut entries = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let entry = IndexEntry { offset: fields.u64()?, carried_ms: time(fields.u64()? as i64) };
+            // Offsets only grow and stay within the indexed part of the log
+            if entry.offset >= indexed_len || entries.last().is_some_and(|last: &IndexEntry| last.offset >= entry.offset) {
+                return None;
+            }
+            entries.push(entry);
+        }
+        Some(Self { stride, indexed_len, lines, head_hash, last_ms, entries })
+    }
+}
+
+/// Reads the fixed-size fields of an index in order
+struct Fields<'a> {
+    bytes: &'a [u8],
+    at: usize,
+}
+
+impl Fields<'_> {
+    fn take<const N: usize>(&mut self) -> Option<[u8; N]> {
+        let field = self.bytes.get(self.at..self.at + N)?.try_into().ok()?;
+        self.at += N;
+        Some(field)
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        self.take().map(u64::from_le_bytes)
+    }
+}
+
+fn time(ms: i64) -> Option<i64> {
+    (ms != NO_TIME).then_some(ms)
+}
+
+/// The first bytes of the log, up to `indexed_len`, that the index hashes
+fn read_head(log_path: &Path, indexed_len: u64) -> io::Result<Vec<u8>> {
+    let mut head = Vec::new();
+    fs::File::open(log_path)?.take(indexed_len.min(HEAD_BYTES)).read_to_end(&mut head)?;
+    Ok(head)
+}
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| (hash ^ *byte as u64).wrapping_mul(0x0100_0000_01b3))
+}
+
+/// Time of a line starting with an RFC 3339 time, bare or in brackets
+pub fn line_time_ms(line: &[u8]) -> Option<i64> {
+    let word = line.split(|b| b.is_ascii_whitespace()).next()?;
+    let word = word.strip_prefix(b"[").unwrap_or(word);
+    let word = word.strip_suffix(b"]").unwrap_or(word);
+    // Longer than any RFC 3339 time, so long first words are not parsed
+    if word.len() > 40 {
+        return None;
+    }
+    let time = DateTime::parse_from_rfc3339(std::str::from_utf8(word).ok()?).ok()?;
+    Some(time.timestamp_millis())
+}
+
+/// Lines read since a time
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SinceRead {
+    /// The lines timed at or after the time, as they are in the file
+    pub content: Vec<u8>,
+    /// Where in the file reading started: 0 unless the index let it skip ahead
+    pub start_offset: u64,
+    /// Bytes of the file read
+    pub bytes_scanned: u64,
+}
+
+/// Read the lines of the log at `log_path` timed at or after `since`. With an index the
+/// read starts at the last entry before them; without one the whole file is scanned.
+pub fn read_since(log_path: &Path, since: DateTime<Utc>, index: Option<&LogIndex>) -> io::Result<SinceRead> {
+    let since_ms = since.timestamp_millis();
+    let start = index.map(|index| index.seek(since_ms)).unwrap_or_default();
+    let mut file = fs::File::open(log_path)?;
+    let len = file.metadata()?.len();
+    file.seek(SeekFrom::Start(start.offset))?;
+    let mut reader = BufReader::new(file.take(len.saturating_sub(start.offset)));
+
+    let mut content = Vec::new();
+    let mut line = Vec::new();
+    let mut carried = start.carried_ms;
+    let mut bytes_scanned = 0;
+    loop {
+        line.clear();
+        let read = reader.read_until(b'\n', &mut line)?;
+        if read == 0 {
+            break;
+        }
+        bytes_scanned += read as u64;
+        if let Some(ms) = line_time_ms(&line) {
+            carried = Some(ms);
+        }
+        if carried.is_some_and(|ms| ms >= since_ms) {
+            content.extend_from_slice(&line);
+        }
+    }
+    Ok(SinceRead { content, start_offset: start.offset, bytes_scanned })
+}
+
+/// Start of a time-filtered log read, as written on the command line: an RFC 3339 time,
+/// or a duration back from now such as `30m`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogSince {
+    At(DateTime<Utc>),
+    Ago(HumanDuration),
+}
+
+impl LogSince {
+    /// The time meant, with durations counted back from `now`
+    pub fn resolve(&self, now: DateTime<Utc>) -> DateTime<Utc> {
+        match self {
+            LogSince::At(time) => *time,
+            LogSince::Ago(duration) => now - duration.as_duration(),
+        }
+    }
+}
+
+impl FromStr for LogSince {
+    type Err = Error;
+
+    fn from_str(input: &str) -> Result<Self> {
+        if let Ok(time) = DateTime::parse_from_rfc3339(input.trim()) {
+            return Ok(LogSince::At(time.with_timezone(&Utc)));
+        }
+        input.parse().map(LogSince::Ago).map_err(|_| {
+            Error::InvalidDuration(format!("'{}': expected a time like 2024-05-01T12:00:00Z or a duration like 30m", input))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::TempDir;
+
+    fn at(secs: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(secs, 0).unwrap()
+    }
+
+    /// A log of `count` seconds, each a timestamped line and an untimed continuation line
+    fn write_log(path: &Path, count: i64) {
+        let mut file = fs::File::create(path).unwrap();
+        for secs in 0..count {
+            writeln!(file, "[{}] request {} served", at(secs).to_rfc3339(), secs).unwrap();
+            writeln!(file, "    detail of {}", secs).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_line_times() {
+        assert_eq!(line_time_ms(b"2024-05-01T12:00:00Z started\n"), Some(1_714_564_800_000));
+        assert_eq!(line_time_ms(b"[2024-05-01T14:00:00.250+02:00] started"), Some(1_714_564_800_250));
+        for line in [&b"started at 2024-05-01T12:00:00Z"[..], b"", b"\n", b"[2024-05-01] started"] {
+            assert_eq!(line_time_ms(line), None);
+        }
+    }
+
+    #[test]
+    fn test_since_parses_times_and_durations() {
+        let now = at(10_000);
+        assert_eq!("1970-01-01T00:00:10Z".parse::<LogSince>().unwrap().resolve(now), at(10));
+        assert_eq!("30m".parse::<LogSince>().unwrap().resolve(now), at(10_000 - 1800));
+        let err = "yesterday".parse::<LogSince>().unwrap_err();
+        assert!(matches!(err, Error::InvalidDuration(_)), "{}", err);
+    }
+
+    #[test]
+    fn test_indexed_since_reads_a_fraction_of_a_large_log() {
+        let dir = TempDir::new().unwrap();
+        let log = dir.path().join("web.log");
+        write_log(&log, 50_000);
+        let len = fs::metadata(&log).unwrap().len();
+
+        let scanned = read_since(&log, at(49_000), None).unwrap();
+        assert_eq!(scanned.bytes_scanned, len);
+        LogIndex::build(&log, DEFAULT_STRIDE).unwrap().write(&log).unwrap();
+        let index = LogIndex::load(&log).unwrap();
+        assert_eq!((index.lines(), index.indexed_len(), index.entries().len()), (100_000, len, 391));
+        let indexed = read_since(&log, at(49_000), Some(&index)).unwrap();
+
+        // Same lines, from a read of about the last 2% of the file instead of all of it
+        assert_eq!(indexed.content, scanned.content);
+        assert!(String::from_utf8_lossy(&indexed.content).starts_with("[1970-01-01T13:36:40+00:00] request 49000 served\n"));
+        assert!(indexed.bytes_scanned * 20 < len, "read {} of {} bytes", indexed.bytes_scanned, len);
+        assert!(indexed.start_offset > 0);
+
+        // Before everything: the whole file from the start; after everything: only the tail
+        assert_eq!(read_since(&log, at(-5), Some(&index)).unwrap().content, fs::read(&log).unwrap());
+        let after = read_since(&log, at(60_000), Some(&index)).unwrap();
+        assert!(after.content.is_empty());
+        assert!(after.bytes_scanned < 16 * 1024);
+    }
+
+    #[test]
+    fn test_index_extends_with_whole_lines() {
+        let dir = TempDir::new().unwrap();
+        let log = dir.path().join("web.log");
+        fs::write(&log, "2024-05-01T12:00:00Z one\nno time\n2024-05-01T12:00:01Z par").unwrap();
+        let mut index = LogIndex::build(&log, 2).unwrap();
+        assert_eq!((index.lines(), index.indexed_len()), (2, 33));
+        assert_eq!(index.entries(), &[IndexEntry { offset: 0, carried_ms: None }]);
+
+        fs::OpenOptions::new().append(true).open(&log).unwrap().write_all(b"tial\n2024-05-01T12:00:02Z three\n").unwrap();
+        assert!(index.extend(&log).unwrap());
+        assert!(!index.extend(&log).unwrap());
+        assert_eq!(index, LogIndex::build(&log, 2).unwrap());
+        assert_eq!(index.entries()[1], IndexEntry { offset: 33, carried_ms: Some(1_714_564_800_000) });
+        index.write(&log).unwrap();
+        assert_eq!(LogIndex::load(&log), Some(index));
+    }
+
+    #[test]
+    fn test_untrusted_indexes_are_ignored() {
+        let dir = TempDir::new().unwrap();
+        let log = dir.path().join("web.log");
+        write_log(&log, 2_000);
+        assert_eq!(LogIndex::load(&log), None);
+        let index = LogIndex::build(&log, 16).unwrap();
+        index.write(&log).unwrap();
+        let good = fs::read(index_path(&log)).unwrap();
+
+        // A flipped byte anywhere, a short file, another version or magic
+        let mut corrupt = good.clone();
+        corrupt[HEADER_LEN + 20] ^= 1;
+        let mut version = good.clone();
+        version[MAGIC.len()] = INDEX_VERSION + 1;
+        for bytes in [corrupt, good[..good.len() - 1].to_vec(), good[..10].to_vec(), version, b"garbage".to_vec(), Vec::new()] {
+            fs::write(index_path(&log), bytes).unwrap();
+            assert_eq!(LogIndex::load(&log), None);
+        }
+
+        // A log truncated below, or rewritten within, what was indexed
+        fs::write(index_path(&log), &good).unwrap();
+        assert!(LogIndex::load(&log).is_some());
+        let content = fs::read(&log).unwrap();
+        fs::write(&log, &content[..content.len() / 2]).unwrap();
+        assert_eq!(LogIndex::load(&log), None);
+        let mut rewritten = content.clone();
+        rewritten[3] = b'9';
+        fs::write(&log, &rewritten).unwrap();
+        assert_eq!(LogIndex::load(&log), None);
+    }
+
+    #[test]
+    fn test_restart_keeps_the_stride_of_an_index() {
+        let dir = TempDir::new().unwrap();
+        let log = dir.path().join("web.log");
+        write_log(&log, 100);
+        LogIndex::restart(&log).unwrap();
+        assert!(!index_path(&log).exists());
+
+        LogIndex::build(&log, 16).unwrap().write(&log).unwrap();
+        fs::write(&log, "").unwrap();
+        assert_eq!(LogIndex::load(&log), None);
+        LogIndex::restart(&log).unwrap();
+        assert_eq!(LogIndex::load(&log), Some(LogIndex::empty(16)));
+        fs::write(index_path(&log), "garbage").unwrap();
+        LogIndex::restart(&log).unwrap();
+        assert_eq!(LogIndex::load(&log), Some(LogIndex::empty(DEFAULT_STRIDE)));
+    }
+}
@@ -2,50 +2,151 @@ use std::path::{Path, PathBuf};
 use std::fs;
 use crate::{Result, Error};
 use crate::config::LogRotationConfig;
+use crate::log_index::LogIndex;
+use crate::retry_fs::RetryingFs;
+
+/// What one rotation did to the files on disk
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RotationOutcome {
+    /// Size of the live log when it was rotated
+    pub rotated_from_size: u64,
+    /// Existing archives moved up one number
+    pub archives_shifted: usize,
+    /// Archives removed because they were past `max_files`
+    pub archives_deleted: Vec<PathBuf>,
+}
+
+/// Renames done by one rotation, kept so a failed start can put the files back
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RotationRecord {
+    renames: Vec<(PathBuf, PathBuf)>,
+    outcome: RotationOutcome,
+}
+
+impl RotationRecord {
+    pub fn outcome(&self) -> &RotationOutcome {
+        &self.outcome
+    }
+
+    /// Move every file back to where it was before the rotation, last rename first.
+    /// An archive the rotation deleted at the `max_files` limit cannot be recovered.
+    pub fn undo(&self) -> Result<()> {
+        self.undo_with(&RetryingFs::default())
+    }
+
+    /// [`Self::undo`] with the moves retried by `files`
+    pub fn undo_with(&self, files: &RetryingFs) -> Result<()> {
+        for (from, to) in self.renames.iter().rev() {
+            files.retry("rename", to, || move_file(rename_file, to, from))?;
+        }
+        Ok(())
+    }
+}
+
+/// An archive of a log found next to it on disk
+#[derive(Debug, Clone, PartialEq)]
+pub struct RotatedFile {
+    /// `N` in `<name>.<N>.log`; 1 is the newest archive
+    pub index: usize,
+    pub path: PathBuf,
+    /// Whether this is the `.log.gz` form of the archive
+    pub compressed: bool,
+    pub size: u64,
+    pub modified: std::time::SystemTime,
+}
+
+/// Renames a file; replaced in tests to simulate logs on another filesystem
+type RenameFn = fn(&Path, &Path) -> std::io::Result<()>;
+
+fn rename_file(from: &Path, to: &Path) -> std::io::Result<()> {
+    fs::rename(from, to)
+}
+
+/// Move `from` to `to`. A rename fails with EXDEV across filesystems, which a log directory
+/// on a tmpfs or a bind mount can run into; the file is then copied and the original
+/// removed, so the move still happens, only not atomically.
+fn move_file(rename: RenameFn, from: &Path, to: &Path) -> std::io::Result<()> {
+    match rename(from, to) {
+        Err(e) if e.kind() == std::io::ErrorKind::CrossesDevices => {
+            if let Err(e) = fs::copy(from, to) {
+                let _ = fs::remove_file(to);
+                return Err(e);
+            }
+            fs::remove_file(from)
+        }
+        moved => moved,
+    }
+}
 
 pub struct LogRotator {
-    config: LogRotationConfig,
+    /// Replaced by a config reload; every operation works on one snapshot of it
+    config: std::sync::RwLock<LogRotationConfig>,
+    rename: RenameFn,
 }
 
 impl LogRotator {
     pub fn new(config: LogRotationConfig) -> Self {
-        Self { config }
+        Self { config: std::sync::RwLock::new(config), rename: rename_file }
+    }
+
+    /// Rotator that renames with `rename`, to exercise the fallbacks for failed renames
+    #[cfg(test)]
+    fn with_rename(config: LogRotationConfig, rename: RenameFn) -> Self {
+        Self { config: std::sync::RwLock::new(config), rename }
+    }
+
+    /// Current rotation settings
+    pub fn config(&self) -> LogRotationConfig {
+        self.config.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Apply `config` to rotations from now on
+    pub fn set_config(&self, config: LogRotationConfig) {
+        *self.config.write().unwrap_or_else(|e| e.into_inner()) = config;
+    }
+
+    /// Check if log rotation is needed and perform it if necessary,
+    /// returning what was moved when it did
+    pub async fn rotate_if_needed(&self, log_path: &Path) -> Result<Option<RotationRecord>> {
+        self.rotate_if_needed_with(log_path, &RetryingFs::default()).await
     }
 
-    /// Check if log rotation is needed and perform it if necessary
-    pub async fn rotate_if_needed(&self, log_path: &Path) -> Result<()> {
-        if !self.config.enabled {
-            return Ok(());
+    /// [`Self::rotate_if_needed`] with the moves and the new log file retried by `files`
+    pub async fn rotate_if_needed_with(&self, log_path: &Path, files: &RetryingFs) -> Result<Option<RotationRecord>> {
+        let config = self.config();
+        if !config.enabled {
+            return Ok(None);
         }
 
         // Check if the log file exists and its size
         if !log_path.exists() {
-            return Ok(());
+            return Ok(None);
         }
 
         let metadata = fs::metadata(log_path)?;
-        if metadata.len() <= self.config.max_file_size {
-            return Ok(());
+        if metadata.len() <= config.max_file_size {
+            return Ok(None);
         }
 
         // Perform rotation
-        self.rotate_log(log_path).await?;
-        Ok(())
+        let record = self.rotate_log(log_path, files).await?;
+        Ok(Some(record))
     }
 
-    /// Force log rotation regardless of file size
-    pub async fn force_rotate(&self, log_path: &Path) -> Result<()> {
+    /// Force log rotation regardless of file size; None when there is no log to rotate
+    pub async fn force_rotate(&self, log_path: &Path) -> Result<Option<RotationOutcome>> {
         if !log_path.exists() {
-            return Ok(());
+            return Ok(None);
         }
 
         // Perform rotation
-        self.rotate_log(log_path).await?;
-        Ok(())
+        let record = self.rotate_log(log_path, &RetryingFs::default()).await?;
+        Ok(Some(record.outcome))
     }
 
     /// Rotate the log file
-    async fn rotate_log(&self, log_path: &Path) -> Result<()> {
+    async fn rotate_log(&self, log_path: &Path, files: &RetryingFs) -> Result<RotationRecord> {
+        let config = self.config();
         let log_dir = log_path.parent()
             .ok_or_else(|| Error::Other("Invalid log path".to_string()))?;
         
@@ -53,30 +154,61 @@ impl LogRotator {
             .ok_or_else(|| Error::Other("Invalid log file name".to_string()))?
             .to_string_lossy();
 
-        // Move existing rotated files
-        for i in (1..self.config.max_files).rev() {
-            let old_file = log_dir.join(format!("{}.{}.log", log_name, i));
-            let new_file = log_dir.join(format!("{}.{}.log", log_name, i + 1));
-            
-            if old_file.exists() {
-                if i + 1 > self.config.max_files {
-                    // Remove the oldest file
-                    let _ = fs::remove_file(&old_file);
-                } else {
-                    // Move to next number
-                    let _ = fs::rename(&old_file, &new_file);
+        let mut record = RotationRecord::default();
+        record.outcome.rotated_from_size = self.get_log_size(log_path)?;
+
+        // Existing archives are renumbered 2, 3, ... in age order, closing any gaps left by
+        // deleted files or a reduced max_files; those that end up past the limit are dropped
+        let mut slots: Vec<Vec<RotatedFile>> = Vec::new();
+        for file in self.get_rotated_files(log_path)? {
+            match slots.last_mut() {
+                Some(slot) if slot[0].index == file.index => slot.push(file),
+                _ => slots.push(vec![file]),
+            }
+        }
+        let mut moves = Vec::new();
+        for (rank, slot) in slots.into_iter().enumerate() {
+            let target = rank + 2;
+            if target > config.max_files {
+                for file in slot {
+                    if fs::remove_file(&file.path).is_ok() {
+                        record.outcome.archives_deleted.push(file.path);
+                    }
+                }
+            } else if slot[0].index != target {
+                moves.push((target, slot));
+            }
+        }
+
+        // Moves to a lower number go first, lowest up, then moves to a higher number,
+        // highest down, so no rename lands on a file that has yet to move
+        let (down, up): (Vec<_>, Vec<_>) = moves.into_iter().partition(|(target, slot)| *target < slot[0].index);
+        for (target, slot) in down.into_iter().chain(up.into_iter().rev()) {
+            for file in slot {
+                let new_file = log_dir.join(archive_name(&log_name, target, file.compressed));
+                if files.retry("rename", &file.path, || move_file(self.rename, &file.path, &new_file)).is_ok() {
+                    record.renames.push((file.path, new_file));
+                    record.outcome.archives_shifted += 1;
                 }
             }
         }
 
         // Move current log to .1
-        let rotated_file = log_dir.join(format!("{}.1.log", log_name));
-        fs::rename(log_path, &rotated_file)?;
+        let rotated_file = log_dir.join(archive_name(&log_name, 1, false));
+        files.retry("rename", log_path, || move_file(self.rename, log_path, &rotated_file))?;
+        record.renames.push((log_path.to_path_buf(), rotated_file));
 
         // Create new empty log file
-        fs::File::create(log_path)?;
+        files.create_file(log_path)?;
+        // An index of the log now describes the archive; start it over for the new log
+        if let Err(e) = LogIndex::restart(log_path) {
+            tracing::warn!(path = %log_path.display(), error = %e, "could not restart the log index");
+        }
 
-        Ok(())
+        // Only left to do when max_files is 0 and the new archive is already past it
+        record.outcome.archives_deleted.extend(self.cleanup_old_files(log_path)?);
+
+        Ok(record)
     }
 
     /// Get the size of a log file
@@ -90,16 +222,19 @@ impl LogRotator {
 
     /// Check if rotation is needed without performing it
     pub fn needs_rotation(&self, log_path: &Path) -> Result<bool> {
-        if !self.config.enabled {
+        let config = self.config();
+        if !config.enabled {
             return Ok(false);
         }
 
         let size = self.get_log_size(log_path)?;
-        Ok(size > self.config.max_file_size)
+        Ok(size > config.max_file_size)
     }
 
-    /// Get list of rotated log files for a given log path
-    pub fn get_rotated_files(&self, log_path: &Path) -> Result<Vec<PathBuf>> {
+    /// Rotated files of a log, oldest-numbered last: every `<name>.<N>.log` and
+    /// `<name>.<N>.log.gz` in its directory, whatever N is and whether or not the
+    /// numbers are contiguous. The compressed form of an archive follows the plain one.
+    pub fn get_rotated_files(&self, log_path: &Path) -> Result<Vec<RotatedFile>> {
         let log_dir = log_path.parent()
             .ok_or_else(|| Error::Other("Invalid log path".to_string()))?;
         
@@ -107,36 +242,154 @@ impl LogRotator {
             .ok_or_else(|| Error::Other("Invalid log file name".to_string()))?
             .to_string_lossy();
 
+        let entries = match fs::read_dir(log_dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+
         let mut rotated_files = Vec::new();
-        
-        for i in 1..=self.config.max_files {
-            let rotated_file = log_dir.join(format!("{}.{}.log", log_name, i));
-            if rotated_file.exists() {
-                rotated_files.push(rotated_file);
-            }
+        for entry in entries {
+            let entry = entry?;
+            let file_name = entry.file_name();
+            let Some((index, compressed)) = parse_archive_name(&log_name, &file_name.to_string_lossy()) else {
+                continue;
+            };
+            // Removed since the directory was read
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            rotated_files.push(RotatedFile {
+                index,
+                path: entry.path(),
+                compressed,
+                size: metadata.len(),
+                modified: metadata.modified()?,
+            });
         }
+        rotated_files.sort_by_key(|file| (file.index, file.compressed));
 
         Ok(rotated_files)
     }
 
-    /// Clean up old rotated files beyond the configured limit
-    pub fn cleanup_old_files(&self, log_path: &Path) -> Result<()> {
-        let log_dir = log_path.parent()
-            .ok_or_else(|| Error::Other("Invalid log path".to_string()))?;
-        
-        let log_name = log_path.file_stem()
-            .ok_or_else(|| Error::Other("Invalid log file name".to_string()))?
-            .to_string_lossy();
-
-        // Remove files beyond the max_files limit
-        for i in (self.config.max_files + 1)..=20 { // Check up to 20 files
-            let old_file = log_dir.join(format!("{}.{}.log", log_name, i));
-            if old_file.exists() {
-                let _ = fs::remove_file(&old_file);
+    /// Clean up old rotated files beyond the configured limit, returning the ones removed
+    pub fn cleanup_old_files(&self, log_path: &Path) -> Result<Vec<PathBuf>> {
+        let config = self.config();
+        let mut removed = Vec::new();
+        for file in self.get_rotated_files(log_path)? {
+            if file.index > config.max_files && fs::remove_file(&file.path).is_ok() {
+                removed.push(file.path);
             }
         }
 
-        Ok(())
+        Ok(removed)
+    }
+}
+
+/// File name of archive `index` of the log named `log_name`
+fn archive_name(log_name: &str, index: usize, compressed: bool) -> String {
+    format!("{}.{}.log{}", log_name, index, if compressed { ".gz" } else { "" })
+}
+
+/// Index and compression of `file_name` if it is an archive of the log named `log_name`
+fn parse_archive_name(log_name: &str, file_name: &str) -> Option<(usize, bool)> {
+    let rest = file_name.strip_prefix(log_name)?.strip_prefix('.')?;
+    let (number, compressed) = match rest.strip_suffix(".log.gz") {
+        Some(number) => (number, true),
+        None => (rest.strip_suffix(".log")?, false),
+    };
+    if number.is_empty() || !number.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    match number.parse() {
+        Ok(index) if index > 0 => Some((index, compressed)),
+        _ => None,
+    }
+}
+
+/// Size of the blocks read backwards by [`read_last_lines`]
+const TAIL_CHUNK_SIZE: u64 = 64 * 1024;
+
+/// Read at most `max` trailing lines of a file, scanning backwards in blocks so only
+/// the needed end of the file is loaded
+pub fn read_last_lines(path: &Path, max: usize) -> Result<Vec<String>> {
+    read_last_lines_lossy(path, max).map(|(lines, _)| lines)
+}
+
+/// [`read_last_lines`], also telling whether any of the returned lines held bytes that
+/// are not UTF-8 and now show U+FFFD in their place
+pub fn read_last_lines_lossy(path: &Path, max: usize) -> Result<(Vec<String>, bool)> {
+    use std::io::{Read, Seek, SeekFrom};
+
+    if max == 0 {
+        return Ok((Vec::new(), false));
+    }
+
+    let mut file = fs::File::open(path)?;
+    let mut pos = file.metadata()?.len();
+    let mut tail: Vec<u8> = Vec::new();
+    let mut newlines = 0;
+
+    // Collect more than `max` line breaks so the first returned line is complete;
+    // a newline terminating the file does not start another line
+    while pos > 0 {
+        let size = TAIL_CHUNK_SIZE.min(pos);
+        pos -= size;
+        file.seek(SeekFrom::Start(pos))?;
+        let mut chunk = vec![0u8; size as usize];
+        file.read_exact(&mut chunk)?;
+
+        newlines += chunk.iter().filter(|b| **b == b'\n').count();
+        chunk.extend_from_slice(&tail);
+        tail = chunk;
+
+        let terminated = tail.last() == Some(&b'\n');
+        if newlines - usize::from(terminated) >= max {
+            break;
+        }
+    }
+
+    // Split as str::lines does, but before decoding so only the kept lines are checked
+    if tail.is_empty() {
+        return Ok((Vec::new(), false));
+    }
+    let body = tail.strip_suffix(b"\n").unwrap_or(&tail);
+    let lines: Vec<&[u8]> = body.split(|b| *b == b'\n').collect();
+    let start = lines.len().saturating_sub(max);
+    let mut lossy = false;
+    let lines = lines[start..]
+        .iter()
+        .map(|line| {
+            let line = String::from_utf8_lossy(line.strip_suffix(b"\r").unwrap_or(line));
+            lossy |= matches!(line, std::borrow::Cow::Owned(_));
+            line.into_owned()
+        })
+        .collect();
+    Ok((lines, lossy))
+}
+
+/// The last `max` bytes of a file, read with a single seek, and the offset in the file
+/// they start at. Unless `allow_partial`, the cut moves on past the next line break so the
+/// first line is whole; a cut right after a line break keeps every byte, and a tail without
+/// any line break is dropped whole.
+pub fn read_tail_bytes(path: &Path, max: u64, allow_partial: bool) -> Result<(Vec<u8>, u64)> {
+    use std::io::{Read, Seek, SeekFrom};
+
+    let mut file = fs::File::open(path)?;
+    let len = file.metadata()?.len();
+    let cut = len.saturating_sub(max);
+    // The byte before the cut tells whether it falls on a line boundary
+    let from = if allow_partial || cut == 0 { cut } else { cut - 1 };
+    file.seek(SeekFrom::Start(from))?;
+    let mut tail = Vec::with_capacity((len - from) as usize);
+    // Bounded by the length seen, however much is appended meanwhile
+    file.take(len - from).read_to_end(&mut tail)?;
+    if from == cut {
+        return Ok((tail, cut));
+    }
+    match tail.iter().position(|b| *b == b'\n') {
+        Some(newline) => Ok((tail.split_off(newline + 1), from + newline as u64 + 1)),
+        None => Ok((Vec::new(), from + tail.len() as u64)),
     }
 }
 
@@ -146,6 +399,81 @@ mod tests {
     use tempfile::TempDir;
     use std::io::Write;
 
+    #[test]
+    fn test_read_tail_bytes_starts_at_a_whole_line() {
+        let temp_dir = TempDir::new().unwrap();
+        let log_path = temp_dir.path().join("json.log");
+        // Lines of 10, 7 and 4 bytes: the second starts at 10, the third at 17
+        fs::write(&log_path, "{\"a\":123}\n{\"b\":}\nend\n").unwrap();
+        let tail = |max, partial| {
+            let (bytes, offset) = read_tail_bytes(&log_path, max, partial).unwrap();
+            (String::from_utf8(bytes).unwrap(), offset)
+        };
+
+        // A cut right after a line break keeps everything
+        assert_eq!(tail(11, false), ("{\"b\":}\nend\n".to_string(), 10));
+        assert_eq!(tail(4, false), ("end\n".to_string(), 17));
+        // A cut inside a line drops the rest of it, unless partial lines are allowed
+        assert_eq!(tail(8, false), ("end\n".to_string(), 17));
+        assert_eq!(tail(8, true), ("\":}\nend\n".to_string(), 13));
+        assert_eq!(tail(2, false), (String::new(), 21));
+        assert_eq!(tail(2, true), ("d\n".to_string(), 19));
+        assert_eq!(tail(0, false), (String::new(), 21));
+        // A file no larger than the tail is returned whole, from the start
+        assert_eq!(tail(21, false), ("{\"a\":123}\n{\"b\":}\nend\n".to_string(), 0));
+        assert_eq!(tail(1 << 20, false), ("{\"a\":123}\n{\"b\":}\nend\n".to_string(), 0));
+
+        // A single line longer than the tail leaves nothing whole to return
+        let long_path = temp_dir.path().join("long.log");
+        fs::write(&long_path, "x".repeat(1000)).unwrap();
+        assert_eq!(read_tail_bytes(&long_path, 100, false).unwrap(), (Vec::new(), 1000));
+        assert_eq!(read_tail_bytes(&long_path, 100, true).unwrap(), (vec![b'x'; 100], 900));
+    }
+
+    #[test]
+    fn test_read_last_lines_across_chunks() {
+        let temp_dir = TempDir::new().unwrap();
+        let log_path = temp_dir.path().join("big.log");
+        let content: String = (0..20_000).map(|i| format!("line {}\n", i)).collect();
+        fs::write(&log_path, &content).unwrap();
+
+        let lines = read_last_lines(&log_path, 3).unwrap();
+        assert_eq!(lines, vec!["line 19997", "line 19998", "line 19999"]);
+
+        let lines = read_last_lines(&log_path, 15_000).unwrap();
+        assert_eq!(lines.len(), 15_000);
+        assert_eq!(lines[0], "line 5000");
+
+        assert_eq!(read_last_lines(&log_path, 50_000).unwrap().len(), 20_000);
+        assert!(read_last_lines(&log_path, 0).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_read_last_lines_without_trailing_newline() {
+        let temp_dir = TempDir::new().unwrap();
+        let log_path = temp_dir.path().join("partial.log");
+        fs::write(&log_path, "a\nb\nc").unwrap();
+        assert_eq!(read_last_lines(&log_path, 2).unwrap(), vec!["b", "c"]);
+
+        fs::write(&log_path, "").unwrap();
+        assert!(read_last_lines(&log_path, 2).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_read_last_lines_lossy_flags_only_kept_lines() {
+        let temp_dir = TempDir::new().unwrap();
+        let log_path = temp_dir.path().join("binary.log");
+        fs::write(&log_path, b"\xff\xfe early\r\nok\nbad \xc3\n\n").unwrap();
+        let (lines, lossy) = read_last_lines_lossy(&log_path, 3).unwrap();
+        assert_eq!(lines, vec!["ok", "bad \u{fffd}", ""]);
+        assert!(lossy);
+
+        let (lines, lossy) = read_last_lines_lossy(&log_path, 1).unwrap();
+        assert_eq!(lines, vec![""]);
+        assert!(!lossy);
+        assert_eq!(read_last_lines(&log_path, 4).unwrap()[0], "\u{fffd}\u{fffd} early");
+    }
+
     #[tokio::test]
     async fn test_log_rotation() {
         let temp_dir = TempDir::new().unwrap();
@@ -161,7 +489,7 @@ mod tests {
 
         // Create a log file larger than max_file_size
         let mut file = fs::File::create(&log_path).unwrap();
-        file.write_all(&vec![b'x'; 150]).unwrap();
+        file.write_all(&[b'x'; 150]).unwrap();
         drop(file);
 
         // Perform rotation
@@ -204,7 +532,7 @@ mod tests {
 
         // Create large file
         let mut file = fs::File::create(&log_path).unwrap();
-        file.write_all(&vec![b'x'; 150]).unwrap();
+        file.write_all(&[b'x'; 150]).unwrap();
         drop(file);
 
         assert!(rotator.needs_rotation(&log_path).unwrap());
@@ -252,6 +580,70 @@ mod tests {
         assert!(content_2.contains("first rotation"));
     }
 
+    #[tokio::test]
+    async fn test_rotation_outcomes_match_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let log_path = temp_dir.path().join("test.log");
+        let rotator = LogRotator::new(LogRotationConfig {
+            max_file_size: 10,
+            max_files: 2,
+            enabled: true,
+        });
+        let archive = |i: usize| temp_dir.path().join(format!("test.{}.log", i));
+
+        let mut outcomes = Vec::new();
+        for run in ["first", "second run", "third run!"] {
+            fs::write(&log_path, run).unwrap();
+            outcomes.push(rotator.force_rotate(&log_path).await.unwrap().unwrap());
+        }
+
+        assert_eq!(outcomes[0], RotationOutcome { rotated_from_size: 5, archives_shifted: 0, archives_deleted: vec![] });
+        assert_eq!(outcomes[1], RotationOutcome { rotated_from_size: 10, archives_shifted: 1, archives_deleted: vec![] });
+        // The third rotation pushes "first" past max_files
+        assert_eq!(
+            outcomes[2],
+            RotationOutcome { rotated_from_size: 10, archives_shifted: 1, archives_deleted: vec![archive(2)] }
+        );
+        assert_eq!(fs::read_to_string(archive(1)).unwrap(), "third run!");
+        assert_eq!(fs::read_to_string(archive(2)).unwrap(), "second run");
+
+        // Archives left from a larger max_files are cleaned up and reported
+        fs::write(archive(4), "stale").unwrap();
+        fs::write(&log_path, "fourth").unwrap();
+        let outcome = rotator.force_rotate(&log_path).await.unwrap().unwrap();
+        assert_eq!(outcome.archives_deleted, vec![archive(2), archive(4)]);
+        assert!(!archive(4).exists());
+
+        fs::remove_file(&log_path).unwrap();
+        assert_eq!(rotator.force_rotate(&log_path).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_undo_rotation() {
+        let temp_dir = TempDir::new().unwrap();
+        let log_path = temp_dir.path().join("test.log");
+        let rotator = LogRotator::new(LogRotationConfig {
+            max_file_size: 10,
+            max_files: 3,
+            enabled: true,
+        });
+
+        fs::write(temp_dir.path().join("test.1.log"), "older").unwrap();
+        fs::write(&log_path, "current content over the limit").unwrap();
+
+        let record = rotator.rotate_if_needed(&log_path).await.unwrap().unwrap();
+        assert_eq!(fs::read_to_string(temp_dir.path().join("test.2.log")).unwrap(), "older");
+        assert_eq!(fs::read_to_string(&log_path).unwrap(), "");
+
+        record.undo().unwrap();
+        assert_eq!(fs::read_to_string(&log_path).unwrap(), "current content over the limit");
+        assert_eq!(fs::read_to_string(temp_dir.path().join("test.1.log")).unwrap(), "older");
+        assert!(!temp_dir.path().join("test.2.log").exists());
+
+        // Small files are left alone
+        assert_eq!(rotator.rotate_if_needed(&temp_dir.path().join("test.1.log")).await.unwrap(), None);
+    }
+
     #[test]
     fn test_disabled_rotation() {
         let temp_dir = TempDir::new().unwrap();
@@ -267,7 +659,7 @@ mod tests {
 
         // Create large file
         let mut file = fs::File::create(&log_path).unwrap();
-        file.write_all(&vec![b'x'; 150]).unwrap();
+        file.write_all(&[b'x'; 150]).unwrap();
         drop(file);
 
         // Should not need rotation when disabled
@@ -297,8 +689,142 @@ mod tests {
         assert_eq!(rotated_files.len(), 3);
 
         // Check that files are in correct order
-        assert!(rotated_files[0].to_string_lossy().contains("test.1.log"));
-        assert!(rotated_files[1].to_string_lossy().contains("test.2.log"));
-        assert!(rotated_files[2].to_string_lossy().contains("test.3.log"));
+        assert!(rotated_files[0].path.to_string_lossy().contains("test.1.log"));
+        assert!(rotated_files[1].path.to_string_lossy().contains("test.2.log"));
+        assert!(rotated_files[2].path.to_string_lossy().contains("test.3.log"));
+    }
+
+    #[test]
+    fn test_rotated_files_sorted_numerically_with_gaps() {
+        let temp_dir = TempDir::new().unwrap();
+        let log_path = temp_dir.path().join("test.log");
+        let rotator = LogRotator::new(LogRotationConfig { max_file_size: 100, max_files: 3, enabled: true });
+        for name in ["test.10.log", "test.2.log", "test.25.log", "test.2.log.gz", "test.log", "test.0.log", "test.x.log", "other.1.log", "test.1.txt"] {
+            fs::write(temp_dir.path().join(name), name).unwrap();
+        }
+
+        let rotated = rotator.get_rotated_files(&log_path).unwrap();
+        let found: Vec<(usize, bool)> = rotated.iter().map(|file| (file.index, file.compressed)).collect();
+        assert_eq!(found, vec![(2, false), (2, true), (10, false), (25, false)]);
+        assert_eq!(rotated[3].path, temp_dir.path().join("test.25.log"));
+        assert!(rotated[0].modified <= std::time::SystemTime::now());
+
+        // Strays far past the old fixed scan limit are cleaned up too
+        let removed = rotator.cleanup_old_files(&log_path).unwrap();
+        assert_eq!(removed, vec![temp_dir.path().join("test.10.log"), temp_dir.path().join("test.25.log")]);
+        assert!(rotator.get_rotated_files(&temp_dir.path().join("missing/test.log")).unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_rotation_compacts_gapped_indices() {
+        let temp_dir = TempDir::new().unwrap();
+        let log_path = temp_dir.path().join("test.log");
+        let rotator = LogRotator::new(LogRotationConfig { max_file_size: 10, max_files: 4, enabled: true });
+        let archive = |name: &str| temp_dir.path().join(name);
+        fs::write(archive("test.1.log"), "newest").unwrap();
+        fs::write(archive("test.3.log"), "middle").unwrap();
+        fs::write(archive("test.3.log.gz"), "middle gz").unwrap();
+        fs::write(archive("test.7.log"), "older").unwrap();
+        fs::write(archive("test.22.log"), "oldest").unwrap();
+        fs::write(&log_path, "current").unwrap();
+
+        let record = rotator.rotate_log(&log_path, &RetryingFs::default()).await.unwrap();
+        let contents: Vec<(usize, String)> = rotator
+            .get_rotated_files(&log_path)
+            .unwrap()
+            .into_iter()
+            .map(|file| (file.index, fs::read_to_string(file.path).unwrap()))
+            .collect();
+        assert_eq!(
+            contents,
+            vec![
+                (1, "current".to_string()),
+                (2, "newest".to_string()),
+                (3, "middle".to_string()),
+                (3, "middle gz".to_string()),
+                (4, "older".to_string()),
+            ]
+        );
+        assert_eq!(record.outcome().archives_deleted, vec![archive("test.22.log")]);
+        assert_eq!(record.outcome().archives_shifted, 2);
+
+        // Undoing puts the renumbered archives back where they were
+        record.undo().unwrap();
+        assert_eq!(fs::read_to_string(&log_path).unwrap(), "current");
+        assert_eq!(fs::read_to_string(archive("test.1.log")).unwrap(), "newest");
+        assert_eq!(fs::read_to_string(archive("test.7.log")).unwrap(), "older");
+        assert!(!archive("test.4.log").exists());
+    }
+
+    fn cross_device_rename(_: &Path, _: &Path) -> std::io::Result<()> {
+        Err(std::io::Error::from_raw_os_error(libc::EXDEV))
+    }
+
+    #[tokio::test]
+    async fn test_rotation_copies_across_filesystems() {
+        let temp_dir = TempDir::new().unwrap();
+        let log_path = temp_dir.path().join("test.log");
+        let config = LogRotationConfig { max_file_size: 10, max_files: 3, enabled: true };
+        let rotator = LogRotator::with_rename(config, cross_device_rename);
+        let archive = |i: usize| temp_dir.path().join(format!("test.{}.log", i));
+        fs::write(archive(1), "older").unwrap();
+        let content: String = (0..10_000).map(|i| format!("line {}\n", i)).collect();
+        fs::write(&log_path, &content).unwrap();
+
+        let record = rotator.rotate_if_needed(&log_path).await.unwrap().unwrap();
+        assert_eq!(record.outcome().archives_shifted, 1);
+        assert_eq!(fs::read_to_string(archive(1)).unwrap(), content);
+        assert_eq!(fs::read_to_string(archive(2)).unwrap(), "older");
+
+        // The live log is a fresh file that keeps taking writes and rotating
+        assert_eq!(fs::read_to_string(&log_path).unwrap(), "");
+        fs::OpenOptions::new().append(true).open(&log_path).unwrap().write_all(b"after the move").unwrap();
+        rotator.rotate_if_needed(&log_path).await.unwrap().unwrap();
+        assert_eq!(fs::read_to_string(archive(1)).unwrap(), "after the move");
+        assert_eq!(fs::read_to_string(archive(2)).unwrap(), content);
+        assert_eq!(fs::read_to_string(archive(3)).unwrap(), "older");
+    }
+
+    #[tokio::test]
+    async fn test_failed_rename_leaves_log_in_place() {
+        fn denied(_: &Path, _: &Path) -> std::io::Result<()> {
+            Err(std::io::Error::from(std::io::ErrorKind::PermissionDenied))
+        }
+
+        let temp_dir = TempDir::new().unwrap();
+        let log_path = temp_dir.path().join("test.log");
+        let config = LogRotationConfig { max_file_size: 10, max_files: 3, enabled: true };
+        let rotator = LogRotator::with_rename(config, denied);
+        fs::write(&log_path, "current content over the limit").unwrap();
+
+        // Only a cross-device failure is copied around; anything else is an error
+        assert!(rotator.rotate_if_needed(&log_path).await.is_err());
+        assert_eq!(fs::read_to_string(&log_path).unwrap(), "current content over the limit");
+        assert!(!temp_dir.path().join("test.1.log").exists());
+    }
+
+    #[tokio::test]
+    async fn test_transient_rename_failure_is_retried() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        static DENIALS: AtomicUsize = AtomicUsize::new(0);
+        /// EACCES on the first call, as an NFS client with a stale attribute cache gives
+        fn flaky(from: &Path, to: &Path) -> std::io::Result<()> {
+            if DENIALS.fetch_add(1, Ordering::SeqCst) == 0 {
+                return Err(std::io::Error::from_raw_os_error(libc::EACCES));
+            }
+            rename_file(from, to)
+        }
+
+        let temp_dir = TempDir::new().unwrap();
+        let log_path = temp_dir.path().join("test.log");
+        let config = LogRotationConfig { max_file_size: 10, max_files: 3, enabled: true };
+        let rotator = LogRotator::with_rename(config, flaky);
+        fs::write(&log_path, "current content over the limit").unwrap();
+
+        let files = RetryingFs::default();
+        assert!(rotator.rotate_if_needed_with(&log_path, &files).await.unwrap().is_some());
+        assert_eq!(fs::read_to_string(temp_dir.path().join("test.1.log")).unwrap(), "current content over the limit");
+        assert_eq!(fs::read_to_string(&log_path).unwrap(), "");
+        assert_eq!(files.tally().retries(), 1);
     }
 }
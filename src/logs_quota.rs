@@ -0,0 +1,250 @@
+//! The global logs quota (`logs_quota_bytes`): what the live and rotated logs of every
+//! process take on disk, and which archives to delete when that is more than allowed.
+//!
+//! Archives are deleted oldest first across all processes until the total fits; live logs
+//! are never touched. Measuring stats every log, so the last measurement is cached for
+//! [`LOGS_USAGE_MAX_AGE`] and checks within that time only compare it with the quota.
+//! The logs are measured through the [`crate::file_pool::FilePool`]; those it could not
+//! measure in time are counted as unknown and left out of the totals.
+
+use crate::log_rotation::{LogRotator, RotatedFile};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// Trigger recorded in the rotation history of a process whose archives the quota deleted
+pub const LOGS_QUOTA_TRIGGER: &str = "quota";
+
+/// How long a measurement is used before the logs are measured again
+pub const LOGS_USAGE_MAX_AGE: Duration = Duration::from_secs(30);
+
+/// How often a long-running manager checks the quota in the background
+pub const LOGS_QUOTA_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Enforcements kept for `pmr stats`
+pub const LOGS_QUOTA_HISTORY_LIMIT: usize = 20;
+
+/// Disk usage of the logs in one directory
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "http-api", derive(utoipa::ToSchema))]
+pub struct LogDirUsage {
+    pub log_dir: String,
+    /// Processes logging to the directory
+    pub processes: usize,
+    pub live_bytes: u64,
+    pub rotated_bytes: u64,
+    /// Processes whose logs could not be measured in time, left out of the byte counts
+    #[serde(default)]
+    pub unknown: usize,
+}
+
+/// Disk usage of the logs of every process, by directory
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "http-api", derive(utoipa::ToSchema))]
+pub struct LogsUsage {
+    /// Sorted by directory
+    pub directories: Vec<LogDirUsage>,
+    pub measured_at: DateTime<Utc>,
+}
+
+impl LogsUsage {
+    pub fn live_bytes(&self) -> u64 {
+        self.directories.iter().map(|dir| dir.live_bytes).sum()
+    }
+
+    pub fn rotated_bytes(&self) -> u64 {
+        self.directories.iter().map(|dir| dir.rotated_bytes).sum()
+    }
+
+    pub fn total_bytes(&self) -> u64 {
+        self.live_bytes() + self.rotated_bytes()
+    }
+
+    pub fn unknown(&self) -> usize {
+        self.directories.iter().map(|dir| dir.unknown).sum()
+    }
+
+    /// Whether the measurement is recent enough to use at `now`
+    pub fn is_fresh(&self, now: DateTime<Utc>) -> bool {
+        (now - self.measured_at).to_std().is_ok_and(|age| age < LOGS_USAGE_MAX_AGE)
+    }
+}
+
+/// A rotated log the quota may delete
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuotaArchive {
+    pub process_name: String,
+    pub path: PathBuf,
+    pub size: u64,
+    pub modified: SystemTime,
+}
+
+/// An archive deleted to get the logs under the quota
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "http-api", derive(utoipa::ToSchema))]
+pub struct DeletedArchive {
+    pub process_name: String,
+    pub path: String,
+    pub size: u64,
+}
+
+/// One pass that found the logs over the quota
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "http-api", derive(utoipa::ToSchema))]
+pub struct QuotaEnforcement {
+    pub enforced_at: DateTime<Utc>,
+    pub quota_bytes: u64,
+    /// Total size of the logs before and after deleting archives
+    pub used_before: u64,
+    pub used_after: u64,
+    /// Oldest first
+    pub deleted: Vec<DeletedArchive>,
+}
+
+impl QuotaEnforcement {
+    pub fn freed_bytes(&self) -> u64 {
+        self.deleted.iter().map(|archive| archive.size).sum()
+    }
+
+    /// Still over the quota: the live logs alone are too large, or some archives could not
+    /// be deleted and are left for the next pass
+    pub fn over_quota(&self) -> bool {
+        self.used_after > self.quota_bytes
+    }
+}
+
+/// Logs usage and quota, for `pmr stats`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "http-api", derive(utoipa::ToSchema))]
+pub struct LogsQuotaStats {
+    /// 0 means no quota
+    pub quota_bytes: u64,
+    pub usage: LogsUsage,
+    pub last_enforcement: Option<QuotaEnforcement>,
+}
+
+/// The logs of one process as found on disk
+#[derive(Debug, Clone)]
+pub struct LogMeasurement {
+    /// 0 when there is no live log
+    pub live_bytes: u64,
+    pub archives: Vec<RotatedFile>,
+}
+
+/// Measure the live log at `log_path` and its archives
+pub fn measure_one(log_rotator: &LogRotator, log_path: &Path) -> LogMeasurement {
+    LogMeasurement {
+        live_bytes: std::fs::metadata(log_path).map_or(0, |metadata| metadata.len()),
+        archives: log_rotator.get_rotated_files(log_path).unwrap_or_default(),
+    }
+}
+
+/// Add up the measurements of the logs at `log_paths` (process name, live log path), one
+/// per path and None for those not measured. Returns the usage by directory and every
+/// archive found.
+pub fn tally(log_paths: &[(String, PathBuf)], measurements: Vec<Option<LogMeasurement>>, now: DateTime<Utc>) -> (LogsUsage, Vec<QuotaArchive>) {
+    let mut directories: BTreeMap<String, LogDirUsage> = BTreeMap::new();
+    let mut archives = Vec::new();
+    for ((name, log_path), measurement) in log_paths.iter().zip(measurements) {
+        let log_dir = log_path.parent().unwrap_or(Path::new("")).to_string_lossy().to_string();
+        let usage = directories.entry(log_dir.clone()).or_insert_with(|| LogDirUsage {
+            log_dir,
+            processes: 0,
+            live_bytes: 0,
+            rotated_bytes: 0,
+            unknown: 0,
+        });
+        usage.processes += 1;
+        let Some(measurement) = measurement else {
+            usage.unknown += 1;
+            continue;
+        };
+        usage.live_bytes += measurement.live_bytes;
+        for file in measurement.archives {
+            usage.rotated_bytes += file.size;
+            archives.push(QuotaArchive { process_name: name.clone(), path: file.path, size: file.size, modified: file.modified });
+        }
+    }
+    (LogsUsage { directories: directories.into_values().collect(), measured_at: now }, archives)
+}
+
+/// The archives to delete, oldest first, for `used` bytes of logs to fit in `quota`: as
+/// few as it takes, or all of them when even that is not enough
+pub fn archives_to_delete(mut archives: Vec<QuotaArchive>, used: u64, quota: u64) -> Vec<QuotaArchive> {
+    archives.sort_by(|a, b| a.modified.cmp(&b.modified).then_with(|| a.path.cmp(&b.path)));
+    let mut remaining = used;
+    archives
+        .into_iter()
+        .take_while(|archive| {
+            let needed = remaining > quota;
+            remaining = remaining.saturating_sub(archive.size);
+            needed
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn archive(process_name: &str, path: &str, size: u64, age_secs: u64) -> QuotaArchive {
+        QuotaArchive {
+            process_name: process_name.to_string(),
+            path: PathBuf::from(path),
+            size,
+            modified: SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000 - age_secs),
+        }
+    }
+
+    fn paths(archives: &[QuotaArchive]) -> Vec<&str> {
+        archives.iter().map(|archive| archive.path.to_str().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_tally_leaves_unmeasured_logs_out_of_the_totals() {
+        let log_paths = vec![
+            ("web".to_string(), PathBuf::from("/logs/web.log")),
+            ("api".to_string(), PathBuf::from("/logs/api.log")),
+            ("job".to_string(), PathBuf::from("/nfs/job.log")),
+        ];
+        let rotated = RotatedFile {
+            index: 1,
+            path: PathBuf::from("/logs/web.1.log"),
+            compressed: false,
+            size: 40,
+            modified: SystemTime::UNIX_EPOCH,
+        };
+        let measurements = vec![
+            Some(LogMeasurement { live_bytes: 100, archives: vec![rotated] }),
+            Some(LogMeasurement { live_bytes: 10, archives: Vec::new() }),
+            None,
+        ];
+        let (usage, archives) = tally(&log_paths, measurements, Utc::now());
+        assert_eq!(usage.total_bytes(), 150);
+        assert_eq!(usage.unknown(), 1);
+        assert_eq!(usage.directories[0].log_dir, "/logs");
+        assert_eq!((usage.directories[0].processes, usage.directories[0].unknown), (2, 0));
+        assert_eq!((usage.directories[1].processes, usage.directories[1].unknown), (1, 1));
+        assert_eq!(paths(&archives), ["/logs/web.1.log"]);
+    }
+
+    #[test]
+    fn test_archives_to_delete_oldest_first_until_under_quota() {
+        let archives = vec![
+            archive("web", "/logs/web.1.log", 100, 10),
+            archive("api", "/logs/api.2.log", 100, 300),
+            archive("web", "/logs/web.2.log", 100, 200),
+            archive("api", "/logs/api.1.log", 100, 100),
+        ];
+        assert!(archives_to_delete(archives.clone(), 500, 500).is_empty());
+        assert_eq!(paths(&archives_to_delete(archives.clone(), 501, 500)), ["/logs/api.2.log"]);
+        assert_eq!(paths(&archives_to_delete(archives.clone(), 650, 500)), ["/logs/api.2.log", "/logs/web.2.log"]);
+        // Live logs alone are over the quota: every archive goes, and that is all
+        assert_eq!(
+            paths(&archives_to_delete(archives, 1000, 100)),
+            ["/logs/api.2.log", "/logs/web.2.log", "/logs/api.1.log", "/logs/web.1.log"]
+        );
+    }
+}
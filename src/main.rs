@@ -1,137 +1,1136 @@
 use clap::Parser;
+use futures_util::StreamExt;
 use pmr::{
-    cli::{Cli, Commands},
-    config::Config,
-    formatter::Formatter,
-    process::ProcessManager,
+    batch::{parse_batch, BatchCommand, BatchResult},
+    cancel::Cancellation,
+    cgroup::CgroupLimits,
+    cli::{Cli, Commands, ConfigCommands, DbCommands, GenerateCommands, OutputFormat},
+    config::{Config, ConfigIssue, DEFAULT_MAX_LOG_LINES, DEFAULT_MAX_LOGS_RESPONSE_BYTES, StorageKind},
+    confirm::{self, Confirmation, Expected},
+    database::{Database, ProcessKind, ProcessStatus},
+    env::EnvVarProblem,
+    file_watch::{self, WatchConfig},
+    format::human_duration,
+    formatter::{rotated_log_header, Formatter, OnelineSymbols},
+    limits,
+    name::ProcessName,
+    process::{parse_signal, read_resource_usage, StatusRefresh, DEFAULT_EPHEMERAL_KEEP, RECENT_RUNS, RUN_HISTORY_LIMIT, DeleteOptions, LogOptions, ProcessManager, RestartOptions, StartOptions, StartProgress, StopOptions},
+    profiles,
+    resolution::ResolutionCache,
+    systemd::{self, UnitContext, UnitScope},
+    timings::TimingCollector,
+    watch::StatusWatch,
 };
+use std::cell::RefCell;
+use std::fmt::Write as _;
+use std::io::IsTerminal;
+use std::sync::{Arc, Mutex};
 
 #[cfg(feature = "http-api")]
 use pmr::{
-    api::{ApiServer, AuthManager},
-    cli::AuthCommands,
+    api::{log_broker::LogBroker, ApiServer, AuthManager},
+    cli::{AuthCommands, ServeFlags},
+    text_width::fit,
+    database::{http_server_process_name, HTTP_SERVER_PROCESS_NAME},
+    duration::format_duration,
+    process::ProcessLogs,
 };
 
+/// Where a command prints: straight to the terminal, or into buffers that become a batch result
+enum Output {
+    Terminal,
+    Captured { stdout: RefCell<String>, stderr: RefCell<String> },
+}
+
+impl Output {
+    fn captured() -> Self {
+        Output::Captured { stdout: RefCell::default(), stderr: RefCell::default() }
+    }
+
+    fn is_terminal(&self) -> bool {
+        matches!(self, Output::Terminal)
+    }
+
+    fn print(&self, args: std::fmt::Arguments) {
+        match self {
+            Output::Terminal => println!("{}", args),
+            Output::Captured { stdout, .. } => {
+                let _ = writeln!(stdout.borrow_mut(), "{}", args);
+            }
+        }
+    }
+
+    fn eprint(&self, args: std::fmt::Arguments) {
+        match self {
+            Output::Terminal => eprintln!("{}", args),
+            Output::Captured { stderr, .. } => {
+                let _ = writeln!(stderr.borrow_mut(), "{}", args);
+            }
+        }
+    }
+
+    /// Captured stdout and stderr, each without its final newline
+    fn into_captured(self) -> (String, String) {
+        match self {
+            Output::Terminal => (String::new(), String::new()),
+            Output::Captured { stdout, stderr } => {
+                let trim = |text: RefCell<String>| {
+                    let mut text = text.into_inner();
+                    if text.ends_with('\n') {
+                        text.pop();
+                    }
+                    text
+                };
+                (trim(stdout), trim(stderr))
+            }
+        }
+    }
+}
+
+/// `println!` through an [`Output`]
+macro_rules! outln {
+    ($out:expr) => { $out.print(format_args!("")) };
+    ($out:expr, $($arg:tt)*) => { $out.print(format_args!($($arg)*)) };
+}
+
+/// `eprintln!` through an [`Output`]
+macro_rules! errln {
+    ($out:expr, $($arg:tt)*) => { $out.eprint(format_args!($($arg)*)) };
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let cli = Cli::parse();
-    let formatter = Formatter::new(cli.format.clone());
-    let config = Config::new();
-    let process_manager = ProcessManager::new(config).await?;
+    let timings = if cli.timings {
+        let collector = TimingCollector::new();
+        collector.install_global()?;
+        Some(collector)
+    } else {
+        None
+    };
+    // Validation must work on a config that would stop the manager from opening
+    if let Commands::Config { command: ConfigCommands::Validate { config } } = &cli.command {
+        let valid = validate_config(config.as_deref(), &formatter(&cli), &Output::Terminal);
+        std::process::exit(if valid { 0 } else { 1 });
+    }
+    let config = Config::load()?;
+    // Paths are most needed when the manager cannot open them, so show them without one
+    if let Commands::Config { command: ConfigCommands::Show } = &cli.command {
+        println!("{}", formatter(&cli).format_config_paths(&config.paths()));
+        return Ok(());
+    }
+    // Inspected without opening the manager, which would refuse or migrate the database
+    if let Commands::Db { command: DbCommands::Info { check_compat } } = &cli.command {
+        let info = Database::inspect(&config.paths().database).await?;
+        println!("{}", formatter(&cli).format_schema_info(&info));
+        std::process::exit(if *check_compat && !info.is_compatible() { 1 } else { 0 });
+    }
+    // The damaged file is moved away, so nothing may have it open
+    if let Commands::Db { command: DbCommands::Salvage { path } } = &cli.command {
+        let report = Database::salvage(path).await?;
+        println!("{}", formatter(&cli).format_salvage_report(&report));
+        return Ok(());
+    }
+    let config = if cli.migrate { config.with_destructive_migrations(true) } else { config };
+    #[cfg(feature = "http-api")]
+    let config = match cli.command {
+        Commands::Serve { flags, daemon: false, .. } => {
+            let config = if flags.database_header { config.with_database_header(true) } else { config };
+            let config = if flags.no_docs { config.with_docs_enabled(false) } else { config };
+            if flags.docs_require_auth { config.with_docs_require_auth(true) } else { config }
+        }
+        _ => config,
+    };
+    // A read-only server must not migrate, reap or purge anything on its way up
+    #[cfg(feature = "http-api")]
+    let read_only = matches!(cli.command, Commands::Serve { flags: ServeFlags { read_only: true, .. }, daemon: false, .. });
+    #[cfg(not(feature = "http-api"))]
+    let read_only = false;
+    let opened = if read_only {
+        ProcessManager::read_only(config.clone()).await
+    } else {
+        ProcessManager::new(config.clone()).await
+    };
+    let opened = match opened {
+        Err(e @ pmr::Error::MigrationRequired(_, _)) if confirm_migration(&e) => {
+            ProcessManager::new(config.clone().with_destructive_migrations(true)).await
+        }
+        opened => opened,
+    };
+    // A damaged database would fail every command, so rebuild it from what is readable
+    // and say loudly what was lost. A read-only server leaves the file alone.
+    let opened = match opened {
+        Err(pmr::Error::DatabaseCorrupt(e)) if !read_only => {
+            eprintln!("Warning: the database at '{}' is damaged: {}", config.database_path.display(), e);
+            let report = Database::salvage(&config.database_path).await?;
+            eprintln!("{}", formatter(&cli).format_salvage_report(&report));
+            ProcessManager::new(config.clone()).await
+        }
+        opened => opened,
+    };
+    // Spelled out rather than debug-printed: these errors name the paths to fix
+    let process_manager = match opened {
+        Ok(process_manager) => process_manager,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(match e {
+                pmr::Error::SchemaTooNew(_, _, _) => EXIT_SCHEMA_TOO_NEW,
+                pmr::Error::MigrationRequired(_, _) => EXIT_MIGRATION_REQUIRED,
+                _ => 1,
+            });
+        }
+    };
+    let notifier = process_manager.notifier().clone();
+    let cancellation = process_manager.cancellation().clone();
 
+    // The server handles its own shutdown; everything else finishes the item in
+    // progress on SIGTERM instead of dying halfway through it
+    #[cfg(feature = "http-api")]
+    let handles_sigterm = matches!(cli.command, Commands::Serve { .. });
+    #[cfg(not(feature = "http-api"))]
+    let handles_sigterm = false;
+    if !handles_sigterm {
+        let mut sigterm = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())?;
+        let cancellation = cancellation.clone();
+        tokio::spawn(async move {
+            if sigterm.recv().await.is_some() {
+                cancellation.cancel();
+            }
+        });
+    }
+
+    let exit_code = match cli.command {
+        Commands::Batch { fail_fast } => run_batch(&process_manager, fail_fast).await?,
+        #[cfg(feature = "http-api")]
+        Commands::Serve { port, daemon: false, .. } => {
+            let api_server = ApiServer::new(process_manager, port)?;
+            println!(
+                "Starting PMR HTTP API server on port {}{}...",
+                port,
+                if api_server.is_read_only() { " (read-only)" } else { "" }
+            );
+            println!("Use 'pmr auth generate <name>' to create API tokens for authentication");
+            api_server.start().await?;
+            0
+        }
+        _ => {
+            let suggests_profiles = matches!(cli.command, Commands::Status { .. } | Commands::Stop { .. });
+            match run_command(cli, &process_manager, &Output::Terminal, None).await {
+                Err(e) => match e.downcast_ref::<pmr::Error>() {
+                    Some(pmr::Error::ProcessNotFound(name)) => {
+                        report_process_not_found(&process_manager, name, suggests_profiles).await;
+                        1
+                    }
+                    _ => return Err(e),
+                },
+                Ok(exit_code) => exit_code,
+            }
+        }
+    };
+
+    // Give queued webhook events a chance to go out before the CLI exits
+    notifier.flush(std::time::Duration::from_secs(5)).await;
+    if let Some(timings) = timings {
+        eprint!("{}", timings.format_table());
+    }
+    if cancellation.is_cancelled() {
+        std::process::exit(EXIT_CANCELLED);
+    }
+    if exit_code != 0 {
+        std::process::exit(exit_code);
+    }
+    Ok(())
+}
+
+/// Say which database a process is missing from, since with several profiles the wrong
+/// one is easily used, and with `suggest_profiles` which other profiles have it
+async fn report_process_not_found(process_manager: &ProcessManager, name: &str, suggest_profiles: bool) {
+    let database = process_manager.config().database_path.clone();
+    let id = match process_manager.database_instance_id().await {
+        Ok(Some(id)) => format!(" (id {})", profiles::short_id(&id)),
+        _ => String::new(),
+    };
+    match process_manager.config().storage.backend {
+        StorageKind::Sqlite => eprintln!("Error: Process '{}' not found in database {}{}", name, database.display(), id),
+        StorageKind::Postgres => eprintln!("Error: Process '{}' not found for host '{}' in the postgres storage{}", name, process_manager.storage_host(), id),
+    }
+    // The name came from the command line, so it parses again
+    if let Some(name) = suggest_profiles.then(|| name.parse::<ProcessName>().ok()).flatten() {
+        let elsewhere = profiles::find_elsewhere(&profiles::discover(&profiles::default_home()), &database, &name).await;
+        for profile in elsewhere {
+            eprintln!("Hint: profile '{}' has a process '{}'; run with {} to use it", profile.name, name, profile.env_hint());
+        }
+    }
+}
+
+fn formatter(cli: &Cli) -> Formatter {
+    Formatter::new(cli.format.clone())
+        .with_quiet(cli.quiet)
+        .with_no_header(cli.no_header)
+}
+
+/// Run one parsed command, returning the exit status it ends with; starts use `resolution`
+/// when it is given, so the commands of a batch share it
+async fn run_command(cli: Cli, process_manager: &ProcessManager, out: &Output, resolution: Option<&ResolutionCache>) -> Result<i32, Box<dyn std::error::Error>> {
+    let formatter = formatter(&cli).with_color(out.is_terminal() && std::io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none());
+    // Commands of a batch come from stdin, so they are never asked about
+    let asks = confirm::should_ask(cli.yes || confirm::assume_yes_from_env(), out.is_terminal() && std::io::stdin().is_terminal());
     match cli.command {
-        Commands::Start { name, command, args, env, workdir, log_dir } => {
-            let env_vars = Commands::parse_env_vars(env);
-            let message = process_manager.start_process(&name, &command, args, env_vars, workdir, log_dir).await?;
-            println!("{}", formatter.format_success_message(&message));
-        }
-        Commands::Stop { name } => {
-            let message = process_manager.stop_process(&name).await?;
-            println!("{}", formatter.format_success_message(&message));
-        }
-        Commands::Restart { name } => {
-            let message = process_manager.restart_process(&name).await?;
-            println!("{}", formatter.format_success_message(&message));
-        }
-        Commands::Delete { name } => {
-            let message = process_manager.delete_process(&name).await?;
-            println!("{}", formatter.format_success_message(&message));
-        }
-        Commands::Clear { all } => {
-            let result = process_manager.clear_processes(all).await?;
-            println!("{}", formatter.format_clear_result(&result));
-        }
-        Commands::List => {
-            let processes = process_manager.list_processes().await?;
-            if processes.is_empty() {
-                println!("{}", formatter.format_empty_list_message("No processes found."));
+        Commands::Start { name, command, args, env, env_allow_duplicates, workdir, log_dir, log_dir_mode, cgroup_memory_max, cgroup_cpu_max, cpu_affinity, umask, description, idempotent, snapshot, verbose, strict, kind, rerun, stop_signal, ephemeral, ephemeral_keep, watch, watch_ignore, watch_debounce_ms, queue_on_pressure, rotate_on_start, no_rotate_on_start } => {
+            let env_vars = match Commands::parse_env_vars(env, env_allow_duplicates) {
+                Ok(env_vars) => env_vars,
+                Err(errors) => {
+                    let duplicates = errors.iter().any(|e| matches!(e.problem, EnvVarProblem::Duplicate { .. }));
+                    errln!(out, "{}", pmr::Error::InvalidEnvVars(errors));
+                    if duplicates {
+                        errln!(out, "Use --env-allow-duplicates to let the last value of a repeated key win");
+                    }
+                    return Ok(1);
+                }
+            };
+            let mut options = StartOptions {
+                working_dir: workdir,
+                log_dir,
+                cgroup_limits: CgroupLimits {
+                    memory_max: cgroup_memory_max.map(|bytes| bytes.as_u64()),
+                    cpu_max_percent: cgroup_cpu_max,
+                },
+                cpu_affinity,
+                umask,
+                description,
+                idempotent,
+                capture_snapshot: snapshot,
+                progress: None,
+                strict,
+                kind,
+                rerun,
+                stop_signal: stop_signal.as_deref().map(parse_signal).transpose()?,
+                log_dir_mode,
+                internal: false,
+                ephemeral_keep: ephemeral.then(|| {
+                    ephemeral_keep.and_then(|keep| keep.as_duration().to_std().ok()).unwrap_or(DEFAULT_EPHEMERAL_KEEP)
+                }),
+                // A single start reports how the window went, so it waits it out
+                check_in_background: false,
+                watch: (!watch.is_empty()).then(|| WatchConfig::new(watch, watch_ignore, watch_debounce_ms)),
+                queue_on_pressure,
+                rotate_on_start: if rotate_on_start { Some(true) } else if no_rotate_on_start { Some(false) } else { None },
+                reservation_token: None,
+                resolution: resolution.cloned(),
+            };
+            // Text output shows phases as they finish; JSON collects them into the final object
+            let phases = Arc::new(Mutex::new(Vec::new()));
+            let json = matches!(cli.format, OutputFormat::Json);
+            let live = out.is_terminal();
+            if verbose {
+                let phases = phases.clone();
+                options.progress = Some(StartProgress::new(move |report| {
+                    if live && !json {
+                        println!("{}", report);
+                    }
+                    phases.lock().unwrap().push(report.clone());
+                }));
+            }
+            let started = process_manager.start_with_options(&name, &command, args, env_vars, options).await;
+            let phases = std::mem::take(&mut *phases.lock().unwrap());
+            if verbose && !live && !json {
+                for report in &phases {
+                    outln!(out, "{}", report);
+                }
+            }
+            let outcome = match started {
+                Ok(outcome) => outcome,
+                Err(e @ pmr::Error::DefinitionMismatch(_, _)) => {
+                    errln!(out, "{}", formatter.format_start_failure(&e.to_string(), &phases));
+                    return Ok(EXIT_DEFINITION_MISMATCH);
+                }
+                Err(e @ pmr::Error::StartExited(_, _, _)) => {
+                    errln!(out, "{}", formatter.format_start_failure(&e.to_string(), &phases));
+                    return Ok(EXIT_START_EXITED);
+                }
+                // Spelled out rather than debug-printed: the message says what to change
+                Err(e @ pmr::Error::StartTooLarge(_)) => {
+                    errln!(out, "{}", formatter.format_start_failure(&e.to_string(), &phases));
+                    return Ok(1);
+                }
+                Err(e) if verbose && json => {
+                    errln!(out, "{}", formatter.format_start_failure(&e.to_string(), &phases));
+                    return Ok(1);
+                }
+                Err(e) => return Err(e.into()),
+            };
+            outln!(out, "{}", formatter.format_start_outcome(&outcome, &phases));
+        }
+        Commands::Stop { name, signal, escalate } => {
+            let options = StopOptions {
+                signal: signal.as_deref().map(parse_signal).transpose()?,
+                escalate,
+            };
+            let outcome = process_manager.stop_with_options(&name, options).await?;
+            outln!(out, "{}", formatter.format_stop_outcome(&outcome));
+        }
+        Commands::Restart { name, reset_logs, only_if_changed, force } => {
+            let options = RestartOptions { reset_logs, only_if_changed, force };
+            let outcome = process_manager.restart_with_options(&name, options).await?;
+            outln!(out, "{}", formatter.format_restart_outcome(&outcome));
+        }
+        Commands::Delete { name, hard } => {
+            if asks {
+                // A missing process is left for the delete to report
+                let confirmation = process_manager.get_process_status(&name).await.ok().and_then(|process| Confirmation::delete(&process, hard));
+                if confirmation.is_some_and(|confirmation| !ask(&confirmation)) {
+                    errln!(out, "Cancelled; process '{}' was not deleted", name);
+                    return Ok(1);
+                }
+            }
+            let outcome = process_manager
+                .delete_with_options(&name, DeleteOptions { hard })
+                .await?;
+            outln!(out, "{}", formatter.format_delete_outcome(&outcome));
+        }
+        Commands::Update { name, workdir, description } => {
+            if let Some(workdir) = workdir {
+                let process = process_manager.update_working_dir(&name, &workdir).await?;
+                outln!(out, "{}", formatter.format_success_message(&format!(
+                    "Process '{}' will start in {} from its next restart",
+                    name, process.working_dir
+                )));
+            }
+            if let Some(description) = description {
+                let message = match process_manager.update_description(&name, &description).await?.description {
+                    Some(description) => format!("Process '{}' is now described as: {}", name, description),
+                    None => format!("Process '{}' no longer has a description", name),
+                };
+                outln!(out, "{}", formatter.format_success_message(&message));
+            }
+        }
+        Commands::Undelete { name } => {
+            let outcome = process_manager.undelete(&name).await?;
+            outln!(out, "{}", formatter.format_undelete_outcome(&outcome));
+        }
+        Commands::Exists { name } => {
+            let check = process_manager.check_name(&name).await?;
+            outln!(out, "{}", formatter.format_name_check(&check));
+            if !check.taken() {
+                return Ok(2);
+            }
+        }
+        Commands::Clear { all, purge_deleted } => {
+            if asks && !purge_deleted {
+                let targets = process_manager.clear_targets(all).await?;
+                if Confirmation::clear(&targets, all).is_some_and(|confirmation| !ask(&confirmation)) {
+                    errln!(out, "Cancelled; nothing was cleared");
+                    return Ok(1);
+                }
+            }
+            let result = if purge_deleted {
+                process_manager.purge_deleted_processes().await?
+            } else {
+                process_manager.clear_processes(all).await?
+            };
+            outln!(out, "{}", formatter.format_clear_result(&result));
+        }
+        Commands::List { all_hosts: true, refresh, .. } => {
+            let listings = process_manager.list_all_hosts(refresh).await?;
+            let output = formatter.format_host_listings(&listings);
+            if !output.is_empty() {
+                outln!(out, "{}", output);
+            }
+        }
+        Commands::List { all_profiles: true, .. } => {
+            let found = profiles::discover(&profiles::default_home());
+            let listings = profiles::scan(&found, &process_manager.config().database_path).await;
+            let output = formatter.format_profile_listings(&listings);
+            if !output.is_empty() {
+                outln!(out, "{}", output);
+            }
+        }
+        Commands::List { names_only: true, prefix, .. } => {
+            let names = process_manager.process_names(prefix.as_deref()).await?;
+            let output = formatter.format_process_names(&names);
+            if !output.is_empty() {
+                outln!(out, "{}", output);
+            }
+        }
+        Commands::List { counts_only: true, .. } => {
+            let counts = process_manager.process_counts().await?;
+            outln!(out, "{}", formatter.format_process_counts(&counts));
+        }
+        Commands::List { jobs: true, refresh, search, .. } => {
+            let mut jobs: Vec<_> = list_processes(process_manager, refresh, search.as_deref())
+                .await?
+                .into_iter()
+                .filter(|process| process.kind == ProcessKind::Job)
+                .collect();
+            for job in &mut jobs {
+                job.recent_runs = process_manager.process_runs(&job.name, RECENT_RUNS).await?;
+            }
+            let output = if jobs.is_empty() {
+                formatter.format_empty_list_message("No jobs found.")
+            } else {
+                formatter.format_job_list(&jobs, chrono::Utc::now())
+            };
+            if !output.is_empty() {
+                outln!(out, "{}", output);
+            }
+        }
+        Commands::List { refresh, full, search: None, .. } if formatter.lists_line_by_line() => {
+            // Printed as the records are read, so large tables start printing at once
+            let mut processes = process_manager.stream_processes(refresh);
+            while let Some(process) = processes.next().await {
+                let mut process = process?;
+                if !full {
+                    limits::truncate_for_display(&mut process);
+                }
+                outln!(out, "{}", formatter.format_process_list_line(&process));
+            }
+        }
+        Commands::List { refresh, full, search, wide, .. } => {
+            let formatter = formatter.with_wide(wide);
+            let mut processes = list_processes(process_manager, refresh, search.as_deref()).await?;
+            if !full {
+                processes.iter_mut().for_each(limits::truncate_for_display);
+            }
+            let output = if processes.is_empty() {
+                formatter.format_empty_list_message("No processes found.")
             } else {
-                println!("{}", formatter.format_process_list(&processes));
+                formatter.format_process_list(&processes)
+            };
+            // Quiet output for an empty list is nothing at all, not a blank line
+            if !output.is_empty() {
+                outln!(out, "{}", output);
             }
         }
-        Commands::Status { name } => {
-            let process = process_manager.get_process_status(&name).await?;
-            println!("{}", formatter.format_process_status(&process));
+        Commands::Stats { oneline, ascii, unicode, watch } => {
+            let oneline = oneline.then(|| OnelineSymbols::select(ascii, unicode, std::io::stdout().is_terminal()));
+            match watch {
+                Some(interval) => watch_stats(process_manager, &formatter, &cli.format, oneline, interval, out).await?,
+                None => outln!(out, "{}", stats_view(process_manager, &formatter, oneline).await?),
+            }
+        }
+        Commands::Wait { names, timeout } => {
+            if !wait_until_running(process_manager, &names, timeout, out).await? {
+                return Ok(1);
+            }
         }
-        Commands::Logs { name, lines, rotated, rotate } => {
-            if rotate {
+        Commands::Watch { name, history } => {
+            if history {
+                let history = process_manager.get_watch_restart_history(&name).await?;
+                outln!(out, "{}", formatter.format_watch_history(&history, &name));
+            } else {
+                watch_files(process_manager, &formatter, &name, out).await?;
+            }
+        }
+        Commands::History { name, runs: _ } => {
+            let runs = process_manager.process_runs(&name, RUN_HISTORY_LIMIT).await?;
+            outln!(out, "{}", formatter.format_run_history(&runs, &name));
+        }
+        Commands::Status { name, watch, drift, snapshot, notes, refresh, full } => {
+            if let Some(interval) = watch {
+                watch_process_status(process_manager, &formatter, &cli.format, &name, interval, out).await?;
+            } else {
+                let mut process = process_manager.get_process_status_with_refresh(&name, refresh).await?;
+                if drift {
+                    process.drift = Some(process_manager.process_drift(&process).await);
+                }
+                process.recent_runs = process_manager.process_runs(&name, RECENT_RUNS).await?;
+                if !snapshot {
+                    process.start_snapshot = None;
+                } else if process.start_snapshot.is_none() {
+                    errln!(out, "Note: no start snapshot was recorded for '{}'; start it with --snapshot", name);
+                }
+                // Text output previews the latest note; JSON carries notes only when asked for
+                let text = matches!(cli.format, OutputFormat::Text);
+                let mut all_notes = Vec::new();
+                if notes || text {
+                    all_notes = process_manager.get_process_notes(&name).await?;
+                    process.notes = if text { all_notes.last().cloned().into_iter().collect() } else { all_notes.clone() };
+                }
+                if !full {
+                    limits::truncate_for_display(&mut process);
+                }
+                outln!(out, "{}", formatter.format_process_status(&process));
+                if notes && text {
+                    outln!(out, "{}", formatter.format_notes(&all_notes, &name));
+                }
+            }
+        }
+        Commands::Annotate { name, text } => {
+            let note = process_manager.annotate_process(&name, &current_user(), &text).await?;
+            outln!(out, "{}", formatter.format_success_message(&format!("Added note {} to process '{}'", note.id, name)));
+        }
+        Commands::Notes { name, delete } => {
+            if let Some(note_id) = delete {
+                process_manager.delete_process_note(&name, note_id).await?;
+                outln!(out, "{}", formatter.format_success_message(&format!("Deleted note {} from process '{}'", note_id, name)));
+            } else {
+                let notes = process_manager.get_process_notes(&name).await?;
+                outln!(out, "{}", formatter.format_notes(&notes, &name));
+            }
+        }
+        Commands::Inspect { name } => {
+            let report = process_manager.inspect(&name).await?;
+            outln!(out, "{}", formatter.format_inspect_report(&report));
+        }
+        Commands::Doctor { fix_permissions } => {
+            let flapping = process_manager.flapping_processes().await?;
+            let zombies = process_manager.zombie_processes().await?;
+            let mut loose = process_manager.loose_permissions();
+            let mut fixed = Vec::new();
+            if fix_permissions {
+                for path in std::mem::take(&mut loose) {
+                    match path.fix() {
+                        Ok(()) => fixed.push(path),
+                        Err(e) => {
+                            eprintln!("Error: failed to restrict {}: {}", path.path.display(), e);
+                            loose.push(path);
+                        }
+                    }
+                }
+            }
+            outln!(out, "{}", formatter.format_doctor_report(&flapping, &zombies, &loose, &fixed));
+            // Zombies left to init go away without anyone's help
+            if !flapping.is_empty() || zombies.iter().any(|zombie| !zombie.reparented) || !loose.is_empty() {
+                return Ok(1);
+            }
+        }
+        Commands::Drift { name } => {
+            let reports = process_manager.check_drift(name.as_ref()).await?;
+            outln!(out, "{}", formatter.format_drift_reports(&reports));
+        }
+        Commands::Logs { name, lines, rotated, merge_rotated, rotate, rotation_history, archive, collapse_repeats, tail_bytes, allow_partial, since, reindex } => {
+            if let Some(path) = archive {
+                let message = process_manager.archive_process_logs(&name, &path).await?;
+                outln!(out, "{}", formatter.format_success_message(&message));
+            } else if reindex {
+                let message = process_manager.reindex_process_logs(&name).await?;
+                outln!(out, "{}", formatter.format_success_message(&message));
+            } else if rotate {
                 let message = process_manager.rotate_process_logs(&name).await?;
-                println!("{}", formatter.format_success_message(&message));
+                outln!(out, "{}", formatter.format_success_message(&message));
+            } else if rotation_history {
+                let history = process_manager.get_log_rotation_history(&name).await?;
+                outln!(out, "{}", formatter.format_rotation_history(&history, &name));
             } else if rotated {
-                let rotated_logs = process_manager.get_rotated_logs(&name).await?;
-                println!("{}", formatter.format_rotated_logs(&rotated_logs, &name));
+                let rotated_logs = process_manager.rotated_logs(&name).await?;
+                if rotated_logs.is_empty() || matches!(cli.format, OutputFormat::Json) {
+                    outln!(out, "{}", formatter.format_rotated_logs(&rotated_logs, &name));
+                } else {
+                    // One archive at a time, line by line, so large archives are never held in memory
+                    for log in &rotated_logs {
+                        outln!(out, "{}", rotated_log_header(log));
+                        if !log.compressed {
+                            print_log_file(out, &log.path).await?;
+                        }
+                    }
+                }
             } else {
-                let logs = process_manager.get_process_logs(&name, lines).await?;
-                println!("{}", formatter.format_process_logs(&logs, &name));
+                let (lines, warning) = Commands::clamp_log_lines(lines, DEFAULT_MAX_LOG_LINES);
+                if let Some(warning) = warning {
+                    errln!(out, "Warning: {}", warning);
+                }
+                let (tail_bytes, warning) = Commands::clamp_tail_bytes(tail_bytes, DEFAULT_MAX_LOGS_RESPONSE_BYTES as u64);
+                if let Some(warning) = warning {
+                    errln!(out, "Warning: {}", warning);
+                }
+                let options = LogOptions { lines, collapse_repeats, tail_bytes, allow_partial, since };
+                let logs = if merge_rotated {
+                    process_manager.read_merged_process_logs(&name, &options).await
+                } else {
+                    process_manager.read_process_logs(&name, &options).await
+                };
+                let logs = match logs {
+                    Ok(logs) => logs,
+                    // Spelled out rather than debug-printed: missing and unreadable logs need different fixes
+                    Err(e @ pmr::Error::LogUnreadable(_, _)) => {
+                        errln!(out, "Error: {}", e);
+                        return Ok(1);
+                    }
+                    Err(e) => return Err(e.into()),
+                };
+                outln!(out, "{}", formatter.format_process_logs(&logs, &name));
+                if logs.contains_invalid_utf8 && matches!(cli.format, OutputFormat::Text) {
+                    errln!(out, "Note: the log holds bytes that are not valid UTF-8; they are shown as U+FFFD (�)");
+                }
             }
         }
         #[cfg(feature = "http-api")]
-        Commands::Serve { port, daemon } => {
-            if daemon {
-                handle_serve_daemon(port, &process_manager, &formatter).await?;
-            } else {
-                let api_server = ApiServer::new(process_manager, port)?;
-                println!("Starting PMR HTTP API server on port {}...", port);
-                println!("Use 'pmr auth generate <name>' to create API tokens for authentication");
-                api_server.start().await?;
-            }
+        Commands::Serve { port, daemon: true, flags, .. } => {
+            handle_serve_daemon(port, flags, process_manager, &formatter, out).await?;
         }
         #[cfg(feature = "http-api")]
+        Commands::Serve { daemon: false, .. } => unreachable!("the foreground server is run by main"),
+        #[cfg(feature = "http-api")]
         Commands::ServeStatus => {
-            handle_serve_status(&process_manager, &formatter).await?;
+            handle_serve_status(process_manager, &formatter, out).await?;
         }
         #[cfg(feature = "http-api")]
         Commands::ServeStop => {
-            handle_serve_stop(&process_manager, &formatter).await?;
+            handle_serve_stop(process_manager, &formatter, out).await?;
         }
         #[cfg(feature = "http-api")]
         Commands::ServeRestart { port } => {
-            handle_serve_restart(port, &process_manager, &formatter).await?;
+            handle_serve_restart(port, process_manager, &formatter, out).await?;
+        }
+        #[cfg(feature = "http-api")]
+        Commands::ServeLogs { lines, follow } => {
+            handle_serve_logs(lines, follow, process_manager, &formatter, &cli.format, out).await?;
+        }
+        Commands::Config { command: ConfigCommands::Validate { config } } => {
+            if !validate_config(config.as_deref(), &formatter, out) {
+                return Ok(1);
+            }
+        }
+        Commands::Config { command: ConfigCommands::Show } => {
+            outln!(out, "{}", formatter.format_config_paths(&process_manager.config().paths()));
+        }
+        Commands::Db { command: DbCommands::Info { check_compat } } => {
+            let info = Database::inspect(&process_manager.config().paths().database).await?;
+            outln!(out, "{}", formatter.format_schema_info(&info));
+            if check_compat && !info.is_compatible() {
+                return Ok(1);
+            }
+        }
+        Commands::Db { command: DbCommands::Salvage { path } } => {
+            if path == process_manager.config().database_path {
+                return Err(pmr::Error::Other("Cannot salvage the database this pmr has open".to_string()).into());
+            }
+            let report = Database::salvage(&path).await?;
+            outln!(out, "{}", formatter.format_salvage_report(&report));
+        }
+        Commands::Generate { command: GenerateCommands::Systemd { serve: _, port, process, user, output } } => {
+            let context = UnitContext {
+                pmr_binary: std::env::current_exe()?,
+                working_dir: std::env::current_dir()?,
+                paths: process_manager.config().paths(),
+                scope: if user { UnitScope::User } else { UnitScope::System { user: current_user() } },
+            };
+            let unit = match process {
+                Some(name) => {
+                    let process = process_manager.get_process_status_with_refresh(&name, StatusRefresh::Stored).await?;
+                    systemd::process_unit(&context, &process)?
+                }
+                None if cfg!(feature = "http-api") => systemd::serve_unit(&context, port)?,
+                None => return Err(pmr::Error::Other("This pmr was built without the HTTP API; 'pmr serve' is not available".to_string()).into()),
+            };
+            match output {
+                Some(path) => {
+                    std::fs::write(&path, &unit)?;
+                    outln!(out, "Wrote {}", path.display());
+                }
+                None => outln!(out, "{}", unit.trim_end_matches('\n')),
+            }
         }
+        Commands::Batch { .. } => unreachable!("batches are run by main and cannot be nested"),
         #[cfg(feature = "http-api")]
         Commands::Auth { command } => {
-            handle_auth_command(command, &process_manager).await?;
+            handle_auth_command(command, process_manager, out).await?;
+        }
+    }
+
+    Ok(0)
+}
+
+/// The processes `pmr list` shows, only those matching `search` when it is given
+async fn list_processes(process_manager: &ProcessManager, refresh: StatusRefresh, search: Option<&str>) -> pmr::Result<Vec<pmr::database::ProcessRecord>> {
+    match search {
+        Some(text) => process_manager.search_processes(text, refresh).await,
+        None => process_manager.list_processes_with_refresh(refresh).await,
+    }
+}
+
+/// Run the commands of a batch read from stdin, printing one JSON result line for each;
+/// returns 1 when any of them failed
+async fn run_batch(process_manager: &ProcessManager, fail_fast: bool) -> Result<i32, Box<dyn std::error::Error>> {
+    let input = std::io::read_to_string(std::io::stdin())?;
+    // Starts of the same command resolve and hash it once for the whole batch
+    let resolution = process_manager.resolution_cache();
+    let mut failed = false;
+    for command in parse_batch(&input)? {
+        // As for a single command, SIGTERM lets the command in progress finish
+        if process_manager.cancellation().is_cancelled() {
+            break;
+        }
+        let result = run_batch_command(process_manager, command, &resolution).await;
+        println!("{}", serde_json::to_string(&result)?);
+        if !result.success {
+            failed = true;
+            if fail_fast {
+                break;
+            }
+        }
+    }
+    Ok(if failed { 1 } else { 0 })
+}
+
+async fn run_batch_command(process_manager: &ProcessManager, command: BatchCommand, resolution: &ResolutionCache) -> BatchResult {
+    let out = Output::captured();
+    let argv = match command.argv {
+        Ok(argv) => argv,
+        Err(e) => {
+            errln!(out, "Cannot parse line {}: {}", command.index, e);
+            return batch_result(command.index, Vec::new(), EXIT_USAGE, out);
+        }
+    };
+    let exit_code = match Cli::try_parse_from(std::iter::once("pmr").chain(argv.iter().map(String::as_str))) {
+        // --help and --version end up here too, with exit status 0 and output on stdout
+        Err(e) => {
+            let rendered = e.render().to_string();
+            if e.use_stderr() {
+                errln!(out, "{}", rendered.trim_end());
+            } else {
+                outln!(out, "{}", rendered.trim_end());
+            }
+            e.exit_code()
+        }
+        Ok(cli) => match batch_rejection(&cli.command) {
+            Some(reason) => {
+                errln!(out, "Cannot run in a batch: {}", reason);
+                EXIT_USAGE
+            }
+            None => {
+                if cli.timings {
+                    errln!(out, "Note: --timings is ignored inside a batch; pass it to 'pmr batch' instead");
+                }
+                match run_command(cli, process_manager, &out, Some(resolution)).await {
+                    Ok(exit_code) => exit_code,
+                    Err(e) => {
+                        errln!(out, "Error: {}", e);
+                        1
+                    }
+                }
+            }
+        },
+    };
+    batch_result(command.index, argv, exit_code, out)
+}
+
+/// Print a log file line by line; invalid UTF-8 is replaced rather than rejected
+async fn print_log_file(out: &Output, path: &str) -> std::io::Result<()> {
+    use tokio::io::AsyncBufReadExt;
+
+    let file = match tokio::fs::File::open(path).await {
+        Ok(file) => file,
+        // Rotated away since it was listed
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    let mut reader = tokio::io::BufReader::new(file);
+    let mut line = Vec::new();
+    while reader.read_until(b'\n', &mut line).await? > 0 {
+        if line.last() == Some(&b'\n') {
+            line.pop();
+        }
+        outln!(out, "{}", String::from_utf8_lossy(&line));
+        line.clear();
+    }
+    Ok(())
+}
+
+fn batch_result(index: usize, command: Vec<String>, exit_code: i32, out: Output) -> BatchResult {
+    let (output, stderr) = out.into_captured();
+    BatchResult {
+        index,
+        command,
+        success: exit_code == 0,
+        exit_code,
+        output,
+        stderr,
+    }
+}
+
+/// Why a command cannot run inside a batch, if it cannot
+fn batch_rejection(command: &Commands) -> Option<&'static str> {
+    match command {
+        Commands::Batch { .. } => Some("batches cannot be nested"),
+        Commands::Status { watch: Some(_), .. } => Some("'status --watch' does not finish"),
+        Commands::Stats { watch: Some(_), .. } => Some("'stats --watch' does not finish"),
+        Commands::Watch { history: false, .. } => Some("'watch' does not finish"),
+        #[cfg(feature = "http-api")]
+        Commands::Serve { daemon: false, .. } => Some("'serve' does not finish; use 'serve --daemon'"),
+        #[cfg(feature = "http-api")]
+        Commands::ServeLogs { follow: true, .. } => Some("'serve-logs --follow' does not finish"),
+        _ => None,
+    }
+}
+
+/// Exit status after SIGTERM cut an operation short, as a shell reports for a signal
+const EXIT_CANCELLED: i32 = 130;
+
+/// Exit status of an idempotent start that found the process with a different definition
+const EXIT_DEFINITION_MISMATCH: i32 = 3;
+
+/// Exit status of a strict start whose process exited within the start window
+const EXIT_START_EXITED: i32 = 4;
+
+/// Exit status of a command line that cannot be run at all, as clap uses for usage errors
+const EXIT_USAGE: i32 = 2;
+
+/// Exit status when the database was written by a newer pmr
+const EXIT_SCHEMA_TOO_NEW: i32 = 5;
+
+/// Exit status when the database needs migrations that rebuild tables and none were allowed
+const EXIT_MIGRATION_REQUIRED: i32 = 6;
+
+/// Ask on the terminal whether to run the migrations `error` lists; false without a terminal
+fn confirm_migration(error: &pmr::Error) -> bool {
+    if !std::io::stdin().is_terminal() || !std::io::stderr().is_terminal() {
+        return false;
+    }
+    ask(&Confirmation {
+        summary: error.to_string(),
+        question: "Apply these migrations now?".to_string(),
+        expected: Expected::Yes,
+    })
+}
+
+/// Ask `confirmation` on the terminal; a failed read declines
+fn ask(confirmation: &Confirmation) -> bool {
+    confirmation.ask(&mut std::io::stdin().lock(), &mut std::io::stderr()).unwrap_or(false)
+}
+
+/// Login name of the user running pmr, used as the author of notes
+fn current_user() -> String {
+    let uid = unsafe { libc::getuid() };
+    let passwd = unsafe { libc::getpwuid(uid) };
+    if !passwd.is_null() {
+        let name = unsafe { std::ffi::CStr::from_ptr((*passwd).pw_name) };
+        return name.to_string_lossy().into_owned();
+    }
+    std::env::var("USER").unwrap_or_else(|_| format!("uid {}", uid))
+}
+
+/// Print the findings for the config file at `path` (default location when None),
+/// returning whether it is free of errors
+fn validate_config(path: Option<&std::path::Path>, formatter: &Formatter, out: &Output) -> bool {
+    let path = path.map(std::path::Path::to_path_buf).unwrap_or_else(Config::default_file_path);
+    // A missing file is only fine when it was not asked for by name
+    let loaded = if path.exists() || path != Config::default_file_path() {
+        Config::new().with_file(&path)
+    } else {
+        Ok(Config::new())
+    };
+    let issues = match loaded {
+        Ok(config) => config.validate(),
+        Err(e) => vec![ConfigIssue::error("file", e.to_string())],
+    };
+    outln!(out, "{}", formatter.format_config_issues(&path, &issues));
+    !issues.iter().any(ConfigIssue::is_error)
+}
+
+/// Refresh a single process view until Ctrl+C or SIGTERM
+async fn watch_process_status(
+    process_manager: &ProcessManager,
+    formatter: &Formatter,
+    format: &OutputFormat,
+    name: &ProcessName,
+    interval_secs: u64,
+    out: &Output,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // Only redraw in place on an interactive terminal; pipes get one snapshot per interval
+    let redraw = std::io::stdout().is_terminal() && matches!(format, OutputFormat::Text);
+    let interval = std::time::Duration::from_secs(interval_secs.max(1));
+    let mut watch = StatusWatch::new();
+
+    loop {
+        let process = process_manager.get_process_status(name).await?;
+        let output = match format {
+            OutputFormat::Text => {
+                let usage = process.pid.and_then(read_resource_usage);
+                let tail = process_manager.get_process_logs(name, Some(5)).await.unwrap_or_default();
+                watch.render(formatter, &process, usage.as_ref(), &tail, chrono::Local::now())
+            }
+            OutputFormat::Json | OutputFormat::Porcelain => formatter.format_process_status(&process),
+        };
+
+        if redraw {
+            print!("\x1b[2J\x1b[H");
+        }
+        outln!(out, "{}", output);
+
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => break,
+            _ = process_manager.cancellation().cancelled() => break,
+            _ = tokio::time::sleep(interval) => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// The output of `pmr stats`, or its one-line summary written with these symbols
+async fn stats_view(process_manager: &ProcessManager, formatter: &Formatter, oneline: Option<OnelineSymbols>) -> pmr::Result<String> {
+    Ok(match oneline {
+        Some(symbols) => Formatter::format_stats_oneline(&process_manager.process_counts().await?, symbols),
+        None => formatter.format_stats(&process_manager.stats().await?),
+    })
+}
+
+/// Print the stats every `interval_secs` until Ctrl+C or SIGTERM
+async fn watch_stats(
+    process_manager: &ProcessManager,
+    formatter: &Formatter,
+    format: &OutputFormat,
+    oneline: Option<OnelineSymbols>,
+    interval_secs: u64,
+    out: &Output,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // Like `status --watch`; one-line summaries are appended so status bars can read them
+    let redraw = std::io::stdout().is_terminal() && matches!(format, OutputFormat::Text) && oneline.is_none();
+    let interval = std::time::Duration::from_secs(interval_secs.max(1));
+
+    loop {
+        let output = stats_view(process_manager, formatter, oneline).await?;
+        if redraw {
+            print!("\x1b[2J\x1b[H");
+        }
+        outln!(out, "{}", output);
+
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => break,
+            _ = process_manager.cancellation().cancelled() => break,
+            _ = tokio::time::sleep(interval) => {}
         }
     }
 
     Ok(())
 }
 
+/// Restart a process whenever its watched paths change, until Ctrl+C or SIGTERM
+async fn watch_files(
+    process_manager: &ProcessManager,
+    formatter: &Formatter,
+    name: &ProcessName,
+    out: &Output,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let process = process_manager.get_process_status(name).await?;
+    let Some(watch) = &process.watch else {
+        return Err(format!("Process '{}' was not started with --watch", name).into());
+    };
+    outln!(out, "Watching {} for '{}' (Ctrl+C to stop)", watch.paths.join(", "), name);
+
+    // Stopping waits for a restart under way, which would otherwise leave the record deleted
+    let cancellation = Cancellation::new();
+    let interrupt = {
+        let cancellation = cancellation.clone();
+        let stopping = process_manager.cancellation().clone();
+        tokio::spawn(async move {
+            tokio::select! {
+                _ = tokio::signal::ctrl_c() => {}
+                _ = stopping.cancelled() => {}
+            }
+            cancellation.cancel();
+        })
+    };
+    let watched = file_watch::watch_process(process_manager, name, &cancellation, |restart| match restart {
+        Ok(restart) => outln!(out, "{}", formatter.format_watch_restart(restart)),
+        Err((path, e)) => errln!(out, "Restart after {} changed failed: {}", path, e),
+    })
+    .await;
+    interrupt.abort();
+    watched?;
+    Ok(())
+}
+
+/// Number of log lines shown for each process that did not become ready
+const WAIT_LOG_TAIL_LINES: usize = 10;
+
+/// Wait for processes to be running, printing progress; returns whether all of them are
+async fn wait_until_running(
+    process_manager: &ProcessManager,
+    names: &[ProcessName],
+    timeout_secs: u64,
+    out: &Output,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    let total = names.len();
+    let mut ready = 0;
+    let wait = process_manager.wait_for_status(
+        names,
+        ProcessStatus::Running,
+        std::time::Duration::from_secs(timeout_secs),
+        |process| {
+            ready += 1;
+            outln!(out, "{} is running ({}/{})", process.name, ready, total);
+        },
+    );
+
+    // Waiting never starts or stops anything, so interrupting it leaves processes as they are
+    let report = tokio::select! {
+        report = wait => report?,
+        _ = tokio::signal::ctrl_c() => {
+            errln!(out, "Interrupted; processes are left running");
+            return Ok(false);
+        }
+        _ = process_manager.cancellation().cancelled() => {
+            errln!(out, "Terminated; processes are left running");
+            return Ok(true);
+        }
+    };
+
+    if report.all_ready() {
+        outln!(out, "All {} processes are running", total);
+        return Ok(true);
+    }
+
+    errln!(out, "{} of {} processes are not running after {}:", report.not_ready.len(), total, human_duration(std::time::Duration::from_secs(timeout_secs)));
+    for process in &report.not_ready {
+        errln!(out, "\n{} ({})", process.name, process.status);
+        let tail = process_manager
+            .get_process_logs(&process.name, Some(WAIT_LOG_TAIL_LINES))
+            .await
+            .unwrap_or_default();
+        if tail.trim().is_empty() {
+            errln!(out, "  (no log output)");
+        }
+        for line in tail.lines() {
+            errln!(out, "  {}", line);
+        }
+    }
+    Ok(false)
+}
+
 #[cfg(feature = "http-api")]
-async fn handle_auth_command(command: AuthCommands, process_manager: &ProcessManager) -> Result<(), Box<dyn std::error::Error>> {
+async fn handle_auth_command(command: AuthCommands, process_manager: &ProcessManager, out: &Output) -> Result<(), Box<dyn std::error::Error>> {
     let database = process_manager.get_database();
     let auth_manager = AuthManager::new(database);
 
     match command {
-        AuthCommands::Generate { name, expires_in } => {
-            let token = auth_manager.generate_token(name.clone(), expires_in).await?;
-            println!("Generated new API token:");
-            println!("Name: {}", token.name);
-            println!("Token: {}", token.token);
-            println!("Created: {}", token.created_at.format("%Y-%m-%d %H:%M:%S UTC"));
+        AuthCommands::Generate { name, expires_in, admin } => {
+            let token = if admin {
+                auth_manager.generate_admin_token(name.clone(), expires_in).await?
+            } else {
+                auth_manager.generate_token(name.clone(), expires_in).await?
+            };
+            outln!(out, "Generated new API token:");
+            outln!(out, "Name: {}", token.name);
+            outln!(out, "ID: {}", token.public_id);
+            outln!(out, "Token: {}", token.token);
+            outln!(out, "Scope: {}", token_scope(&token));
+            outln!(out, "Created: {}", token.created_at.format("%Y-%m-%d %H:%M:%S UTC"));
             if let Some(expires_at) = token.expires_at {
-                println!("Expires: {}", expires_at.format("%Y-%m-%d %H:%M:%S UTC"));
+                outln!(out, "Expires: {}", expires_at.format("%Y-%m-%d %H:%M:%S UTC"));
             } else {
-                println!("Expires: Never");
+                outln!(out, "Expires: Never");
             }
-            println!();
-            println!("Use this token in API requests:");
-            println!("Authorization: Bearer {}", token.token);
+            outln!(out);
+            outln!(out, "Use this token in API requests:");
+            outln!(out, "Authorization: Bearer {}", token.token);
         }
         AuthCommands::List => {
             let tokens = auth_manager.list_tokens().await?;
             if tokens.is_empty() {
-                println!("No API tokens found.");
+                outln!(out, "No API tokens found.");
             } else {
-                println!("{:<20} {:<10} {:<20} {:<20}", "NAME", "STATUS", "CREATED", "EXPIRES");
-                println!("{}", "-".repeat(80));
+                outln!(out, "{:<14} {:<20} {:<10} {:<20} {:<20}", "ID", "NAME", "STATUS", "CREATED", "EXPIRES");
+                outln!(out, "{}", "-".repeat(95));
                 for token in tokens {
                     let status = if token.is_active { "active" } else { "revoked" };
                     let expires = token.expires_at
                         .map(|e| e.format("%Y-%m-%d %H:%M:%S").to_string())
                         .unwrap_or_else(|| "Never".to_string());
-                    println!("{:<20} {:<10} {:<20} {:<20}",
-                        token.name,
+                    outln!(out, "{:<14} {} {:<10} {:<20} {:<20}",
+                        token.public_id,
+                        fit(&token.name, 20),
                         status,
                         token.created_at.format("%Y-%m-%d %H:%M:%S"),
                         expires
@@ -139,10 +1138,37 @@ async fn handle_auth_command(command: AuthCommands, process_manager: &ProcessMan
                 }
             }
         }
+        AuthCommands::Show { token } => {
+            let token = auth_manager.find_token(&token).await?;
+            let now = chrono::Utc::now();
+            let status = match token.expires_at {
+                _ if !token.is_active => "revoked",
+                Some(expires_at) if expires_at <= now => "expired",
+                _ => "active",
+            };
+            outln!(out, "Name: {}", token.name);
+            outln!(out, "ID: {}", token.public_id);
+            outln!(out, "Status: {}", status);
+            outln!(out, "Scope: {}", token_scope(&token));
+            outln!(out, "Created: {}", token.created_at.format("%Y-%m-%d %H:%M:%S UTC"));
+            match token.expires_at {
+                Some(expires_at) if expires_at > now => outln!(out, 
+                    "Expires: {} (in {})",
+                    expires_at.format("%Y-%m-%d %H:%M:%S UTC"),
+                    format_duration(expires_at - now)
+                ),
+                Some(expires_at) => outln!(out, 
+                    "Expires: {} ({} ago)",
+                    expires_at.format("%Y-%m-%d %H:%M:%S UTC"),
+                    format_duration(now - expires_at)
+                ),
+                None => outln!(out, "Expires: Never"),
+            }
+        }
         AuthCommands::Revoke { token } => {
             match auth_manager.revoke_token(&token).await {
-                Ok(_) => println!("Token revoked successfully"),
-                Err(e) => println!("Error revoking token: {}", e),
+                Ok(_) => outln!(out, "Token revoked successfully"),
+                Err(e) => outln!(out, "Error revoking token: {}", e),
             }
         }
     }
@@ -150,23 +1176,28 @@ async fn handle_auth_command(command: AuthCommands, process_manager: &ProcessMan
     Ok(())
 }
 
+/// What a token may do, as `pmr auth` shows it
 #[cfg(feature = "http-api")]
-const HTTP_SERVER_PROCESS_NAME: &str = "__pmr_http_server__";
+fn token_scope(token: &pmr::database::ApiToken) -> &'static str {
+    if token.admin { "admin (includes inspect)" } else { "standard" }
+}
 
 #[cfg(feature = "http-api")]
 async fn handle_serve_daemon(
     port: u16,
+    flags: ServeFlags,
     process_manager: &ProcessManager,
     formatter: &Formatter,
+    out: &Output,
 ) -> Result<(), Box<dyn std::error::Error>> {
     // Check if HTTP server is already running
-    if let Ok(process) = process_manager.get_process_status(HTTP_SERVER_PROCESS_NAME).await {
+    if let Ok(process) = process_manager.get_process_status(&http_server_process_name()).await {
         if process.status == pmr::database::ProcessStatus::Running {
-            println!("{}", formatter.format_error_message("HTTP server is already running. Use 'pmr serve-status' to check status or 'pmr serve-stop' to stop it."));
+            outln!(out, "{}", formatter.format_error_message("HTTP server is already running. Use 'pmr serve-status' to check status or 'pmr serve-stop' to stop it."));
             return Ok(());
         } else {
             // Process exists but is not running, delete it first
-            let _ = process_manager.delete_process(HTTP_SERVER_PROCESS_NAME).await;
+            let _ = process_manager.delete(&http_server_process_name()).await;
         }
     }
 
@@ -175,24 +1206,20 @@ async fn handle_serve_daemon(
     let current_exe_str = current_exe.to_string_lossy().to_string();
 
     // Start HTTP server as a managed process
-    let args = vec!["serve".to_string(), "--port".to_string(), port.to_string()];
+    let mut args = vec!["serve".to_string(), "--port".to_string(), port.to_string()];
+    args.extend(flags.to_args());
     let env_vars = std::collections::HashMap::new();
+    // Internal, so the server stays out of `pmr list`, stats and clears
+    let options = StartOptions { internal: true, ..Default::default() };
 
-    let message = process_manager
-        .start_process(
-            HTTP_SERVER_PROCESS_NAME,
-            &current_exe_str,
-            args,
-            env_vars,
-            None,
-            None,
-        )
+    let outcome = process_manager
+        .start_with_options(&http_server_process_name(), &current_exe_str, args, env_vars, options)
         .await?;
 
-    println!("{}", formatter.format_success_message(&message));
-    println!("HTTP server started in daemon mode on port {}", port);
-    println!("Use 'pmr serve-status' to check status");
-    println!("Use 'pmr serve-stop' to stop the server");
+    outln!(out, "{}", formatter.format_start_outcome(&outcome, &[]));
+    outln!(out, "HTTP server started in daemon mode on port {}", port);
+    outln!(out, "Use 'pmr serve-status' to check status");
+    outln!(out, "Use 'pmr serve-stop' to stop the server");
 
     Ok(())
 }
@@ -201,13 +1228,14 @@ async fn handle_serve_daemon(
 async fn handle_serve_status(
     process_manager: &ProcessManager,
     formatter: &Formatter,
+    out: &Output,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    match process_manager.get_process_status(HTTP_SERVER_PROCESS_NAME).await {
+    match process_manager.get_process_status(&http_server_process_name()).await {
         Ok(process) => {
-            println!("{}", formatter.format_process_status(&process));
+            outln!(out, "{}", formatter.format_process_status(&process));
         }
         Err(_) => {
-            println!("{}", formatter.format_error_message("HTTP server is not running"));
+            outln!(out, "{}", formatter.format_error_message("HTTP server is not running"));
         }
     }
     Ok(())
@@ -217,13 +1245,61 @@ async fn handle_serve_status(
 async fn handle_serve_stop(
     process_manager: &ProcessManager,
     formatter: &Formatter,
+    out: &Output,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    match process_manager.stop_process(HTTP_SERVER_PROCESS_NAME).await {
-        Ok(message) => {
-            println!("{}", formatter.format_success_message(&message));
+    match process_manager.stop(&http_server_process_name()).await {
+        Ok(outcome) => {
+            outln!(out, "{}", formatter.format_stop_outcome(&outcome));
         }
         Err(_) => {
-            println!("{}", formatter.format_error_message("HTTP server is not running"));
+            outln!(out, "{}", formatter.format_error_message("HTTP server is not running"));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(feature = "http-api")]
+async fn handle_serve_logs(
+    lines: Option<usize>,
+    follow: bool,
+    process_manager: &ProcessManager,
+    formatter: &Formatter,
+    format: &OutputFormat,
+    out: &Output,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let path = process_manager.config().access_log_path();
+    let (lines, warning) = Commands::clamp_log_lines(lines, DEFAULT_MAX_LOG_LINES);
+    if let Some(warning) = warning {
+        errln!(out, "Warning: {}", warning);
+    }
+    // Subscribe before reading so nothing logged in between is missed
+    let broker = LogBroker::default();
+    let mut subscription = follow.then(|| broker.subscribe(HTTP_SERVER_PROCESS_NAME, &path));
+
+    let logs = if !path.exists() {
+        ProcessLogs { content: String::new(), contains_invalid_utf8: false, start_offset: None }
+    } else if let Some(lines) = lines {
+        let (lines, lossy) = pmr::log_rotation::read_last_lines_lossy(&path, lines)?;
+        ProcessLogs { content: lines.join("\n"), contains_invalid_utf8: lossy, start_offset: None }
+    } else {
+        let content = std::fs::read(&path)?;
+        let lossy = std::str::from_utf8(&content).is_err();
+        ProcessLogs { content: String::from_utf8_lossy(&content).trim_end_matches('\n').to_string(), contains_invalid_utf8: lossy, start_offset: None }
+    };
+    if !logs.content.is_empty() || !matches!(format, OutputFormat::Text) {
+        outln!(out, "{}", formatter.format_process_logs(&logs, HTTP_SERVER_PROCESS_NAME));
+    }
+
+    if let Some(subscription) = subscription.as_mut() {
+        loop {
+            tokio::select! {
+                frame = subscription.next() => match frame {
+                    Some(frame) => outln!(out, "{}", frame),
+                    None => break,
+                },
+                _ = tokio::signal::ctrl_c() => break,
+                _ = process_manager.cancellation().cancelled() => break,
+            }
         }
     }
     Ok(())
@@ -234,13 +1310,16 @@ async fn handle_serve_restart(
     port: u16,
     process_manager: &ProcessManager,
     formatter: &Formatter,
+    out: &Output,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    // Check if server exists and try to stop it
-    match process_manager.get_process_status(HTTP_SERVER_PROCESS_NAME).await {
+    // Check if server exists and try to stop it; the new one serves the way it did
+    let mut flags = ServeFlags::default();
+    match process_manager.get_process_status(&http_server_process_name()).await {
         Ok(process) => {
+            flags = ServeFlags::from_args(&process.args);
             if process.status == pmr::database::ProcessStatus::Running {
-                println!("Stopping HTTP server...");
-                let _ = process_manager.stop_process(HTTP_SERVER_PROCESS_NAME).await;
+                outln!(out, "Stopping HTTP server...");
+                let _ = process_manager.stop(&http_server_process_name()).await;
                 // Wait a moment for the process to fully stop
                 tokio::time::sleep(tokio::time::Duration::from_millis(1000)).await;
             }
@@ -251,9 +1330,9 @@ async fn handle_serve_restart(
     }
 
     // Delete the old process record if it exists
-    let _ = process_manager.delete_process(HTTP_SERVER_PROCESS_NAME).await;
+    let _ = process_manager.delete(&http_server_process_name()).await;
 
     // Start the server again
-    println!("Starting HTTP server...");
-    handle_serve_daemon(port, process_manager, formatter).await
+    outln!(out, "Starting HTTP server...");
+    handle_serve_daemon(port, flags, process_manager, formatter, out).await
 }
\ No newline at end of file
@@ -0,0 +1,118 @@
+//! Sentences describing what the lifecycle operations did, built from their
+//! [outcomes](crate::outcome). The CLI prints them as text output; the HTTP API returns the
+//! one for a start. They need none of the CLI, so library users without it have them too.
+
+use crate::{
+    file_watch::WatchRestart,
+    format::{human_bytes, human_duration},
+    outcome::{DeleteOutcome, NameCheck, RestartOutcome, RestartSkip, StartOutcome, StopOutcome, UndeleteOutcome},
+};
+
+/// Sentence describing a start, as shown by text output
+pub fn start_message(outcome: &StartOutcome) -> String {
+    let name = &outcome.name;
+    if outcome.unchanged {
+        return format!("Process '{}' already running, unchanged", name);
+    }
+    let mut message = start_result(outcome);
+    // Said out loud, since whatever followed the old file now reads an archive
+    if let Some(size) = outcome.rotated_log_size {
+        message.push_str(&format!(
+            "\nRotated the previous log ({}) before starting; `pmr logs {} --rotated` lists it",
+            human_bytes(size),
+            name
+        ));
+    }
+    message
+}
+
+fn start_result(outcome: &StartOutcome) -> String {
+    let name = &outcome.name;
+    match (&outcome.early_exit, outcome.pid) {
+        (None, Some(pid)) => format!("Process '{}' started with PID {}", name, pid),
+        (None, None) => format!("Process '{}' started", name),
+        (Some(exit), _) if exit.succeeded() => {
+            format!("Process '{}' completed successfully in {}", name, human_duration(std::time::Duration::from_millis(exit.elapsed_ms)))
+        }
+        (Some(exit), _) => {
+            let reason = match exit.exit_code {
+                Some(code) => format!("exited with code {}", code),
+                None => "was killed by a signal".to_string(),
+            };
+            let mut message = format!("Process '{}' failed: {} after {}", name, reason, human_duration(std::time::Duration::from_millis(exit.elapsed_ms)));
+            if !exit.log_tail.is_empty() {
+                message.push_str("\nLast log lines:");
+                for line in &exit.log_tail {
+                    message.push_str("\n  ");
+                    message.push_str(line);
+                }
+            }
+            message
+        }
+    }
+}
+
+/// Sentence describing a stop, as shown by text output
+pub fn stop_message(outcome: &StopOutcome) -> String {
+    let name = &outcome.name;
+    let Some(not_running) = &outcome.not_running else {
+        return format!("Process '{}' stopped", name);
+    };
+    let Some(since) = not_running.since else {
+        return format!("Process '{}' was not running (it never started)", name);
+    };
+    let exited_at = since.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M:%S");
+    match not_running.exit_code {
+        Some(code) => format!("Process '{}' was not running (exited at {} with code {})", name, exited_at, code),
+        None => format!("Process '{}' was not running ({} since {})", name, not_running.status, exited_at),
+    }
+}
+
+/// Sentence describing a restart, as shown by text output
+pub fn restart_message(outcome: &RestartOutcome) -> String {
+    let name = &outcome.name;
+    match (outcome.skipped, &outcome.start) {
+        (Some(RestartSkip::NoBinaryHash), _) => format!("Process '{}' has no recorded binary hash; not restarted", name),
+        (Some(RestartSkip::BinaryUnchanged), _) => format!("Process '{}' binary unchanged; not restarted", name),
+        (Some(RestartSkip::StoppedOnPurpose), _) => format!("Process '{}' was stopped on purpose; not restarted", name),
+        (None, Some(start)) => format!("Process '{}' restarted. {}", name, start_message(start)),
+        (None, None) => format!("Process '{}' restarted", name),
+    }
+}
+
+/// Sentence describing a restart made because watched files changed
+pub fn watch_restart_message(restart: &WatchRestart) -> String {
+    let others = match restart.changes {
+        0 | 1 => String::new(),
+        changes => format!(" and {} other file{}", changes - 1, if changes == 2 { "" } else { "s" }),
+    };
+    format!("{}{} changed. {}", restart.path, others, restart_message(&restart.outcome))
+}
+
+/// Sentence describing a delete, as shown by text output
+pub fn delete_message(outcome: &DeleteOutcome) -> String {
+    if outcome.hard {
+        format!("Process '{}' deleted", outcome.name)
+    } else {
+        format!("Process '{}' deleted (restore it with 'pmr undelete {}')", outcome.name, outcome.name)
+    }
+}
+
+/// Sentence describing an undelete, as shown by text output
+pub fn undelete_message(outcome: &UndeleteOutcome) -> String {
+    format!("Process '{}' restored (status: {})", outcome.name, outcome.status)
+}
+
+/// Sentence saying what holds a name, as shown by `pmr exists`
+pub fn name_check_message(check: &NameCheck) -> String {
+    match (&check.status, &check.reservation) {
+        (Some(status), _) => format!("Process '{}' exists (status: {})", check.name, status),
+        (None, Some(reservation)) => format!(
+            "Process name '{}' is reserved by token {} until {}",
+            check.name,
+            reservation.token,
+            reservation.expires_at.format("%Y-%m-%d %H:%M:%S UTC")
+        ),
+        (None, None) => format!("Process name '{}' is free", check.name),
+    }
+}
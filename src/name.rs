@@ -0,0 +1,253 @@
+//! Process names as their own type, so a name cannot be passed where a log path, a token
+//! name or any other string is expected, or the other way round. A [`ProcessName`] is only
+//! made by checking the naming rules: user input is parsed into one at the CLI and API
+//! boundaries, and everything below takes it as is.
+//!
+//! A name becomes the file name of the process's log (`<name>.log`, `<name>.<N>.log.gz`),
+//! which is where most of the rules come from. Spaces and non-ASCII letters are fine.
+
+use crate::{Error, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::ops::Deref;
+use std::str::FromStr;
+
+/// Longest name in bytes; its rotated, compressed log file name must still fit the 255
+/// bytes a file name may have
+pub const MAX_PROCESS_NAME_BYTES: usize = 200;
+
+/// A process name that follows the naming rules: not blank, at most
+/// [`MAX_PROCESS_NAME_BYTES`], no `/`, NUL or other control characters, not `.` or `..`,
+/// not starting with `-`, which the CLI would read as an option, and not ending in
+/// `.<digits>`, which would make its log look like an archive of another process's log
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+#[cfg_attr(feature = "http-api", derive(utoipa::ToSchema), schema(value_type = String))]
+pub struct ProcessName(String);
+
+impl ProcessName {
+    /// Check `name` against the naming rules
+    pub fn new(name: impl Into<String>) -> Result<Self> {
+        let name = name.into();
+        let invalid = |reason: &str| Err(Error::InvalidProcessName(format!("'{}': {}", name.escape_debug(), reason)));
+        if name.trim().is_empty() {
+            return invalid("must not be empty");
+        }
+        if name.len() > MAX_PROCESS_NAME_BYTES {
+            return invalid(&format!("longer than {} bytes", MAX_PROCESS_NAME_BYTES));
+        }
+        if name.contains('/') {
+            return invalid("must not contain '/'");
+        }
+        if name.chars().any(char::is_control) {
+            return invalid("must not contain control characters");
+        }
+        if name == "." || name == ".." {
+            return invalid("must not be '.' or '..'");
+        }
+        if name.starts_with('-') {
+            return invalid("must not start with '-'");
+        }
+        // `web.1.log` would also read as archive 1 of `web`, so rotating or purging one
+        // process's logs would take the other's live log
+        if ends_in_archive_number(&name) {
+            return invalid("must not end in '.' followed by digits, which is how rotated logs are numbered");
+        }
+        Ok(Self(name))
+    }
+
+    /// Name stored by an earlier pmr, taken as it is even if it breaks a rule added since, so
+    /// a database holding one still loads and lists. Not for user input.
+    pub fn stored(name: String) -> Self {
+        Self(name)
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn into_string(self) -> String {
+        self.0
+    }
+}
+
+/// Whether `name` ends in `.<digits>`, like the `<name>.<N>` stem of a rotated log
+fn ends_in_archive_number(name: &str) -> bool {
+    match name.rsplit_once('.') {
+        Some((_, number)) => !number.is_empty() && number.bytes().all(|b| b.is_ascii_digit()),
+        None => false,
+    }
+}
+
+impl Deref for ProcessName {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl AsRef<str> for ProcessName {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::borrow::Borrow<str> for ProcessName {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for ProcessName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl FromStr for ProcessName {
+    type Err = Error;
+
+    fn from_str(name: &str) -> Result<Self> {
+        Self::new(name)
+    }
+}
+
+impl TryFrom<&str> for ProcessName {
+    type Error = Error;
+
+    fn try_from(name: &str) -> Result<Self> {
+        Self::new(name)
+    }
+}
+
+impl TryFrom<String> for ProcessName {
+    type Error = Error;
+
+    fn try_from(name: String) -> Result<Self> {
+        Self::new(name)
+    }
+}
+
+impl From<ProcessName> for String {
+    fn from(name: ProcessName) -> Self {
+        name.0
+    }
+}
+
+impl PartialEq<str> for ProcessName {
+    fn eq(&self, other: &str) -> bool {
+        self.0 == other
+    }
+}
+
+impl PartialEq<&str> for ProcessName {
+    fn eq(&self, other: &&str) -> bool {
+        self.0 == *other
+    }
+}
+
+impl PartialEq<String> for ProcessName {
+    fn eq(&self, other: &String) -> bool {
+        &self.0 == other
+    }
+}
+
+/// Stored as TEXT; names read back are taken as stored, see [`ProcessName::stored`]
+impl sqlx::Type<sqlx::Sqlite> for ProcessName {
+    fn type_info() -> sqlx::sqlite::SqliteTypeInfo {
+        <String as sqlx::Type<sqlx::Sqlite>>::type_info()
+    }
+
+    fn compatible(ty: &sqlx::sqlite::SqliteTypeInfo) -> bool {
+        <String as sqlx::Type<sqlx::Sqlite>>::compatible(ty)
+    }
+}
+
+impl<'q> sqlx::Encode<'q, sqlx::Sqlite> for ProcessName {
+    fn encode_by_ref(
+        &self,
+        args: &mut Vec<sqlx::sqlite::SqliteArgumentValue<'q>>,
+    ) -> std::result::Result<sqlx::encode::IsNull, sqlx::error::BoxDynError> {
+        <String as sqlx::Encode<'q, sqlx::Sqlite>>::encode_by_ref(&self.0, args)
+    }
+}
+
+impl<'r> sqlx::Decode<'r, sqlx::Sqlite> for ProcessName {
+    fn decode(value: sqlx::sqlite::SqliteValueRef<'r>) -> std::result::Result<Self, sqlx::error::BoxDynError> {
+        Ok(Self::stored(<String as sqlx::Decode<'r, sqlx::Sqlite>>::decode(value)?))
+    }
+}
+
+#[cfg(feature = "postgres")]
+impl sqlx::Type<sqlx::Postgres> for ProcessName {
+    fn type_info() -> sqlx::postgres::PgTypeInfo {
+        <String as sqlx::Type<sqlx::Postgres>>::type_info()
+    }
+
+    fn compatible(ty: &sqlx::postgres::PgTypeInfo) -> bool {
+        <String as sqlx::Type<sqlx::Postgres>>::compatible(ty)
+    }
+}
+
+#[cfg(feature = "postgres")]
+impl sqlx::Encode<'_, sqlx::Postgres> for ProcessName {
+    fn encode_by_ref(&self, buf: &mut sqlx::postgres::PgArgumentBuffer) -> std::result::Result<sqlx::encode::IsNull, sqlx::error::BoxDynError> {
+        <String as sqlx::Encode<'_, sqlx::Postgres>>::encode_by_ref(&self.0, buf)
+    }
+}
+
+#[cfg(feature = "postgres")]
+impl<'r> sqlx::Decode<'r, sqlx::Postgres> for ProcessName {
+    fn decode(value: sqlx::postgres::PgValueRef<'r>) -> std::result::Result<Self, sqlx::error::BoxDynError> {
+        Ok(Self::stored(<String as sqlx::Decode<'r, sqlx::Postgres>>::decode(value)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_naming_rules() {
+        let longest = "n".repeat(MAX_PROCESS_NAME_BYTES);
+        for name in ["web", "api-v2", "worker_1", "job.nightly", "web.1a", "web.", "v1.2-beta", "porcelain test", "数据同步", "__pmr_http_server__", "a..b", longest.as_str()] {
+            assert_eq!(name.parse::<ProcessName>().unwrap().as_str(), name);
+        }
+
+        let too_long = "n".repeat(MAX_PROCESS_NAME_BYTES + 1);
+        for (name, reason) in [
+            ("", "must not be empty"),
+            ("   ", "must not be empty"),
+            (too_long.as_str(), "longer than 200 bytes"),
+            ("logs/web", "must not contain '/'"),
+            ("/web", "must not contain '/'"),
+            ("web\n", "must not contain control characters"),
+            ("a\tb", "must not contain control characters"),
+            ("nul\0", "must not contain control characters"),
+            (".", "must not be '.' or '..'"),
+            ("..", "must not be '.' or '..'"),
+            ("-n", "must not start with '-'"),
+            ("web.1", "which is how rotated logs are numbered"),
+            ("api-v1.20", "which is how rotated logs are numbered"),
+            ("web.0", "which is how rotated logs are numbered"),
+        ] {
+            let err = ProcessName::try_from(name).unwrap_err();
+            assert!(matches!(err, Error::InvalidProcessName(_)), "{:?} gave {:?}", name, err);
+            assert!(err.to_string().ends_with(reason), "{:?} gave {}", name, err);
+        }
+        assert_eq!(
+            ProcessName::new("a\nb").unwrap_err().to_string(),
+            "Invalid process name 'a\\nb': must not contain control characters"
+        );
+    }
+
+    #[test]
+    fn test_serde_is_a_plain_string_and_checks_the_rules() {
+        let name: ProcessName = "web".parse().unwrap();
+        assert_eq!(serde_json::to_string(&name).unwrap(), "\"web\"");
+        assert_eq!(serde_json::from_str::<ProcessName>("\"web\"").unwrap(), name);
+        let err = serde_json::from_str::<ProcessName>("\"../etc\"").unwrap_err();
+        assert!(err.to_string().contains("must not contain '/'"), "{}", err);
+    }
+}
@@ -0,0 +1,367 @@
+//! Delivery of process state change events to the global webhook.
+//!
+//! The sender is a background task subscribed to the manager's [`EventBus`], so the
+//! operation that triggered an event never waits on the network. Like any subscriber it
+//! drops events when it falls more than [`EVENT_CAPACITY`](crate::events::EVENT_CAPACITY)
+//! behind, which a slow or unreachable webhook can cause.
+
+use crate::events::{EventBus, ProcessEvent};
+use crate::{config::NotificationConfig, Error, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::sync::broadcast;
+
+/// Delay before the first retry; doubled for every following attempt
+const RETRY_BACKOFF: Duration = Duration::from_millis(250);
+
+/// Status reported for processes that were deleted
+pub const DELETED_STATUS: &str = "deleted";
+
+/// Status reported for processes that started flapping, see [`crate::flapping`]
+pub const FLAPPING_STATUS: &str = "flapping";
+
+/// Status reported when the logs stay over `logs_quota_bytes` with every archive deleted,
+/// for the process with the largest live log
+pub const LOGS_OVER_QUOTA_STATUS: &str = "logs_over_quota";
+
+/// Statuses that `events` may name
+pub const EVENT_STATUSES: [&str; 8] =
+    ["running", "stopped", "failed", "succeeded", "unknown", DELETED_STATUS, FLAPPING_STATUS, LOGS_OVER_QUOTA_STATUS];
+
+/// Payload POSTed to the webhook
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StateChangeEvent {
+    pub process: String,
+    pub old_status: Option<String>,
+    pub new_status: String,
+    pub exit_code: Option<i32>,
+    pub timestamp: DateTime<Utc>,
+    pub host: String,
+}
+
+impl StateChangeEvent {
+    /// Payload for a lifecycle event; None for events that change no status
+    pub fn from_event(event: &ProcessEvent) -> Option<Self> {
+        let (old_status, new_status, exit_code) = event.status_change()?;
+        Some(Self {
+            process: event.process.clone(),
+            old_status,
+            new_status,
+            exit_code,
+            timestamp: event.timestamp,
+            host: hostname(),
+        })
+    }
+}
+
+/// Parsed webhook URL
+#[derive(Debug, Clone, PartialEq)]
+struct WebhookTarget {
+    tls: bool,
+    host: String,
+    port: u16,
+    path: String,
+}
+
+impl WebhookTarget {
+    fn parse(url: &str) -> Result<Self> {
+        let (tls, rest) = if let Some(rest) = url.strip_prefix("https://") {
+            (true, rest)
+        } else if let Some(rest) = url.strip_prefix("http://") {
+            (false, rest)
+        } else {
+            return Err(Error::Config(format!("Webhook URL must start with http:// or https://: {}", url)));
+        };
+
+        let (authority, path) = match rest.find('/') {
+            Some(index) => (&rest[..index], &rest[index..]),
+            None => (rest, "/"),
+        };
+        let default_port = if tls { 443 } else { 80 };
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) => {
+                let port = port
+                    .parse()
+                    .map_err(|_| Error::Config(format!("Invalid port in webhook URL: {}", url)))?;
+                (host, port)
+            }
+            None => (authority, default_port),
+        };
+        if host.is_empty() {
+            return Err(Error::Config(format!("Webhook URL has no host: {}", url)));
+        }
+
+        Ok(Self {
+            tls,
+            host: host.to_string(),
+            port,
+            path: path.to_string(),
+        })
+    }
+
+    fn host_header(&self) -> String {
+        if self.port == if self.tls { 443 } else { 80 } {
+            self.host.clone()
+        } else {
+            format!("{}:{}", self.host, self.port)
+        }
+    }
+}
+
+/// Check that a webhook URL can be delivered to, without connecting
+pub fn check_webhook_url(url: &str) -> Result<()> {
+    WebhookTarget::parse(url).map(|_| ())
+}
+
+/// Webhook target and settings the sender delivers with; None while notifications are off
+type SenderSettings = Arc<std::sync::RwLock<Option<(WebhookTarget, NotificationConfig)>>>;
+
+/// Handle on the webhook sender task
+#[derive(Clone, Default)]
+pub struct Notifier {
+    bus: Option<EventBus>,
+    settings: SenderSettings,
+    /// Events the sender has finished with, delivered or not
+    handled: Arc<AtomicU64>,
+}
+
+impl Notifier {
+    /// A notifier that sends nothing
+    pub fn disabled() -> Self {
+        Self::default()
+    }
+
+    /// Subscribe a sender task to `bus`, delivering to the configured webhook, if any, until
+    /// [`reconfigure`](Self::reconfigure) changes it. Must be called from within a tokio
+    /// runtime, before anything is published.
+    pub fn start(config: &NotificationConfig, bus: &EventBus) -> Result<Self> {
+        let settings = SenderSettings::new(std::sync::RwLock::new(Self::sender_settings(config)?));
+        let handled = Arc::new(AtomicU64::new(bus.published()));
+        tokio::spawn(run_sender(settings.clone(), bus.subscribe(), handled.clone()));
+
+        Ok(Self {
+            bus: Some(bus.clone()),
+            settings,
+            handled,
+        })
+    }
+
+    fn sender_settings(config: &NotificationConfig) -> Result<Option<(WebhookTarget, NotificationConfig)>> {
+        let Some(url) = &config.webhook_url else {
+            return Ok(None);
+        };
+        Ok(Some((WebhookTarget::parse(url)?, config.clone())))
+    }
+
+    /// Deliver events published from now on with `config`. A disabled notifier has no
+    /// sender and stays disabled.
+    pub fn reconfigure(&self, config: &NotificationConfig) -> Result<()> {
+        let settings = Self::sender_settings(config)?;
+        if self.bus.is_some() {
+            *self.settings.write().unwrap_or_else(|e| e.into_inner()) = settings;
+        }
+        Ok(())
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.bus.is_some() && self.settings.read().unwrap_or_else(|e| e.into_inner()).is_some()
+    }
+
+    /// Wait up to `timeout` for the events published so far to be delivered (or given up on)
+    pub async fn flush(&self, timeout: Duration) {
+        let Some(bus) = self.bus.as_ref().filter(|_| self.is_enabled()) else {
+            return;
+        };
+        let target = bus.published();
+        let deadline = tokio::time::Instant::now() + timeout;
+        while self.handled.load(Ordering::SeqCst) < target && tokio::time::Instant::now() < deadline {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    }
+}
+
+async fn run_sender(
+    settings: SenderSettings,
+    mut receiver: broadcast::Receiver<ProcessEvent>,
+    handled: Arc<AtomicU64>,
+) {
+    loop {
+        let event = match receiver.recv().await {
+            Ok(event) => event,
+            Err(broadcast::error::RecvError::Lagged(missed)) => {
+                eprintln!("Warning: webhook fell behind, dropped {} state change events", missed);
+                handled.fetch_add(missed, Ordering::SeqCst);
+                continue;
+            }
+            Err(broadcast::error::RecvError::Closed) => break,
+        };
+        // Read per event, so a reload applies from the next event on
+        let current = settings.read().unwrap_or_else(|e| e.into_inner()).clone();
+        if let Some((target, config)) = current {
+            if let Some(event) = StateChangeEvent::from_event(&event)
+                .filter(|event| config.events.is_empty() || config.events.iter().any(|e| e == &event.new_status))
+            {
+                send_with_retries(&target, &config, &event).await;
+            }
+        }
+        handled.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+async fn send_with_retries(target: &WebhookTarget, config: &NotificationConfig, event: &StateChangeEvent) {
+    let body = match serde_json::to_string(event) {
+        Ok(body) => body,
+        Err(e) => {
+            eprintln!("Warning: failed to serialize state change event: {}", e);
+            return;
+        }
+    };
+
+    let timeout = Duration::from_millis(config.timeout_ms);
+    let mut last_error = String::new();
+    for attempt in 0..=config.max_retries {
+        if attempt > 0 {
+            tokio::time::sleep(RETRY_BACKOFF * 2u32.pow(attempt - 1)).await;
+        }
+        match tokio::time::timeout(timeout, deliver(target, config.bearer_token.as_deref(), &body)).await {
+            Ok(Ok(status)) if (200..300).contains(&status) => {
+                last_error.clear();
+                break;
+            }
+            Ok(Ok(status)) => last_error = format!("webhook responded with HTTP {}", status),
+            Ok(Err(e)) => last_error = e.to_string(),
+            Err(_) => last_error = format!("no response within {}ms", config.timeout_ms),
+        }
+    }
+    if !last_error.is_empty() {
+        eprintln!(
+            "Warning: failed to deliver '{}' event for process '{}': {}",
+            event.new_status, event.process, last_error
+        );
+    }
+}
+
+/// POST the body once and return the HTTP status code
+async fn deliver(target: &WebhookTarget, bearer_token: Option<&str>, body: &str) -> Result<u16> {
+    let mut request = format!(
+        "POST {} HTTP/1.1\r\nHost: {}\r\nUser-Agent: pmr/{}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n",
+        target.path,
+        target.host_header(),
+        env!("CARGO_PKG_VERSION"),
+        body.len()
+    );
+    if let Some(token) = bearer_token {
+        request.push_str(&format!("Authorization: Bearer {}\r\n", token));
+    }
+    request.push_str("\r\n");
+    request.push_str(body);
+
+    let stream = TcpStream::connect((target.host.as_str(), target.port)).await?;
+    if !target.tls {
+        return exchange(stream, &request).await;
+    }
+
+    let mut roots = tokio_rustls::rustls::RootCertStore::empty();
+    roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+    let tls_config = tokio_rustls::rustls::ClientConfig::builder_with_provider(Arc::new(
+        tokio_rustls::rustls::crypto::ring::default_provider(),
+    ))
+    .with_safe_default_protocol_versions()
+    .map_err(|e| Error::Other(format!("TLS setup failed: {}", e)))?
+    .with_root_certificates(roots)
+    .with_no_client_auth();
+    let server_name = tokio_rustls::rustls::pki_types::ServerName::try_from(target.host.clone())
+        .map_err(|e| Error::Other(format!("Invalid webhook host '{}': {}", target.host, e)))?;
+    let stream = tokio_rustls::TlsConnector::from(Arc::new(tls_config))
+        .connect(server_name, stream)
+        .await?;
+    exchange(stream, &request).await
+}
+
+async fn exchange<S: AsyncRead + AsyncWrite + Unpin>(mut stream: S, request: &str) -> Result<u16> {
+    stream.write_all(request.as_bytes()).await?;
+    stream.flush().await?;
+
+    let mut status_line = String::new();
+    BufReader::new(stream).read_line(&mut status_line).await?;
+    status_line
+        .split_whitespace()
+        .nth(1)
+        .and_then(|code| code.parse().ok())
+        .ok_or_else(|| Error::Other(format!("Invalid HTTP response: {}", status_line.trim())))
+}
+
+fn hostname() -> String {
+    let mut buffer = [0u8; 256];
+    let result = unsafe { libc::gethostname(buffer.as_mut_ptr() as *mut libc::c_char, buffer.len()) };
+    if result != 0 {
+        return "unknown".to_string();
+    }
+    let len = buffer.iter().position(|b| *b == 0).unwrap_or(buffer.len());
+    String::from_utf8_lossy(&buffer[..len]).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_webhook_url() {
+        let target = WebhookTarget::parse("http://127.0.0.1:9000/hooks/pmr").unwrap();
+        assert_eq!(target, WebhookTarget {
+            tls: false,
+            host: "127.0.0.1".to_string(),
+            port: 9000,
+            path: "/hooks/pmr".to_string(),
+        });
+        assert_eq!(target.host_header(), "127.0.0.1:9000");
+
+        let target = WebhookTarget::parse("https://hooks.example.com").unwrap();
+        assert!(target.tls);
+        assert_eq!(target.port, 443);
+        assert_eq!(target.path, "/");
+        assert_eq!(target.host_header(), "hooks.example.com");
+
+        assert!(WebhookTarget::parse("ftp://example.com").is_err());
+        assert!(WebhookTarget::parse("http://:80/").is_err());
+        assert!(WebhookTarget::parse("http://example.com:port/").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_event_filter_and_disabled_notifier() {
+        use crate::database::ProcessStatus;
+        use crate::events::ProcessEventKind;
+
+        let event = |kind| ProcessEvent { process: "web".to_string(), timestamp: Utc::now(), kind };
+        let notifier = Notifier::disabled();
+        assert!(!notifier.is_enabled());
+        notifier.flush(Duration::from_secs(5)).await;
+
+        let config = NotificationConfig {
+            // Nothing listens here; deliveries fail quickly and are given up on
+            webhook_url: Some("http://127.0.0.1:9/".to_string()),
+            events: vec!["failed".to_string()],
+            max_retries: 0,
+            ..Default::default()
+        };
+        let bus = EventBus::new();
+        let notifier = Notifier::start(&config, &bus).unwrap();
+        bus.publish(event(ProcessEventKind::Started { pid: Some(1) }));
+        bus.publish(event(ProcessEventKind::LogRotated { trigger: "manual".to_string(), rotated_from_size: 1 }));
+        bus.publish(event(ProcessEventKind::Failed { from: Some(ProcessStatus::Running), exit_code: Some(1) }));
+        notifier.flush(Duration::from_secs(5)).await;
+        assert_eq!(notifier.handled.load(Ordering::SeqCst), 3);
+
+        // Turned off and on again by a reload, without a new sender
+        notifier.reconfigure(&NotificationConfig::default()).unwrap();
+        assert!(!notifier.is_enabled());
+        assert!(notifier.reconfigure(&NotificationConfig { webhook_url: Some("ftp://x".to_string()), ..Default::default() }).is_err());
+        notifier.reconfigure(&config).unwrap();
+        assert!(notifier.is_enabled());
+    }
+}
@@ -0,0 +1,155 @@
+//! What the lifecycle operations of [`ProcessManager`](crate::process::ProcessManager) did,
+//! as data. Sentences for people are built from these by [`messages`](crate::messages);
+//! JSON output and the HTTP API serialize them as they are.
+
+use crate::database::{ProcessStatus, Reservation};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Result of starting a process
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "http-api", derive(utoipa::ToSchema))]
+pub struct StartOutcome {
+    pub name: String,
+    /// PID of the new instance, or of the running one an idempotent start left alone
+    pub pid: Option<u32>,
+    /// Running, or stopped/failed when the process exited within the start window
+    pub status: ProcessStatus,
+    /// An idempotent start found the same definition already running and changed nothing
+    #[serde(default)]
+    pub unchanged: bool,
+    /// How the process exited within the start window, if it did
+    pub early_exit: Option<StartExit>,
+    /// Size of the previous log when the start rotated it aside before the process wrote
+    /// to it; None when the start appended to it or there was none
+    #[serde(default)]
+    pub rotated_log_size: Option<u64>,
+}
+
+/// A process that exited within its start window
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "http-api", derive(utoipa::ToSchema))]
+pub struct StartExit {
+    /// None when the process was killed by a signal
+    pub exit_code: Option<i32>,
+    /// Time from spawn to exit
+    pub elapsed_ms: u64,
+    /// Last lines of the log; only collected when the process failed
+    #[serde(default)]
+    pub log_tail: Vec<String>,
+}
+
+impl StartExit {
+    pub fn succeeded(&self) -> bool {
+        self.exit_code == Some(0)
+    }
+}
+
+/// Result of stopping a process
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "http-api", derive(utoipa::ToSchema))]
+pub struct StopOutcome {
+    pub name: String,
+    /// PID that was stopped; None when there was nothing to stop
+    pub pid: Option<u32>,
+    /// Time from the first signal until the process was gone
+    pub waited_ms: u64,
+    /// The process outlived the grace period and was killed with SIGKILL
+    pub escalated: bool,
+    /// Set when the process was not running: how it had ended
+    pub not_running: Option<NotRunning>,
+}
+
+/// State of a process a stop found already gone
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "http-api", derive(utoipa::ToSchema))]
+pub struct NotRunning {
+    pub status: ProcessStatus,
+    pub exit_code: Option<i32>,
+    /// When the process was last seen changing state; None when it never started
+    pub since: Option<DateTime<Utc>>,
+}
+
+/// Why a restart left the process alone
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "http-api", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum RestartSkip {
+    /// `only_if_changed` was asked for, but no binary hash was recorded at start
+    NoBinaryHash,
+    /// `only_if_changed` was asked for and the binary is the one that was started
+    BinaryUnchanged,
+    /// An automatic restart, e.g. for a watched file, of a process stopped on purpose
+    StoppedOnPurpose,
+}
+
+/// Result of restarting a process
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "http-api", derive(utoipa::ToSchema))]
+pub struct RestartOutcome {
+    pub name: String,
+    /// PID of the instance before the restart
+    pub previous_pid: Option<u32>,
+    /// Set when nothing was restarted
+    pub skipped: Option<RestartSkip>,
+    /// Stop of the previous instance, when it was running
+    pub stop: Option<StopOutcome>,
+    /// Start of the new instance; None when skipped
+    pub start: Option<StartOutcome>,
+}
+
+/// Result of deleting a process
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "http-api", derive(utoipa::ToSchema))]
+pub struct DeleteOutcome {
+    pub name: String,
+    /// Removed for good together with its log, rather than soft-deleted
+    pub hard: bool,
+    /// Status of the process when it was deleted
+    pub previous_status: ProcessStatus,
+    /// Stop of the process, when it was still running
+    pub stop: Option<StopOutcome>,
+}
+
+/// Result of restoring a soft-deleted process
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "http-api", derive(utoipa::ToSchema))]
+pub struct UndeleteOutcome {
+    pub name: String,
+    /// Status the process had when it was deleted
+    pub status: ProcessStatus,
+}
+
+/// Whether a process name is taken, read from the database alone: no PID is probed and
+/// nothing is created
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "http-api", derive(utoipa::ToSchema))]
+pub struct NameCheck {
+    pub name: String,
+    /// A process record holds the name, whatever its status
+    pub exists: bool,
+    /// Status stored for that process, as last recorded
+    pub status: Option<ProcessStatus>,
+    /// Reservation of the name that has not expired
+    pub reservation: Option<Reservation>,
+}
+
+impl NameCheck {
+    /// Whether a start without the reserving token would be refused
+    pub fn taken(&self) -> bool {
+        self.exists || self.reservation.is_some()
+    }
+}
+
+/// Result of reloading the config file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "http-api", derive(utoipa::ToSchema))]
+pub struct ReloadOutcome {
+    /// File the settings were read from; defaults apply when it does not exist
+    #[cfg_attr(feature = "http-api", schema(value_type = String))]
+    pub config_file: std::path::PathBuf,
+    /// Config file sections whose settings changed, e.g. `log_rotation`
+    pub changed: Vec<String>,
+    /// Problems the new settings have that did not stop them being applied
+    pub warnings: Vec<String>,
+}
@@ -0,0 +1,202 @@
+//! Permissions of the files holding secrets: the database (API tokens and the environments
+//! of processes), the directory it lives in and the config file. pmr creates them readable by
+//! its user only, and reports ones other local users can read. Without unix mode bits the
+//! files are created with the platform's defaults and nothing is reported.
+
+use crate::config::{Config, StorageKind};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs::OpenOptions;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Mode of files holding secrets
+pub const PRIVATE_FILE_MODE: u32 = 0o600;
+
+/// Mode of directories holding secret files
+pub const PRIVATE_DIR_MODE: u32 = 0o700;
+
+/// Group and other read bits
+const SHARED_READ_BITS: u32 = 0o044;
+
+/// A secret file or directory other local users can read, as listed by `pmr doctor`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LoosePermissions {
+    pub path: PathBuf,
+    pub is_dir: bool,
+    /// Permission bits found, in octal like `0644`
+    pub mode: String,
+    /// Permission bits `pmr doctor --fix-permissions` sets
+    pub expected_mode: String,
+}
+
+impl LoosePermissions {
+    fn expected_bits(&self) -> u32 {
+        if self.is_dir { PRIVATE_DIR_MODE } else { PRIVATE_FILE_MODE }
+    }
+
+    /// Restrict the path to its owner, leaving the owner's own bits as they should be
+    pub fn fix(&self) -> io::Result<()> {
+        set_mode(&self.path, self.expected_bits())
+    }
+}
+
+impl fmt::Display for LoosePermissions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} is readable by other users (mode {}, expected {})",
+            self.path.display(),
+            self.mode,
+            self.expected_mode
+        )
+    }
+}
+
+/// Paths holding secrets under `config`: the database directory, the SQLite database with
+/// its WAL files, and the config file it was read from; only those that exist
+pub fn secret_paths(config: &Config) -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+    if let Some(dir) = config.database_path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        paths.push(dir.to_path_buf());
+    }
+    if config.storage.backend == StorageKind::Sqlite {
+        for suffix in ["", "-wal", "-shm"] {
+            let mut path = config.database_path.clone().into_os_string();
+            path.push(suffix);
+            paths.push(PathBuf::from(path));
+        }
+    }
+    paths.extend(config.config_file.clone());
+    paths.retain(|path| path.exists());
+    paths
+}
+
+/// Those of `paths` that other local users can read
+pub fn check(paths: &[PathBuf]) -> Vec<LoosePermissions> {
+    paths.iter().filter_map(|path| loose(path)).collect()
+}
+
+#[cfg(unix)]
+fn loose(path: &Path) -> Option<LoosePermissions> {
+    use std::os::unix::fs::PermissionsExt;
+    let metadata = std::fs::metadata(path).ok()?;
+    let mode = metadata.permissions().mode() & 0o777;
+    if mode & SHARED_READ_BITS == 0 {
+        return None;
+    }
+    let expected = if metadata.is_dir() { PRIVATE_DIR_MODE } else { PRIVATE_FILE_MODE };
+    Some(LoosePermissions {
+        path: path.to_path_buf(),
+        is_dir: metadata.is_dir(),
+        mode: format!("{:04o}", mode),
+        expected_mode: format!("{:04o}", expected),
+    })
+}
+
+#[cfg(not(unix))]
+fn loose(_path: &Path) -> Option<LoosePermissions> {
+    None
+}
+
+#[cfg(unix)]
+fn set_mode(path: &Path, mode: u32) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))
+}
+
+#[cfg(not(unix))]
+fn set_mode(_path: &Path, _mode: u32) -> io::Result<()> {
+    Ok(())
+}
+
+/// Create `path` and its missing parents, readable by the owner only; existing directories
+/// keep their mode
+pub fn create_private_dir_all(path: &Path) -> io::Result<()> {
+    let mut builder = std::fs::DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, PRIVATE_DIR_MODE);
+    builder.create(path)
+}
+
+/// Options creating a file readable by the owner only; an existing file keeps its mode
+pub fn private_file_options() -> OpenOptions {
+    let mut options = OpenOptions::new();
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, PRIVATE_FILE_MODE);
+    options
+}
+
+/// Create an empty file at `path` readable by the owner only, unless there is one already
+pub fn create_private_file_if_missing(path: &Path) -> io::Result<()> {
+    match private_file_options().write(true).create_new(true).open(path) {
+        Ok(_) => Ok(()),
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    fn mode(path: &Path) -> u32 {
+        std::fs::metadata(path).unwrap().permissions().mode() & 0o777
+    }
+
+    #[test]
+    fn test_loose_paths_reported_and_fixed() {
+        let root = tempfile::TempDir::new().unwrap();
+        let dir = root.path().join("home");
+        let database = dir.join("processes.db");
+        std::fs::create_dir(&dir).unwrap();
+        std::fs::write(&database, "").unwrap();
+        set_mode(&dir, 0o755).unwrap();
+        set_mode(&database, 0o640).unwrap();
+        let paths = vec![dir.clone(), database.clone()];
+
+        let found = check(&paths);
+        assert_eq!(found.len(), 2);
+        assert_eq!((found[0].is_dir, found[0].mode.as_str(), found[0].expected_mode.as_str()), (true, "0755", "0700"));
+        assert_eq!(found[1].to_string(), format!("{} is readable by other users (mode 0640, expected 0600)", database.display()));
+
+        for path in &found {
+            path.fix().unwrap();
+        }
+        assert_eq!((mode(&dir), mode(&database)), (0o700, 0o600));
+        assert!(check(&paths).is_empty());
+        // Writable but not readable by others is not a leak of the secrets
+        set_mode(&database, 0o602).unwrap();
+        assert!(check(&paths).is_empty());
+    }
+
+    #[test]
+    fn test_private_files_and_directories_are_created_owner_only() {
+        let root = tempfile::TempDir::new().unwrap();
+        let dir = root.path().join("a/b");
+        create_private_dir_all(&dir).unwrap();
+        assert_eq!((mode(&root.path().join("a")), mode(&dir)), (0o700, 0o700));
+
+        let file = dir.join("processes.db");
+        create_private_file_if_missing(&file).unwrap();
+        assert_eq!(mode(&file), 0o600);
+        // An existing file is left as it is, contents and mode
+        std::fs::write(&file, "data").unwrap();
+        set_mode(&file, 0o644).unwrap();
+        create_private_file_if_missing(&file).unwrap();
+        assert_eq!((mode(&file), std::fs::read_to_string(&file).unwrap().as_str()), (0o644, "data"));
+    }
+
+    #[test]
+    fn test_secret_paths_are_the_existing_ones() {
+        let root = tempfile::TempDir::new().unwrap();
+        let mut config = Config::in_directory(root.path());
+        config.config_file = Some(root.path().join("config.toml"));
+        assert_eq!(secret_paths(&config), vec![root.path().to_path_buf()]);
+        std::fs::write(&config.database_path, "").unwrap();
+        std::fs::write(root.path().join("config.toml"), "").unwrap();
+        assert_eq!(secret_paths(&config), vec![root.path().to_path_buf(), config.database_path.clone(), root.path().join("config.toml")]);
+    }
+}
@@ -0,0 +1,452 @@
+//! What pmr asks of PIDs it did not spawn in this run, or no longer holds a child handle
+//! for: whether they are alive, whether they are still the process a record was written
+//! for, whether they are zombies, and delivering signals. Tests swap in a
+//! [`FakePidProbe`] so liveness does not depend on real processes.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Slack for comparing a PID's start time with its record, since boot time is only
+/// known to the second
+const PID_START_TOLERANCE_SECS: i64 = 1;
+
+/// What probing the PID of a record found
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PidLiveness {
+    /// Running, and as far as can be told the process the record was written for
+    Alive,
+    /// Gone, a zombie, or reused by a later process
+    Gone,
+    /// Something runs under the PID, but it belongs to another user (`kill(pid, 0)` fails
+    /// with EPERM) and /proc hides it, so whether it is the record's process is unknown
+    Inconclusive,
+}
+
+/// Liveness from the result of `kill(pid, 0)` and what /proc says about whether the PID is
+/// the record's process (None when /proc hides it)
+fn judge(signal_zero: std::io::Result<()>, identity: Option<bool>) -> PidLiveness {
+    match (signal_zero, identity) {
+        (Ok(()), None | Some(true)) => PidLiveness::Alive,
+        // EPERM means the PID exists but is not ours to signal: alive, if still the same process
+        (Err(e), Some(true)) if e.raw_os_error() == Some(libc::EPERM) => PidLiveness::Alive,
+        (Err(e), None) if e.raw_os_error() == Some(libc::EPERM) => PidLiveness::Inconclusive,
+        _ => PidLiveness::Gone,
+    }
+}
+
+pub trait PidProbe: Send + Sync {
+    /// Whether something is running under `pid`
+    fn is_alive(&self, pid: u32) -> bool;
+
+    /// Whether `pid` can still be the process started for a record created at `created_at`
+    fn belongs_to(&self, pid: u32, created_at: DateTime<Utc>) -> bool;
+
+    /// Whether `pid` is alive and still the process started for a record created at
+    /// `created_at`. By default from [`Self::is_alive`] and [`Self::belongs_to`], which
+    /// never leaves it inconclusive.
+    fn liveness(&self, pid: u32, created_at: DateTime<Utc>) -> PidLiveness {
+        if self.is_alive(pid) && self.belongs_to(pid, created_at) {
+            PidLiveness::Alive
+        } else {
+            PidLiveness::Gone
+        }
+    }
+
+    /// The parent of `pid` when it is a zombie left by the process started for a record
+    /// created at `created_at`: it has exited, but its parent has not collected the exit
+    /// status, so `kill(pid, 0)` still succeeds
+    fn zombie_parent(&self, pid: u32, created_at: DateTime<Utc>) -> Option<u32>;
+
+    /// Deliver `signal` to `pid`
+    fn signal(&self, pid: u32, signal: i32) -> std::io::Result<()>;
+}
+
+/// `kill(2)` and /proc
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemPidProbe;
+
+impl PidProbe for SystemPidProbe {
+    fn is_alive(&self, pid: u32) -> bool {
+        // A zombie has exited; only its parent's wait() is missing, which for processes
+        // pmr did not spawn may never come
+        let exists = match signal_zero(pid) {
+            Ok(()) => true,
+            Err(e) => e.raw_os_error() == Some(libc::EPERM),
+        };
+        exists && !read_stat(pid).is_some_and(|stat| stat.is_zombie())
+    }
+
+    fn belongs_to(&self, pid: u32, created_at: DateTime<Utc>) -> bool {
+        pid_belongs_to(pid, created_at)
+    }
+
+    fn liveness(&self, pid: u32, created_at: DateTime<Utc>) -> PidLiveness {
+        let identity = read_stat(pid).map(|stat| !stat.is_zombie() && stat.started_by(created_at));
+        judge(signal_zero(pid), identity)
+    }
+
+    fn zombie_parent(&self, pid: u32, created_at: DateTime<Utc>) -> Option<u32> {
+        let stat = read_stat(pid)?;
+        (stat.is_zombie() && stat.started_by(created_at)).then_some(stat.ppid)
+    }
+
+    fn signal(&self, pid: u32, signal: i32) -> std::io::Result<()> {
+        if unsafe { libc::kill(pid as i32, signal) } != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+/// `kill(pid, 0)`: whether the PID exists and may be signalled
+fn signal_zero(pid: u32) -> std::io::Result<()> {
+    if unsafe { libc::kill(pid as i32, 0) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Whether `pid` can still be the process started for a record created at `created_at`:
+/// it must not be a zombie and must have started no later than the record was written.
+/// Without a readable /proc the PID is taken at its word.
+fn pid_belongs_to(pid: u32, created_at: DateTime<Utc>) -> bool {
+    match read_stat(pid) {
+        Some(stat) => !stat.is_zombie() && stat.started_by(created_at),
+        None => true,
+    }
+}
+
+/// What pmr reads from /proc/<pid>/stat
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ProcStat {
+    /// `R`, `S`, `Z` and so on
+    state: char,
+    ppid: u32,
+    /// Start time in clock ticks since boot
+    start_ticks: u64,
+}
+
+impl ProcStat {
+    fn parse(stat: &str) -> Option<Self> {
+        // The command name may contain spaces and parentheses; fields resume after the last ')'
+        let (_, rest) = stat.rsplit_once(')')?;
+        let fields: Vec<&str> = rest.split_whitespace().collect();
+        Some(Self {
+            state: fields.first()?.chars().next()?,
+            ppid: fields.get(1)?.parse().ok()?,
+            start_ticks: fields.get(19)?.parse().ok()?,
+        })
+    }
+
+    fn is_zombie(&self) -> bool {
+        self.state == 'Z'
+    }
+
+    /// Whether the process started no later than a record written at `created_at`; true
+    /// when the start time cannot be worked out
+    fn started_by(&self, created_at: DateTime<Utc>) -> bool {
+        started_at(self.start_ticks)
+            .is_none_or(|started| started <= created_at + chrono::Duration::seconds(PID_START_TOLERANCE_SECS))
+    }
+}
+
+/// /proc/<pid>/stat, when readable
+fn read_stat(pid: u32) -> Option<ProcStat> {
+    ProcStat::parse(&std::fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?)
+}
+
+/// A process whose PID is a zombie, as listed by `pmr doctor`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ZombieProcess {
+    pub name: String,
+    pub pid: u32,
+    pub parent_pid: u32,
+    /// The parent is init or gone, so the zombie will be reaped without anyone's help;
+    /// otherwise the parent is alive and failing to wait() for its child
+    pub reparented: bool,
+}
+
+/// Wall-clock start of a process from its start time in clock ticks since boot
+fn started_at(ticks: u64) -> Option<DateTime<Utc>> {
+    let boot_time = std::fs::read_to_string("/proc/stat")
+        .ok()?
+        .lines()
+        .find_map(|line| line.strip_prefix("btime "))
+        .and_then(|value| value.trim().parse::<i64>().ok())?;
+    let ticks_per_second = unsafe { libc::sysconf(libc::_SC_CLK_TCK) };
+    if ticks_per_second <= 0 {
+        return None;
+    }
+    let millis = (ticks as i64).checked_mul(1000)? / ticks_per_second as i64;
+    DateTime::from_timestamp(boot_time, 0).map(|boot| boot + chrono::Duration::milliseconds(millis))
+}
+
+#[cfg(any(test, feature = "test-util"))]
+pub use fake::{FakePidProbe, SentSignal};
+
+#[cfg(any(test, feature = "test-util"))]
+mod fake {
+    use super::{judge, PidLiveness, PidProbe, PID_START_TOLERANCE_SECS};
+    use crate::clock::Clock;
+    use chrono::{DateTime, Utc};
+    use std::collections::{HashMap, HashSet};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    /// A signal delivered through a [`FakePidProbe`]
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct SentSignal {
+        pub pid: u32,
+        pub signal: i32,
+        /// Reading of the probe's clock when it was sent
+        pub at: Duration,
+    }
+
+    #[derive(Debug)]
+    struct FakePid {
+        started: DateTime<Utc>,
+        exits_at: Option<Duration>,
+        ignored: HashSet<i32>,
+        /// Parent of a PID that exited and was never reaped
+        zombie_parent: Option<u32>,
+        /// Owned by another user, so signals fail with EPERM
+        foreign: bool,
+        /// Left out of /proc, as `hidepid` does for other users' processes
+        hidden: bool,
+    }
+
+    #[derive(Debug, Default)]
+    struct State {
+        pids: HashMap<u32, FakePid>,
+        sent: Vec<SentSignal>,
+        probes: usize,
+    }
+
+    /// PIDs that live and die on the schedule a test sets, against the test's clock.
+    /// Any signal other than 0 ends a PID unless it was told to ignore that signal.
+    pub struct FakePidProbe {
+        clock: Arc<dyn Clock>,
+        state: Mutex<State>,
+    }
+
+    impl FakePidProbe {
+        pub fn new(clock: Arc<dyn Clock>) -> Self {
+            Self { clock, state: Mutex::default() }
+        }
+
+        fn state(&self) -> std::sync::MutexGuard<'_, State> {
+            self.state.lock().unwrap_or_else(|e| e.into_inner())
+        }
+
+        /// Make `pid` alive, started now
+        pub fn add(&self, pid: u32) {
+            let started = self.clock.now();
+            self.state().pids.insert(
+                pid,
+                FakePid { started, exits_at: None, ignored: HashSet::new(), zombie_parent: None, foreign: false, hidden: false },
+            );
+        }
+
+        /// Make `pid` belong to another user: signalling it fails with EPERM, and with
+        /// `hidden` /proc does not show it either, so its identity cannot be checked
+        pub fn make_foreign(&self, pid: u32, hidden: bool) {
+            if let Some(fake) = self.state().pids.get_mut(&pid) {
+                fake.foreign = true;
+                fake.hidden = hidden;
+            }
+        }
+
+        /// Make `pid` exit now and stay behind as a zombie of `parent`
+        pub fn make_zombie(&self, pid: u32, parent: u32) {
+            let now = self.clock.elapsed();
+            if let Some(fake) = self.state().pids.get_mut(&pid) {
+                fake.exits_at = Some(now);
+                fake.zombie_parent = Some(parent);
+            }
+        }
+
+        /// Make `pid` exit once `after` has passed on the clock
+        pub fn exit_after(&self, pid: u32, after: Duration) {
+            let exits_at = self.clock.elapsed() + after;
+            if let Some(fake) = self.state().pids.get_mut(&pid) {
+                fake.exits_at = Some(exits_at);
+            }
+        }
+
+        /// Make `pid` survive `signal`
+        pub fn ignore_signal(&self, pid: u32, signal: i32) {
+            if let Some(fake) = self.state().pids.get_mut(&pid) {
+                fake.ignored.insert(signal);
+            }
+        }
+
+        /// Signals delivered so far, oldest first
+        pub fn sent_signals(&self) -> Vec<SentSignal> {
+            self.state().sent.clone()
+        }
+
+        /// How often pmr asked whether a PID is alive
+        pub fn probe_count(&self) -> usize {
+            self.state().probes
+        }
+
+        /// What `kill(pid, 0)` would return
+        fn signal_zero(&self, pid: u32) -> std::io::Result<()> {
+            if !self.alive(pid) {
+                return Err(std::io::Error::from_raw_os_error(libc::ESRCH));
+            }
+            if self.state().pids.get(&pid).is_some_and(|fake| fake.foreign) {
+                return Err(std::io::Error::from_raw_os_error(libc::EPERM));
+            }
+            Ok(())
+        }
+
+        fn alive(&self, pid: u32) -> bool {
+            let now = self.clock.elapsed();
+            self.state()
+                .pids
+                .get(&pid)
+                .is_some_and(|fake| fake.exits_at.is_none_or(|exits_at| now < exits_at))
+        }
+    }
+
+    impl PidProbe for FakePidProbe {
+        fn is_alive(&self, pid: u32) -> bool {
+            self.state().probes += 1;
+            self.alive(pid)
+        }
+
+        fn belongs_to(&self, pid: u32, created_at: DateTime<Utc>) -> bool {
+            let started = self.state().pids.get(&pid).map(|fake| fake.started);
+            self.alive(pid)
+                && started.is_some_and(|started| started <= created_at + chrono::Duration::seconds(PID_START_TOLERANCE_SECS))
+        }
+
+        fn liveness(&self, pid: u32, created_at: DateTime<Utc>) -> PidLiveness {
+            self.state().probes += 1;
+            let hidden = self.state().pids.get(&pid).is_some_and(|fake| fake.hidden);
+            let identity = (!hidden).then(|| self.belongs_to(pid, created_at));
+            judge(self.signal_zero(pid), identity)
+        }
+
+        fn zombie_parent(&self, pid: u32, created_at: DateTime<Utc>) -> Option<u32> {
+            let state = self.state();
+            let fake = state.pids.get(&pid)?;
+            fake.zombie_parent
+                .filter(|_| fake.started <= created_at + chrono::Duration::seconds(PID_START_TOLERANCE_SECS))
+        }
+
+        fn signal(&self, pid: u32, signal: i32) -> std::io::Result<()> {
+            self.signal_zero(pid)?;
+            let at = self.clock.elapsed();
+            let mut state = self.state();
+            state.sent.push(SentSignal { pid, signal, at });
+            if let Some(fake) = state.pids.get_mut(&pid) {
+                if signal != 0 && !fake.ignored.contains(&signal) {
+                    fake.exits_at = Some(at);
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::{Clock, ManualClock};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[test]
+    fn test_pid_identity_check() {
+        let own = std::process::id();
+        assert!(pid_belongs_to(own, Utc::now()));
+        // This process started well before now, but not before a record from far in the past
+        assert!(!pid_belongs_to(own, Utc::now() - chrono::Duration::days(365 * 50)));
+
+        // An exited but unreaped child is not alive, whatever kill(pid, 0) says
+        let mut child = std::process::Command::new("true").spawn().unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(200));
+        assert!(!pid_belongs_to(child.id(), Utc::now()));
+        assert!(!SystemPidProbe.is_alive(child.id()));
+        child.wait().unwrap();
+    }
+
+    #[test]
+    fn test_eperm_is_alive_unless_identity_says_otherwise() {
+        let eperm = || Err(std::io::Error::from_raw_os_error(libc::EPERM));
+        let esrch = || Err(std::io::Error::from_raw_os_error(libc::ESRCH));
+        assert_eq!(judge(Ok(()), None), PidLiveness::Alive);
+        assert_eq!(judge(Ok(()), Some(false)), PidLiveness::Gone);
+        assert_eq!(judge(eperm(), Some(true)), PidLiveness::Alive);
+        assert_eq!(judge(eperm(), Some(false)), PidLiveness::Gone);
+        assert_eq!(judge(eperm(), None), PidLiveness::Inconclusive);
+        assert_eq!(judge(esrch(), None), PidLiveness::Gone);
+        assert_eq!(judge(esrch(), Some(true)), PidLiveness::Gone);
+    }
+
+    #[test]
+    fn test_stat_parsing() {
+        // Captured from /proc/<pid>/stat
+        let sleeping = "48211 (sleep) S 48190 48211 48190 34816 48211 4194304 97 0 0 0 0 0 0 0 20 0 1 0 9612245 8343552 220 18446744073709551615 94870133293056 94870133309009 140724320106224 0 0 0 0 0 0 0 0 0 17 3 0 0 0 0 0 94870133323888 94870133325120 94870162939904 140724320110477 140724320110491 140724320110491 140724320112619 0";
+        let zombie = "48215 (true) Z 48211 48211 48190 34816 48211 4227084 85 0 0 0 0 0 0 0 20 0 1 0 9612246 0 0 18446744073709551615 0 0 0 0 0 0 0 0 0 0 0 0 17 1 0 0 0 0 0 0 0 0 0 0 0 0 0";
+        let odd_name = "1770 (my (odd) app) R 1 1770 1770 0 -1 4194560 350 0 0 0 5 2 0 0 20 0 4 0 1843 228704256 3021 18446744073709551615 1 1 0 0 0 0 0 4096 0 0 0 0 17 0 0 0 0 0 0 0 0 0 0 0 0 0 0";
+
+        assert_eq!(ProcStat::parse(sleeping), Some(ProcStat { state: 'S', ppid: 48190, start_ticks: 9612245 }));
+        let zombie = ProcStat::parse(zombie).unwrap();
+        assert!(zombie.is_zombie());
+        assert_eq!((zombie.ppid, zombie.start_ticks), (48211, 9612246));
+        assert_eq!(ProcStat::parse(odd_name), Some(ProcStat { state: 'R', ppid: 1, start_ticks: 1843 }));
+        assert!(!ProcStat::parse(odd_name).unwrap().is_zombie());
+
+        assert_eq!(ProcStat::parse(""), None);
+        assert_eq!(ProcStat::parse("12 (cut) Z 1 12"), None);
+    }
+
+    #[test]
+    fn test_real_zombie_has_its_parent() {
+        let mut child = std::process::Command::new("true").spawn().unwrap();
+        for _ in 0..100 {
+            if read_stat(child.id()).is_some_and(|stat| stat.is_zombie()) {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(20));
+        }
+        assert_eq!(SystemPidProbe.zombie_parent(child.id(), Utc::now()), Some(std::process::id()));
+        // Not the zombie of a record written before it started
+        assert_eq!(SystemPidProbe.zombie_parent(child.id(), Utc::now() - chrono::Duration::hours(1)), None);
+        child.wait().unwrap();
+        assert_eq!(SystemPidProbe.zombie_parent(child.id(), Utc::now()), None);
+        assert_eq!(SystemPidProbe.zombie_parent(std::process::id(), Utc::now()), None);
+    }
+
+    #[test]
+    fn test_fake_probe_follows_clock_and_signals() {
+        let clock = Arc::new(ManualClock::starting_now());
+        let probe = FakePidProbe::new(clock.clone());
+        probe.add(10);
+        probe.add(20);
+        probe.exit_after(10, Duration::from_secs(5));
+        probe.ignore_signal(20, libc::SIGTERM);
+
+        assert!(probe.is_alive(10) && !probe.is_alive(30));
+        assert!(probe.belongs_to(10, clock.now()));
+        assert!(!probe.belongs_to(10, clock.now() - chrono::Duration::minutes(1)), "recycled PID");
+        clock.advance(Duration::from_secs(5));
+        assert!(!probe.is_alive(10));
+        assert_eq!(probe.signal(10, libc::SIGTERM).unwrap_err().raw_os_error(), Some(libc::ESRCH));
+
+        probe.signal(20, libc::SIGTERM).unwrap();
+        assert!(probe.is_alive(20));
+        probe.signal(20, libc::SIGKILL).unwrap();
+        assert!(!probe.is_alive(20));
+        assert_eq!(probe.sent_signals().len(), 2);
+
+        probe.add(40);
+        assert_eq!(probe.zombie_parent(40, clock.now()), None);
+        probe.make_zombie(40, 1);
+        assert!(!probe.is_alive(40));
+        assert_eq!(probe.zombie_parent(40, clock.now()), Some(1));
+    }
+}
@@ -0,0 +1,129 @@
+//! `--format porcelain`: line-oriented output for scripts whose layout only changes
+//! together with [`PORCELAIN_VERSION`].
+//!
+//! - `list` prints one line per process with the [`LIST_FIELDS`] in order, separated by
+//!   tabs, and nothing at all when there are no processes.
+//! - `status` prints one `key<TAB>value` line for each of the [`STATUS_FIELDS`], in order.
+//!
+//! Missing values (a PID or exit code that was never recorded) are empty. Times are Unix
+//! epoch seconds. In values, backslash, tab, newline and carriage return are written as
+//! `\\`, `\t`, `\n` and `\r`, so every record is exactly one line and every field is
+//! free of tabs. Any change to the field lists or their meaning bumps the version.
+
+use crate::database::ProcessRecord;
+
+/// Version of the layout below
+pub const PORCELAIN_VERSION: u32 = 1;
+
+/// Fields of a `list` line, in output order
+pub const LIST_FIELDS: [&str; 6] = ["name", "status", "pid", "exit_code", "created_at", "log_path"];
+
+/// Keys of the `status` block, in output order
+pub const STATUS_FIELDS: [&str; 9] = [
+    "name",
+    "status",
+    "pid",
+    "exit_code",
+    "command",
+    "working_dir",
+    "created_at",
+    "updated_at",
+    "log_path",
+];
+
+/// Escape a value so it holds no tab or line break
+pub fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\t' => escaped.push_str("\\t"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn optional<T: ToString>(value: Option<T>) -> String {
+    value.map(|v| v.to_string()).unwrap_or_default()
+}
+
+/// Values of [`LIST_FIELDS`] for a process, unescaped
+fn list_values(process: &ProcessRecord) -> [String; LIST_FIELDS.len()] {
+    [
+        process.name.to_string(),
+        process.status.to_string(),
+        optional(process.pid),
+        optional(process.exit_code),
+        process.created_at.timestamp().to_string(),
+        process.log_path.clone(),
+    ]
+}
+
+/// Values of [`STATUS_FIELDS`] for a process, unescaped
+fn status_values(process: &ProcessRecord) -> [String; STATUS_FIELDS.len()] {
+    [
+        process.name.to_string(),
+        process.status.to_string(),
+        optional(process.pid),
+        optional(process.exit_code),
+        process.command.clone(),
+        process.working_dir.clone(),
+        process.created_at.timestamp().to_string(),
+        process.updated_at.timestamp().to_string(),
+        process.log_path.clone(),
+    ]
+}
+
+/// One line per process, without a trailing newline
+pub fn format_list(processes: &[ProcessRecord]) -> String {
+    processes.iter().map(format_list_line).collect::<Vec<_>>().join("\n")
+}
+
+/// The `list` line of one process, without a newline
+pub fn format_list_line(process: &ProcessRecord) -> String {
+    list_values(process).iter().map(|value| escape(value)).collect::<Vec<_>>().join("\t")
+}
+
+/// The `key<TAB>value` block for one process, without a trailing newline
+pub fn format_status(process: &ProcessRecord) -> String {
+    STATUS_FIELDS
+        .iter()
+        .zip(status_values(process))
+        .map(|(key, value)| format!("{}\t{}", key, escape(&value)))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every porcelain layout that has shipped. Changing the fields without bumping
+    /// [`PORCELAIN_VERSION`] and adding a row here fails this test.
+    const RELEASED_LAYOUTS: &[(u32, &[&str], &[&str])] = &[(
+        1,
+        &["name", "status", "pid", "exit_code", "created_at", "log_path"],
+        &["name", "status", "pid", "exit_code", "command", "working_dir", "created_at", "updated_at", "log_path"],
+    )];
+
+    #[test]
+    fn test_layout_changes_require_version_bump() {
+        let (_, list, status) = RELEASED_LAYOUTS
+            .iter()
+            .find(|(version, _, _)| *version == PORCELAIN_VERSION)
+            .expect("a new porcelain version needs its layout recorded in RELEASED_LAYOUTS");
+        assert_eq!(&LIST_FIELDS[..], *list, "list fields changed without bumping PORCELAIN_VERSION");
+        assert_eq!(&STATUS_FIELDS[..], *status, "status fields changed without bumping PORCELAIN_VERSION");
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape("plain name"), "plain name");
+        assert_eq!(escape("a\tb\nc\rd\\e"), "a\\tb\\nc\\rd\\\\e");
+        // A literal backslash-t stays distinguishable from an escaped tab
+        assert_eq!(escape("\\t"), "\\\\t");
+    }
+}
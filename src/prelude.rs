@@ -0,0 +1,22 @@
+//! The types most programs embedding pmr need, in one import.
+//!
+//! ```no_run
+//! use pmr::prelude::*;
+//!
+//! # async fn run() -> Result<()> {
+//! let manager = ProcessManager::in_directory("/tmp/pmr-embedded").await?;
+//! let name: ProcessName = "worker".parse()?;
+//! manager.start(&name, "sleep", vec!["60".to_string()], Default::default(), None, None).await?;
+//! let record: ProcessRecord = manager.get_process_status(&name).await?;
+//! assert_eq!(record.status, ProcessStatus::Running);
+//! # Ok(())
+//! # }
+//! ```
+
+pub use crate::config::{Config, LogRotationConfig};
+pub use crate::database::{ProcessRecord, ProcessStatus};
+pub use crate::error::{Error, Result};
+pub use crate::events::{ProcessEvent, ProcessEventKind};
+pub use crate::name::ProcessName;
+pub use crate::outcome::{DeleteOutcome, RestartOutcome, StartOutcome, StopOutcome, UndeleteOutcome};
+pub use crate::process::{ProcessManager, RestartOptions, StartOptions, StopOptions};
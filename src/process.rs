@@ -1,631 +1,5551 @@
+//! Starting, stopping and supervising processes: [`ProcessManager`] and its options.
+//!
+//! # Stops and automatic restarts
+//!
+//! A process can be restarted without anyone asking, when files it watches change (see
+//! [`crate::file_watch`]), and that must never bring back a process the user just stopped.
+//! Every name has a stop mark, owned as follows:
+//!
+//! - [`ProcessManager::stop_with_options`] and [`ProcessManager::delete_with_options`] set
+//!   the mark before anything else, so before the child leaves the tracking map, a signal
+//!   is sent or the record says stopped.
+//! - An automatic restart holds the mark for its whole run. If the mark is set it does
+//!   nothing and says [`RestartSkip::StoppedOnPurpose`]; a stop arriving meanwhile waits
+//!   for the restart to finish and then stops the new instance.
+//! - An explicit start or restart clears the mark.
+//! - A hard delete, purge, ephemeral sweep or clear drops the mark with the record. A
+//!   single soft delete keeps it, so a restored process is still stopped on purpose.
+//!
+//! The reaper only ever sees children still in the tracking map, and a stop takes its child
+//! out of the map before signalling it, so the reaper never records the exit of a process
+//! being stopped as a crash of its own.
+
 use crate::{
-    config::Config,
-    database::{Database, ProcessRecord, ProcessStatus},
-    log_rotation::LogRotator,
+    affinity::{available_cpus, CpuSet},
+    archive::{write_tar_archive_to_path, ArchiveEntry},
+    cancel::Cancellation,
+    cgroup::{Cgroup, CgroupLimits},
+    clock::{Clock, SystemClock},
+    config::{Config, ConfigPaths, FlappingConfig},
+    database::{LogDirMode, HTTP_SERVER_PROCESS_NAME, LogRotationEntry, ProcessCounts, ProcessNote, ProcessKind, ProcessRecord, ProcessRun, ProcessStatus, Reservation, WatchRestartEntry},
+    drift::{check_drift, DriftReport, StartFingerprint},
+    env,
+    log_index::{index_path, read_since, LogIndex, LogSince, DEFAULT_STRIDE},
+    log_rotation::{read_last_lines, read_last_lines_lossy, read_tail_bytes, LogRotator, RotationOutcome, RotationRecord},
+    logs_quota::{self, LogsQuotaStats, LogsUsage, QuotaEnforcement, LOGS_QUOTA_CHECK_INTERVAL, LOGS_QUOTA_HISTORY_LIMIT, LOGS_QUOTA_TRIGGER},
+    events::{EventBus, ProcessEvent, ProcessEventKind},
+    file_pool::{FilePool, FilePoolStats},
+    file_watch::{WatchConfig, WATCH_HISTORY_LIMIT},
+    flapping::{is_failure, FailureHistory, FlappingProcess},
+    format::{human_bytes, human_duration},
+    messages,
+    inspect::{InspectEnvironment, InspectHistory, InspectLogs, InspectReport, Liveness},
+    limits,
+    name::ProcessName,
+    notify::Notifier,
+    permissions::{self, LoosePermissions},
+    outcome::{DeleteOutcome, NameCheck, NotRunning, ReloadOutcome, RestartOutcome, RestartSkip, StartExit, StartOutcome, StopOutcome, UndeleteOutcome},
+    pid_probe::{PidLiveness, PidProbe, SystemPidProbe, ZombieProcess},
+    resolution::{ResolutionCache, Resolver, SystemResolver},
+    retry_fs::{FileSystem, RealFs, RetryPolicy, RetryingFs},
+    snapshot::StartSnapshot,
+    start_queue::{self, StartQueue},
+    storage::{self, HostListing, StorageBackend},
+    umask::Umask,
     Error, Result,
 };
-use chrono::Utc;
+use futures_util::stream::{self, BoxStream, StreamExt};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::path::PathBuf;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use std::process::Stdio;
 use std::sync::Arc;
-use tokio::sync::Mutex;
+use tokio::sync::{broadcast, Mutex};
 use uuid::Uuid;
 
+/// What a clear, purge or sweep did. Every list is sorted by name, and a name is in
+/// exactly one of them: failed when any of its records could not be removed, otherwise
+/// cleared, and skipped only when nothing else happened to it.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[cfg_attr(feature = "http-api", derive(utoipa::ToSchema))]
 pub struct ClearResult {
     pub cleared_count: usize,
     pub cleared_processes: Vec<String>,
     pub failed_processes: Vec<String>,
+    /// Why each of `failed_processes` could not be removed, by name
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub details: BTreeMap<String, String>,
     pub operation_type: String,
+    /// Processes left untouched because the operation was cancelled
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub skipped_processes: Vec<String>,
+    #[serde(default)]
+    pub cancelled: bool,
+}
+
+impl ClearResult {
+    /// Nothing done yet; fill in with the `record_*` methods and [`Self::finish`]
+    fn new(operation_type: &str) -> Self {
+        Self {
+            cleared_count: 0,
+            cleared_processes: Vec::new(),
+            failed_processes: Vec::new(),
+            details: BTreeMap::new(),
+            operation_type: operation_type.to_string(),
+            skipped_processes: Vec::new(),
+            cancelled: false,
+        }
+    }
+
+    fn record_cleared(&mut self, name: String) {
+        self.cleared_processes.push(name);
+    }
+
+    /// Keeps the first reason when a name fails more than once
+    fn record_failed(&mut self, name: String, error: String) {
+        self.details.entry(name.clone()).or_insert(error);
+        self.failed_processes.push(name);
+    }
+
+    fn record_skipped(&mut self, name: String) {
+        self.skipped_processes.push(name);
+    }
+
+    /// Sort the lists and leave each name in one of them, see [`ClearResult`]
+    fn finish(mut self) -> Self {
+        for names in [&mut self.failed_processes, &mut self.cleared_processes, &mut self.skipped_processes] {
+            names.sort();
+            names.dedup();
+        }
+        let failed: HashSet<String> = self.failed_processes.iter().cloned().collect();
+        self.cleared_processes.retain(|name| !failed.contains(name));
+        let cleared: HashSet<&String> = self.cleared_processes.iter().collect();
+        self.skipped_processes.retain(|name| !failed.contains(name) && !cleared.contains(name));
+        self.cleared_count = self.cleared_processes.len();
+        self.cancelled = !self.skipped_processes.is_empty();
+        self
+    }
+}
+
+/// Optional settings for starting a process
+#[derive(Debug, Clone, Default)]
+pub struct StartOptions {
+    /// Working directory (defaults to the current directory)
+    pub working_dir: Option<String>,
+    /// Log directory (defaults to the configured log directory)
+    pub log_dir: Option<String>,
+    /// cgroup v2 limits to enforce on the process
+    pub cgroup_limits: CgroupLimits,
+    /// CPUs to pin the process to (Linux only)
+    pub cpu_affinity: Option<CpuSet>,
+    /// Umask of the process; None inherits the umask of pmr
+    pub umask: Option<Umask>,
+    /// What the process is for, see [`limits::clean_description`]
+    pub description: Option<String>,
+    /// Succeed without changes when an identical process is already running, and start a
+    /// stopped or failed one with the same definition instead of reporting that it exists
+    pub idempotent: bool,
+    /// Record what /proc shows about the child right after spawn (Linux only)
+    pub capture_snapshot: bool,
+    /// Called as each phase of the start finishes or fails
+    pub progress: Option<StartProgress>,
+    /// Treat any exit within the start window as a failure: the start is rolled back and
+    /// [`Error::StartExited`] returned instead of recording a stopped or failed process.
+    /// For jobs only a failing exit counts.
+    pub strict: bool,
+    /// Service (the default) or oneshot job
+    pub kind: ProcessKind,
+    /// Run a job again although it already ran under this name: its previous log is
+    /// archived and its run count goes up. Without it a succeeded job is not started again.
+    pub rerun: bool,
+    /// Signal stops send first unless they name their own (defaults to SIGTERM)
+    pub stop_signal: Option<i32>,
+    /// Where the log goes without a `log_dir`: the default directory or `logs/` in the
+    /// working directory (defaults to the configured mode). An explicit `log_dir` wins,
+    /// and `workdir` without a `working_dir` behaves like `default`.
+    pub log_dir_mode: Option<LogDirMode>,
+    /// Mark the record as pmr's own, hiding it from listings, counts and clears. Only
+    /// internal starts may use [`HTTP_SERVER_PROCESS_NAME`].
+    pub internal: bool,
+    /// Delete the record and its logs this long after the process exits; None keeps it
+    /// until it is deleted or cleared
+    pub ephemeral_keep: Option<std::time::Duration>,
+    /// Record the process as running right after spawn and watch the start window from a
+    /// background task, so consecutive starts do not each wait it out. The outcome then
+    /// says running; [`ProcessManager::finish_start_check`] waits for the final answer.
+    /// Ignored by strict starts, which must see the window through before returning.
+    pub check_in_background: bool,
+    /// Restart the process when these paths change; relative paths are taken from the
+    /// working directory
+    pub watch: Option<WatchConfig>,
+    /// Wait in the start queue and retry when the spawn fails for lack of processes or
+    /// memory, instead of failing; `[start_queue] enabled` turns this on for every start
+    pub queue_on_pressure: bool,
+    /// Shared by the starts of one batch so a command and working directory they have in
+    /// common are resolved and hashed once, see [`ProcessManager::resolution_cache`]; None
+    /// looks them up afresh
+    pub resolution: Option<ResolutionCache>,
+    /// Whether this start, and the restarts after it, rotate a previous log over the size
+    /// limit before appending to it; None follows [`Config::rotate_logs_on_start`]
+    pub rotate_on_start: Option<bool>,
+    /// Public id of the API token starting the process. A name it reserved is taken over,
+    /// see [`ProcessManager::reserve_name`]; a name another token reserved is refused.
+    pub reservation_token: Option<String>,
+}
+
+/// Phases of a start, in the order they run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StartPhase {
+    /// Environment, affinity, name and capacity checks, plus cgroup creation
+    Validate,
+    /// Log directory, rotation of the previous log and the new log file
+    Log,
+    /// Waiting in the start queue after a spawn failed under resource pressure; only
+    /// reported for queued starts, followed by the spawn that got through or failed
+    Queue,
+    Spawn,
+    /// The start window in which an early exit marks the start as failed
+    Wait,
+    Record,
+}
+
+impl StartPhase {
+    fn next(self) -> Self {
+        match self {
+            Self::Validate => Self::Log,
+            Self::Log | Self::Queue => Self::Spawn,
+            Self::Spawn => Self::Wait,
+            Self::Wait | Self::Record => Self::Record,
+        }
+    }
+}
+
+impl std::fmt::Display for StartPhase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let label = match self {
+            Self::Validate => "validate",
+            Self::Log => "log",
+            Self::Queue => "queue",
+            Self::Spawn => "spawn",
+            Self::Wait => "wait",
+            Self::Record => "record",
+        };
+        write!(f, "{}", label)
+    }
+}
+
+/// How one phase of a start went
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StartPhaseReport {
+    pub phase: StartPhase,
+    /// What the phase did, or the error it failed with
+    pub detail: String,
+    pub elapsed_ms: u64,
+    pub failed: bool,
+}
+
+impl std::fmt::Display for StartPhaseReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.failed {
+            write!(f, "{} failed: {} ({}ms)", self.phase, self.detail, self.elapsed_ms)
+        } else {
+            write!(f, "{}: {} ({}ms)", self.phase, self.detail, self.elapsed_ms)
+        }
+    }
+}
+
+/// Callback receiving start phases as they happen, for `pmr start --verbose`
+#[derive(Clone)]
+pub struct StartProgress(Arc<dyn Fn(&StartPhaseReport) + Send + Sync>);
+
+impl StartProgress {
+    pub fn new(callback: impl Fn(&StartPhaseReport) + Send + Sync + 'static) -> Self {
+        Self(Arc::new(callback))
+    }
+}
+
+impl std::fmt::Debug for StartProgress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("StartProgress(..)")
+    }
+}
+
+/// Times the phases of one start and hands them to its progress callback, along with the
+/// filesystem retries each phase needed
+struct PhaseTracker {
+    progress: Option<StartProgress>,
+    phase: StartPhase,
+    since: std::time::Instant,
+    /// File operations of the start, retried when they fail transiently
+    files: RetryingFs,
+}
+
+impl PhaseTracker {
+    fn new(progress: Option<StartProgress>, files: RetryingFs) -> Self {
+        Self { progress, phase: StartPhase::Validate, since: std::time::Instant::now(), files }
+    }
+
+    /// Report the current phase as done and move on to the next one
+    fn finish(&mut self, detail: String) {
+        self.report(detail, false);
+        self.phase = self.phase.next();
+    }
+
+    /// Report the current phase as the one that failed
+    fn fail(&mut self, error: &Error) {
+        self.report(error.to_string(), true);
+    }
+
+    /// Report joining the start queue; the current phase carries on once the wait ends
+    fn queued(&mut self, detail: String) {
+        let phase = std::mem::replace(&mut self.phase, StartPhase::Queue);
+        self.report(detail, false);
+        self.phase = phase;
+    }
+
+    fn report(&mut self, mut detail: String, failed: bool) {
+        let (retries, waited) = self.files.tally().take();
+        if retries > 0 {
+            detail.push_str(&format!(" ({} filesystem {}, waited {}ms)", retries, if retries == 1 { "retry" } else { "retries" }, waited.as_millis()));
+        }
+        if let Some(progress) = &self.progress {
+            (progress.0)(&StartPhaseReport {
+                phase: self.phase,
+                detail,
+                elapsed_ms: self.since.elapsed().as_millis() as u64,
+                failed,
+            });
+        }
+        self.since = std::time::Instant::now();
+    }
+}
+
+/// Optional settings for stopping a process
+#[derive(Debug, Clone, Default)]
+pub struct StopOptions {
+    /// Signal sent first (defaults to the one the process was started with)
+    pub signal: Option<i32>,
+    /// Send SIGKILL if the process is still alive after the grace period
+    pub escalate: bool,
+}
+
+/// Optional settings for restarting a process
+#[derive(Debug, Clone, Default)]
+pub struct RestartOptions {
+    /// Rotate the current log before the new instance starts
+    pub reset_logs: bool,
+    /// Only restart when the binary differs from the one recorded at start
+    pub only_if_changed: bool,
+    /// Restart even while the process is flapping
+    pub force: bool,
+}
+
+/// Optional settings for deleting a process
+#[derive(Debug, Clone, Default)]
+pub struct DeleteOptions {
+    /// Remove the record and its log file immediately instead of soft-deleting it
+    pub hard: bool,
+}
+
+/// Optional settings for reading a process's logs
+#[derive(Debug, Clone, Default)]
+pub struct LogOptions {
+    /// Keep only this many entries from the end (None keeps everything)
+    pub lines: Option<usize>,
+    /// Show each run of identical consecutive lines once, with a repeat count.
+    /// Applied before `lines`, so the limit counts collapsed entries.
+    pub collapse_repeats: bool,
+    /// Keep only this many bytes from the end instead of counting lines, for logs of very
+    /// long lines; only the live log is read this way. Cannot be combined with `lines`.
+    pub tail_bytes: Option<u64>,
+    /// Keep the partial line a `tail_bytes` cut lands in instead of starting at the next one
+    pub allow_partial: bool,
+    /// Keep only the lines of the live log timed at or after this, see [`crate::log_index`].
+    /// Applied before `collapse_repeats` and `lines`; cannot be combined with `tail_bytes`.
+    pub since: Option<LogSince>,
+}
+
+/// How listing processes or showing one brings the stored statuses up to date
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StatusRefresh {
+    /// Report the stored statuses without probing any PID (`false`)
+    Stored,
+    /// Probe the PID of every record (`true`)
+    #[default]
+    All,
+    /// Probe only records whose status was not verified within the configured
+    /// `status_stale_after` window (`stale`)
+    Stale,
+}
+
+impl std::str::FromStr for StatusRefresh {
+    type Err = Error;
+
+    fn from_str(mode: &str) -> Result<Self> {
+        match mode {
+            "false" => Ok(StatusRefresh::Stored),
+            "true" => Ok(StatusRefresh::All),
+            "stale" => Ok(StatusRefresh::Stale),
+            _ => Err(Error::Other(format!("Unknown refresh mode '{}': expected true, false or stale", mode))),
+        }
+    }
+}
+
+/// One rotated log file of a process, without its content
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "http-api", derive(utoipa::ToSchema))]
+pub struct RotatedLog {
+    /// `N` in `<name>.<N>.log`; 1 is the newest archive
+    pub index: usize,
+    pub path: String,
+    pub size_bytes: u64,
+    pub modified: chrono::DateTime<chrono::Utc>,
+    /// The archive is the gzip-compressed `.log.gz` form
+    pub compressed: bool,
+}
+
+/// Log content of a process as read for display
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "http-api", derive(utoipa::ToSchema))]
+pub struct ProcessLogs {
+    pub content: String,
+    /// Some bytes were not UTF-8 and show as U+FFFD in `content`
+    pub contains_invalid_utf8: bool,
+    /// Byte offset in the log file `content` starts at; only known for a `tail_bytes` read
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start_offset: Option<u64>,
+}
+
+/// Content of the rotated logs of a process, read up to a byte limit
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "http-api", derive(utoipa::ToSchema))]
+pub struct RotatedLogsContent {
+    /// Each archive, newest first, after a `=== <path> ===` line
+    pub content: String,
+    /// Bytes of `content`
+    pub bytes: u64,
+    /// Combined size of the archives that were read, in full
+    pub total_bytes: u64,
+    /// `content` stopped at the limit before the end of the archives
+    pub truncated: bool,
+}
+
+/// Size of the blocks rotated logs are read in
+const ROTATED_READ_CHUNK: usize = 64 * 1024;
+
+/// Append as much of `bytes` as fits within `max` bytes; false when some was left out
+fn push_capped(buffer: &mut Vec<u8>, bytes: &[u8], max: usize) -> bool {
+    let room = max.saturating_sub(buffer.len());
+    if bytes.len() <= room {
+        buffer.extend_from_slice(bytes);
+        true
+    } else {
+        buffer.extend_from_slice(&bytes[..room]);
+        false
+    }
+}
+
+/// Snapshot for `pmr stats`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "http-api", derive(utoipa::ToSchema))]
+pub struct ManagerStats {
+    /// Processes currently managed (soft-deleted ones excluded)
+    pub managed: usize,
+    /// Configured ceiling; 0 means unlimited
+    pub max_processes: usize,
+    pub counts: ProcessCounts,
+    /// Seconds since the longest-failed process failed; None when none is failed
+    pub oldest_failed_age_seconds: Option<u64>,
+    /// Seconds since the longest-stopped process stopped; None when none is stopped
+    pub oldest_stopped_age_seconds: Option<u64>,
+    pub paths: ConfigPaths,
+    pub logs: LogsQuotaStats,
+}
+
+/// Gauges for alerting on processes left failed or stopped. The ages are measured from
+/// `updated_at`, which every status change bumps.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "http-api", derive(utoipa::ToSchema))]
+pub struct StatusGauges {
+    pub counts: ProcessCounts,
+    /// Seconds since the longest-failed process failed; None when none is failed
+    pub oldest_failed_age_seconds: Option<u64>,
+    /// Seconds since the longest-stopped process stopped; None when none is stopped
+    pub oldest_stopped_age_seconds: Option<u64>,
+}
+
+/// Outcome of waiting for processes to reach a status
+#[derive(Debug, Clone, Default)]
+pub struct WaitReport {
+    /// Processes that reached the status, in the order they got there
+    pub ready: Vec<ProcessName>,
+    /// Last seen record of every process that did not reach it
+    pub not_ready: Vec<ProcessRecord>,
+}
+
+impl WaitReport {
+    pub fn all_ready(&self) -> bool {
+        self.not_ready.is_empty()
+    }
+}
+
+/// Resource usage of a live process, read from /proc
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "http-api", derive(utoipa::ToSchema))]
+pub struct ResourceUsage {
+    pub rss_kb: u64,
+    pub threads: u32,
+}
+
+/// Read resident memory and thread count from /proc/<pid>/status
+pub fn read_resource_usage(pid: u32) -> Option<ResourceUsage> {
+    let status = std::fs::read_to_string(format!("/proc/{}/status", pid)).ok()?;
+    let field = |key: &str| {
+        status
+            .lines()
+            .find_map(|line| line.strip_prefix(key))
+            .and_then(|value| value.split_whitespace().next())
+            .and_then(|value| value.parse::<u64>().ok())
+    };
+
+    Some(ResourceUsage {
+        rss_kb: field("VmRSS:").unwrap_or(0),
+        threads: field("Threads:").unwrap_or(0) as u32,
+    })
+}
+
+/// How a process that a stop found already gone had ended
+fn not_running(process: &ProcessRecord) -> NotRunning {
+    NotRunning {
+        status: process.status.clone(),
+        exit_code: process.exit_code,
+        since: process.pid.map(|_| process.updated_at),
+    }
+}
+
+/// Where a [`ProcessManager::stream_processes`] listing is
+struct StreamedListing<'a> {
+    rows: BoxStream<'a, Result<ProcessRecord>>,
+    /// Names of flapping processes, looked up when the first record is asked for
+    flapping: Option<Vec<String>>,
+    /// Records probed since verification times were last stored
+    verified: Vec<String>,
+}
+
+/// The records that are not pmr's own
+fn user_processes(processes: Vec<ProcessRecord>) -> Vec<ProcessRecord> {
+    processes.into_iter().filter(|process| !process.internal).collect()
+}
+
+/// When an ephemeral process kept `keep_secs` after exiting at `exited_at` is deleted
+fn deletion_deadline(keep_secs: u64, exited_at: chrono::DateTime<chrono::Utc>) -> chrono::DateTime<chrono::Utc> {
+    exited_at + chrono::Duration::seconds(keep_secs.min(i64::MAX as u64) as i64)
+}
+
+/// Whether a process ending in `status` gets its last output captured: failures and
+/// non-zero exits, never a clean stop or a stop by signal
+fn keeps_last_output(status: &ProcessStatus, exit_code: Option<i32>) -> bool {
+    *status == ProcessStatus::Failed || (*status == ProcessStatus::Stopped && exit_code.is_some_and(|code| code != 0))
+}
+
+/// The end of a log as kept on a failed record, and whether it held bytes that are not UTF-8
+fn capture_last_output(log_path: &str) -> Option<(Vec<String>, bool)> {
+    let (lines, lossy) = read_last_lines_lossy(Path::new(log_path), LAST_OUTPUT_LINES).ok()?;
+    let lines = lines
+        .into_iter()
+        .map(|mut line| {
+            if line.len() > LAST_OUTPUT_LINE_BYTES {
+                let mut end = LAST_OUTPUT_LINE_BYTES;
+                while !line.is_char_boundary(end) {
+                    end -= 1;
+                }
+                line.truncate(end);
+                line.push('…');
+            }
+            line
+        })
+        .collect();
+    Some((lines, lossy))
+}
+
+/// Last `lines` lines of `content`, joined with `\n` and without a trailing newline.
+///
+/// `lines == 0` yields no output; asking for more lines than exist returns every line.
+pub fn tail_lines(content: &str, lines: usize) -> String {
+    let all: Vec<&str> = content.lines().collect();
+    let start = all.len().saturating_sub(lines);
+    all[start..].join("\n")
+}
+
+/// Replace each run of identical consecutive lines with the line followed by
+/// `  [repeated N times]`, N being the length of the run.
+///
+/// Purely presentational: the log file itself is never rewritten.
+pub fn collapse_repeats(content: &str) -> String {
+    let mut collapsed = Vec::new();
+    let mut lines = content.lines().peekable();
+    while let Some(line) = lines.next() {
+        let mut count = 1;
+        while lines.next_if_eq(&line).is_some() {
+            count += 1;
+        }
+        collapsed.push(repeat_entry(line, count));
+    }
+    collapsed.join("\n")
+}
+
+/// A line as shown after collapsing a run of `count` copies of it
+fn repeat_entry(line: &str, count: usize) -> String {
+    if count > 1 {
+        format!("{}  [repeated {} times]", line, count)
+    } else {
+        line.to_string()
+    }
+}
+
+/// How often a stopping process is checked for exit
+const STOP_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(50);
+
+/// How often a new process is checked for an early exit during the start window
+const START_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(10);
+
+/// Log lines included in the message of a start whose process failed right away
+const START_FAILURE_LOG_LINES: usize = 10;
+
+/// Log lines kept on the record of a process that failed, for `pmr status`
+pub const LAST_OUTPUT_LINES: usize = 15;
+
+/// Longer captured lines are cut to this many bytes, keeping the record small
+pub const LAST_OUTPUT_LINE_BYTES: usize = 512;
+
+/// Verified records a streamed listing stores at once
+const VERIFIED_BATCH: usize = 256;
+
+/// Reason in [`ClearResult::details`] for a record removed by someone else mid-operation
+const VANISHED_DURING_CLEAR: &str = "the record was removed by another operation first";
+
+/// Longest note accepted by [`ProcessManager::annotate_process`], in bytes
+pub const MAX_NOTE_BYTES: usize = 4096;
+
+/// Notes a single process can carry
+pub const MAX_NOTES_PER_PROCESS: usize = 100;
+
+/// Finished runs kept per process, see [`ProcessManager::process_runs`]
+pub const RUN_HISTORY_LIMIT: usize = 50;
+
+/// Runs a status shows and averages its run duration over
+pub const RECENT_RUNS: usize = 10;
+
+/// How long an ephemeral process is kept after it exits unless its start says otherwise
+pub const DEFAULT_EPHEMERAL_KEEP: std::time::Duration = std::time::Duration::from_secs(10 * 60);
+
+/// How long a name reservation lasts unless the request says otherwise
+pub const DEFAULT_RESERVATION_TTL: std::time::Duration = std::time::Duration::from_secs(5 * 60);
+
+/// Longest a name reservation may last; a provisioning run that needs more can renew it
+pub const MAX_RESERVATION_TTL: std::time::Duration = std::time::Duration::from_secs(24 * 3600);
+
+/// Final status and exit of a process whose start window was watched in the background,
+/// when it exited within it
+type StartCheck = (ProcessStatus, StartExit);
+
+/// A process that exited within the start window
+struct EarlyExit {
+    /// None when it was killed by a signal
+    code: Option<i32>,
+    elapsed: std::time::Duration,
+}
+
+impl EarlyExit {
+    /// Stopped after a clean exit, failed otherwise (jobs: succeeded or failed)
+    fn status(&self, kind: ProcessKind) -> ProcessStatus {
+        let status = if self.code == Some(0) { ProcessStatus::Stopped } else { ProcessStatus::Failed };
+        kind.exit_status(status, self.code)
+    }
+
+    /// The exit as reported to the caller, with the end of the log when it failed
+    fn into_start_exit(self, log_path: &Path) -> StartExit {
+        StartExit {
+            exit_code: self.code,
+            elapsed_ms: self.elapsed.as_millis() as u64,
+            log_tail: if self.code == Some(0) {
+                Vec::new()
+            } else {
+                read_last_lines(log_path, START_FAILURE_LOG_LINES).unwrap_or_default()
+            },
+        }
+    }
+}
+
+/// What a start has created so far, undone if a later step fails
+#[derive(Default)]
+struct StartArtifacts {
+    /// Log directories created by this start, deepest first
+    created_dirs: Vec<PathBuf>,
+    /// Rotation of the previous log done by this start
+    rotation: Option<RotationRecord>,
+    /// Log file created by this start (None when it already existed)
+    created_log_file: Option<PathBuf>,
+    cgroup: Option<Cgroup>,
+    /// Spawned child, tracked in `running_processes`
+    child_pid: Option<u32>,
+}
+
+/// Start steps that unit tests can make fail to exercise the rollback
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StartStep {
+    CreateLog,
+    Spawn,
+    InsertRecord,
+}
+
+/// Where a start stores its record
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RecordSlot {
+    /// A new row; the name must be free
+    New,
+    /// The live row a restart reset, overwritten in place
+    Reset,
+}
+
+/// Working directory a start uses: the requested one, or the current directory
+fn resolve_working_dir(working_dir: Option<String>) -> String {
+    working_dir.unwrap_or_else(|| std::env::current_dir().unwrap_or_default().to_string_lossy().to_string())
+}
+
+/// Directories `create_dir_all(dir)` would create, deepest first
+fn missing_directories(dir: &Path) -> Vec<PathBuf> {
+    dir.ancestors()
+        .take_while(|ancestor| !ancestor.as_os_str().is_empty() && !ancestor.exists())
+        .map(Path::to_path_buf)
+        .collect()
+}
+
+/// First delay between status polls while waiting; doubled after every round
+const WAIT_INITIAL_BACKOFF: std::time::Duration = std::time::Duration::from_millis(100);
+
+/// Upper bound for the delay between status polls while waiting
+const WAIT_MAX_BACKOFF: std::time::Duration = std::time::Duration::from_secs(2);
+
+/// Parse a signal name ("TERM", "SIGTERM") or number ("15")
+pub fn parse_signal(signal: &str) -> Result<i32> {
+    let trimmed = signal.trim();
+    if let Ok(number) = trimmed.parse::<i32>() {
+        if number > 0 && number < 32 {
+            return Ok(number);
+        }
+        return Err(Error::Other(format!("Invalid signal number: {}", number)));
+    }
+
+    let upper = trimmed.to_ascii_uppercase();
+    let name = upper.strip_prefix("SIG").unwrap_or(&upper);
+    let number = match name {
+        "HUP" => libc::SIGHUP,
+        "INT" => libc::SIGINT,
+        "QUIT" => libc::SIGQUIT,
+        "KILL" => libc::SIGKILL,
+        "USR1" => libc::SIGUSR1,
+        "USR2" => libc::SIGUSR2,
+        "TERM" => libc::SIGTERM,
+        _ => return Err(Error::Other(format!("Unknown signal: {}", signal))),
+    };
+    Ok(number)
+}
+
+/// Name of a signal accepted by [`parse_signal`] ("SIGTERM"), or its number for others
+pub fn signal_name(signal: i32) -> String {
+    let name = match signal {
+        libc::SIGHUP => "SIGHUP",
+        libc::SIGINT => "SIGINT",
+        libc::SIGQUIT => "SIGQUIT",
+        libc::SIGKILL => "SIGKILL",
+        libc::SIGUSR1 => "SIGUSR1",
+        libc::SIGUSR2 => "SIGUSR2",
+        libc::SIGTERM => "SIGTERM",
+        _ => return signal.to_string(),
+    };
+    name.to_string()
 }
 
 pub struct ProcessManager {
-    db: Database,
-    config: Config,
-    log_rotator: LogRotator,
+    db: Arc<dyn StorageBackend>,
+    /// Swapped whole by [`ProcessManager::reload_config`]; readers take a snapshot
+    config: Arc<std::sync::RwLock<Arc<Config>>>,
+    log_rotator: Arc<LogRotator>,
+    notifier: Notifier,
+    events: EventBus,
     // Track running processes to properly reap them
     running_processes: Arc<Mutex<HashMap<u32, tokio::process::Child>>>,
+    /// Running processes found in the database at construction, which this manager did not
+    /// spawn; the reaper polls them since they cannot be waited for
+    external_processes: Arc<std::sync::Mutex<HashMap<u32, ExternalProcess>>>,
+    /// Children whose start has not recorded them yet, or whose start window a background
+    /// check is still watching; the reaper leaves them alone so the start classifies their
+    /// exit with its code
+    in_start_window: Arc<std::sync::Mutex<HashSet<u32>>>,
+    /// Children reaped and no longer tracked whose exit is still being stored; a refresh
+    /// leaves their records to the reaper rather than calling them stopped
+    recording_exits: Arc<std::sync::Mutex<HashSet<u32>>>,
+    /// Last measurement of the logs' disk usage, reused by quota checks while it is fresh
+    logs_usage: Arc<std::sync::Mutex<Option<LogsUsage>>>,
+    /// Bounds the file operations of sweeps over every process, see [`crate::file_pool`]
+    file_pool: FilePool,
+    /// Log files and directories of starts and their rollbacks, see [`crate::retry_fs`]
+    file_system: Arc<dyn FileSystem>,
+    /// Background start checks by PID, until [`ProcessManager::finish_start_check`] collects them
+    start_checks: std::sync::Mutex<HashMap<u32, tokio::task::JoinHandle<Option<StartCheck>>>>,
+    cancellation: Cancellation,
+    reaper: std::sync::Mutex<Option<Reaper>>,
+    /// Set by [`ProcessManager::read_only`]; mutating methods fail with [`Error::ReadOnly`]
+    read_only: bool,
+    /// Set while refreshed statuses cannot be stored because the database storage is
+    /// read-only, full or failing, see [`ProcessManager::is_degraded`]
+    degraded: std::sync::atomic::AtomicBool,
+    clock: Arc<dyn Clock>,
+    pid_probe: Arc<dyn PidProbe>,
+    /// Looks up executables and working directories for [`ProcessManager::resolution_cache`]
+    resolver: Arc<dyn Resolver>,
+    /// Stop marks by name, see "Stops and automatic restarts" in the module docs; true while
+    /// the process was stopped on purpose
+    stop_marks: Arc<std::sync::Mutex<HashMap<ProcessName, Arc<Mutex<bool>>>>>,
+    /// Starts waiting out resource pressure, see [`crate::start_queue`]
+    start_queue: StartQueue,
+    /// Set by [`ProcessManager::simulate_spawn_pressure`]
+    #[cfg(any(test, feature = "test-util"))]
+    spawn_pressure: std::sync::atomic::AtomicBool,
+    #[cfg(test)]
+    fail_start_at: Option<StartStep>,
+    /// Waited on twice by a restart between stopping the process and re-reading its
+    /// definition, so a test can change the definition in between
+    #[cfg(test)]
+    restart_pause: Option<Arc<tokio::sync::Barrier>>,
+    /// Reaping passes run so far, to check the reaper stops with its manager
+    #[cfg(test)]
+    reaper_ticks: Arc<std::sync::atomic::AtomicUsize>,
+}
+
+/// Builds a [`ProcessManager`] with a substitute clock or PID probe, see
+/// [`ProcessManager::builder`]. Anything not set uses the real implementation.
+#[cfg(any(test, feature = "test-util"))]
+pub struct ProcessManagerBuilder {
+    config: Config,
+    clock: Arc<dyn Clock>,
+    pid_probe: Arc<dyn PidProbe>,
+}
+
+#[cfg(any(test, feature = "test-util"))]
+impl ProcessManagerBuilder {
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    pub fn pid_probe(mut self, pid_probe: Arc<dyn PidProbe>) -> Self {
+        self.pid_probe = pid_probe;
+        self
+    }
+
+    pub async fn build(self) -> Result<ProcessManager> {
+        ProcessManager::with_runtime(self.config, self.clock, self.pid_probe).await
+    }
+}
+
+/// A process adopted from the database by [`ProcessManager::reattach_running_processes`]
+struct ExternalProcess {
+    name: ProcessName,
+    created_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// Recorded as the exit reason of adopted processes, whose exit codes cannot be collected
+pub const EXTERNAL_EXIT_REASON: &str = "exit code unavailable: not a child of this pmr instance";
+
+/// Recorded as the exit reason of processes found as zombies: they have exited, but their
+/// exit status belongs to a parent that has not collected it
+pub const ZOMBIE_EXIT_REASON: &str = "exit status unobtainable: exited as a zombie its parent has not reaped";
+
+/// Whether `pid` is a zombie whose parent is holding on to it. One left to init is about to
+/// be reaped, and is no different from any other exited process pmr did not spawn.
+fn is_unreaped_zombie(pid_probe: &dyn PidProbe, pid: u32, created_at: chrono::DateTime<chrono::Utc>) -> bool {
+    pid_probe.zombie_parent(pid, created_at).is_some_and(|parent| parent > 1)
+}
+
+/// Background reaper task and the means to stop it
+struct Reaper {
+    shutdown: tokio::sync::oneshot::Sender<()>,
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl Drop for ProcessManager {
+    fn drop(&mut self) {
+        if let Some(reaper) = self.reaper.get_mut().unwrap_or_else(|e| e.into_inner()).take() {
+            reaper.task.abort();
+        }
+    }
 }
 
 impl ProcessManager {
+    #[tracing::instrument(name = "manager.init", skip_all)]
     pub async fn new(config: Config) -> Result<Self> {
+        Self::with_runtime(config, Arc::new(SystemClock::new()), Arc::new(SystemPidProbe)).await
+    }
+
+    /// Start building a manager whose clock and PID liveness are supplied by the caller,
+    /// for tests that should neither sleep nor spawn
+    #[cfg(any(test, feature = "test-util"))]
+    pub fn builder(config: Config) -> ProcessManagerBuilder {
+        ProcessManagerBuilder {
+            config,
+            clock: Arc::new(SystemClock::new()),
+            pid_probe: Arc::new(SystemPidProbe),
+        }
+    }
+
+    async fn with_runtime(config: Config, clock: Arc<dyn Clock>, pid_probe: Arc<dyn PidProbe>) -> Result<Self> {
+        let (errors, warnings): (Vec<_>, Vec<_>) = config.validate().into_iter().partition(|issue| issue.is_error());
+        if !errors.is_empty() {
+            return Err(Error::InvalidConfig(errors));
+        }
+        for warning in warnings {
+            eprintln!("Warning: config {}: {}", warning.key, warning.message);
+        }
         config.ensure_directories()?;
-        // Add create_if_missing parameter to SQLite URL to automatically create the database file
-        let database_url = format!("sqlite:{}?mode=rwc", config.database_path.display());
-        let db = Database::new(&database_url).await?;
-        let log_rotator = LogRotator::new(config.log_rotation.clone());
+        let db = storage::open(&config).await?;
+        for loose in permissions::check(&permissions::secret_paths(&config)) {
+            eprintln!("Warning: {}; run 'pmr doctor --fix-permissions' to restrict it to its owner", loose);
+        }
+        let log_rotator = Arc::new(LogRotator::new(config.log_rotation.clone()));
         let running_processes = Arc::new(Mutex::new(HashMap::new()));
+        let events = EventBus::new();
+        // A broken webhook setting must not make process management unusable; the sender
+        // still starts, so a reload that fixes it takes effect
+        let notifier = Notifier::start(&config.notifications, &events).unwrap_or_else(|e| {
+            eprintln!("Warning: webhook notifications disabled: {}", e);
+            Notifier::start(&Default::default(), &events).unwrap_or_default()
+        });
 
-        let process_manager = Self {
+        let mut process_manager = Self {
             db,
-            config,
+            file_pool: FilePool::new(&config.file_ops),
+            file_system: Arc::new(RealFs),
+            config: Arc::new(std::sync::RwLock::new(Arc::new(config))),
             log_rotator,
-            running_processes: running_processes.clone()
+            notifier,
+            events,
+            running_processes: running_processes.clone(),
+            external_processes: Arc::default(),
+            in_start_window: Arc::default(),
+            recording_exits: Arc::default(),
+            logs_usage: Arc::default(),
+            start_checks: std::sync::Mutex::default(),
+            cancellation: Cancellation::new(),
+            reaper: std::sync::Mutex::new(None),
+            read_only: false,
+            degraded: Default::default(),
+            clock,
+            pid_probe,
+            resolver: Arc::new(SystemResolver),
+            stop_marks: Arc::default(),
+            start_queue: StartQueue::default(),
+            #[cfg(any(test, feature = "test-util"))]
+            spawn_pressure: Default::default(),
+            #[cfg(test)]
+            fail_start_at: None,
+            #[cfg(test)]
+            restart_pause: None,
+            #[cfg(test)]
+            reaper_ticks: Arc::default(),
         };
 
+        // Children of an earlier manager are watched from here on
+        if let Err(e) = process_manager.reattach_running_processes().await {
+            eprintln!("Warning: failed to reattach running processes: {}", e);
+        }
+
         // Start background task to reap zombie processes
-        process_manager.start_process_reaper().await;
+        let reaper = process_manager.start_process_reaper();
+        *process_manager.reaper.get_mut().unwrap() = Some(reaper);
+
+        // Soft-deleted records past their retention are purged automatically, and so are
+        // ephemeral processes past their grace period and expired name reservations
+        if let Err(e) = process_manager.purge_deleted_processes().await {
+            eprintln!("Warning: failed to purge deleted processes: {}", e);
+        }
+        if let Err(e) = process_manager.sweep_ephemeral_processes().await {
+            eprintln!("Warning: failed to delete expired ephemeral processes: {}", e);
+        }
+        if let Err(e) = process_manager.purge_expired_reservations().await {
+            eprintln!("Warning: failed to purge expired name reservations: {}", e);
+        }
 
         Ok(process_manager)
     }
 
-    #[cfg(any(test, feature = "http-api"))]
-    pub fn get_database(&self) -> std::sync::Arc<Database> {
-        std::sync::Arc::new(self.db.clone())
+    /// Manager over an existing database opened with `mode=ro`, for dashboards and other
+    /// observers. Nothing is migrated, reaped or purged, statuses are refreshed without
+    /// being stored, and every mutating method returns [`Error::ReadOnly`].
+    #[tracing::instrument(name = "manager.init", skip_all)]
+    pub async fn read_only(config: Config) -> Result<Self> {
+        let db = storage::open_read_only(&config).await?;
+        let log_rotator = Arc::new(LogRotator::new(config.log_rotation.clone()));
+
+        Ok(Self {
+            db,
+            file_pool: FilePool::new(&config.file_ops),
+            file_system: Arc::new(RealFs),
+            config: Arc::new(std::sync::RwLock::new(Arc::new(config))),
+            log_rotator,
+            notifier: Notifier::disabled(),
+            events: EventBus::new(),
+            running_processes: Arc::new(Mutex::new(HashMap::new())),
+            external_processes: Arc::default(),
+            in_start_window: Arc::default(),
+            recording_exits: Arc::default(),
+            logs_usage: Arc::default(),
+            start_checks: std::sync::Mutex::default(),
+            cancellation: Cancellation::new(),
+            reaper: std::sync::Mutex::new(None),
+            read_only: true,
+            degraded: Default::default(),
+            clock: Arc::new(SystemClock::new()),
+            pid_probe: Arc::new(SystemPidProbe),
+            resolver: Arc::new(SystemResolver),
+            stop_marks: Arc::default(),
+            start_queue: StartQueue::default(),
+            #[cfg(any(test, feature = "test-util"))]
+            spawn_pressure: Default::default(),
+            #[cfg(test)]
+            fail_start_at: None,
+            #[cfg(test)]
+            restart_pause: None,
+            #[cfg(test)]
+            reaper_ticks: Arc::default(),
+        })
     }
 
-    /// Start background task to reap zombie processes
-    async fn start_process_reaper(&self) {
-        let running_processes = self.running_processes.clone();
-        tokio::spawn(async move {
-            let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(5));
-            loop {
-                interval.tick().await;
-                let mut processes = running_processes.lock().await;
-                let mut to_remove = Vec::new();
-
-                for (pid, child) in processes.iter_mut() {
-                    // Try to reap the process without blocking
-                    match child.try_wait() {
-                        Ok(Some(_exit_status)) => {
-                            // Process has terminated, mark for removal
-                            to_remove.push(*pid);
-                        }
-                        Ok(None) => {
-                            // Process is still running, continue
-                        }
-                        Err(_) => {
-                            // Error checking process status, assume it's dead
-                            to_remove.push(*pid);
-                        }
-                    }
-                }
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Whether the last status write of a refresh failed because the database storage is
+    /// read-only, full or failing. Listings and status queries still return the probed
+    /// statuses meanwhile; mutating methods fail at their own writes.
+    pub fn is_degraded(&self) -> bool {
+        self.degraded.load(std::sync::atomic::Ordering::Relaxed)
+    }
 
-                // Remove reaped processes
-                for pid in to_remove {
-                    processes.remove(&pid);
+    /// Let a status write of a refresh fail when the storage is at fault, so reads keep
+    /// working on a read-only or full disk; any other error is returned
+    fn tolerate_storage_failure(&self, written: Result<()>) -> Result<()> {
+        match written {
+            Ok(()) => {
+                self.degraded.store(false, std::sync::atomic::Ordering::Relaxed);
+                Ok(())
+            }
+            Err(e) if crate::database::is_storage_failure(&e) => {
+                if !self.degraded.swap(true, std::sync::atomic::Ordering::Relaxed) {
+                    tracing::warn!(error = %e, "cannot store refreshed statuses, reporting them unstored");
                 }
+                Ok(())
             }
-        });
+            Err(e) => Err(e),
+        }
     }
 
-    pub async fn start_process(
-        &self,
-        name: &str,
-        command: &str,
-        args: Vec<String>,
-        env_vars: HashMap<String, String>,
-        working_dir: Option<String>,
-        log_dir: Option<String>,
-    ) -> Result<String> {
-        // Check if process already exists
-        if self.db.get_process_by_name(name).await?.is_some() {
-            return Err(Error::ProcessAlreadyExists(name.to_string()));
+    /// Refuse `operation` on a read-only manager
+    fn ensure_writable(&self, operation: &str) -> Result<()> {
+        if self.read_only {
+            return Err(Error::ReadOnly(operation.to_string()));
+        }
+        Ok(())
+    }
+
+    /// Manager using the built-in defaults (`~/.pmr/processes.db`, `./logs`), ignoring any config file
+    pub async fn with_defaults() -> Result<Self> {
+        Self::new(Config::default()).await
+    }
+
+    /// Manager with its own database and log directory under `dir`, see [`Config::in_directory`]
+    pub async fn in_directory(dir: impl AsRef<Path>) -> Result<Self> {
+        Self::new(Config::in_directory(dir)).await
+    }
+
+    /// The configuration in effect now; a later [`reload_config`](Self::reload_config)
+    /// does not change the returned snapshot
+    pub fn config(&self) -> Arc<Config> {
+        self.config.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Rotator of process logs, to rotate other logs with the same (reloadable) settings
+    pub fn log_rotator(&self) -> Arc<LogRotator> {
+        self.log_rotator.clone()
+    }
+
+    /// Read the config file again and apply the settings it controls: log rotation,
+    /// notifications, flapping detection, the process limit and environment defaults.
+    /// Everything else (paths, timings, API options) keeps the value it started with.
+    /// A file that does not parse or validate changes nothing.
+    pub async fn reload_config(&self) -> Result<ReloadOutcome> {
+        self.ensure_writable("reload the configuration")?;
+        let current = self.config();
+        let (config, changed) = current.reloaded()?;
+        let (errors, warnings): (Vec<_>, Vec<_>) = config.validate().into_iter().partition(|issue| issue.is_error());
+        if !errors.is_empty() {
+            return Err(Error::InvalidConfig(errors));
         }
 
-        let id = Uuid::new_v4().to_string();
-        let working_dir = working_dir.unwrap_or_else(|| std::env::current_dir()
-            .unwrap_or_default()
-            .to_string_lossy()
-            .to_string());
+        self.notifier.reconfigure(&config.notifications)?;
+        self.log_rotator.set_config(config.log_rotation.clone());
+        let outcome = ReloadOutcome {
+            config_file: config.config_file.clone().unwrap_or_else(Config::default_file_path),
+            changed,
+            warnings: warnings.iter().map(|warning| format!("{}: {}", warning.key, warning.message)).collect(),
+        };
+        *self.config.write().unwrap_or_else(|e| e.into_inner()) = Arc::new(config);
+        tracing::info!(config_file = %outcome.config_file.display(), changed = ?outcome.changed, "configuration reloaded");
+        Ok(outcome)
+    }
+
+    /// Run a trivial query, to tell whether the database answers at all
+    pub async fn ping_database(&self) -> Result<()> {
+        self.db.ping().await
+    }
+
+    /// Instance id of the database, see [`crate::database::Database::instance_id`]
+    pub async fn database_instance_id(&self) -> Result<Option<String>> {
+        self.db.instance_id().await
+    }
+
+    /// Host the records of this manager are kept under, see [`StorageBackend::host`]
+    pub fn storage_host(&self) -> &str {
+        self.db.host()
+    }
+
+    #[cfg(any(test, feature = "http-api", feature = "test-util"))]
+    pub fn get_database(&self) -> Arc<dyn StorageBackend> {
+        self.db.clone()
+    }
+
+    /// Cancelling this stops multi-item operations (clear, purge) after the item in progress
+    pub fn cancellation(&self) -> &Cancellation {
+        &self.cancellation
+    }
+
+    /// How busy the pool bounding file sweeps is, see [`crate::file_pool`]
+    pub fn file_pool_stats(&self) -> FilePoolStats {
+        self.file_pool.stats()
+    }
+
+    /// Handle to the webhook notifier, e.g. to flush queued events before exiting
+    pub fn notifier(&self) -> &Notifier {
+        &self.notifier
+    }
+
+    /// Receive the lifecycle events published from now on. Delivery is best-effort: a
+    /// receiver more than [`EVENT_CAPACITY`](crate::events::EVENT_CAPACITY) events behind
+    /// loses the oldest and is told how many with `RecvError::Lagged`.
+    pub fn subscribe(&self) -> broadcast::Receiver<ProcessEvent> {
+        self.events.subscribe()
+    }
+
+    fn publish(&self, name: &ProcessName, kind: ProcessEventKind) {
+        self.events.publish(ProcessEvent { process: name.to_string(), timestamp: self.clock.now(), kind });
+    }
+
+    /// Start background task to reap zombie processes; it runs until the manager is
+    /// shut down or dropped
+    fn start_process_reaper(&self) -> Reaper {
+        let running_processes = self.running_processes.clone();
+        let db = self.db.clone();
+        let external_processes = self.external_processes.clone();
+        let in_start_window = self.in_start_window.clone();
+        let recording_exits = self.recording_exits.clone();
+        let pid_probe = self.pid_probe.clone();
+        let events = self.events.clone();
+        let clock = self.clock.clone();
+        let config = self.config.clone();
+        let log_rotator = self.log_rotator.clone();
+        let logs_usage = self.logs_usage.clone();
+        let file_pool = self.file_pool.clone();
+        let stop_marks = self.stop_marks.clone();
+        let cancellation = self.cancellation.clone();
+        // Left to the start and rotation checks at first, so short-lived managers never pay for it
+        let mut next_quota_check = clock.elapsed() + LOGS_QUOTA_CHECK_INTERVAL;
+        // tokio intervals cannot have a zero period
+        let period = self.config().reaper_interval.max(std::time::Duration::from_millis(1));
+        #[cfg(test)]
+        let ticks = self.reaper_ticks.clone();
+        let (shutdown, mut shutdown_requested) = tokio::sync::oneshot::channel::<()>();
+        let task = tokio::spawn(async move {
+            let mut interval = tokio::time::interval(period);
+            loop {
+                tokio::select! {
+                    _ = interval.tick() => {}
+                    _ = &mut shutdown_requested => break,
+                }
+                #[cfg(test)]
+                ticks.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                // Flapping settings may be reloaded between passes
+                let flapping = config.read().unwrap_or_else(|e| e.into_inner()).flapping.clone();
+                let exited = Self::reap_exited_children(&running_processes, &in_start_window, &recording_exits).await;
+                if !exited.is_empty() {
+                    Self::record_reaped_exits(db.as_ref(), &events, &flapping, clock.as_ref(), &recording_exits, exited).await;
+                }
+
+                // Adopted processes cannot be waited for; their exit shows as a dead or reused PID
+                let gone: Vec<(u32, ExternalProcess)> = {
+                    let mut external = external_processes.lock().unwrap_or_else(|e| e.into_inner());
+                    let pids: Vec<u32> = external
+                        .iter()
+                        .filter(|(pid, process)| !pid_probe.belongs_to(**pid, process.created_at))
+                        .map(|(pid, _)| *pid)
+                        .collect();
+                    pids.into_iter().filter_map(|pid| external.remove(&pid).map(|process| (pid, process))).collect()
+                };
+                if !gone.is_empty() {
+                    Self::record_external_exits(db.as_ref(), &events, clock.as_ref(), pid_probe.as_ref(), gone).await;
+                }
+
+                // Flapping ends by failures ageing out, which no exit announces
+                if let Err(e) = Self::settle_flapping(db.as_ref(), Some(&events), &flapping, clock.now()).await {
+                    tracing::warn!(error = %e, "failed to update flapping processes");
+                }
+                // A long-running manager deletes ephemeral processes as their grace periods end
+                if let Err(e) = Self::delete_expired_ephemeral(db.as_ref(), &log_rotator, &stop_marks, &cancellation, clock.now()).await {
+                    tracing::warn!(error = %e, "failed to delete expired ephemeral processes");
+                }
+                if let Err(e) = db.delete_expired_reservations(clock.now()).await {
+                    tracing::warn!(error = %e, "failed to purge expired name reservations");
+                }
+                if clock.elapsed() >= next_quota_check {
+                    next_quota_check = clock.elapsed() + LOGS_QUOTA_CHECK_INTERVAL;
+                    let quota = config.read().unwrap_or_else(|e| e.into_inner()).logs_quota_bytes;
+                    if let Err(e) = Self::apply_logs_quota(db.as_ref(), &log_rotator, &file_pool, &events, &logs_usage, quota, clock.now(), false).await {
+                        tracing::warn!(error = %e, "failed to enforce the logs quota");
+                    }
+                }
+            }
+        });
+        Reaper { shutdown, task }
+    }
+
+    /// Stop the background reaper, letting a reaping pass in progress finish first, after
+    /// pending background start checks have recorded their result.
+    /// Dropping the manager stops it too, but without waiting; calling this twice is harmless.
+    pub async fn shutdown(&self) {
+        self.finish_start_checks().await;
+        let reaper = self.reaper.lock().unwrap().take();
+        if let Some(reaper) = reaper {
+            let _ = reaper.shutdown.send(());
+            let _ = reaper.task.await;
+        }
+    }
+
+    /// Wait for every tracked child that has exited, outside its start window, and stop
+    /// tracking it; returns the PIDs with their exit codes, which stay in `recording_exits`
+    /// until [`Self::record_reaped_exits`] has stored them
+    async fn reap_exited_children(
+        running_processes: &Mutex<HashMap<u32, tokio::process::Child>>,
+        in_start_window: &std::sync::Mutex<HashSet<u32>>,
+        recording_exits: &std::sync::Mutex<HashSet<u32>>,
+    ) -> Vec<(u32, Option<i32>)> {
+        let mut processes = running_processes.lock().await;
+        let mut to_remove = Vec::new();
+        let mut exited = Vec::new();
+        let starting = in_start_window.lock().unwrap_or_else(|e| e.into_inner()).clone();
+
+        for (pid, child) in processes.iter_mut().filter(|(pid, _)| !starting.contains(pid)) {
+            // Try to reap the process without blocking
+            match child.try_wait() {
+                Ok(Some(exit_status)) => {
+                    // Process has terminated, mark for removal
+                    to_remove.push(*pid);
+                    exited.push((*pid, exit_status.code()));
+                }
+                Ok(None) => {
+                    // Process is still running, continue
+                }
+                Err(_) => {
+                    // Error checking process status, assume it's dead
+                    to_remove.push(*pid);
+                }
+            }
+        }
+
+        // Remove reaped processes, marking those with an exit to record before the lock is
+        // released so a refresh never finds them neither tracked nor being recorded
+        recording_exits.lock().unwrap_or_else(|e| e.into_inner()).extend(exited.iter().map(|(pid, _)| *pid));
+        for pid in to_remove {
+            processes.remove(&pid);
+        }
+        exited
+    }
+
+    /// Reap tracked children that have exited and record their exits, as a reaper pass
+    /// does; returns how many exits were collected. Starts call it too, so children that
+    /// exit quickly under heavy churn do not pile up between reaper passes.
+    pub async fn prune_dead_tracking(&self) -> usize {
+        let exited = Self::reap_exited_children(&self.running_processes, &self.in_start_window, &self.recording_exits).await;
+        if !exited.is_empty() {
+            let flapping = self.config().flapping.clone();
+            let count = exited.len();
+            Self::record_reaped_exits(self.db.as_ref(), &self.events, &flapping, self.clock.as_ref(), &self.recording_exits, exited).await;
+            return count;
+        }
+        0
+    }
+
+    /// Track a freshly spawned child until it is reaped. An entry already there for the PID
+    /// belongs to an earlier child whose exit was never recorded; the PID could only be
+    /// reused once that child was gone, so its handle is polled once without blocking and
+    /// then dropped.
+    async fn track_child(&self, pid: u32, child: tokio::process::Child) {
+        let previous = self.running_processes.lock().await.insert(pid, child);
+        if let Some(mut previous) = previous {
+            tracing::warn!(pid, "PID still tracked for an earlier child; dropping its handle without recording its exit");
+            let _ = previous.try_wait();
+        }
+    }
+
+    /// Mark records whose child was reaped as stopped (or, for jobs, succeeded or failed)
+    /// and publish the exit codes, taking each PID off `recording_exits` once it is done
+    async fn record_reaped_exits(
+        db: &dyn StorageBackend,
+        events: &EventBus,
+        flapping: &FlappingConfig,
+        clock: &dyn Clock,
+        recording_exits: &std::sync::Mutex<HashSet<u32>>,
+        exited: Vec<(u32, Option<i32>)>,
+    ) {
+        let recorded = |pid: u32| {
+            recording_exits.lock().unwrap_or_else(|e| e.into_inner()).remove(&pid);
+        };
+        let Ok(running) = db.get_processes_by_status(&[ProcessStatus::Running]).await else {
+            exited.iter().for_each(|(pid, _)| recorded(*pid));
+            return;
+        };
+
+        for (pid, exit_code) in exited {
+            if let Some(process) = running.iter().find(|p| p.pid == Some(pid)) {
+                let status = process.kind.exit_status(ProcessStatus::Stopped, exit_code);
+                if db.record_exit(&process.name, status.clone(), exit_code, None, clock.now()).await.is_ok() {
+                    Self::store_last_output(db, process, &status, exit_code).await;
+                    Self::schedule_ephemeral_deletion(db, process, clock.now()).await;
+                    Self::record_run(db, &process.name, process.created_at, &status, exit_code, clock.now()).await;
+                    events.publish(ProcessEvent {
+                        process: process.name.to_string(),
+                        timestamp: clock.now(),
+                        kind: ProcessEventKind::transition(Some(process.status.clone()), status.clone(), exit_code),
+                    });
+                    if is_failure(exit_code) {
+                        Self::record_failure(db, events, flapping, &process.name, status, exit_code, clock.now()).await;
+                    }
+                }
+            }
+            recorded(pid);
+        }
+    }
+
+    /// Keep the end of the log on the record of a process that just failed or exited
+    /// non-zero. Problems are only logged: the exit itself is already recorded.
+    async fn store_last_output(db: &dyn StorageBackend, process: &ProcessRecord, status: &ProcessStatus, exit_code: Option<i32>) {
+        if !keeps_last_output(status, exit_code) {
+            return;
+        }
+        let Some((lines, lossy)) = capture_last_output(&process.log_path) else { return };
+        if let Err(e) = db.set_last_output(&process.id, &lines, lossy).await {
+            tracing::warn!(process = %process.name, error = %e, "failed to store last output");
+        }
+    }
+
+    /// Start the grace period of an ephemeral process that exited at `at`. Problems are only
+    /// logged: the exit itself is already recorded.
+    async fn schedule_ephemeral_deletion(db: &dyn StorageBackend, process: &ProcessRecord, at: chrono::DateTime<chrono::Utc>) {
+        let Some(keep) = process.ephemeral_keep_secs else { return };
+        if let Err(e) = db.schedule_deletion(&process.id, deletion_deadline(keep, at)).await {
+            tracing::warn!(process = %process.name, error = %e, "failed to schedule deletion of ephemeral process");
+        }
+    }
+
+    /// Add a run that ended at `ended_at` to the process's run history. Problems are only
+    /// logged: the exit itself is already recorded.
+    async fn record_run(
+        db: &dyn StorageBackend,
+        name: &ProcessName,
+        started_at: chrono::DateTime<chrono::Utc>,
+        status: &ProcessStatus,
+        exit_code: Option<i32>,
+        ended_at: chrono::DateTime<chrono::Utc>,
+    ) {
+        let run = ProcessRun::new(name, started_at, ended_at, status.clone(), exit_code);
+        if let Err(e) = db.insert_process_run(&run, RUN_HISTORY_LIMIT).await {
+            tracing::warn!(process = %name, error = %e, "failed to record process run");
+        }
+    }
+
+    /// Add a failed exit to the process's failure history and announce it when that makes
+    /// the process flapping. Problems are only logged: the exit itself is already recorded.
+    async fn record_failure(
+        db: &dyn StorageBackend,
+        events: &EventBus,
+        flapping: &FlappingConfig,
+        name: &ProcessName,
+        status: ProcessStatus,
+        exit_code: Option<i32>,
+        at: chrono::DateTime<chrono::Utc>,
+    ) {
+        let recorded = async {
+            let mut history = db.get_failure_history(name).await?;
+            let transition = history.record_failure(flapping, at);
+            db.save_failure_history(name, &history).await?;
+            Ok::<_, Error>((transition, history.recent_failures(flapping, at)))
+        };
+        match recorded.await {
+            Ok((Some(transition), failures)) => {
+                tracing::debug!(process = %name, ?exit_code, failures, ?transition, "flapping state changed");
+                events.publish(ProcessEvent {
+                    process: name.to_string(),
+                    timestamp: at,
+                    kind: ProcessEventKind::flapping(transition, status, failures, flapping.window_secs),
+                });
+            }
+            Ok((None, _)) => {}
+            Err(e) => tracing::warn!(process = %name, error = %e, "failed to record process failure"),
+        }
+    }
+
+    /// Failure histories of the processes flapping at `now`, after clearing the ones whose
+    /// failures aged out of the window. Without `events` nothing is stored, for read-only
+    /// managers; otherwise clearing is stored and announced.
+    async fn settle_flapping(
+        db: &dyn StorageBackend,
+        events: Option<&EventBus>,
+        flapping: &FlappingConfig,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Vec<(ProcessName, FailureHistory)>> {
+        let mut still_flapping = Vec::new();
+        for (name, mut history) in db.get_flapping_histories().await? {
+            match history.refresh(flapping, now) {
+                // Only clearing can happen here: these were all flapping
+                Some(_) => {
+                    let Some(events) = events else { continue };
+                    db.save_failure_history(&name, &history).await?;
+                    // Soft-deleted processes clear quietly
+                    if let Some(process) = db.get_process_by_name(&name).await? {
+                        events.publish(ProcessEvent {
+                            process: name.into_string(),
+                            timestamp: now,
+                            kind: ProcessEventKind::FlappingCleared { status: process.status },
+                        });
+                    }
+                }
+                None => still_flapping.push((name, history)),
+            }
+        }
+        Ok(still_flapping)
+    }
+
+    /// Mark adopted processes that are gone as stopped, with no exit code and the reason why
+    async fn record_external_exits(db: &dyn StorageBackend, events: &EventBus, clock: &dyn Clock, pid_probe: &dyn PidProbe, gone: Vec<(u32, ExternalProcess)>) {
+        let Ok(running) = db.get_processes_by_status(&[ProcessStatus::Running]).await else {
+            return;
+        };
+
+        for (pid, external) in gone {
+            // A record restarted or deleted since adoption is no longer this PID's
+            let Some(process) = running.iter().find(|p| p.name == external.name && p.pid == Some(pid)) else {
+                continue;
+            };
+            let reason = if is_unreaped_zombie(pid_probe, pid, external.created_at) { ZOMBIE_EXIT_REASON } else { EXTERNAL_EXIT_REASON };
+            if db.record_exit(&external.name, ProcessStatus::Stopped, None, Some(reason), clock.now()).await.is_ok() {
+                Self::schedule_ephemeral_deletion(db, process, clock.now()).await;
+                Self::record_run(db, &process.name, process.created_at, &ProcessStatus::Stopped, None, clock.now()).await;
+                events.publish(ProcessEvent {
+                    process: external.name.into_string(),
+                    timestamp: clock.now(),
+                    kind: ProcessEventKind::Stopped { from: Some(ProcessStatus::Running), exit_code: None },
+                });
+            }
+        }
+    }
+
+    /// Watch the running processes recorded by an earlier manager (a restarted API server,
+    /// say) whose PIDs still belong to them
+    async fn reattach_running_processes(&self) -> Result<()> {
+        let running = self.db.get_processes_by_status(&[ProcessStatus::Running]).await?;
+        let tracked = self.running_processes.lock().await;
+        let mut external = self.external_processes.lock().unwrap_or_else(|e| e.into_inner());
+        for process in running {
+            let Some(pid) = process.pid else { continue };
+            if tracked.contains_key(&pid) || !self.pid_probe.belongs_to(pid, process.created_at) {
+                continue;
+            }
+            tracing::debug!(process = %process.name, pid, "reattached running process");
+            external.insert(pid, ExternalProcess { name: process.name, created_at: process.created_at });
+        }
+        Ok(())
+    }
+
+    /// Stop watching an adopted PID; returns whether it was adopted
+    fn release_external(&self, pid: u32) -> bool {
+        self.external_processes.lock().unwrap_or_else(|e| e.into_inner()).remove(&pid).is_some()
+    }
+
+    /// Names of the processes adopted from an earlier manager and still being watched
+    pub fn external_processes(&self) -> Vec<ProcessName> {
+        let mut names: Vec<ProcessName> = self
+            .external_processes
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .values()
+            .map(|process| process.name.clone())
+            .collect();
+        names.sort();
+        names
+    }
+
+    pub async fn start(
+        &self,
+        name: &ProcessName,
+        command: &str,
+        args: Vec<String>,
+        env_vars: HashMap<String, String>,
+        working_dir: Option<String>,
+        log_dir: Option<String>,
+    ) -> Result<StartOutcome> {
+        let options = StartOptions {
+            working_dir,
+            log_dir,
+            ..Default::default()
+        };
+        self.start_with_options(name, command, args, env_vars, options).await
+    }
+
+    #[deprecated(note = "use `start`, which returns a `StartOutcome`; `messages::start_message` gives this text")]
+    pub async fn start_process(
+        &self,
+        name: &ProcessName,
+        command: &str,
+        args: Vec<String>,
+        env_vars: HashMap<String, String>,
+        working_dir: Option<String>,
+        log_dir: Option<String>,
+    ) -> Result<String> {
+        let outcome = self.start(name, command, args, env_vars, working_dir, log_dir).await?;
+        Ok(messages::start_message(&outcome))
+    }
+
+    #[tracing::instrument(name = "process.start", skip_all)]
+    pub async fn start_with_options(
+        &self,
+        name: &ProcessName,
+        command: &str,
+        args: Vec<String>,
+        env_vars: HashMap<String, String>,
+        mut options: StartOptions,
+    ) -> Result<StartOutcome> {
+        // Only new definitions are checked; restarting one stored before the limits still works
+        limits::check_start_size(&self.config().start_limits, &args, &env_vars)?;
+        options.description = options.description.as_deref().map(limits::clean_description).transpose()?.flatten();
+        self.set_stop_mark(name, false).await;
+        self.start_counted(name, command, args, env_vars, options, 1, RecordSlot::New).await
+    }
+
+    /// Start that records `runs` as the number of runs, unless a rerun counts on from the
+    /// record it replaces
+    #[allow(clippy::too_many_arguments)]
+    async fn start_counted(
+        &self,
+        name: &ProcessName,
+        command: &str,
+        args: Vec<String>,
+        env_vars: HashMap<String, String>,
+        mut options: StartOptions,
+        runs: u32,
+        slot: RecordSlot,
+    ) -> Result<StartOutcome> {
+        self.ensure_writable("start a process")?;
+        // Collect children that exited since the last reaper pass before adding another
+        self.prune_dead_tracking().await;
+        let mut tracker = PhaseTracker::new(options.progress.take(), self.retrying_fs());
+        let started = self.start_tracked(name, command, args, env_vars, options, runs, slot, &mut tracker).await;
+        match &started {
+            Ok(_) => self.check_logs_quota(false).await,
+            Err(e) => tracker.fail(e),
+        }
+        started
+    }
+
+    #[deprecated(note = "use `start_with_options`, which returns a `StartOutcome`; `messages::start_message` gives this text")]
+    pub async fn start_process_with_options(
+        &self,
+        name: &ProcessName,
+        command: &str,
+        args: Vec<String>,
+        env_vars: HashMap<String, String>,
+        options: StartOptions,
+    ) -> Result<String> {
+        let outcome = self.start_with_options(name, command, args, env_vars, options).await?;
+        Ok(messages::start_message(&outcome))
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn start_tracked(
+        &self,
+        name: &ProcessName,
+        command: &str,
+        args: Vec<String>,
+        env_vars: HashMap<String, String>,
+        mut options: StartOptions,
+        mut runs: u32,
+        slot: RecordSlot,
+        tracker: &mut PhaseTracker,
+    ) -> Result<StartOutcome> {
+        // Defaults are part of the definition, so an idempotent start compares against them too
+        let env = env::layer_env(&self.config().env_defaults, name, env_vars);
+        let mut replaced = None;
+        if options.rerun && self.db.get_process_by_name(name).await?.is_some() {
+            let existing = self.get_process_status(name).await?;
+            if existing.kind != ProcessKind::Job || existing.status == ProcessStatus::Running {
+                return Err(Error::ProcessAlreadyExists(name.to_string()));
+            }
+            // The previous run's output is archived so the new run starts with a clean log
+            if let Some(outcome) = self.log_rotator.force_rotate(&PathBuf::from(&existing.log_path)).await? {
+                self.record_rotation(name, "rerun", &outcome).await;
+            }
+            self.db.delete_process(name).await?;
+            runs = existing.runs + 1;
+            replaced = Some(existing);
+        } else if options.idempotent {
+            if let Some(existing) = self.db.get_process_by_name(name).await? {
+                let working_dir = resolve_working_dir(options.working_dir.take());
+                let differences = existing.definition_differences(command, &args, &env.vars, &working_dir);
+                if !differences.is_empty() {
+                    return Err(Error::DefinitionMismatch(name.to_string(), differences));
+                }
+                let current = self.get_process_status(name).await?;
+                // A job that succeeded is done; an idempotent start must not run it again
+                if matches!(current.status, ProcessStatus::Running | ProcessStatus::Succeeded) {
+                    return Ok(StartOutcome {
+                        name: name.to_string(),
+                        pid: current.pid,
+                        status: current.status,
+                        unchanged: true,
+                        early_exit: None,
+                        rotated_log_size: None,
+                    });
+                }
+
+                // Same definition but not running: start it in place of the old record
+                self.db.delete_process(name).await?;
+                options.working_dir = Some(working_dir);
+                replaced = Some(existing);
+            }
+        }
+
+        let started = self.spawn_process(name, command, args, env, options, runs, slot, tracker).await;
+        if started.is_err() {
+            if let Some(existing) = replaced {
+                self.restore_definition(existing, "start").await;
+            }
+        }
+        started
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn spawn_process(
+        &self,
+        name: &ProcessName,
+        command: &str,
+        args: Vec<String>,
+        env: env::EffectiveEnv,
+        options: StartOptions,
+        runs: u32,
+        slot: RecordSlot,
+        tracker: &mut PhaseTracker,
+    ) -> Result<StartOutcome> {
+        let env::EffectiveEnv { vars: env_vars, default_keys: default_env_keys } = env;
+        let StartOptions { working_dir, log_dir, cgroup_limits, cpu_affinity, umask, description, idempotent: _, capture_snapshot, progress: _, strict, kind, rerun: _, stop_signal, log_dir_mode, internal, ephemeral_keep, check_in_background, watch, queue_on_pressure, resolution, rotate_on_start, reservation_token } = options;
+        // A strict start is rolled back on an early exit, so it cannot return before the window ends
+        let check_in_background = check_in_background && !strict;
+
+        if !internal && name == HTTP_SERVER_PROCESS_NAME {
+            return Err(Error::ReservedName(name.to_string()));
+        }
+
+        // Reject malformed keys before touching anything; reserved keys may only warn
+        match env::validate_env_vars(&env_vars, self.config().reserved_env_policy) {
+            Ok(warnings) => {
+                for warning in warnings {
+                    eprintln!("Warning: {}", warning);
+                }
+            }
+            Err(errors) => return Err(Error::InvalidEnvVars(errors)),
+        }
+
+        if let Some(cpu_affinity) = &cpu_affinity {
+            cpu_affinity.validate(available_cpus())?;
+        }
+
+        // Check if process already exists; a restart needs its reset row to still be there
+        match (slot, self.db.get_process_by_name(name).await?) {
+            (RecordSlot::New, Some(existing)) => {
+                if existing.kind == ProcessKind::Job && existing.status == ProcessStatus::Succeeded {
+                    return Err(Error::JobCompleted(name.to_string()));
+                }
+                return Err(Error::ProcessAlreadyExists(name.to_string()));
+            }
+            (RecordSlot::Reset, None) => return Err(Error::ProcessNotFound(name.to_string())),
+            _ => {}
+        }
+        // A reserved name is only for the token that reserved it, whose start ends the reservation
+        let reserved = slot == RecordSlot::New
+            && match self.db.get_reservation(name, self.clock.now()).await? {
+                Some(reservation) if reservation_token.as_deref() != Some(reservation.token.as_str()) => {
+                    return Err(Error::NameReserved(name.to_string(), reservation.token));
+                }
+                reservation => reservation.is_some(),
+            };
+        // pmr's own processes do not count against max_processes, nor does a reset row again
+        if !internal && slot == RecordSlot::New {
+            self.check_capacity(1).await?;
+        }
+
+        let mut artifacts = StartArtifacts::default();
+
+        // Create the cgroup up front so an unusable hierarchy fails before anything is spawned
+        if !cgroup_limits.is_empty() {
+            let cgroup = Cgroup::new(&self.config().cgroup_root, name);
+            cgroup.create(&cgroup_limits)?;
+            artifacts.cgroup = Some(cgroup);
+        }
+        tracker.finish(if artifacts.cgroup.is_some() {
+            "validated inputs, created cgroup".to_string()
+        } else {
+            "validated inputs".to_string()
+        });
+
+        let id = Uuid::new_v4().to_string();
+        // Only a working directory that was asked for gets the logs; the current one does not
+        let log_dir_mode = match log_dir_mode.unwrap_or(self.config().log_dir_mode) {
+            LogDirMode::Workdir if log_dir.is_none() && working_dir.is_some() => LogDirMode::Workdir,
+            _ => LogDirMode::Default,
+        };
+        let working_dir = resolve_working_dir(working_dir);
+        let watch = watch.map(|watch| watch.resolved(&working_dir));
+
+        // Determine log directory - use custom log_dir if provided, then the working
+        // directory's logs/ in workdir mode, otherwise the default
+        let log_directory = if let Some(custom_log_dir) = &log_dir {
+            PathBuf::from(custom_log_dir)
+        } else if log_dir_mode == LogDirMode::Workdir {
+            PathBuf::from(&working_dir).join("logs")
+        } else {
+            self.config().default_log_dir.clone()
+        };
+
+        // Ensure the log directory exists, remembering every level this start creates
+        artifacts.created_dirs = missing_directories(&log_directory);
+        let files = tracker.files.clone();
+        if let Err(e) = self.config().ensure_log_directory_with(&log_directory, &files) {
+            return Err(self.fail_start(name, artifacts, e).await);
+        }
+
+        let log_path = log_directory.join(format!("{}.log", name));
+        let log_existed = log_path.exists();
+
+        // Rotate an oversized previous log unless the process or the config says to keep
+        // appending, so whatever follows the file is not cut off by a start
+        if log_existed && rotate_on_start.unwrap_or(self.config().rotate_logs_on_start) {
+            match self.log_rotator.rotate_if_needed_with(&log_path, &files).await {
+                Ok(rotation) => artifacts.rotation = rotation,
+                Err(e) => return Err(self.fail_start(name, artifacts, e).await),
+            }
+        }
+
+        // One append-mode open shared by both streams: writes from either always land at the
+        // end, and an existing log is never reset here (only rotation does that)
+        let log_file = match self
+            .injected_failure(StartStep::CreateLog)
+            .and_then(|()| files.open_append(&log_path))
+        {
+            Ok(file) => file,
+            Err(e) => return Err(self.fail_start(name, artifacts, e.into()).await),
+        };
+        if !log_existed {
+            artifacts.created_log_file = Some(log_path.clone());
+        }
+
+        let stderr_file = match log_file.try_clone() {
+            Ok(file) => file,
+            Err(e) => return Err(self.fail_start(name, artifacts, e.into()).await),
+        };
+        tracker.finish(match (&artifacts.rotation, log_existed) {
+            (Some(_), _) => format!("rotated previous log, created log file {}", log_path.display()),
+            (None, true) => format!("appending to log file {}", log_path.display()),
+            (None, false) => format!("created log file {}", log_path.display()),
+        });
+
+        // Remember what is about to be executed so later drift can be detected
+        let resolution = resolution.unwrap_or_else(|| self.resolution_cache());
+        let fingerprint = StartFingerprint::capture_with(&resolution, command, &working_dir, env_vars.get("PATH").map(String::as_str));
+
+        // Use setsid to create a new session and detach from terminal
+        let mut cmd = tokio::process::Command::new("setsid");
+        cmd.arg(command)
+            .args(&args)
+            .current_dir(&working_dir)
+            .envs(&env_vars)
+            // Injected variables are applied last so they always win over user values
+            .env(env::PMR_PROCESS_NAME, name.as_str())
+            .env(env::PMR_INSTANCE_ID, &id);
+
+        cmd.stdout(Stdio::from(log_file))
+            .stderr(Stdio::from(stderr_file))
+            .stdin(Stdio::null());
+
+        // Pinning happens in the child before exec, so the command never runs unpinned
+        if let Some(cpu_affinity) = &cpu_affinity {
+            if let Err(e) = cpu_affinity.apply_to(&mut cmd) {
+                return Err(self.fail_start(name, artifacts, e).await);
+            }
+        }
+        // Set in the child too, so nothing the command creates gets pmr's umask
+        if let Some(umask) = umask {
+            umask.apply_to(&mut cmd);
+        }
+
+        // Start the process
+        let queue_on_pressure = queue_on_pressure || self.config().start_queue.enabled;
+        let child = self.spawn_child(name, &mut cmd, queue_on_pressure, tracker).await;
+        // The run is timed from here, not from when the record is written after the start window
+        let spawned_at = self.clock.now();
+
+        let (pid, initial_status, early_exit, start_snapshot) = match child {
+            Ok(child) => {
+                let Some(pid) = child.id() else {
+                    let error = Error::Other("Failed to get process ID".to_string());
+                    return Err(self.fail_start(name, artifacts, error).await);
+                };
+
+                // Store the child process for proper reaping. Until the start has recorded
+                // it, the reaper and other starts leave it alone, so an exit within the start
+                // window is seen by this start with its code.
+                self.in_start_window.lock().unwrap_or_else(|e| e.into_inner()).insert(pid);
+                self.track_child(pid, child).await;
+                artifacts.child_pid = Some(pid);
+
+                // Move the child into its cgroup before it gets a chance to run unconstrained for long
+                if let Some(cgroup) = &artifacts.cgroup {
+                    if let Err(e) = cgroup.add_process(pid) {
+                        return Err(self.fail_start(name, artifacts, e).await);
+                    }
+                }
+                tracker.finish(format!("spawned pid {}", pid));
+
+                // Read /proc before the start window, while even quick commands are likely still there
+                let start_snapshot = capture_snapshot.then(|| StartSnapshot::capture(pid));
+
+                if check_in_background {
+                    (Some(pid), ProcessStatus::Running, None, start_snapshot)
+                } else {
+                    // Watch the child for a moment; an early exit tells success from failure
+                    match self.watch_start_window(pid).await {
+                        None => (Some(pid), ProcessStatus::Running, None, start_snapshot),
+                        Some(exit) if strict && kind.exit_status(ProcessStatus::Failed, exit.code) == ProcessStatus::Failed => {
+                            // Anything the command left running in its session goes too. The
+                            // session id stays reserved while members remain, so it is not reused.
+                            unsafe { libc::kill(-(pid as i32), libc::SIGKILL) };
+                            let output = read_last_lines(&log_path, START_FAILURE_LOG_LINES).unwrap_or_default();
+                            let error = Error::StartExited(name.to_string(), exit.code, output);
+                            return Err(self.fail_start(name, artifacts, error).await);
+                        }
+                        Some(exit) => {
+                            (Some(pid), exit.status(kind), Some(exit), start_snapshot)
+                        }
+                    }
+                }
+            }
+            Err(e) => {
+                // Process failed to start at all - perform rollback
+                let error = Error::Other(format!("Failed to start process '{}': {}", name, e));
+                return Err(self.fail_start(name, artifacts, error).await);
+            }
+        };
+
+        // A process that failed right away keeps the end of its log, like any failure
+        let exit_code = early_exit.as_ref().and_then(|exit| exit.code);
+        let (last_output, last_output_lossy) = match early_exit {
+            Some(_) if keeps_last_output(&initial_status, exit_code) => capture_last_output(&log_path.to_string_lossy()).unwrap_or_default(),
+            _ => (Vec::new(), false),
+        };
+
+        tracker.finish(if check_in_background {
+            "start window left to a background check".to_string()
+        } else {
+            format!("initial status: {}", initial_status)
+        });
+
+        // Create process record
+        let process_record = ProcessRecord {
+            id: id.clone(),
+            name: name.clone(),
+            command: command.to_string(),
+            args,
+            env_vars,
+            working_dir,
+            pid,
+            status: initial_status.clone(),
+            created_at: spawned_at,
+            updated_at: self.clock.now(),
+            log_path: log_path.to_string_lossy().to_string(),
+            log_dir,
+            cgroup_memory_max: cgroup_limits.memory_max,
+            cgroup_cpu_max: cgroup_limits.cpu_max_percent,
+            cgroup: None,
+            deleted_at: None,
+            binary_path: fingerprint.binary_path,
+            binary_sha256: fingerprint.binary_sha256,
+            canonical_workdir: fingerprint.canonical_workdir,
+            exit_code,
+            exit_reason: None,
+            cpu_affinity: cpu_affinity.as_ref().map(CpuSet::to_string),
+            umask: umask.map(|umask| umask.to_string()),
+            description,
+            start_snapshot,
+            default_env_keys,
+            drift: None,
+            notes: Vec::new(),
+            recent_runs: Vec::new(),
+            flapping: false,
+            degraded: false,
+            inferred: false,
+            truncated_fields: Vec::new(),
+            kind,
+            runs,
+            stop_signal: stop_signal.unwrap_or(libc::SIGTERM),
+            log_dir_mode,
+            status_verified_at: Some(self.clock.now()),
+            internal,
+            ephemeral_keep_secs: ephemeral_keep.map(|keep| keep.as_secs()),
+            // A process that already exited in the start window starts its grace period now
+            delete_after: ephemeral_keep
+                .filter(|_| initial_status != ProcessStatus::Running)
+                .map(|keep| deletion_deadline(keep.as_secs(), self.clock.now())),
+            last_output,
+            last_output_lossy,
+            watch,
+            rotate_on_start,
+        };
+
+        // Insert process record - if this fails, the spawned child is killed as part of the rollback
+        let inserted = match (self.injected_failure(StartStep::InsertRecord), slot) {
+            (Ok(()), RecordSlot::New) => self.db.insert_process(&process_record).await,
+            (Ok(()), RecordSlot::Reset) => match self.db.replace_process(&process_record).await {
+                Ok(true) => Ok(()),
+                Ok(false) => Err(Error::ProcessNotFound(name.to_string())),
+                Err(e) => Err(e),
+            },
+            (Err(e), _) => Err(e.into()),
+        };
+        if let Err(e) = inserted {
+            return Err(self.fail_start(name, artifacts, e).await);
+        }
+        tracker.finish("recorded in database".to_string());
+
+        // The record now holds the name, so the reservation that kept it has served its purpose
+        if reserved {
+            if let Err(e) = self.db.delete_reservation(name).await {
+                tracing::warn!(process = %name, error = %e, "failed to drop the reservation of a started process");
+            }
+        }
+
+        if let Some(rotation) = &artifacts.rotation {
+            self.record_rotation(name, "size", rotation.outcome()).await;
+        }
+
+        let event = match early_exit.as_ref() {
+            None => ProcessEventKind::Started { pid },
+            Some(exit) => ProcessEventKind::transition(None, initial_status.clone(), exit.code),
+        };
+        self.publish(name, event);
+        if let Some(exit) = early_exit.as_ref() {
+            Self::record_run(self.db.as_ref(), name, spawned_at, &initial_status, exit.code, self.clock.now()).await;
+        }
+        if let Some(exit) = early_exit.as_ref().filter(|exit| is_failure(exit.code)) {
+            Self::record_failure(self.db.as_ref(), &self.events, &self.config().flapping, name, initial_status.clone(), exit.code, self.clock.now()).await;
+        }
+        if let Some(pid) = pid {
+            if check_in_background {
+                // Keeps the PID in the start window until its own check is done
+                self.check_start_in_background(process_record, pid);
+            } else {
+                self.in_start_window.lock().unwrap_or_else(|e| e.into_inner()).remove(&pid);
+            }
+        }
+
+        let early_exit = early_exit.map(|exit| exit.into_start_exit(&log_path));
+        let rotated_log_size = artifacts.rotation.as_ref().map(|rotation| rotation.outcome().rotated_from_size);
+        Ok(StartOutcome { name: name.to_string(), pid, status: initial_status, unchanged: false, early_exit, rotated_log_size })
+    }
+
+    /// Poll a freshly spawned child for the configured start window.
+    /// Returns how it exited if it did so within the window and its exit status could be
+    /// collected; an exit whose code is unknown is left to the record's liveness checks.
+    async fn watch_start_window(&self, pid: u32) -> Option<EarlyExit> {
+        let window = self.config().start_check_window;
+        Self::watch_child(&self.running_processes, self.clock.as_ref(), self.pid_probe.as_ref(), window, pid).await
+    }
+
+    #[tracing::instrument(name = "start.window", skip(running_processes, clock, pid_probe))]
+    async fn watch_child(
+        running_processes: &Mutex<HashMap<u32, tokio::process::Child>>,
+        clock: &dyn Clock,
+        pid_probe: &dyn PidProbe,
+        window: std::time::Duration,
+        pid: u32,
+    ) -> Option<EarlyExit> {
+        let started = clock.elapsed();
+        let deadline = started + window;
+        loop {
+            {
+                let mut processes = running_processes.lock().await;
+                match processes.get_mut(&pid).map(|child| child.try_wait()) {
+                    Some(Ok(Some(status))) => {
+                        processes.remove(&pid);
+                        return Some(EarlyExit { code: status.code(), elapsed: clock.elapsed() - started });
+                    }
+                    Some(Ok(None)) => {}
+                    // Taken by a stop or delete, or not waitable. How it exited is unknown, so
+                    // it is not called a failure here; the record's checks settle its status.
+                    Some(Err(_)) | None => {
+                        if !pid_probe.is_alive(pid) {
+                            processes.remove(&pid);
+                            tracing::warn!(pid, "child exited within the start window but its exit status could not be collected");
+                            return None;
+                        }
+                    }
+                }
+            }
+            let now = clock.elapsed();
+            if now >= deadline {
+                return None;
+            }
+            clock.sleep(START_POLL_INTERVAL.min(deadline - now)).await;
+        }
+    }
+
+    /// Watch the start window of a process already recorded as running from a task of its
+    /// own, recording an early exit as a waiting start would have
+    fn check_start_in_background(&self, record: ProcessRecord, pid: u32) {
+        let running_processes = self.running_processes.clone();
+        let in_start_window = self.in_start_window.clone();
+        let db = self.db.clone();
+        let events = self.events.clone();
+        let clock = self.clock.clone();
+        let pid_probe = self.pid_probe.clone();
+        let config = self.config();
+        in_start_window.lock().unwrap_or_else(|e| e.into_inner()).insert(pid);
+        let task = tokio::spawn(async move {
+            let exit = Self::watch_child(&running_processes, clock.as_ref(), pid_probe.as_ref(), config.start_check_window, pid).await;
+            in_start_window.lock().unwrap_or_else(|e| e.into_inner()).remove(&pid);
+            let exit = exit?;
+
+            // A stop, restart or delete during the window has settled the record already
+            match db.get_process_by_name(&record.name).await {
+                Ok(Some(current)) if current.id == record.id && current.status == ProcessStatus::Running => {}
+                _ => return None,
+            }
+            let status = exit.status(record.kind);
+            if let Err(e) = db.record_exit(&record.name, status.clone(), exit.code, None, clock.now()).await {
+                tracing::warn!(process = %record.name, error = %e, "failed to record exit within the start window");
+                return None;
+            }
+            Self::store_last_output(db.as_ref(), &record, &status, exit.code).await;
+            Self::schedule_ephemeral_deletion(db.as_ref(), &record, clock.now()).await;
+            Self::record_run(db.as_ref(), &record.name, record.created_at, &status, exit.code, clock.now()).await;
+            events.publish(ProcessEvent {
+                process: record.name.to_string(),
+                timestamp: clock.now(),
+                kind: ProcessEventKind::transition(Some(ProcessStatus::Running), status.clone(), exit.code),
+            });
+            if is_failure(exit.code) {
+                Self::record_failure(db.as_ref(), &events, &config.flapping, &record.name, status.clone(), exit.code, clock.now()).await;
+            }
+            Some((status, exit.into_start_exit(Path::new(&record.log_path))))
+        });
+        self.start_checks.lock().unwrap_or_else(|e| e.into_inner()).insert(pid, task);
+    }
+
+    /// Wait for the background start check of `outcome`, if it had one, and return the
+    /// outcome with the final status: stopped or failed with the early exit when the process
+    /// exited within its start window
+    pub async fn finish_start_check(&self, outcome: StartOutcome) -> StartOutcome {
+        let check = outcome.pid.and_then(|pid| self.start_checks.lock().unwrap_or_else(|e| e.into_inner()).remove(&pid));
+        let Some(check) = check else { return outcome };
+        match check.await {
+            Ok(Some((status, exit))) => StartOutcome { status, early_exit: Some(exit), ..outcome },
+            Ok(None) => outcome,
+            Err(e) => {
+                tracing::warn!(process = %outcome.name, error = %e, "background start check failed");
+                outcome
+            }
+        }
+    }
+
+    /// Wait for every background start check still pending
+    pub async fn finish_start_checks(&self) {
+        let checks: Vec<_> = self.start_checks.lock().unwrap_or_else(|e| e.into_inner()).drain().map(|(_, check)| check).collect();
+        for check in checks {
+            let _ = check.await;
+        }
+    }
+
+    /// Undo a failed start and return its error, with any rollback problems attached
+    async fn fail_start(&self, name: &ProcessName, artifacts: StartArtifacts, error: Error) -> Error {
+        tracing::debug!(process = %name, error = %error, "start failed, rolling back");
+        let problems = self.rollback_start_process(name, artifacts).await;
+        if problems.is_empty() {
+            error
+        } else {
+            Error::StartRolledBack(Box::new(error), problems)
+        }
+    }
+
+    /// Remove exactly what a failed start created, newest first, returning the steps that failed
+    async fn rollback_start_process(&self, name: &ProcessName, artifacts: StartArtifacts) -> Vec<String> {
+        let mut problems = Vec::new();
+        let files = self.retrying_fs();
+
+        if let Some(pid) = artifacts.child_pid {
+            self.in_start_window.lock().unwrap_or_else(|e| e.into_inner()).remove(&pid);
+            let child = self.running_processes.lock().await.remove(&pid);
+            if let Some(mut child) = child {
+                match child.kill().await {
+                    Ok(()) => tracing::debug!(process = %name, pid, "rollback killed child"),
+                    Err(e) => problems.push(format!("failed to kill PID {}: {}", pid, e)),
+                }
+            }
+        }
+
+        if let Some(cgroup) = &artifacts.cgroup {
+            if let Err(e) = cgroup.remove() {
+                problems.push(format!("failed to remove cgroup: {}", e));
+            }
+        }
+
+        if let Some(log_path) = &artifacts.created_log_file {
+            match files.remove_file(log_path) {
+                Ok(true) => tracing::debug!(process = %name, path = %log_path.display(), "rollback removed log file"),
+                Ok(false) => {}
+                Err(e) => problems.push(format!("failed to remove log file {}: {}", log_path.display(), e)),
+            }
+        }
+
+        if let Some(rotation) = &artifacts.rotation {
+            match rotation.undo_with(&files) {
+                Ok(()) => tracing::debug!(process = %name, "rollback restored rotated logs"),
+                Err(e) => problems.push(format!("failed to undo log rotation: {}", e)),
+            }
+        }
+
+        // Directories are removed deepest first and only while nothing else has landed in them
+        for dir in &artifacts.created_dirs {
+            let entries = match std::fs::read_dir(dir) {
+                Ok(entries) => entries.count(),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => {
+                    problems.push(format!("failed to read log directory {}: {}", dir.display(), e));
+                    break;
+                }
+            };
+            if entries > 0 {
+                tracing::debug!(process = %name, path = %dir.display(), entries, "rollback kept non-empty log directory");
+                break;
+            }
+            match files.remove_dir(dir) {
+                Ok(_) => tracing::debug!(process = %name, path = %dir.display(), "rollback removed log directory"),
+                Err(e) => {
+                    problems.push(format!("failed to remove log directory {}: {}", dir.display(), e));
+                    break;
+                }
+            }
+        }
+
+        for problem in &problems {
+            tracing::warn!(process = %name, problem = %problem, "start rollback incomplete");
+        }
+        problems
+    }
+
+    /// Spawn `cmd`, or with `queue_on_pressure` keep retrying it from the start queue while
+    /// it fails for lack of processes or memory. After the configured wait the last error
+    /// is returned, annotated with how long the start waited and how many were queued.
+    async fn spawn_child(
+        &self,
+        name: &ProcessName,
+        cmd: &mut tokio::process::Command,
+        queue_on_pressure: bool,
+        tracker: &mut PhaseTracker,
+    ) -> std::io::Result<tokio::process::Child> {
+        let spawn = |cmd: &mut tokio::process::Command| {
+            let _span = tracing::info_span!("spawn").entered();
+            self.injected_failure(StartStep::Spawn)
+                .and_then(|()| self.injected_pressure())
+                .and_then(|()| cmd.spawn())
+        };
+        let mut error = match spawn(cmd) {
+            Err(e) if queue_on_pressure && start_queue::is_resource_pressure(&e) => e,
+            result => return result,
+        };
+
+        let settings = self.config().start_queue.clone();
+        let Some(_slot) = self.start_queue.join(settings.depth) else {
+            let message = format!("{} (the start queue is full with {} starts waiting)", error, self.start_queue.waiting());
+            return Err(std::io::Error::new(error.kind(), message));
+        };
+        let queue_depth = self.start_queue.waiting();
+        // Subscribed before anything is retried, so no exit in between goes unnoticed
+        let mut events = self.events.subscribe();
+        tracker.queued(format!(
+            "{}; waiting up to {} with {} start(s) queued",
+            error,
+            human_duration(settings.max_wait()),
+            queue_depth
+        ));
+        self.publish(name, ProcessEventKind::StartQueued { error: error.to_string(), queue_depth });
+        tracing::info!(process = %name, error = %error, queue_depth, "start queued under resource pressure");
+
+        let queued_at = std::time::Instant::now();
+        for attempt in 0.. {
+            let remaining = settings.max_wait().saturating_sub(queued_at.elapsed());
+            if remaining.is_zero() {
+                break;
+            }
+            // An exit may free what the spawn was short of, so it ends the wait early
+            tokio::select! {
+                _ = tokio::time::sleep(start_queue::retry_delay(attempt).min(remaining)) => {}
+                _ = start_queue::next_exit(&mut events) => {}
+            }
+            match spawn(cmd) {
+                Err(e) if start_queue::is_resource_pressure(&e) => error = e,
+                result => return result,
+            }
+        }
+        let message = format!(
+            "{} (waited {} in the start queue with {} start(s) queued)",
+            error,
+            human_duration(queued_at.elapsed()),
+            self.start_queue.waiting()
+        );
+        Err(std::io::Error::new(error.kind(), message))
+    }
+
+    /// Make every spawn fail with EAGAIN until called with false, to simulate a system out
+    /// of processes for the start queue
+    #[cfg(any(test, feature = "test-util"))]
+    pub fn simulate_spawn_pressure(&self, on: bool) {
+        self.spawn_pressure.store(on, std::sync::atomic::Ordering::SeqCst);
+    }
+
+    /// Fail the spawn with EAGAIN while [`ProcessManager::simulate_spawn_pressure`] is on
+    fn injected_pressure(&self) -> std::io::Result<()> {
+        #[cfg(any(test, feature = "test-util"))]
+        if self.spawn_pressure.load(std::sync::atomic::Ordering::SeqCst) {
+            return Err(std::io::Error::from_raw_os_error(libc::EAGAIN));
+        }
+        Ok(())
+    }
+
+    /// File operations of one start or rollback, with their own retry tally
+    fn retrying_fs(&self) -> RetryingFs {
+        RetryingFs::new(self.file_system.clone(), RetryPolicy::default())
+    }
+
+    /// An empty cache of executable and working directory lookups, for one batch of starts
+    /// or drift checks to share through [`StartOptions::resolution`]
+    pub fn resolution_cache(&self) -> ResolutionCache {
+        ResolutionCache::new(self.resolver.clone())
+    }
+
+    /// Fail `step` when a unit test asked for it; never fails outside tests
+    #[cfg_attr(not(test), allow(unused_variables))]
+    fn injected_failure(&self, step: StartStep) -> std::io::Result<()> {
+        #[cfg(test)]
+        if self.fail_start_at == Some(step) {
+            return Err(std::io::Error::other(format!("injected failure at {:?}", step)));
+        }
+        Ok(())
+    }
+
+    pub async fn stop(&self, name: &ProcessName) -> Result<StopOutcome> {
+        self.stop_with_options(name, StopOptions::default()).await
+    }
+
+    #[deprecated(note = "use `stop`, which returns a `StopOutcome`; `messages::stop_message` gives this text")]
+    pub async fn stop_process(&self, name: &ProcessName) -> Result<String> {
+        Ok(messages::stop_message(&self.stop(name).await?))
+    }
+
+    #[deprecated(note = "use `stop_with_options`, which returns a `StopOutcome`; `messages::stop_message` gives this text")]
+    pub async fn stop_process_with_options(&self, name: &ProcessName, options: StopOptions) -> Result<String> {
+        Ok(messages::stop_message(&self.stop_with_options(name, options).await?))
+    }
+
+    #[tracing::instrument(name = "process.stop", skip_all)]
+    pub async fn stop_with_options(&self, name: &ProcessName, options: StopOptions) -> Result<StopOutcome> {
+        self.ensure_writable("stop a process")?;
+        self.set_stop_mark(name, true).await;
+        self.stop_instance(name, options).await
+    }
+
+    /// The mark `name` has while it is stopped on purpose, see "Stops and automatic
+    /// restarts" in the module docs
+    fn stop_mark(&self, name: &ProcessName) -> Arc<Mutex<bool>> {
+        let mut marks = self.stop_marks.lock().unwrap_or_else(|e| e.into_inner());
+        marks.entry(name.clone()).or_default().clone()
+    }
+
+    /// Drop the mark of `name` once its record is gone for good or cleared, so the marks
+    /// do not outlive the names they were kept for
+    fn forget_stop_mark(stop_marks: &std::sync::Mutex<HashMap<ProcessName, Arc<Mutex<bool>>>>, name: &ProcessName) {
+        stop_marks.lock().unwrap_or_else(|e| e.into_inner()).remove(name);
+    }
+
+    /// Waits for an automatic restart of `name` in progress to finish first
+    async fn set_stop_mark(&self, name: &ProcessName, stopped: bool) {
+        *self.stop_mark(name).lock().await = stopped;
+    }
+
+    /// Stop the running instance of `name`, leaving its stop mark as it is
+    async fn stop_instance(&self, name: &ProcessName, options: StopOptions) -> Result<StopOutcome> {
+        let mut process = self.db.get_process_by_name(name).await?
+            .ok_or_else(|| Error::ProcessNotFound(name.to_string()))?;
+
+        // Work from the freshest state: a process that exited a moment ago must not have
+        // its (possibly recycled) PID signalled
+        self.refresh_status(&mut process).await?;
+        let pid = match process.pid {
+            Some(pid) if self.is_record_alive(&process).await => pid,
+            _ => {
+                return Ok(StopOutcome {
+                    name: name.to_string(),
+                    pid: None,
+                    waited_ms: 0,
+                    escalated: false,
+                    not_running: Some(not_running(&process)),
+                })
+            }
+        };
+        let signal = options.signal.unwrap_or(process.stop_signal);
+        let grace_period = self.config().stop_grace_period;
+        let signalled_at = self.clock.elapsed();
+        let mut escalated = false;
+
+        // First try to get the child process from our tracking
+        let child_opt = {
+            let mut processes = self.running_processes.lock().await;
+            processes.remove(&pid)
+        };
+
+        if let Some(mut child) = child_opt {
+            // We own the child, so its exit can be confirmed through wait()
+            match self.signal_tracked_child(&mut child, pid, signal, grace_period).await {
+                Ok(true) => {}
+                Ok(false) if options.escalate => {
+                    // tokio's kill() sends SIGKILL and waits for the child to be reaped
+                    escalated = true;
+                    if let Err(e) = child.kill().await {
+                        self.track_child(pid, child).await;
+                        return Err(Error::Other(format!("Failed to stop process '{}' with PID {}: {}", name, pid, e)));
+                    }
+                }
+                Ok(false) => {
+                    self.track_child(pid, child).await;
+                    return Err(Error::StopTimedOut(name.to_string(), pid));
+                }
+                Err(e) => {
+                    // Re-insert the child back if signalling failed
+                    self.track_child(pid, child).await;
+                    return Err(Error::Other(format!("Failed to stop process '{}' with PID {}: {}", name, pid, e)));
+                }
+            }
+        } else {
+            // Fallback to signalling the PID for processes not in our tracking, adopted ones
+            // included; the stop records their exit, so the reaper no longer watches them
+            self.release_external(pid);
+            if self.pid_probe.signal(pid, signal).is_err() {
+                return Err(Error::Other(format!("Failed to stop process '{}' with PID {}", name, pid)));
+            }
+
+            if !self.wait_for_exit(pid, grace_period).await {
+                if !options.escalate {
+                    return Err(Error::StopTimedOut(name.to_string(), pid));
+                }
+                escalated = true;
+                let _ = self.pid_probe.signal(pid, libc::SIGKILL);
+                if !self.wait_for_exit(pid, grace_period).await {
+                    return Err(Error::StopTimedOut(name.to_string(), pid));
+                }
+            }
+        }
+
+        self.db.update_process_status(name, ProcessStatus::Stopped, Some(pid), self.clock.now()).await?;
+        Self::schedule_ephemeral_deletion(self.db.as_ref(), &process, self.clock.now()).await;
+        if process.status == ProcessStatus::Running {
+            Self::record_run(self.db.as_ref(), name, process.created_at, &ProcessStatus::Stopped, None, self.clock.now()).await;
+        }
+        self.cleanup_cgroup(&process);
+        if process.status != ProcessStatus::Stopped {
+            self.publish(name, ProcessEventKind::Stopped { from: Some(process.status.clone()), exit_code: None });
+        }
+        Ok(StopOutcome {
+            name: name.to_string(),
+            pid: Some(pid),
+            waited_ms: self.clock.elapsed().saturating_sub(signalled_at).as_millis() as u64,
+            escalated,
+            not_running: None,
+        })
+    }
+
+    /// Send a signal to a tracked child and wait up to the grace period for it to exit.
+    /// Returns whether the child exited.
+    async fn signal_tracked_child(
+        &self,
+        child: &mut tokio::process::Child,
+        pid: u32,
+        signal: i32,
+        grace_period: std::time::Duration,
+    ) -> std::io::Result<bool> {
+        self.pid_probe.signal(pid, signal)?;
+
+        let deadline = self.clock.elapsed() + grace_period;
+        loop {
+            if child.try_wait()?.is_some() {
+                return Ok(true);
+            }
+            if self.clock.elapsed() >= deadline {
+                return Ok(false);
+            }
+            self.clock.sleep(STOP_POLL_INTERVAL).await;
+        }
+    }
+
+    /// Poll until a PID is gone or the grace period elapses. Returns whether it exited.
+    async fn wait_for_exit(&self, pid: u32, grace_period: std::time::Duration) -> bool {
+        let deadline = self.clock.elapsed() + grace_period;
+        loop {
+            if !self.is_process_running(pid).await {
+                return true;
+            }
+            if self.clock.elapsed() >= deadline {
+                return false;
+            }
+            self.clock.sleep(STOP_POLL_INTERVAL).await;
+        }
+    }
+
+    /// Compare a process against the binary and working directory recorded at start
+    pub async fn process_drift(&self, process: &ProcessRecord) -> DriftReport {
+        self.process_drift_with(process, &self.resolution_cache()).await
+    }
+
+    async fn process_drift_with(&self, process: &ProcessRecord, cache: &ResolutionCache) -> DriftReport {
+        let running_pid = match process.pid {
+            Some(pid) if self.is_process_running(pid).await => Some(pid),
+            _ => None,
+        };
+        check_drift(process, running_pid, cache)
+    }
+
+    /// Drift reports for one process, or for every process when no name is given
+    pub async fn check_drift(&self, name: Option<&ProcessName>) -> Result<Vec<DriftReport>> {
+        let processes = match name {
+            Some(name) => vec![self.get_process_status(name).await?],
+            None => self.list_processes().await?,
+        };
+
+        // Replicas running the same binary have it hashed once
+        let cache = self.resolution_cache();
+        let mut reports = Vec::with_capacity(processes.len());
+        for process in &processes {
+            reports.push(self.process_drift_with(process, &cache).await);
+        }
+        Ok(reports)
+    }
+
+    /// Poll the named processes concurrently, with backoff, until each one reports `target`
+    /// or the timeout elapses. `on_ready` is called as each process gets there.
+    ///
+    /// A process that is Failed, or Stopped while waiting for Running, will not get there
+    /// on its own and stops being polled. Nothing is started or stopped by waiting.
+    pub async fn wait_for_status<F>(
+        &self,
+        names: &[ProcessName],
+        target: ProcessStatus,
+        timeout: std::time::Duration,
+        mut on_ready: F,
+    ) -> Result<WaitReport>
+    where
+        F: FnMut(&ProcessRecord),
+    {
+        let deadline = self.clock.elapsed() + timeout;
+        let mut pending: Vec<ProcessName> = names.to_vec();
+        let mut report = WaitReport::default();
+        let mut backoff = WAIT_INITIAL_BACKOFF;
+
+        loop {
+            let statuses = futures_util::future::join_all(
+                pending.iter().map(|name| self.get_process_status(name)),
+            )
+            .await;
+
+            let mut still_pending = Vec::new();
+            for (name, status) in pending.into_iter().zip(statuses) {
+                let process = status?;
+                if process.status == target {
+                    on_ready(&process);
+                    report.ready.push(name);
+                } else if process.status == ProcessStatus::Failed
+                    || (target == ProcessStatus::Running && process.status == ProcessStatus::Stopped)
+                    || self.clock.elapsed() >= deadline
+                {
+                    report.not_ready.push(process);
+                } else {
+                    still_pending.push(name);
+                }
+            }
+            pending = still_pending;
+
+            if pending.is_empty() {
+                return Ok(report);
+            }
+            let remaining = deadline.saturating_sub(self.clock.elapsed());
+            self.clock.sleep(backoff.min(remaining)).await;
+            backoff = (backoff * 2).min(WAIT_MAX_BACKOFF);
+        }
+    }
+
+    pub async fn restart(&self, name: &ProcessName) -> Result<RestartOutcome> {
+        self.restart_with_options(name, RestartOptions::default()).await
+    }
+
+    #[deprecated(note = "use `restart`, which returns a `RestartOutcome`; `messages::restart_message` gives this text")]
+    pub async fn restart_process(&self, name: &ProcessName) -> Result<String> {
+        Ok(messages::restart_message(&self.restart(name).await?))
+    }
+
+    #[deprecated(note = "use `restart_with_options`, which returns a `RestartOutcome`; `messages::restart_message` gives this text")]
+    pub async fn restart_process_with_options(&self, name: &ProcessName, options: RestartOptions) -> Result<String> {
+        Ok(messages::restart_message(&self.restart_with_options(name, options).await?))
+    }
+
+    #[tracing::instrument(name = "process.restart", skip_all)]
+    pub async fn restart_with_options(&self, name: &ProcessName, options: RestartOptions) -> Result<RestartOutcome> {
+        self.ensure_writable("restart a process")?;
+        self.set_stop_mark(name, false).await;
+        self.restart_instance(name, options, false).await
+    }
+
+    /// Restart `name`, leaving its stop mark as it is; `stopped_on_purpose` skips the restart
+    async fn restart_instance(&self, name: &ProcessName, options: RestartOptions, stopped_on_purpose: bool) -> Result<RestartOutcome> {
+        let process = self.db.get_process_by_name(name).await?
+            .ok_or_else(|| Error::ProcessNotFound(name.to_string()))?;
+        if !options.force {
+            if let Some((_, history)) = self.flapping_histories().await?.into_iter().find(|(flapping, _)| flapping == name) {
+                let failures = history.recent_failures(&self.config().flapping, self.clock.now());
+                return Err(Error::Flapping(name.to_string(), failures, self.config().flapping.window_secs));
+            }
+        }
+        let mut outcome = RestartOutcome {
+            name: name.to_string(),
+            previous_pid: process.pid,
+            skipped: None,
+            stop: None,
+            start: None,
+        };
+        if stopped_on_purpose {
+            outcome.skipped = Some(RestartSkip::StoppedOnPurpose);
+            return Ok(outcome);
+        }
+
+        if options.only_if_changed {
+            let report = self.process_drift(&process).await;
+            if !report.fingerprinted {
+                outcome.skipped = Some(RestartSkip::NoBinaryHash);
+                return Ok(outcome);
+            }
+            if !report.binary_changed() {
+                outcome.skipped = Some(RestartSkip::BinaryUnchanged);
+                return Ok(outcome);
+            }
+        }
+
+        // Stop the process if it's running
+        if process.pid.is_some() && self.is_process_running(process.pid.unwrap()).await {
+            outcome.stop = Some(self.stop_instance(name, StopOptions::default()).await?);
+            // Wait a bit for the process to stop
+            self.clock.sleep(std::time::Duration::from_millis(500)).await;
+        }
+
+        // Archive the previous run's output so the new instance starts with a clean log
+        if options.reset_logs {
+            if let Some(outcome) = self.log_rotator.force_rotate(&PathBuf::from(&process.log_path)).await? {
+                self.record_rotation(name, "restart", &outcome).await;
+            }
+        }
+
+        #[cfg(test)]
+        if let Some(pause) = &self.restart_pause {
+            pause.wait().await;
+            pause.wait().await;
+        }
+
+        // Read the definition again and reset the row in one transaction, so a change that
+        // committed while the process was stopping is what the new instance runs. The row is
+        // never removed: if the start fails, or pmr dies before it, the definition stays.
+        let process = self.db.reset_for_restart(name, self.clock.now()).await?
+            .ok_or_else(|| Error::ProcessNotFound(name.to_string()))?;
+        let options = StartOptions {
+            working_dir: Some(process.working_dir.clone()),
+            log_dir: process.log_dir.clone(),
+            cgroup_limits: process.cgroup_limits(),
+            cpu_affinity: process.cpu_set(),
+            umask: process.umask_value(),
+            description: process.description.clone(),
+            idempotent: false,
+            capture_snapshot: process.start_snapshot.is_some(),
+            progress: None,
+            strict: false,
+            kind: process.kind,
+            rerun: false,
+            stop_signal: Some(process.stop_signal),
+            // Worked out again, so an updated working directory moves the log with it
+            log_dir_mode: Some(process.log_dir_mode),
+            internal: process.internal,
+            // The new record has no deadline, which cancels a pending deletion
+            ephemeral_keep: process.ephemeral_keep_secs.map(std::time::Duration::from_secs),
+            check_in_background: false,
+            watch: process.watch.clone(),
+            // Still queued when the config file turns the queue on for every start
+            queue_on_pressure: false,
+            resolution: None,
+            rotate_on_start: process.rotate_on_start,
+            reservation_token: None,
+        };
+        // Restarting a job runs it again
+        let runs = if process.kind == ProcessKind::Job { process.runs + 1 } else { process.runs };
+        let started = self.start_counted(
+            name,
+            &process.command,
+            process.args.clone(),
+            // Defaults are applied afresh, so config changes reach the restarted process
+            process.explicit_env_vars(),
+            options,
+            runs,
+            RecordSlot::Reset,
+        ).await;
+
+        match started {
+            Ok(start) => {
+                self.publish(name, ProcessEventKind::Restarted { pid: start.pid, previous_pid: outcome.previous_pid });
+                outcome.start = Some(start);
+                Ok(outcome)
+            }
+            Err(e) => {
+                if let Err(mark_error) = self.db.update_process_status(name, ProcessStatus::Failed, None, self.clock.now()).await {
+                    eprintln!("Warning: restart of '{}' failed and it could not be marked failed: {}", name, mark_error);
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// Put back a record removed by a rerun or idempotent start whose new instance failed,
+    /// marked stopped, so the definition is not lost
+    async fn restore_definition(&self, process: ProcessRecord, action: &str) {
+        let name = process.name.clone();
+        let restored = ProcessRecord {
+            status: ProcessStatus::Stopped,
+            updated_at: self.clock.now(),
+            ..process
+        };
+        match self.db.insert_process(&restored).await {
+            Ok(()) => eprintln!("Warning: {} of '{}' failed; its previous definition was kept as stopped", action, name),
+            Err(restore_error) => eprintln!(
+                "Warning: {} of '{}' failed and its previous definition could not be restored: {}",
+                action, name, restore_error
+            ),
+        }
+    }
+
+    pub async fn delete(&self, name: &ProcessName) -> Result<DeleteOutcome> {
+        self.delete_with_options(name, DeleteOptions::default()).await
+    }
+
+    #[deprecated(note = "use `delete`, which returns a `DeleteOutcome`; `messages::delete_message` gives this text")]
+    pub async fn delete_process(&self, name: &ProcessName) -> Result<String> {
+        Ok(messages::delete_message(&self.delete(name).await?))
+    }
+
+    #[deprecated(note = "use `delete_with_options`, which returns a `DeleteOutcome`; `messages::delete_message` gives this text")]
+    pub async fn delete_process_with_options(&self, name: &ProcessName, options: DeleteOptions) -> Result<String> {
+        Ok(messages::delete_message(&self.delete_with_options(name, options).await?))
+    }
+
+    /// Delete a process. By default the record is only marked as deleted and its logs are
+    /// kept, so it can be brought back with [`ProcessManager::undelete`].
+    #[tracing::instrument(name = "process.delete", skip_all)]
+    pub async fn delete_with_options(&self, name: &ProcessName, options: DeleteOptions) -> Result<DeleteOutcome> {
+        self.ensure_writable("delete a process")?;
+        self.set_stop_mark(name, true).await;
+        let process = self.db.get_process_by_name(name).await?
+            .ok_or_else(|| Error::ProcessNotFound(name.to_string()))?;
+        let mut last_status = process.status.clone();
+        let mut stop = None;
+
+        // Stop the process if it's running
+        if let Some(pid) = process.pid {
+            if self.is_process_running(pid).await {
+                stop = Some(self.stop(name).await?);
+                last_status = ProcessStatus::Stopped;
+            } else {
+                // Not running, but our child may not have been waited for yet: reap it now
+                self.reap_if_exited(pid).await;
+            }
+        }
+
+        if !options.hard {
+            if !self.db.soft_delete_process_at(name, self.clock.now()).await? {
+                return Err(Error::ProcessNotFound(name.to_string()));
+            }
+            self.cleanup_cgroup(&process);
+            self.publish(name, ProcessEventKind::Deleted { from: last_status.clone(), hard: false });
+            return Ok(DeleteOutcome { name: name.to_string(), hard: false, previous_status: last_status, stop });
+        }
+
+        // Delete from database
+        if self.db.delete_process(name).await? {
+            self.db.delete_log_rotations(name).await?;
+            self.db.delete_process_notes(name).await?;
+            self.db.delete_failure_history(name).await?;
+            self.db.delete_watch_restarts(name).await?;
+            self.db.delete_process_runs(name).await?;
+            self.cleanup_cgroup(&process);
+            Self::forget_stop_mark(&self.stop_marks, name);
+            self.publish(name, ProcessEventKind::Deleted { from: last_status.clone(), hard: true });
+            if !Self::log_shared(self.db.as_ref(), &process).await? {
+                let _ = tokio::fs::remove_file(&process.log_path).await;
+                let _ = tokio::fs::remove_file(index_path(Path::new(&process.log_path))).await;
+            }
+            Ok(DeleteOutcome { name: name.to_string(), hard: true, previous_status: last_status, stop })
+        } else {
+            Err(Error::ProcessNotFound(name.to_string()))
+        }
+    }
+
+    #[deprecated(note = "use `undelete`, which returns an `UndeleteOutcome`; `messages::undelete_message` gives this text")]
+    pub async fn undelete_process(&self, name: &ProcessName) -> Result<String> {
+        Ok(messages::undelete_message(&self.undelete(name).await?))
+    }
+
+    /// Restore the most recently soft-deleted process with this name
+    pub async fn undelete(&self, name: &ProcessName) -> Result<UndeleteOutcome> {
+        self.ensure_writable("restore a process")?;
+        if self.db.get_process_by_name(name).await?.is_some() {
+            return Err(Error::ProcessAlreadyExists(name.to_string()));
+        }
+
+        let process = self.db.get_deleted_process_by_name(name).await?
+            .ok_or_else(|| Error::ProcessNotFound(name.to_string()))?;
+        self.check_capacity(1).await?;
+
+        if !self.db.restore_process(&process.id).await? {
+            return Err(Error::ProcessNotFound(name.to_string()));
+        }
+        self.publish(name, ProcessEventKind::Restored { status: process.status.clone() });
+
+        Ok(UndeleteOutcome { name: name.to_string(), status: process.status })
+    }
+
+    /// Start the process in `working_dir` from its next start on. A running process keeps
+    /// its current directory until restarted; in workdir log mode the restart also moves
+    /// the log to the new directory's `logs/`. Returns the updated record.
+    pub async fn update_working_dir(&self, name: &ProcessName, working_dir: &str) -> Result<ProcessRecord> {
+        self.ensure_writable("update a process")?;
+        let path = Path::new(working_dir);
+        if !path.is_dir() {
+            return Err(Error::Other(format!("Working directory '{}' does not exist", working_dir)));
+        }
+        let working_dir = std::path::absolute(path)?.to_string_lossy().to_string();
+
+        if !self.db.update_working_dir(name, &working_dir).await? {
+            return Err(Error::ProcessNotFound(name.to_string()));
+        }
+        self.db.get_process_by_name(name).await?
+            .ok_or_else(|| Error::ProcessNotFound(name.to_string()))
+    }
+
+    /// Set the description of a process, or clear it with a blank one. Returns the updated record.
+    pub async fn update_description(&self, name: &ProcessName, description: &str) -> Result<ProcessRecord> {
+        self.ensure_writable("update a process")?;
+        let description = limits::clean_description(description)?;
+        if !self.db.update_description(name, description.as_deref()).await? {
+            return Err(Error::ProcessNotFound(name.to_string()));
+        }
+        self.db.get_process_by_name(name).await?
+            .ok_or_else(|| Error::ProcessNotFound(name.to_string()))
+    }
+
+    /// Permanently remove soft-deleted processes older than the configured retention,
+    /// together with their log files
+    pub async fn purge_deleted_processes(&self) -> Result<ClearResult> {
+        self.ensure_writable("purge deleted processes")?;
+        let retention = chrono::Duration::from_std(self.config().deleted_retention)
+            .map_err(|e| Error::Other(format!("Invalid deleted process retention: {}", e)))?;
+        let mut expired = self.db.stream_deleted_processes_before(self.clock.now() - retention);
+
+        let mut result = ClearResult::new("soft-deleted processes");
+        while let Some(process) = expired.next().await {
+            let process = process?;
+            if self.cancellation.is_cancelled() {
+                result.record_skipped(process.name.into_string());
+                continue;
+            }
+            match self.db.delete_process_by_id(&process.id).await {
+                Ok(true) => {
+                    self.remove_unused_logs(&process).await?;
+                    Self::forget_stop_mark(&self.stop_marks, &process.name);
+                    result.record_cleared(process.name.into_string());
+                }
+                Ok(false) => result.record_failed(process.name.into_string(), VANISHED_DURING_CLEAR.to_string()),
+                Err(e) => result.record_failed(process.name.into_string(), e.to_string()),
+            }
+        }
+        Ok(result.finish())
+    }
+
+    /// Whether a name is taken by a process or a reservation, from the database alone
+    pub async fn check_name(&self, name: &ProcessName) -> Result<NameCheck> {
+        let process = self.db.get_process_by_name(name).await?;
+        Ok(NameCheck {
+            name: name.to_string(),
+            exists: process.is_some(),
+            status: process.map(|process| process.status),
+            reservation: self.db.get_reservation(name, self.clock.now()).await?,
+        })
+    }
+
+    /// Reserve a name nothing runs under for the API token with public id `token`, for
+    /// `ttl`. Nothing is spawned and no log is created; until the reservation expires only
+    /// a start by the same token may use the name, and that start ends the reservation.
+    /// Reserving again with the same token renews it.
+    pub async fn reserve_name(&self, name: &ProcessName, token: &str, ttl: std::time::Duration) -> Result<Reservation> {
+        self.ensure_writable("reserve a process name")?;
+        if name == HTTP_SERVER_PROCESS_NAME {
+            return Err(Error::ReservedName(name.to_string()));
+        }
+        if ttl < std::time::Duration::from_secs(1) || ttl > MAX_RESERVATION_TTL {
+            return Err(Error::InvalidDuration(format!("a reservation lasts from 1s to {}s", MAX_RESERVATION_TTL.as_secs())));
+        }
+        if self.db.get_process_by_name(name).await?.is_some() {
+            return Err(Error::ProcessAlreadyExists(name.to_string()));
+        }
+
+        let now = self.clock.now();
+        let ttl = chrono::Duration::from_std(ttl).map_err(|e| Error::InvalidDuration(e.to_string()))?;
+        let reservation = Reservation { name: name.to_string(), token: token.to_string(), reserved_at: now, expires_at: now + ttl };
+        // One statement decides between concurrent reservations, so exactly one gets the name
+        if !self.db.claim_reservation(&reservation, now).await? {
+            return Err(match self.db.get_reservation(name, now).await? {
+                Some(holder) => Error::NameReserved(name.to_string(), holder.token),
+                None => Error::Other(format!("The reservation of '{}' changed while reserving it; try again", name)),
+            });
+        }
+        // A start that did not go through a reservation may have taken the name meanwhile
+        if self.db.get_process_by_name(name).await?.is_some() {
+            self.db.delete_reservation(name).await?;
+            return Err(Error::ProcessAlreadyExists(name.to_string()));
+        }
+        Ok(reservation)
+    }
+
+    /// Drop name reservations whose time is up; returns how many. Expired ones no longer
+    /// hold their name either way, this only keeps them from piling up.
+    pub async fn purge_expired_reservations(&self) -> Result<u64> {
+        self.ensure_writable("purge expired reservations")?;
+        self.db.delete_expired_reservations(self.clock.now()).await
+    }
+
+    /// Delete exited ephemeral processes whose grace period is over, together with their
+    /// log files
+    pub async fn sweep_ephemeral_processes(&self) -> Result<ClearResult> {
+        self.ensure_writable("delete expired ephemeral processes")?;
+        Self::delete_expired_ephemeral(self.db.as_ref(), &self.log_rotator, &self.stop_marks, &self.cancellation, self.clock.now()).await
+    }
+
+    /// [`Self::sweep_ephemeral_processes`] for the reaper, which has no manager to call it on
+    async fn delete_expired_ephemeral(
+        db: &dyn StorageBackend,
+        log_rotator: &LogRotator,
+        stop_marks: &std::sync::Mutex<HashMap<ProcessName, Arc<Mutex<bool>>>>,
+        cancellation: &Cancellation,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> Result<ClearResult> {
+        let mut expired = db.stream_expired_ephemeral_processes(now);
+
+        let mut result = ClearResult::new("expired ephemeral processes");
+        while let Some(process) = expired.next().await {
+            let process = process?;
+            if cancellation.is_cancelled() {
+                result.record_skipped(process.name.into_string());
+                continue;
+            }
+            match db.delete_process_by_id(&process.id).await {
+                Ok(true) => {
+                    Self::remove_logs_unless_reused(db, log_rotator, &process).await?;
+                    Self::forget_stop_mark(stop_marks, &process.name);
+                    result.record_cleared(process.name.into_string());
+                }
+                Ok(false) => result.record_failed(process.name.into_string(), VANISHED_DURING_CLEAR.to_string()),
+                Err(e) => result.record_failed(process.name.into_string(), e.to_string()),
+            }
+        }
+        Ok(result.finish())
+    }
+
+    /// Remove the log files of a purged record unless a live process, such as a new one
+    /// with the same name, has reused the path
+    async fn remove_unused_logs(&self, process: &ProcessRecord) -> Result<()> {
+        Self::remove_logs_unless_reused(self.db.as_ref(), &self.log_rotator, process).await
+    }
+
+    async fn remove_logs_unless_reused(db: &dyn StorageBackend, log_rotator: &LogRotator, process: &ProcessRecord) -> Result<()> {
+        if Self::log_shared(db, process).await? {
+            return Ok(());
+        }
+
+        let log_path = PathBuf::from(&process.log_path);
+        for rotated in log_rotator.get_rotated_files(&log_path).unwrap_or_default() {
+            let _ = tokio::fs::remove_file(rotated.path).await;
+        }
+        let _ = tokio::fs::remove_file(&log_path).await;
+        let _ = tokio::fs::remove_file(index_path(&log_path)).await;
+
+        Ok(())
+    }
+
+    /// Whether a live record other than `process`, already removed from the live ones,
+    /// writes to its log file; if so the file is kept and a warning names the other process
+    async fn log_shared(db: &dyn StorageBackend, process: &ProcessRecord) -> Result<bool> {
+        if db.count_processes_with_log_path(&process.log_path).await? == 0 {
+            return Ok(false);
+        }
+        let others: Vec<String> = db.get_all_processes().await?
+            .into_iter()
+            .filter(|other| other.log_path == process.log_path && other.id != process.id)
+            .map(|other| other.name.into_string())
+            .collect();
+        tracing::warn!(
+            process = %process.name,
+            path = %process.log_path,
+            shared_with = %others.join(", "),
+            "log file is also used by another process; keeping it"
+        );
+        Ok(true)
+    }
+
+    #[tracing::instrument(name = "process.list", skip_all)]
+    pub async fn list_processes(&self) -> Result<Vec<ProcessRecord>> {
+        self.list_processes_with_refresh(StatusRefresh::All).await
+    }
+
+    /// Names of the listed processes, sorted and optionally only those starting with
+    /// `prefix`, without reading the records or probing any PID
+    pub async fn process_names(&self, prefix: Option<&str>) -> Result<Vec<String>> {
+        self.db.get_process_names(prefix).await
+    }
+
+    /// List processes, probing the PIDs `refresh` asks for. Internal processes are left out.
+    pub async fn list_processes_with_refresh(&self, refresh: StatusRefresh) -> Result<Vec<ProcessRecord>> {
+        self.listed(self.db.get_all_processes().await?, refresh).await
+    }
+
+    /// Processes of every host sharing the storage, see [`StorageBackend::list_all_hosts`].
+    /// This host's are refreshed as `refresh` asks; other hosts' are shown as they stored
+    /// them, since their PIDs mean nothing here, and nothing of theirs is written.
+    pub async fn list_all_hosts(&self, refresh: StatusRefresh) -> Result<Vec<HostListing>> {
+        let mut listings = self.db.list_all_hosts().await?;
+        for listing in listings.iter_mut().filter(|listing| listing.current) {
+            listing.processes = self.list_processes_with_refresh(refresh).await?;
+        }
+        Ok(listings)
+    }
+
+    /// [`Self::list_processes_with_refresh`], only the processes whose name or description
+    /// contains `text`, ignoring ASCII case
+    pub async fn search_processes(&self, text: &str, refresh: StatusRefresh) -> Result<Vec<ProcessRecord>> {
+        self.listed(self.db.search_processes(text).await?, refresh).await
+    }
+
+    /// Records as a listing shows them: without internal processes, refreshed as `refresh`
+    /// asks, and marked flapping and degraded
+    async fn listed(&self, processes: Vec<ProcessRecord>, refresh: StatusRefresh) -> Result<Vec<ProcessRecord>> {
+        let mut processes = user_processes(processes);
+        let flapping = self.flapping_histories().await?;
+
+        let mut verified = Vec::new();
+        for process in &mut processes {
+            if self.refresh_with(process, refresh).await? {
+                verified.push(process.id.clone());
+            }
+            process.flapping = flapping.iter().any(|(name, _)| name == &process.name);
+        }
+        self.mark_status_verified(&verified).await?;
+        let degraded = self.is_degraded();
+        for process in &mut processes {
+            process.degraded = degraded;
+        }
+
+        Ok(processes)
+    }
+
+    /// Processes as [`Self::list_processes_with_refresh`] returns them, one at a time: the
+    /// first is ready before the rest are read, and memory does not grow with the table.
+    /// The stream ends after its first error.
+    pub fn stream_processes(&self, refresh: StatusRefresh) -> BoxStream<'_, Result<ProcessRecord>> {
+        let listing = StreamedListing { rows: self.db.stream_processes(), flapping: None, verified: Vec::new() };
+        stream::unfold(Some(listing), move |listing| async move {
+            let mut listing = listing?;
+            match self.next_listed(&mut listing, refresh).await {
+                Ok(Some(process)) => Some((Ok(process), Some(listing))),
+                Ok(None) => None,
+                Err(e) => Some((Err(e), None)),
+            }
+        })
+        .boxed()
+    }
+
+    /// The next record of a streamed listing, refreshed; verification times are stored in
+    /// batches, the last when the rows run out
+    async fn next_listed(&self, listing: &mut StreamedListing<'_>, refresh: StatusRefresh) -> Result<Option<ProcessRecord>> {
+        if listing.flapping.is_none() {
+            let flapping = self.flapping_histories().await?;
+            listing.flapping = Some(flapping.into_iter().map(|(name, _)| name.into_string()).collect());
+        }
+        while let Some(process) = listing.rows.next().await {
+            let mut process = process?;
+            if process.internal {
+                continue;
+            }
+            if self.refresh_with(&mut process, refresh).await? {
+                listing.verified.push(process.id.clone());
+            }
+            if listing.verified.len() >= VERIFIED_BATCH {
+                self.mark_status_verified(&std::mem::take(&mut listing.verified)).await?;
+            }
+            process.flapping = listing.flapping.as_ref().is_some_and(|flapping| flapping.iter().any(|name| process.name == *name));
+            process.degraded = self.is_degraded();
+            return Ok(Some(process));
+        }
+        self.mark_status_verified(&std::mem::take(&mut listing.verified)).await?;
+        Ok(None)
+    }
+
+    /// Refresh the record as `refresh` asks, returning whether its PID was probed. Fresh
+    /// records in stale mode skip the probe, but an exit of our own child is still collected.
+    async fn refresh_with(&self, process: &mut ProcessRecord, refresh: StatusRefresh) -> Result<bool> {
+        let probe = match refresh {
+            StatusRefresh::Stored => return Ok(false),
+            StatusRefresh::All => true,
+            StatusRefresh::Stale => {
+                let window = chrono::Duration::from_std(self.config().status_stale_after).unwrap_or(chrono::Duration::MAX);
+                process.status_verified_at.is_none_or(|at| self.clock.now() - at >= window)
+            }
+        };
+        self.refresh_status_probing(process, probe).await?;
+        Ok(probe)
+    }
+
+    /// Store that these records were just refreshed; read-only managers keep it to the response
+    async fn mark_status_verified(&self, ids: &[String]) -> Result<()> {
+        if self.read_only || ids.is_empty() {
+            return Ok(());
+        }
+        let marked = self.db.mark_status_verified(ids, self.clock.now()).await;
+        self.tolerate_storage_failure(marked)
+    }
+
+    /// Bring a record's status in line with its PID, storing any change. Failed records
+    /// stay failed, succeeded jobs stay succeeded, and a record without a PID never started.
+    async fn refresh_status(&self, process: &mut ProcessRecord) -> Result<()> {
+        self.refresh_status_probing(process, true).await
+    }
+
+    /// [`Self::refresh_status`], leaving the PID unprobed unless `probe` is set
+    async fn refresh_status_probing(&self, process: &mut ProcessRecord, probe: bool) -> Result<()> {
+        if probe {
+            process.status_verified_at = Some(self.clock.now());
+            process.inferred = false;
+        }
+        let (new_status, exit_code) = match process.pid {
+            None => (ProcessStatus::Failed, None),
+            Some(_) if matches!(process.status, ProcessStatus::Failed | ProcessStatus::Succeeded) => (process.status.clone(), None),
+            Some(pid) => match self.reap_if_exited(pid).await {
+                // Our own child exited and the reaper has not got to it yet
+                Some(exit_code) => (process.kind.exit_status(ProcessStatus::Stopped, exit_code), exit_code),
+                None if !probe => return Ok(()),
+                // The reaper has its exit code and is storing it; calling it stopped now would
+                // lose the code and the job's result
+                None if self.recording_exits.lock().unwrap_or_else(|e| e.into_inner()).contains(&pid) => return Ok(()),
+                None => match self.record_liveness(process).await {
+                    PidLiveness::Alive => (ProcessStatus::Running, None),
+                    PidLiveness::Inconclusive if self.log_recently_written(process) => {
+                        process.inferred = true;
+                        (ProcessStatus::Running, None)
+                    }
+                    PidLiveness::Inconclusive | PidLiveness::Gone => (ProcessStatus::Stopped, None),
+                },
+            },
+        };
+
+        // The reaper may have stored the exit since the record was read
+        if new_status != process.status && process.status == ProcessStatus::Running && exit_code.is_none() {
+            if let Some(stored) = self.db.get_process_by_name(&process.name).await.ok().flatten() {
+                if stored.pid == process.pid && stored.status != ProcessStatus::Running {
+                    let verified_at = process.status_verified_at;
+                    *process = stored;
+                    process.status_verified_at = verified_at;
+                    return Ok(());
+                }
+            }
+        }
+
+        if new_status != process.status {
+            if keeps_last_output(&new_status, exit_code) {
+                if let Some((lines, lossy)) = capture_last_output(&process.log_path) {
+                    process.last_output = lines;
+                    process.last_output_lossy = lossy;
+                }
+            }
+            let recorded = self.record_refreshed_status(process, new_status.clone(), exit_code).await;
+            self.tolerate_storage_failure(recorded)?;
+            if new_status != ProcessStatus::Running && !self.read_only && process.delete_after.is_none() {
+                process.delete_after = process.ephemeral_keep_secs.map(|keep| deletion_deadline(keep, self.clock.now()));
+            }
+            process.status = new_status;
+            process.updated_at = self.clock.now();
+            if exit_code.is_some() {
+                process.exit_code = exit_code;
+            }
+        }
+        Ok(())
+    }
+
+    /// Collect the exit of a tracked child that has already terminated
+    async fn reap_if_exited(&self, pid: u32) -> Option<Option<i32>> {
+        let mut processes = self.running_processes.lock().await;
+        let exit_status = processes.get_mut(&pid)?.try_wait().ok()??;
+        processes.remove(&pid);
+        Some(exit_status.code())
+    }
+
+    /// Whether the record's PID is alive and still the process it started
+    async fn is_record_alive(&self, process: &ProcessRecord) -> bool {
+        self.record_liveness(process).await == PidLiveness::Alive
+    }
+
+    /// Whether the record's PID is alive and still the process it started, if that can be told
+    #[tracing::instrument(name = "proc.probe", skip_all)]
+    async fn record_liveness(&self, process: &ProcessRecord) -> PidLiveness {
+        match process.pid {
+            Some(pid) => self.pid_probe.liveness(pid, process.created_at),
+            None => PidLiveness::Gone,
+        }
+    }
+
+    /// Whether the record's log was written within the inferred-liveness window, the last
+    /// sign of life of a process whose PID cannot be checked
+    fn log_recently_written(&self, process: &ProcessRecord) -> bool {
+        let window = self.config().inferred_liveness_window;
+        if window.is_zero() {
+            return false;
+        }
+        let Ok(modified) = std::fs::metadata(&process.log_path).and_then(|metadata| metadata.modified()) else {
+            return false;
+        };
+        let age = self.clock.now().signed_duration_since(chrono::DateTime::<chrono::Utc>::from(modified));
+        age.to_std().map_or(true, |age| age <= window)
+    }
+
+    /// Store and publish a status found by a refresh; read-only managers only report it
+    async fn record_refreshed_status(&self, process: &ProcessRecord, new_status: ProcessStatus, exit_code: Option<i32>) -> Result<()> {
+        if self.read_only {
+            return Ok(());
+        }
+        let adopted = new_status != ProcessStatus::Running && process.pid.is_some_and(|pid| self.release_external(pid));
+        let zombie = new_status != ProcessStatus::Running
+            && process.pid.is_some_and(|pid| is_unreaped_zombie(self.pid_probe.as_ref(), pid, process.created_at));
+        if exit_code.is_some() {
+            self.db.record_exit(&process.name, new_status.clone(), exit_code, None, self.clock.now()).await?;
+        } else if zombie {
+            self.db.record_exit(&process.name, new_status.clone(), None, Some(ZOMBIE_EXIT_REASON), self.clock.now()).await?;
+        } else if adopted {
+            self.db.record_exit(&process.name, new_status.clone(), None, Some(EXTERNAL_EXIT_REASON), self.clock.now()).await?;
+        } else {
+            self.db.update_process_status(&process.name, new_status.clone(), process.pid, self.clock.now()).await?;
+        }
+        if keeps_last_output(&new_status, exit_code) && !process.last_output.is_empty() {
+            if let Err(e) = self.db.set_last_output(&process.id, &process.last_output, process.last_output_lossy).await {
+                tracing::warn!(process = %process.name, error = %e, "failed to store last output");
+            }
+        }
+        if new_status != ProcessStatus::Running {
+            Self::schedule_ephemeral_deletion(self.db.as_ref(), process, self.clock.now()).await;
+            if process.status == ProcessStatus::Running {
+                Self::record_run(self.db.as_ref(), &process.name, process.created_at, &new_status, exit_code, self.clock.now()).await;
+            }
+        }
+        self.publish(&process.name, ProcessEventKind::transition(Some(process.status.clone()), new_status.clone(), exit_code));
+        if is_failure(exit_code) {
+            Self::record_failure(self.db.as_ref(), &self.events, &self.config().flapping, &process.name, new_status, exit_code, self.clock.now()).await;
+        }
+        Ok(())
+    }
+
+    /// Processes flapping now, by name, see [`crate::flapping`]
+    async fn flapping_histories(&self) -> Result<Vec<(ProcessName, FailureHistory)>> {
+        let events = (!self.read_only).then_some(&self.events);
+        let flapping = &self.config().flapping;
+        match Self::settle_flapping(self.db.as_ref(), events, flapping, self.clock.now()).await {
+            // Histories that aged out stay stored until the storage takes writes again
+            Err(e) if crate::database::is_storage_failure(&e) => {
+                self.tolerate_storage_failure(Err(e))?;
+                Self::settle_flapping(self.db.as_ref(), None, flapping, self.clock.now()).await
+            }
+            settled => settled,
+        }
+    }
+
+    /// Processes that failed repeatedly within the flapping window, by name
+    pub async fn flapping_processes(&self) -> Result<Vec<FlappingProcess>> {
+        let now = self.clock.now();
+        let mut flapping = Vec::new();
+        for (name, history) in self.flapping_histories().await? {
+            // Soft-deleted processes keep their history for undelete, but are not listed
+            let Some(mut process) = self.db.get_process_by_name(&name).await? else { continue };
+            if process.internal {
+                continue;
+            }
+            self.refresh_status(&mut process).await?;
+            flapping.push(FlappingProcess {
+                name: name.into_string(),
+                status: process.status,
+                recent_failures: history.recent_failures(&self.config().flapping, now),
+                window_secs: self.config().flapping.window_secs,
+                flapping_since: history.flapping_since.unwrap_or(now),
+                last_failure: history.failures.last().copied().unwrap_or(now),
+            });
+        }
+        Ok(flapping)
+    }
+
+    /// The database, its directory and the config file, where other local users can read them
+    pub fn loose_permissions(&self) -> Vec<LoosePermissions> {
+        permissions::check(&permissions::secret_paths(&self.config()))
+    }
+
+    /// Processes whose PID is a zombie left by the process they started, by name. Their
+    /// status is refreshed on the way, so each is recorded as stopped.
+    pub async fn zombie_processes(&self) -> Result<Vec<ZombieProcess>> {
+        let mut zombies = Vec::new();
+        for mut process in self.db.get_all_processes().await? {
+            let Some(pid) = process.pid else { continue };
+            if process.internal {
+                continue;
+            }
+            let Some(parent_pid) = self.pid_probe.zombie_parent(pid, process.created_at) else { continue };
+            self.refresh_status(&mut process).await?;
+            zombies.push(ZombieProcess {
+                name: process.name.into_string(),
+                pid,
+                parent_pid,
+                reparented: parent_pid <= 1 || !self.pid_probe.is_alive(parent_pid),
+            });
+        }
+        zombies.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(zombies)
+    }
+
+    /// Count processes per status using the stored statuses, without refreshing them
+    pub async fn process_counts(&self) -> Result<ProcessCounts> {
+        self.db.count_processes_by_status().await
+    }
+
+    /// Fail with [`Error::ProcessLimitReached`] unless `additional` more processes fit under
+    /// `max_processes`. Batches check their whole size before starting anything.
+    pub async fn check_capacity(&self, additional: usize) -> Result<()> {
+        let max = self.config().max_processes;
+        if max == 0 {
+            return Ok(());
+        }
+        let current = self.db.count_processes().await?;
+        if current + additional > max {
+            return Err(Error::ProcessLimitReached(current, max));
+        }
+        Ok(())
+    }
+
+    /// Process counts per status and the ages of the oldest failed and stopped processes,
+    /// from one aggregate query over the stored statuses
+    pub async fn status_gauges(&self) -> Result<StatusGauges> {
+        let summary = self.db.summarize_processes_by_status().await?;
+        let now = self.clock.now();
+        let age = |since: Option<chrono::DateTime<chrono::Utc>>| since.map(|since| (now - since).num_seconds().max(0) as u64);
+        Ok(StatusGauges {
+            counts: summary.counts,
+            oldest_failed_age_seconds: age(summary.oldest_failed),
+            oldest_stopped_age_seconds: age(summary.oldest_stopped),
+        })
+    }
+
+    /// Managed process counts together with the configured limit
+    pub async fn stats(&self) -> Result<ManagerStats> {
+        let gauges = self.status_gauges().await?;
+        let logs = LogsQuotaStats {
+            quota_bytes: self.config().logs_quota_bytes,
+            usage: self.logs_usage().await?,
+            last_enforcement: self.db.get_last_quota_enforcement().await?,
+        };
+        Ok(ManagerStats {
+            managed: gauges.counts.total,
+            max_processes: self.config().max_processes,
+            counts: gauges.counts,
+            oldest_failed_age_seconds: gauges.oldest_failed_age_seconds,
+            oldest_stopped_age_seconds: gauges.oldest_stopped_age_seconds,
+            paths: self.config().paths(),
+            logs,
+        })
+    }
+
+    pub async fn clear_processes(&self, all: bool) -> Result<ClearResult> {
+        self.ensure_writable("clear processes")?;
+        let mut processes_to_clear = if all {
+            // Every process, read as the clear goes
+            self.db.stream_processes()
+        } else {
+            stream::iter(self.finished_processes().await?.into_iter().map(Ok)).boxed()
+        };
+
+        let mut result = ClearResult::new(if all { "all processes" } else { "stopped/failed processes" });
+
+        // On cancellation the process in progress is finished and the rest are left alone.
+        // pmr's own processes are never cleared.
+        while let Some(process) = processes_to_clear.next().await {
+            let process = process?;
+            if process.internal {
+                continue;
+            }
+            if self.cancellation.is_cancelled() {
+                result.record_skipped(process.name.into_string());
+                continue;
+            }
+            match self.delete_single_process(&process).await {
+                Ok(_) => result.record_cleared(process.name.into_string()),
+                Err(e) => result.record_failed(process.name.into_string(), e.to_string()),
+            }
+        }
+        Ok(result.finish())
+    }
+
+    /// The processes [`Self::clear_processes`] would remove, without removing anything, so
+    /// they can be shown before asking
+    pub async fn clear_targets(&self, all: bool) -> Result<Vec<ProcessRecord>> {
+        let processes = if all { self.db.get_all_processes().await? } else { self.finished_processes().await? };
+        Ok(processes.into_iter().filter(|process| !process.internal).collect())
+    }
+
+    /// Stopped and failed processes, and jobs that succeeded longer than the job retention ago
+    async fn finished_processes(&self) -> Result<Vec<ProcessRecord>> {
+        let retention = chrono::Duration::from_std(self.config().job_retention)
+            .map_err(|e| Error::Other(format!("Invalid job retention: {}", e)))?;
+        let cutoff = self.clock.now() - retention;
+        Ok(self.db
+            .get_processes_by_status(&[ProcessStatus::Stopped, ProcessStatus::Failed, ProcessStatus::Succeeded])
+            .await?
+            .into_iter()
+            .filter(|process| process.status != ProcessStatus::Succeeded || process.updated_at <= cutoff)
+            .collect())
+    }
+
+    async fn delete_single_process(&self, process: &ProcessRecord) -> Result<()> {
+        let mut last_status = process.status.clone();
+
+        // Stop the process if it's running
+        if let Some(pid) = process.pid {
+            if self.is_process_running(pid).await {
+                last_status = ProcessStatus::Stopped;
+                // Try to stop the process properly
+                if self.stop(&process.name).await.is_err() {
+                    // If proper stop fails, try direct kill
+                    if self.pid_probe.signal(pid, process.stop_signal).is_ok() {
+                        // Wait a bit for termination
+                        self.clock.sleep(std::time::Duration::from_millis(500)).await;
+                    }
+                }
+            } else {
+                // Not running, but our child may not have been waited for yet: reap it now
+                self.reap_if_exited(pid).await;
+            }
+        }
+
+        // Cleared records are soft-deleted like single deletes; logs are kept until purge
+        if !self.db.soft_delete_process_at(&process.name, self.clock.now()).await? {
+            return Err(Error::ProcessNotFound(process.name.to_string()));
+        }
+        self.cleanup_cgroup(process);
+        Self::forget_stop_mark(&self.stop_marks, &process.name);
+        self.publish(&process.name, ProcessEventKind::Deleted { from: last_status, hard: false });
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "status_refresh", skip_all)]
+    pub async fn get_process_status(&self, name: &ProcessName) -> Result<ProcessRecord> {
+        self.get_process_status_with_refresh(name, StatusRefresh::All).await
+    }
+
+    /// Status of one process, probing its PID if `refresh` asks for it
+    pub async fn get_process_status_with_refresh(&self, name: &ProcessName, refresh: StatusRefresh) -> Result<ProcessRecord> {
+        let mut process = self.db.get_process_by_name(name).await?
+            .ok_or_else(|| Error::ProcessNotFound(name.to_string()))?;
+
+        if self.refresh_with(&mut process, refresh).await? {
+            self.mark_status_verified(std::slice::from_ref(&process.id)).await?;
+        }
+        process.flapping = self.flapping_histories().await?.iter().any(|(flapping, _)| flapping == name);
+        process.degraded = self.is_degraded();
+
+        if !process.cgroup_limits().is_empty() {
+            process.cgroup = Some(Cgroup::new(&self.config().cgroup_root, name).stats());
+        }
+
+        Ok(process)
+    }
+
+    /// Everything known about one process in one report: the stored record, what probing
+    /// its PID finds, its logs, histories and notes, and the installation it runs under
+    pub async fn inspect(&self, name: &ProcessName) -> Result<InspectReport> {
+        let record = self.db.get_process_by_name(name).await?
+            .ok_or_else(|| Error::ProcessNotFound(name.to_string()))?;
+        let refreshed = self.get_process_status(name).await?;
+        let pid_alive = match record.pid {
+            Some(pid) => self.is_process_running(pid).await,
+            None => false,
+        };
+        let same_process = pid_alive && self.is_record_alive(&record).await;
+        let resources = match (&refreshed.status, refreshed.pid) {
+            (ProcessStatus::Running, Some(pid)) => read_resource_usage(pid),
+            _ => None,
+        };
+        let rotated = self.rotated_logs(name).await?;
+
+        Ok(InspectReport {
+            generated_at: self.clock.now(),
+            liveness: Liveness {
+                pid: record.pid,
+                stored_status: record.status.clone(),
+                status: refreshed.status,
+                pid_alive,
+                same_process,
+                flapping: refreshed.flapping,
+            },
+            resources,
+            cgroup: refreshed.cgroup,
+            drift: self.process_drift(&record).await,
+            logs: InspectLogs {
+                log_path: record.log_path.clone(),
+                live_bytes: tokio::fs::metadata(&record.log_path).await.ok().map(|metadata| metadata.len()),
+                rotated_bytes: rotated.iter().map(|log| log.size_bytes).sum(),
+                rotated,
+                rotations: self.db.get_log_rotations(name).await?,
+            },
+            history: InspectHistory {
+                failures: self.db.get_failure_history(name).await?,
+                watch_restarts: self.db.get_watch_restarts(name).await?,
+                runs: self.db.get_process_runs(name, RUN_HISTORY_LIMIT).await?,
+            },
+            notes: self.db.get_process_notes(name).await?,
+            environment: InspectEnvironment::new(self.config().paths(), self.read_only, self.is_degraded()),
+            record,
+        })
+    }
+
+    #[tracing::instrument(name = "log.read", skip_all)]
+    pub async fn get_process_logs(&self, name: &ProcessName, lines: Option<usize>) -> Result<String> {
+        self.get_process_logs_with_options(name, &LogOptions { lines, ..Default::default() }).await
+    }
+
+    /// Read the live log, optionally collapsing repeated lines before taking the tail
+    pub async fn get_process_logs_with_options(&self, name: &ProcessName, options: &LogOptions) -> Result<String> {
+        Ok(self.read_process_logs(name, options).await?.content)
+    }
+
+    /// The live log as in [`Self::get_process_logs_with_options`], telling whether it held
+    /// bytes that are not UTF-8. The file is read once: only its end for a plain tail,
+    /// whole otherwise, and decoded after reading.
+    pub async fn read_process_logs(&self, name: &ProcessName, options: &LogOptions) -> Result<ProcessLogs> {
+        let process = self.db.get_process_by_name(name).await?
+            .ok_or_else(|| Error::ProcessNotFound(name.to_string()))?;
+        let unreadable = |e| Error::LogUnreadable(process.log_path.clone(), e);
+        let unreadable_io = |e| match e {
+            Error::Io(e) => unreadable(e),
+            e => e,
+        };
+
+        if let Some(since) = &options.since {
+            if options.tail_bytes.is_some() {
+                return Err(Error::Other("A log is read either since a time or as a byte tail, not both".to_string()));
+            }
+            let log_path = Path::new(&process.log_path);
+            let index = self.current_log_index(log_path);
+            let read = read_since(log_path, since.resolve(self.clock.now()), index.as_ref()).map_err(unreadable)?;
+            let (content, contains_invalid_utf8) = match String::from_utf8(read.content) {
+                Ok(content) => (content, false),
+                Err(e) => (String::from_utf8_lossy(e.as_bytes()).into_owned(), true),
+            };
+            let content = if options.collapse_repeats { collapse_repeats(&content) } else { content };
+            let content = match options.lines {
+                Some(lines) => tail_lines(&content, lines),
+                None => content,
+            };
+            return Ok(ProcessLogs { content, contains_invalid_utf8, start_offset: None });
+        }
+
+        if let Some(max) = options.tail_bytes {
+            if options.lines.is_some() {
+                return Err(Error::Other("A log tail is taken either in lines or in bytes, not both".to_string()));
+            }
+            let (bytes, offset) = read_tail_bytes(Path::new(&process.log_path), max, options.allow_partial).map_err(unreadable_io)?;
+            let (content, contains_invalid_utf8) = match String::from_utf8(bytes) {
+                Ok(content) => (content, false),
+                Err(e) => (String::from_utf8_lossy(e.as_bytes()).into_owned(), true),
+            };
+            let content = if options.collapse_repeats { collapse_repeats(&content) } else { content };
+            return Ok(ProcessLogs { content, contains_invalid_utf8, start_offset: Some(offset) });
+        }
+
+        // Collapsing needs every line, since runs may reach back past the tail
+        if let (Some(lines), false) = (options.lines, options.collapse_repeats) {
+            let (lines, lossy) = read_last_lines_lossy(Path::new(&process.log_path), lines).map_err(unreadable_io)?;
+            return Ok(ProcessLogs { content: lines.join("\n"), contains_invalid_utf8: lossy, start_offset: None });
+        }
+        let bytes = tokio::fs::read(&process.log_path).await.map_err(unreadable)?;
+        let (content, contains_invalid_utf8) = match String::from_utf8(bytes) {
+            Ok(content) => (content, false),
+            Err(e) => (String::from_utf8_lossy(e.as_bytes()).into_owned(), true),
+        };
+
+        let content = if options.collapse_repeats { collapse_repeats(&content) } else { content };
+        let content = match options.lines {
+            Some(lines) => tail_lines(&content, lines),
+            None => content,
+        };
+        Ok(ProcessLogs { content, contains_invalid_utf8, start_offset: None })
+    }
+
+    /// The index of a log if it has one that can be trusted, first brought up to date with
+    /// the lines written since, unless the manager is read-only. Failing to update it only
+    /// means the next read scans a little more.
+    fn current_log_index(&self, log_path: &Path) -> Option<LogIndex> {
+        let mut index = LogIndex::load(log_path)?;
+        if !self.read_only {
+            if let Err(e) = index.extend(log_path).and_then(|grown| if grown { index.write(log_path) } else { Ok(()) }) {
+                tracing::warn!(path = %log_path.display(), error = %e, "could not update the log index");
+            }
+        }
+        Some(index)
+    }
+
+    /// Index the live log of a process afresh, see [`crate::log_index`]. Reads since a time
+    /// use the index and keep it up to date from then on.
+    pub async fn reindex_process_logs(&self, name: &ProcessName) -> Result<String> {
+        self.ensure_writable("rebuild the log index")?;
+        let process = self.db.get_process_by_name(name).await?
+            .ok_or_else(|| Error::ProcessNotFound(name.to_string()))?;
+
+        let log_path = Path::new(&process.log_path);
+        let index = LogIndex::build(log_path, DEFAULT_STRIDE).map_err(|e| Error::LogUnreadable(process.log_path.clone(), e))?;
+        index.write(log_path)?;
+
+        Ok(format!(
+            "Log index rebuilt for process '{}': {} lines, {} entries",
+            name,
+            index.lines(),
+            index.entries().len()
+        ))
+    }
+
+    /// Remove the cgroup of a process that was started with cgroup limits
+    fn cleanup_cgroup(&self, process: &ProcessRecord) {
+        if process.cgroup_limits().is_empty() {
+            return;
+        }
+        if let Err(e) = Cgroup::new(&self.config().cgroup_root, &process.name).remove() {
+            eprintln!("Warning: {}", e);
+        }
+    }
+
+    #[tracing::instrument(name = "proc.probe", skip_all)]
+    async fn is_process_running(&self, pid: u32) -> bool {
+        self.pid_probe.is_alive(pid)
+    }
+
+    /// Get the last `lines` lines across the rotated files and the live log, treated as one
+    /// stream (oldest archive first). Older files are only read while more lines are needed.
+    #[tracing::instrument(name = "log.read", skip_all)]
+    pub async fn get_merged_process_logs(&self, name: &ProcessName, lines: Option<usize>) -> Result<String> {
+        self.get_merged_process_logs_with_options(name, &LogOptions { lines, ..Default::default() }).await
+    }
+
+    /// Merged logs as in [`Self::get_merged_process_logs`], optionally with repeats collapsed
+    pub async fn get_merged_process_logs_with_options(&self, name: &ProcessName, options: &LogOptions) -> Result<String> {
+        Ok(self.read_merged_process_logs(name, options).await?.content)
+    }
+
+    /// Merged logs as in [`Self::get_merged_process_logs_with_options`], telling whether the
+    /// lines read held bytes that are not UTF-8
+    pub async fn read_merged_process_logs(&self, name: &ProcessName, options: &LogOptions) -> Result<ProcessLogs> {
+        if options.tail_bytes.is_some() {
+            return Err(Error::Other("A byte tail reads only the live log, not the merged rotated logs".to_string()));
+        }
+        if options.since.is_some() {
+            return Err(Error::Other("Reading since a time covers only the live log, not the merged rotated logs".to_string()));
+        }
+        let process = self.db.get_process_by_name(name).await?
+            .ok_or_else(|| Error::ProcessNotFound(name.to_string()))?;
+
+        let log_path = PathBuf::from(&process.log_path);
+        // Newest first: the live log, then .1.log, .2.log, ...; compressed archives are not read
+        let mut files = vec![log_path.clone()];
+        files.extend(
+            self.log_rotator
+                .get_rotated_files(&log_path)?
+                .into_iter()
+                .filter(|rotated| !rotated.compressed)
+                .map(|rotated| rotated.path),
+        );
+
+        // Runs may span files and shrink to a single entry, so collapsing needs every line
+        let mut remaining = if options.collapse_repeats { usize::MAX } else { options.lines.unwrap_or(usize::MAX) };
+        let mut segments = Vec::new();
+        let mut contains_invalid_utf8 = false;
+        for file in files {
+            if remaining == 0 {
+                break;
+            }
+            let segment = match read_last_lines_lossy(&file, remaining) {
+                Ok((segment, lossy)) => {
+                    contains_invalid_utf8 |= lossy;
+                    segment
+                }
+                Err(Error::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(Error::Io(e)) => return Err(Error::LogUnreadable(file.display().to_string(), e)),
+                Err(e) => return Err(e),
+            };
+            remaining -= segment.len();
+            segments.push(segment);
+        }
+
+        segments.reverse();
+        let merged = segments.concat().join("\n");
+        let content = if !options.collapse_repeats {
+            merged
+        } else {
+            let collapsed = collapse_repeats(&merged);
+            match options.lines {
+                Some(lines) => tail_lines(&collapsed, lines),
+                None => collapsed,
+            }
+        };
+        Ok(ProcessLogs { content, contains_invalid_utf8, start_offset: None })
+    }
+
+    /// Get rotated log files for a process
+    #[deprecated(note = "reads every archive into memory; use `rotated_logs` to list them and `read_rotated_logs` to read them up to a limit")]
+    pub async fn get_rotated_logs(&self, name: &ProcessName) -> Result<Vec<String>> {
+        let process = self.db.get_process_by_name(name).await?
+            .ok_or_else(|| Error::ProcessNotFound(name.to_string()))?;
+
+        let log_path = PathBuf::from(&process.log_path);
+        let rotated_files = self.log_rotator.get_rotated_files(&log_path)?;
+
+        let mut logs = Vec::new();
+        for file_path in rotated_files.into_iter().filter(|rotated| !rotated.compressed).map(|rotated| rotated.path) {
+            let content = match tokio::fs::read_to_string(&file_path).await {
+                Ok(content) => content,
+                Err(_) => {
+                    // Try to read as bytes and convert to string, replacing invalid UTF-8
+                    match tokio::fs::read(&file_path).await {
+                        Ok(bytes) => String::from_utf8_lossy(&bytes).to_string(),
+                        Err(_) => continue,
+                    }
+                }
+            };
+            logs.push(format!("=== {} ===\n{}", file_path.display(), content));
+        }
+
+        Ok(logs)
+    }
+
+    /// The rotated log files of a process, newest first, without reading them
+    pub async fn rotated_logs(&self, name: &ProcessName) -> Result<Vec<RotatedLog>> {
+        let process = self.db.get_process_by_name(name).await?
+            .ok_or_else(|| Error::ProcessNotFound(name.to_string()))?;
+
+        let log_path = PathBuf::from(&process.log_path);
+        Ok(self
+            .log_rotator
+            .get_rotated_files(&log_path)?
+            .into_iter()
+            .map(|rotated| RotatedLog {
+                index: rotated.index,
+                path: rotated.path.to_string_lossy().to_string(),
+                size_bytes: rotated.size,
+                modified: rotated.modified.into(),
+                compressed: rotated.compressed,
+            })
+            .collect())
+    }
+
+    /// Read the uncompressed rotated logs of a process, newest first, keeping at most
+    /// `max_bytes` of content. Archives are read a block at a time, so memory use is
+    /// bounded by the limit rather than by the size of the archives.
+    pub async fn read_rotated_logs(&self, name: &ProcessName, max_bytes: usize) -> Result<RotatedLogsContent> {
+        use tokio::io::AsyncReadExt;
+
+        let rotated: Vec<RotatedLog> = self.rotated_logs(name).await?.into_iter().filter(|log| !log.compressed).collect();
+        let total_bytes = rotated.iter().map(|log| log.size_bytes).sum::<u64>();
+
+        let mut content = Vec::with_capacity(max_bytes.min(total_bytes as usize + rotated.len() * 256));
+        let mut chunk = vec![0u8; ROTATED_READ_CHUNK];
+        let mut truncated = false;
+        for (i, log) in rotated.iter().enumerate() {
+            let mut file = match tokio::fs::File::open(&log.path).await {
+                Ok(file) => file,
+                Err(_) => continue,
+            };
+            let header = format!("{}=== {} ===\n", if i == 0 { "" } else { "\n" }, log.path);
+            if !push_capped(&mut content, header.as_bytes(), max_bytes) {
+                truncated = true;
+                break;
+            }
+            loop {
+                let read = file.read(&mut chunk).await?;
+                if read == 0 {
+                    break;
+                }
+                if !push_capped(&mut content, &chunk[..read], max_bytes) {
+                    truncated = true;
+                    break;
+                }
+            }
+            if truncated {
+                break;
+            }
+        }
+
+        // Do not leave half a character at the cut
+        if truncated {
+            if let Err(e) = std::str::from_utf8(&content) {
+                if e.error_len().is_none() {
+                    content.truncate(e.valid_up_to());
+                }
+            }
+        }
+        let content = String::from_utf8(content).unwrap_or_else(|e| String::from_utf8_lossy(e.as_bytes()).into_owned());
+        Ok(RotatedLogsContent {
+            bytes: content.len() as u64,
+            content,
+            total_bytes,
+            truncated,
+        })
+    }
+
+    /// List the files that make up a process's logs: the live log followed by rotated files.
+    /// Compressed rotated files (`.gz`) are included as-is.
+    pub async fn log_archive_entries(&self, name: &ProcessName) -> Result<Vec<ArchiveEntry>> {
+        let process = self.db.get_process_by_name(name).await?
+            .ok_or_else(|| Error::ProcessNotFound(name.to_string()))?;
+
+        let log_path = PathBuf::from(&process.log_path);
+        let mut files = vec![log_path.clone()];
+        files.extend(self.log_rotator.get_rotated_files(&log_path)?.into_iter().map(|rotated| rotated.path));
+
+        Ok(files
+            .into_iter()
+            .map(|source| ArchiveEntry {
+                archive_name: format!(
+                    "{}/{}",
+                    name,
+                    source.file_name().map(|f| f.to_string_lossy().to_string()).unwrap_or_default()
+                ),
+                source,
+            })
+            .collect())
+    }
+
+    /// Bundle the live and rotated logs of a process into a tar file
+    pub async fn archive_process_logs(&self, name: &ProcessName, path: &Path) -> Result<String> {
+        let entries = self.log_archive_entries(name).await?;
+        write_tar_archive_to_path(path, name, &entries).await?;
+
+        Ok(format!("Logs for process '{}' archived to {}", name, path.display()))
+    }
+
+    /// Manually rotate log file for a process
+    #[tracing::instrument(name = "log.rotate", skip_all)]
+    pub async fn rotate_process_logs(&self, name: &ProcessName) -> Result<String> {
+        self.ensure_writable("rotate logs")?;
+        let process = self.db.get_process_by_name(name).await?
+            .ok_or_else(|| Error::ProcessNotFound(name.to_string()))?;
+
+        let log_path = PathBuf::from(&process.log_path);
+        if let Some(outcome) = self.log_rotator.force_rotate(&log_path).await? {
+            self.record_rotation(name, "manual", &outcome).await;
+        }
+        self.check_logs_quota(true).await;
+
+        Ok(format!("Log rotation completed for process '{}'", name))
+    }
+
+    /// Measure the logs of every process, or reuse a measurement that is still fresh
+    pub async fn logs_usage(&self) -> Result<LogsUsage> {
+        let cached = self.logs_usage.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        match cached {
+            Some(usage) if usage.is_fresh(self.clock.now()) => Ok(usage),
+            _ => Ok(Self::measure_logs(self.db.as_ref(), &self.log_rotator, &self.file_pool, &self.logs_usage, self.clock.now()).await?.0),
+        }
+    }
+
+    /// Measure the logs now and delete the oldest archives if they are over
+    /// `logs_quota_bytes`. None when there is no quota or the logs fit in it.
+    pub async fn enforce_logs_quota(&self) -> Result<Option<QuotaEnforcement>> {
+        self.ensure_writable("enforce the logs quota")?;
+        let quota = self.config().logs_quota_bytes;
+        Self::apply_logs_quota(self.db.as_ref(), &self.log_rotator, &self.file_pool, &self.events, &self.logs_usage, quota, self.clock.now(), false).await
+    }
+
+    /// Whether the last measurement found no rotated archives left to delete
+    fn archives_exhausted(&self) -> bool {
+        self.logs_usage.lock().unwrap_or_else(|e| e.into_inner()).as_ref().is_some_and(|usage| usage.rotated_bytes() == 0)
+    }
+
+    /// Check the quota after a start (reusing a fresh measurement) or a rotation (measuring
+    /// again). Failures are only logged; staying over the quota is warned about.
+    async fn check_logs_quota(&self, measure: bool) {
+        let quota = self.config().logs_quota_bytes;
+        match Self::apply_logs_quota(self.db.as_ref(), &self.log_rotator, &self.file_pool, &self.events, &self.logs_usage, quota, self.clock.now(), !measure).await {
+            Ok(Some(enforcement)) if enforcement.over_quota() && self.archives_exhausted() => eprintln!(
+                "Warning: logs use {}, over the quota of {} even with every rotated archive deleted",
+                human_bytes(enforcement.used_after),
+                human_bytes(enforcement.quota_bytes)
+            ),
+            Ok(_) => {}
+            Err(e) => tracing::warn!(error = %e, "failed to enforce the logs quota"),
+        }
+    }
+
+    /// Stat every log off the async threads, caching the result
+    async fn measure_logs(
+        db: &dyn StorageBackend,
+        log_rotator: &Arc<LogRotator>,
+        file_pool: &FilePool,
+        logs_usage: &std::sync::Mutex<Option<LogsUsage>>,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> Result<(LogsUsage, Vec<logs_quota::QuotaArchive>)> {
+        let log_paths: Vec<(String, PathBuf)> = db.get_log_paths().await?
+            .into_iter()
+            .map(|(name, path)| (name, PathBuf::from(path)))
+            .collect();
+        let log_rotator = log_rotator.clone();
+        let measurements = file_pool
+            .run_all(log_paths.iter().map(|(_, path)| path.clone()), move |path| logs_quota::measure_one(&log_rotator, &path))
+            .await;
+        let measured = logs_quota::tally(&log_paths, measurements, now);
+        *logs_usage.lock().unwrap_or_else(|e| e.into_inner()) = Some(measured.0.clone());
+        Ok(measured)
+    }
+
+    /// [`Self::enforce_logs_quota`] for the reaper, which has no manager to call it on.
+    /// With `reuse_cached`, a fresh measurement under the quota ends the check without
+    /// statting anything.
+    #[allow(clippy::too_many_arguments)]
+    async fn apply_logs_quota(
+        db: &dyn StorageBackend,
+        log_rotator: &Arc<LogRotator>,
+        file_pool: &FilePool,
+        events: &EventBus,
+        logs_usage: &std::sync::Mutex<Option<LogsUsage>>,
+        quota: u64,
+        now: chrono::DateTime<chrono::Utc>,
+        reuse_cached: bool,
+    ) -> Result<Option<QuotaEnforcement>> {
+        if quota == 0 {
+            return Ok(None);
+        }
+        if reuse_cached {
+            let cached = logs_usage.lock().unwrap_or_else(|e| e.into_inner()).clone();
+            if cached.is_some_and(|usage| usage.is_fresh(now) && usage.total_bytes() <= quota) {
+                return Ok(None);
+            }
+        }
+        let (usage, archives) = Self::measure_logs(db, log_rotator, file_pool, logs_usage, now).await?;
+        let used_before = usage.total_bytes();
+        if used_before <= quota {
+            return Ok(None);
+        }
+
+        let to_delete = logs_quota::archives_to_delete(archives, used_before, quota);
+        let removals = file_pool
+            .run_all(to_delete.iter().map(|archive| archive.path.clone()), std::fs::remove_file)
+            .await;
+        let mut deleted = Vec::new();
+        for (archive, removal) in to_delete.into_iter().zip(removals) {
+            // Gone already, or not ours to delete; the next measurement shows what is left
+            match removal {
+                Some(Ok(())) => {}
+                Some(Err(e)) => {
+                    tracing::warn!(path = %archive.path.display(), error = %e, "failed to delete log archive over the quota");
+                    continue;
+                }
+                None => {
+                    tracing::warn!(path = %archive.path.display(), "deleting log archive over the quota did not finish");
+                    continue;
+                }
+            }
+            deleted.push(logs_quota::DeletedArchive {
+                process_name: archive.process_name,
+                path: archive.path.to_string_lossy().to_string(),
+                size: archive.size,
+            });
+        }
+        let usage = if deleted.is_empty() { usage } else { Self::measure_logs(db, log_rotator, file_pool, logs_usage, now).await?.0 };
+        // Archives that could not be deleted leave the overrun to the next pass
+        let archives_exhausted = usage.rotated_bytes() == 0;
+        let enforcement = QuotaEnforcement { enforced_at: now, quota_bytes: quota, used_before, used_after: usage.total_bytes(), deleted };
+
+        // The archives are gone either way, so failures to record that are only logged
+        let mut by_process: std::collections::BTreeMap<&str, Vec<&logs_quota::DeletedArchive>> = Default::default();
+        for archive in &enforcement.deleted {
+            by_process.entry(&archive.process_name).or_default().push(archive);
+        }
+        for (name, archives) in by_process {
+            let entry = LogRotationEntry {
+                process_name: name.to_string(),
+                rotated_at: now,
+                trigger: LOGS_QUOTA_TRIGGER.to_string(),
+                rotated_from_size: 0,
+                archives_shifted: 0,
+                archives_deleted: archives.iter().map(|archive| archive.path.clone()).collect(),
+            };
+            if let Err(e) = db.insert_log_rotation(&entry).await {
+                tracing::warn!(process = %name, error = %e, "failed to record archives deleted for the logs quota");
+            }
+        }
+        if let Err(e) = db.insert_quota_enforcement(&enforcement, LOGS_QUOTA_HISTORY_LIMIT).await {
+            tracing::warn!(error = %e, "failed to record logs quota enforcement");
+        }
+
+        if enforcement.over_quota() && archives_exhausted {
+            tracing::warn!(used = enforcement.used_after, quota, "logs are over the quota with every archive deleted");
+            let log_paths = db.get_log_paths().await?;
+            let sizes = file_pool
+                .run_all(log_paths.iter().map(|(_, path)| path.clone()), |path| std::fs::metadata(path).map_or(0, |metadata| metadata.len()))
+                .await;
+            let largest = log_paths.into_iter().zip(sizes).max_by_key(|(_, size)| size.unwrap_or(0));
+            if let Some(((name, _), _)) = largest {
+                events.publish(ProcessEvent {
+                    process: name,
+                    timestamp: now,
+                    kind: ProcessEventKind::LogsOverQuota { used_bytes: enforcement.used_after, quota_bytes: quota },
+                });
+            }
+        }
+        Ok(Some(enforcement))
+    }
+
+    /// Store a rotation in the process's rotation history. The files have already moved,
+    /// so a failure to record it is only logged.
+    async fn record_rotation(&self, name: &ProcessName, trigger: &str, outcome: &RotationOutcome) {
+        let entry = LogRotationEntry {
+            process_name: name.to_string(),
+            rotated_at: self.clock.now(),
+            trigger: trigger.to_string(),
+            rotated_from_size: outcome.rotated_from_size,
+            archives_shifted: outcome.archives_shifted,
+            archives_deleted: outcome.archives_deleted.iter().map(|path| path.to_string_lossy().to_string()).collect(),
+        };
+        // Written here rather than by a subscriber, which could lag and lose entries
+        if let Err(e) = self.db.insert_log_rotation(&entry).await {
+            tracing::warn!(process = %name, error = %e, "failed to record log rotation");
+        }
+        self.publish(name, ProcessEventKind::LogRotated {
+            trigger: trigger.to_string(),
+            rotated_from_size: outcome.rotated_from_size,
+        });
+    }
+
+    /// Restart a process because files it watches changed, and remember that it did. A
+    /// process stopped on purpose is left alone, see "Stops and automatic restarts" in the
+    /// module docs.
+    pub async fn restart_for_change(&self, name: &ProcessName, path: &str, changes: usize) -> Result<RestartOutcome> {
+        self.ensure_writable("restart a process")?;
+        let mark = self.stop_mark(name);
+        let stopped_on_purpose = mark.lock().await;
+        let outcome = self.restart_instance(name, RestartOptions::default(), *stopped_on_purpose).await?;
+        drop(stopped_on_purpose);
+        if outcome.skipped.is_some() {
+            return Ok(outcome);
+        }
+        let entry = WatchRestartEntry {
+            process_name: name.to_string(),
+            restarted_at: self.clock.now(),
+            path: path.to_string(),
+            changes,
+        };
+        // The process has restarted either way, so a failure to record it is only logged
+        if let Err(e) = self.db.insert_watch_restart(&entry, WATCH_HISTORY_LIMIT).await {
+            tracing::warn!(process = %name, error = %e, "failed to record watch restart");
+        }
+        Ok(outcome)
+    }
+
+    /// Past restarts of a process made by watching files, oldest first
+    pub async fn get_watch_restart_history(&self, name: &ProcessName) -> Result<Vec<WatchRestartEntry>> {
+        self.db.get_process_by_name(name).await?
+            .ok_or_else(|| Error::ProcessNotFound(name.to_string()))?;
+        self.db.get_watch_restarts(name).await
+    }
+
+    /// The latest `limit` finished runs of a process, oldest first
+    pub async fn process_runs(&self, name: &ProcessName, limit: usize) -> Result<Vec<ProcessRun>> {
+        self.db.get_process_by_name(name).await?
+            .ok_or_else(|| Error::ProcessNotFound(name.to_string()))?;
+        self.db.get_process_runs(name, limit).await
+    }
+
+    /// Running processes with a watch configuration
+    pub async fn watched_processes(&self) -> Result<Vec<ProcessRecord>> {
+        self.db.get_watched_processes().await
+    }
+
+    /// Past rotations of a process's log, oldest first
+    pub async fn get_log_rotation_history(&self, name: &ProcessName) -> Result<Vec<LogRotationEntry>> {
+        self.db.get_process_by_name(name).await?
+            .ok_or_else(|| Error::ProcessNotFound(name.to_string()))?;
+        self.db.get_log_rotations(name).await
+    }
+
+    /// Attach a note to a process. Notes are kept by name, so they survive restarts and
+    /// soft deletes; a hard delete removes them.
+    pub async fn annotate_process(&self, name: &ProcessName, author: &str, text: &str) -> Result<ProcessNote> {
+        self.ensure_writable("annotate a process")?;
+        self.db.get_process_by_name(name).await?
+            .ok_or_else(|| Error::ProcessNotFound(name.to_string()))?;
+
+        let text = text.trim();
+        if text.is_empty() {
+            return Err(Error::InvalidNote("note text is empty".to_string()));
+        }
+        if text.len() > MAX_NOTE_BYTES {
+            return Err(Error::InvalidNote(format!(
+                "note is {} bytes, the limit is {}",
+                text.len(),
+                MAX_NOTE_BYTES
+            )));
+        }
+        if self.db.get_process_notes(name).await?.len() >= MAX_NOTES_PER_PROCESS {
+            return Err(Error::NoteLimitReached(name.to_string(), MAX_NOTES_PER_PROCESS));
+        }
+
+        self.db.insert_process_note(name, author, text).await
+    }
+
+    /// Notes on a process, oldest first
+    pub async fn get_process_notes(&self, name: &ProcessName) -> Result<Vec<ProcessNote>> {
+        self.db.get_process_by_name(name).await?
+            .ok_or_else(|| Error::ProcessNotFound(name.to_string()))?;
+        self.db.get_process_notes(name).await
+    }
+
+    pub async fn delete_process_note(&self, name: &ProcessName, note_id: i64) -> Result<()> {
+        self.ensure_writable("delete a note")?;
+        self.db.get_process_by_name(name).await?
+            .ok_or_else(|| Error::ProcessNotFound(name.to_string()))?;
+        if !self.db.delete_process_note(name, note_id).await? {
+            return Err(Error::NoteNotFound(name.to_string(), note_id));
+        }
+        Ok(())
+    }
+
+    /// Get log rotation status for a process
+    pub async fn get_log_rotation_status(&self, name: &ProcessName) -> Result<String> {
+        let process = self.db.get_process_by_name(name).await?
+            .ok_or_else(|| Error::ProcessNotFound(name.to_string()))?;
+
+        let log_path = PathBuf::from(&process.log_path);
+        let current_size = self.log_rotator.get_log_size(&log_path)?;
+        let needs_rotation = self.log_rotator.needs_rotation(&log_path)?;
+        let rotated_files = self.log_rotator.get_rotated_files(&log_path)?;
+        let last_rotation = self.db.get_log_rotations(name).await?
+            .last()
+            .map(|entry| entry.rotated_at.format("%Y-%m-%d %H:%M:%S UTC").to_string())
+            .unwrap_or_else(|| "Never".to_string());
+
+        let status = format!(
+            "Log file: {}\nCurrent size: {}\nNeeds rotation: {}\nRotated files: {}\nLast rotation: {}",
+            log_path.display(),
+            human_bytes(current_size),
+            if needs_rotation { "Yes" } else { "No" },
+            rotated_files.len(),
+            last_rotation
+        );
+
+        Ok(status)
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tail_lines_zero_is_empty() {
+        assert_eq!(tail_lines("one\ntwo\n", 0), "");
+        assert_eq!(tail_lines("", 0), "");
+    }
+
+    #[test]
+    fn test_tail_lines_more_than_available() {
+        // No trailing empty segment, whatever the file ends with
+        assert_eq!(tail_lines("one\ntwo\n", 10), "one\ntwo");
+        assert_eq!(tail_lines("one\ntwo", 10), "one\ntwo");
+        assert_eq!(tail_lines("one\ntwo\n", usize::MAX), "one\ntwo");
+    }
+
+    #[test]
+    fn test_tail_lines_takes_the_end() {
+        assert_eq!(tail_lines("a\nb\nc\nd\n", 2), "c\nd");
+        assert_eq!(tail_lines("a\r\nb\r\n", 1), "b");
+    }
+
+    #[test]
+    fn test_collapse_repeats_runs_at_start_and_end() {
+        assert_eq!(
+            collapse_repeats("boom\nboom\nboom\nok\nretry\nretry\n"),
+            "boom  [repeated 3 times]\nok\nretry  [repeated 2 times]"
+        );
+        assert_eq!(collapse_repeats("a\nb\na\n"), "a\nb\na");
+        assert_eq!(collapse_repeats(""), "");
+    }
+
+    #[test]
+    fn test_collapse_repeats_before_line_limit() {
+        let content = "start\nboom\nboom\nboom\nboom\nend\n";
+        // The limit counts collapsed entries, so the whole run fits in one of them
+        assert_eq!(tail_lines(&collapse_repeats(content), 2), "boom  [repeated 4 times]\nend");
+        assert_eq!(tail_lines(&collapse_repeats(content), 1), "end");
+        assert_eq!(tail_lines(&collapse_repeats("x\nx\n"), 1), "x  [repeated 2 times]");
+    }
+
+    fn named(name: &str) -> ProcessName {
+        name.parse().unwrap()
+    }
+
+    /// Every file and directory below `root`, relative to it, with file contents
+    fn snapshot(root: &Path) -> Vec<(PathBuf, Option<Vec<u8>>)> {
+        let mut entries = Vec::new();
+        let mut pending = vec![root.to_path_buf()];
+        while let Some(dir) = pending.pop() {
+            for entry in std::fs::read_dir(&dir).unwrap() {
+                let path = entry.unwrap().path();
+                let relative = path.strip_prefix(root).unwrap().to_path_buf();
+                if path.is_dir() {
+                    entries.push((relative, None));
+                    pending.push(path);
+                } else {
+                    entries.push((relative, Some(std::fs::read(&path).unwrap())));
+                }
+            }
+        }
+        entries.sort();
+        entries
+    }
+
+    /// Manager whose database and default log directory live outside the directory checked for leftovers
+    async fn manager(state_dir: &Path) -> ProcessManager {
+        let config = Config::new()
+            .with_database_path(state_dir.join("test.db"))
+            .with_log_dir(state_dir.join("logs"))
+            .with_log_rotation(crate::config::LogRotationConfig {
+                enabled: true,
+                max_file_size: 16,
+                max_files: 3,
+            });
+        ProcessManager::new(config).await.unwrap()
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_start_churn_leaves_no_zombies_and_bounded_tracking() {
+        let root = tempfile::TempDir::new().unwrap();
+        // No reaper pass during the churn: starts and deletes alone must keep up
+        let config = Config::in_directory(root.path())
+            .with_reaper_interval(std::time::Duration::from_secs(3600))
+            .with_start_check_window(std::time::Duration::ZERO);
+        let pm = ProcessManager::new(config).await.unwrap();
+        // An exited child of this test process that nothing has waited for
+        let is_our_zombie = |pid: u32| SystemPidProbe.zombie_parent(pid, chrono::Utc::now()) == Some(std::process::id());
+
+        let mut pids = Vec::new();
+        let mut largest = 0;
+        for i in 0..200 {
+            let name = named(&format!("churn-{}", i));
+            let outcome = pm.start(&name, "echo", vec![i.to_string()], HashMap::new(), None, None).await.unwrap();
+            pids.extend(outcome.pid);
+            // Half are deleted straight away, the rest left to exit on their own
+            if i % 2 == 0 {
+                pm.delete(&name).await.unwrap();
+            }
+            let tracked = pm.running_processes.lock().await;
+            largest = largest.max(tracked.len());
+            // A zombie no longer tracked would never be waited for
+            let leaked: Vec<u32> = pids.iter().copied().filter(|pid| !tracked.contains_key(pid) && is_our_zombie(*pid)).collect();
+            assert!(leaked.is_empty(), "untracked zombies after {} starts: {:?}", i + 1, leaked);
+        }
+        assert!(largest <= 32, "tracking grew to {} children", largest);
+
+        for _ in 0..100 {
+            pm.prune_dead_tracking().await;
+            if pm.running_processes.lock().await.is_empty() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        assert!(pm.running_processes.lock().await.is_empty());
+        let zombies: Vec<u32> = pids.into_iter().filter(|pid| is_our_zombie(*pid)).collect();
+        assert!(zombies.is_empty(), "zombies left: {:?}", zombies);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_concurrent_quick_exits_keep_their_exit_codes() {
+        let root = tempfile::TempDir::new().unwrap();
+        // Reaper passes and the prunes of other starts run while each start window is open
+        let config = Config::in_directory(root.path()).with_reaper_interval(std::time::Duration::from_millis(1));
+        let pm = ProcessManager::new(config).await.unwrap();
+
+        let starts = (0..40).map(|i| {
+            let pm = &pm;
+            async move {
+                tokio::time::sleep(std::time::Duration::from_millis(i * 3)).await;
+                let args = vec!["-c".to_string(), "sleep 0.02; exit 0".to_string()];
+                pm.start(&named(&format!("quick-{}", i)), "sh", args, HashMap::new(), None, None).await.unwrap()
+            }
+        });
+        for outcome in futures_util::future::join_all(starts).await {
+            assert_eq!(outcome.status, ProcessStatus::Stopped, "{:?}", outcome);
+            assert_eq!(outcome.early_exit.as_ref().and_then(|exit| exit.exit_code), Some(0), "{:?}", outcome);
+            let record = pm.db.get_process_by_name(&named(&outcome.name)).await.unwrap().unwrap();
+            assert_eq!((record.status, record.exit_code), (ProcessStatus::Stopped, Some(0)), "{}", outcome.name);
+        }
+        assert!(pm.in_start_window.lock().unwrap().is_empty());
+    }
+
+    async fn assert_rollback_restores(step: StartStep, prepare: impl FnOnce(&Path)) {
+        let db_dir = tempfile::TempDir::new().unwrap();
+        let log_root = tempfile::TempDir::new().unwrap();
+        prepare(log_root.path());
+        let before = snapshot(log_root.path());
+
+        let mut pm = manager(db_dir.path()).await;
+        pm.fail_start_at = Some(step);
+        let options = StartOptions {
+            log_dir: Some(log_root.path().join("nested/logs").to_string_lossy().to_string()),
+            ..Default::default()
+        };
+        let result = pm
+            .start_with_options(&named("victim"), "sleep", vec!["30".to_string()], HashMap::new(), options)
+            .await;
+
+        let error = result.unwrap_err().to_string();
+        assert!(error.contains("injected failure"), "unexpected error: {}", error);
+        assert!(!error.contains("rollback was incomplete"), "unexpected error: {}", error);
+        assert_eq!(snapshot(log_root.path()), before, "rollback after {:?} left changes", step);
+        assert!(pm.db.get_process_by_name(&named("victim")).await.unwrap().is_none());
+        assert!(pm.running_processes.lock().await.is_empty());
+        assert!(pm.db.get_log_rotations(&named("victim")).await.unwrap().is_empty(), "undone rotation was recorded");
+    }
+
+    #[tokio::test]
+    async fn test_rollback_when_log_create_fails() {
+        assert_rollback_restores(StartStep::CreateLog, |_| {}).await;
+    }
+
+    #[tokio::test]
+    async fn test_rollback_when_spawn_fails() {
+        assert_rollback_restores(StartStep::Spawn, |_| {}).await;
+    }
+
+    #[tokio::test]
+    async fn test_rollback_when_insert_fails() {
+        assert_rollback_restores(StartStep::InsertRecord, |_| {}).await;
+    }
+
+    #[tokio::test]
+    async fn test_rollback_undoes_rotation_and_keeps_existing_files() {
+        let prepare = |root: &Path| {
+            let logs = root.join("nested/logs");
+            std::fs::create_dir_all(&logs).unwrap();
+            std::fs::write(logs.join("victim.log"), "previous run output over the size limit").unwrap();
+            std::fs::write(logs.join("victim.1.log"), "older run").unwrap();
+            std::fs::write(logs.join("neighbour.log"), "another process").unwrap();
+        };
+        assert_rollback_restores(StartStep::Spawn, prepare).await;
+        assert_rollback_restores(StartStep::InsertRecord, prepare).await;
+    }
+
+    #[tokio::test]
+    async fn test_rotation_history_matches_files() {
+        let db_dir = tempfile::TempDir::new().unwrap();
+        let pm = manager(db_dir.path()).await;
+        pm.start(&named("victim"), "true", vec![], HashMap::new(), None, None).await.unwrap();
+        let log_path = PathBuf::from(pm.db.get_process_by_name(&named("victim")).await.unwrap().unwrap().log_path);
+        let archive = |i: usize| log_path.with_file_name(format!("victim.{}.log", i));
+
+        assert!(pm.get_log_rotation_status(&named("victim")).await.unwrap().contains("Last rotation: Never"));
+        for run in 1..=4 {
+            std::fs::write(&log_path, format!("run {}\n", run)).unwrap();
+            pm.rotate_process_logs(&named("victim")).await.unwrap();
+        }
+
+        let history = pm.get_log_rotation_history(&named("victim")).await.unwrap();
+        let shifted: Vec<usize> = history.iter().map(|entry| entry.archives_shifted).collect();
+        assert_eq!(shifted, vec![0, 1, 2, 2]);
+        assert!(history.iter().all(|entry| entry.trigger == "manual" && entry.rotated_from_size == 6));
+        // Only the fourth rotation went past max_files, dropping run 1
+        let deleted: Vec<&[String]> = history.iter().map(|entry| entry.archives_deleted.as_slice()).collect();
+        assert_eq!(deleted[..3], [&[] as &[String]; 3]);
+        assert_eq!(deleted[3], [archive(3).to_string_lossy().to_string()]);
+        assert_eq!(std::fs::read_to_string(archive(3)).unwrap(), "run 2\n");
+        assert!(!archive(4).exists());
+
+        let status = pm.get_log_rotation_status(&named("victim")).await.unwrap();
+        let last = history[3].rotated_at.format("%Y-%m-%d %H:%M:%S UTC").to_string();
+        assert!(status.contains(&format!("Last rotation: {}", last)), "{}", status);
+
+        // A hard delete forgets the history with the process
+        pm.delete_with_options(&named("victim"), DeleteOptions { hard: true }).await.unwrap();
+        assert!(pm.db.get_log_rotations(&named("victim")).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_rollback_keeps_directory_that_gained_files() {
+        let db_dir = tempfile::TempDir::new().unwrap();
+        let log_root = tempfile::TempDir::new().unwrap();
+        let pm = manager(db_dir.path()).await;
+
+        let logs = log_root.path().join("shared");
+        let artifacts = StartArtifacts {
+            created_dirs: missing_directories(&logs),
+            ..Default::default()
+        };
+        std::fs::create_dir_all(&logs).unwrap();
+        std::fs::write(logs.join("other.log"), "landed meanwhile").unwrap();
+
+        assert!(pm.rollback_start_process(&named("victim"), artifacts).await.is_empty());
+        assert!(logs.join("other.log").exists());
+    }
+
+    #[tokio::test]
+    async fn test_rollback_problems_are_reported() {
+        let db_dir = tempfile::TempDir::new().unwrap();
+        let log_root = tempfile::TempDir::new().unwrap();
+        let pm = manager(db_dir.path()).await;
+
+        // A "log file" that cannot be removed with remove_file
+        let not_a_file = log_root.path().join("victim.log");
+        std::fs::create_dir(&not_a_file).unwrap();
+        let artifacts = StartArtifacts {
+            created_log_file: Some(not_a_file),
+            ..Default::default()
+        };
+
+        let problems = pm.rollback_start_process(&named("victim"), artifacts).await;
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].starts_with("failed to remove log file"));
+
+        let error = Error::StartRolledBack(Box::new(Error::Other("spawn failed".to_string())), problems);
+        assert!(error.to_string().starts_with("spawn failed\nWarning: rollback was incomplete:\n  - failed to remove log file"));
+    }
+
+    /// Start `victim` with a progress callback, returning the result and the phases seen
+    async fn start_with_progress(pm: &ProcessManager, command: &str) -> (Result<StartOutcome>, Vec<StartPhaseReport>) {
+        let phases = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let seen = phases.clone();
+        let options = StartOptions {
+            progress: Some(StartProgress::new(move |report| seen.lock().unwrap().push(report.clone()))),
+            ..Default::default()
+        };
+        let result = pm.start_with_options(&named("victim"), command, vec!["30".to_string()], HashMap::new(), options).await;
+        let phases = phases.lock().unwrap().clone();
+        (result, phases)
+    }
+
+    #[tokio::test]
+    async fn test_start_progress_reports_every_phase() {
+        let db_dir = tempfile::TempDir::new().unwrap();
+        let pm = manager(db_dir.path()).await;
+
+        let (result, phases) = start_with_progress(&pm, "sleep").await;
+        result.unwrap();
+        let labels: Vec<String> = phases.iter().map(|report| report.phase.to_string()).collect();
+        assert_eq!(labels, ["validate", "log", "spawn", "wait", "record"]);
+        assert!(phases.iter().all(|report| !report.failed));
+        assert!(phases[1].detail.starts_with("created log file "));
+        assert!(phases[2].detail.starts_with("spawned pid "));
+        assert_eq!(phases[3].detail, "initial status: running");
+        // The start window dominates the wait phase
+        assert!(phases[3].elapsed_ms >= pm.config().start_check_window.as_millis() as u64 / 2);
+    }
+
+    #[tokio::test]
+    async fn test_start_progress_names_the_failing_phase() {
+        let db_dir = tempfile::TempDir::new().unwrap();
+        let mut pm = manager(db_dir.path()).await;
+        pm.fail_start_at = Some(StartStep::Spawn);
+
+        let (result, phases) = start_with_progress(&pm, "sleep").await;
+        assert!(result.is_err());
+        let labels: Vec<String> = phases.iter().map(ToString::to_string).collect();
+        assert_eq!(labels.len(), 3);
+        assert!(labels[0].starts_with("validate: validated inputs"));
+        assert!(labels[2].starts_with("spawn failed: Failed to start process 'victim': injected failure"), "unexpected phase: {}", labels[2]);
+    }
+
+    #[tokio::test]
+    async fn test_transient_log_failures_are_retried_and_reported() {
+        use crate::retry_fs::ScriptedFs;
+        let db_dir = tempfile::TempDir::new().unwrap();
+        let mut pm = manager(db_dir.path()).await;
+        let scripted = Arc::new(ScriptedFs::default().fail("create_dir_all", [libc::ENOENT]).fail("open_append", [libc::EACCES]));
+        pm.file_system = scripted.clone();
+
+        let (result, phases) = start_with_progress(&pm, "sleep").await;
+        result.unwrap();
+        assert_eq!(scripted.calls("create_dir_all"), 2);
+        assert_eq!(scripted.calls("open_append"), 2);
+        assert!(phases[1].detail.starts_with("created log file "), "{}", phases[1].detail);
+        assert!(phases[1].detail.ends_with(" (2 filesystem retries, waited 50ms)"), "{}", phases[1].detail);
+        assert!(!phases[2].detail.contains("retr"), "{}", phases[2].detail);
+        pm.stop(&named("victim")).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_read_only_log_directory_fails_the_start_without_retrying() {
+        use crate::retry_fs::ScriptedFs;
+        let db_dir = tempfile::TempDir::new().unwrap();
+        let mut pm = manager(db_dir.path()).await;
+        let scripted = Arc::new(ScriptedFs::default().fail("open_append", [libc::EROFS]).fail("remove_dir", [libc::EBUSY]));
+        pm.file_system = scripted.clone();
+        let log_root = tempfile::TempDir::new().unwrap();
+        let options = StartOptions {
+            log_dir: Some(log_root.path().join("nested/logs").to_string_lossy().to_string()),
+            ..Default::default()
+        };
+
+        let error = pm
+            .start_with_options(&named("victim"), "sleep", vec!["30".to_string()], HashMap::new(), options)
+            .await
+            .unwrap_err();
+        assert_eq!(scripted.calls("open_append"), 1);
+        assert!(error.to_string().contains("Read-only file system"), "{}", error);
+        // The rollback still removes the directories it made, retrying the busy one
+        assert!(!error.to_string().contains("rollback was incomplete"), "{}", error);
+        assert_eq!(scripted.calls("remove_dir"), 3);
+        assert!(!log_root.path().join("nested").exists());
+    }
+
+    async fn queueing_manager(state_dir: &Path, depth: usize, max_wait_secs: u64) -> ProcessManager {
+        let config = Config::new()
+            .with_database_path(state_dir.join("test.db"))
+            .with_log_dir(state_dir.join("logs"))
+            .with_start_queue(crate::config::StartQueueConfig { enabled: false, depth, max_wait_secs });
+        ProcessManager::new(config).await.unwrap()
+    }
+
+    /// Start `victim` queued under pressure, returning the result and the phases seen
+    async fn start_queued(pm: &ProcessManager) -> (Result<StartOutcome>, Vec<StartPhaseReport>) {
+        let phases = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let seen = phases.clone();
+        let options = StartOptions {
+            progress: Some(StartProgress::new(move |report| seen.lock().unwrap().push(report.clone()))),
+            queue_on_pressure: true,
+            ..Default::default()
+        };
+        let result = pm.start_with_options(&named("victim"), "sleep", vec!["30".to_string()], HashMap::new(), options).await;
+        let phases = phases.lock().unwrap().clone();
+        (result, phases)
+    }
+
+    #[tokio::test]
+    async fn test_queued_start_retries_once_pressure_clears() {
+        let db_dir = tempfile::TempDir::new().unwrap();
+        let pm = queueing_manager(db_dir.path(), 4, 10).await;
+        let mut events = pm.subscribe();
+        pm.simulate_spawn_pressure(true);
+
+        let ((result, phases), ()) = tokio::join!(start_queued(&pm), async {
+            tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+            pm.simulate_spawn_pressure(false);
+        });
+        let outcome = result.unwrap();
+        assert_eq!(outcome.status, ProcessStatus::Running);
+        let labels: Vec<String> = phases.iter().map(|report| report.phase.to_string()).collect();
+        assert_eq!(labels, ["validate", "log", "queue", "spawn", "wait", "record"]);
+        assert!(phases[2].detail.contains("waiting up to 10s with 1 start(s) queued"), "{}", phases[2].detail);
+        // The spawn phase includes the time spent queued
+        assert!(phases[3].elapsed_ms >= 250, "{:?}", phases[3]);
+        assert_eq!(pm.start_queue.waiting(), 0);
+
+        let queued = events.recv().await.unwrap();
+        assert_eq!(queued.process, "victim");
+        assert!(matches!(queued.kind, ProcessEventKind::StartQueued { queue_depth: 1, .. }), "{:?}", queued.kind);
+        assert!(matches!(events.recv().await.unwrap().kind, ProcessEventKind::Started { .. }));
+        pm.stop(&named("victim")).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_queued_start_gives_up_after_max_wait() {
+        let db_dir = tempfile::TempDir::new().unwrap();
+        let pm = queueing_manager(db_dir.path(), 4, 1).await;
+        pm.simulate_spawn_pressure(true);
+
+        let started = std::time::Instant::now();
+        let (result, phases) = start_queued(&pm).await;
+        let error = result.unwrap_err().to_string();
+        assert!(started.elapsed() >= std::time::Duration::from_secs(1));
+        assert!(error.starts_with("Failed to start process 'victim': Resource temporarily unavailable"), "{}", error);
+        assert!(error.contains("in the start queue with 1 start(s) queued)"), "{}", error);
+        let labels: Vec<String> = phases.iter().map(|report| report.phase.to_string()).collect();
+        assert_eq!(labels, ["validate", "log", "queue", "spawn"]);
+        assert!(phases[3].failed);
+        assert_eq!(pm.start_queue.waiting(), 0);
+        assert!(pm.db.get_process_by_name(&named("victim")).await.unwrap().is_none());
+
+        // Without the option the pressure fails the start at once
+        let started = std::time::Instant::now();
+        let error = pm.start(&named("victim"), "sleep", vec!["30".to_string()], HashMap::new(), None, None).await.unwrap_err();
+        assert!(started.elapsed() < std::time::Duration::from_secs(1));
+        assert!(!error.to_string().contains("start queue"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn test_full_start_queue_fails_at_once() {
+        let db_dir = tempfile::TempDir::new().unwrap();
+        let pm = queueing_manager(db_dir.path(), 0, 10).await;
+        pm.simulate_spawn_pressure(true);
+
+        let (result, phases) = start_queued(&pm).await;
+        let error = result.unwrap_err().to_string();
+        assert!(error.contains("(the start queue is full with 0 starts waiting)"), "{}", error);
+        assert!(phases.iter().all(|report| report.phase != StartPhase::Queue));
+    }
+
+    #[tokio::test]
+    async fn test_failed_restart_keeps_previous_definition() {
+        let db_dir = tempfile::TempDir::new().unwrap();
+        let mut pm = manager(db_dir.path()).await;
+        pm.start(&named("victim"), "sleep", vec!["30".to_string()], HashMap::new(), None, None)
+            .await
+            .unwrap();
+
+        pm.fail_start_at = Some(StartStep::Spawn);
+        let error = pm.restart(&named("victim")).await.unwrap_err().to_string();
+        assert!(error.contains("injected failure"), "unexpected error: {}", error);
+
+        let record = pm.db.get_process_by_name(&named("victim")).await.unwrap().unwrap();
+        assert_eq!(record.status, ProcessStatus::Failed);
+        assert_eq!(record.pid, None);
+        assert_eq!(record.command, "sleep");
+        assert_eq!(record.args, vec!["30".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_restart_runs_the_definition_changed_while_it_stopped() {
+        let db_dir = tempfile::TempDir::new().unwrap();
+        let old_dir = tempfile::TempDir::new().unwrap();
+        let new_dir = tempfile::TempDir::new().unwrap();
+        let mut pm = manager(db_dir.path()).await;
+        let options = StartOptions { working_dir: Some(old_dir.path().to_string_lossy().to_string()), ..Default::default() };
+        let env = HashMap::from([("PMR_DEFINITION".to_string(), "old".to_string())]);
+        pm.start_with_options(&named("victim"), "sleep", vec!["30".to_string()], env, options).await.unwrap();
+        let id = pm.db.get_process_by_name(&named("victim")).await.unwrap().unwrap().id;
+
+        let pause = Arc::new(tokio::sync::Barrier::new(2));
+        pm.restart_pause = Some(pause.clone());
+        let pm = Arc::new(pm);
+        let restart = tokio::spawn({
+            let pm = pm.clone();
+            async move { pm.restart(&named("victim")).await }
+        });
+        // The restart has read the old definition and stopped the process
+        pause.wait().await;
+        let new_dir_path = new_dir.path().to_string_lossy().to_string();
+        pm.update_working_dir(&named("victim"), &new_dir_path).await.unwrap();
+        // An environment change committed to the stored definition is picked up the same way
+        let mut changed = pm.db.get_process_by_name(&named("victim")).await.unwrap().unwrap();
+        changed.env_vars.insert("PMR_DEFINITION".to_string(), "new".to_string());
+        assert!(pm.db.replace_process(&changed).await.unwrap());
+        pause.wait().await;
+
+        let outcome = restart.await.unwrap().unwrap();
+        let pid = outcome.start.unwrap().pid.unwrap();
+        assert_eq!(std::fs::read_link(format!("/proc/{}/cwd", pid)).unwrap(), new_dir.path().canonicalize().unwrap());
+        let environ = std::fs::read(format!("/proc/{}/environ", pid)).unwrap();
+        assert!(environ.split(|byte| *byte == 0).any(|var| var == b"PMR_DEFINITION=new"));
+        let record = pm.db.get_process_by_name(&named("victim")).await.unwrap().unwrap();
+        assert_eq!(record.working_dir, new_dir_path);
+        assert_eq!(record.explicit_env_vars().get("PMR_DEFINITION").map(String::as_str), Some("new"));
+        assert_eq!(record.pid, Some(pid));
+        assert_ne!(record.id, id);
+        pm.delete_with_options(&named("victim"), DeleteOptions { hard: true }).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_reset_for_restart_keeps_the_row() {
+        let db_dir = tempfile::TempDir::new().unwrap();
+        let pm = manager(db_dir.path()).await;
+        pm.start(&named("victim"), "sleep", vec!["30".to_string()], HashMap::new(), None, None).await.unwrap();
+        let before = pm.db.get_process_by_name(&named("victim")).await.unwrap().unwrap();
+
+        let reset = pm.db.reset_for_restart(&named("victim"), pm.clock.now()).await.unwrap().unwrap();
+        assert_eq!((reset.status.clone(), reset.pid), (ProcessStatus::Stopped, None));
+        let stored = pm.db.get_process_by_name(&named("victim")).await.unwrap().unwrap();
+        assert_eq!((stored.id, stored.status, stored.pid), (before.id, ProcessStatus::Stopped, None));
+        assert!(pm.db.reset_for_restart(&named("missing"), pm.clock.now()).await.unwrap().is_none());
+        unsafe { libc::kill(before.pid.unwrap() as i32, libc::SIGKILL) };
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_clear_skips_remaining_processes() {
+        let db_dir = tempfile::TempDir::new().unwrap();
+        let pm = manager(db_dir.path()).await;
+        for name in ["one", "two"] {
+            pm.start(&named(name), "true", vec![], HashMap::new(), None, None).await.unwrap();
+            pm.stop(&named(name)).await.ok();
+        }
+
+        pm.cancellation().cancel();
+        let result = pm.clear_processes(true).await.unwrap();
+        assert!(result.cancelled);
+        assert_eq!(result.cleared_count, 0);
+        assert_eq!(result.skipped_processes.len(), 2);
+        assert_eq!(pm.db.get_all_processes().await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_in_directory_layout() {
+        let root = tempfile::TempDir::new().unwrap();
+        let dir = root.path().join("embedded");
+        let pm = ProcessManager::in_directory(&dir).await.unwrap();
+
+        assert_eq!(pm.config().database_path, dir.join("processes.db"));
+        assert_eq!(pm.config().default_log_dir, dir.join("logs"));
+        assert!(dir.join("processes.db").is_file());
+        assert!(dir.join("logs").is_dir());
+
+        pm.start(&named("echo"), "echo", vec!["hi".to_string()], HashMap::new(), None, None)
+            .await
+            .unwrap();
+        let record = pm.get_process_status(&named("echo")).await.unwrap();
+        assert_eq!(PathBuf::from(&record.log_path), dir.join("logs").join("echo.log"));
+    }
+
+    #[tokio::test]
+    async fn test_reload_applies_new_rotation_threshold() {
+        let root = tempfile::TempDir::new().unwrap();
+        let path = root.path().join("config.toml");
+        std::fs::write(&path, "[log_rotation]\nmax_file_size = 1000000\n").unwrap();
+        let pm = ProcessManager::new(Config::in_directory(root.path()).with_file(&path).unwrap()).await.unwrap();
+        let log = root.path().join("logs").join("web.log");
+        std::fs::write(&log, "x".repeat(500)).unwrap();
+        assert!(!pm.log_rotator.needs_rotation(&log).unwrap());
 
-        // Determine log directory - use custom log_dir if provided, otherwise use default
-        let log_directory = if let Some(custom_log_dir) = log_dir {
-            PathBuf::from(custom_log_dir)
-        } else {
-            self.config.default_log_dir.clone()
-        };
+        // An invalid file is refused and changes nothing
+        std::fs::write(&path, "[log_rotation]\nmax_file_size = \"small\"\n").unwrap();
+        assert!(pm.reload_config().await.is_err());
+        assert!(!pm.log_rotator.needs_rotation(&log).unwrap());
 
-        // Track resources created for potential rollback
-        let mut created_log_dir = false;
-        let mut created_log_file = false;
-        let inserted_db_record = false;
+        std::fs::write(&path, "[log_rotation]\nmax_file_size = 100\n").unwrap();
+        let outcome = pm.reload_config().await.unwrap();
+        assert_eq!(outcome.changed, vec!["log_rotation"]);
+        assert_eq!(outcome.config_file, path);
+        assert_eq!(pm.config().log_rotation.max_file_size, 100);
+        assert!(pm.log_rotator.rotate_if_needed(&log).await.unwrap().is_some());
+        assert!(root.path().join("logs").join("web.1.log").is_file());
+    }
 
-        // Ensure the log directory exists
-        let log_dir_existed = log_directory.exists();
-        if let Err(e) = self.config.ensure_log_directory(&log_directory) {
-            return Err(e);
-        }
-        if !log_dir_existed {
-            created_log_dir = true;
+    /// Poll until the named process leaves `Running`
+    async fn wait_until_exited(pm: &ProcessManager, name: &str) -> ProcessRecord {
+        for _ in 0..100 {
+            let record = pm.get_process_status(&named(name)).await.unwrap();
+            if record.status != ProcessStatus::Running {
+                return record;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
         }
+        panic!("{} is still running", name);
+    }
 
-        let log_path = log_directory.join(format!("{}.log", name));
+    #[tokio::test]
+    async fn test_job_succeeds_and_reruns() {
+        let root = tempfile::TempDir::new().unwrap();
+        let pm = ProcessManager::in_directory(root.path()).await.unwrap();
+        let job = StartOptions { kind: ProcessKind::Job, ..Default::default() };
+        let backup = named("backup");
+        let echo = |options| pm.start_with_options(&backup, "echo", vec!["done".to_string()], HashMap::new(), options);
+        echo(job.clone()).await.unwrap();
+        let record = wait_until_exited(&pm, "backup").await;
+        assert_eq!(record.status, ProcessStatus::Succeeded);
+        assert_eq!(record.runs, 1);
+
+        assert!(matches!(echo(job.clone()).await, Err(Error::JobCompleted(_))));
+
+        echo(StartOptions { rerun: true, ..job.clone() }).await.unwrap();
+        let record = wait_until_exited(&pm, "backup").await;
+        assert_eq!(record.status, ProcessStatus::Succeeded);
+        assert_eq!(record.runs, 2);
+        let rotations = pm.db.get_log_rotations(&named("backup")).await.unwrap();
+        assert_eq!(rotations.len(), 1);
+        assert_eq!(rotations[0].trigger, "rerun");
 
-        // Check if log rotation is needed for existing log file
-        if log_path.exists() {
-            if let Err(e) = self.log_rotator.rotate_if_needed(&log_path).await {
-                self.rollback_start_process(&id, &log_path, created_log_dir, created_log_file, inserted_db_record).await;
-                return Err(e);
+        // Services cannot be rerun, and a failing job ends up failed
+        pm.start(&named("web"), "sleep", vec!["30".to_string()], HashMap::new(), None, None).await.unwrap();
+        let rerun = StartOptions { rerun: true, ..Default::default() };
+        assert!(matches!(
+            pm.start_with_options(&named("web"), "sleep", vec!["30".to_string()], HashMap::new(), rerun).await,
+            Err(Error::ProcessAlreadyExists(_))
+        ));
+        pm.start_with_options(&named("broken"), "false", vec![], HashMap::new(), job).await.unwrap();
+        assert_eq!(wait_until_exited(&pm, "broken").await.status, ProcessStatus::Failed);
+        pm.stop(&named("web")).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_refresh_during_reaping_keeps_the_job_result() {
+        let root = tempfile::TempDir::new().unwrap();
+        // Reaper passes are driven by hand
+        let config = Config::in_directory(root.path())
+            .with_reaper_interval(std::time::Duration::from_secs(3600))
+            .with_start_check_window(std::time::Duration::ZERO);
+        let pm = ProcessManager::new(config).await.unwrap();
+        let job = StartOptions { kind: ProcessKind::Job, ..Default::default() };
+        pm.start_with_options(&named("backup"), "true", vec![], HashMap::new(), job).await.unwrap();
+        let read_before_exit = pm.db.get_process_by_name(&named("backup")).await.unwrap().unwrap();
+
+        let mut exited = Vec::new();
+        for _ in 0..100 {
+            exited = ProcessManager::reap_exited_children(&pm.running_processes, &pm.in_start_window, &pm.recording_exits).await;
+            if !exited.is_empty() {
+                break;
             }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
         }
+        assert_eq!(exited.len(), 1);
+
+        // Reaped but not yet stored: the PID is untracked and gone, yet not stopped
+        let mut refreshed = read_before_exit.clone();
+        pm.refresh_status(&mut refreshed).await.unwrap();
+        assert_eq!(refreshed.status, ProcessStatus::Running);
 
-        // Create log file
-        if let Err(e) = tokio::fs::File::create(&log_path).await {
-            self.rollback_start_process(&id, &log_path, created_log_dir, created_log_file, inserted_db_record).await;
-            return Err(e.into());
+        let flapping = pm.config().flapping.clone();
+        ProcessManager::record_reaped_exits(pm.db.as_ref(), &pm.events, &flapping, pm.clock.as_ref(), &pm.recording_exits, exited).await;
+        assert!(pm.recording_exits.lock().unwrap().is_empty());
+
+        // A record read before the exit was stored picks up the stored result
+        let mut refreshed = read_before_exit;
+        pm.refresh_status(&mut refreshed).await.unwrap();
+        assert_eq!(refreshed.status, ProcessStatus::Succeeded);
+        assert_eq!(refreshed.exit_code, Some(0));
+        let runs = pm.process_runs(&named("backup"), RECENT_RUNS).await.unwrap();
+        assert_eq!(runs.len(), 1, "{:?}", runs);
+        assert_eq!(runs[0].status, ProcessStatus::Succeeded);
+        assert_eq!(runs[0].exit_code, Some(0));
+    }
+
+    #[tokio::test]
+    async fn test_each_job_run_is_recorded_with_its_duration() {
+        let root = tempfile::TempDir::new().unwrap();
+        let pm = ProcessManager::in_directory(root.path()).await.unwrap();
+        let job = StartOptions { kind: ProcessKind::Job, ..Default::default() };
+        let args = vec!["-c".to_string(), "sleep 0.2".to_string()];
+        pm.start_with_options(&named("backup"), "sh", args.clone(), HashMap::new(), job.clone()).await.unwrap();
+        wait_until_exited(&pm, "backup").await;
+        for _ in 0..2 {
+            pm.start_with_options(&named("backup"), "sh", args.clone(), HashMap::new(), StartOptions { rerun: true, ..job.clone() }).await.unwrap();
+            wait_until_exited(&pm, "backup").await;
         }
-        created_log_file = true;
 
-        // Use setsid to create a new session and detach from terminal
-        let mut cmd = tokio::process::Command::new("setsid");
-        cmd.arg(command)
-            .args(&args)
-            .current_dir(&working_dir)
-            .envs(&env_vars);
+        let runs = pm.process_runs(&named("backup"), RECENT_RUNS).await.unwrap();
+        assert_eq!(runs.len(), 3, "{:?}", runs);
+        for run in &runs {
+            assert_eq!(run.status, ProcessStatus::Succeeded, "{:?}", run);
+            assert!((150..10_000).contains(&run.duration_ms), "{:?}", run);
+        }
+        assert!(runs.windows(2).all(|pair| pair[0].ended_at <= pair[1].started_at), "{:?}", runs);
+        assert_eq!(pm.process_runs(&named("backup"), 2).await.unwrap(), runs[1..].to_vec());
+        let average = ProcessRun::average_duration_ms(&runs).unwrap();
+        assert!((150..10_000).contains(&average));
 
-        // Set up stdio - redirect to log file
-        let stdout_file = match std::fs::File::create(&log_path) {
-            Ok(file) => file,
-            Err(e) => {
-                self.rollback_start_process(&id, &log_path, created_log_dir, created_log_file, inserted_db_record).await;
-                return Err(e.into());
-            }
-        };
+        // Services get a run each time they stop
+        pm.start(&named("web"), "sleep", vec!["30".to_string()], HashMap::new(), None, None).await.unwrap();
+        pm.stop(&named("web")).await.unwrap();
+        let runs = pm.process_runs(&named("web"), RECENT_RUNS).await.unwrap();
+        assert_eq!(runs.len(), 1);
+        assert_eq!(runs[0].status, ProcessStatus::Stopped);
+        assert!(matches!(pm.process_runs(&named("missing"), RECENT_RUNS).await, Err(Error::ProcessNotFound(_))));
+    }
 
-        let stderr_file = match std::fs::File::options().create(true).append(true).open(&log_path) {
-            Ok(file) => file,
-            Err(e) => {
-                self.rollback_start_process(&id, &log_path, created_log_dir, created_log_file, inserted_db_record).await;
-                return Err(e.into());
-            }
-        };
+    #[tokio::test]
+    async fn test_clear_keeps_recent_succeeded_jobs() {
+        let root = tempfile::TempDir::new().unwrap();
+        for (retention, cleared) in [(std::time::Duration::from_secs(3600), 0), (std::time::Duration::ZERO, 1)] {
+            let dir = root.path().join(format!("retention-{}", retention.as_secs()));
+            let pm = ProcessManager::new(Config::in_directory(&dir).with_job_retention(retention)).await.unwrap();
+            let job = StartOptions { kind: ProcessKind::Job, ..Default::default() };
+            pm.start_with_options(&named("backup"), "true", vec![], HashMap::new(), job).await.unwrap();
+            wait_until_exited(&pm, "backup").await;
 
-        cmd.stdout(Stdio::from(stdout_file))
-            .stderr(Stdio::from(stderr_file))
-            .stdin(Stdio::null());
+            let result = pm.clear_processes(false).await.unwrap();
+            assert_eq!(result.cleared_count, cleared, "retention {:?}", retention);
+        }
+    }
 
-        // Start the process
-        let child = cmd.spawn();
+    #[tokio::test]
+    async fn test_read_rotated_logs_stops_at_limit() {
+        let root = tempfile::TempDir::new().unwrap();
+        let pm = ProcessManager::in_directory(root.path()).await.unwrap();
+        pm.start(&named("big"), "true", vec![], HashMap::new(), None, None).await.unwrap();
+        let logs = root.path().join("logs");
+        std::fs::write(logs.join("big.1.log"), "a".repeat(300_000)).unwrap();
+        std::fs::write(logs.join("big.2.log"), "é".repeat(150_000)).unwrap();
+        std::fs::write(logs.join("big.3.log.gz"), "not read").unwrap();
 
-        let (pid, initial_status) = match child {
-            Ok(child) => {
-                let pid = child.id().ok_or_else(|| {
-                    Error::Other("Failed to get process ID".to_string())
-                })?;
+        let listed = pm.rotated_logs(&named("big")).await.unwrap();
+        let sizes: Vec<(usize, u64, bool)> = listed.iter().map(|log| (log.index, log.size_bytes, log.compressed)).collect();
+        assert_eq!(sizes, vec![(1, 300_000, false), (2, 300_000, false), (3, 8, true)]);
 
-                // Store the child process for proper reaping
-                {
-                    let mut processes = self.running_processes.lock().await;
-                    processes.insert(pid, child);
-                }
+        let header = |i: usize| format!("=== {} ===\n", logs.join(format!("big.{}.log", i)).display());
+        let whole = pm.read_rotated_logs(&named("big"), usize::MAX).await.unwrap();
+        assert!(!whole.truncated);
+        assert_eq!(whole.total_bytes, 600_000);
+        assert_eq!(whole.content, format!("{}{}\n{}{}", header(1), "a".repeat(300_000), header(2), "é".repeat(150_000)));
+        assert_eq!(whole.bytes, whole.content.len() as u64);
 
-                // Wait a moment to check if the process actually started successfully
-                tokio::time::sleep(tokio::time::Duration::from_millis(200)).await;
+        let cut = pm.read_rotated_logs(&named("big"), 100_000).await.unwrap();
+        assert!(cut.truncated);
+        assert_eq!(cut.bytes, 100_000);
+        assert_eq!(cut.content.len(), 100_000);
+        assert_eq!(cut.total_bytes, 600_000);
 
-                // Check if the process is still running
-                let status = if self.is_process_running(pid).await {
-                    ProcessStatus::Running
-                } else {
-                    // Process started but exited quickly - this could be either
-                    // a failed command or a command that completed successfully
-                    // We'll mark it as stopped for now, and let the user check logs
-                    ProcessStatus::Stopped
-                };
+        // A cut inside a two-byte character drops the half character
+        let odd = header(1).len() + 300_000 + 1 + header(2).len() + 5;
+        let cut = pm.read_rotated_logs(&named("big"), odd).await.unwrap();
+        assert!(cut.truncated);
+        assert_eq!(cut.bytes as usize, odd - 1);
+        assert!(cut.content.ends_with("éé"));
+    }
 
-                (Some(pid), status)
-            }
-            Err(e) => {
-                // Process failed to start at all - perform rollback
-                self.rollback_start_process(&id, &log_path, created_log_dir, created_log_file, inserted_db_record).await;
-                return Err(Error::Other(format!("Failed to start process '{}': {}", name, e)));
-            }
-        };
+    #[tokio::test]
+    async fn test_invalid_utf8_log_is_read_once() {
+        let root = tempfile::TempDir::new().unwrap();
+        let pm = ProcessManager::in_directory(root.path()).await.unwrap();
+        pm.start(&named("binary"), "true", vec![], HashMap::new(), None, None).await.unwrap();
+        let log_path = root.path().join("logs").join("binary.log");
 
-        // Create process record
-        let process_record = ProcessRecord {
-            id: id.clone(),
-            name: name.to_string(),
-            command: command.to_string(),
-            args,
-            env_vars,
-            working_dir,
-            pid,
-            status: initial_status.clone(),
-            created_at: Utc::now(),
-            updated_at: Utc::now(),
-            log_path: log_path.to_string_lossy().to_string(),
+        // A FIFO gives its content to one reader only; a second read would wait for a writer
+        std::fs::remove_file(&log_path).unwrap();
+        let fifo = std::ffi::CString::new(log_path.to_string_lossy().as_bytes()).unwrap();
+        assert_eq!(unsafe { libc::mkfifo(fifo.as_ptr(), 0o600) }, 0);
+        let writer = {
+            let log_path = log_path.clone();
+            std::thread::spawn(move || std::fs::write(log_path, b"ok\n\xff\xfe bad\nok\n").unwrap())
         };
+        let logs = tokio::time::timeout(std::time::Duration::from_secs(5), pm.read_process_logs(&named("binary"), &LogOptions::default()))
+            .await
+            .expect("the log was read more than once")
+            .unwrap();
+        writer.join().unwrap();
+        assert_eq!(logs.content, "ok\n\u{FFFD}\u{FFFD} bad\nok\n");
+        assert!(logs.contains_invalid_utf8);
+
+        // Tails read only the end, and only the lines kept decide the flag
+        std::fs::remove_file(&log_path).unwrap();
+        std::fs::write(&log_path, b"\xff\nok\nfine\n").unwrap();
+        let tail = |lines| LogOptions { lines: Some(lines), ..Default::default() };
+        let logs = pm.read_process_logs(&named("binary"), &tail(2)).await.unwrap();
+        assert_eq!(logs, ProcessLogs { content: "ok\nfine".to_string(), contains_invalid_utf8: false, start_offset: None });
+        assert!(pm.read_process_logs(&named("binary"), &tail(3)).await.unwrap().contains_invalid_utf8);
+        assert!(pm.read_merged_process_logs(&named("binary"), &tail(3)).await.unwrap().contains_invalid_utf8);
+        let collapsed = LogOptions { lines: Some(1), collapse_repeats: true, ..Default::default() };
+        assert!(pm.read_process_logs(&named("binary"), &collapsed).await.unwrap().contains_invalid_utf8);
+    }
+
+    #[tokio::test]
+    async fn test_unreadable_log_errors_keep_their_cause() {
+        let root = tempfile::TempDir::new().unwrap();
+        let pm = ProcessManager::in_directory(root.path()).await.unwrap();
+        pm.start(&named("gone"), "true", vec![], HashMap::new(), None, None).await.unwrap();
+        let log_path = root.path().join("logs").join("gone.log");
 
-        // Insert process record - if this fails, we need to rollback
-        if let Err(e) = self.db.insert_process(&process_record).await {
-            self.rollback_start_process(&id, &log_path, created_log_dir, created_log_file, inserted_db_record).await;
-            return Err(e);
+        std::fs::remove_file(&log_path).unwrap();
+        for lines in [None, Some(5)] {
+            let error = pm.read_process_logs(&named("gone"), &LogOptions { lines, ..Default::default() }).await.unwrap_err();
+            assert!(matches!(&error, Error::LogUnreadable(_, e) if e.kind() == std::io::ErrorKind::NotFound), "{:?}", error);
+            assert_eq!(error.to_string(), format!("Log file '{}' does not exist; it was removed or the process never started", log_path.display()));
         }
 
-        let message = match initial_status {
-            ProcessStatus::Running => {
-                if let Some(pid) = pid {
-                    format!("Process '{}' started with PID {}", name, pid)
-                } else {
-                    format!("Process '{}' started", name)
-                }
-            }
-            ProcessStatus::Stopped => {
-                if let Some(pid) = pid {
-                    format!("Process '{}' started with PID {} but exited quickly", name, pid)
-                } else {
-                    format!("Process '{}' started but exited quickly", name)
-                }
-            }
-            ProcessStatus::Failed => {
-                // This case should not happen anymore since we rollback on spawn failure
-                format!("Process '{}' failed to start", name)
-            }
-            _ => format!("Process '{}' started with unknown status", name),
+        // Root reads files whatever their mode, so permissions only bite other users
+        std::fs::write(&log_path, "secret\n").unwrap();
+        std::fs::set_permissions(&log_path, std::os::unix::fs::PermissionsExt::from_mode(0o000)).unwrap();
+        if unsafe { libc::geteuid() } != 0 {
+            let error = pm.read_process_logs(&named("gone"), &LogOptions::default()).await.unwrap_err();
+            assert_eq!(error.to_string(), format!("Permission denied reading log file '{}'", log_path.display()));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_since_reads_seek_with_the_log_index_and_keep_it_current() {
+        let root = tempfile::TempDir::new().unwrap();
+        let pm = ProcessManager::in_directory(root.path()).await.unwrap();
+        pm.start(&named("web"), "true", vec![], HashMap::new(), None, None).await.unwrap();
+        let log_path = root.path().join("logs").join("web.log");
+        let idx_path = index_path(&log_path);
+        let line = |secs: i64| format!("{} request {}\n", chrono::DateTime::from_timestamp(secs, 0).unwrap().to_rfc3339(), secs);
+        std::fs::write(&log_path, (0..2_000).map(line).collect::<String>()).unwrap();
+        let since = |secs: i64| LogOptions {
+            since: Some(LogSince::At(chrono::DateTime::from_timestamp(secs, 0).unwrap())),
+            ..Default::default()
         };
 
-        Ok(message)
+        // Without an index the log is scanned, with one it is sought, and the lines match
+        let scanned = pm.read_process_logs(&named("web"), &since(1_998)).await.unwrap();
+        assert_eq!(scanned.content, format!("{}{}", line(1_998), line(1_999)));
+        assert!(!idx_path.exists());
+        let message = pm.reindex_process_logs(&named("web")).await.unwrap();
+        assert_eq!(message, "Log index rebuilt for process 'web': 2000 lines, 8 entries");
+        assert_eq!(pm.read_process_logs(&named("web"), &since(1_998)).await.unwrap(), scanned);
+        let tail = LogOptions { lines: Some(1), ..since(1_000) };
+        assert_eq!(pm.read_process_logs(&named("web"), &tail).await.unwrap().content, line(1_999).trim_end());
+
+        // Lines written since are indexed by the next read
+        std::io::Write::write_all(&mut std::fs::OpenOptions::new().append(true).open(&log_path).unwrap(), (2_000..2_300).map(line).collect::<String>().as_bytes()).unwrap();
+        assert_eq!(pm.read_process_logs(&named("web"), &since(2_299)).await.unwrap().content, line(2_299));
+        assert_eq!(LogIndex::load(&log_path).unwrap().lines(), 2_300);
+
+        // A corrupt index is passed over; rotation starts it over for the new log
+        std::fs::write(&idx_path, "corrupt").unwrap();
+        assert_eq!(pm.read_process_logs(&named("web"), &since(2_299)).await.unwrap().content, line(2_299));
+        pm.reindex_process_logs(&named("web")).await.unwrap();
+        pm.rotate_process_logs(&named("web")).await.unwrap();
+        assert_eq!(LogIndex::load(&log_path), Some(LogIndex::empty(DEFAULT_STRIDE)));
+
+        let error = pm.read_process_logs(&named("web"), &LogOptions { tail_bytes: Some(10), ..since(0) }).await.unwrap_err();
+        assert!(matches!(error, Error::Other(_)), "{:?}", error);
+        assert!(pm.read_merged_process_logs(&named("web"), &since(0)).await.is_err());
+        let read_only = ProcessManager::read_only(Config::in_directory(root.path())).await.unwrap();
+        assert!(matches!(read_only.reindex_process_logs(&named("web")).await, Err(Error::ReadOnly(_))));
+
+        pm.delete_with_options(&named("web"), DeleteOptions { hard: true }).await.unwrap();
+        assert!(!idx_path.exists());
     }
 
-    /// Rollback resources created during a failed start_process operation
-    async fn rollback_start_process(
-        &self,
-        process_id: &str,
-        log_path: &PathBuf,
-        created_log_dir: bool,
-        created_log_file: bool,
-        inserted_db_record: bool,
-    ) {
-        // Remove database record if it was inserted
-        if inserted_db_record {
-            if let Err(e) = self.db.delete_process_by_id(process_id).await {
-                eprintln!("Warning: Failed to rollback database record for process ID {}: {}", process_id, e);
-            }
-        }
+    #[tokio::test]
+    async fn test_in_directory_managers_are_isolated() {
+        let root = tempfile::TempDir::new().unwrap();
+        let first = ProcessManager::in_directory(root.path().join("first")).await.unwrap();
+        let second = ProcessManager::in_directory(root.path().join("second")).await.unwrap();
 
-        // Remove log file if it was created
-        if created_log_file && log_path.exists() {
-            if let Err(e) = tokio::fs::remove_file(log_path).await {
-                eprintln!("Warning: Failed to rollback log file {}: {}", log_path.display(), e);
-            }
+        first.start(&named("only_here"), "true", vec![], HashMap::new(), None, None).await.unwrap();
+        assert_eq!(first.list_processes().await.unwrap().len(), 1);
+        assert!(second.list_processes().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_reaper_stops_when_manager_is_dropped() {
+        let root = tempfile::TempDir::new().unwrap();
+        let mut ticks = Vec::new();
+        for i in 0..20 {
+            let config = Config::in_directory(root.path().join(i.to_string()))
+                .with_reaper_interval(std::time::Duration::from_millis(5));
+            let pm = ProcessManager::new(config).await.unwrap();
+            tokio::time::sleep(std::time::Duration::from_millis(12)).await;
+            ticks.push(pm.reaper_ticks.clone());
         }
+        assert!(ticks.iter().all(|count| count.load(std::sync::atomic::Ordering::SeqCst) > 0));
 
-        // Remove log directory if it was created and is now empty
-        if created_log_dir {
-            if let Some(log_dir) = log_path.parent() {
-                // Only remove if directory is empty
-                if let Ok(mut entries) = tokio::fs::read_dir(log_dir).await {
-                    let mut is_empty = true;
-                    if let Ok(Some(_)) = entries.next_entry().await {
-                        is_empty = false;
-                    }
+        let before: Vec<usize> = ticks.iter().map(|count| count.load(std::sync::atomic::Ordering::SeqCst)).collect();
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        let after: Vec<usize> = ticks.iter().map(|count| count.load(std::sync::atomic::Ordering::SeqCst)).collect();
+        assert_eq!(before, after);
+    }
 
-                    if is_empty {
-                        if let Err(e) = tokio::fs::remove_dir(log_dir).await {
-                            eprintln!("Warning: Failed to rollback log directory {}: {}", log_dir.display(), e);
-                        }
-                    }
-                }
-            }
-        }
+    #[tokio::test]
+    async fn test_shutdown_stops_reaper() {
+        let root = tempfile::TempDir::new().unwrap();
+        let config = Config::in_directory(root.path()).with_reaper_interval(std::time::Duration::from_millis(5));
+        let pm = ProcessManager::new(config).await.unwrap();
+
+        pm.shutdown().await;
+        pm.shutdown().await;
+        let stopped_at = pm.reaper_ticks.load(std::sync::atomic::Ordering::SeqCst);
+        tokio::time::sleep(std::time::Duration::from_millis(30)).await;
+        assert_eq!(pm.reaper_ticks.load(std::sync::atomic::Ordering::SeqCst), stopped_at);
     }
 
-    pub async fn stop_process(&self, name: &str) -> Result<String> {
-        let process = self.db.get_process_by_name(name).await?
-            .ok_or_else(|| Error::ProcessNotFound(name.to_string()))?;
+    #[test]
+    fn test_missing_directories() {
+        let root = tempfile::TempDir::new().unwrap();
+        let dir = root.path().join("a/b/c");
+        assert_eq!(
+            missing_directories(&dir),
+            vec![dir.clone(), root.path().join("a/b"), root.path().join("a")]
+        );
+        assert!(missing_directories(root.path()).is_empty());
+    }
 
-        if let Some(pid) = process.pid {
-            // First try to get the child process from our tracking
-            let mut child_opt = {
-                let mut processes = self.running_processes.lock().await;
-                processes.remove(&pid)
-            };
+    /// Manager on a manual clock whose PIDs only exist in the returned probe
+    async fn fake_runtime_manager(config: Config) -> (ProcessManager, Arc<crate::clock::ManualClock>, Arc<crate::pid_probe::FakePidProbe>) {
+        let clock = Arc::new(crate::clock::ManualClock::starting_now());
+        let probe = Arc::new(crate::pid_probe::FakePidProbe::new(clock.clone()));
+        let pm = ProcessManager::builder(config)
+            .clock(clock.clone())
+            .pid_probe(probe.clone())
+            .build()
+            .await
+            .unwrap();
+        (pm, clock, probe)
+    }
 
-            if let Some(ref mut child) = child_opt {
-                // We have the child process, use tokio's kill method
-                match child.kill().await {
-                    Ok(_) => {
-                        // Wait for the process to actually terminate
-                        let _ = child.wait().await;
-                        self.db.update_process_status(name, ProcessStatus::Stopped, Some(pid)).await?;
-                        Ok(format!("Process '{}' stopped", name))
-                    }
-                    Err(e) => {
-                        // Re-insert the child back if kill failed
-                        let mut processes = self.running_processes.lock().await;
-                        processes.insert(pid, child_opt.unwrap());
-                        Err(Error::Other(format!("Failed to stop process '{}' with PID {}: {}", name, pid, e)))
-                    }
-                }
-            } else {
-                // Fallback to using libc::kill for processes not in our tracking
-                let result = unsafe { libc::kill(pid as i32, libc::SIGTERM) };
-                if result == 0 {
-                    // Wait a bit for the process to terminate
-                    tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
-                    self.db.update_process_status(name, ProcessStatus::Stopped, Some(pid)).await?;
-                    Ok(format!("Process '{}' stopped", name))
-                } else {
-                    Err(Error::Other(format!("Failed to stop process '{}' with PID {}", name, pid)))
-                }
-            }
-        } else {
-            Err(Error::InvalidProcessState(format!("Process '{}' has no PID", name)))
+    /// Running record for a PID the manager did not spawn
+    async fn insert_running(pm: &ProcessManager, name: &str, pid: u32) {
+        pm.db.insert_process(&running_record(pm, name, pid)).await.unwrap();
+    }
+
+    fn running_record(pm: &ProcessManager, name: &str, pid: u32) -> ProcessRecord {
+        let now = pm.clock.now();
+        ProcessRecord {
+            id: Uuid::new_v4().to_string(),
+            name: name.parse().unwrap(),
+            command: "sleep".to_string(),
+            args: vec!["30".to_string()],
+            env_vars: HashMap::new(),
+            working_dir: "/".to_string(),
+            pid: Some(pid),
+            status: ProcessStatus::Running,
+            created_at: now,
+            updated_at: now,
+            log_path: pm.config().default_log_dir.join(format!("{}.log", name)).to_string_lossy().to_string(),
+            log_dir: None,
+            cgroup_memory_max: None,
+            cgroup_cpu_max: None,
+            cgroup: None,
+            deleted_at: None,
+            binary_path: None,
+            binary_sha256: None,
+            canonical_workdir: None,
+            cpu_affinity: None,
+            umask: None,
+            description: None,
+            exit_code: None,
+            exit_reason: None,
+            start_snapshot: None,
+            default_env_keys: Vec::new(),
+            drift: None,
+            notes: Vec::new(),
+            recent_runs: Vec::new(),
+            flapping: false,
+            degraded: false,
+            inferred: false,
+            truncated_fields: Vec::new(),
+            kind: ProcessKind::Service,
+            runs: 1,
+            stop_signal: libc::SIGTERM,
+            log_dir_mode: LogDirMode::Default,
+            status_verified_at: None,
+            internal: false,
+            ephemeral_keep_secs: None,
+            delete_after: None,
+            last_output: Vec::new(),
+            last_output_lossy: false,
+            watch: None,
+            rotate_on_start: None,
         }
     }
 
-    pub async fn restart_process(&self, name: &str) -> Result<String> {
-        let process = self.db.get_process_by_name(name).await?
-            .ok_or_else(|| Error::ProcessNotFound(name.to_string()))?;
+    #[tokio::test]
+    async fn test_start_window_classification_without_sleeping() {
+        let root = tempfile::TempDir::new().unwrap();
+        let window = std::time::Duration::from_secs(30);
+        let config = Config::in_directory(root.path()).with_start_check_window(window);
+        let (pm, clock, probe) = fake_runtime_manager(config).await;
 
-        // Stop the process if it's running
-        if process.pid.is_some() && self.is_process_running(process.pid.unwrap()).await {
-            self.stop_process(name).await?;
-            // Wait a bit for the process to stop
-            tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
-        }
+        // A PID that vanishes inside the window without a child to wait for has no known
+        // exit code; it is not reported as an early exit, which would read as a failure
+        probe.add(100);
+        probe.exit_after(100, std::time::Duration::from_millis(2500));
+        let started = clock.elapsed();
+        assert!(pm.watch_start_window(100).await.is_none());
+        assert_eq!(clock.elapsed() - started, std::time::Duration::from_millis(2500));
 
-        // Extract log directory from the existing log path
-        let log_dir = PathBuf::from(&process.log_path)
-            .parent()
-            .map(|p| p.to_string_lossy().to_string());
+        // Surviving the window, or exiting only after it, is a successful start
+        probe.add(200);
+        let started = clock.elapsed();
+        assert!(pm.watch_start_window(200).await.is_none());
+        assert_eq!(clock.elapsed() - started, window);
+        probe.add(300);
+        probe.exit_after(300, window + std::time::Duration::from_millis(10));
+        assert!(pm.watch_start_window(300).await.is_none());
+    }
 
-        // Delete the process record
-        self.db.delete_process(name).await?;
+    #[tokio::test]
+    async fn test_flapping_enters_and_clears_without_sleeping() {
+        let root = tempfile::TempDir::new().unwrap();
+        let flapping = FlappingConfig { failures: 2, window_secs: 60 };
+        let config = Config::in_directory(root.path()).with_flapping(flapping.clone());
+        let (pm, clock, probe) = fake_runtime_manager(config).await;
+        probe.add(100);
+        insert_running(&pm, "crashy", 100).await;
+        let mut events = pm.subscribe();
 
-        // Start the process again
-        let start_message = self.start_process(
-            name,
-            &process.command,
-            process.args,
-            process.env_vars,
-            Some(process.working_dir),
-            log_dir,
-        ).await?;
+        let crashy = named("crashy");
+        let fail = |at| ProcessManager::record_failure(pm.db.as_ref(), &pm.events, &flapping, &crashy, ProcessStatus::Stopped, Some(1), at);
+        fail(clock.now()).await;
+        assert!(!pm.get_process_status(&named("crashy")).await.unwrap().flapping);
+        clock.advance(std::time::Duration::from_secs(30));
+        fail(clock.now()).await;
+        assert_eq!(
+            events.recv().await.unwrap().kind,
+            ProcessEventKind::FlappingEntered { status: ProcessStatus::Stopped, failures: 2, window_secs: 60 }
+        );
+        assert!(pm.list_processes().await.unwrap()[0].flapping);
+        assert!(matches!(
+            pm.restart_with_options(&named("crashy"), RestartOptions::default()).await,
+            Err(Error::Flapping(_, 2, 60))
+        ));
+        let doctor = pm.flapping_processes().await.unwrap();
+        assert_eq!(doctor.len(), 1);
+        assert_eq!(doctor[0].recent_failures, 2);
 
-        Ok(format!("Process '{}' restarted. {}", name, start_message))
+        // The first failure leaves the window exactly 60s after it happened
+        clock.advance(std::time::Duration::from_secs(30));
+        assert!(!pm.list_processes().await.unwrap()[0].flapping);
+        assert_eq!(events.recv().await.unwrap().kind, ProcessEventKind::FlappingCleared { status: ProcessStatus::Running });
+        assert!(pm.flapping_processes().await.unwrap().is_empty());
     }
 
-    pub async fn delete_process(&self, name: &str) -> Result<String> {
-        let process = self.db.get_process_by_name(name).await?
-            .ok_or_else(|| Error::ProcessNotFound(name.to_string()))?;
+    #[tokio::test]
+    async fn test_stop_escalation_timing_without_sleeping() {
+        let root = tempfile::TempDir::new().unwrap();
+        let grace = std::time::Duration::from_secs(10);
+        let config = Config::in_directory(root.path()).with_stop_grace_period(grace);
+        let (pm, clock, probe) = fake_runtime_manager(config).await;
 
-        // Stop the process if it's running
-        if let Some(pid) = process.pid {
-            if self.is_process_running(pid).await {
-                self.stop_process(name).await?;
-            } else {
-                // Process is not running, but remove it from tracking if present
-                let mut processes = self.running_processes.lock().await;
-                processes.remove(&pid);
-            }
-        }
+        probe.add(4242);
+        probe.ignore_signal(4242, libc::SIGTERM);
+        insert_running(&pm, "stubborn", 4242).await;
 
-        // Delete from database
-        if self.db.delete_process(name).await? {
-            // Optionally remove log file
-            let _ = tokio::fs::remove_file(&process.log_path).await;
-            Ok(format!("Process '{}' deleted", name))
-        } else {
-            Err(Error::ProcessNotFound(name.to_string()))
-        }
+        // Without escalation the stop gives up after exactly one grace period
+        let started = clock.elapsed();
+        let error = pm.stop(&named("stubborn")).await.unwrap_err();
+        assert!(matches!(error, Error::StopTimedOut(_, 4242)), "{}", error);
+        assert_eq!(clock.elapsed() - started, grace);
+
+        let started = clock.elapsed();
+        let options = StopOptions { escalate: true, ..Default::default() };
+        pm.stop_with_options(&named("stubborn"), options).await.unwrap();
+        let sent: Vec<(i32, std::time::Duration)> = probe
+            .sent_signals()
+            .into_iter()
+            .skip(1)
+            .map(|sent| (sent.signal, sent.at - started))
+            .collect();
+        assert_eq!(sent, vec![(libc::SIGTERM, std::time::Duration::ZERO), (libc::SIGKILL, grace)]);
+        assert_eq!(pm.db.get_process_by_name(&named("stubborn")).await.unwrap().unwrap().status, ProcessStatus::Stopped);
     }
 
-    pub async fn list_processes(&self) -> Result<Vec<ProcessRecord>> {
-        let mut processes = self.db.get_all_processes().await?;
+    #[test]
+    fn test_clear_result_lists_each_name_once() {
+        let mut result = ClearResult::new("soft-deleted processes");
+        // Two deleted records named 'web': one goes, the other cannot be removed
+        result.record_cleared("web".to_string());
+        result.record_skipped("zeta".to_string());
+        result.record_failed("web".to_string(), "disk I/O error".to_string());
+        result.record_cleared("api".to_string());
+        result.record_failed("web".to_string(), "later error".to_string());
+        result.record_skipped("api".to_string());
+        result.record_skipped("batch".to_string());
 
-        // Update status for each process
-        for process in &mut processes {
-            if let Some(pid) = process.pid {
-                let is_running = self.is_process_running(pid).await;
-                let new_status = match process.status {
-                    ProcessStatus::Failed => ProcessStatus::Failed, // Keep failed status
-                    _ => {
-                        if is_running {
-                            ProcessStatus::Running
-                        } else {
-                            ProcessStatus::Stopped
-                        }
-                    }
-                };
+        let result = result.finish();
+        assert_eq!(result.cleared_processes, ["api"]);
+        assert_eq!(result.cleared_count, 1);
+        assert_eq!(result.failed_processes, ["web"]);
+        assert_eq!(result.details, BTreeMap::from([("web".to_string(), "disk I/O error".to_string())]));
+        assert_eq!(result.skipped_processes, ["batch", "zeta"]);
+        assert!(result.cancelled);
+    }
 
-                if new_status != process.status {
-                    self.db.update_process_status(&process.name, new_status.clone(), Some(pid)).await?;
-                    process.status = new_status;
-                }
-            } else {
-                // No PID means the process failed to start
-                if process.status != ProcessStatus::Failed {
-                    self.db.update_process_status(&process.name, ProcessStatus::Failed, None).await?;
-                    process.status = ProcessStatus::Failed;
-                }
-            }
+    #[tokio::test]
+    async fn test_zombies_are_stopped_and_listed() {
+        let root = tempfile::TempDir::new().unwrap();
+        let (pm, _clock, probe) = fake_runtime_manager(Config::in_directory(root.path())).await;
+        probe.add(50);
+        for (name, pid) in [("orphaned", 10), ("unreaped", 11), ("healthy", 12)] {
+            probe.add(pid);
+            insert_running(&pm, name, pid).await;
         }
+        probe.make_zombie(10, 1);
+        probe.make_zombie(11, 50);
 
-        Ok(processes)
+        let status = pm.get_process_status(&named("unreaped")).await.unwrap();
+        assert_eq!(status.status, ProcessStatus::Stopped);
+        let record = pm.db.get_process_by_name(&named("unreaped")).await.unwrap().unwrap();
+        assert_eq!((record.exit_code, record.exit_reason.as_deref()), (None, Some(ZOMBIE_EXIT_REASON)));
+
+        // The parent of an actionable zombie is alive; the other will be reaped by init
+        let zombies = pm.zombie_processes().await.unwrap();
+        assert_eq!(
+            zombies,
+            vec![
+                ZombieProcess { name: "orphaned".to_string(), pid: 10, parent_pid: 1, reparented: true },
+                ZombieProcess { name: "unreaped".to_string(), pid: 11, parent_pid: 50, reparented: false },
+            ]
+        );
+        let record = pm.db.get_process_by_name(&named("orphaned")).await.unwrap().unwrap();
+        assert_eq!((record.status, record.exit_reason), (ProcessStatus::Stopped, None));
+        assert_eq!(pm.get_process_status(&named("healthy")).await.unwrap().status, ProcessStatus::Running);
     }
 
-    pub async fn clear_processes(&self, all: bool) -> Result<ClearResult> {
-        let processes_to_clear = if all {
-            // Get all processes
-            self.db.get_all_processes().await?
-        } else {
-            // Get only stopped and failed processes
-            self.db.get_processes_by_status(&[ProcessStatus::Stopped, ProcessStatus::Failed]).await?
-        };
+    #[tokio::test]
+    async fn test_other_users_processes_stay_running_or_are_inferred_from_their_logs() {
+        let root = tempfile::TempDir::new().unwrap();
+        let (pm, clock, probe) = fake_runtime_manager(Config::in_directory(root.path())).await;
+        for (name, pid) in [("visible", 20), ("hidden", 21), ("quiet", 22)] {
+            probe.add(pid);
+            insert_running(&pm, name, pid).await;
+        }
+        // Signalling all three fails with EPERM; only the first can be looked up in /proc
+        probe.make_foreign(20, false);
+        probe.make_foreign(21, true);
+        probe.make_foreign(22, true);
+        std::fs::create_dir_all(&pm.config().default_log_dir).unwrap();
+        std::fs::write(pm.config().default_log_dir.join("hidden.log"), "serving\n").unwrap();
 
-        let mut cleared_processes = Vec::new();
-        let mut failed_processes = Vec::new();
+        let visible = pm.get_process_status(&named("visible")).await.unwrap();
+        assert_eq!((visible.status, visible.inferred), (ProcessStatus::Running, false));
+        let hidden = pm.get_process_status(&named("hidden")).await.unwrap();
+        assert_eq!((hidden.status, hidden.inferred), (ProcessStatus::Running, true));
+        // Nothing in /proc and no log: inconclusive, so stopped
+        let quiet = pm.get_process_status(&named("quiet")).await.unwrap();
+        assert_eq!((quiet.status, quiet.inferred), (ProcessStatus::Stopped, false));
 
-        for process in processes_to_clear {
-            match self.delete_single_process(&process).await {
-                Ok(_) => cleared_processes.push(process.name),
-                Err(_) => failed_processes.push(process.name),
-            }
-        }
+        // A log not written within the window no longer counts as a sign of life
+        clock.advance(pm.config().inferred_liveness_window + std::time::Duration::from_secs(1));
+        let hidden = pm.get_process_status(&named("hidden")).await.unwrap();
+        assert_eq!((hidden.status, hidden.inferred), (ProcessStatus::Stopped, false));
+        assert_eq!(pm.get_process_status(&named("visible")).await.unwrap().status, ProcessStatus::Running);
+    }
 
-        let operation_type = if all {
-            "all processes".to_string()
-        } else {
-            "stopped/failed processes".to_string()
-        };
+    #[tokio::test]
+    async fn test_retention_sweep_without_sleeping() {
+        let root = tempfile::TempDir::new().unwrap();
+        let retention = std::time::Duration::from_secs(7 * 24 * 3600);
+        let config = Config::in_directory(root.path()).with_deleted_retention(retention);
+        let (pm, clock, probe) = fake_runtime_manager(config).await;
 
-        Ok(ClearResult {
-            cleared_count: cleared_processes.len(),
-            cleared_processes,
-            failed_processes,
-            operation_type,
-        })
+        probe.add(7);
+        insert_running(&pm, "old", 7).await;
+        probe.exit_after(7, std::time::Duration::ZERO);
+        pm.delete(&named("old")).await.unwrap();
+
+        clock.advance(retention - std::time::Duration::from_secs(1));
+        assert_eq!(pm.purge_deleted_processes().await.unwrap().cleared_count, 0);
+        clock.advance(std::time::Duration::from_secs(2));
+        let result = pm.purge_deleted_processes().await.unwrap();
+        assert_eq!(result.cleared_processes, vec!["old".to_string()]);
+        assert!(pm.db.get_deleted_process_by_name(&named("old")).await.unwrap().is_none());
     }
 
-    async fn delete_single_process(&self, process: &ProcessRecord) -> Result<()> {
-        // Stop the process if it's running
-        if let Some(pid) = process.pid {
-            if self.is_process_running(pid).await {
-                // Try to stop the process properly
-                if let Err(_) = self.stop_process(&process.name).await {
-                    // If proper stop fails, try direct kill
-                    let result = unsafe { libc::kill(pid as i32, libc::SIGTERM) };
-                    if result == 0 {
-                        // Wait a bit for termination
-                        tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
-                    }
-                }
-            } else {
-                // Process is not running, but remove it from tracking if present
-                let mut processes = self.running_processes.lock().await;
-                processes.remove(&pid);
-            }
-        }
+    #[tokio::test]
+    async fn test_name_reservation_expires() {
+        let root = tempfile::TempDir::new().unwrap();
+        let (pm, clock, _probe) = fake_runtime_manager(Config::in_directory(root.path())).await;
+        let ttl = std::time::Duration::from_secs(60);
 
-        // Delete from database
-        if !self.db.delete_process(&process.name).await? {
-            return Err(Error::ProcessNotFound(process.name.clone()));
-        }
+        let reservation = pm.reserve_name(&named("slot"), "pmr_aaaaaaaa", ttl).await.unwrap();
+        assert_eq!(reservation.expires_at, clock.now() + chrono::Duration::seconds(60));
+        assert!(pm.check_name(&named("slot")).await.unwrap().taken());
+        assert!(matches!(
+            pm.reserve_name(&named("slot"), "pmr_bbbbbbbb", ttl).await,
+            Err(Error::NameReserved(_, ref token)) if token == "pmr_aaaaaaaa"
+        ));
+        let refused = pm.start(&named("slot"), "true", vec![], HashMap::new(), None, None).await.unwrap_err();
+        assert_eq!(refused.to_string(), "Process name 'slot' is reserved by token pmr_aaaaaaaa");
 
-        // Remove log file
-        let _ = tokio::fs::remove_file(&process.log_path).await;
+        // Once it expires the name is free, even before the sweeper has dropped it
+        clock.advance(ttl);
+        assert!(!pm.check_name(&named("slot")).await.unwrap().taken());
+        pm.reserve_name(&named("slot"), "pmr_bbbbbbbb", ttl).await.unwrap();
+        pm.reserve_name(&named("other"), "pmr_aaaaaaaa", ttl).await.unwrap();
+        clock.advance(ttl);
+        assert_eq!(pm.purge_expired_reservations().await.unwrap(), 2);
+        assert_eq!(pm.purge_expired_reservations().await.unwrap(), 0);
 
-        Ok(())
+        insert_running(&pm, "taken", 7).await;
+        assert!(matches!(pm.reserve_name(&named("taken"), "pmr_aaaaaaaa", ttl).await, Err(Error::ProcessAlreadyExists(_))));
+        assert!(matches!(pm.reserve_name(&named("slot"), "pmr_aaaaaaaa", std::time::Duration::ZERO).await, Err(Error::InvalidDuration(_))));
     }
 
-    pub async fn get_process_status(&self, name: &str) -> Result<ProcessRecord> {
-        let mut process = self.db.get_process_by_name(name).await?
-            .ok_or_else(|| Error::ProcessNotFound(name.to_string()))?;
+    #[tokio::test]
+    async fn test_stats_age_failed_and_stopped_processes_from_their_last_transition() {
+        let root = tempfile::TempDir::new().unwrap();
+        let (pm, clock, probe) = fake_runtime_manager(Config::in_directory(root.path())).await;
+        let hours = |n: i64| chrono::Duration::hours(n);
 
-        // Update status
-        if let Some(pid) = process.pid {
-            let is_running = self.is_process_running(pid).await;
-            let new_status = match process.status {
-                ProcessStatus::Failed => ProcessStatus::Failed, // Keep failed status
-                _ => {
-                    if is_running {
-                        ProcessStatus::Running
-                    } else {
-                        ProcessStatus::Stopped
-                    }
-                }
-            };
+        let gauges = pm.status_gauges().await.unwrap();
+        assert_eq!((gauges.oldest_failed_age_seconds, gauges.oldest_stopped_age_seconds), (None, None));
 
-            if new_status != process.status {
-                self.db.update_process_status(name, new_status.clone(), Some(pid)).await?;
-                process.status = new_status;
-            }
-        } else {
-            // No PID means the process failed to start
-            if process.status != ProcessStatus::Failed {
-                self.db.update_process_status(name, ProcessStatus::Failed, None).await?;
-                process.status = ProcessStatus::Failed;
-            }
-        }
+        let failed = ProcessRecord { status: ProcessStatus::Failed, pid: None, updated_at: clock.now() - hours(2), ..running_record(&pm, "crashed", 5) };
+        pm.db.insert_process(&failed).await.unwrap();
+        let stopped = ProcessRecord { status: ProcessStatus::Stopped, pid: None, updated_at: clock.now() - hours(1), ..running_record(&pm, "done", 6) };
+        pm.db.insert_process(&stopped).await.unwrap();
+        probe.add(7);
+        insert_running(&pm, "web", 7).await;
 
-        Ok(process)
+        // The refresh that finds web gone stamps the manager's time, not the wall clock's
+        clock.advance(std::time::Duration::from_secs(600));
+        probe.exit_after(7, std::time::Duration::ZERO);
+        pm.get_process_status(&named("web")).await.unwrap();
+        assert_eq!(pm.db.get_process_by_name(&named("web")).await.unwrap().unwrap().updated_at, clock.now());
+
+        clock.advance(std::time::Duration::from_secs(30));
+        let stats = pm.stats().await.unwrap();
+        assert_eq!((stats.counts.failed, stats.counts.stopped, stats.counts.running), (1, 2, 0));
+        assert_eq!(stats.oldest_failed_age_seconds, Some(2 * 3600 + 630));
+        assert_eq!(stats.oldest_stopped_age_seconds, Some(3600 + 630));
+
+        // Editing a process is not a transition, so it keeps its age
+        pm.update_description(&named("done"), "nightly export").await.unwrap();
+        assert_eq!(pm.status_gauges().await.unwrap().oldest_stopped_age_seconds, Some(3600 + 630));
+        pm.delete(&named("done")).await.unwrap();
+        assert_eq!(pm.status_gauges().await.unwrap().oldest_stopped_age_seconds, Some(30));
     }
 
-    pub async fn get_process_logs(&self, name: &str, lines: Option<usize>) -> Result<String> {
-        let process = self.db.get_process_by_name(name).await?
-            .ok_or_else(|| Error::ProcessNotFound(name.to_string()))?;
+    #[tokio::test]
+    async fn test_ephemeral_process_deleted_after_grace_period() {
+        let root = tempfile::TempDir::new().unwrap();
+        let (pm, clock, probe) = fake_runtime_manager(Config::in_directory(root.path())).await;
+        let keep = std::time::Duration::from_secs(600);
 
-        let content = match tokio::fs::read_to_string(&process.log_path).await {
-            Ok(content) => content,
-            Err(e) => {
-                // Try to read as bytes and convert to string, replacing invalid UTF-8
-                match tokio::fs::read(&process.log_path).await {
-                    Ok(bytes) => String::from_utf8_lossy(&bytes).to_string(),
-                    Err(_) => return Err(Error::Other(format!("Failed to read log file: {}", e))),
-                }
-            }
-        };
+        probe.add(7);
+        let record = ProcessRecord { ephemeral_keep_secs: Some(keep.as_secs()), ..running_record(&pm, "scratch", 7) };
+        pm.db.insert_process(&record).await.unwrap();
+        std::fs::write(&record.log_path, "output\n").unwrap();
+        probe.add(8);
+        insert_running(&pm, "kept", 8).await;
 
-        if let Some(lines) = lines {
-            let lines_vec: Vec<&str> = content.lines().collect();
-            let start = if lines_vec.len() > lines {
-                lines_vec.len() - lines
-            } else {
-                0
-            };
-            Ok(lines_vec[start..].join("\n"))
-        } else {
-            Ok(content)
-        }
+        // Nothing is due while it runs
+        clock.advance(keep * 2);
+        assert_eq!(pm.sweep_ephemeral_processes().await.unwrap().cleared_count, 0);
+
+        probe.exit_after(7, std::time::Duration::ZERO);
+        probe.exit_after(8, std::time::Duration::ZERO);
+        let exited_at = clock.now();
+        let scratch = pm.get_process_status(&named("scratch")).await.unwrap();
+        assert_eq!(scratch.status, ProcessStatus::Stopped);
+        assert_eq!(scratch.delete_after, Some(exited_at + chrono::Duration::seconds(600)));
+        pm.get_process_status(&named("kept")).await.unwrap();
+
+        clock.advance(keep - std::time::Duration::from_secs(1));
+        assert_eq!(pm.sweep_ephemeral_processes().await.unwrap().cleared_count, 0);
+        clock.advance(std::time::Duration::from_secs(1));
+        let result = pm.sweep_ephemeral_processes().await.unwrap();
+        assert_eq!(result.cleared_processes, vec!["scratch".to_string()]);
+        assert!(pm.db.get_process_by_name(&named("scratch")).await.unwrap().is_none());
+        assert!(!Path::new(&record.log_path).exists());
+        // Ordinary processes stay however long ago they exited
+        assert!(pm.db.get_process_by_name(&named("kept")).await.unwrap().is_some());
     }
 
-    async fn is_process_running(&self, pid: u32) -> bool {
-        let result = unsafe { libc::kill(pid as i32, 0) };
-        result == 0
+    #[tokio::test]
+    async fn test_stop_marks_go_with_their_records() {
+        let root = tempfile::TempDir::new().unwrap();
+        let config = Config::in_directory(root.path()).with_deleted_retention(std::time::Duration::from_secs(60));
+        let (pm, clock, probe) = fake_runtime_manager(config).await;
+        let marked = |pm: &ProcessManager, name: &str| pm.stop_marks.lock().unwrap().contains_key(&named(name));
+
+        for (pid, name) in [(7, "hard"), (8, "purged"), (9, "cleared")] {
+            probe.add(pid);
+            insert_running(&pm, name, pid).await;
+            probe.exit_after(pid, std::time::Duration::ZERO);
+        }
+        probe.add(10);
+        let record = ProcessRecord { ephemeral_keep_secs: Some(60), ..running_record(&pm, "scratch", 10) };
+        pm.db.insert_process(&record).await.unwrap();
+        probe.exit_after(10, std::time::Duration::ZERO);
+        for name in ["hard", "purged", "cleared", "scratch"] {
+            pm.set_stop_mark(&named(name), true).await;
+            pm.get_process_status(&named(name)).await.unwrap();
+        }
+
+        pm.delete_with_options(&named("hard"), DeleteOptions { hard: true }).await.unwrap();
+        assert!(!marked(&pm, "hard"));
+        // A soft delete can be undone, so the mark stays until the record is purged
+        pm.delete(&named("purged")).await.unwrap();
+        assert!(marked(&pm, "purged"));
+
+        clock.advance(std::time::Duration::from_secs(61));
+        assert_eq!(pm.sweep_ephemeral_processes().await.unwrap().cleared_processes, vec!["scratch".to_string()]);
+        assert!(!marked(&pm, "scratch"));
+        pm.purge_deleted_processes().await.unwrap();
+        assert!(!marked(&pm, "purged"));
+        assert_eq!(pm.clear_processes(false).await.unwrap().cleared_processes, vec!["cleared".to_string()]);
+        assert!(pm.stop_marks.lock().unwrap().is_empty());
     }
 
-    /// Get rotated log files for a process
-    pub async fn get_rotated_logs(&self, name: &str) -> Result<Vec<String>> {
-        let process = self.db.get_process_by_name(name).await?
-            .ok_or_else(|| Error::ProcessNotFound(name.to_string()))?;
+    /// The real filesystem, counting lookups by working directory
+    #[derive(Default)]
+    struct CountingResolver {
+        executables: std::sync::Mutex<Vec<PathBuf>>,
+        hashes: std::sync::atomic::AtomicUsize,
+    }
 
-        let log_path = PathBuf::from(&process.log_path);
-        let rotated_files = self.log_rotator.get_rotated_files(&log_path)?;
+    impl Resolver for CountingResolver {
+        fn executable(&self, command: &str, working_dir: &Path, path_var: Option<&str>) -> Option<PathBuf> {
+            self.executables.lock().unwrap().push(working_dir.to_path_buf());
+            SystemResolver.executable(command, working_dir, path_var)
+        }
 
-        let mut logs = Vec::new();
-        for file_path in rotated_files {
-            let content = match tokio::fs::read_to_string(&file_path).await {
-                Ok(content) => content,
-                Err(_) => {
-                    // Try to read as bytes and convert to string, replacing invalid UTF-8
-                    match tokio::fs::read(&file_path).await {
-                        Ok(bytes) => String::from_utf8_lossy(&bytes).to_string(),
-                        Err(_) => continue,
-                    }
-                }
-            };
-            logs.push(format!("=== {} ===\n{}", file_path.display(), content));
+        fn sha256(&self, path: &Path) -> Option<String> {
+            self.hashes.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            SystemResolver.sha256(path)
         }
 
-        Ok(logs)
+        fn canonical_dir(&self, dir: &Path) -> Option<PathBuf> {
+            SystemResolver.canonical_dir(dir)
+        }
     }
 
-    /// Manually rotate log file for a process
-    pub async fn rotate_process_logs(&self, name: &str) -> Result<String> {
-        let process = self.db.get_process_by_name(name).await?
-            .ok_or_else(|| Error::ProcessNotFound(name.to_string()))?;
+    #[tokio::test]
+    async fn test_replicas_of_a_batch_resolve_their_command_once() {
+        let db_dir = tempfile::TempDir::new().unwrap();
+        let workdir = tempfile::TempDir::new().unwrap();
+        let other_workdir = tempfile::TempDir::new().unwrap();
+        let mut pm = manager(db_dir.path()).await;
+        let resolver = Arc::new(CountingResolver::default());
+        pm.resolver = resolver.clone();
+        let pm = Arc::new(pm);
 
-        let log_path = PathBuf::from(&process.log_path);
-        self.log_rotator.force_rotate(&log_path).await?;
+        let resolution = pm.resolution_cache();
+        let starts = (0..51).map(|i| {
+            let pm = pm.clone();
+            let dir = if i == 50 { &other_workdir } else { &workdir };
+            let options = StartOptions {
+                working_dir: Some(dir.path().to_string_lossy().to_string()),
+                check_in_background: true,
+                resolution: Some(resolution.clone()),
+                ..Default::default()
+            };
+            async move {
+                let name = named(&format!("replica-{}", i));
+                let outcome = pm.start_with_options(&name, "sleep", vec!["30".to_string()], HashMap::new(), options).await.unwrap();
+                pm.finish_start_check(outcome).await;
+                name
+            }
+        });
+        let names = futures_util::future::join_all(starts).await;
 
-        Ok(format!("Log rotation completed for process '{}'", name))
+        let executables = resolver.executables.lock().unwrap().clone();
+        assert_eq!(executables.len(), 2);
+        assert_eq!(executables.iter().filter(|dir| dir.as_path() == workdir.path()).count(), 1);
+        assert_eq!(executables.iter().filter(|dir| dir.as_path() == other_workdir.path()).count(), 1);
+        // Both resolve to the same binary, which is hashed once
+        assert_eq!(resolver.hashes.load(std::sync::atomic::Ordering::SeqCst), 1);
+        let first = pm.get_process_status(&names[0]).await.unwrap();
+        let last = pm.get_process_status(&names[50]).await.unwrap();
+        assert!(first.binary_sha256.is_some());
+        assert_eq!((first.binary_path, first.binary_sha256), (last.binary_path, last.binary_sha256));
+        assert_ne!(first.canonical_workdir, last.canonical_workdir);
+
+        // Checking drift of all of them hashes the binary once more, not once per replica
+        let reports = pm.check_drift(None).await.unwrap();
+        assert_eq!(reports.len(), 51);
+        assert!(reports.iter().all(|report| report.fingerprinted && !report.has_drift()));
+        assert_eq!(resolver.hashes.load(std::sync::atomic::Ordering::SeqCst), 2);
+        // A start without a batch looks again
+        pm.start_with_options(&named("single"), "sleep", vec!["30".to_string()], HashMap::new(), StartOptions {
+            working_dir: Some(workdir.path().to_string_lossy().to_string()),
+            ..Default::default()
+        }).await.unwrap();
+        assert_eq!(resolver.executables.lock().unwrap().len(), 3);
+
+        for name in names.iter().chain([&named("single")]) {
+            pm.stop(name).await.ok();
+        }
     }
 
-    /// Get log rotation status for a process
-    pub async fn get_log_rotation_status(&self, name: &str) -> Result<String> {
-        let process = self.db.get_process_by_name(name).await?
-            .ok_or_else(|| Error::ProcessNotFound(name.to_string()))?;
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_stop_racing_a_watch_restart_is_never_undone() {
+        let db_dir = tempfile::TempDir::new().unwrap();
+        let config = Config::new()
+            .with_database_path(db_dir.path().join("test.db"))
+            .with_log_dir(db_dir.path().join("logs"))
+            .with_reaper_interval(std::time::Duration::from_millis(1));
+        let pm = Arc::new(ProcessManager::new(config).await.unwrap());
+        let name = named("victim");
+        pm.start(&name, "sleep", vec!["30".to_string()], HashMap::new(), None, None).await.unwrap();
 
-        let log_path = PathBuf::from(&process.log_path);
-        let current_size = self.log_rotator.get_log_size(&log_path)?;
-        let needs_rotation = self.log_rotator.needs_rotation(&log_path)?;
-        let rotated_files = self.log_rotator.get_rotated_files(&log_path)?;
+        for i in 0..8u64 {
+            if i > 0 {
+                pm.restart(&name).await.unwrap();
+            }
+            let watch_restart = tokio::spawn({
+                let (pm, name) = (pm.clone(), name.clone());
+                async move { pm.restart_for_change(&name, "src/main.rs", 1).await }
+            });
+            // The stop lands before, during and after the restart's own stop and start
+            tokio::time::sleep(std::time::Duration::from_millis(i * 75)).await;
+            pm.stop(&name).await.unwrap();
+            let restarted = watch_restart.await.unwrap();
 
-        let status = format!(
-            "Log file: {}\nCurrent size: {} bytes\nNeeds rotation: {}\nRotated files: {}",
-            log_path.display(),
-            current_size,
-            if needs_rotation { "Yes" } else { "No" },
-            rotated_files.len()
-        );
+            // Give the reaper a few passes to act on anything it saw
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+            let record = pm.get_process_status(&name).await.unwrap();
+            assert_eq!(record.status, ProcessStatus::Stopped, "iteration {}: {:?}", i, restarted);
+            if let Some(pid) = record.pid {
+                assert!(!pm.is_process_running(pid).await, "iteration {}: PID {} still runs", i, pid);
+            }
+        }
 
-        Ok(status)
+        // An explicit restart clears the mark, so the next change restarts again
+        pm.restart(&name).await.unwrap();
+        let outcome = pm.restart_for_change(&name, "src/main.rs", 1).await.unwrap();
+        assert_eq!(outcome.skipped, None);
+        pm.stop(&name).await.unwrap();
+        let outcome = pm.restart_for_change(&name, "src/main.rs", 1).await.unwrap();
+        assert_eq!(outcome.skipped, Some(RestartSkip::StoppedOnPurpose));
+        assert_eq!(pm.get_process_status(&name).await.unwrap().status, ProcessStatus::Stopped);
     }
 }
-
-
@@ -0,0 +1,173 @@
+//! Profiles: separate pmr homes, each with its own database and config file, kept in
+//! `profiles/<name>` below the default home (`~/.pmr`) and picked with `PMR_HOME`. The
+//! default home itself is the `default` profile. Other profiles are only ever opened
+//! read-only, to list what they hold or to tell where a process that is missing from the
+//! current database lives instead.
+
+use crate::config::{DATABASE_FILE_NAME, HOME_ENV};
+use crate::database::{Database, ProcessRecord};
+use crate::name::ProcessName;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+/// Directory of the named profiles, below the default home
+pub const PROFILES_DIR: &str = "profiles";
+
+/// Name of the profile living in the default home itself
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// A pmr home whose database can be looked into
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Profile {
+    pub name: String,
+    pub home: PathBuf,
+}
+
+impl Profile {
+    pub fn database(&self) -> PathBuf {
+        self.home.join(DATABASE_FILE_NAME)
+    }
+
+    /// How to run a command against this profile
+    pub fn env_hint(&self) -> String {
+        format!("{}={}", HOME_ENV, self.home.display())
+    }
+}
+
+/// What one profile holds, as read by [`scan`]
+#[derive(Debug, Clone, Serialize)]
+pub struct ProfileListing {
+    pub profile: String,
+    pub database: String,
+    pub instance_id: Option<String>,
+    /// Whether this is the database the command runs against
+    pub current: bool,
+    pub processes: Vec<ProcessRecord>,
+    /// Why the database could not be read; its processes are then empty
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// The default home, `~/.pmr`, whatever `PMR_HOME` says
+pub fn default_home() -> PathBuf {
+    let home = std::env::var("HOME").unwrap_or_else(|_| "/tmp".to_string());
+    PathBuf::from(home).join(".pmr")
+}
+
+/// Profiles below `default_home` that have a database: the default one first, then the
+/// named ones in name order
+pub fn discover(default_home: &Path) -> Vec<Profile> {
+    let mut profiles = Vec::new();
+    let default = Profile { name: DEFAULT_PROFILE.to_string(), home: default_home.to_path_buf() };
+    if default.database().is_file() {
+        profiles.push(default);
+    }
+    let mut named: Vec<Profile> = std::fs::read_dir(default_home.join(PROFILES_DIR))
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            let profile = Profile { name, home: entry.path() };
+            profile.database().is_file().then_some(profile)
+        })
+        .collect();
+    named.sort_by(|a, b| a.name.cmp(&b.name));
+    profiles.extend(named);
+    profiles
+}
+
+/// Read the processes of every profile, each database opened read-only. A profile that
+/// cannot be read is listed with its error rather than failing the scan.
+pub async fn scan(profiles: &[Profile], current_database: &Path) -> Vec<ProfileListing> {
+    let mut listings = Vec::new();
+    for profile in profiles {
+        let database = profile.database();
+        let mut listing = ProfileListing {
+            profile: profile.name.clone(),
+            database: database.display().to_string(),
+            instance_id: None,
+            current: same_file(&database, current_database),
+            processes: Vec::new(),
+            error: None,
+        };
+        match read_profile(&database).await {
+            Ok((instance_id, processes)) => {
+                listing.instance_id = instance_id;
+                listing.processes = processes;
+            }
+            Err(e) => listing.error = Some(e.to_string()),
+        }
+        listings.push(listing);
+    }
+    listings
+}
+
+async fn read_profile(database: &Path) -> crate::Result<(Option<String>, Vec<ProcessRecord>)> {
+    let db = Database::new_read_only(database).await?;
+    let instance_id = db.instance_id().await?;
+    let processes = db.get_all_processes().await?.into_iter().filter(|process| !process.internal).collect();
+    Ok((instance_id, processes))
+}
+
+/// Profiles other than the current one holding a process called `name`; unreadable
+/// ones are skipped
+pub async fn find_elsewhere(profiles: &[Profile], current_database: &Path, name: &ProcessName) -> Vec<Profile> {
+    let mut found = Vec::new();
+    for profile in profiles {
+        let database = profile.database();
+        if same_file(&database, current_database) {
+            continue;
+        }
+        let Ok(db) = Database::new_read_only(&database).await else { continue };
+        if let Ok(Some(process)) = db.get_process_by_name(name).await {
+            if !process.internal {
+                found.push(profile.clone());
+            }
+        }
+    }
+    found
+}
+
+/// The first characters of an instance id, enough to tell databases apart at a glance
+pub fn short_id(instance_id: &str) -> String {
+    let short: String = instance_id.chars().take(8).collect();
+    if short.len() < instance_id.len() {
+        format!("{}…", short)
+    } else {
+        short
+    }
+}
+
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_short_id() {
+        assert_eq!(short_id("3fa2b1c4-5d6e-4f70-8192-a3b4c5d6e7f8"), "3fa2b1c4…");
+        assert_eq!(short_id("3fa2"), "3fa2");
+    }
+
+    #[test]
+    fn test_discover_lists_the_default_profile_first_and_skips_homes_without_a_database() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let home = temp.path().join(".pmr");
+        for dir in ["profiles/work", "profiles/alpha", "profiles/empty"] {
+            std::fs::create_dir_all(home.join(dir)).unwrap();
+        }
+        for db in ["processes.db", "profiles/work/processes.db", "profiles/alpha/processes.db"] {
+            std::fs::write(home.join(db), b"").unwrap();
+        }
+        let names: Vec<String> = discover(&home).into_iter().map(|profile| profile.name).collect();
+        assert_eq!(names, ["default", "alpha", "work"]);
+        assert!(discover(&temp.path().join("missing")).is_empty());
+    }
+}
@@ -0,0 +1,69 @@
+//! Whether a manager can do its work right now: the database answers a query within
+//! [`DATABASE_DEADLINE`] and the default log directory takes new files. Served without a
+//! token as `GET /readyz`, for load balancers that should only route to a working server.
+
+use crate::format::human_duration;
+use crate::process::ProcessManager;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::time::Duration;
+
+/// How long the database gets to answer before it counts as not ready
+pub const DATABASE_DEADLINE: Duration = Duration::from_secs(2);
+
+/// One dependency that was checked
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "http-api", derive(utoipa::ToSchema))]
+pub struct ReadinessCheck {
+    /// `database` or `log_dir`
+    pub name: String,
+    pub ok: bool,
+    /// What was found, or why the check failed
+    pub detail: String,
+}
+
+/// Every dependency with its result
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "http-api", derive(utoipa::ToSchema))]
+pub struct ReadinessReport {
+    /// Whether every check passed
+    pub ready: bool,
+    pub checks: Vec<ReadinessCheck>,
+    /// Names of the checks that failed
+    pub failed: Vec<String>,
+}
+
+impl ReadinessReport {
+    fn new(checks: Vec<ReadinessCheck>) -> Self {
+        let failed: Vec<String> = checks.iter().filter(|check| !check.ok).map(|check| check.name.clone()).collect();
+        Self { ready: failed.is_empty(), checks, failed }
+    }
+}
+
+/// Run every check against `process_manager`
+pub async fn check(process_manager: &ProcessManager) -> ReadinessReport {
+    let log_dir = process_manager.config().default_log_dir.clone();
+    ReadinessReport::new(vec![check_database(process_manager).await, check_log_dir(&log_dir)])
+}
+
+async fn check_database(process_manager: &ProcessManager) -> ReadinessCheck {
+    let (ok, detail) = match tokio::time::timeout(DATABASE_DEADLINE, process_manager.ping_database()).await {
+        Ok(Ok(())) => (true, "answered a query".to_string()),
+        Ok(Err(e)) => (false, e.to_string()),
+        Err(_) => (false, format!("no answer within {}", human_duration(DATABASE_DEADLINE))),
+    };
+    ReadinessCheck { name: "database".to_string(), ok, detail }
+}
+
+/// Create and remove a file in `dir`, as a start creating its log would
+fn check_log_dir(dir: &Path) -> ReadinessCheck {
+    let probe = dir.join(format!(".pmr-ready-{}", std::process::id()));
+    let (ok, detail) = match std::fs::File::create(&probe) {
+        Ok(_) => {
+            let _ = std::fs::remove_file(&probe);
+            (true, format!("{} is writable", dir.display()))
+        }
+        Err(e) => (false, format!("{} is not writable: {}", dir.display(), e)),
+    };
+    ReadinessCheck { name: "log_dir".to_string(), ok, detail }
+}
@@ -0,0 +1,157 @@
+//! What starts and drift checks look up on the filesystem: the executable a command
+//! resolves to through `PATH`, its hash, and the canonical working directory. A
+//! [`ResolutionCache`] remembers the answers for one operation, so a bulk start of many
+//! replicas of the same command resolves and hashes its binary once rather than once per
+//! replica. It lives only as long as that operation; the next one looks again, so a binary
+//! replaced between two commands is seen.
+//!
+//! Tests swap in their own [`Resolver`] to count lookups.
+
+use crate::drift::{resolve_executable, sha256_file};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+
+pub trait Resolver: Send + Sync {
+    /// Absolute path `command` runs from when started in `working_dir` with `path_var`
+    fn executable(&self, command: &str, working_dir: &Path, path_var: Option<&str>) -> Option<PathBuf>;
+
+    /// Hex-encoded SHA-256 of the file at `path`
+    fn sha256(&self, path: &Path) -> Option<String>;
+
+    /// `dir` with symlinks resolved
+    fn canonical_dir(&self, dir: &Path) -> Option<PathBuf>;
+}
+
+/// The real filesystem, see [`resolve_executable`] and [`sha256_file`]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemResolver;
+
+impl Resolver for SystemResolver {
+    fn executable(&self, command: &str, working_dir: &Path, path_var: Option<&str>) -> Option<PathBuf> {
+        resolve_executable(command, working_dir, path_var)
+    }
+
+    fn sha256(&self, path: &Path) -> Option<String> {
+        sha256_file(path).ok()
+    }
+
+    fn canonical_dir(&self, dir: &Path) -> Option<PathBuf> {
+        std::fs::canonicalize(dir).ok()
+    }
+}
+
+/// Answers already looked up, each computed at most once even when replicas ask at the
+/// same time
+type Memo<K, V> = Mutex<HashMap<K, Arc<OnceLock<V>>>>;
+
+#[derive(Default)]
+struct Entries {
+    /// By command, working directory and `PATH`: relative `PATH` entries and commands
+    /// with a `/` depend on the working directory
+    executables: Memo<(String, PathBuf, Option<String>), Option<PathBuf>>,
+    hashes: Memo<PathBuf, Option<String>>,
+    workdirs: Memo<PathBuf, Option<PathBuf>>,
+}
+
+/// Lookups of one operation; clones share the answers, so hand one to every start of a batch
+#[derive(Clone)]
+pub struct ResolutionCache {
+    resolver: Arc<dyn Resolver>,
+    entries: Arc<Entries>,
+}
+
+impl std::fmt::Debug for ResolutionCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ResolutionCache")
+            .field("executables", &self.entries.executables.lock().unwrap().len())
+            .field("hashes", &self.entries.hashes.lock().unwrap().len())
+            .field("workdirs", &self.entries.workdirs.lock().unwrap().len())
+            .finish()
+    }
+}
+
+impl Default for ResolutionCache {
+    fn default() -> Self {
+        Self::new(Arc::new(SystemResolver))
+    }
+}
+
+impl ResolutionCache {
+    pub fn new(resolver: Arc<dyn Resolver>) -> Self {
+        Self { resolver, entries: Arc::default() }
+    }
+
+    /// See [`Resolver::executable`]
+    pub fn executable(&self, command: &str, working_dir: &Path, path_var: Option<&str>) -> Option<PathBuf> {
+        let key = (command.to_string(), working_dir.to_path_buf(), path_var.map(str::to_string));
+        memo(&self.entries.executables, key, || self.resolver.executable(command, working_dir, path_var))
+    }
+
+    /// See [`Resolver::sha256`]
+    pub fn sha256(&self, path: &Path) -> Option<String> {
+        memo(&self.entries.hashes, path.to_path_buf(), || self.resolver.sha256(path))
+    }
+
+    /// See [`Resolver::canonical_dir`]
+    pub fn canonical_dir(&self, dir: &Path) -> Option<PathBuf> {
+        memo(&self.entries.workdirs, dir.to_path_buf(), || self.resolver.canonical_dir(dir))
+    }
+}
+
+/// The answer for `key`, from `look_up` the first time it is asked for
+fn memo<K, V>(memo: &Memo<K, V>, key: K, look_up: impl FnOnce() -> V) -> V
+where
+    K: std::hash::Hash + Eq,
+    V: Clone,
+{
+    // Only the slot is taken under the lock; lookups of other keys go on meanwhile
+    let slot = memo.lock().unwrap().entry(key).or_default().clone();
+    slot.get_or_init(look_up).clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[derive(Default)]
+    struct CountingResolver {
+        executables: AtomicUsize,
+    }
+
+    impl Resolver for CountingResolver {
+        fn executable(&self, command: &str, working_dir: &Path, _path_var: Option<&str>) -> Option<PathBuf> {
+            self.executables.fetch_add(1, Ordering::SeqCst);
+            Some(working_dir.join(command))
+        }
+
+        fn sha256(&self, _path: &Path) -> Option<String> {
+            None
+        }
+
+        fn canonical_dir(&self, dir: &Path) -> Option<PathBuf> {
+            Some(dir.to_path_buf())
+        }
+    }
+
+    #[test]
+    fn test_concurrent_lookups_of_one_key_resolve_once() {
+        let resolver = Arc::new(CountingResolver::default());
+        let cache = ResolutionCache::new(resolver.clone());
+        std::thread::scope(|scope| {
+            for _ in 0..16 {
+                let cache = cache.clone();
+                scope.spawn(move || assert_eq!(cache.executable("tool", Path::new("/srv"), None), Some(PathBuf::from("/srv/tool"))));
+            }
+        });
+        assert_eq!(resolver.executables.load(Ordering::SeqCst), 1);
+
+        // A different PATH is a different lookup
+        cache.executable("tool", Path::new("/srv"), Some("/bin"));
+        assert_eq!(resolver.executables.load(Ordering::SeqCst), 2);
+        // A fresh cache looks again
+        ResolutionCache::new(resolver.clone()).executable("tool", Path::new("/srv"), None);
+        assert_eq!(resolver.executables.load(Ordering::SeqCst), 3);
+    }
+}
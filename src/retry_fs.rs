@@ -0,0 +1,346 @@
+//! Retries of filesystem operations that fail only for a moment. On NFS and overlayfs a
+//! start now and then sees ENOENT or EACCES while creating its log directory or log file
+//! that the same call 50ms later would not, and failing the start for it means rolling
+//! everything back. The log steps of a start, the renames and creates of a rotation and
+//! the removals of a rollback are therefore tried a few times with a growing backoff.
+//! Errors that waiting does not fix, like EPERM or EROFS, fail at once as before.
+//!
+//! The operations go through a [`FileSystem`], which tests replace to script failures.
+//! A backoff sleeps the calling thread; it lasts tens of milliseconds and only ever
+//! follows a failure.
+
+use std::fs::File;
+use std::io;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// How many times and how patiently an operation is tried
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Attempts in all, the first one included
+    pub max_attempts: u32,
+    /// Wait before the second attempt, doubled before each one after it
+    pub backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    /// Four attempts, 25ms apart and doubling: at most 175ms of waiting
+    fn default() -> Self {
+        Self { max_attempts: 4, backoff: Duration::from_millis(25) }
+    }
+}
+
+/// Whether `error` can go away by trying again: a missing entry or a denied access that
+/// a network or overlay filesystem has yet to catch up on, a stale handle, a busy or
+/// interrupted call
+pub fn is_retryable(error: &io::Error) -> bool {
+    match error.raw_os_error() {
+        Some(code) => matches!(code, libc::ENOENT | libc::EACCES | libc::ESTALE | libc::EAGAIN | libc::EINTR | libc::EBUSY),
+        None => matches!(error.kind(), io::ErrorKind::NotFound | io::ErrorKind::Interrupted | io::ErrorKind::WouldBlock),
+    }
+}
+
+/// Retries made and time waited by the operations of one [`RetryingFs`]; clones share them
+#[derive(Debug, Clone, Default)]
+pub struct RetryTally(Arc<Counts>);
+
+#[derive(Debug, Default)]
+struct Counts {
+    retries: AtomicU64,
+    waited_ms: AtomicU64,
+}
+
+impl RetryTally {
+    pub fn retries(&self) -> u64 {
+        self.0.retries.load(Ordering::Relaxed)
+    }
+
+    pub fn waited(&self) -> Duration {
+        Duration::from_millis(self.0.waited_ms.load(Ordering::Relaxed))
+    }
+
+    /// The retries and wait so far, counting again from zero
+    pub fn take(&self) -> (u64, Duration) {
+        let retries = self.0.retries.swap(0, Ordering::Relaxed);
+        let waited = self.0.waited_ms.swap(0, Ordering::Relaxed);
+        (retries, Duration::from_millis(waited))
+    }
+
+    fn record(&self, wait: Duration) {
+        self.0.retries.fetch_add(1, Ordering::Relaxed);
+        self.0.waited_ms.fetch_add(wait.as_millis() as u64, Ordering::Relaxed);
+    }
+}
+
+/// Run `op` until it succeeds, fails with an error that is not [retryable](is_retryable)
+/// or has been tried `policy.max_attempts` times. The last error is returned as it was.
+pub fn retry_fs<T>(
+    policy: &RetryPolicy,
+    tally: &RetryTally,
+    operation: &str,
+    path: &Path,
+    mut op: impl FnMut() -> io::Result<T>,
+) -> io::Result<T> {
+    let mut backoff = policy.backoff;
+    let mut attempt = 1;
+    loop {
+        match op() {
+            Err(e) if attempt < policy.max_attempts && is_retryable(&e) => {
+                tracing::debug!(operation, path = %path.display(), attempt, error = %e, backoff_ms = backoff.as_millis() as u64, "retrying filesystem operation");
+                std::thread::sleep(backoff);
+                tally.record(backoff);
+                backoff *= 2;
+                attempt += 1;
+            }
+            Err(e) => {
+                if attempt > 1 {
+                    tracing::warn!(operation, path = %path.display(), attempts = attempt, error = %e, "filesystem operation failed after retries");
+                }
+                return Err(e);
+            }
+            Ok(value) => {
+                if attempt > 1 {
+                    tracing::debug!(operation, path = %path.display(), attempts = attempt, "filesystem operation succeeded after retries");
+                }
+                return Ok(value);
+            }
+        }
+    }
+}
+
+/// The filesystem operations that are retried; [`RealFs`] outside tests
+pub trait FileSystem: Send + Sync {
+    fn create_dir_all(&self, path: &Path) -> io::Result<()>;
+
+    /// Open `path` for appending, creating it when it is missing
+    fn open_append(&self, path: &Path) -> io::Result<File>;
+
+    /// Create `path` empty, truncating it when it exists
+    fn create_file(&self, path: &Path) -> io::Result<File>;
+
+    fn remove_file(&self, path: &Path) -> io::Result<()>;
+
+    fn remove_dir(&self, path: &Path) -> io::Result<()>;
+}
+
+/// `std::fs`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RealFs;
+
+impl FileSystem for RealFs {
+    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        std::fs::create_dir_all(path)
+    }
+
+    fn open_append(&self, path: &Path) -> io::Result<File> {
+        File::options().create(true).append(true).open(path)
+    }
+
+    fn create_file(&self, path: &Path) -> io::Result<File> {
+        File::create(path)
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        std::fs::remove_file(path)
+    }
+
+    fn remove_dir(&self, path: &Path) -> io::Result<()> {
+        std::fs::remove_dir(path)
+    }
+}
+
+/// A [`FileSystem`] whose operations are retried by one policy, tallied together
+#[derive(Clone)]
+pub struct RetryingFs {
+    fs: Arc<dyn FileSystem>,
+    policy: RetryPolicy,
+    tally: RetryTally,
+}
+
+impl Default for RetryingFs {
+    fn default() -> Self {
+        Self::new(Arc::new(RealFs), RetryPolicy::default())
+    }
+}
+
+impl std::fmt::Debug for RetryingFs {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RetryingFs").field("policy", &self.policy).field("tally", &self.tally).finish_non_exhaustive()
+    }
+}
+
+impl RetryingFs {
+    pub fn new(fs: Arc<dyn FileSystem>, policy: RetryPolicy) -> Self {
+        Self { fs, policy, tally: RetryTally::default() }
+    }
+
+    pub fn tally(&self) -> &RetryTally {
+        &self.tally
+    }
+
+    /// [`retry_fs`] `op` under this policy and tally, for operations the trait lacks
+    pub fn retry<T>(&self, operation: &str, path: &Path, op: impl FnMut() -> io::Result<T>) -> io::Result<T> {
+        retry_fs(&self.policy, &self.tally, operation, path, op)
+    }
+
+    pub fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        self.retry("create_dir_all", path, || self.fs.create_dir_all(path))
+    }
+
+    pub fn open_append(&self, path: &Path) -> io::Result<File> {
+        self.retry("open_append", path, || self.fs.open_append(path))
+    }
+
+    pub fn create_file(&self, path: &Path) -> io::Result<File> {
+        self.retry("create_file", path, || self.fs.create_file(path))
+    }
+
+    /// Remove the file at `path`; false when there was none, which is not retried
+    pub fn remove_file(&self, path: &Path) -> io::Result<bool> {
+        self.retry("remove_file", path, || absent_is_false(self.fs.remove_file(path)))
+    }
+
+    /// Remove the empty directory at `path`; false when there was none, which is not retried
+    pub fn remove_dir(&self, path: &Path) -> io::Result<bool> {
+        self.retry("remove_dir", path, || absent_is_false(self.fs.remove_dir(path)))
+    }
+}
+
+fn absent_is_false(removed: io::Result<()>) -> io::Result<bool> {
+    match removed {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// [`RealFs`] failing the calls of each operation with scripted errnos before doing them
+#[cfg(test)]
+#[derive(Default)]
+pub(crate) struct ScriptedFs {
+    failures: std::sync::Mutex<std::collections::HashMap<&'static str, std::collections::VecDeque<i32>>>,
+    calls: std::sync::Mutex<std::collections::HashMap<&'static str, u32>>,
+}
+
+#[cfg(test)]
+impl ScriptedFs {
+    /// Fail the next calls of `operation` with `errnos`, one each
+    pub(crate) fn fail(self, operation: &'static str, errnos: impl IntoIterator<Item = i32>) -> Self {
+        self.failures.lock().unwrap().entry(operation).or_default().extend(errnos);
+        self
+    }
+
+    pub(crate) fn calls(&self, operation: &str) -> u32 {
+        self.calls.lock().unwrap().get(operation).copied().unwrap_or(0)
+    }
+
+    fn call(&self, operation: &'static str) -> io::Result<()> {
+        *self.calls.lock().unwrap().entry(operation).or_default() += 1;
+        match self.failures.lock().unwrap().get_mut(operation).and_then(|errnos| errnos.pop_front()) {
+            Some(errno) => Err(io::Error::from_raw_os_error(errno)),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+impl FileSystem for ScriptedFs {
+    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        self.call("create_dir_all").and_then(|()| RealFs.create_dir_all(path))
+    }
+
+    fn open_append(&self, path: &Path) -> io::Result<File> {
+        self.call("open_append").and_then(|()| RealFs.open_append(path))
+    }
+
+    fn create_file(&self, path: &Path) -> io::Result<File> {
+        self.call("create_file").and_then(|()| RealFs.create_file(path))
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        self.call("remove_file").and_then(|()| RealFs.remove_file(path))
+    }
+
+    fn remove_dir(&self, path: &Path) -> io::Result<()> {
+        self.call("remove_dir").and_then(|()| RealFs.remove_dir(path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quick() -> RetryPolicy {
+        RetryPolicy { max_attempts: 4, backoff: Duration::from_millis(1) }
+    }
+
+    fn retrying(fs: &Arc<ScriptedFs>) -> RetryingFs {
+        RetryingFs::new(fs.clone(), quick())
+    }
+
+    #[test]
+    fn test_transient_errors_are_retried_until_the_operation_succeeds() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let scripted = Arc::new(ScriptedFs::default().fail("create_dir_all", [libc::ENOENT, libc::EACCES]).fail("open_append", [libc::ESTALE]));
+        let files = retrying(&scripted);
+
+        let dir = temp.path().join("a/b");
+        files.create_dir_all(&dir).unwrap();
+        files.open_append(&dir.join("x.log")).unwrap();
+
+        assert!(dir.join("x.log").is_file());
+        assert_eq!(scripted.calls("create_dir_all"), 3);
+        assert_eq!(scripted.calls("open_append"), 2);
+        assert_eq!(files.tally().retries(), 3);
+        // 1ms and 2ms before the two retries of the directory, 1ms before the file's
+        assert_eq!(files.tally().waited(), Duration::from_millis(4));
+        assert_eq!(files.tally().take(), (3, Duration::from_millis(4)));
+        assert_eq!(files.tally().retries(), 0);
+    }
+
+    #[test]
+    fn test_retries_stop_after_the_last_attempt_with_its_error() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let scripted = Arc::new(ScriptedFs::default().fail("create_file", [libc::EACCES, libc::ENOENT, libc::EACCES, libc::EBUSY, libc::EACCES]));
+        let files = retrying(&scripted);
+
+        let err = files.create_file(&temp.path().join("x.log")).unwrap_err();
+        assert_eq!(err.raw_os_error(), Some(libc::EBUSY));
+        assert_eq!(scripted.calls("create_file"), 4);
+        assert_eq!(files.tally().retries(), 3);
+        assert!(!temp.path().join("x.log").exists());
+    }
+
+    #[test]
+    fn test_permanent_errors_fail_at_once() {
+        let temp = tempfile::TempDir::new().unwrap();
+        for errno in [libc::EPERM, libc::EROFS, libc::ENOSPC, libc::ENOTDIR] {
+            let scripted = Arc::new(ScriptedFs::default().fail("create_dir_all", [errno]));
+            let files = retrying(&scripted);
+            let err = files.create_dir_all(&temp.path().join("logs")).unwrap_err();
+            assert_eq!(err.raw_os_error(), Some(errno));
+            assert_eq!(scripted.calls("create_dir_all"), 1, "errno {}", errno);
+            assert_eq!(files.tally().retries(), 0);
+        }
+        assert!(!is_retryable(&io::Error::other("not an OS error")));
+        assert!(is_retryable(&io::Error::from(io::ErrorKind::Interrupted)));
+    }
+
+    #[test]
+    fn test_removing_what_is_already_gone_is_not_retried() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let scripted = Arc::new(ScriptedFs::default().fail("remove_file", [libc::EBUSY]));
+        let files = retrying(&scripted);
+        let path = temp.path().join("x.log");
+        std::fs::write(&path, b"log").unwrap();
+
+        assert!(files.remove_file(&path).unwrap());
+        assert_eq!(scripted.calls("remove_file"), 2);
+        assert!(!files.remove_file(&path).unwrap());
+        assert!(!files.remove_dir(&temp.path().join("gone")).unwrap());
+        assert_eq!(scripted.calls("remove_file"), 3);
+        assert_eq!(scripted.calls("remove_dir"), 1);
+    }
+}
@@ -0,0 +1,151 @@
+//! Snapshot of what a process looked like right after it was spawned, read from
+//! `/proc/<pid>/{status,environ,cwd}` and stored with its record for audits.
+
+use crate::env::redact_value;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Upper bound for the serialized snapshot; environment entries past it are dropped
+pub const MAX_SNAPSHOT_BYTES: usize = 16 * 1024;
+
+/// Process state captured from /proc at start
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "http-api", derive(utoipa::ToSchema))]
+pub struct StartSnapshot {
+    pub captured_at: DateTime<Utc>,
+    pub pid: u32,
+    /// Parent PID as the kernel reported it
+    pub ppid: Option<u32>,
+    /// Effective user id
+    pub uid: Option<u32>,
+    /// Effective group id
+    pub gid: Option<u32>,
+    /// Working directory as the kernel sees it
+    pub cwd: Option<String>,
+    pub rss_kb: Option<u64>,
+    /// Environment actually present in the process, with sensitive values redacted
+    pub environ: Option<BTreeMap<String, String>>,
+    /// Set when environment entries were dropped to stay under [`MAX_SNAPSHOT_BYTES`]
+    #[serde(default)]
+    pub environ_truncated: bool,
+    /// What could not be read, e.g. because the process had already exited
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub notes: Vec<String>,
+}
+
+impl StartSnapshot {
+    /// Read what /proc offers about `pid`. Unreadable parts are left empty and noted
+    /// rather than failing, since quick commands may be gone already.
+    pub fn capture(pid: u32) -> Self {
+        let mut snapshot = Self {
+            captured_at: Utc::now(),
+            pid,
+            ..Default::default()
+        };
+
+        match std::fs::read_to_string(format!("/proc/{}/status", pid)) {
+            Ok(status) => snapshot.apply_status(&status),
+            Err(e) => snapshot.notes.push(format!("status unavailable: {}", e)),
+        }
+
+        match std::fs::read_link(format!("/proc/{}/cwd", pid)) {
+            Ok(cwd) => snapshot.cwd = Some(cwd.to_string_lossy().to_string()),
+            Err(e) => snapshot.notes.push(format!("cwd unavailable: {}", e)),
+        }
+
+        match std::fs::read(format!("/proc/{}/environ", pid)) {
+            // A zombie has an empty environ, which says nothing about what it started with
+            Ok(environ) if environ.is_empty() => snapshot.notes.push("environ empty (process exited?)".to_string()),
+            Ok(environ) => snapshot.apply_environ(parse_environ(&environ)),
+            Err(e) => snapshot.notes.push(format!("environ unavailable: {}", e)),
+        }
+
+        snapshot
+    }
+
+    /// Take parent, effective ids and resident memory from the contents of /proc/<pid>/status
+    fn apply_status(&mut self, status: &str) {
+        let field = |key: &str, index: usize| {
+            status
+                .lines()
+                .find_map(|line| line.strip_prefix(key))
+                .and_then(|value| value.split_whitespace().nth(index))
+                .and_then(|value| value.parse::<u64>().ok())
+        };
+
+        self.ppid = field("PPid:", 0).map(|ppid| ppid as u32);
+        // Uid and Gid list real, effective, saved and filesystem ids
+        self.uid = field("Uid:", 1).map(|uid| uid as u32);
+        self.gid = field("Gid:", 1).map(|gid| gid as u32);
+        self.rss_kb = field("VmRSS:", 0);
+    }
+
+    /// Store the environment redacted, keeping entries in key order until the size cap
+    fn apply_environ(&mut self, environ: BTreeMap<String, String>) {
+        let truncated_note = format!("environ truncated to {} bytes", MAX_SNAPSHOT_BYTES);
+        let used = serde_json::to_string(self).map(|s| s.len()).unwrap_or(0) + truncated_note.len() + 4;
+        let mut budget = MAX_SNAPSHOT_BYTES.saturating_sub(used);
+        let mut kept = BTreeMap::new();
+        for (key, value) in environ {
+            let value = redact_value(&key, &value);
+            // Quotes, colon and comma around each entry
+            let cost = key.len() + value.len() + 6;
+            if cost > budget {
+                self.environ_truncated = true;
+                continue;
+            }
+            budget -= cost;
+            kept.insert(key, value);
+        }
+        if self.environ_truncated {
+            self.notes.push(truncated_note);
+        }
+        self.environ = Some(kept);
+    }
+}
+
+/// Split the NUL-separated `KEY=VALUE` entries of /proc/<pid>/environ
+fn parse_environ(environ: &[u8]) -> BTreeMap<String, String> {
+    environ
+        .split(|byte| *byte == 0)
+        .filter(|entry| !entry.is_empty())
+        .filter_map(|entry| {
+            let entry = String::from_utf8_lossy(entry);
+            entry.split_once('=').map(|(key, value)| (key.to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::env::REDACTED;
+
+    #[test]
+    fn test_status_and_environ_parsing() {
+        let mut snapshot = StartSnapshot::default();
+        snapshot.apply_status("Name:\tsleep\nPPid:\t1\nUid:\t1000\t1001\t1000\t1000\nGid:\t100\t101\t100\t100\nVmRSS:\t  980 kB\n");
+        assert_eq!((snapshot.ppid, snapshot.uid, snapshot.gid, snapshot.rss_kb), (Some(1), Some(1001), Some(101), Some(980)));
+
+        snapshot.apply_environ(parse_environ(b"HOME=/root\0DB_PASSWORD=hunter2\0EMPTY=\0junk\0"));
+        let environ = snapshot.environ.unwrap();
+        assert_eq!(environ.get("HOME").map(String::as_str), Some("/root"));
+        assert_eq!(environ.get("DB_PASSWORD").map(String::as_str), Some(REDACTED));
+        assert_eq!(environ.get("EMPTY").map(String::as_str), Some(""));
+        assert_eq!(environ.len(), 3);
+        assert!(!snapshot.environ_truncated);
+    }
+
+    #[test]
+    fn test_environ_is_size_capped() {
+        let mut snapshot = StartSnapshot::default();
+        let big = "x".repeat(MAX_SNAPSHOT_BYTES / 4);
+        let environ = (0..8).map(|i| (format!("VAR_{}", i), big.clone())).collect();
+        snapshot.apply_environ(environ);
+
+        assert!(snapshot.environ_truncated);
+        assert!(snapshot.environ.as_ref().unwrap().len() < 8);
+        assert!(serde_json::to_string(&snapshot).unwrap().len() <= MAX_SNAPSHOT_BYTES);
+    }
+}
@@ -0,0 +1,114 @@
+//! Waiting out resource exhaustion: a start whose spawn fails with EAGAIN or ENOMEM can
+//! join a bounded queue and retry with backoff, earlier when another process exits, instead
+//! of failing at once. Opt-in per start (`--queue-on-pressure`) or for all of them through
+//! [`crate::config::StartQueueConfig`].
+
+use crate::events::{ProcessEvent, ProcessEventKind};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+/// Delay before the first retry; each further one doubles it up to [`MAX_RETRY_DELAY`]
+pub const FIRST_RETRY_DELAY: Duration = Duration::from_millis(100);
+pub const MAX_RETRY_DELAY: Duration = Duration::from_secs(2);
+
+/// Whether a spawn failed because the system is out of processes or memory, which may
+/// pass once other processes exit
+pub fn is_resource_pressure(error: &std::io::Error) -> bool {
+    matches!(error.raw_os_error(), Some(libc::EAGAIN) | Some(libc::ENOMEM))
+}
+
+/// Delay before retry number `attempt`, counted from 0
+pub fn retry_delay(attempt: u32) -> Duration {
+    FIRST_RETRY_DELAY.saturating_mul(1 << attempt.min(16)).min(MAX_RETRY_DELAY)
+}
+
+/// Starts currently waiting, shared by all starts of one manager
+#[derive(Debug, Default)]
+pub struct StartQueue {
+    waiting: AtomicUsize,
+}
+
+impl StartQueue {
+    /// Take a place in the queue, or None when `depth` starts already wait
+    pub fn join(&self, depth: usize) -> Option<QueueSlot<'_>> {
+        self.waiting
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |waiting| (waiting < depth).then_some(waiting + 1))
+            .ok()?;
+        Some(QueueSlot { queue: self })
+    }
+
+    /// Starts waiting right now, including the caller's own while it holds a slot
+    pub fn waiting(&self) -> usize {
+        self.waiting.load(Ordering::SeqCst)
+    }
+}
+
+/// A place in the [`StartQueue`], given up when dropped
+#[derive(Debug)]
+pub struct QueueSlot<'a> {
+    queue: &'a StartQueue,
+}
+
+impl Drop for QueueSlot<'_> {
+    fn drop(&mut self) {
+        self.queue.waiting.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Wait until a process exits, which may free what the spawn was short of. Returns at once
+/// when events were missed, since one of them may have been an exit.
+pub async fn next_exit(events: &mut broadcast::Receiver<ProcessEvent>) {
+    loop {
+        match events.recv().await {
+            Ok(event) => {
+                if matches!(
+                    event.kind,
+                    ProcessEventKind::Stopped { .. } | ProcessEventKind::Failed { .. } | ProcessEventKind::Succeeded { .. }
+                ) {
+                    return;
+                }
+            }
+            Err(broadcast::error::RecvError::Lagged(_)) => return,
+            // Nothing publishes anymore, so only the backoff ends the wait
+            Err(broadcast::error::RecvError::Closed) => std::future::pending().await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_resource_pressure() {
+        assert!(is_resource_pressure(&std::io::Error::from_raw_os_error(libc::EAGAIN)));
+        assert!(is_resource_pressure(&std::io::Error::from_raw_os_error(libc::ENOMEM)));
+        assert!(!is_resource_pressure(&std::io::Error::from_raw_os_error(libc::ENOENT)));
+        assert!(!is_resource_pressure(&std::io::Error::other("EAGAIN")));
+    }
+
+    #[test]
+    fn test_retry_delay_backs_off_to_the_cap() {
+        assert_eq!(retry_delay(0), Duration::from_millis(100));
+        assert_eq!(retry_delay(1), Duration::from_millis(200));
+        assert_eq!(retry_delay(4), Duration::from_millis(1600));
+        assert_eq!(retry_delay(5), MAX_RETRY_DELAY);
+        assert_eq!(retry_delay(u32::MAX), MAX_RETRY_DELAY);
+    }
+
+    #[test]
+    fn test_queue_is_bounded_and_slots_are_given_back() {
+        let queue = StartQueue::default();
+        let first = queue.join(2).unwrap();
+        let second = queue.join(2).unwrap();
+        assert_eq!(queue.waiting(), 2);
+        assert!(queue.join(2).is_none());
+        drop(first);
+        assert_eq!(queue.waiting(), 1);
+        assert!(queue.join(2).is_some());
+        drop(second);
+        assert_eq!(queue.waiting(), 0);
+        assert!(queue.join(0).is_none());
+    }
+}
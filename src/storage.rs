@@ -0,0 +1,508 @@
+//! Where process records live. [`StorageBackend`] is everything a
+//! [`ProcessManager`](crate::process::ProcessManager) and the API token store ask of a
+//! database; [`Database`] implements it over the SQLite file at
+//! [`Config::database_path`](crate::config::Config::database_path), the default.
+//!
+//! With the `postgres` feature, `backend = "postgres"` in the `[storage]` section of the
+//! config file keeps records in a Postgres database instead (see [`postgres`]), which
+//! several hosts can share. Every row there carries the name of the host it belongs to and
+//! each pmr reads and writes only its own rows, so two hosts may both run a `web` without
+//! clashing. [`StorageBackend::list_all_hosts`] is the one read across hosts, for
+//! `pmr list --all-hosts`; nothing ever changes another host's rows.
+//!
+//! Schema inspection and salvage (`pmr db ...`) are about the SQLite file and stay on
+//! [`Database`].
+
+#[cfg(feature = "postgres")]
+pub mod postgres;
+
+use crate::config::{Config, StorageKind};
+use crate::database::{Database, LogRotationEntry, ProcessCounts, ProcessNote, ProcessRecord, ProcessRun, ProcessStatus, Reservation, StatusSummary, WatchRestartEntry};
+use crate::flapping::FailureHistory;
+use crate::logs_quota::QuotaEnforcement;
+use crate::name::ProcessName;
+use crate::{Error, Result};
+use chrono::{DateTime, Utc};
+use futures_util::stream::BoxStream;
+use serde::Serialize;
+use std::sync::Arc;
+
+#[cfg(feature = "http-api")]
+use crate::database::ApiToken;
+
+/// The records of one host, as read by [`StorageBackend::list_all_hosts`]
+#[derive(Debug, Clone, Serialize)]
+pub struct HostListing {
+    pub host: String,
+    /// Whether these are the rows of the pmr reading them
+    pub current: bool,
+    /// Live processes that are not internal, statuses as their host last stored them
+    pub processes: Vec<ProcessRecord>,
+}
+
+/// A database holding process records, see the module docs. Methods act on the rows of
+/// this host only; their meaning is that of the [`Database`] method of the same name.
+#[async_trait::async_trait]
+pub trait StorageBackend: Send + Sync {
+    /// Name of the host whose rows this backend reads and writes
+    fn host(&self) -> &str;
+
+    /// Live processes of every host sharing this storage, by host name, this host
+    /// included even when it has none
+    async fn list_all_hosts(&self) -> Result<Vec<HostListing>>;
+
+    /// See [`Database::ping`]
+    async fn ping(&self) -> Result<()>;
+
+    /// See [`Database::instance_id`]
+    async fn instance_id(&self) -> Result<Option<String>>;
+
+    /// See [`Database::fail_status_writes`]
+    #[cfg(any(test, feature = "test-util"))]
+    fn fail_status_writes(&self, fail: bool);
+
+    async fn insert_process(&self, process: &ProcessRecord) -> Result<()>;
+
+    async fn replace_process(&self, process: &ProcessRecord) -> Result<bool>;
+
+    async fn get_process_by_name(&self, name: &ProcessName) -> Result<Option<ProcessRecord>>;
+
+    async fn get_all_processes(&self) -> Result<Vec<ProcessRecord>>;
+
+    async fn search_processes(&self, text: &str) -> Result<Vec<ProcessRecord>>;
+
+    async fn get_process_names(&self, prefix: Option<&str>) -> Result<Vec<String>>;
+
+    async fn get_processes_by_status(&self, statuses: &[ProcessStatus]) -> Result<Vec<ProcessRecord>>;
+
+    async fn get_watched_processes(&self) -> Result<Vec<ProcessRecord>>;
+
+    async fn get_deleted_process_by_name(&self, name: &ProcessName) -> Result<Option<ProcessRecord>>;
+
+    fn stream_processes(&self) -> BoxStream<'_, Result<ProcessRecord>>;
+
+    fn stream_deleted_processes_before(&self, cutoff: DateTime<Utc>) -> BoxStream<'_, Result<ProcessRecord>>;
+
+    fn stream_expired_ephemeral_processes(&self, now: DateTime<Utc>) -> BoxStream<'_, Result<ProcessRecord>>;
+
+    async fn get_log_paths(&self) -> Result<Vec<(String, String)>>;
+
+    async fn count_processes_with_log_path(&self, log_path: &str) -> Result<usize>;
+
+    async fn count_processes(&self) -> Result<usize>;
+
+    async fn count_processes_by_status(&self) -> Result<ProcessCounts>;
+
+    async fn summarize_processes_by_status(&self) -> Result<StatusSummary>;
+
+    async fn update_process_status(&self, name: &ProcessName, status: ProcessStatus, pid: Option<u32>, at: DateTime<Utc>) -> Result<()>;
+
+    async fn reset_for_restart(&self, name: &ProcessName, at: DateTime<Utc>) -> Result<Option<ProcessRecord>>;
+
+    async fn update_working_dir(&self, name: &ProcessName, working_dir: &str) -> Result<bool>;
+
+    async fn update_description(&self, name: &ProcessName, description: Option<&str>) -> Result<bool>;
+
+    async fn mark_status_verified(&self, ids: &[String], at: DateTime<Utc>) -> Result<()>;
+
+    async fn schedule_deletion(&self, id: &str, at: DateTime<Utc>) -> Result<()>;
+
+    async fn set_last_output(&self, id: &str, lines: &[String], lossy: bool) -> Result<()>;
+
+    async fn record_exit(&self, name: &ProcessName, status: ProcessStatus, exit_code: Option<i32>, exit_reason: Option<&str>, at: DateTime<Utc>) -> Result<()>;
+
+    async fn delete_process(&self, name: &ProcessName) -> Result<bool>;
+
+    async fn delete_process_by_id(&self, id: &str) -> Result<bool>;
+
+    async fn soft_delete_process_at(&self, name: &ProcessName, deleted_at: DateTime<Utc>) -> Result<bool>;
+
+    async fn restore_process(&self, id: &str) -> Result<bool>;
+
+    async fn insert_log_rotation(&self, entry: &LogRotationEntry) -> Result<()>;
+
+    async fn get_log_rotations(&self, process_name: &ProcessName) -> Result<Vec<LogRotationEntry>>;
+
+    async fn delete_log_rotations(&self, process_name: &ProcessName) -> Result<u64>;
+
+    async fn insert_watch_restart(&self, entry: &WatchRestartEntry, keep: usize) -> Result<()>;
+
+    async fn get_watch_restarts(&self, process_name: &ProcessName) -> Result<Vec<WatchRestartEntry>>;
+
+    async fn delete_watch_restarts(&self, process_name: &ProcessName) -> Result<u64>;
+
+    async fn insert_process_run(&self, run: &ProcessRun, keep: usize) -> Result<()>;
+
+    async fn get_process_runs(&self, process_name: &ProcessName, limit: usize) -> Result<Vec<ProcessRun>>;
+
+    async fn delete_process_runs(&self, process_name: &ProcessName) -> Result<u64>;
+
+    async fn insert_quota_enforcement(&self, enforcement: &QuotaEnforcement, keep: usize) -> Result<()>;
+
+    async fn get_last_quota_enforcement(&self) -> Result<Option<QuotaEnforcement>>;
+
+    async fn insert_process_note(&self, process_name: &ProcessName, author: &str, text: &str) -> Result<ProcessNote>;
+
+    async fn get_process_notes(&self, process_name: &ProcessName) -> Result<Vec<ProcessNote>>;
+
+    async fn delete_process_note(&self, process_name: &ProcessName, note_id: i64) -> Result<bool>;
+
+    async fn delete_process_notes(&self, process_name: &ProcessName) -> Result<u64>;
+
+    async fn get_failure_history(&self, process_name: &ProcessName) -> Result<FailureHistory>;
+
+    async fn get_flapping_histories(&self) -> Result<Vec<(ProcessName, FailureHistory)>>;
+
+    async fn save_failure_history(&self, process_name: &ProcessName, history: &FailureHistory) -> Result<()>;
+
+    async fn delete_failure_history(&self, process_name: &ProcessName) -> Result<bool>;
+
+    async fn claim_reservation(&self, reservation: &Reservation, now: DateTime<Utc>) -> Result<bool>;
+
+    async fn get_reservation(&self, name: &ProcessName, now: DateTime<Utc>) -> Result<Option<Reservation>>;
+
+    async fn delete_reservation(&self, name: &ProcessName) -> Result<bool>;
+
+    async fn delete_expired_reservations(&self, now: DateTime<Utc>) -> Result<u64>;
+
+    #[cfg(feature = "http-api")]
+    async fn insert_api_token(&self, token: &ApiToken) -> Result<()>;
+
+    #[cfg(feature = "http-api")]
+    async fn get_api_token_by_token(&self, token: &str) -> Result<Option<ApiToken>>;
+
+    #[cfg(feature = "http-api")]
+    async fn get_api_token_by_public_id(&self, public_id: &str) -> Result<Option<ApiToken>>;
+
+    #[cfg(feature = "http-api")]
+    async fn get_all_api_tokens(&self) -> Result<Vec<ApiToken>>;
+
+    #[cfg(feature = "http-api")]
+    async fn update_api_token_status(&self, token: &str, is_active: bool) -> Result<bool>;
+
+    #[cfg(feature = "http-api")]
+    async fn delete_api_token(&self, token: &str) -> Result<bool>;
+}
+
+/// Open the storage `config` selects for reading and writing, migrating it first
+pub async fn open(config: &Config) -> Result<Arc<dyn StorageBackend>> {
+    match config.storage.backend {
+        StorageKind::Sqlite => {
+            // Created owner-only before SQLite opens it; its WAL files take the same mode
+            crate::permissions::create_private_file_if_missing(&config.database_path)?;
+            let database_url = format!("sqlite:{}?mode=rwc", config.database_path.display());
+            Ok(Arc::new(Database::open(&database_url, config.allow_destructive_migrations).await?))
+        }
+        StorageKind::Postgres => open_postgres(config, true).await,
+    }
+}
+
+/// Open the storage `config` selects without migrating it, for read-only managers
+pub async fn open_read_only(config: &Config) -> Result<Arc<dyn StorageBackend>> {
+    match config.storage.backend {
+        StorageKind::Sqlite => Ok(Arc::new(Database::new_read_only(&config.database_path).await?)),
+        StorageKind::Postgres => open_postgres(config, false).await,
+    }
+}
+
+#[cfg(feature = "postgres")]
+async fn open_postgres(config: &Config, migrate: bool) -> Result<Arc<dyn StorageBackend>> {
+    let url = config.storage.url.as_deref().ok_or_else(|| Error::Config("storage.url is required by the postgres backend".to_string()))?;
+    let host = config.storage.host.clone().unwrap_or_else(local_host_name);
+    Ok(Arc::new(postgres::PostgresStorage::connect(url, &host, migrate).await?))
+}
+
+#[cfg(not(feature = "postgres"))]
+async fn open_postgres(_config: &Config, _migrate: bool) -> Result<Arc<dyn StorageBackend>> {
+    Err(Error::Config("storage.backend = \"postgres\" needs a pmr built with the postgres feature".to_string()))
+}
+
+/// Name of this machine, as `hostname` prints it; `localhost` when it has none
+pub fn local_host_name() -> String {
+    let mut buffer = [0u8; 256];
+    // SAFETY: gethostname writes at most buffer.len() bytes into the buffer
+    if unsafe { libc::gethostname(buffer.as_mut_ptr().cast(), buffer.len()) } != 0 {
+        return "localhost".to_string();
+    }
+    let end = buffer.iter().position(|&byte| byte == 0).unwrap_or(buffer.len());
+    match String::from_utf8_lossy(&buffer[..end]).trim() {
+        "" => "localhost".to_string(),
+        name => name.to_string(),
+    }
+}
+
+/// The SQLite file belongs to this host alone; its rows are all this host's
+#[async_trait::async_trait]
+impl StorageBackend for Database {
+    fn host(&self) -> &str {
+        static HOST: std::sync::OnceLock<String> = std::sync::OnceLock::new();
+        HOST.get_or_init(local_host_name)
+    }
+
+    async fn list_all_hosts(&self) -> Result<Vec<HostListing>> {
+        let processes = Database::get_all_processes(self).await?.into_iter().filter(|process| !process.internal).collect();
+        Ok(vec![HostListing { host: StorageBackend::host(self).to_string(), current: true, processes }])
+    }
+
+    async fn ping(&self) -> Result<()> {
+        Database::ping(self).await
+    }
+
+    async fn instance_id(&self) -> Result<Option<String>> {
+        Database::instance_id(self).await
+    }
+
+    #[cfg(any(test, feature = "test-util"))]
+    fn fail_status_writes(&self, fail: bool) {
+        Database::fail_status_writes(self, fail)
+    }
+
+    async fn insert_process(&self, process: &ProcessRecord) -> Result<()> {
+        Database::insert_process(self, process).await
+    }
+
+    async fn replace_process(&self, process: &ProcessRecord) -> Result<bool> {
+        Database::replace_process(self, process).await
+    }
+
+    async fn get_process_by_name(&self, name: &ProcessName) -> Result<Option<ProcessRecord>> {
+        Database::get_process_by_name(self, name).await
+    }
+
+    async fn get_all_processes(&self) -> Result<Vec<ProcessRecord>> {
+        Database::get_all_processes(self).await
+    }
+
+    async fn search_processes(&self, text: &str) -> Result<Vec<ProcessRecord>> {
+        Database::search_processes(self, text).await
+    }
+
+    async fn get_process_names(&self, prefix: Option<&str>) -> Result<Vec<String>> {
+        Database::get_process_names(self, prefix).await
+    }
+
+    async fn get_processes_by_status(&self, statuses: &[ProcessStatus]) -> Result<Vec<ProcessRecord>> {
+        Database::get_processes_by_status(self, statuses).await
+    }
+
+    async fn get_watched_processes(&self) -> Result<Vec<ProcessRecord>> {
+        Database::get_watched_processes(self).await
+    }
+
+    async fn get_deleted_process_by_name(&self, name: &ProcessName) -> Result<Option<ProcessRecord>> {
+        Database::get_deleted_process_by_name(self, name).await
+    }
+
+    fn stream_processes(&self) -> BoxStream<'_, Result<ProcessRecord>> {
+        Database::stream_processes(self)
+    }
+
+    fn stream_deleted_processes_before(&self, cutoff: DateTime<Utc>) -> BoxStream<'_, Result<ProcessRecord>> {
+        Database::stream_deleted_processes_before(self, cutoff)
+    }
+
+    fn stream_expired_ephemeral_processes(&self, now: DateTime<Utc>) -> BoxStream<'_, Result<ProcessRecord>> {
+        Database::stream_expired_ephemeral_processes(self, now)
+    }
+
+    async fn get_log_paths(&self) -> Result<Vec<(String, String)>> {
+        Database::get_log_paths(self).await
+    }
+
+    async fn count_processes_with_log_path(&self, log_path: &str) -> Result<usize> {
+        Database::count_processes_with_log_path(self, log_path).await
+    }
+
+    async fn count_processes(&self) -> Result<usize> {
+        Database::count_processes(self).await
+    }
+
+    async fn count_processes_by_status(&self) -> Result<ProcessCounts> {
+        Database::count_processes_by_status(self).await
+    }
+
+    async fn summarize_processes_by_status(&self) -> Result<StatusSummary> {
+        Database::summarize_processes_by_status(self).await
+    }
+
+    async fn update_process_status(&self, name: &ProcessName, status: ProcessStatus, pid: Option<u32>, at: DateTime<Utc>) -> Result<()> {
+        Database::update_process_status(self, name, status, pid, at).await
+    }
+
+    async fn reset_for_restart(&self, name: &ProcessName, at: DateTime<Utc>) -> Result<Option<ProcessRecord>> {
+        Database::reset_for_restart(self, name, at).await
+    }
+
+    async fn update_working_dir(&self, name: &ProcessName, working_dir: &str) -> Result<bool> {
+        Database::update_working_dir(self, name, working_dir).await
+    }
+
+    async fn update_description(&self, name: &ProcessName, description: Option<&str>) -> Result<bool> {
+        Database::update_description(self, name, description).await
+    }
+
+    async fn mark_status_verified(&self, ids: &[String], at: DateTime<Utc>) -> Result<()> {
+        Database::mark_status_verified(self, ids, at).await
+    }
+
+    async fn schedule_deletion(&self, id: &str, at: DateTime<Utc>) -> Result<()> {
+        Database::schedule_deletion(self, id, at).await
+    }
+
+    async fn set_last_output(&self, id: &str, lines: &[String], lossy: bool) -> Result<()> {
+        Database::set_last_output(self, id, lines, lossy).await
+    }
+
+    async fn record_exit(&self, name: &ProcessName, status: ProcessStatus, exit_code: Option<i32>, exit_reason: Option<&str>, at: DateTime<Utc>) -> Result<()> {
+        Database::record_exit(self, name, status, exit_code, exit_reason, at).await
+    }
+
+    async fn delete_process(&self, name: &ProcessName) -> Result<bool> {
+        Database::delete_process(self, name).await
+    }
+
+    async fn delete_process_by_id(&self, id: &str) -> Result<bool> {
+        Database::delete_process_by_id(self, id).await
+    }
+
+    async fn soft_delete_process_at(&self, name: &ProcessName, deleted_at: DateTime<Utc>) -> Result<bool> {
+        Database::soft_delete_process_at(self, name, deleted_at).await
+    }
+
+    async fn restore_process(&self, id: &str) -> Result<bool> {
+        Database::restore_process(self, id).await
+    }
+
+    async fn insert_log_rotation(&self, entry: &LogRotationEntry) -> Result<()> {
+        Database::insert_log_rotation(self, entry).await
+    }
+
+    async fn get_log_rotations(&self, process_name: &ProcessName) -> Result<Vec<LogRotationEntry>> {
+        Database::get_log_rotations(self, process_name).await
+    }
+
+    async fn delete_log_rotations(&self, process_name: &ProcessName) -> Result<u64> {
+        Database::delete_log_rotations(self, process_name).await
+    }
+
+    async fn insert_watch_restart(&self, entry: &WatchRestartEntry, keep: usize) -> Result<()> {
+        Database::insert_watch_restart(self, entry, keep).await
+    }
+
+    async fn get_watch_restarts(&self, process_name: &ProcessName) -> Result<Vec<WatchRestartEntry>> {
+        Database::get_watch_restarts(self, process_name).await
+    }
+
+    async fn delete_watch_restarts(&self, process_name: &ProcessName) -> Result<u64> {
+        Database::delete_watch_restarts(self, process_name).await
+    }
+
+    async fn insert_process_run(&self, run: &ProcessRun, keep: usize) -> Result<()> {
+        Database::insert_process_run(self, run, keep).await
+    }
+
+    async fn get_process_runs(&self, process_name: &ProcessName, limit: usize) -> Result<Vec<ProcessRun>> {
+        Database::get_process_runs(self, process_name, limit).await
+    }
+
+    async fn delete_process_runs(&self, process_name: &ProcessName) -> Result<u64> {
+        Database::delete_process_runs(self, process_name).await
+    }
+
+    async fn insert_quota_enforcement(&self, enforcement: &QuotaEnforcement, keep: usize) -> Result<()> {
+        Database::insert_quota_enforcement(self, enforcement, keep).await
+    }
+
+    async fn get_last_quota_enforcement(&self) -> Result<Option<QuotaEnforcement>> {
+        Database::get_last_quota_enforcement(self).await
+    }
+
+    async fn insert_process_note(&self, process_name: &ProcessName, author: &str, text: &str) -> Result<ProcessNote> {
+        Database::insert_process_note(self, process_name, author, text).await
+    }
+
+    async fn get_process_notes(&self, process_name: &ProcessName) -> Result<Vec<ProcessNote>> {
+        Database::get_process_notes(self, process_name).await
+    }
+
+    async fn delete_process_note(&self, process_name: &ProcessName, note_id: i64) -> Result<bool> {
+        Database::delete_process_note(self, process_name, note_id).await
+    }
+
+    async fn delete_process_notes(&self, process_name: &ProcessName) -> Result<u64> {
+        Database::delete_process_notes(self, process_name).await
+    }
+
+    async fn get_failure_history(&self, process_name: &ProcessName) -> Result<FailureHistory> {
+        Database::get_failure_history(self, process_name).await
+    }
+
+    async fn get_flapping_histories(&self) -> Result<Vec<(ProcessName, FailureHistory)>> {
+        Database::get_flapping_histories(self).await
+    }
+
+    async fn save_failure_history(&self, process_name: &ProcessName, history: &FailureHistory) -> Result<()> {
+        Database::save_failure_history(self, process_name, history).await
+    }
+
+    async fn delete_failure_history(&self, process_name: &ProcessName) -> Result<bool> {
+        Database::delete_failure_history(self, process_name).await
+    }
+
+    async fn claim_reservation(&self, reservation: &Reservation, now: DateTime<Utc>) -> Result<bool> {
+        Database::claim_reservation(self, reservation, now).await
+    }
+
+    async fn get_reservation(&self, name: &ProcessName, now: DateTime<Utc>) -> Result<Option<Reservation>> {
+        Database::get_reservation(self, name, now).await
+    }
+
+    async fn delete_reservation(&self, name: &ProcessName) -> Result<bool> {
+        Database::delete_reservation(self, name).await
+    }
+
+    async fn delete_expired_reservations(&self, now: DateTime<Utc>) -> Result<u64> {
+        Database::delete_expired_reservations(self, now).await
+    }
+
+    #[cfg(feature = "http-api")]
+    async fn insert_api_token(&self, token: &ApiToken) -> Result<()> {
+        Database::insert_api_token(self, token).await
+    }
+
+    #[cfg(feature = "http-api")]
+    async fn get_api_token_by_token(&self, token: &str) -> Result<Option<ApiToken>> {
+        Database::get_api_token_by_token(self, token).await
+    }
+
+    #[cfg(feature = "http-api")]
+    async fn get_api_token_by_public_id(&self, public_id: &str) -> Result<Option<ApiToken>> {
+        Database::get_api_token_by_public_id(self, public_id).await
+    }
+
+    #[cfg(feature = "http-api")]
+    async fn get_all_api_tokens(&self) -> Result<Vec<ApiToken>> {
+        Database::get_all_api_tokens(self).await
+    }
+
+    #[cfg(feature = "http-api")]
+    async fn update_api_token_status(&self, token: &str, is_active: bool) -> Result<bool> {
+        Database::update_api_token_status(self, token, is_active).await
+    }
+
+    #[cfg(feature = "http-api")]
+    async fn delete_api_token(&self, token: &str) -> Result<bool> {
+        Database::delete_api_token(self, token).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_local_host_name_is_the_kernel_host_name() {
+        let kernel = std::fs::read_to_string("/proc/sys/kernel/hostname").unwrap();
+        assert_eq!(local_host_name(), kernel.trim());
+    }
+}
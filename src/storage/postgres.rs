@@ -0,0 +1,1145 @@
+//! [`StorageBackend`] over a Postgres database several hosts share. Every table has a
+//! `host` column and every statement is limited to the rows of [`PostgresStorage::host`],
+//! so each pmr manages its own processes as if the database were its alone; names are
+//! unique per host. Only [`StorageBackend::list_all_hosts`] reads other hosts' rows.
+//!
+//! The columns mirror the SQLite schema, timestamps as RFC 3339 text and JSON as text, so
+//! the `row_to_*` decoders of [`Database`] read these rows too. Text that is compared or
+//! sorted (names and timestamps) is declared `COLLATE "C"` to order as SQLite does.
+//!
+//! The schema has its own migrations, [`MIGRATIONS`], run in order when a pmr connects
+//! for writing. An advisory lock keeps hosts that start together from running them twice,
+//! and `schema_version` records how many have run.
+
+use super::{HostListing, StorageBackend};
+use crate::database::{Database, LogRotationEntry, ProcessCounts, ProcessNote, ProcessRecord, ProcessRun, ProcessStatus, Reservation, StatusSummary, WatchRestartEntry};
+use crate::flapping::FailureHistory;
+use crate::logs_quota::QuotaEnforcement;
+use crate::name::ProcessName;
+use crate::{Error, Result};
+use chrono::{DateTime, Utc};
+use futures_util::stream::{BoxStream, StreamExt};
+use sqlx::postgres::{PgArguments, PgPool, PgPoolOptions};
+use sqlx::query::Query;
+use sqlx::{Postgres, Row};
+
+#[cfg(feature = "http-api")]
+use crate::database::ApiToken;
+
+/// Schema changes, in order; a database is at version N once the first N have run.
+/// Append new ones, never edit one that was released.
+const MIGRATIONS: &[&str] = &[r#"
+    CREATE TABLE processes (
+        host TEXT COLLATE "C" NOT NULL,
+        id TEXT PRIMARY KEY,
+        name TEXT COLLATE "C" NOT NULL,
+        command TEXT NOT NULL,
+        args TEXT NOT NULL,
+        env_vars TEXT NOT NULL,
+        working_dir TEXT NOT NULL,
+        pid BIGINT,
+        status TEXT NOT NULL,
+        created_at TEXT COLLATE "C" NOT NULL,
+        updated_at TEXT COLLATE "C" NOT NULL,
+        log_path TEXT NOT NULL,
+        log_dir TEXT,
+        cgroup_memory_max BIGINT,
+        cgroup_cpu_max BIGINT,
+        deleted_at TEXT COLLATE "C",
+        binary_path TEXT,
+        binary_sha256 TEXT,
+        canonical_workdir TEXT,
+        exit_code INTEGER,
+        exit_reason TEXT,
+        cpu_affinity TEXT,
+        start_snapshot TEXT,
+        default_env_keys TEXT,
+        kind TEXT,
+        runs BIGINT,
+        stop_signal INTEGER,
+        log_dir_mode TEXT,
+        status_verified_at TEXT COLLATE "C",
+        internal BOOLEAN NOT NULL DEFAULT FALSE,
+        ephemeral_keep_secs BIGINT,
+        delete_after TEXT COLLATE "C",
+        last_output TEXT,
+        last_output_lossy BOOLEAN NOT NULL DEFAULT FALSE,
+        watch TEXT,
+        umask TEXT,
+        description TEXT
+    );
+    CREATE UNIQUE INDEX idx_processes_live_name ON processes (host, name) WHERE deleted_at IS NULL;
+    CREATE INDEX idx_processes_live_created_at ON processes (host, created_at) WHERE deleted_at IS NULL;
+    CREATE INDEX idx_processes_deleted_at ON processes (host, deleted_at) WHERE deleted_at IS NOT NULL;
+    CREATE INDEX idx_processes_delete_after ON processes (host, delete_after) WHERE delete_after IS NOT NULL;
+
+    CREATE TABLE log_rotations (
+        id BIGINT GENERATED ALWAYS AS IDENTITY PRIMARY KEY,
+        host TEXT COLLATE "C" NOT NULL,
+        process_name TEXT COLLATE "C" NOT NULL,
+        rotated_at TEXT NOT NULL,
+        trigger TEXT NOT NULL,
+        rotated_from_size BIGINT NOT NULL,
+        archives_shifted BIGINT NOT NULL,
+        archives_deleted TEXT NOT NULL
+    );
+    CREATE INDEX idx_log_rotations_process ON log_rotations (host, process_name);
+
+    CREATE TABLE process_notes (
+        id BIGINT GENERATED ALWAYS AS IDENTITY PRIMARY KEY,
+        host TEXT COLLATE "C" NOT NULL,
+        process_name TEXT COLLATE "C" NOT NULL,
+        created_at TEXT NOT NULL,
+        author TEXT NOT NULL,
+        text TEXT NOT NULL
+    );
+    CREATE INDEX idx_process_notes_process ON process_notes (host, process_name);
+
+    CREATE TABLE process_failures (
+        host TEXT COLLATE "C" NOT NULL,
+        process_name TEXT COLLATE "C" NOT NULL,
+        failures TEXT NOT NULL,
+        flapping_since TEXT,
+        PRIMARY KEY (host, process_name)
+    );
+
+    CREATE TABLE watch_restarts (
+        id BIGINT GENERATED ALWAYS AS IDENTITY PRIMARY KEY,
+        host TEXT COLLATE "C" NOT NULL,
+        process_name TEXT COLLATE "C" NOT NULL,
+        restarted_at TEXT NOT NULL,
+        path TEXT NOT NULL,
+        changes BIGINT NOT NULL
+    );
+    CREATE INDEX idx_watch_restarts_process ON watch_restarts (host, process_name);
+
+    CREATE TABLE process_runs (
+        id BIGINT GENERATED ALWAYS AS IDENTITY PRIMARY KEY,
+        host TEXT COLLATE "C" NOT NULL,
+        process_name TEXT COLLATE "C" NOT NULL,
+        started_at TEXT NOT NULL,
+        ended_at TEXT NOT NULL,
+        status TEXT NOT NULL,
+        exit_code INTEGER,
+        duration_ms BIGINT NOT NULL
+    );
+    CREATE UNIQUE INDEX idx_process_runs_process ON process_runs (host, process_name, started_at);
+
+    CREATE TABLE meta (
+        host TEXT COLLATE "C" NOT NULL,
+        key TEXT NOT NULL,
+        value TEXT NOT NULL,
+        PRIMARY KEY (host, key)
+    );
+
+    CREATE TABLE logs_quota_enforcements (
+        id BIGINT GENERATED ALWAYS AS IDENTITY PRIMARY KEY,
+        host TEXT COLLATE "C" NOT NULL,
+        enforced_at TEXT NOT NULL,
+        quota_bytes BIGINT NOT NULL,
+        used_before BIGINT NOT NULL,
+        used_after BIGINT NOT NULL,
+        deleted TEXT NOT NULL
+    );
+
+    CREATE TABLE api_tokens (
+        id TEXT PRIMARY KEY,
+        host TEXT COLLATE "C" NOT NULL,
+        public_id TEXT NOT NULL,
+        token TEXT UNIQUE NOT NULL,
+        name TEXT NOT NULL,
+        created_at TEXT COLLATE "C" NOT NULL,
+        expires_at TEXT,
+        is_active BOOLEAN NOT NULL DEFAULT TRUE,
+        admin BOOLEAN NOT NULL DEFAULT FALSE
+    );
+    CREATE UNIQUE INDEX idx_api_tokens_public_id ON api_tokens (host, public_id);
+"#, r#"
+    ALTER TABLE processes ADD COLUMN rotate_on_start BOOLEAN;
+"#, r#"
+    CREATE TABLE reservations (
+        host TEXT COLLATE "C" NOT NULL,
+        name TEXT COLLATE "C" NOT NULL,
+        token TEXT NOT NULL,
+        reserved_at TEXT COLLATE "C" NOT NULL,
+        expires_at TEXT COLLATE "C" NOT NULL,
+        PRIMARY KEY (host, name)
+    );
+"#];
+
+/// Schema version this build creates and understands
+pub const SCHEMA_VERSION: i64 = MIGRATIONS.len() as i64;
+
+/// Oldest pmr release that reads a Postgres storage at [`SCHEMA_VERSION`]
+pub const MIN_BINARY_VERSION: &str = "0.3.0";
+
+/// Key of the advisory lock held while migrating ("pmr" in ASCII)
+const MIGRATION_LOCK: i64 = 0x706d72;
+
+/// Columns of `processes` a record is written to, in the order [`bind_process`] binds them
+const PROCESS_COLUMNS: &str = "id, name, command, args, env_vars, working_dir, pid, status, \
+    created_at, updated_at, log_path, log_dir, cgroup_memory_max, cgroup_cpu_max, \
+    binary_path, binary_sha256, canonical_workdir, exit_code, exit_reason, cpu_affinity, \
+    start_snapshot, default_env_keys, kind, runs, stop_signal, log_dir_mode, status_verified_at, internal, \
+    ephemeral_keep_secs, delete_after, last_output, last_output_lossy, watch, umask, description, rotate_on_start";
+
+/// Number of [`PROCESS_COLUMNS`]
+const PROCESS_COLUMN_COUNT: usize = 36;
+
+type PgQuery<'q> = Query<'q, Postgres, PgArguments>;
+
+/// Process records of one host in a shared Postgres database, see the module docs
+#[derive(Clone)]
+pub struct PostgresStorage {
+    pool: PgPool,
+    host: String,
+    /// Set by [`StorageBackend::fail_status_writes`]; shared by clones
+    #[cfg(any(test, feature = "test-util"))]
+    failing_status_writes: std::sync::Arc<std::sync::atomic::AtomicBool>,
+}
+
+impl PostgresStorage {
+    /// Connect to the database at `url` as `host`. With `migrate` pending migrations are
+    /// run first; without, a database this build cannot read as it is fails instead.
+    #[tracing::instrument(name = "db.open", skip_all)]
+    pub async fn connect(url: &str, host: &str, migrate: bool) -> Result<Self> {
+        // The URL may hold a password, so it is left out of the error
+        let pool = PgPoolOptions::new()
+            .max_connections(16)
+            .acquire_timeout(std::time::Duration::from_secs(30))
+            .connect(url)
+            .await
+            .map_err(|e| Error::Other(format!("Failed to connect to the postgres storage: {}", e)))?;
+        let storage = Self {
+            pool,
+            host: host.to_string(),
+            #[cfg(any(test, feature = "test-util"))]
+            failing_status_writes: Default::default(),
+        };
+        if migrate {
+            storage.migrate().await?;
+        }
+        let (version, min_binary_version) = storage.stored_schema_version().await?;
+        if version > SCHEMA_VERSION {
+            return Err(Error::SchemaTooNew(version, SCHEMA_VERSION, min_binary_version.unwrap_or_default()));
+        }
+        if version < SCHEMA_VERSION {
+            return Err(Error::Other(format!(
+                "the postgres storage is at schema version {} and this pmr needs {}; run a pmr command that writes to migrate it",
+                version, SCHEMA_VERSION
+            )));
+        }
+        if migrate {
+            sqlx::query("INSERT INTO meta (host, key, value) VALUES ($1, 'instance_id', $2) ON CONFLICT DO NOTHING")
+                .bind(&storage.host)
+                .bind(uuid::Uuid::new_v4().to_string())
+                .execute(&storage.pool)
+                .await?;
+        }
+        Ok(storage)
+    }
+
+    /// Run the migrations the database has not seen, all in one transaction
+    async fn migrate(&self) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("SELECT pg_advisory_xact_lock($1)").bind(MIGRATION_LOCK).execute(&mut *tx).await?;
+        sqlx::raw_sql(
+            "CREATE TABLE IF NOT EXISTS schema_version (
+                id INTEGER PRIMARY KEY CHECK (id = 1),
+                version BIGINT NOT NULL,
+                min_binary_version TEXT NOT NULL,
+                updated_at TEXT NOT NULL
+            )",
+        )
+        .execute(&mut *tx)
+        .await?;
+        let version: i64 = sqlx::query_scalar("SELECT version FROM schema_version WHERE id = 1")
+            .fetch_optional(&mut *tx)
+            .await?
+            .unwrap_or(0);
+        // A newer schema is reported by the caller
+        for migration in MIGRATIONS.iter().skip(version.max(0) as usize) {
+            sqlx::raw_sql(migration).execute(&mut *tx).await?;
+        }
+        sqlx::query(
+            "INSERT INTO schema_version (id, version, min_binary_version, updated_at) VALUES (1, $1, $2, $3)
+             ON CONFLICT (id) DO UPDATE SET
+                 version = excluded.version,
+                 min_binary_version = excluded.min_binary_version,
+                 updated_at = excluded.updated_at
+             WHERE schema_version.version < excluded.version",
+        )
+        .bind(SCHEMA_VERSION)
+        .bind(MIN_BINARY_VERSION)
+        .bind(Utc::now().to_rfc3339())
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Schema version and the oldest release that reads it; 0 for a database pmr never set up
+    async fn stored_schema_version(&self) -> Result<(i64, Option<String>)> {
+        let table_exists: bool = sqlx::query_scalar("SELECT to_regclass('schema_version') IS NOT NULL").fetch_one(&self.pool).await?;
+        if !table_exists {
+            return Ok((0, None));
+        }
+        let row = sqlx::query("SELECT version, min_binary_version FROM schema_version WHERE id = 1")
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(row.map_or((0, None), |row| (row.get("version"), row.get("min_binary_version"))))
+    }
+
+    /// The error of a status write while [`StorageBackend::fail_status_writes`] is in effect
+    fn injected_write_failure(&self) -> Result<()> {
+        #[cfg(any(test, feature = "test-util"))]
+        if self.failing_status_writes.load(std::sync::atomic::Ordering::SeqCst) {
+            let error = std::io::Error::new(std::io::ErrorKind::ReadOnlyFilesystem, "injected status write failure");
+            return Err(Error::Database(sqlx::Error::Io(error)));
+        }
+        Ok(())
+    }
+
+    async fn fetch_records(&self, query: PgQuery<'_>) -> Result<Vec<ProcessRecord>> {
+        let rows = query.fetch_all(&self.pool).await?;
+        rows.iter().map(Database::row_to_process_record).collect()
+    }
+
+    async fn fetch_record(&self, query: PgQuery<'_>) -> Result<Option<ProcessRecord>> {
+        let row = query.fetch_optional(&self.pool).await?;
+        row.as_ref().map(Database::row_to_process_record).transpose()
+    }
+
+    fn stream_records<'a>(&'a self, query: PgQuery<'a>) -> BoxStream<'a, Result<ProcessRecord>> {
+        query.fetch(&self.pool).map(|row| Database::row_to_process_record(&row?)).boxed()
+    }
+}
+
+/// `$from, $from+1, ...`, `count` placeholders
+fn placeholders(from: usize, count: usize) -> String {
+    (from..from + count).map(|index| format!("${}", index)).collect::<Vec<_>>().join(", ")
+}
+
+/// `query` with the [`PROCESS_COLUMNS`] of `process` bound
+fn bind_process<'q>(query: PgQuery<'q>, process: &'q ProcessRecord) -> Result<PgQuery<'q>> {
+    let start_snapshot_json = process.start_snapshot.as_ref().map(serde_json::to_string).transpose()?;
+    let last_output_json = (!process.last_output.is_empty()).then(|| serde_json::to_string(&process.last_output)).transpose()?;
+    let watch_json = process.watch.as_ref().map(serde_json::to_string).transpose()?;
+    Ok(query
+        .bind(&process.id)
+        .bind(&process.name)
+        .bind(&process.command)
+        .bind(serde_json::to_string(&process.args)?)
+        .bind(serde_json::to_string(&process.env_vars)?)
+        .bind(&process.working_dir)
+        .bind(process.pid.map(|p| p as i64))
+        .bind(process.status.to_string())
+        .bind(process.created_at.to_rfc3339())
+        .bind(process.updated_at.to_rfc3339())
+        .bind(&process.log_path)
+        .bind(&process.log_dir)
+        .bind(process.cgroup_memory_max.map(|m| m as i64))
+        .bind(process.cgroup_cpu_max.map(|c| c as i64))
+        .bind(&process.binary_path)
+        .bind(&process.binary_sha256)
+        .bind(&process.canonical_workdir)
+        .bind(process.exit_code)
+        .bind(&process.exit_reason)
+        .bind(&process.cpu_affinity)
+        .bind(start_snapshot_json)
+        .bind(serde_json::to_string(&process.default_env_keys)?)
+        .bind(process.kind.to_string())
+        .bind(process.runs as i64)
+        .bind(process.stop_signal)
+        .bind(process.log_dir_mode.to_string())
+        .bind(process.status_verified_at.map(|at| at.to_rfc3339()))
+        .bind(process.internal)
+        .bind(process.ephemeral_keep_secs.map(|secs| secs as i64))
+        .bind(process.delete_after.map(|at| at.to_rfc3339()))
+        .bind(last_output_json)
+        .bind(process.last_output_lossy)
+        .bind(watch_json)
+        .bind(&process.umask)
+        .bind(&process.description)
+        .bind(process.rotate_on_start))
+}
+
+#[async_trait::async_trait]
+impl StorageBackend for PostgresStorage {
+    fn host(&self) -> &str {
+        &self.host
+    }
+
+    #[tracing::instrument(name = "db.query", skip_all)]
+    async fn list_all_hosts(&self) -> Result<Vec<HostListing>> {
+        let rows = sqlx::query("SELECT * FROM processes WHERE deleted_at IS NULL AND NOT internal ORDER BY host, created_at DESC")
+            .fetch_all(&self.pool)
+            .await?;
+        let mut listings: Vec<HostListing> = Vec::new();
+        for row in &rows {
+            let host: String = row.get("host");
+            let process = Database::row_to_process_record(row)?;
+            match listings.last_mut() {
+                Some(listing) if listing.host == host => listing.processes.push(process),
+                _ => listings.push(HostListing { current: host == self.host, host, processes: vec![process] }),
+            }
+        }
+        if !listings.iter().any(|listing| listing.current) {
+            let at = listings.partition_point(|listing| listing.host < self.host);
+            listings.insert(at, HostListing { host: self.host.clone(), current: true, processes: Vec::new() });
+        }
+        Ok(listings)
+    }
+
+    #[tracing::instrument(name = "db.query", skip_all)]
+    async fn ping(&self) -> Result<()> {
+        sqlx::query("SELECT 1").execute(&self.pool).await?;
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "db.query", skip_all)]
+    async fn instance_id(&self) -> Result<Option<String>> {
+        Ok(sqlx::query_scalar("SELECT value FROM meta WHERE host = $1 AND key = 'instance_id'")
+            .bind(&self.host)
+            .fetch_optional(&self.pool)
+            .await?)
+    }
+
+    #[cfg(any(test, feature = "test-util"))]
+    fn fail_status_writes(&self, fail: bool) {
+        self.failing_status_writes.store(fail, std::sync::atomic::Ordering::SeqCst);
+    }
+
+    #[tracing::instrument(name = "db.query", skip_all)]
+    async fn insert_process(&self, process: &ProcessRecord) -> Result<()> {
+        let sql = format!(
+            "INSERT INTO processes (host, {}) VALUES ($1, {})",
+            PROCESS_COLUMNS,
+            placeholders(2, PROCESS_COLUMN_COUNT)
+        );
+        bind_process(sqlx::query(&sql).bind(&self.host), process)?.execute(&self.pool).await?;
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "db.query", skip_all)]
+    async fn replace_process(&self, process: &ProcessRecord) -> Result<bool> {
+        let sql = format!(
+            "UPDATE processes SET ({}) = ROW({}) WHERE host = $1 AND name = ${} AND deleted_at IS NULL",
+            PROCESS_COLUMNS,
+            placeholders(2, PROCESS_COLUMN_COUNT),
+            PROCESS_COLUMN_COUNT + 2
+        );
+        let result = bind_process(sqlx::query(&sql).bind(&self.host), process)?
+            .bind(&process.name)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    #[tracing::instrument(name = "db.query", skip_all)]
+    async fn get_process_by_name(&self, name: &ProcessName) -> Result<Option<ProcessRecord>> {
+        self.fetch_record(sqlx::query("SELECT * FROM processes WHERE host = $1 AND name = $2 AND deleted_at IS NULL").bind(&self.host).bind(name))
+            .await
+    }
+
+    #[tracing::instrument(name = "db.query", skip_all)]
+    async fn get_all_processes(&self) -> Result<Vec<ProcessRecord>> {
+        self.fetch_records(sqlx::query("SELECT * FROM processes WHERE host = $1 AND deleted_at IS NULL ORDER BY created_at DESC").bind(&self.host))
+            .await
+    }
+
+    #[tracing::instrument(name = "db.query", skip_all)]
+    async fn search_processes(&self, text: &str) -> Result<Vec<ProcessRecord>> {
+        // strpos rather than LIKE, so `%` and `_` in the text match themselves
+        self.fetch_records(
+            sqlx::query(
+                "SELECT * FROM processes WHERE host = $1 AND deleted_at IS NULL \
+                 AND (strpos(lower(name), lower($2)) > 0 OR strpos(lower(coalesce(description, '')), lower($2)) > 0) \
+                 ORDER BY created_at DESC",
+            )
+            .bind(&self.host)
+            .bind(text),
+        )
+        .await
+    }
+
+    #[tracing::instrument(name = "db.query", skip_all)]
+    async fn get_process_names(&self, prefix: Option<&str>) -> Result<Vec<String>> {
+        Ok(sqlx::query_scalar(
+            "SELECT name FROM processes WHERE host = $1 AND deleted_at IS NULL AND NOT internal AND starts_with(name, $2) ORDER BY name",
+        )
+        .bind(&self.host)
+        .bind(prefix.unwrap_or(""))
+        .fetch_all(&self.pool)
+        .await?)
+    }
+
+    #[tracing::instrument(name = "db.query", skip_all)]
+    async fn get_processes_by_status(&self, statuses: &[ProcessStatus]) -> Result<Vec<ProcessRecord>> {
+        let statuses: Vec<String> = statuses.iter().map(|status| status.to_string()).collect();
+        self.fetch_records(
+            sqlx::query("SELECT * FROM processes WHERE host = $1 AND status = ANY($2) AND deleted_at IS NULL ORDER BY created_at DESC")
+                .bind(&self.host)
+                .bind(statuses),
+        )
+        .await
+    }
+
+    #[tracing::instrument(name = "db.query", skip_all)]
+    async fn get_watched_processes(&self) -> Result<Vec<ProcessRecord>> {
+        self.fetch_records(
+            sqlx::query("SELECT * FROM processes WHERE host = $1 AND deleted_at IS NULL AND watch IS NOT NULL AND status = $2 ORDER BY name")
+                .bind(&self.host)
+                .bind(ProcessStatus::Running.to_string()),
+        )
+        .await
+    }
+
+    #[tracing::instrument(name = "db.query", skip_all)]
+    async fn get_deleted_process_by_name(&self, name: &ProcessName) -> Result<Option<ProcessRecord>> {
+        self.fetch_record(
+            sqlx::query("SELECT * FROM processes WHERE host = $1 AND name = $2 AND deleted_at IS NOT NULL ORDER BY deleted_at DESC LIMIT 1")
+                .bind(&self.host)
+                .bind(name),
+        )
+        .await
+    }
+
+    fn stream_processes(&self) -> BoxStream<'_, Result<ProcessRecord>> {
+        self.stream_records(sqlx::query("SELECT * FROM processes WHERE host = $1 AND deleted_at IS NULL ORDER BY created_at DESC").bind(&self.host))
+    }
+
+    fn stream_deleted_processes_before(&self, cutoff: DateTime<Utc>) -> BoxStream<'_, Result<ProcessRecord>> {
+        self.stream_records(
+            sqlx::query("SELECT * FROM processes WHERE host = $1 AND deleted_at IS NOT NULL AND deleted_at <= $2 ORDER BY deleted_at ASC")
+                .bind(&self.host)
+                .bind(cutoff.to_rfc3339()),
+        )
+    }
+
+    fn stream_expired_ephemeral_processes(&self, now: DateTime<Utc>) -> BoxStream<'_, Result<ProcessRecord>> {
+        self.stream_records(
+            sqlx::query(
+                "SELECT * FROM processes WHERE host = $1 AND deleted_at IS NULL AND delete_after IS NOT NULL AND delete_after <= $2 AND status != $3 ORDER BY delete_after ASC",
+            )
+            .bind(&self.host)
+            .bind(now.to_rfc3339())
+            .bind(ProcessStatus::Running.to_string()),
+        )
+    }
+
+    #[tracing::instrument(name = "db.query", skip_all)]
+    async fn get_log_paths(&self) -> Result<Vec<(String, String)>> {
+        Ok(sqlx::query_as("SELECT name, log_path FROM processes WHERE host = $1 ORDER BY name")
+            .bind(&self.host)
+            .fetch_all(&self.pool)
+            .await?)
+    }
+
+    #[tracing::instrument(name = "db.query", skip_all)]
+    async fn count_processes_with_log_path(&self, log_path: &str) -> Result<usize> {
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM processes WHERE host = $1 AND deleted_at IS NULL AND log_path = $2")
+            .bind(&self.host)
+            .bind(log_path)
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(count as usize)
+    }
+
+    #[tracing::instrument(name = "db.query", skip_all)]
+    async fn count_processes(&self) -> Result<usize> {
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM processes WHERE host = $1 AND deleted_at IS NULL AND NOT internal")
+            .bind(&self.host)
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(count as usize)
+    }
+
+    #[tracing::instrument(name = "db.query", skip_all)]
+    async fn count_processes_by_status(&self) -> Result<ProcessCounts> {
+        let rows: Vec<(String, i64)> =
+            sqlx::query_as("SELECT status, COUNT(*) FROM processes WHERE host = $1 AND deleted_at IS NULL AND NOT internal GROUP BY status")
+                .bind(&self.host)
+                .fetch_all(&self.pool)
+                .await?;
+        let mut counts = ProcessCounts::default();
+        for (status, count) in rows {
+            counts.add(&Database::parse_status(&status), count as usize);
+        }
+        Ok(counts)
+    }
+
+    #[tracing::instrument(name = "db.query", skip_all)]
+    async fn summarize_processes_by_status(&self) -> Result<StatusSummary> {
+        let rows: Vec<(String, i64, String)> = sqlx::query_as(
+            "SELECT status, COUNT(*), MIN(updated_at) FROM processes WHERE host = $1 AND deleted_at IS NULL AND NOT internal GROUP BY status",
+        )
+        .bind(&self.host)
+        .fetch_all(&self.pool)
+        .await?;
+        let mut summary = StatusSummary::default();
+        for (status, count, oldest) in rows {
+            summary.add(&Database::parse_status(&status), count as usize, &oldest)?;
+        }
+        Ok(summary)
+    }
+
+    #[tracing::instrument(name = "db.query", skip_all)]
+    async fn update_process_status(&self, name: &ProcessName, status: ProcessStatus, pid: Option<u32>, at: DateTime<Utc>) -> Result<()> {
+        self.injected_write_failure()?;
+        sqlx::query("UPDATE processes SET status = $3, pid = $4, updated_at = $5 WHERE host = $1 AND name = $2 AND deleted_at IS NULL")
+            .bind(&self.host)
+            .bind(name)
+            .bind(status.to_string())
+            .bind(pid.map(|p| p as i64))
+            .bind(at.to_rfc3339())
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "db.query", skip_all)]
+    async fn reset_for_restart(&self, name: &ProcessName, at: DateTime<Utc>) -> Result<Option<ProcessRecord>> {
+        self.injected_write_failure()?;
+        let mut tx = self.pool.begin().await?;
+        let Some(row) = sqlx::query("SELECT * FROM processes WHERE host = $1 AND name = $2 AND deleted_at IS NULL FOR UPDATE")
+            .bind(&self.host)
+            .bind(name)
+            .fetch_optional(&mut *tx)
+            .await?
+        else {
+            return Ok(None);
+        };
+        let mut process = Database::row_to_process_record(&row)?;
+        process.status = ProcessStatus::Stopped;
+        process.pid = None;
+        process.updated_at = at;
+        sqlx::query("UPDATE processes SET status = $3, pid = NULL, updated_at = $4 WHERE host = $1 AND id = $2")
+            .bind(&self.host)
+            .bind(&process.id)
+            .bind(process.status.to_string())
+            .bind(process.updated_at.to_rfc3339())
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(Some(process))
+    }
+
+    #[tracing::instrument(name = "db.query", skip_all)]
+    async fn update_working_dir(&self, name: &ProcessName, working_dir: &str) -> Result<bool> {
+        let result = sqlx::query("UPDATE processes SET working_dir = $3 WHERE host = $1 AND name = $2 AND deleted_at IS NULL")
+            .bind(&self.host)
+            .bind(name)
+            .bind(working_dir)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    #[tracing::instrument(name = "db.query", skip_all)]
+    async fn update_description(&self, name: &ProcessName, description: Option<&str>) -> Result<bool> {
+        let result = sqlx::query("UPDATE processes SET description = $3 WHERE host = $1 AND name = $2 AND deleted_at IS NULL")
+            .bind(&self.host)
+            .bind(name)
+            .bind(description)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    #[tracing::instrument(name = "db.query", skip_all)]
+    async fn mark_status_verified(&self, ids: &[String], at: DateTime<Utc>) -> Result<()> {
+        self.injected_write_failure()?;
+        sqlx::query("UPDATE processes SET status_verified_at = $3 WHERE host = $1 AND id = ANY($2)")
+            .bind(&self.host)
+            .bind(ids)
+            .bind(at.to_rfc3339())
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "db.query", skip_all)]
+    async fn schedule_deletion(&self, id: &str, at: DateTime<Utc>) -> Result<()> {
+        self.injected_write_failure()?;
+        sqlx::query("UPDATE processes SET delete_after = $3 WHERE host = $1 AND id = $2 AND delete_after IS NULL")
+            .bind(&self.host)
+            .bind(id)
+            .bind(at.to_rfc3339())
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "db.query", skip_all)]
+    async fn set_last_output(&self, id: &str, lines: &[String], lossy: bool) -> Result<()> {
+        self.injected_write_failure()?;
+        sqlx::query("UPDATE processes SET last_output = $3, last_output_lossy = $4 WHERE host = $1 AND id = $2")
+            .bind(&self.host)
+            .bind(id)
+            .bind(serde_json::to_string(lines)?)
+            .bind(lossy)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "db.query", skip_all)]
+    async fn record_exit(&self, name: &ProcessName, status: ProcessStatus, exit_code: Option<i32>, exit_reason: Option<&str>, at: DateTime<Utc>) -> Result<()> {
+        self.injected_write_failure()?;
+        sqlx::query(
+            "UPDATE processes SET status = $3, exit_code = $4, exit_reason = $5, updated_at = $6 WHERE host = $1 AND name = $2 AND deleted_at IS NULL",
+        )
+        .bind(&self.host)
+        .bind(name)
+        .bind(status.to_string())
+        .bind(exit_code)
+        .bind(exit_reason)
+        .bind(at.to_rfc3339())
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "db.query", skip_all)]
+    async fn delete_process(&self, name: &ProcessName) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM processes WHERE host = $1 AND name = $2 AND deleted_at IS NULL")
+            .bind(&self.host)
+            .bind(name)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    #[tracing::instrument(name = "db.query", skip_all)]
+    async fn delete_process_by_id(&self, id: &str) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM processes WHERE host = $1 AND id = $2")
+            .bind(&self.host)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    #[tracing::instrument(name = "db.query", skip_all)]
+    async fn soft_delete_process_at(&self, name: &ProcessName, deleted_at: DateTime<Utc>) -> Result<bool> {
+        let result = sqlx::query("UPDATE processes SET deleted_at = $3, updated_at = $3 WHERE host = $1 AND name = $2 AND deleted_at IS NULL")
+            .bind(&self.host)
+            .bind(name)
+            .bind(deleted_at.to_rfc3339())
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    #[tracing::instrument(name = "db.query", skip_all)]
+    async fn restore_process(&self, id: &str) -> Result<bool> {
+        let result = sqlx::query("UPDATE processes SET deleted_at = NULL, updated_at = $3 WHERE host = $1 AND id = $2 AND deleted_at IS NOT NULL")
+            .bind(&self.host)
+            .bind(id)
+            .bind(Utc::now().to_rfc3339())
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    #[tracing::instrument(name = "db.query", skip_all)]
+    async fn insert_log_rotation(&self, entry: &LogRotationEntry) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO log_rotations (host, process_name, rotated_at, trigger, rotated_from_size, archives_shifted, archives_deleted) \
+             VALUES ($1, $2, $3, $4, $5, $6, $7)",
+        )
+        .bind(&self.host)
+        .bind(&entry.process_name)
+        .bind(entry.rotated_at.to_rfc3339())
+        .bind(&entry.trigger)
+        .bind(entry.rotated_from_size as i64)
+        .bind(entry.archives_shifted as i64)
+        .bind(serde_json::to_string(&entry.archives_deleted)?)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "db.query", skip_all)]
+    async fn get_log_rotations(&self, process_name: &ProcessName) -> Result<Vec<LogRotationEntry>> {
+        let rows = sqlx::query("SELECT * FROM log_rotations WHERE host = $1 AND process_name = $2 ORDER BY id")
+            .bind(&self.host)
+            .bind(process_name)
+            .fetch_all(&self.pool)
+            .await?;
+        rows.iter().map(Database::row_to_log_rotation).collect()
+    }
+
+    #[tracing::instrument(name = "db.query", skip_all)]
+    async fn delete_log_rotations(&self, process_name: &ProcessName) -> Result<u64> {
+        let result = sqlx::query("DELETE FROM log_rotations WHERE host = $1 AND process_name = $2")
+            .bind(&self.host)
+            .bind(process_name)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    #[tracing::instrument(name = "db.query", skip_all)]
+    async fn insert_watch_restart(&self, entry: &WatchRestartEntry, keep: usize) -> Result<()> {
+        sqlx::query("INSERT INTO watch_restarts (host, process_name, restarted_at, path, changes) VALUES ($1, $2, $3, $4, $5)")
+            .bind(&self.host)
+            .bind(&entry.process_name)
+            .bind(entry.restarted_at.to_rfc3339())
+            .bind(&entry.path)
+            .bind(entry.changes as i64)
+            .execute(&self.pool)
+            .await?;
+        sqlx::query(
+            "DELETE FROM watch_restarts WHERE host = $1 AND process_name = $2 \
+             AND id NOT IN (SELECT id FROM watch_restarts WHERE host = $1 AND process_name = $2 ORDER BY id DESC LIMIT $3)",
+        )
+        .bind(&self.host)
+        .bind(&entry.process_name)
+        .bind(keep as i64)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "db.query", skip_all)]
+    async fn get_watch_restarts(&self, process_name: &ProcessName) -> Result<Vec<WatchRestartEntry>> {
+        let rows = sqlx::query("SELECT * FROM watch_restarts WHERE host = $1 AND process_name = $2 ORDER BY id")
+            .bind(&self.host)
+            .bind(process_name)
+            .fetch_all(&self.pool)
+            .await?;
+        rows.iter().map(Database::row_to_watch_restart).collect()
+    }
+
+    #[tracing::instrument(name = "db.query", skip_all)]
+    async fn delete_watch_restarts(&self, process_name: &ProcessName) -> Result<u64> {
+        let result = sqlx::query("DELETE FROM watch_restarts WHERE host = $1 AND process_name = $2")
+            .bind(&self.host)
+            .bind(process_name)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    #[tracing::instrument(name = "db.query", skip_all)]
+    async fn insert_process_run(&self, run: &ProcessRun, keep: usize) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO process_runs (host, process_name, started_at, ended_at, status, exit_code, duration_ms) \
+             VALUES ($1, $2, $3, $4, $5, $6, $7) ON CONFLICT DO NOTHING",
+        )
+        .bind(&self.host)
+        .bind(&run.process_name)
+        .bind(run.started_at.to_rfc3339())
+        .bind(run.ended_at.to_rfc3339())
+        .bind(run.status.to_string())
+        .bind(run.exit_code)
+        .bind(run.duration_ms as i64)
+        .execute(&self.pool)
+        .await?;
+        sqlx::query(
+            "DELETE FROM process_runs WHERE host = $1 AND process_name = $2 \
+             AND id NOT IN (SELECT id FROM process_runs WHERE host = $1 AND process_name = $2 ORDER BY id DESC LIMIT $3)",
+        )
+        .bind(&self.host)
+        .bind(&run.process_name)
+        .bind(keep as i64)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "db.query", skip_all)]
+    async fn get_process_runs(&self, process_name: &ProcessName, limit: usize) -> Result<Vec<ProcessRun>> {
+        let rows = sqlx::query(
+            "SELECT * FROM (SELECT * FROM process_runs WHERE host = $1 AND process_name = $2 ORDER BY id DESC LIMIT $3) AS latest ORDER BY id",
+        )
+        .bind(&self.host)
+        .bind(process_name)
+        .bind(limit as i64)
+        .fetch_all(&self.pool)
+        .await?;
+        rows.iter().map(Database::row_to_process_run).collect()
+    }
+
+    #[tracing::instrument(name = "db.query", skip_all)]
+    async fn delete_process_runs(&self, process_name: &ProcessName) -> Result<u64> {
+        let result = sqlx::query("DELETE FROM process_runs WHERE host = $1 AND process_name = $2")
+            .bind(&self.host)
+            .bind(process_name)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    #[tracing::instrument(name = "db.query", skip_all)]
+    async fn insert_quota_enforcement(&self, enforcement: &QuotaEnforcement, keep: usize) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO logs_quota_enforcements (host, enforced_at, quota_bytes, used_before, used_after, deleted) VALUES ($1, $2, $3, $4, $5, $6)",
+        )
+        .bind(&self.host)
+        .bind(enforcement.enforced_at.to_rfc3339())
+        .bind(enforcement.quota_bytes as i64)
+        .bind(enforcement.used_before as i64)
+        .bind(enforcement.used_after as i64)
+        .bind(serde_json::to_string(&enforcement.deleted)?)
+        .execute(&self.pool)
+        .await?;
+        sqlx::query(
+            "DELETE FROM logs_quota_enforcements WHERE host = $1 \
+             AND id NOT IN (SELECT id FROM logs_quota_enforcements WHERE host = $1 ORDER BY id DESC LIMIT $2)",
+        )
+        .bind(&self.host)
+        .bind(keep as i64)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "db.query", skip_all)]
+    async fn get_last_quota_enforcement(&self) -> Result<Option<QuotaEnforcement>> {
+        let row = sqlx::query("SELECT * FROM logs_quota_enforcements WHERE host = $1 ORDER BY id DESC LIMIT 1")
+            .bind(&self.host)
+            .fetch_optional(&self.pool)
+            .await?;
+        row.as_ref().map(Database::row_to_quota_enforcement).transpose()
+    }
+
+    #[tracing::instrument(name = "db.query", skip_all)]
+    async fn insert_process_note(&self, process_name: &ProcessName, author: &str, text: &str) -> Result<ProcessNote> {
+        let created_at = Utc::now();
+        let id: i64 =
+            sqlx::query_scalar("INSERT INTO process_notes (host, process_name, created_at, author, text) VALUES ($1, $2, $3, $4, $5) RETURNING id")
+                .bind(&self.host)
+                .bind(process_name)
+                .bind(created_at.to_rfc3339())
+                .bind(author)
+                .bind(text)
+                .fetch_one(&self.pool)
+                .await?;
+        Ok(ProcessNote {
+            id,
+            process_name: process_name.to_string(),
+            created_at,
+            author: author.to_string(),
+            text: text.to_string(),
+        })
+    }
+
+    #[tracing::instrument(name = "db.query", skip_all)]
+    async fn get_process_notes(&self, process_name: &ProcessName) -> Result<Vec<ProcessNote>> {
+        let rows = sqlx::query("SELECT * FROM process_notes WHERE host = $1 AND process_name = $2 ORDER BY id")
+            .bind(&self.host)
+            .bind(process_name)
+            .fetch_all(&self.pool)
+            .await?;
+        rows.iter().map(Database::row_to_process_note).collect()
+    }
+
+    #[tracing::instrument(name = "db.query", skip_all)]
+    async fn delete_process_note(&self, process_name: &ProcessName, note_id: i64) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM process_notes WHERE host = $1 AND process_name = $2 AND id = $3")
+            .bind(&self.host)
+            .bind(process_name)
+            .bind(note_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    #[tracing::instrument(name = "db.query", skip_all)]
+    async fn delete_process_notes(&self, process_name: &ProcessName) -> Result<u64> {
+        let result = sqlx::query("DELETE FROM process_notes WHERE host = $1 AND process_name = $2")
+            .bind(&self.host)
+            .bind(process_name)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    #[tracing::instrument(name = "db.query", skip_all)]
+    async fn get_failure_history(&self, process_name: &ProcessName) -> Result<FailureHistory> {
+        let row = sqlx::query("SELECT * FROM process_failures WHERE host = $1 AND process_name = $2")
+            .bind(&self.host)
+            .bind(process_name)
+            .fetch_optional(&self.pool)
+            .await?;
+        match row {
+            Some(row) => Ok(Database::row_to_failure_history(&row)?.1),
+            None => Ok(FailureHistory::default()),
+        }
+    }
+
+    #[tracing::instrument(name = "db.query", skip_all)]
+    async fn get_flapping_histories(&self) -> Result<Vec<(ProcessName, FailureHistory)>> {
+        let rows = sqlx::query("SELECT * FROM process_failures WHERE host = $1 AND flapping_since IS NOT NULL ORDER BY process_name")
+            .bind(&self.host)
+            .fetch_all(&self.pool)
+            .await?;
+        rows.iter().map(Database::row_to_failure_history).collect()
+    }
+
+    #[tracing::instrument(name = "db.query", skip_all)]
+    async fn save_failure_history(&self, process_name: &ProcessName, history: &FailureHistory) -> Result<()> {
+        self.injected_write_failure()?;
+        let failures: Vec<String> = history.failures.iter().map(|at| at.to_rfc3339()).collect();
+        sqlx::query(
+            "INSERT INTO process_failures (host, process_name, failures, flapping_since) VALUES ($1, $2, $3, $4) \
+             ON CONFLICT (host, process_name) DO UPDATE SET failures = excluded.failures, flapping_since = excluded.flapping_since",
+        )
+        .bind(&self.host)
+        .bind(process_name)
+        .bind(serde_json::to_string(&failures)?)
+        .bind(history.flapping_since.map(|at| at.to_rfc3339()))
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "db.query", skip_all)]
+    async fn delete_failure_history(&self, process_name: &ProcessName) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM process_failures WHERE host = $1 AND process_name = $2")
+            .bind(&self.host)
+            .bind(process_name)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    #[tracing::instrument(name = "db.query", skip_all)]
+    async fn claim_reservation(&self, reservation: &Reservation, now: DateTime<Utc>) -> Result<bool> {
+        let result = sqlx::query(
+            "INSERT INTO reservations (host, name, token, reserved_at, expires_at) VALUES ($1, $2, $3, $4, $5) \
+             ON CONFLICT (host, name) DO UPDATE SET token = excluded.token, reserved_at = excluded.reserved_at, expires_at = excluded.expires_at \
+             WHERE reservations.expires_at <= $6 OR reservations.token = excluded.token",
+        )
+        .bind(&self.host)
+        .bind(&reservation.name)
+        .bind(&reservation.token)
+        .bind(reservation.reserved_at.to_rfc3339())
+        .bind(reservation.expires_at.to_rfc3339())
+        .bind(now.to_rfc3339())
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    #[tracing::instrument(name = "db.query", skip_all)]
+    async fn get_reservation(&self, name: &ProcessName, now: DateTime<Utc>) -> Result<Option<Reservation>> {
+        let row = sqlx::query("SELECT * FROM reservations WHERE host = $1 AND name = $2 AND expires_at > $3")
+            .bind(&self.host)
+            .bind(name)
+            .bind(now.to_rfc3339())
+            .fetch_optional(&self.pool)
+            .await?;
+        row.as_ref().map(Database::row_to_reservation).transpose()
+    }
+
+    #[tracing::instrument(name = "db.query", skip_all)]
+    async fn delete_reservation(&self, name: &ProcessName) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM reservations WHERE host = $1 AND name = $2")
+            .bind(&self.host)
+            .bind(name)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    #[tracing::instrument(name = "db.query", skip_all)]
+    async fn delete_expired_reservations(&self, now: DateTime<Utc>) -> Result<u64> {
+        let result = sqlx::query("DELETE FROM reservations WHERE host = $1 AND expires_at <= $2")
+            .bind(&self.host)
+            .bind(now.to_rfc3339())
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    #[cfg(feature = "http-api")]
+    #[tracing::instrument(name = "db.query", skip_all)]
+    async fn insert_api_token(&self, token: &ApiToken) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO api_tokens (host, id, public_id, token, name, created_at, expires_at, is_active, admin) \
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)",
+        )
+        .bind(&self.host)
+        .bind(&token.id)
+        .bind(&token.public_id)
+        .bind(&token.token)
+        .bind(&token.name)
+        .bind(token.created_at.to_rfc3339())
+        .bind(token.expires_at.map(|at| at.to_rfc3339()))
+        .bind(token.is_active)
+        .bind(token.admin)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    #[cfg(feature = "http-api")]
+    #[tracing::instrument(name = "db.query", skip_all)]
+    async fn get_api_token_by_token(&self, token: &str) -> Result<Option<ApiToken>> {
+        let row = sqlx::query("SELECT * FROM api_tokens WHERE host = $1 AND token = $2")
+            .bind(&self.host)
+            .bind(token)
+            .fetch_optional(&self.pool)
+            .await?;
+        row.as_ref().map(Database::row_to_api_token).transpose()
+    }
+
+    #[cfg(feature = "http-api")]
+    #[tracing::instrument(name = "db.query", skip_all)]
+    async fn get_api_token_by_public_id(&self, public_id: &str) -> Result<Option<ApiToken>> {
+        let row = sqlx::query("SELECT * FROM api_tokens WHERE host = $1 AND public_id = $2")
+            .bind(&self.host)
+            .bind(public_id)
+            .fetch_optional(&self.pool)
+            .await?;
+        row.as_ref().map(Database::row_to_api_token).transpose()
+    }
+
+    #[cfg(feature = "http-api")]
+    #[tracing::instrument(name = "db.query", skip_all)]
+    async fn get_all_api_tokens(&self) -> Result<Vec<ApiToken>> {
+        let rows = sqlx::query("SELECT * FROM api_tokens WHERE host = $1 ORDER BY created_at DESC")
+            .bind(&self.host)
+            .fetch_all(&self.pool)
+            .await?;
+        rows.iter().map(Database::row_to_api_token).collect()
+    }
+
+    #[cfg(feature = "http-api")]
+    #[tracing::instrument(name = "db.query", skip_all)]
+    async fn update_api_token_status(&self, token: &str, is_active: bool) -> Result<bool> {
+        let result = sqlx::query("UPDATE api_tokens SET is_active = $3 WHERE host = $1 AND token = $2")
+            .bind(&self.host)
+            .bind(token)
+            .bind(is_active)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    #[cfg(feature = "http-api")]
+    #[tracing::instrument(name = "db.query", skip_all)]
+    async fn delete_api_token(&self, token: &str) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM api_tokens WHERE host = $1 AND token = $2")
+            .bind(&self.host)
+            .bind(token)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_process_columns_match_their_count() {
+        assert_eq!(PROCESS_COLUMNS.split(',').count(), PROCESS_COLUMN_COUNT);
+        assert_eq!(placeholders(2, 3), "$2, $3, $4");
+    }
+}
@@ -0,0 +1,93 @@
+//! "Did you mean" hints for misspelled keys in user-written config files and requests.
+
+/// Edit distance (insertions, deletions, substitutions) between two strings
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// The candidate closest to `word`, if any is close enough to be a plausible typo:
+/// at most a third of the word's length away, and never more than 3 edits
+pub fn closest<'a>(word: &str, candidates: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+    let limit = (word.chars().count() / 3).clamp(1, 3);
+    candidates
+        .into_iter()
+        .map(|candidate| (edit_distance(word, candidate), candidate))
+        .filter(|(distance, _)| *distance <= limit)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+/// Add a suggestion to serde's "unknown field `x`, expected one of `a`, `b`" message,
+/// wherever it appears in `message`; other messages are returned unchanged
+pub fn explain_unknown_field(message: &str) -> String {
+    let Some(start) = message.find("unknown field `") else {
+        return message.to_string();
+    };
+    let rest = &message[start + "unknown field `".len()..];
+    let Some((field, rest)) = rest.split_once('`') else {
+        return message.to_string();
+    };
+    // The expected names run to the end of the line: `a`, `b` or `c`
+    let expected_line = rest.lines().next().unwrap_or_default();
+    let expected: Vec<&str> = expected_line.split('`').skip(1).step_by(2).collect();
+    match closest(field, expected.iter().copied()) {
+        Some(suggestion) => {
+            let line_end = start + "unknown field `".len() + field.len() + 1 + expected_line.len();
+            format!("{}; did you mean `{}`?{}", &message[..line_end], suggestion, &message[line_end..])
+        }
+        None => message.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("webhook_url", "webhook_url"), 0);
+        assert_eq!(edit_distance("webhok_url", "webhook_url"), 1);
+    }
+
+    #[test]
+    fn test_closest_only_suggests_plausible_typos() {
+        let fields = ["webhook_url", "bearer_token", "events", "timeout_ms", "max_retries"];
+        assert_eq!(closest("webhok_url", fields), Some("webhook_url"));
+        assert_eq!(closest("event", fields), Some("events"));
+        assert_eq!(closest("max_retry", fields), Some("max_retries"));
+        assert_eq!(closest("colour", fields), None);
+        assert_eq!(closest("x", fields), None);
+    }
+
+    #[test]
+    fn test_explain_unknown_field() {
+        let message = "unknown field `enviroment`, expected one of `name`, `environment` at line 1 column 12";
+        assert_eq!(
+            explain_unknown_field(message),
+            "unknown field `enviroment`, expected one of `name`, `environment` at line 1 column 12; did you mean `environment`?"
+        );
+
+        // The hint goes on the line naming the field, ahead of anything that follows
+        let message = "TOML parse error at line 2, column 1\nunknown field `evnts`, expected `events`\n";
+        assert_eq!(
+            explain_unknown_field(message),
+            "TOML parse error at line 2, column 1\nunknown field `evnts`, expected `events`; did you mean `events`?\n"
+        );
+
+        let unrelated = "unknown field `zzz`, expected `events`";
+        assert_eq!(explain_unknown_field(unrelated), unrelated);
+        assert_eq!(explain_unknown_field("invalid type: integer"), "invalid type: integer");
+    }
+}
@@ -0,0 +1,270 @@
+//! `pmr generate systemd`: unit files for running the HTTP API server under systemd, or
+//! for handing the lifecycle of one stored process to systemd while pmr keeps its logs and
+//! records.
+//!
+//! Values are written for the unit file syntax: command line words and `Environment=`
+//! assignments are quoted when they need to be, `%` is doubled so it is not read as a
+//! specifier, and `$` in command lines is doubled so it is not expanded. Paths are
+//! absolute. A user or working directory holding a control character cannot be written
+//! and is refused.
+
+use crate::config::{ConfigPaths, CONFIG_PATH_ENV, HOME_ENV};
+use crate::database::{LogDirMode, ProcessKind, ProcessRecord};
+use crate::process::signal_name;
+use crate::{Error, Result};
+use std::path::{Path, PathBuf};
+
+/// Seconds the unit of a process waits for it to be running before the start fails
+pub const WAIT_TIMEOUT_SECS: u64 = 120;
+
+/// Whether a unit is for the system manager or for a user's own (`systemctl --user`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UnitScope {
+    /// Run as `user` by the system manager
+    System { user: String },
+    /// Run by the user's manager as that user
+    User,
+}
+
+/// Where the generated unit finds pmr and its data
+#[derive(Debug, Clone)]
+pub struct UnitContext {
+    /// Absolute path of the pmr binary
+    pub pmr_binary: PathBuf,
+    /// Working directory of the unit; processes started without `--log-dir` log below it
+    pub working_dir: PathBuf,
+    pub paths: ConfigPaths,
+    pub scope: UnitScope,
+}
+
+/// Unit running `pmr serve --port <port>` in the foreground
+pub fn serve_unit(context: &UnitContext, port: u16) -> Result<String> {
+    let exec = |args: &[String]| exec_line(&context.pmr_binary, args);
+    let mut unit = Unit::new(format!("pmr generate systemd --serve --port {}", port));
+    unit.unit("Description", "pmr HTTP API server");
+    unit.unit("After", "network.target");
+    unit.service("Type", "simple");
+    context.service_environment(&mut unit)?;
+    unit.service("ExecStart", &exec(&["serve".to_string(), "--port".to_string(), port.to_string()]));
+    unit.service("Restart", "on-failure");
+    unit.service("RestartSec", "2");
+    unit.comment("Processes started through the API keep running when the server stops");
+    unit.service("KillMode", "process");
+    Ok(unit.finish(&context.scope))
+}
+
+/// Unit starting the stored definition of `process` with an idempotent `pmr start`,
+/// waiting until it runs, and stopping it with `pmr stop`. A job is run again on every
+/// start of the unit and is not waited for.
+pub fn process_unit(context: &UnitContext, process: &ProcessRecord) -> Result<String> {
+    let exec = |args: &[String]| exec_line(&context.pmr_binary, args);
+    let name = process.name.to_string();
+    let mut unit = Unit::new(format!("pmr generate systemd --process {}", name));
+    unit.unit("Description", &format!("pmr process {}", escape_specifiers(&name)));
+    unit.unit("After", "network.target");
+    unit.service("Type", "oneshot");
+    context.service_environment(&mut unit)?;
+    unit.service("ExecStart", &exec(&start_args(process)));
+    match process.kind {
+        ProcessKind::Service => {
+            unit.service("RemainAfterExit", "yes");
+            let wait = ["wait".to_string(), "--timeout".to_string(), WAIT_TIMEOUT_SECS.to_string(), name.clone()];
+            unit.service("ExecStartPost", &exec(&wait));
+            unit.service("ExecStop", &exec(&["stop".to_string(), name]));
+        }
+        // The run ends on its own; there is nothing to wait for or stop
+        ProcessKind::Job => {
+            unit.comment("The job keeps running after `pmr start` returns");
+            unit.service("KillMode", "process");
+        }
+    }
+    Ok(unit.finish(&context.scope))
+}
+
+/// Arguments of `pmr start` giving the stored definition of `process`
+fn start_args(process: &ProcessRecord) -> Vec<String> {
+    let mut args = vec!["start".to_string()];
+    match process.kind {
+        ProcessKind::Service => args.push("--idempotent".to_string()),
+        ProcessKind::Job => args.extend(["--kind".to_string(), "job".to_string(), "--rerun".to_string()]),
+    }
+    args.extend(["--workdir".to_string(), process.working_dir.clone()]);
+    let mut env: Vec<(String, String)> = process.explicit_env_vars().into_iter().collect();
+    env.sort();
+    for (key, value) in env {
+        args.extend(["--env".to_string(), format!("{}={}", key, value)]);
+    }
+    if let Some(log_dir) = &process.log_dir {
+        args.extend(["--log-dir".to_string(), log_dir.clone()]);
+    } else if process.log_dir_mode == LogDirMode::Workdir {
+        args.extend(["--log-dir-mode".to_string(), LogDirMode::Workdir.to_string()]);
+    }
+    if let Some(memory_max) = process.cgroup_memory_max {
+        args.extend(["--cgroup-memory-max".to_string(), memory_max.to_string()]);
+    }
+    if let Some(cpu_max) = process.cgroup_cpu_max {
+        args.extend(["--cgroup-cpu-max".to_string(), cpu_max.to_string()]);
+    }
+    if let Some(cpu_set) = process.cpu_set() {
+        args.extend(["--cpu-affinity".to_string(), cpu_set.to_string()]);
+    }
+    if let Some(umask) = process.umask_value() {
+        args.extend(["--umask".to_string(), umask.to_string()]);
+    }
+    if let Some(description) = &process.description {
+        args.extend(["--description".to_string(), description.clone()]);
+    }
+    if process.stop_signal != libc::SIGTERM {
+        args.extend(["--stop-signal".to_string(), signal_name(process.stop_signal)]);
+    }
+    args.push(process.name.to_string());
+    args.push(process.command.clone());
+    args.extend(process.args.iter().cloned());
+    args
+}
+
+impl UnitContext {
+    /// The user, working directory and environment every unit sets, so its pmr opens the
+    /// same database and config file as the one that generated it
+    fn service_environment(&self, unit: &mut Unit) -> Result<()> {
+        if let UnitScope::System { user } = &self.scope {
+            unit.service("User", &plain_value(user)?);
+        }
+        unit.service("WorkingDirectory", &plain_value(&absolute(&self.working_dir))?);
+        let home = self.paths.database.parent().unwrap_or(Path::new("/"));
+        unit.service("Environment", &quote_environment(HOME_ENV, &absolute(home)));
+        if let Some(config_file) = &self.paths.config_file {
+            unit.service("Environment", &quote_environment(CONFIG_PATH_ENV, &absolute(config_file)));
+        }
+        Ok(())
+    }
+}
+
+/// Lines of the `[Unit]` and `[Service]` sections, in the order they were added
+struct Unit {
+    header: String,
+    unit: Vec<String>,
+    service: Vec<String>,
+}
+
+impl Unit {
+    fn new(command: String) -> Self {
+        Self { header: format!("# Generated by `{}", escape_line_breaks(&command)), unit: Vec::new(), service: Vec::new() }
+    }
+
+    fn unit(&mut self, key: &str, value: &str) {
+        self.unit.push(format!("{}={}", key, value));
+    }
+
+    fn service(&mut self, key: &str, value: &str) {
+        self.service.push(format!("{}={}", key, value));
+    }
+
+    fn comment(&mut self, text: &str) {
+        self.service.push(format!("# {}", text));
+    }
+
+    fn finish(self, scope: &UnitScope) -> String {
+        let (flag, wanted_by) = match scope {
+            UnitScope::System { .. } => ("", "multi-user.target"),
+            UnitScope::User => (" --user", "default.target"),
+        };
+        format!(
+            "{}{}`\n[Unit]\n{}\n\n[Service]\n{}\n\n[Install]\nWantedBy={}\n",
+            self.header,
+            flag,
+            self.unit.join("\n"),
+            self.service.join("\n"),
+            wanted_by
+        )
+    }
+}
+
+/// `ExecStart=`-style command line running `pmr` with `args`
+fn exec_line(pmr: &Path, args: &[String]) -> String {
+    std::iter::once(absolute(pmr)).chain(args.iter().cloned()).map(|word| quote_word(&word)).collect::<Vec<_>>().join(" ")
+}
+
+/// One word of a command line: specifiers and variables escaped, and quoted when it is
+/// empty, holds whitespace, quotes, backslashes or control characters, or is a lone `;`
+pub fn quote_word(word: &str) -> String {
+    let escaped = escape_specifiers(word).replace('$', "$$");
+    let needs_quotes = escaped.is_empty()
+        || escaped == ";"
+        || escaped.chars().any(|c| c.is_whitespace() || c.is_control() || matches!(c, '"' | '\'' | '\\'));
+    if needs_quotes {
+        quoted(&escaped)
+    } else {
+        escaped
+    }
+}
+
+/// `KEY=value` for `Environment=`, always quoted; variables are not expanded there
+fn quote_environment(key: &str, value: &str) -> String {
+    quoted(&escape_specifiers(&format!("{}={}", key, value)))
+}
+
+/// Double-quoted with C-style escapes, which systemd reads back inside quotes
+fn quoted(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => quoted.push_str(&format!("\\x{:02x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// A value taken as is to the end of the line, like `User=` or `WorkingDirectory=`
+fn plain_value(value: &str) -> Result<String> {
+    if value.chars().any(char::is_control) {
+        return Err(Error::Other(format!("Cannot write {:?} into a systemd unit: it holds a control character", value)));
+    }
+    Ok(escape_specifiers(value))
+}
+
+fn escape_specifiers(value: &str) -> String {
+    value.replace('%', "%%")
+}
+
+/// Line breaks of a comment, which would end it early
+fn escape_line_breaks(value: &str) -> String {
+    value.replace('\n', "\\n").replace('\r', "\\r")
+}
+
+fn absolute(path: &Path) -> String {
+    std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf()).to_string_lossy().into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quote_word() {
+        assert_eq!(quote_word("serve"), "serve");
+        assert_eq!(quote_word("/srv/my app"), "\"/srv/my app\"");
+        assert_eq!(quote_word(""), "\"\"");
+        assert_eq!(quote_word(";"), "\";\"");
+        assert_eq!(quote_word("50%"), "50%%");
+        assert_eq!(quote_word("$HOME"), "$$HOME");
+        assert_eq!(quote_word("say \"hi\"\n"), "\"say \\\"hi\\\"\\n\"");
+        assert_eq!(quote_word("C:\\logs"), "\"C:\\\\logs\"");
+    }
+
+    #[test]
+    fn test_environment_is_quoted_and_plain_values_refuse_line_breaks() {
+        assert_eq!(quote_environment("PMR_HOME", "/home/a b/.pmr"), "\"PMR_HOME=/home/a b/.pmr\"");
+        assert_eq!(quote_environment("RATE", "5%"), "\"RATE=5%%\"");
+        assert_eq!(plain_value("/srv/100% app").unwrap(), "/srv/100%% app");
+        assert!(plain_value("/srv/a\nb").is_err());
+    }
+}
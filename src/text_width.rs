@@ -0,0 +1,102 @@
+//! Text measured in terminal cells, for lining up the columns of text tables. CJK
+//! characters and most emoji take two cells, combining marks and zero-width joiners none,
+//! so padding with `{:<20}`, which counts chars, leaves rows holding them ragged.
+//!
+//! Text is cut between grapheme clusters, never inside one: an accented letter keeps its
+//! combining mark and a family emoji is not split at a joiner.
+
+use std::borrow::Cow;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+/// Marks text cut short by [`truncate`]; one cell wide
+const ELLIPSIS: char = '…';
+
+/// Cells `text` takes in a terminal
+pub fn width(text: &str) -> usize {
+    text.graphemes(true).map(UnicodeWidthStr::width).sum()
+}
+
+/// `text` followed by spaces up to `cells`; longer text is left as is
+pub fn pad(text: &str, cells: usize) -> String {
+    let padding = cells.saturating_sub(width(text));
+    format!("{}{}", text, " ".repeat(padding))
+}
+
+/// `text` cut to at most `cells`, ending in an ellipsis when anything was left out
+pub fn truncate(text: &str, cells: usize) -> Cow<'_, str> {
+    if width(text) <= cells {
+        return Cow::Borrowed(text);
+    }
+    let Some(room) = cells.checked_sub(1) else {
+        return Cow::Borrowed("");
+    };
+    let mut used = 0;
+    let mut cut = String::new();
+    for grapheme in text.graphemes(true) {
+        let grapheme_width = grapheme.width();
+        if used + grapheme_width > room {
+            break;
+        }
+        used += grapheme_width;
+        cut.push_str(grapheme);
+    }
+    cut.push(ELLIPSIS);
+    Cow::Owned(cut)
+}
+
+/// `text` taking exactly `cells`: cut short with an ellipsis or padded with spaces
+pub fn fit(text: &str, cells: usize) -> String {
+    pad(&truncate(text, cells), cells)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLES: [&str; 7] = [
+        "web",
+        "数据同步服务",
+        "deploy 🚀 now",
+        // e followed by a combining acute accent
+        "cafe\u{301} latte",
+        // man, woman, girl joined by zero-width joiners
+        "family \u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467} app",
+        "ｆｕｌｌｗｉｄｔｈ",
+        "",
+    ];
+
+    #[test]
+    fn test_width_counts_cells() {
+        assert_eq!(width("web"), 3);
+        assert_eq!(width("数据同步服务"), 12);
+        assert_eq!(width("🚀"), 2);
+        assert_eq!(width("cafe\u{301}"), 4);
+        assert_eq!(width("\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}"), 2);
+    }
+
+    #[test]
+    fn test_truncate_keeps_grapheme_clusters_whole() {
+        assert_eq!(truncate("web", 3), "web");
+        assert_eq!(truncate("数据同步服务", 7), "数据同…");
+        // The next character is two cells wide and does not fit beside the ellipsis
+        assert_eq!(truncate("数据同步服务", 8), "数据同…");
+        assert_eq!(truncate("cafe\u{301} latte", 5), "cafe\u{301}…");
+        assert_eq!(truncate("x\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}yz", 4), "x\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}…");
+        assert_eq!(truncate("x\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}yz", 3), "x…");
+        assert_eq!(truncate("web", 1), "…");
+        assert_eq!(truncate("web", 0), "");
+    }
+
+    #[test]
+    fn test_fit_takes_exactly_the_cells_asked_for() {
+        for sample in SAMPLES {
+            for cells in 0..24 {
+                let fitted = fit(sample, cells);
+                assert_eq!(width(&fitted), cells, "{:?} in {} cells: {:?}", sample, cells, fitted);
+            }
+        }
+        assert_eq!(pad("数据", 6), "数据  ");
+        assert_eq!(pad("数据同步服务", 6), "数据同步服务");
+    }
+}
@@ -0,0 +1,188 @@
+//! Self-profiling for `--timings`: a minimal `tracing` subscriber that times pmr spans
+//! from creation to close and aggregates them per operation name.
+//!
+//! Nothing is installed unless timings are requested, so instrumented code only pays
+//! for a disabled callsite check.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Metadata, Subscriber};
+
+/// Aggregated time spent in one kind of operation
+#[derive(Debug, Clone, PartialEq)]
+pub struct OperationTiming {
+    pub name: &'static str,
+    pub count: usize,
+    pub total: Duration,
+    pub max: Duration,
+}
+
+struct OpenSpan {
+    name: &'static str,
+    started: Instant,
+    refs: usize,
+}
+
+#[derive(Default)]
+struct State {
+    open: HashMap<u64, OpenSpan>,
+    /// Finished operations in order of first completion
+    finished: Vec<OperationTiming>,
+}
+
+/// Collects span timings; cheap to clone, all clones share the same data
+#[derive(Clone, Default)]
+pub struct TimingCollector {
+    state: Arc<Mutex<State>>,
+    next_id: Arc<AtomicU64>,
+}
+
+impl TimingCollector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Install as the process-wide subscriber; fails if another one is already set
+    pub fn install_global(&self) -> crate::Result<()> {
+        tracing::subscriber::set_global_default(self.clone())
+            .map_err(|e| crate::Error::Other(format!("Failed to enable timings: {}", e)))
+    }
+
+    /// Collect spans entered on the current thread until the guard is dropped
+    pub fn set_default(&self) -> tracing::subscriber::DefaultGuard {
+        tracing::subscriber::set_default(self.clone())
+    }
+
+    /// Operations seen so far, in the order they first completed
+    pub fn report(&self) -> Vec<OperationTiming> {
+        self.state.lock().unwrap().finished.clone()
+    }
+
+    /// Render the report as a small text table
+    pub fn format_table(&self) -> String {
+        let report = self.report();
+        let mut output = format!("{:<20} {:>6} {:>12} {:>12}\n", "OPERATION", "COUNT", "TOTAL", "MAX");
+        for timing in &report {
+            output.push_str(&format!(
+                "{:<20} {:>6} {:>12} {:>12}\n",
+                timing.name,
+                timing.count,
+                format_duration(timing.total),
+                format_duration(timing.max)
+            ));
+        }
+        if report.is_empty() {
+            output.push_str("(no operations recorded)\n");
+        }
+        output
+    }
+
+    fn finish(state: &mut State, span: OpenSpan) {
+        let elapsed = span.started.elapsed();
+        match state.finished.iter_mut().find(|timing| timing.name == span.name) {
+            Some(timing) => {
+                timing.count += 1;
+                timing.total += elapsed;
+                timing.max = timing.max.max(elapsed);
+            }
+            None => state.finished.push(OperationTiming {
+                name: span.name,
+                count: 1,
+                total: elapsed,
+                max: elapsed,
+            }),
+        }
+    }
+}
+
+fn format_duration(duration: Duration) -> String {
+    format!("{:.3}ms", duration.as_secs_f64() * 1000.0)
+}
+
+impl Subscriber for TimingCollector {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        // Only pmr's own spans are timed; events and dependency spans are ignored
+        metadata.is_span() && metadata.target().starts_with("pmr")
+    }
+
+    fn new_span(&self, attributes: &Attributes<'_>) -> Id {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        self.state.lock().unwrap().open.insert(
+            id,
+            OpenSpan {
+                name: attributes.metadata().name(),
+                started: Instant::now(),
+                refs: 1,
+            },
+        );
+        Id::from_u64(id)
+    }
+
+    fn record(&self, _span: &Id, _values: &Record<'_>) {}
+
+    fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+    fn event(&self, _event: &Event<'_>) {}
+
+    fn enter(&self, _span: &Id) {}
+
+    fn exit(&self, _span: &Id) {}
+
+    fn clone_span(&self, id: &Id) -> Id {
+        if let Some(span) = self.state.lock().unwrap().open.get_mut(&id.into_u64()) {
+            span.refs += 1;
+        }
+        id.clone()
+    }
+
+    fn try_close(&self, id: Id) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let closed = match state.open.get_mut(&id.into_u64()) {
+            Some(span) => {
+                span.refs -= 1;
+                span.refs == 0
+            }
+            None => return false,
+        };
+        if closed {
+            if let Some(span) = state.open.remove(&id.into_u64()) {
+                Self::finish(&mut state, span);
+            }
+        }
+        closed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spans_are_aggregated_by_name() {
+        let collector = TimingCollector::new();
+        {
+            let _guard = collector.set_default();
+            for _ in 0..3 {
+                let _span = tracing::info_span!("db.query").entered();
+            }
+            let span = tracing::info_span!("spawn");
+            let clone = span.clone();
+            drop(span);
+            assert!(collector.report().iter().all(|timing| timing.name != "spawn"));
+            drop(clone);
+        }
+
+        let report = collector.report();
+        assert_eq!(report.len(), 2);
+        assert_eq!((report[0].name, report[0].count), ("db.query", 3));
+        assert_eq!((report[1].name, report[1].count), ("spawn", 1));
+        assert!(report[0].max <= report[0].total);
+
+        let table = collector.format_table();
+        assert!(table.starts_with("OPERATION"));
+        assert!(table.contains("db.query"));
+    }
+}
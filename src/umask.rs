@@ -0,0 +1,82 @@
+//! File mode creation mask of started processes, given in octal like the shell's `umask`
+//! (`--umask 027`). Without one a process inherits the umask of the pmr that starts it,
+//! as any child does, so the modes of the files it creates depend on where pmr runs.
+
+use crate::{Error, Result};
+use std::fmt;
+use std::str::FromStr;
+
+/// A umask between 0 and 0777
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Umask(u32);
+
+impl Umask {
+    pub fn bits(self) -> u32 {
+        self.0
+    }
+
+    /// Make a command set this umask in its child before it executes
+    pub fn apply_to(self, command: &mut tokio::process::Command) {
+        let mask = self.0 as libc::mode_t;
+        // SAFETY: the hook only calls umask, which is async-signal-safe and cannot fail
+        unsafe {
+            command.pre_exec(move || {
+                libc::umask(mask);
+                Ok(())
+            });
+        }
+    }
+}
+
+impl FromStr for Umask {
+    type Err = Error;
+
+    /// Octal digits with an optional `0o` prefix, e.g. `022`, `0027` or `0o77`
+    fn from_str(value: &str) -> Result<Self> {
+        let invalid = |reason: &str| Error::InvalidUmask(format!("'{}': {}", value, reason));
+        let digits = value.trim();
+        let digits = digits.strip_prefix("0o").unwrap_or(digits);
+        if digits.is_empty() {
+            return Err(invalid("no octal digits"));
+        }
+        if !digits.chars().all(|c| c.is_digit(8)) {
+            return Err(invalid("only the octal digits 0-7 are allowed"));
+        }
+        match u32::from_str_radix(digits, 8) {
+            Ok(bits) if bits <= 0o777 => Ok(Self(bits)),
+            _ => Err(invalid("must be between 0 and 0777")),
+        }
+    }
+}
+
+impl fmt::Display for Umask {
+    /// Four octal digits, as `umask` prints it
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04o}", self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_display() {
+        for (value, bits) in [("022", 0o022), ("0027", 0o027), ("0o77", 0o077), ("0", 0), ("777", 0o777), (" 007 ", 0o007)] {
+            let umask: Umask = value.parse().unwrap();
+            assert_eq!(umask.bits(), bits, "{}", value);
+        }
+        assert_eq!("22".parse::<Umask>().unwrap().to_string(), "0022");
+        assert_eq!("0".parse::<Umask>().unwrap().to_string(), "0000");
+    }
+
+    #[test]
+    fn test_malformed_umasks_are_refused() {
+        for value in ["", "0o", "089", "0x1f", "-022", "22a", "1000", "0777777777777", "0 22"] {
+            let err = value.parse::<Umask>().unwrap_err();
+            assert!(matches!(err, Error::InvalidUmask(_)), "{:?} gave {:?}", value, err);
+        }
+        let err = "1000".parse::<Umask>().unwrap_err();
+        assert_eq!(err.to_string(), "Invalid umask '1000': must be between 0 and 0777");
+    }
+}
@@ -0,0 +1,173 @@
+use crate::{
+    database::{ProcessRecord, ProcessStatus},
+    format::human_bytes,
+    formatter::Formatter,
+    process::ResourceUsage,
+};
+use chrono::{DateTime, Local};
+
+/// Number of status transitions kept on screen while watching
+const MAX_CHANGES: usize = 5;
+
+/// Renders successive snapshots of a single process for `pmr status --watch`,
+/// remembering status transitions between refreshes
+#[derive(Default)]
+pub struct StatusWatch {
+    last_status: Option<ProcessStatus>,
+    changes: Vec<String>,
+}
+
+impl StatusWatch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record the latest status, returning a description if it changed since the last refresh
+    pub fn observe(&mut self, status: &ProcessStatus, at: DateTime<Local>) -> Option<String> {
+        let change = match &self.last_status {
+            Some(previous) if previous != status => Some(format!(
+                "status changed {} → {} at {}",
+                previous,
+                status,
+                at.format("%H:%M:%S")
+            )),
+            _ => None,
+        };
+        self.last_status = Some(status.clone());
+
+        if let Some(change) = &change {
+            self.changes.push(change.clone());
+            if self.changes.len() > MAX_CHANGES {
+                self.changes.remove(0);
+            }
+        }
+        change
+    }
+
+    /// Render one text snapshot: status, resource usage, transitions and the log tail
+    pub fn render(
+        &mut self,
+        formatter: &Formatter,
+        process: &ProcessRecord,
+        usage: Option<&ResourceUsage>,
+        log_tail: &str,
+        at: DateTime<Local>,
+    ) -> String {
+        self.observe(&process.status, at);
+
+        let mut output = String::new();
+        output.push_str(&format!("Watching '{}' (updated {})\n\n", process.name, at.format("%H:%M:%S")));
+        output.push_str(&formatter.format_process_status(process));
+
+        if let Some(usage) = usage {
+            output.push_str(&format!("Memory (RSS): {}\n", human_bytes(usage.rss_kb.saturating_mul(1024))));
+            output.push_str(&format!("Threads: {}\n", usage.threads));
+        }
+
+        if !self.changes.is_empty() {
+            output.push_str("\nStatus changes:\n");
+            for change in &self.changes {
+                output.push_str(&format!("  >> {}\n", change));
+            }
+        }
+
+        output.push_str("\nRecent log output:\n");
+        if log_tail.trim().is_empty() {
+            output.push_str("  (empty)\n");
+        } else {
+            for line in log_tail.lines() {
+                output.push_str(&format!("  {}\n", line));
+            }
+        }
+
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::OutputFormat;
+    use chrono::{TimeZone, Utc};
+    use std::collections::HashMap;
+
+    fn record(status: ProcessStatus) -> ProcessRecord {
+        ProcessRecord {
+            id: "id".to_string(),
+            name: "web".parse().unwrap(),
+            command: "sleep".to_string(),
+            args: vec!["60".to_string()],
+            env_vars: HashMap::new(),
+            working_dir: "/tmp".to_string(),
+            pid: Some(42),
+            status,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            log_path: "/tmp/web.log".to_string(),
+            log_dir: None,
+            cgroup_memory_max: None,
+            cgroup_cpu_max: None,
+            cgroup: None,
+            deleted_at: None,
+            binary_path: None,
+            binary_sha256: None,
+            canonical_workdir: None,
+            exit_code: None,
+            exit_reason: None,
+            cpu_affinity: None,
+            umask: None,
+            description: None,
+            start_snapshot: None,
+            default_env_keys: Vec::new(),
+            drift: None,
+            notes: Vec::new(),
+            recent_runs: Vec::new(),
+            flapping: false,
+            degraded: false,
+            inferred: false,
+            truncated_fields: Vec::new(),
+            kind: crate::database::ProcessKind::Service,
+            runs: 1,
+            stop_signal: libc::SIGTERM,
+            log_dir_mode: crate::database::LogDirMode::Default,
+            status_verified_at: None,
+            internal: false,
+            ephemeral_keep_secs: None,
+            delete_after: None,
+            last_output: Vec::new(),
+            last_output_lossy: false,
+            watch: None,
+            rotate_on_start: None,
+        }
+    }
+
+    #[test]
+    fn test_status_change_is_highlighted() {
+        let formatter = Formatter::new(OutputFormat::Text);
+        let mut watch = StatusWatch::new();
+        let first = Local.with_ymd_and_hms(2025, 1, 1, 12, 3, 10).unwrap();
+        let second = Local.with_ymd_and_hms(2025, 1, 1, 12, 3, 11).unwrap();
+
+        let output = watch.render(&formatter, &record(ProcessStatus::Stopped), None, "", first);
+        assert!(!output.contains("status changed"));
+        assert!(output.contains("(empty)"));
+
+        let output = watch.render(&formatter, &record(ProcessStatus::Running), None, "line one\nline two", second);
+        assert!(output.contains(">> status changed stopped → running at 12:03:11"));
+        assert!(output.contains("  line two"));
+
+        // An unchanged status keeps the history but adds nothing new
+        assert_eq!(watch.observe(&ProcessStatus::Running, second), None);
+    }
+
+    #[test]
+    fn test_change_history_is_bounded() {
+        let mut watch = StatusWatch::new();
+        let at = Local::now();
+        for i in 0..20 {
+            let status = if i % 2 == 0 { ProcessStatus::Running } else { ProcessStatus::Stopped };
+            watch.observe(&status, at);
+        }
+        assert_eq!(watch.changes.len(), MAX_CHANGES);
+    }
+}
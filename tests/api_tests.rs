@@ -1,15 +1,34 @@
+#[cfg(feature = "http-api")]
+mod support;
+
 #[cfg(feature = "http-api")]
 mod http_api_tests {
+    use axum::{
+        extract::{Path, Query, State},
+        http::StatusCode,
+        Json,
+    };
     use pmr::{
-        api::{ApiServer, AuthManager},
-        config::{Config, LogRotationConfig},
+        api::{
+            handlers::{
+                add_process_note, bulk_start_processes, Authenticated, check_process_name, delete_process_note, get_process_logs, get_process_status, inspect_process, list_process_notes,
+                list_process_names, list_rotated_logs, LogsQuery, NamesQuery,
+                reload_config, reserve_process_name, restart_process, start_process, stop_process, AddNoteRequest, ReserveQuery, RestartQuery,
+                StartProcessRequest, StartQuery,
+                StatusQuery, StopQuery,
+            },
+            log_broker::{LogBroker, LogFrame, LogSubscription},
+            ApiServer, AuthManager,
+        },
+        config::{Config, FlappingConfig, LogRotationConfig},
         process::ProcessManager,
-        database::Database,
+        database::{ApiToken, Database},
     };
     use std::collections::HashMap;
     use std::sync::{Arc, Mutex};
     use tempfile::TempDir;
     use tokio::time::{sleep, Duration};
+    use crate::support::named;
 
     /// Helper function to create test components
     async fn create_test_components() -> (Arc<ProcessManager>, Arc<Mutex<AuthManager>>, TempDir) {
@@ -121,6 +140,100 @@ mod http_api_tests {
         assert!(token_values.contains(&api_token2.token));
     }
 
+    #[tokio::test]
+    async fn test_auth_manager_expiry_and_lookup() {
+        let temp_dir = TempDir::new().unwrap();
+        let db_path = temp_dir.path().join("test.db");
+        let database_url = format!("sqlite:{}?mode=rwc", db_path.display());
+        let database = std::sync::Arc::new(Database::new(&database_url).await.unwrap());
+        let auth_manager = AuthManager::new(database);
+
+        let ci = auth_manager.generate_token("ci".to_string(), Some("12h".parse().unwrap())).await.unwrap();
+        let lifetime = ci.expires_at.unwrap() - ci.created_at;
+        assert_eq!(lifetime.num_hours(), 12);
+        assert!(auth_manager.validate_token(&ci.token).await);
+
+        // By name, or by a prefix of the token string
+        assert_eq!(auth_manager.find_token("ci").await.unwrap().id, ci.id);
+        assert_eq!(auth_manager.find_token(&ci.token[..10]).await.unwrap().id, ci.id);
+        assert!(auth_manager.find_token("no-such-token").await.is_err());
+
+        // A prefix shared by every token is ambiguous
+        auth_manager.generate_token("other".to_string(), None).await.unwrap();
+        let err = auth_manager.find_token("").await.unwrap_err().to_string();
+        assert!(err.contains("matches 2 tokens"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_tokens_carry_a_public_id() {
+        let temp_dir = TempDir::new().unwrap();
+        let database_url = format!("sqlite:{}?mode=rwc", temp_dir.path().join("test.db").display());
+        let auth_manager = AuthManager::new(Arc::new(Database::new(&database_url).await.unwrap()));
+
+        let api_token = auth_manager.generate_token("deploy".to_string(), None).await.unwrap();
+        assert!(pmr::api::auth::is_token_id(&api_token.public_id), "{}", api_token.public_id);
+        let (public_id, secret) = pmr::api::auth::split_token(&api_token.token).unwrap();
+        assert_eq!(public_id, api_token.public_id);
+        assert_eq!(auth_manager.find_token(public_id).await.unwrap().id, api_token.id);
+
+        // The id finds the row, but only the whole value authenticates
+        assert_eq!(auth_manager.authenticate(&api_token.token).await.unwrap().id, api_token.id);
+        assert!(!auth_manager.validate_token(public_id).await);
+        assert!(!auth_manager.validate_token(&format!("{}_{}x", public_id, secret)).await);
+        assert!(!auth_manager.validate_token(secret).await);
+
+        auth_manager.revoke_token(public_id).await.unwrap();
+        assert!(!auth_manager.validate_token(&api_token.token).await);
+        assert!(auth_manager.revoke_token("pmr_00000000").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_existing_tokens_get_a_derived_public_id() {
+        let temp_dir = TempDir::new().unwrap();
+        let database_url = format!("sqlite:{}?mode=rwc", temp_dir.path().join("test.db").display());
+        Database::new(&database_url).await.unwrap().close().await;
+
+        // Put the table back the way it was before public ids
+        let pool = sqlx::SqlitePool::connect(&database_url).await.unwrap();
+        for statement in [
+            "DROP INDEX idx_api_tokens_public_id",
+            "ALTER TABLE api_tokens DROP COLUMN public_id",
+            "CREATE INDEX idx_api_tokens_token ON api_tokens(token)",
+        ] {
+            sqlx::query(statement).execute(&pool).await.unwrap();
+        }
+        for (id, token) in [("old-1", "N2mVqk0r-first-legacy-token"), ("old-2", "Zq81-second-legacy-token")] {
+            sqlx::query("INSERT INTO api_tokens (id, token, name, created_at, is_active) VALUES (?, ?, ?, ?, 1)")
+                .bind(id)
+                .bind(token)
+                .bind(id)
+                .bind(chrono::Utc::now().to_rfc3339())
+                .execute(&pool)
+                .await
+                .unwrap();
+        }
+        pool.close().await;
+
+        let auth_manager = AuthManager::new(Arc::new(Database::new(&database_url).await.unwrap()));
+        let tokens = auth_manager.list_tokens().await.unwrap();
+        assert_eq!(tokens.len(), 2);
+        assert_ne!(tokens[0].public_id, tokens[1].public_id);
+        for token in &tokens {
+            assert!(pmr::api::auth::is_token_id(&token.public_id), "{}", token.public_id);
+        }
+
+        // Old values keep working as they are, and can be revoked by their new id
+        let first = tokens.iter().find(|token| token.id == "old-1").unwrap();
+        assert_eq!(auth_manager.token_name("N2mVqk0r-first-legacy-token").await.as_deref(), Some("old-1"));
+        auth_manager.revoke_token(&first.public_id).await.unwrap();
+        assert!(!auth_manager.validate_token("N2mVqk0r-first-legacy-token").await);
+        assert!(auth_manager.validate_token("Zq81-second-legacy-token").await);
+
+        // Ids are stable across opens
+        let reopened = AuthManager::new(Arc::new(Database::new(&database_url).await.unwrap()));
+        assert_eq!(reopened.find_token(&first.public_id).await.unwrap().id, "old-1");
+    }
+
     #[tokio::test]
     async fn test_auth_manager_persistence() {
         let temp_dir = TempDir::new().unwrap();
@@ -144,6 +257,7 @@ mod http_api_tests {
     // For now, we focus on unit tests of the components
     
     #[tokio::test]
+    #[allow(clippy::await_holding_lock)]
     async fn test_api_components_integration() {
         let (process_manager, auth_manager, _temp_dir) = create_test_components().await;
         
@@ -156,7 +270,7 @@ mod http_api_tests {
         // Start a test process
         let env_vars = HashMap::new();
         process_manager
-            .start_process("api_test", "echo", vec!["Hello API".to_string()], env_vars, None, None)
+            .start(&named("api_test"), "echo", vec!["Hello API".to_string()], env_vars, None, None)
             .await
             .unwrap();
         
@@ -164,11 +278,11 @@ mod http_api_tests {
         sleep(Duration::from_millis(300)).await;
         
         // Get process status
-        let status = process_manager.get_process_status("api_test").await.unwrap();
+        let status = process_manager.get_process_status(&named("api_test")).await.unwrap();
         assert_eq!(status.name, "api_test");
         
         // Get process logs
-        let logs = process_manager.get_process_logs("api_test", None).await.unwrap();
+        let logs = process_manager.get_process_logs(&named("api_test"), None).await.unwrap();
         assert!(logs.contains("Hello API"));
         
         // List processes
@@ -183,77 +297,2050 @@ mod http_api_tests {
         
         // Clean up
         drop(auth);
-        process_manager.delete_process("api_test").await.unwrap();
+        process_manager.delete(&named("api_test")).await.unwrap();
     }
 
-    #[tokio::test]
-    async fn test_api_error_handling() {
-        let (process_manager, _auth_manager, _temp_dir) = create_test_components().await;
-        
-        // Test getting non-existent process
-        let result = process_manager.get_process_status("nonexistent").await;
-        assert!(result.is_err());
-        
-        // Test stopping non-existent process
-        let result = process_manager.stop_process("nonexistent").await;
-        assert!(result.is_err());
-        
-        // Test deleting non-existent process
-        let result = process_manager.delete_process("nonexistent").await;
-        assert!(result.is_err());
-        
-        // Test getting logs for non-existent process
-        let result = process_manager.get_process_logs("nonexistent", None).await;
-        assert!(result.is_err());
+    /// What the router's auth middleware hands handlers for requests made with `token`
+    fn authenticated(token: &ApiToken) -> Authenticated {
+        Authenticated(token.clone())
     }
 
-    #[tokio::test]
-    async fn test_concurrent_api_operations() {
-        let (process_manager, _auth_manager, _temp_dir) = create_test_components().await;
-        
-        // Start multiple processes concurrently
-        let mut handles = vec![];
-        
-        for i in 0..5 {
-            let pm = process_manager.clone();
-            let handle = tokio::spawn(async move {
-                let env_vars = HashMap::new();
-                pm.start_process(
-                    &format!("concurrent_{}", i),
-                    "echo",
-                    vec![format!("Process {}", i)],
-                    env_vars,
-                    None,
-                    None,
-                )
-                .await
-            });
-            handles.push(handle);
+    fn start_request(name: &str, command: &str, args: &[&str]) -> StartProcessRequest {
+        StartProcessRequest {
+            name: name.to_string(),
+            command: command.to_string(),
+            args: args.iter().map(|a| a.to_string()).collect(),
+            env_vars: None,
+            working_dir: None,
+            log_dir: None,
+            log_dir_mode: None,
+            cpu_affinity: None,
+            umask: None,
+            description: None,
+            idempotent: false,
+            snapshot: false,
+            strict: false,
+            kind: Default::default(),
+            rerun: false,
+            stop_signal: None,
+            ephemeral: false,
+            ephemeral_keep: None,
+            watch: Vec::new(),
+            watch_ignore: Vec::new(),
+            watch_debounce_ms: None,
+            queue_on_pressure: false,
+            rotate_on_start: None,
         }
-        
-        // Wait for all processes to start
-        for handle in handles {
-            handle.await.unwrap().unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_bulk_start_mixed_success_and_conflict() {
+        let (process_manager, auth_manager, _temp_dir) = create_test_components().await;
+        let api_token = AuthManager::new(process_manager.get_database())
+            .generate_token("bulk".to_string(), None)
+            .await
+            .unwrap();
+
+        process_manager
+            .start(&named("bulk_existing"), "sleep", vec!["5".to_string()], HashMap::new(), None, None)
+            .await
+            .unwrap();
+
+        let requests = vec![
+            start_request("bulk_new_1", "sleep", &["5"]),
+            start_request("bulk_existing", "sleep", &["5"]),
+            start_request("bulk_new_2", "echo", &["hello"]),
+        ];
+
+        let (status, Json(response)) = bulk_start_processes(
+            State((process_manager.clone(), auth_manager.clone())),
+            authenticated(&api_token),
+            Json(requests),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(status, StatusCode::OK);
+        assert!(response.success);
+        let results = response.data.unwrap();
+        let names: Vec<&str> = results.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, vec!["bulk_new_1", "bulk_existing", "bulk_new_2"]);
+        assert!(results[0].success);
+        assert_eq!(results[0].status_code, 200);
+        assert!(!results[1].success);
+        assert_eq!(results[1].status_code, 409);
+        assert!(results[1].error.is_some());
+        assert!(results[2].success);
+
+        for name in ["bulk_new_1", "bulk_existing", "bulk_new_2"] {
+            process_manager.delete(&named(name)).await.unwrap();
         }
-        
-        // Wait for processes to complete
-        sleep(Duration::from_millis(500)).await;
-        
-        // List all processes
-        let processes = process_manager.list_processes().await.unwrap();
-        assert_eq!(processes.len(), 5);
-        
-        // Clean up all processes
-        for i in 0..5 {
-            process_manager
-                .delete_process(&format!("concurrent_{}", i))
+    }
+
+    #[cfg(feature = "test-util")]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_bulk_start_queues_on_pressure_when_asked() {
+        let (process_manager, auth_manager, _temp_dir) = create_test_components().await;
+        let api_token = AuthManager::new(process_manager.get_database())
+            .generate_token("bulk".to_string(), None)
+            .await
+            .unwrap();
+
+        let queued = |name: &str| StartProcessRequest { queue_on_pressure: true, ..start_request(name, "sleep", &["5"]) };
+        let requests = vec![queued("bulk_queued_1"), queued("bulk_queued_2"), start_request("bulk_unqueued", "sleep", &["5"])];
+
+        process_manager.simulate_spawn_pressure(true);
+        let relief = {
+            let process_manager = process_manager.clone();
+            tokio::spawn(async move {
+                sleep(Duration::from_millis(300)).await;
+                process_manager.simulate_spawn_pressure(false);
+            })
+        };
+        let (status, Json(response)) = bulk_start_processes(
+            State((process_manager.clone(), auth_manager.clone())),
+            authenticated(&api_token),
+            Json(requests),
+        )
+        .await
+        .unwrap();
+        relief.await.unwrap();
+
+        assert_eq!(status, StatusCode::OK);
+        let results = response.data.unwrap();
+        assert!(results[0].success, "{:?}", results[0].error);
+        assert!(results[1].success, "{:?}", results[1].error);
+        assert!(!results[2].success);
+        assert!(results[2].error.as_deref().unwrap().contains("Resource temporarily unavailable"));
+
+        for name in ["bulk_queued_1", "bulk_queued_2"] {
+            process_manager.delete(&named(name)).await.unwrap();
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_bulk_start_rejects_duplicates_before_starting() {
+        let (process_manager, auth_manager, _temp_dir) = create_test_components().await;
+        let api_token = AuthManager::new(process_manager.get_database())
+            .generate_token("bulk".to_string(), None)
+            .await
+            .unwrap();
+
+        let requests = vec![
+            start_request("bulk_dup", "sleep", &["5"]),
+            start_request("bulk_other", "sleep", &["5"]),
+            start_request("bulk_dup", "sleep", &["5"]),
+        ];
+
+        let (status, Json(response)) = bulk_start_processes(
+            State((process_manager.clone(), auth_manager.clone())),
+            authenticated(&api_token),
+            Json(requests),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(!response.success);
+        let results = response.data.unwrap();
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].status_code, 424);
+        assert_eq!(results[1].status_code, 424);
+        assert_eq!(results[2].status_code, 400);
+
+        // Nothing from the batch may have been started
+        assert!(process_manager.list_processes().await.unwrap().is_empty());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_bulk_start_rejects_invalid_cpu_affinity() {
+        let (process_manager, auth_manager, _temp_dir) = create_test_components().await;
+        let api_token = AuthManager::new(process_manager.get_database())
+            .generate_token("bulk".to_string(), None)
+            .await
+            .unwrap();
+
+        let mut pinned = start_request("bulk_pinned", "sleep", &["5"]);
+        pinned.cpu_affinity = Some("3-1".to_string());
+        let requests = vec![start_request("bulk_plain", "sleep", &["5"]), pinned];
+
+        let (status, Json(response)) = bulk_start_processes(
+            State((process_manager.clone(), auth_manager.clone())),
+            authenticated(&api_token),
+            Json(requests),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let results = response.data.unwrap();
+        assert_eq!(results[1].status_code, 400);
+        assert!(results[1].error.as_deref().unwrap().contains("Invalid CPU affinity"));
+        assert!(process_manager.list_processes().await.unwrap().is_empty());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_status_snapshot_only_on_request() {
+        let (process_manager, auth_manager, _temp_dir) = create_test_components().await;
+        let api_token = AuthManager::new(process_manager.get_database())
+            .generate_token("snapshot".to_string(), None)
+            .await
+            .unwrap();
+        let state = || State((process_manager.clone(), auth_manager.clone()));
+
+        let mut request = start_request("api_snapshot", "sleep", &["30"]);
+        request.snapshot = true;
+        let Json(response) = start_process(state(), authenticated(&api_token), Query(StartQuery { return_value: None }), Json(request)).await.unwrap();
+        assert!(response.success);
+
+        let status = |snapshot| {
+            get_process_status(
+                state(),
+                authenticated(&api_token),
+                Path(named("api_snapshot")),
+                Query(StatusQuery { snapshot, notes: None, refresh: None, truncate: None }),
+            )
+        };
+        let Json(response) = status(None).await.unwrap();
+        assert!(response.data.unwrap().start_snapshot.is_none());
+        let Json(response) = status(Some(true)).await.unwrap();
+        let snapshot = response.data.unwrap().start_snapshot.expect("snapshot requested");
+        assert_eq!(
+            snapshot.environ.unwrap().get("PMR_PROCESS_NAME").map(String::as_str),
+            Some("api_snapshot")
+        );
+
+        process_manager.delete(&named("api_snapshot")).await.unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_start_can_return_the_stored_record() {
+        let (process_manager, auth_manager, _temp_dir) = create_test_components().await;
+        let api_token = AuthManager::new(process_manager.get_database())
+            .generate_token("start_record".to_string(), None)
+            .await
+            .unwrap();
+        let state = || State((process_manager.clone(), auth_manager.clone()));
+        let start = |name: &str, return_value: Option<&str>| {
+            start_process(
+                state(),
+                authenticated(&api_token),
+                Query(StartQuery { return_value: return_value.map(str::to_string) }),
+                Json(start_request(name, "sleep", &["30"])),
+            )
+        };
+
+        let Json(response) = start("api_record", Some("record")).await.unwrap();
+        assert!(response.success);
+        let outcome = response.data.clone().unwrap();
+        let record = response.record.clone().expect("record asked for");
+        assert_eq!(response.message.as_deref(), Some(format!("Process 'api_record' started with PID {}", outcome.pid.unwrap()).as_str()));
+        assert_eq!(record.pid, outcome.pid);
+
+        let Json(stored) = get_process_status(
+            state(),
+            authenticated(&api_token),
+            Path(named("api_record")),
+            Query(StatusQuery { snapshot: None, notes: None, refresh: Some("false".to_string()), truncate: None }),
+        )
+        .await
+        .unwrap();
+        let returned = serde_json::to_value(&record).unwrap();
+        let stored = serde_json::to_value(stored.data.unwrap()).unwrap();
+        for field in ["id", "name", "command", "args", "env_vars", "working_dir", "pid", "status", "created_at", "log_path"] {
+            assert_eq!(returned[field].to_string(), stored[field].to_string(), "{}", field);
+        }
+
+        // The default answer stays the outcome alone
+        let Json(response) = start("api_outcome", None).await.unwrap();
+        let body = serde_json::to_value(&response).unwrap();
+        assert!(body.get("record").is_none() && body.get("message").is_none(), "{}", body);
+        let Json(response) = start("api_outcome_named", Some("outcome")).await.unwrap();
+        assert!(response.record.is_none() && response.data.is_some());
+
+        let (status, Json(response)) = start("api_other", Some("pid")).await.unwrap_err();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(response.error.unwrap().contains("Unknown return value 'pid'"));
+        assert!(process_manager.get_process_status(&named("api_other")).await.is_err());
+
+        for name in ["api_record", "api_outcome", "api_outcome_named"] {
+            process_manager.delete(&named(name)).await.unwrap();
+        }
+    }
+
+    #[test]
+    fn test_start_request_refuses_a_repeated_env_key() {
+        let parse = |body: &str| serde_json::from_str::<StartProcessRequest>(body);
+        let error = parse(r#"{"name": "web", "command": "server", "env_vars": {"DATABASE_URL": "a", "DATABASE_URL": "b"}}"#)
+            .err()
+            .unwrap();
+        assert!(error.to_string().contains("key 'DATABASE_URL' is given more than once ('a' and 'b')"), "{}", error);
+
+        let request = parse(r#"{"name": "web", "command": "server", "env_vars": {"A": "1", "B": "2"}}"#).unwrap();
+        assert_eq!(request.env_vars.unwrap().len(), 2);
+        assert!(parse(r#"{"name": "web", "command": "server", "env_vars": null}"#).unwrap().env_vars.is_none());
+        assert!(parse(r#"{"name": "web", "command": "server"}"#).unwrap().env_vars.is_none());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_status_and_inspect_carry_recent_runs() {
+        let (process_manager, auth_manager, _temp_dir) = create_test_components().await;
+        let api_token = AuthManager::new(process_manager.get_database())
+            .generate_admin_token("runs".to_string(), None)
+            .await
+            .unwrap();
+        let state = || State((process_manager.clone(), auth_manager.clone()));
+        process_manager.start(&named("api_runs"), "sleep", vec!["30".to_string()], HashMap::new(), None, None).await.unwrap();
+        process_manager.stop(&named("api_runs")).await.unwrap();
+
+        let Json(status) = get_process_status(
+            state(),
+            authenticated(&api_token),
+            Path(named("api_runs")),
+            Query(StatusQuery { snapshot: None, notes: None, refresh: None, truncate: None }),
+        )
+        .await
+        .unwrap();
+        let status = serde_json::to_value(status.data.unwrap()).unwrap();
+        assert_eq!(status["recent_runs"].as_array().unwrap().len(), 1, "{}", status);
+        assert_eq!(status["recent_runs"][0]["status"], "Stopped");
+        assert!(status["recent_runs"][0]["duration_ms"].is_u64());
+
+        let Json(inspect) = inspect_process(state(), authenticated(&api_token), Path(named("api_runs"))).await.unwrap();
+        let inspect = serde_json::to_value(inspect.data.unwrap()).unwrap();
+        assert_eq!(inspect["history"]["runs"], status["recent_runs"]);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_reserved_name_goes_to_the_reserving_token() {
+        let (process_manager, auth_manager, temp_dir) = create_test_components().await;
+        let tokens = AuthManager::new(process_manager.get_database());
+        let provisioner = tokens.generate_token("provisioner".to_string(), None).await.unwrap();
+        let other = tokens.generate_token("other".to_string(), None).await.unwrap();
+        let state = || State((process_manager.clone(), auth_manager.clone()));
+        let reserve = |token: &ApiToken, ttl: Option<&str>| {
+            reserve_process_name(state(), authenticated(token), Path(named("api_reserved")), Query(ReserveQuery { ttl: ttl.map(str::to_string) }))
+        };
+        let start = |token: &ApiToken| {
+            start_process(state(), authenticated(token), Query(StartQuery { return_value: None }), Json(start_request("api_reserved", "sleep", &["30"])))
+        };
+
+        let (status, Json(check)) = check_process_name(state(), authenticated(&provisioner), Path(named("api_reserved"))).await.unwrap();
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert!(!check.data.unwrap().taken());
+
+        // Two provisioners racing for the name: exactly one gets it
+        let (first, second) = tokio::join!(reserve(&provisioner, Some("1h")), reserve(&other, Some("1h")));
+        let (winner, loser) = match (first, second) {
+            (Ok(_), Err(_)) => (&provisioner, &other),
+            (Err(_), Ok(_)) => (&other, &provisioner),
+            (first, second) => panic!("expected one reservation to win: {:?} / {:?}", first, second),
+        };
+        let (status, Json(response)) = reserve(loser, None).await.unwrap_err();
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(response.error.unwrap(), format!("Process name 'api_reserved' is reserved by token {}", winner.public_id));
+        // Renewing is the holder's to do
+        let Json(renewed) = reserve(winner, Some("10m")).await.unwrap();
+        assert_eq!(renewed.data.unwrap().token, winner.public_id);
+
+        let (status, Json(check)) = check_process_name(state(), authenticated(loser), Path(named("api_reserved"))).await.unwrap();
+        assert_eq!(status, StatusCode::OK);
+        let check = check.data.unwrap();
+        assert!(!check.exists && check.reservation.is_some());
+
+        // Another token's start is refused before it creates anything
+        let (status, Json(response)) = start(loser).await.unwrap_err();
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert!(response.error.unwrap().contains(&winner.public_id));
+        assert!(!temp_dir.path().join("logs").join("api_reserved.log").exists());
+
+        // The holder's start converts the reservation into the process
+        let Json(response) = start(winner).await.unwrap();
+        assert!(response.success);
+        let (status, Json(check)) = check_process_name(state(), authenticated(loser), Path(named("api_reserved"))).await.unwrap();
+        assert_eq!(status, StatusCode::OK);
+        let check = check.data.unwrap();
+        assert!(check.exists && check.reservation.is_none());
+        let (status, _) = reserve(winner, None).await.unwrap_err();
+        assert_eq!(status, StatusCode::CONFLICT);
+
+        let (status, Json(response)) =
+            reserve_process_name(state(), authenticated(winner), Path(named("api_other")), Query(ReserveQuery { ttl: Some("2d".to_string()) }))
                 .await
-                .unwrap();
+                .unwrap_err();
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{:?}", response.error);
+        process_manager.delete(&named("api_reserved")).await.unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_process_notes_endpoints() {
+        let (process_manager, auth_manager, _temp_dir) = create_test_components().await;
+        let api_token = AuthManager::new(process_manager.get_database())
+            .generate_token("deploy-bot".to_string(), None)
+            .await
+            .unwrap();
+        let state = || State((process_manager.clone(), auth_manager.clone()));
+        let add = |name: &str, text: String| {
+            add_process_note(state(), authenticated(&api_token), Path(name.parse().unwrap()), Json(AddNoteRequest { text }))
+        };
+
+        let Json(response) = start_process(state(), authenticated(&api_token), Query(StartQuery { return_value: None }), Json(start_request("api_notes", "sleep", &["30"])))
+            .await
+            .unwrap();
+        assert!(response.success);
+
+        // The author is the token the note was added with
+        let Json(response) = add("api_notes", "bumped pool size".to_string()).await.unwrap();
+        let note = response.data.unwrap();
+        assert_eq!(note.author, "deploy-bot");
+
+        let (status, _) = add("api_notes", "x".repeat(4097)).await.unwrap_err();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, _) = add("missing", "hello".to_string()).await.unwrap_err();
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        // Notes appear in the status response only when asked for
+        let status = |notes| {
+            get_process_status(
+                state(),
+                authenticated(&api_token),
+                Path(named("api_notes")),
+                Query(StatusQuery { snapshot: None, notes, refresh: None, truncate: None }),
+            )
+        };
+        let Json(response) = status(None).await.unwrap();
+        assert!(response.data.unwrap().notes.is_empty());
+        let Json(response) = status(Some(true)).await.unwrap();
+        assert_eq!(response.data.unwrap().notes, vec![note.clone()]);
+
+        let Json(response) = delete_process_note(state(), authenticated(&api_token), Path((named("api_notes"), note.id)))
+            .await
+            .unwrap();
+        assert!(response.success);
+        let status = delete_process_note(state(), authenticated(&api_token), Path((named("api_notes"), note.id)))
+            .await
+            .unwrap_err();
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let Json(response) = list_process_notes(state(), authenticated(&api_token), Path(named("api_notes")))
+            .await
+            .unwrap();
+        assert!(response.data.unwrap().is_empty());
+
+        process_manager.delete(&named("api_notes")).await.unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_oversized_starts_are_refused_and_long_values_truncated_on_request() {
+        use pmr::api::handlers::{list_processes, ListQuery};
+
+        let (process_manager, auth_manager, _temp_dir) = create_test_components().await;
+        let api_token = AuthManager::new(process_manager.get_database()).generate_token("limits".to_string(), None).await.unwrap();
+        let state = || State((process_manager.clone(), auth_manager.clone()));
+
+        let mut request = start_request("api_blob", "sleep", &["30"]);
+        request.env_vars = Some(HashMap::from([("BLOB".to_string(), "b".repeat(40_000))]));
+        let (status, Json(response)) = start_process(state(), authenticated(&api_token), Query(StartQuery { return_value: None }), Json(request)).await.err().unwrap();
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+        assert!(response.error.as_deref().unwrap().contains("start_limits.max_env_value_bytes"), "{:?}", response.error);
+        assert!(process_manager.list_processes().await.unwrap().is_empty());
+
+        let script = format!("sleep 30 # {}", "x".repeat(1000));
+        let request = start_request("api_long", "sh", &["-c", &script]);
+        let Json(response) = start_process(state(), authenticated(&api_token), Query(StartQuery { return_value: None }), Json(request)).await.unwrap();
+        assert!(response.success);
+
+        let list = |truncate| list_processes(state(), authenticated(&api_token), Query(ListQuery { refresh: None, truncate, search: None }));
+        let Json(full) = list(None).await.unwrap();
+        let full = &full.data.unwrap()[0];
+        assert_eq!(full.args[1], script);
+        assert!(full.truncated_fields.is_empty());
+        let json = serde_json::to_value(full).unwrap();
+        assert!(json.get("truncated_fields").is_none());
+
+        let Json(truncated) = list(Some(true)).await.unwrap();
+        let truncated = &truncated.data.unwrap()[0];
+        assert_eq!(truncated.truncated_fields, ["args[1]"]);
+        assert!(truncated.args[1].ends_with("… (1011 B in full)"), "{}", truncated.args[1]);
+
+        let query = StatusQuery { snapshot: None, notes: None, refresh: None, truncate: Some(true) };
+        let Json(status) = get_process_status(state(), authenticated(&api_token), Path(named("api_long")), Query(query)).await.unwrap();
+        assert_eq!(status.data.unwrap().truncated_fields, ["args[1]"]);
+
+        process_manager.delete(&named("api_long")).await.unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_description_is_validated_returned_and_searchable() {
+        use pmr::api::handlers::{list_processes, ListQuery};
+
+        let (process_manager, auth_manager, _temp_dir) = create_test_components().await;
+        let api_token = AuthManager::new(process_manager.get_database()).generate_token("describe".to_string(), None).await.unwrap();
+        let state = || State((process_manager.clone(), auth_manager.clone()));
+
+        let mut request = start_request("api_described", "sleep", &["30"]);
+        request.description = Some("first\nsecond".to_string());
+        let (status, Json(response)) = start_process(state(), authenticated(&api_token), Query(StartQuery { return_value: None }), Json(request)).await.unwrap_err();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(response.error.as_deref().unwrap().starts_with("Invalid description"), "{:?}", response.error);
+
+        let mut request = start_request("api_described", "sleep", &["30"]);
+        request.description = Some("Thumbnail renderer".to_string());
+        let query = StartQuery { return_value: Some("record".to_string()) };
+        let Json(response) = start_process(state(), authenticated(&api_token), Query(query), Json(request)).await.unwrap();
+        assert_eq!(response.record.unwrap().description.as_deref(), Some("Thumbnail renderer"));
+        let Json(response) = start_process(state(), authenticated(&api_token), Query(StartQuery { return_value: None }), Json(start_request("api_plain", "sleep", &["30"])))
+            .await
+            .unwrap();
+        assert!(response.success);
+
+        let search = |text: &str| list_processes(state(), authenticated(&api_token), Query(ListQuery { refresh: None, truncate: None, search: Some(text.to_string()) }));
+        let Json(found) = search("THUMBNAIL").await.unwrap();
+        let names: Vec<String> = found.data.unwrap().into_iter().map(|process| process.name.into_string()).collect();
+        assert_eq!(names, ["api_described"]);
+        let Json(found) = search("api_").await.unwrap();
+        assert_eq!(found.data.unwrap().len(), 2);
+
+        process_manager.delete(&named("api_described")).await.unwrap();
+        process_manager.delete(&named("api_plain")).await.unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_inspect_is_for_admin_tokens() {
+        let (process_manager, auth_manager, _temp_dir) = create_test_components().await;
+        let tokens = AuthManager::new(process_manager.get_database());
+        let user_token = tokens.generate_token("dashboard".to_string(), None).await.unwrap();
+        let admin_token = tokens.generate_admin_token("support".to_string(), None).await.unwrap();
+        assert!(!user_token.admin && admin_token.admin);
+        let state = || State((process_manager.clone(), auth_manager.clone()));
+        let inspect = |token: &ApiToken, name: &str| inspect_process(state(), authenticated(token), Path(name.parse().unwrap()));
+
+        let mut request = start_request("api_inspect", "sh", &["-c", "echo ready; sleep 30"]);
+        request.env_vars = Some(HashMap::from([("DB_PASSWORD".to_string(), "hunter2".to_string())]));
+        let Json(response) = start_process(state(), authenticated(&user_token), Query(StartQuery { return_value: None }), Json(request)).await.unwrap();
+        assert!(response.success);
+        process_manager.annotate_process(&named("api_inspect"), "alice", "rotated keys").await.unwrap();
+        sleep(Duration::from_millis(200)).await;
+
+        assert_eq!(inspect(&user_token, "api_inspect").await.err(), Some(StatusCode::FORBIDDEN));
+        assert_eq!(inspect(&admin_token, "missing").await.err(), Some(StatusCode::NOT_FOUND));
+
+        let Json(response) = inspect(&admin_token, "api_inspect").await.unwrap();
+        let report = response.data.unwrap();
+        // The record, environment unredacted
+        assert_eq!(report.record.name, "api_inspect");
+        assert_eq!(report.record.env_vars.get("DB_PASSWORD").map(String::as_str), Some("hunter2"));
+        // Liveness
+        assert_eq!(report.liveness.pid, report.record.pid);
+        assert_eq!(report.liveness.status, pmr::database::ProcessStatus::Running);
+        assert!(report.liveness.pid_alive && report.liveness.same_process);
+        assert!(report.resources.is_some());
+        // Logs metadata
+        assert_eq!(report.logs.log_path, report.record.log_path);
+        assert!(report.logs.live_bytes.unwrap() > 0);
+        assert!(report.logs.rotated.is_empty());
+        // Notes and the installation
+        assert_eq!(report.notes.len(), 1);
+        assert_eq!(report.environment.pmr_version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(report.environment.paths, process_manager.config().paths());
+
+        process_manager.delete(&named("api_inspect")).await.unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_start_refuses_batches_over_process_limit() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = Config::in_directory(temp_dir.path()).with_max_processes(2);
+        let process_manager = Arc::new(ProcessManager::new(config).await.unwrap());
+        let auth_manager = Arc::new(Mutex::new(AuthManager::new(process_manager.get_database())));
+        let api_token = AuthManager::new(process_manager.get_database())
+            .generate_token("limit".to_string(), None)
+            .await
+            .unwrap();
+        let state = || State((process_manager.clone(), auth_manager.clone()));
+
+        let Json(response) = start_process(state(), authenticated(&api_token), Query(StartQuery { return_value: None }), Json(start_request("limit_a", "sleep", &["30"])))
+            .await
+            .unwrap();
+        assert!(response.success);
+
+        // Two more would make three: the whole batch is refused and nothing starts
+        let requests = vec![start_request("limit_b", "sleep", &["30"]), start_request("limit_c", "sleep", &["30"])];
+        let (status, Json(response)) = bulk_start_processes(state(), authenticated(&api_token), Json(requests))
+            .await
+            .unwrap();
+        assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+        assert!(response.error.as_deref().unwrap().contains("Process limit reached: 1 of 2"));
+        assert_eq!(process_manager.list_processes().await.unwrap().len(), 1);
+
+        let Json(response) = start_process(state(), authenticated(&api_token), Query(StartQuery { return_value: None }), Json(start_request("limit_b", "sleep", &["30"])))
+            .await
+            .unwrap();
+        assert!(response.success);
+
+        let (status, Json(response)) = start_process(state(), authenticated(&api_token), Query(StartQuery { return_value: None }), Json(start_request("limit_c", "sleep", &["30"])))
+            .await
+            .unwrap_err();
+        assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(
+            response.error.as_deref(),
+            Some("Process limit reached: 2 of 2 allowed processes are managed; delete some or raise max_processes")
+        );
+
+        for name in ["limit_a", "limit_b"] {
+            process_manager.delete(&named(name)).await.unwrap();
         }
-        
-        // Verify cleanup
-        let processes = process_manager.list_processes().await.unwrap();
-        assert_eq!(processes.len(), 0);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_strict_start_status_codes() {
+        let (process_manager, auth_manager, _temp_dir) = create_test_components().await;
+        let api_token = AuthManager::new(process_manager.get_database())
+            .generate_token("strict".to_string(), None)
+            .await
+            .unwrap();
+        let state = || State((process_manager.clone(), auth_manager.clone()));
+        let strict = |name: &str, command: &str, args: &[&str]| StartProcessRequest {
+            strict: true,
+            ..start_request(name, command, args)
+        };
+
+        // Exiting in the start window is a runtime failure, reported apart from a name conflict
+        let (status, Json(response)) = start_process(state(), authenticated(&api_token), Query(StartQuery { return_value: None }), Json(strict("strict_svc", "sh", &["-c", "exit 2"])))
+            .await
+            .unwrap_err();
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert!(response.error.as_deref().unwrap().contains("exited with code 2 during the start window"));
+        assert!(process_manager.list_processes().await.unwrap().is_empty());
+
+        // A command that cannot be spawned is a server-side failure
+        let (status, _) = start_process(state(), authenticated(&api_token), Query(StartQuery { return_value: None }), Json(strict("strict_svc", "/nonexistent/binary", &[])))
+            .await
+            .unwrap_err();
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+
+        let Json(response) = start_process(state(), authenticated(&api_token), Query(StartQuery { return_value: None }), Json(strict("strict_svc", "sleep", &["30"])))
+            .await
+            .unwrap();
+        assert!(response.success);
+        let (status, _) = start_process(state(), authenticated(&api_token), Query(StartQuery { return_value: None }), Json(strict("strict_svc", "sleep", &["30"])))
+            .await
+            .unwrap_err();
+        assert_eq!(status, StatusCode::CONFLICT);
+
+        process_manager.delete(&named("strict_svc")).await.unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_restart_of_flapping_process_needs_force() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = Config::in_directory(temp_dir.path()).with_flapping(FlappingConfig { failures: 1, window_secs: 600 });
+        let process_manager = Arc::new(ProcessManager::new(config).await.unwrap());
+        let auth_manager = Arc::new(Mutex::new(AuthManager::new(process_manager.get_database())));
+        let api_token = AuthManager::new(process_manager.get_database())
+            .generate_token("flapping".to_string(), None)
+            .await
+            .unwrap();
+        let state = || State((process_manager.clone(), auth_manager.clone()));
+        process_manager
+            .start(&named("crashy"), "sh", vec!["-c".to_string(), "exit 3".to_string()], HashMap::new(), None, None)
+            .await
+            .unwrap();
+
+        let Json(status) = get_process_status(state(), authenticated(&api_token), Path(named("crashy")), Query(StatusQuery { snapshot: None, notes: None, refresh: None, truncate: None }))
+            .await
+            .unwrap();
+        assert!(status.data.unwrap().flapping);
+
+        let refused = restart_process(state(), authenticated(&api_token), Path(named("crashy")), Query(RestartQuery { force: None }))
+            .await
+            .unwrap_err();
+        assert_eq!(refused, StatusCode::CONFLICT);
+        let Json(forced) = restart_process(state(), authenticated(&api_token), Path(named("crashy")), Query(RestartQuery { force: Some(true) }))
+            .await
+            .unwrap();
+        assert!(forced.success);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_stop_signal_stored_at_start_and_overridable() {
+        let (process_manager, auth_manager, _temp_dir) = create_test_components().await;
+        let api_token = AuthManager::new(process_manager.get_database())
+            .generate_token("signals".to_string(), None)
+            .await
+            .unwrap();
+        let state = || State((process_manager.clone(), auth_manager.clone()));
+
+        let mut request = start_request("sig_bad", "sleep", &["30"]);
+        request.stop_signal = Some("BOGUS".to_string());
+        let (status, _) = start_process(state(), authenticated(&api_token), Query(StartQuery { return_value: None }), Json(request)).await.unwrap_err();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let mut request = start_request("sig_int", "sleep", &["30"]);
+        request.stop_signal = Some("INT".to_string());
+        let Json(started) = start_process(state(), authenticated(&api_token), Query(StartQuery { return_value: None }), Json(request)).await.unwrap();
+        assert!(started.success);
+        let Json(status) = get_process_status(state(), authenticated(&api_token), Path(named("sig_int")), Query(StatusQuery { snapshot: None, notes: None, refresh: None, truncate: None }))
+            .await
+            .unwrap();
+        assert_eq!(status.data.unwrap().stop_signal, libc::SIGINT);
+
+        let refused = stop_process(state(), authenticated(&api_token), Path(named("sig_int")), Query(StopQuery { signal: Some("NOPE".to_string()) }))
+            .await
+            .unwrap_err();
+        assert_eq!(refused, StatusCode::BAD_REQUEST);
+        let Json(stopped) = stop_process(state(), authenticated(&api_token), Path(named("sig_int")), Query(StopQuery { signal: Some("KILL".to_string()) }))
+            .await
+            .unwrap();
+        assert!(stopped.success);
+
+        process_manager.delete(&named("sig_int")).await.unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_process_names_filtered_by_prefix() {
+        let (process_manager, auth_manager, _temp_dir) = create_test_components().await;
+        let api_token = AuthManager::new(process_manager.get_database())
+            .generate_token("names".to_string(), None)
+            .await
+            .unwrap();
+        let state = || State((process_manager.clone(), auth_manager.clone()));
+        for name in ["web-2", "worker", "web-1", "db", "web%x"] {
+            process_manager.start(&named(name), "sleep", vec!["30".to_string()], HashMap::new(), None, None).await.unwrap();
+        }
+        process_manager.delete(&named("db")).await.unwrap();
+
+        let names = |prefix: Option<&str>| {
+            let query = NamesQuery { prefix: prefix.map(str::to_string) };
+            list_process_names(state(), authenticated(&api_token), Query(query))
+        };
+        let Json(all) = names(None).await.unwrap();
+        assert_eq!(all, vec!["web%x", "web-1", "web-2", "worker"]);
+        let Json(web) = names(Some("web-")).await.unwrap();
+        assert_eq!(web, vec!["web-1", "web-2"]);
+        // The prefix is matched literally, not as a pattern
+        let Json(literal) = names(Some("web%")).await.unwrap();
+        assert_eq!(literal, vec!["web%x"]);
+        let Json(none) = names(Some("x")).await.unwrap();
+        assert!(none.is_empty());
+        let Json(everything) = names(Some("")).await.unwrap();
+        assert_eq!(everything, all);
+
+        for name in all {
+            process_manager.delete(&named(&name)).await.unwrap();
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_ephemeral_start_request() {
+        let (process_manager, auth_manager, _temp_dir) = create_test_components().await;
+        let api_token = AuthManager::new(process_manager.get_database())
+            .generate_token("ephemeral".to_string(), None)
+            .await
+            .unwrap();
+        let state = || State((process_manager.clone(), auth_manager.clone()));
+
+        // A grace period alone, or one that does not parse, is refused
+        let mut request = start_request("eph_bad", "sleep", &["30"]);
+        request.ephemeral_keep = Some("30s".to_string());
+        let (status, _) = start_process(state(), authenticated(&api_token), Query(StartQuery { return_value: None }), Json(request)).await.unwrap_err();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let mut request = start_request("eph_bad", "sleep", &["30"]);
+        request.ephemeral = true;
+        request.ephemeral_keep = Some("soon".to_string());
+        let (status, _) = start_process(state(), authenticated(&api_token), Query(StartQuery { return_value: None }), Json(request)).await.unwrap_err();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        for (name, keep, secs) in [("eph_default", None, 600), ("eph_short", Some("30s"), 30)] {
+            let mut request = start_request(name, "sleep", &["30"]);
+            request.ephemeral = true;
+            request.ephemeral_keep = keep.map(str::to_string);
+            let Json(started) = start_process(state(), authenticated(&api_token), Query(StartQuery { return_value: None }), Json(request)).await.unwrap();
+            assert!(started.success);
+            let process = process_manager.get_process_status(&named(name)).await.unwrap();
+            assert_eq!(process.ephemeral_keep_secs, Some(secs));
+            process_manager.delete(&named(name)).await.unwrap();
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_logs_report_invalid_utf8() {
+        let (process_manager, auth_manager, temp_dir) = create_test_components().await;
+        let api_token = AuthManager::new(process_manager.get_database())
+            .generate_token("utf8".to_string(), None)
+            .await
+            .unwrap();
+        let state = || State((process_manager.clone(), auth_manager.clone()));
+        process_manager.start(&named("binary"), "true", vec![], HashMap::new(), None, None).await.unwrap();
+        std::fs::write(temp_dir.path().join("logs").join("binary.log"), b"\xffboot\nready\n").unwrap();
+
+        let token = &api_token;
+        let body = |lines: Option<usize>| {
+            let query = LogsQuery { lines, rotated: None, merge_rotated: None, collapse_repeats: None, tail_bytes: None, allow_partial: None, since: None };
+            async move {
+                let response = get_process_logs(state(), authenticated(token), Path(named("binary")), Query(query))
+                    .await
+                    .unwrap();
+                let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+                serde_json::from_slice::<serde_json::Value>(&body).unwrap()
+            }
+        };
+        let whole = body(None).await;
+        assert_eq!(whole["data"], "\u{FFFD}boot\nready\n");
+        assert_eq!(whole["contains_invalid_utf8"], true);
+        let tail = body(Some(1)).await;
+        assert_eq!(tail["data"], "ready");
+        assert_eq!(tail["contains_invalid_utf8"], false);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_rotated_logs_content_is_capped() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut config = Config::in_directory(temp_dir.path());
+        config.api.max_logs_response_bytes = 1000;
+        let process_manager = Arc::new(ProcessManager::new(config).await.unwrap());
+        let auth_manager = Arc::new(Mutex::new(AuthManager::new(process_manager.get_database())));
+        let api_token = AuthManager::new(process_manager.get_database())
+            .generate_token("rotated".to_string(), None)
+            .await
+            .unwrap();
+        let state = || State((process_manager.clone(), auth_manager.clone()));
+        process_manager.start(&named("big"), "true", vec![], HashMap::new(), None, None).await.unwrap();
+        std::fs::write(temp_dir.path().join("logs").join("big.1.log"), "y".repeat(5000)).unwrap();
+
+        let Json(listed) = list_rotated_logs(state(), authenticated(&api_token), Path(named("big"))).await.unwrap();
+        let listed = listed.data.unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].size_bytes, 5000);
+
+        let query = LogsQuery { lines: None, rotated: Some(true), merge_rotated: None, collapse_repeats: None, tail_bytes: None, allow_partial: None, since: None };
+        let response = get_process_logs(state(), authenticated(&api_token), Path(named("big")), Query(query))
+            .await
+            .unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["data"]["truncated"], true);
+        assert_eq!(body["data"]["bytes"], 1000);
+        assert_eq!(body["data"]["total_bytes"], 5000);
+        assert_eq!(body["data"]["content"].as_str().unwrap().len(), 1000);
+        assert!(body["hint"].as_str().unwrap().contains("/api/processes/big/logs/archive"));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_reload_config_endpoint() {
+        let temp_dir = TempDir::new().unwrap();
+        let config_path = temp_dir.path().join("config.toml");
+        std::fs::write(&config_path, "[log_rotation]\nmax_file_size = 1000000\n").unwrap();
+        let config = Config::in_directory(temp_dir.path()).with_file(&config_path).unwrap();
+        let process_manager = Arc::new(ProcessManager::new(config).await.unwrap());
+        let auth_manager = Arc::new(Mutex::new(AuthManager::new(process_manager.get_database())));
+        let tokens = AuthManager::new(process_manager.get_database());
+        let api_token = tokens.generate_admin_token("reload".to_string(), None).await.unwrap();
+        let user_token = tokens.generate_token("user".to_string(), None).await.unwrap();
+        let state = || State((process_manager.clone(), auth_manager.clone()));
+
+        std::fs::write(&config_path, "[log_rotation]\nmax_file_size = 100\nmax_files = 1\n").unwrap();
+        // Only admin tokens may reload; the file is not even read otherwise
+        let (status, Json(refused)) = reload_config(state(), authenticated(&user_token)).await.unwrap_err();
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert!(!refused.success);
+        assert_eq!(process_manager.config().log_rotation.max_file_size, 1000000);
+
+        let Json(reloaded) = reload_config(state(), authenticated(&api_token)).await.unwrap();
+        assert_eq!(reloaded.data.unwrap().changed, vec!["log_rotation"]);
+
+        // The next start's rotation check uses the new threshold
+        let log = temp_dir.path().join("logs").join("web.log");
+        std::fs::write(&log, "x".repeat(500)).unwrap();
+        process_manager.start(&named("web"), "true", vec![], HashMap::new(), None, None).await.unwrap();
+        assert!(temp_dir.path().join("logs").join("web.1.log").is_file());
+
+        std::fs::write(&config_path, "[log_rotation]\nmax_files = \"many\"\n").unwrap();
+        let (status, Json(refused)) = reload_config(state(), authenticated(&api_token)).await.unwrap_err();
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert!(refused.error.unwrap().contains("config.toml"));
+        assert_eq!(process_manager.config().log_rotation.max_file_size, 100);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_bulk_start_router() {
+        use axum::body::{to_bytes, Body};
+        use axum::http::Request;
+        use tower::ServiceExt;
+
+        let (process_manager, _auth_manager, temp_dir) = create_test_components().await;
+        let api_token = AuthManager::new(process_manager.get_database())
+            .generate_token("bulk".to_string(), None)
+            .await
+            .unwrap();
+        drop(process_manager);
+
+        let config = Config::new()
+            .with_database_path(temp_dir.path().join("test.db"))
+            .with_log_dir(temp_dir.path().join("logs"));
+        let server = ApiServer::new(ProcessManager::new(config).await.unwrap(), 0).unwrap();
+
+        let body = serde_json::json!([
+            { "name": "router_bulk_1", "command": "echo", "args": ["one"] },
+            { "name": "router_bulk_2", "command": "echo", "args": ["two"] }
+        ]);
+        let request = Request::builder()
+            .method("POST")
+            .uri("/api/processes/bulk")
+            .header("Authorization", format!("Bearer {}", api_token.token))
+            .header("Content-Type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+
+        let response = server.create_router().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(json["success"], true);
+        assert_eq!(json["data"][0]["name"], "router_bulk_1");
+        assert_eq!(json["data"][1]["name"], "router_bulk_2");
+        assert_eq!(json["data"][1]["status_code"], 200);
+
+        // Unauthenticated requests are rejected
+        let request = Request::builder()
+            .method("POST")
+            .uri("/api/processes/bulk")
+            .header("Content-Type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let response = server.create_router().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    // The token check awaits the lookup, so it also works on a current-thread runtime
+    #[tokio::test]
+    async fn test_token_check_on_current_thread_runtime() {
+        use axum::body::Body;
+        use axum::http::Request;
+        use tower::ServiceExt;
+
+        let (process_manager, _auth_manager, temp_dir) = create_test_components().await;
+        let api_token = AuthManager::new(process_manager.get_database())
+            .generate_token("single".to_string(), None)
+            .await
+            .unwrap();
+        drop(process_manager);
+
+        let config = Config::new()
+            .with_database_path(temp_dir.path().join("test.db"))
+            .with_log_dir(temp_dir.path().join("logs"));
+        let server = ApiServer::new(ProcessManager::new(config).await.unwrap(), 0).unwrap();
+        let list = |token: Option<&str>| {
+            let request = Request::builder().uri("/api/processes");
+            let request = match token {
+                Some(token) => request.header("Authorization", format!("Bearer {}", token)),
+                None => request,
+            };
+            request.body(Body::empty()).unwrap()
+        };
+
+        let response = server.create_router().oneshot(list(Some(&api_token.token))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = server.create_router().oneshot(list(Some("pmr_unknown0_secret"))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let response = server.create_router().oneshot(list(None)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_read_only_router() {
+        use axum::body::{to_bytes, Body};
+        use axum::http::Request;
+        use tower::ServiceExt;
+
+        // Populate the database through a normal manager first
+        let (process_manager, _auth_manager, temp_dir) = create_test_components().await;
+        let api_token = AuthManager::new(process_manager.get_database())
+            .generate_token("dashboard".to_string(), None)
+            .await
+            .unwrap();
+        process_manager
+            .start(&named("ro_echo"), "echo", vec!["visible".to_string()], HashMap::new(), None, None)
+            .await
+            .unwrap();
+        drop(process_manager);
+
+        let config = Config::new()
+            .with_database_path(temp_dir.path().join("test.db"))
+            .with_log_dir(temp_dir.path().join("logs"));
+        let server = ApiServer::new_read_only(ProcessManager::read_only(config).await.unwrap(), 0).unwrap();
+        assert!(server.is_read_only());
+
+        let send = |method: &str, uri: &str| {
+            let request = Request::builder()
+                .method(method)
+                .uri(uri)
+                .header("Authorization", format!("Bearer {}", api_token.token))
+                .header("Content-Type", "application/json")
+                .body(Body::from(r#"{"name":"ro_new","command":"echo"}"#))
+                .unwrap();
+            server.create_router().oneshot(request)
+        };
+        let json = |response: axum::response::Response| async move {
+            let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            serde_json::from_slice::<serde_json::Value>(&bytes).unwrap()
+        };
+
+        let response = send("GET", "/api/processes").await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(json(response).await["data"][0]["name"], "ro_echo");
+        let response = send("GET", "/api/processes/ro_echo").await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = send("GET", "/api/processes/ro_echo/logs").await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(json(response).await["data"].as_str().unwrap().contains("visible"));
+
+        for (method, uri) in [
+            ("POST", "/api/processes"),
+            ("POST", "/api/processes/bulk"),
+            ("PUT", "/api/processes/ro_echo/stop"),
+            ("PUT", "/api/processes/ro_echo/restart"),
+            ("DELETE", "/api/processes/ro_echo"),
+        ] {
+            let response = send(method, uri).await.unwrap();
+            assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED, "{} {}", method, uri);
+            let body = json(response).await;
+            assert_eq!(body["success"], false);
+            assert!(body["error"].as_str().unwrap().contains("read-only"), "{}", body);
+        }
+
+        let response = server
+            .create_router()
+            .oneshot(Request::builder().uri("/healthz").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(json(response).await["read_only"], true);
+
+        // Nothing was changed behind the server's back
+        let config = Config::new()
+            .with_database_path(temp_dir.path().join("test.db"))
+            .with_log_dir(temp_dir.path().join("logs"));
+        let process_manager = ProcessManager::new(config).await.unwrap();
+        let names: Vec<String> = process_manager.list_processes().await.unwrap().into_iter().map(|p| p.name.into_string()).collect();
+        assert_eq!(names, vec!["ro_echo".to_string()]);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_logs_archive_router() {
+        use axum::body::{to_bytes, Body};
+        use axum::http::Request;
+        use tower::ServiceExt;
+
+        let (process_manager, _auth_manager, temp_dir) = create_test_components().await;
+        let api_token = AuthManager::new(process_manager.get_database())
+            .generate_token("archive".to_string(), None)
+            .await
+            .unwrap();
+        process_manager
+            .start(&named("archived"), "echo", vec!["archived output".to_string()], HashMap::new(), None, None)
+            .await
+            .unwrap();
+        tokio::time::sleep(tokio::time::Duration::from_millis(200)).await;
+        std::fs::write(temp_dir.path().join("logs/archived.1.log"), "older output\n").unwrap();
+        drop(process_manager);
+
+        let config = Config::new()
+            .with_database_path(temp_dir.path().join("test.db"))
+            .with_log_dir(temp_dir.path().join("logs"));
+        let server = ApiServer::new(ProcessManager::new(config).await.unwrap(), 0).unwrap();
+
+        let request = Request::builder()
+            .uri("/api/processes/archived/logs/archive")
+            .header("Authorization", format!("Bearer {}", api_token.token))
+            .body(Body::empty())
+            .unwrap();
+        let response = server.create_router().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["content-type"], "application/x-tar");
+        let disposition = response.headers()["content-disposition"].to_str().unwrap().to_string();
+        assert!(disposition.starts_with("attachment; filename=\"archived-logs-"));
+        assert!(disposition.ends_with(".tar\""));
+
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(bytes.len() % 512, 0);
+        let text = String::from_utf8_lossy(&bytes);
+        assert!(text.contains("archived output"));
+        assert!(text.contains("older output"));
+        assert!(text.contains("archived/MANIFEST.txt"));
+
+        let request = Request::builder()
+            .uri("/api/processes/missing/logs/archive")
+            .header("Authorization", format!("Bearer {}", api_token.token))
+            .body(Body::empty())
+            .unwrap();
+        let response = server.create_router().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let request = Request::builder()
+            .uri("/api/processes/archived/logs/archive")
+            .body(Body::empty())
+            .unwrap();
+        let response = server.create_router().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_webhook_receives_state_changes() {
+        use axum::{http::HeaderMap, routing::post, Router};
+        use pmr::config::NotificationConfig;
+        use pmr::notify::StateChangeEvent;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        type Captured = Arc<Mutex<Vec<(Option<String>, StateChangeEvent)>>>;
+        let captured: Captured = Arc::new(Mutex::new(Vec::new()));
+        let attempts = Arc::new(AtomicUsize::new(0));
+
+        // The first delivery is rejected to exercise the retry path
+        let app = Router::new()
+            .route(
+                "/hook",
+                post(
+                    |State((captured, attempts)): State<(Captured, Arc<AtomicUsize>)>,
+                     headers: HeaderMap,
+                     Json(event): Json<StateChangeEvent>| async move {
+                        if attempts.fetch_add(1, Ordering::SeqCst) == 0 {
+                            return StatusCode::INTERNAL_SERVER_ERROR;
+                        }
+                        let auth = headers
+                            .get("Authorization")
+                            .and_then(|h| h.to_str().ok())
+                            .map(str::to_string);
+                        captured.lock().unwrap().push((auth, event));
+                        StatusCode::NO_CONTENT
+                    },
+                ),
+            )
+            .with_state((captured.clone(), attempts.clone()));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let temp_dir = TempDir::new().unwrap();
+        let config = Config::new()
+            .with_database_path(temp_dir.path().join("test.db"))
+            .with_log_dir(temp_dir.path().join("logs"))
+            .with_notifications(NotificationConfig {
+                webhook_url: Some(format!("http://{}/hook", address)),
+                bearer_token: Some("hook-token".to_string()),
+                events: vec!["running".to_string(), "stopped".to_string(), "deleted".to_string()],
+                ..Default::default()
+            });
+        let process_manager = ProcessManager::new(config).await.unwrap();
+
+        process_manager
+            .start(&named("notified"), "sleep", vec!["30".to_string()], HashMap::new(), None, None)
+            .await
+            .unwrap();
+        process_manager.stop(&named("notified")).await.unwrap();
+        process_manager.delete(&named("notified")).await.unwrap();
+        process_manager.notifier().flush(Duration::from_secs(10)).await;
+
+        let captured = captured.lock().unwrap();
+        let transitions: Vec<(Option<String>, String)> = captured
+            .iter()
+            .map(|(_, e)| (e.old_status.clone(), e.new_status.clone()))
+            .collect();
+        assert_eq!(transitions, vec![
+            (None, "running".to_string()),
+            (Some("running".to_string()), "stopped".to_string()),
+            (Some("stopped".to_string()), "deleted".to_string()),
+        ]);
+        assert!(captured.iter().all(|(auth, event)| {
+            auth.as_deref() == Some("Bearer hook-token") && event.process == "notified" && !event.host.is_empty()
+        }));
+        assert_eq!(attempts.load(Ordering::SeqCst), 4);
+    }
+
+    #[test]
+    fn test_request_bodies_reject_unknown_fields() {
+        use pmr::suggest::explain_unknown_field;
+
+        // A misspelt key fails the request instead of being dropped silently
+        let error = serde_json::from_value::<StartProcessRequest>(serde_json::json!({
+            "name": "web",
+            "command": "sleep",
+            "env_var": { "PORT": "8080" }
+        }))
+        .err()
+        .unwrap()
+        .to_string();
+        assert!(error.contains("unknown field `env_var`"), "{}", error);
+        assert!(explain_unknown_field(&error).contains("did you mean `env_vars`?"), "{}", error);
+
+        let error = serde_json::from_value::<AddNoteRequest>(serde_json::json!({ "text": "hi", "author": "me" }))
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(error.contains("unknown field `author`"), "{}", error);
+        assert_eq!(explain_unknown_field(&error), error);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_access_log_router() {
+        use axum::body::Body;
+        use axum::http::Request;
+        use tower::ServiceExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        let access_log = temp_dir.path().join("access").join("api-access.log");
+        let config = Config::new()
+            .with_database_path(temp_dir.path().join("test.db"))
+            .with_log_dir(temp_dir.path().join("logs"))
+            .with_access_log_path(access_log.clone());
+        let process_manager = ProcessManager::new(config).await.unwrap();
+        let api_token = AuthManager::new(process_manager.get_database())
+            .generate_token("dashboard".to_string(), None)
+            .await
+            .unwrap();
+        let server = ApiServer::new(process_manager, 0).unwrap();
+
+        let get = |uri: &str, token: Option<&str>| {
+            let mut request = Request::builder().uri(uri);
+            if let Some(token) = token {
+                request = request.header("Authorization", format!("Bearer {}", token));
+            }
+            request.body(Body::empty()).unwrap()
+        };
+        let router = server.create_router();
+        router.clone().oneshot(get("/healthz", None)).await.unwrap();
+        router.clone().oneshot(get("/api/processes", Some(&api_token.token))).await.unwrap();
+        router.clone().oneshot(get("/api/processes/missing?token=secret&lines=5", Some("bogus"))).await.unwrap();
+
+        let content = std::fs::read_to_string(&access_log).unwrap();
+        assert!(!content.contains("secret"), "{}", content);
+        let lines: Vec<Vec<&str>> = content.lines().map(|line| line.split(' ').collect()).collect();
+        assert_eq!(lines.len(), 3, "{}", content);
+        for fields in &lines {
+            assert_eq!(fields.len(), 7, "{:?}", fields);
+            assert!(chrono::DateTime::parse_from_rfc3339(fields[0]).is_ok(), "{:?}", fields);
+            assert!(fields[5].strip_suffix("ms").unwrap().parse::<u64>().is_ok(), "{:?}", fields);
+            // oneshot requests carry no peer address
+            assert_eq!(fields[6], "-");
+        }
+        assert_eq!(&lines[0][1..5], &["-", "GET", "/healthz", "200"]);
+        assert_eq!(&lines[1][1..5], &[api_token.public_id.as_str(), "GET", "/api/processes", "200"]);
+        assert_eq!(&lines[2][1..5], &["-", "GET", "/api/processes/missing?token=[REDACTED]&lines=5", "401"]);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_access_log_rotates() {
+        use axum::body::Body;
+        use axum::http::Request;
+        use tower::ServiceExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        let config = Config::new()
+            .with_database_path(temp_dir.path().join("test.db"))
+            .with_log_dir(temp_dir.path().join("logs"))
+            .with_log_rotation(LogRotationConfig { enabled: true, max_file_size: 200, max_files: 2 });
+        let access_log = config.access_log_path();
+        assert_eq!(access_log, temp_dir.path().join("api-access.log"));
+        let server = ApiServer::new(ProcessManager::new(config).await.unwrap(), 0).unwrap();
+
+        let router = server.create_router();
+        for _ in 0..10 {
+            let request = Request::builder().uri("/healthz").body(Body::empty()).unwrap();
+            router.clone().oneshot(request).await.unwrap();
+        }
+
+        let first_archive = temp_dir.path().join("api-access.1.log");
+        assert!(first_archive.exists());
+        assert!(temp_dir.path().join("api-access.2.log").exists());
+        assert!(!temp_dir.path().join("api-access.3.log").exists());
+        // Every file stays near the threshold, and the live log was reopened after rotating
+        for path in [&access_log, &first_archive] {
+            let size = std::fs::metadata(path).unwrap().len();
+            assert!(size <= 200 + 80, "{} is {} bytes", path.display(), size);
+        }
+        assert!(std::fs::read_to_string(&first_archive).unwrap().contains("GET /healthz 200"));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_health_and_readiness_probes() {
+        use axum::body::{to_bytes, Body};
+        use axum::http::Request;
+        use tower::ServiceExt;
+        let json = |response: axum::response::Response| async move {
+            let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            serde_json::from_slice::<serde_json::Value>(&bytes).unwrap()
+        };
+
+        let temp_dir = TempDir::new().unwrap();
+        let log_dir = temp_dir.path().join("logs");
+        let config = Config::new()
+            .with_database_path(temp_dir.path().join("test.db"))
+            .with_log_dir(log_dir.clone());
+        let server = ApiServer::new(ProcessManager::new(config).await.unwrap(), 0).unwrap();
+        // No token: both are meant for load balancers
+        let probe = |uri: &str| server.create_router().oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap());
+
+        let response = probe("/healthz").await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = json(response).await;
+        assert_eq!(body["status"], "ok");
+        assert_eq!(body["version"], env!("CARGO_PKG_VERSION"));
+        assert!(body["uptime_secs"].is_u64(), "{}", body);
+
+        let response = probe("/readyz").await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = json(response).await;
+        assert_eq!(body["ready"], true);
+        assert_eq!(body["checks"].as_array().unwrap().len(), 2);
+        assert!(body["checks"].as_array().unwrap().iter().all(|check| check["ok"] == true), "{}", body);
+
+        // A file where the log directory should be cannot take logs, even for root
+        std::fs::remove_dir_all(&log_dir).unwrap();
+        std::fs::write(&log_dir, "not a directory").unwrap();
+        let response = probe("/readyz").await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let body = json(response).await;
+        assert_eq!(body["ready"], false);
+        assert_eq!(body["failed"], serde_json::json!(["log_dir"]));
+        let log_check = body["checks"].as_array().unwrap().iter().find(|check| check["name"] == "log_dir").unwrap();
+        assert!(log_check["detail"].as_str().unwrap().contains("is not writable"), "{}", body);
+
+        let paths = serde_json::to_value(pmr::api::docs::ApiDoc::get_openapi()).unwrap()["paths"].clone();
+        assert!(paths.get("/healthz").is_some() && paths.get("/readyz").is_some(), "{}", paths);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_every_route_requires_a_token_unless_allowlisted() {
+        use axum::body::Body;
+        use axum::http::Request;
+        use tower::ServiceExt;
+
+        // The only routes anyone may call without a token; adding to it must be deliberate
+        const PUBLIC: [&str; 4] = ["/healthz", "/readyz", "/swagger-ui/", "/api-docs/openapi.json"];
+        let temp_dir = TempDir::new().unwrap();
+        let config = |require_auth| {
+            Config::new()
+                .with_database_path(temp_dir.path().join("test.db"))
+                .with_log_dir(temp_dir.path().join("logs"))
+                .with_docs_enabled(true)
+                .with_docs_require_auth(require_auth)
+        };
+        let request = |method: &str, uri: &str, token: Option<&str>| {
+            let request = Request::builder().method(method).uri(uri);
+            let request = match token {
+                Some(token) => request.header("Authorization", format!("Bearer {}", token)),
+                None => request,
+            };
+            request.body(Body::empty()).unwrap()
+        };
+
+        let servers = [
+            ApiServer::new(ProcessManager::new(config(false)).await.unwrap(), 0).unwrap(),
+            ApiServer::new(ProcessManager::new(config(true)).await.unwrap(), 0).unwrap(),
+            ApiServer::new_read_only(ProcessManager::read_only(config(false)).await.unwrap(), 0).unwrap(),
+        ];
+        for server in &servers {
+            let routes = server.routes();
+            assert!(routes.iter().any(|route| route.path == "/api/processes/:name/inspect"), "{:?}", routes);
+            for route in &routes {
+                assert!(!route.public || PUBLIC.contains(&route.path.as_str()), "{} is public", route.path);
+                // Path parameters are never looked at before the token is
+                let uri: Vec<&str> = route.path.split('/').map(|segment| if segment.starts_with(':') { "x" } else { segment }).collect();
+                let uri = uri.join("/");
+                if route.public {
+                    let response = server.create_router().oneshot(request("GET", &uri, None)).await.unwrap();
+                    assert_ne!(response.status(), StatusCode::UNAUTHORIZED, "GET {}", uri);
+                    continue;
+                }
+                for method in ["GET", "POST", "PUT", "DELETE"] {
+                    let response = server.create_router().oneshot(request(method, &uri, None)).await.unwrap();
+                    assert_eq!(response.status(), StatusCode::UNAUTHORIZED, "{} {}", method, uri);
+                }
+                let response = server.create_router().oneshot(request("GET", &uri, Some("not-a-token"))).await.unwrap();
+                assert_eq!(response.status(), StatusCode::UNAUTHORIZED, "GET {}", uri);
+            }
+        }
+        // Docs that require a token are not public
+        assert_eq!(servers[0].routes().iter().filter(|route| route.public).count(), PUBLIC.len());
+        assert_eq!(servers[1].routes().iter().filter(|route| route.public).count(), 2);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_docs_routes_follow_config() {
+        use axum::body::Body;
+        use axum::http::Request;
+        use tower::ServiceExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        let config = |enabled, require_auth| {
+            Config::new()
+                .with_database_path(temp_dir.path().join("test.db"))
+                .with_log_dir(temp_dir.path().join("logs"))
+                .with_docs_enabled(enabled)
+                .with_docs_require_auth(require_auth)
+        };
+        let get = |uri: &str, token: Option<&str>| {
+            let request = Request::builder().uri(uri);
+            let request = match token {
+                Some(token) => request.header("Authorization", format!("Bearer {}", token)),
+                None => request,
+            };
+            request.body(Body::empty()).unwrap()
+        };
+        const DOCS: [&str; 2] = ["/api-docs/openapi.json", "/swagger-ui/"];
+
+        let process_manager = ProcessManager::new(config(true, false)).await.unwrap();
+        let api_token = AuthManager::new(process_manager.get_database()).generate_token("docs".to_string(), None).await.unwrap();
+        let server = ApiServer::new(process_manager, 0).unwrap();
+        for uri in DOCS {
+            let response = server.create_router().oneshot(get(uri, None)).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK, "{}", uri);
+        }
+
+        // Off: the routes are not there at all, whatever the token
+        let server = ApiServer::new(ProcessManager::new(config(false, false)).await.unwrap(), 0).unwrap();
+        for uri in DOCS {
+            let response = server.create_router().oneshot(get(uri, Some(&api_token.token))).await.unwrap();
+            assert_eq!(response.status(), StatusCode::NOT_FOUND, "{}", uri);
+        }
+        let response = server.create_router().oneshot(get("/healthz", None)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let server = ApiServer::new(ProcessManager::new(config(true, true)).await.unwrap(), 0).unwrap();
+        for uri in DOCS {
+            let response = server.create_router().oneshot(get(uri, None)).await.unwrap();
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED, "{}", uri);
+            let response = server.create_router().oneshot(get(uri, Some("not-a-token"))).await.unwrap();
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED, "{}", uri);
+            let response = server.create_router().oneshot(get(uri, Some(&api_token.token))).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK, "{}", uri);
+        }
+        // Unknown paths under the docs are still plain 404s
+        let response = server.create_router().oneshot(get("/api-docs/other.json", None)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_database_header() {
+        use axum::body::Body;
+        use axum::http::Request;
+        use pmr::api::server::DATABASE_HEADER;
+        use tower::ServiceExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        let database = temp_dir.path().join("test.db");
+        let config = |database_header| {
+            Config::new()
+                .with_database_path(database.clone())
+                .with_log_dir(temp_dir.path().join("logs"))
+                .with_database_header(database_header)
+        };
+        let healthz = || Request::builder().uri("/healthz").body(Body::empty()).unwrap();
+
+        let server = ApiServer::new(ProcessManager::new(config(false)).await.unwrap(), 0).unwrap();
+        let response = server.create_router().oneshot(healthz()).await.unwrap();
+        assert!(response.headers().get(DATABASE_HEADER).is_none());
+
+        let server = ApiServer::new(ProcessManager::new(config(true)).await.unwrap(), 0).unwrap();
+        let response = server.create_router().oneshot(healthz()).await.unwrap();
+        assert_eq!(response.headers()[DATABASE_HEADER], database.to_str().unwrap());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_logs_lines_limit_router() {
+        use axum::body::{to_bytes, Body};
+        use axum::http::Request;
+        use tower::ServiceExt;
+
+        let (process_manager, _auth_manager, temp_dir) = create_test_components().await;
+        let api_token = AuthManager::new(process_manager.get_database())
+            .generate_token("lines".to_string(), None)
+            .await
+            .unwrap();
+        process_manager
+            .start(&named("limited"), "echo", vec!["only line".to_string()], HashMap::new(), None, None)
+            .await
+            .unwrap();
+        sleep(Duration::from_millis(200)).await;
+        drop(process_manager);
+
+        let config = Config::new()
+            .with_database_path(temp_dir.path().join("test.db"))
+            .with_log_dir(temp_dir.path().join("logs"));
+        let server = ApiServer::new(ProcessManager::new(config).await.unwrap(), 0).unwrap();
+        let get = |query: &str| {
+            Request::builder()
+                .uri(format!("/api/processes/limited/logs?{}", query))
+                .header("Authorization", format!("Bearer {}", api_token.token))
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let response = server.create_router().oneshot(get("lines=18446744073709551615")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let response = server.create_router().oneshot(get("lines=10001")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        let response = server.create_router().oneshot(get("lines=10000")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(json["data"], "only line");
+
+        let response = server.create_router().oneshot(get("lines=0")).await.unwrap();
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(json["data"], "");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_logs_tail_bytes_router() {
+        use axum::body::{to_bytes, Body};
+        use axum::http::Request;
+        use tower::ServiceExt;
+
+        let (process_manager, _auth_manager, temp_dir) = create_test_components().await;
+        let api_token = AuthManager::new(process_manager.get_database())
+            .generate_token("bytes".to_string(), None)
+            .await
+            .unwrap();
+        process_manager
+            .start(&named("jsonl"), "echo", vec!["start".to_string()], HashMap::new(), None, None)
+            .await
+            .unwrap();
+        sleep(Duration::from_millis(200)).await;
+        // One huge line between two short ones
+        let log_path = process_manager.get_process_status(&named("jsonl")).await.unwrap().log_path;
+        let content = format!("short\n{{\"blob\":\"{}\"}}\n{{\"end\":1}}\n", "x".repeat(100_000));
+        std::fs::write(&log_path, &content).unwrap();
+        drop(process_manager);
+
+        let config = Config::new()
+            .with_database_path(temp_dir.path().join("test.db"))
+            .with_log_dir(temp_dir.path().join("logs"));
+        let server = ApiServer::new(ProcessManager::new(config).await.unwrap(), 0).unwrap();
+        let get = |query: &str| {
+            Request::builder()
+                .uri(format!("/api/processes/jsonl/logs?{}", query))
+                .header("Authorization", format!("Bearer {}", api_token.token))
+                .body(Body::empty())
+                .unwrap()
+        };
+        let json = |response: axum::response::Response| async move {
+            let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            serde_json::from_slice::<serde_json::Value>(&bytes).unwrap()
+        };
+
+        // The cut lands in the huge line, which is dropped
+        let response = server.create_router().oneshot(get("tail_bytes=64")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = json(response).await;
+        assert_eq!(body["data"], "{\"end\":1}\n");
+        assert_eq!(body["start_offset"], content.len() - 10);
+
+        let body = json(server.create_router().oneshot(get("tail_bytes=64&allow_partial=true")).await.unwrap()).await;
+        assert_eq!(body["data"].as_str().unwrap(), &content[content.len() - 64..]);
+        assert_eq!(body["start_offset"], content.len() - 64);
+
+        let body = json(server.create_router().oneshot(get("tail_bytes=1000000")).await.unwrap()).await;
+        assert_eq!(body["data"], content);
+        assert_eq!(body["start_offset"], 0);
+
+        // Line tails do not know their offset
+        let body = json(server.create_router().oneshot(get("lines=1")).await.unwrap()).await;
+        assert!(body.get("start_offset").is_none());
+
+        let response = server.create_router().oneshot(get("tail_bytes=5242881")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        // No line starts with a time, so none is known to be recent
+        let body = json(server.create_router().oneshot(get("since=1h")).await.unwrap()).await;
+        assert_eq!(body["data"], "");
+        for query in [
+            "tail_bytes=64&lines=1",
+            "tail_bytes=64&merge_rotated=true",
+            "tail_bytes=64&rotated=true",
+            "since=yesterday",
+            "since=1h&tail_bytes=64",
+            "since=1h&merge_rotated=true",
+        ] {
+            let response = server.create_router().oneshot(get(query)).await.unwrap();
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{}", query);
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_idempotent_start_status_codes() {
+        let (process_manager, auth_manager, temp_dir) = create_test_components().await;
+        let api_token = AuthManager::new(process_manager.get_database())
+            .generate_token("idempotent".to_string(), None)
+            .await
+            .unwrap();
+        let request = |args: &[&str], idempotent: bool| {
+            let mut request = start_request("api_steady", "sleep", args);
+            request.working_dir = Some(temp_dir.path().to_string_lossy().to_string());
+            request.idempotent = idempotent;
+            Json(request)
+        };
+        let state = || State((process_manager.clone(), auth_manager.clone()));
+
+        let Json(response) = start_process(state(), authenticated(&api_token), Query(StartQuery { return_value: None }), request(&["30"], true)).await.unwrap();
+        assert!(response.success);
+
+        let Json(response) = start_process(state(), authenticated(&api_token), Query(StartQuery { return_value: None }), request(&["30"], true)).await.unwrap();
+        assert!(response.data.unwrap().unchanged);
+
+        let status = start_process(state(), authenticated(&api_token), Query(StartQuery { return_value: None }), request(&["60"], true)).await.err().map(|(status, _)| status);
+        assert_eq!(status, Some(StatusCode::UNPROCESSABLE_ENTITY));
+
+        let status = start_process(state(), authenticated(&api_token), Query(StartQuery { return_value: None }), request(&["30"], false)).await.err().map(|(status, _)| status);
+        assert_eq!(status, Some(StatusCode::CONFLICT));
+
+        process_manager.delete(&named("api_steady")).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_api_error_handling() {
+        let (process_manager, _auth_manager, _temp_dir) = create_test_components().await;
+        
+        // Test getting non-existent process
+        let result = process_manager.get_process_status(&named("nonexistent")).await;
+        assert!(result.is_err());
+        
+        // Test stopping non-existent process
+        let result = process_manager.stop(&named("nonexistent")).await;
+        assert!(result.is_err());
+        
+        // Test deleting non-existent process
+        let result = process_manager.delete(&named("nonexistent")).await;
+        assert!(result.is_err());
+        
+        // Test getting logs for non-existent process
+        let result = process_manager.get_process_logs(&named("nonexistent"), None).await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_api_operations() {
+        let (process_manager, _auth_manager, _temp_dir) = create_test_components().await;
+        
+        // Start multiple processes concurrently
+        let mut handles = vec![];
+        
+        for i in 0..5 {
+            let pm = process_manager.clone();
+            let handle = tokio::spawn(async move {
+                let env_vars = HashMap::new();
+                pm.start(
+                    &named(&format!("concurrent_{}", i)),
+                    "echo",
+                    vec![format!("Process {}", i)],
+                    env_vars,
+                    None,
+                    None,
+                )
+                .await
+            });
+            handles.push(handle);
+        }
+        
+        // Wait for all processes to start
+        for handle in handles {
+            handle.await.unwrap().unwrap();
+        }
+        
+        // Wait for processes to complete
+        sleep(Duration::from_millis(500)).await;
+        
+        // List all processes
+        let processes = process_manager.list_processes().await.unwrap();
+        assert_eq!(processes.len(), 5);
+        
+        // Clean up all processes
+        for i in 0..5 {
+            process_manager
+                .delete(&named(&format!("concurrent_{}", i)))
+                .await
+                .unwrap();
+        }
+        
+        // Verify cleanup
+        let processes = process_manager.list_processes().await.unwrap();
+        assert_eq!(processes.len(), 0);
+    }
+
+    /// Wait for the next frame, failing instead of hanging when none arrives
+    async fn next_frame(subscription: &mut LogSubscription) -> LogFrame {
+        tokio::time::timeout(Duration::from_secs(5), subscription.next())
+            .await
+            .expect("no frame within 5s")
+            .expect("tailer closed")
+    }
+
+    #[tokio::test]
+    async fn test_log_broker_shares_one_tailer() {
+        let temp_dir = TempDir::new().unwrap();
+        let log_path = temp_dir.path().join("busy.log");
+        std::fs::write(&log_path, "before anyone followed\n").unwrap();
+
+        let broker = LogBroker::new(64, Duration::from_millis(10));
+        let mut first = broker.subscribe("busy", &log_path);
+        let mut second = broker.subscribe("busy", &log_path);
+        assert_eq!(broker.tailer_count(), 1);
+        assert_eq!(broker.subscriber_counts()[0].subscribers, 2);
+
+        // Followers start at the end and only see complete lines
+        sleep(Duration::from_millis(50)).await;
+        let mut file = std::fs::OpenOptions::new().append(true).open(&log_path).unwrap();
+        std::io::Write::write_all(&mut file, b"one\ntwo\npart").unwrap();
+        for subscription in [&mut first, &mut second] {
+            assert_eq!(next_frame(subscription).await, LogFrame::Line("one".to_string()));
+            assert_eq!(next_frame(subscription).await, LogFrame::Line("two".to_string()));
+        }
+        std::io::Write::write_all(&mut file, b"ial\n").unwrap();
+        assert_eq!(next_frame(&mut first).await, LogFrame::Line("partial".to_string()));
+
+        // A different file gets its own tailer
+        let _other = broker.subscribe("quiet", &temp_dir.path().join("quiet.log"));
+        assert_eq!(broker.tailer_count(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_log_broker_reports_skipped_lines_to_slow_subscriber() {
+        let temp_dir = TempDir::new().unwrap();
+        let log_path = temp_dir.path().join("flood.log");
+        std::fs::write(&log_path, "").unwrap();
+
+        let broker = LogBroker::new(4, Duration::from_millis(10));
+        let mut slow = broker.subscribe("flood", &log_path);
+        sleep(Duration::from_millis(50)).await;
+        let lines: String = (1..=10).map(|i| format!("line {}\n", i)).collect();
+        std::fs::write(&log_path, lines).unwrap();
+        sleep(Duration::from_millis(200)).await;
+
+        // Only the newest lines are kept; the rest is announced, not silently lost
+        assert_eq!(next_frame(&mut slow).await, LogFrame::Skipped(6));
+        assert_eq!(LogFrame::Skipped(6).to_string(), "[6 lines skipped]");
+        assert_eq!(next_frame(&mut slow).await, LogFrame::Line("line 7".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_log_broker_stops_tailer_after_last_unsubscribe() {
+        let temp_dir = TempDir::new().unwrap();
+        let log_path = temp_dir.path().join("short.log");
+        let broker = LogBroker::new(8, Duration::from_millis(10));
+
+        let first = broker.subscribe("short", &log_path);
+        let second = broker.subscribe("short", &log_path);
+        drop(first);
+        assert_eq!(broker.tailer_count(), 1);
+        assert_eq!(broker.subscriber_counts()[0].subscribers, 1);
+        drop(second);
+        assert_eq!(broker.tailer_count(), 0);
+        assert!(broker.subscriber_counts().is_empty());
+
+        // Subscribing again starts a fresh tailer
+        let _again = broker.subscribe("short", &log_path);
+        assert_eq!(broker.tailer_count(), 1);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_metrics_router() {
+        use axum::body::{to_bytes, Body};
+        use axum::http::Request;
+        use tower::ServiceExt;
+
+        let (process_manager, _auth_manager, temp_dir) = create_test_components().await;
+        let api_token = AuthManager::new(process_manager.get_database())
+            .generate_token("metrics".to_string(), None)
+            .await
+            .unwrap();
+        drop(process_manager);
+
+        let config = Config::new()
+            .with_database_path(temp_dir.path().join("test.db"))
+            .with_log_dir(temp_dir.path().join("logs"));
+        let server = ApiServer::new(ProcessManager::new(config).await.unwrap(), 0).unwrap();
+        let get = |token: Option<&str>| {
+            let mut request = Request::builder().uri("/api/metrics");
+            if let Some(token) = token {
+                request = request.header("Authorization", format!("Bearer {}", token));
+            }
+            request.body(Body::empty()).unwrap()
+        };
+
+        let response = server.create_router().oneshot(get(None)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let response = server.create_router().oneshot(get(Some(&api_token.token))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(json["total_log_subscribers"], 0);
+        assert_eq!(json["log_subscribers"], serde_json::json!([]));
+        assert_eq!(json["file_pool"]["limit"], 64);
+        assert_eq!(json["file_pool"]["in_flight"], 0);
+        assert_eq!(json["file_pool"]["timed_out"], 0);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_internal_processes_are_not_served() {
+        use pmr::{api::handlers::{list_processes, ListQuery}, database::HTTP_SERVER_PROCESS_NAME, process::StartOptions};
+
+        let (process_manager, auth_manager, _temp_dir) = create_test_components().await;
+        let api_token = AuthManager::new(process_manager.get_database())
+            .generate_token("internal".to_string(), None)
+            .await
+            .unwrap();
+        let state = || State((process_manager.clone(), auth_manager.clone()));
+        let internal = StartOptions { internal: true, ..Default::default() };
+        process_manager
+            .start_with_options(&named(HTTP_SERVER_PROCESS_NAME), "sleep", vec!["30".to_string()], HashMap::new(), internal)
+            .await
+            .unwrap();
+
+        let Json(response) = list_processes(state(), authenticated(&api_token), Query(ListQuery { refresh: None, truncate: None, search: None })).await.unwrap();
+        assert!(response.data.unwrap().is_empty());
+        let query = StatusQuery { snapshot: None, notes: None, refresh: None, truncate: None };
+        let response = get_process_status(state(), authenticated(&api_token), Path(HTTP_SERVER_PROCESS_NAME.parse().unwrap()), Query(query)).await;
+        assert_eq!(response.err(), Some(StatusCode::NOT_FOUND));
+
+        // The name cannot be taken over through the API either
+        let request = start_request(HTTP_SERVER_PROCESS_NAME, "sleep", &["30"]);
+        let (status, Json(response)) = start_process(state(), authenticated(&api_token), Query(StartQuery { return_value: None }), Json(request)).await.err().unwrap();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(response.error.as_deref().unwrap().contains("reserved"));
+
+        process_manager.delete(&named(HTTP_SERVER_PROCESS_NAME)).await.unwrap();
+    }
+
+    /// Running record for a PID that only exists in a fake probe
+    #[cfg(feature = "test-util")]
+    fn running_record(name: &str, pid: u32, created_at: chrono::DateTime<chrono::Utc>) -> pmr::database::ProcessRecord {
+        pmr::database::ProcessRecord {
+            id: uuid::Uuid::new_v4().to_string(),
+            name: name.parse().unwrap(),
+            command: "sleep".to_string(),
+            args: vec!["30".to_string()],
+            env_vars: HashMap::new(),
+            working_dir: "/".to_string(),
+            pid: Some(pid),
+            status: pmr::database::ProcessStatus::Running,
+            created_at,
+            updated_at: created_at,
+            log_path: format!("/tmp/{}.log", name),
+            log_dir: None,
+            cgroup_memory_max: None,
+            cgroup_cpu_max: None,
+            cgroup: None,
+            deleted_at: None,
+            binary_path: None,
+            binary_sha256: None,
+            canonical_workdir: None,
+            cpu_affinity: None,
+            umask: None,
+            description: None,
+            exit_code: None,
+            exit_reason: None,
+            start_snapshot: None,
+            default_env_keys: Vec::new(),
+            drift: None,
+            notes: Vec::new(),
+            recent_runs: Vec::new(),
+            flapping: false,
+            degraded: false,
+            inferred: false,
+            truncated_fields: Vec::new(),
+            kind: pmr::database::ProcessKind::Service,
+            runs: 1,
+            stop_signal: libc::SIGTERM,
+            log_dir_mode: pmr::database::LogDirMode::Default,
+            status_verified_at: None,
+            internal: false,
+            ephemeral_keep_secs: None,
+            delete_after: None,
+            last_output: Vec::new(),
+            last_output_lossy: false,
+            watch: None,
+            rotate_on_start: None,
+        }
+    }
+
+    #[cfg(feature = "test-util")]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_stats_and_metrics_report_status_ages() {
+        use axum::body::{to_bytes, Body};
+        use axum::http::Request;
+        use pmr::{clock::{Clock, ManualClock}, database::ProcessStatus};
+        use tower::ServiceExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        let clock = Arc::new(ManualClock::starting_now());
+        let process_manager = ProcessManager::builder(Config::in_directory(temp_dir.path())).clock(clock.clone()).build().await.unwrap();
+        let database = process_manager.get_database();
+        let api_token = AuthManager::new(database.clone()).generate_token("alerts".to_string(), None).await.unwrap();
+        let backdated = |name: &str, status: ProcessStatus, minutes: i64| pmr::database::ProcessRecord {
+            status,
+            pid: None,
+            ..running_record(name, 1, clock.now() - chrono::Duration::minutes(minutes))
+        };
+        database.insert_process(&backdated("crashed", ProcessStatus::Failed, 90)).await.unwrap();
+        database.insert_process(&backdated("crashed-again", ProcessStatus::Failed, 5)).await.unwrap();
+        database.insert_process(&backdated("migrate", ProcessStatus::Stopped, 20)).await.unwrap();
+        database.insert_process(&backdated("report", ProcessStatus::Succeeded, 600)).await.unwrap();
+
+        let router = ApiServer::new(process_manager, 0).unwrap().create_router();
+        let get = |uri: &str| {
+            let request = Request::builder().uri(uri).header("Authorization", format!("Bearer {}", api_token.token));
+            let router = router.clone();
+            async move {
+                let response = router.oneshot(request.body(Body::empty()).unwrap()).await.unwrap();
+                assert_eq!(response.status(), StatusCode::OK);
+                let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+                serde_json::from_slice::<serde_json::Value>(&bytes).unwrap()
+            }
+        };
+
+        let stats = get("/api/stats").await;
+        assert_eq!(stats["oldest_failed_age_seconds"], 90 * 60);
+        assert_eq!(stats["oldest_stopped_age_seconds"], 20 * 60);
+        assert_eq!(stats["counts"]["failed"], 2);
+        assert_eq!(stats["counts"]["succeeded"], 1);
+
+        clock.advance(std::time::Duration::from_secs(60));
+        let gauges = get("/api/metrics").await["processes"].clone();
+        assert_eq!(gauges["oldest_failed_age_seconds"], 91 * 60);
+        assert_eq!(gauges["oldest_stopped_age_seconds"], 21 * 60);
+        assert_eq!(gauges["counts"]["total"], 4);
+
+        database.delete_process(&named("migrate")).await.unwrap();
+        assert_eq!(get("/api/metrics").await["processes"]["oldest_stopped_age_seconds"], serde_json::Value::Null);
+    }
+
+    #[cfg(feature = "test-util")]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_refresh_modes_probe_as_asked() {
+        use pmr::{api::handlers::{list_processes, ListQuery}, clock::{Clock, ManualClock}, pid_probe::FakePidProbe};
+
+        let temp_dir = TempDir::new().unwrap();
+        let clock = Arc::new(ManualClock::starting_now());
+        let probe = Arc::new(FakePidProbe::new(clock.clone()));
+        let process_manager = Arc::new(
+            ProcessManager::builder(Config::in_directory(temp_dir.path()))
+                .clock(clock.clone())
+                .pid_probe(probe.clone())
+                .build()
+                .await
+                .unwrap(),
+        );
+        let database = process_manager.get_database();
+        let auth_manager = Arc::new(Mutex::new(AuthManager::new(database.clone())));
+        let api_token = AuthManager::new(database.clone()).generate_token("refresh".to_string(), None).await.unwrap();
+        for (name, pid) in [("a", 9001), ("b", 9002), ("c", 9003)] {
+            probe.add(pid);
+            database.insert_process(&running_record(name, pid, clock.now())).await.unwrap();
+        }
+
+        let state = || State((process_manager.clone(), auth_manager.clone()));
+        let list = |refresh: Option<&str>| {
+            list_processes(state(), authenticated(&api_token), Query(ListQuery { refresh: refresh.map(str::to_string), truncate: None, search: None }))
+        };
+        let status = |name: &str, refresh: Option<&str>| {
+            get_process_status(
+                state(),
+                authenticated(&api_token),
+                Path(name.parse().unwrap()),
+                Query(StatusQuery { snapshot: None, notes: None, refresh: refresh.map(str::to_string), truncate: None }),
+            )
+        };
+        // Probes made by `request`, and what it answered
+        async fn probes<T>(probe: &FakePidProbe, request: impl std::future::Future<Output = T>) -> (usize, T) {
+            let before = probe.probe_count();
+            let response = request.await;
+            (probe.probe_count() - before, response)
+        }
+
+        // Stored statuses are returned as they are, never verified
+        let (count, response) = probes(&probe, list(Some("false"))).await;
+        assert_eq!(count, 0);
+        let processes = response.unwrap().0.data.unwrap();
+        assert!(processes.iter().all(|process| process.status_verified_at.is_none()));
+
+        // Stale (the default) probes what was never verified, then nothing within the window
+        let (count, response) = probes(&probe, list(None)).await;
+        assert_eq!(count, 3);
+        let verified_at = clock.now();
+        let processes = response.unwrap().0.data.unwrap();
+        assert!(processes.iter().all(|process| process.status_verified_at == Some(verified_at)));
+        assert_eq!(probes(&probe, list(Some("stale"))).await.0, 0);
+        assert_eq!(probes(&probe, status("a", None)).await.0, 0);
+
+        // A full refresh probes everything regardless of freshness
+        assert_eq!(probes(&probe, list(Some("true"))).await.0, 3);
+        assert_eq!(probes(&probe, status("a", Some("true"))).await.0, 1);
+
+        // Once the window has passed, stale probes again and records the new time
+        clock.advance(std::time::Duration::from_secs(3));
+        let (count, response) = probes(&probe, status("b", None)).await;
+        assert_eq!(count, 1);
+        assert_eq!(response.unwrap().0.data.unwrap().status_verified_at, Some(clock.now()));
+        // Only the two records not just verified are probed
+        assert_eq!(probes(&probe, list(None)).await.0, 2);
+
+        // Stale records are probed for real: an exit shows up
+        probe.exit_after(9003, std::time::Duration::ZERO);
+        clock.advance(std::time::Duration::from_secs(3));
+        let (_, response) = probes(&probe, status("c", Some("false"))).await;
+        assert_eq!(response.unwrap().0.data.unwrap().status, pmr::database::ProcessStatus::Running);
+        let (_, response) = probes(&probe, status("c", Some("stale"))).await;
+        assert_eq!(response.unwrap().0.data.unwrap().status, pmr::database::ProcessStatus::Stopped);
+
+        assert_eq!(list(Some("sometimes")).await.err(), Some(StatusCode::BAD_REQUEST));
+        assert_eq!(status("a", Some("yes")).await.err(), Some(StatusCode::BAD_REQUEST));
+    }
+
+    #[cfg(feature = "test-util")]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_failing_status_writes_report_probed_statuses_degraded() {
+        use pmr::{api::handlers::{list_processes, ListQuery}, clock::{Clock, ManualClock}, database::ProcessStatus, pid_probe::FakePidProbe};
+
+        let temp_dir = TempDir::new().unwrap();
+        let clock = Arc::new(ManualClock::starting_now());
+        let probe = Arc::new(FakePidProbe::new(clock.clone()));
+        let process_manager = Arc::new(
+            ProcessManager::builder(Config::in_directory(temp_dir.path()))
+                .clock(clock.clone())
+                .pid_probe(probe.clone())
+                .build()
+                .await
+                .unwrap(),
+        );
+        let database = process_manager.get_database();
+        let auth_manager = Arc::new(Mutex::new(AuthManager::new(database.clone())));
+        let api_token = AuthManager::new(database.clone()).generate_token("degraded".to_string(), None).await.unwrap();
+        for (name, pid) in [("up", 9101), ("gone", 9102)] {
+            probe.add(pid);
+            database.insert_process(&running_record(name, pid, clock.now())).await.unwrap();
+        }
+        database.fail_status_writes(true);
+        probe.exit_after(9102, std::time::Duration::ZERO);
+
+        let state = || State((process_manager.clone(), auth_manager.clone()));
+        let response = list_processes(state(), authenticated(&api_token), Query(ListQuery { refresh: Some("true".to_string()), truncate: None, search: None }))
+            .await
+            .unwrap();
+        let body = serde_json::to_value(&response.0).unwrap();
+        assert!(body["data"].as_array().unwrap().iter().all(|process| process["degraded"] == true));
+        let processes = response.0.data.unwrap();
+        let status_of = |name: &str| processes.iter().find(|p| p.name == name).unwrap().status.clone();
+        assert_eq!(status_of("up"), ProcessStatus::Running);
+        assert_eq!(status_of("gone"), ProcessStatus::Stopped);
+
+        let query = Query(StatusQuery { snapshot: None, notes: None, refresh: Some("true".to_string()), truncate: None });
+        let response = get_process_status(state(), authenticated(&api_token), Path(named("gone")), query).await.unwrap();
+        let process = response.0.data.unwrap();
+        assert_eq!(process.status, ProcessStatus::Stopped);
+        assert!(process.degraded);
+        assert_eq!(database.get_process_by_name(&named("gone")).await.unwrap().unwrap().status, ProcessStatus::Running);
     }
 }
 
@@ -262,5 +2349,4 @@ mod http_api_tests {
 #[test]
 fn test_http_api_not_enabled() {
     // This test just ensures the file compiles when http-api feature is disabled
-    assert!(true);
 }
@@ -1,3 +1,5 @@
+mod support;
+
 use pmr::{
     config::{Config, LogRotationConfig},
     database::ProcessStatus,
@@ -6,6 +8,7 @@ use pmr::{
 use std::collections::HashMap;
 use tempfile::TempDir;
 use tokio::time::{sleep, Duration};
+use support::named;
 
 /// Helper function to create a test ProcessManager with temporary directories
 async fn create_test_process_manager() -> (ProcessManager, TempDir) {
@@ -36,7 +39,7 @@ async fn test_clear_stopped_processes() {
     let args_running = vec!["10".to_string()];
     let env_vars_running = HashMap::new();
 
-    pm.start_process(name_running, command_running, args_running, env_vars_running, None, None).await.unwrap();
+    pm.start(&named(name_running), command_running, args_running, env_vars_running, None, None).await.unwrap();
 
     // Start a process that will finish quickly
     let name_finished = "test_clear_finished";
@@ -44,7 +47,7 @@ async fn test_clear_stopped_processes() {
     let args_finished = vec!["test".to_string()];
     let env_vars_finished = HashMap::new();
 
-    pm.start_process(name_finished, command_finished, args_finished, env_vars_finished, None, None).await.unwrap();
+    pm.start(&named(name_finished), command_finished, args_finished, env_vars_finished, None, None).await.unwrap();
 
     // Wait for the echo process to finish and status to be updated
     sleep(Duration::from_millis(1000)).await;
@@ -68,7 +71,7 @@ async fn test_clear_stopped_processes() {
 
     // Clean up any remaining processes
     for process in processes_after {
-        let _ = pm.delete_process(&process.name).await;
+        let _ = pm.delete(&process.name).await;
     }
 }
 
@@ -82,7 +85,7 @@ async fn test_clear_all_processes() {
     let args1 = vec!["Hello".to_string()];
     let env_vars1 = HashMap::new();
     
-    pm.start_process(name1, command1, args1, env_vars1, None, None).await.unwrap();
+    pm.start(&named(name1), command1, args1, env_vars1, None, None).await.unwrap();
     
     // Start a long-running process
     let name2 = "test_clear_all_running";
@@ -90,7 +93,7 @@ async fn test_clear_all_processes() {
     let args2 = vec!["10".to_string()];
     let env_vars2 = HashMap::new();
     
-    pm.start_process(name2, command2, args2, env_vars2, None, None).await.unwrap();
+    pm.start(&named(name2), command2, args2, env_vars2, None, None).await.unwrap();
     
     // Wait for the echo process to finish
     sleep(Duration::from_millis(500)).await;
@@ -139,7 +142,7 @@ async fn test_clear_only_running_processes() {
     let args = vec!["10".to_string()];
     let env_vars = HashMap::new();
     
-    pm.start_process(name, command, args, env_vars, None, None).await.unwrap();
+    pm.start(&named(name), command, args, env_vars, None, None).await.unwrap();
     
     // Clear stopped/failed processes (should clear nothing)
     let clear_result = pm.clear_processes(false).await.unwrap();
@@ -157,5 +160,43 @@ async fn test_clear_only_running_processes() {
     assert_eq!(processes[0].status, ProcessStatus::Running);
     
     // Clean up
-    pm.delete_process(name).await.unwrap();
+    pm.delete(&named(name)).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_clear_result_is_sorted_exclusive_and_explains_failures() {
+    let (pm, temp_dir) = create_test_process_manager().await;
+    for name in ["zeta", "ghost", "alpha", "mid"] {
+        pm.start(&named(name), "true", vec![], HashMap::new(), None, None).await.unwrap();
+    }
+    sleep(Duration::from_millis(500)).await;
+    assert!(pm.list_processes().await.unwrap().iter().all(|process| process.status != ProcessStatus::Running));
+
+    // Another client removes 'ghost' just as the clear gets to it
+    let database_url = format!("sqlite:{}", temp_dir.path().join("test.db").display());
+    let pool = sqlx::SqlitePool::connect(&database_url).await.unwrap();
+    sqlx::query(
+        "CREATE TRIGGER ghost_vanishes BEFORE UPDATE OF deleted_at ON processes WHEN OLD.name = 'ghost' \
+         BEGIN DELETE FROM processes WHERE id = OLD.id; SELECT RAISE(IGNORE); END",
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+    pool.close().await;
+
+    let result = pm.clear_processes(false).await.unwrap();
+    assert_eq!(result.cleared_processes, ["alpha", "mid", "zeta"]);
+    assert_eq!(result.cleared_count, 3);
+    assert_eq!(result.failed_processes, ["ghost"]);
+    assert!(result.cleared_processes.iter().all(|name| !result.failed_processes.contains(name)));
+    assert_eq!(result.details.keys().collect::<Vec<_>>(), ["ghost"]);
+    assert_eq!(result.details["ghost"], "Process 'ghost' not found");
+
+    let json = serde_json::to_value(&result).unwrap();
+    assert_eq!(json["details"]["ghost"], "Process 'ghost' not found");
+    #[cfg(feature = "cli")]
+    {
+        let text = pmr::formatter::Formatter::new(pmr::cli::OutputFormat::Text).format_clear_result(&result);
+        assert!(text.contains("Failed to clear 1 processes:\n  - ghost\n      Process 'ghost' not found"), "{}", text);
+    }
 }
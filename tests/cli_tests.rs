@@ -1,3 +1,6 @@
+// Runs the pmr binary, which is only built with the `cli` feature
+#![cfg(feature = "cli")]
+
 use std::process::Command;
 use std::path::PathBuf;
 use tempfile::TempDir;
@@ -16,12 +19,30 @@ fn create_test_command() -> (Command, TempDir) {
     let temp_dir = TempDir::new().unwrap();
     let mut cmd = Command::new(get_pmr_binary());
     
-    // Set HOME to temp directory so pmr uses it for config
-    cmd.env("HOME", temp_dir.path());
+    // Set HOME to temp directory so pmr uses it for config, and run from it so logs
+    // written relative to the working directory stay out of the source tree
+    cmd.env("HOME", temp_dir.path()).current_dir(temp_dir.path());
     
     (cmd, temp_dir)
 }
 
+/// Run `cmd` with a terminal for stdin on which `typed` is already waiting, as if typed
+/// at the prompt
+fn output_with_terminal_stdin(cmd: &mut Command, typed: &str) -> std::process::Output {
+    use std::io::Write;
+    use std::os::fd::FromRawFd;
+
+    let (mut master, mut slave) = (0, 0);
+    let opened = unsafe { libc::openpty(&mut master, &mut slave, std::ptr::null_mut(), std::ptr::null(), std::ptr::null()) };
+    assert_eq!(opened, 0, "openpty failed");
+    let master = unsafe { std::fs::File::from_raw_fd(master) };
+    let slave = unsafe { std::os::fd::OwnedFd::from_raw_fd(slave) };
+    (&master).write_all(typed.as_bytes()).unwrap();
+    let output = cmd.stdin(std::process::Stdio::from(slave)).output().expect("Failed to execute pmr");
+    drop(master);
+    output
+}
+
 #[test]
 fn test_pmr_help() {
     let (mut cmd, _temp_dir) = create_test_command();
@@ -65,40 +86,40 @@ fn test_pmr_list_empty() {
 #[test]
 fn test_pmr_start_simple_process() {
     let (mut cmd, temp_dir) = create_test_command();
-    cmd.args(&["start", "test_echo", "echo", "Hello, World!"]);
+    cmd.args(["start", "test_echo", "echo", "Hello, World!"]);
     
     let output = cmd.output().expect("Failed to execute pmr");
     assert!(output.status.success());
     
     let stdout = String::from_utf8_lossy(&output.stdout);
-    assert!(stdout.contains("Process 'test_echo' started") || stdout.contains("exited quickly"));
+    assert!(stdout.contains("Process 'test_echo' completed successfully in"));
     
     // Clean up - delete the process
     let (mut cleanup_cmd, _) = create_test_command();
-    cleanup_cmd.env("HOME", temp_dir.path());
-    cleanup_cmd.args(&["delete", "test_echo"]);
+    cleanup_cmd.env("HOME", temp_dir.path()).current_dir(temp_dir.path());
+    cleanup_cmd.args(["delete", "test_echo"]);
     let _ = cleanup_cmd.output();
 }
 
 #[test]
 fn test_pmr_start_with_args() {
     let (mut cmd, temp_dir) = create_test_command();
-    cmd.args(&["start", "test_args", "echo", "arg1", "arg2", "arg3"]);
+    cmd.args(["start", "test_args", "echo", "arg1", "arg2", "arg3"]);
     
     let output = cmd.output().expect("Failed to execute pmr");
     assert!(output.status.success());
     
     // Clean up
     let (mut cleanup_cmd, _) = create_test_command();
-    cleanup_cmd.env("HOME", temp_dir.path());
-    cleanup_cmd.args(&["delete", "test_args"]);
+    cleanup_cmd.env("HOME", temp_dir.path()).current_dir(temp_dir.path());
+    cleanup_cmd.args(["delete", "test_args"]);
     let _ = cleanup_cmd.output();
 }
 
 #[test]
 fn test_pmr_start_with_env_vars() {
     let (mut cmd, temp_dir) = create_test_command();
-    cmd.args(&[
+    cmd.args([
         "start", 
         "test_env", 
         "-e", "TEST_VAR=test_value",
@@ -111,8 +132,8 @@ fn test_pmr_start_with_env_vars() {
     
     // Clean up
     let (mut cleanup_cmd, _) = create_test_command();
-    cleanup_cmd.env("HOME", temp_dir.path());
-    cleanup_cmd.args(&["delete", "test_env"]);
+    cleanup_cmd.env("HOME", temp_dir.path()).current_dir(temp_dir.path());
+    cleanup_cmd.args(["delete", "test_env"]);
     let _ = cleanup_cmd.output();
 }
 
@@ -121,7 +142,7 @@ fn test_pmr_start_with_working_directory() {
     let (mut cmd, temp_dir) = create_test_command();
     let work_dir = temp_dir.path().to_string_lossy();
     
-    cmd.args(&[
+    cmd.args([
         "start", 
         "test_workdir", 
         "--workdir", &work_dir,
@@ -133,23 +154,23 @@ fn test_pmr_start_with_working_directory() {
     
     // Clean up
     let (mut cleanup_cmd, _) = create_test_command();
-    cleanup_cmd.env("HOME", temp_dir.path());
-    cleanup_cmd.args(&["delete", "test_workdir"]);
+    cleanup_cmd.env("HOME", temp_dir.path()).current_dir(temp_dir.path());
+    cleanup_cmd.args(["delete", "test_workdir"]);
     let _ = cleanup_cmd.output();
 }
 
 #[test]
 fn test_pmr_start_duplicate_name() {
     let (mut cmd1, temp_dir) = create_test_command();
-    cmd1.args(&["start", "duplicate", "sleep", "1"]);
+    cmd1.args(["start", "duplicate", "sleep", "1"]);
     
     let output1 = cmd1.output().expect("Failed to execute pmr");
     assert!(output1.status.success());
     
     // Try to start another process with the same name
     let (mut cmd2, _) = create_test_command();
-    cmd2.env("HOME", temp_dir.path());
-    cmd2.args(&["start", "duplicate", "sleep", "1"]);
+    cmd2.env("HOME", temp_dir.path()).current_dir(temp_dir.path());
+    cmd2.args(["start", "duplicate", "sleep", "1"]);
     
     let output2 = cmd2.output().expect("Failed to execute pmr");
     assert!(!output2.status.success());
@@ -159,15 +180,137 @@ fn test_pmr_start_duplicate_name() {
     
     // Clean up
     let (mut cleanup_cmd, _) = create_test_command();
-    cleanup_cmd.env("HOME", temp_dir.path());
-    cleanup_cmd.args(&["delete", "duplicate"]);
+    cleanup_cmd.env("HOME", temp_dir.path()).current_dir(temp_dir.path());
+    cleanup_cmd.args(["delete", "duplicate"]);
     let _ = cleanup_cmd.output();
 }
 
+#[test]
+fn test_pmr_start_rejects_names_that_read_as_log_archives() {
+    let (_, temp_dir) = create_test_command();
+    let pmr = |args: &[&str]| {
+        let (mut cmd, _) = create_test_command();
+        cmd.env("HOME", temp_dir.path()).current_dir(temp_dir.path()).args(args);
+        cmd.output().expect("Failed to execute pmr")
+    };
+
+    assert!(pmr(&["start", "web", "echo", "web output"]).status.success());
+    // `web.1.log` would be taken for archive 1 of `web` by rotation and purges
+    let output = pmr(&["start", "web.1", "echo", "web.1 output"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Invalid process name 'web.1'"), "{}", stderr);
+    assert!(!temp_dir.path().join("logs").join("web.1.log").exists());
+
+    assert!(pmr(&["restart", "web", "--reset-logs"]).status.success());
+    assert!(pmr(&["delete", "web"]).status.success());
+}
+
+#[test]
+fn test_pmr_start_idempotent_exit_codes() {
+    let (_, temp_dir) = create_test_command();
+    let workdir = temp_dir.path().to_string_lossy().to_string();
+    let start = |args: &[&str]| {
+        let (mut cmd, _) = create_test_command();
+        cmd.env("HOME", temp_dir.path()).current_dir(temp_dir.path());
+        cmd.args(["start", "--idempotent", "--workdir", &workdir, "steady", "sleep"]);
+        cmd.args(args);
+        cmd.output().expect("Failed to execute pmr")
+    };
+
+    assert!(start(&["30"]).status.success());
+
+    let output = start(&["30"]);
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("already running, unchanged"));
+
+    let output = start(&["60"]);
+    assert_eq!(output.status.code(), Some(3));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("differs in: args"));
+
+    let (mut cleanup_cmd, _) = create_test_command();
+    cleanup_cmd.env("HOME", temp_dir.path()).current_dir(temp_dir.path());
+    cleanup_cmd.args(["delete", "--hard", "steady"]);
+    let _ = cleanup_cmd.output();
+}
+
+#[test]
+fn test_pmr_exists_exit_codes() {
+    let (_, temp_dir) = create_test_command();
+    let pmr = |args: &[&str]| {
+        let (mut cmd, _) = create_test_command();
+        cmd.env("HOME", temp_dir.path()).current_dir(temp_dir.path());
+        cmd.args(args);
+        cmd.output().expect("Failed to execute pmr")
+    };
+
+    let output = pmr(&["exists", "probe"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "Process name 'probe' is free");
+
+    assert!(pmr(&["start", "probe", "sleep", "30"]).status.success());
+    let output = pmr(&["exists", "probe"]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "Process 'probe' exists (status: running)");
+    let output = pmr(&["--format", "json", "exists", "probe"]);
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!((json["exists"].as_bool(), json["reservation"].is_null()), (Some(true), true), "{}", json);
+
+    let _ = pmr(&["delete", "--hard", "probe"]);
+}
+
+fn set_mode(path: &std::path::Path, mode: u32) {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode)).unwrap();
+}
+
+fn mode(path: &std::path::Path) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::metadata(path).unwrap().permissions().mode() & 0o777
+}
+
+#[test]
+fn test_pmr_warns_about_and_fixes_readable_secrets() {
+    let (_, temp_dir) = create_test_command();
+    let pmr = |args: &[&str]| {
+        let (mut cmd, _) = create_test_command();
+        cmd.env("HOME", temp_dir.path()).current_dir(temp_dir.path()).args(args).output().expect("Failed to execute pmr")
+    };
+    let pmr_dir = temp_dir.path().join(".pmr");
+    let database = pmr_dir.join("processes.db");
+
+    // Created by pmr, they are its user's only
+    let list = pmr(&["list"]);
+    assert!(list.status.success());
+    assert_eq!((mode(&pmr_dir), mode(&database)), (0o700, 0o600));
+    assert!(!String::from_utf8_lossy(&list.stderr).contains("readable by other users"));
+    assert_eq!(String::from_utf8_lossy(&pmr(&["doctor"]).stdout).trim(), "No problems found.");
+
+    set_mode(&pmr_dir, 0o755);
+    set_mode(&database, 0o644);
+    let stderr = String::from_utf8_lossy(&pmr(&["list"]).stderr).to_string();
+    assert!(
+        stderr.contains(&format!("Warning: {} is readable by other users (mode 0644, expected 0600)", database.display())),
+        "{}",
+        stderr
+    );
+    let doctor = pmr(&["doctor"]);
+    assert_eq!(doctor.status.code(), Some(1));
+    let report = String::from_utf8_lossy(&doctor.stdout);
+    assert!(report.contains(&format!("\n  {}: mode 0755, expected 0700", pmr_dir.display())), "{}", report);
+    assert!(report.contains(&format!("\n  {}: mode 0644, expected 0600", database.display())), "{}", report);
+
+    let fixed = pmr(&["doctor", "--fix-permissions"]);
+    assert_eq!(fixed.status.code(), Some(0));
+    assert!(String::from_utf8_lossy(&fixed.stdout).contains(&format!("\n  {}: mode 0644 -> 0600", database.display())));
+    assert_eq!((mode(&pmr_dir), mode(&database)), (0o700, 0o600));
+    assert_eq!(String::from_utf8_lossy(&pmr(&["doctor"]).stdout).trim(), "No problems found.");
+}
+
 #[test]
 fn test_pmr_status_nonexistent() {
     let (mut cmd, _temp_dir) = create_test_command();
-    cmd.args(&["status", "nonexistent"]);
+    cmd.args(["status", "nonexistent"]);
     
     let output = cmd.output().expect("Failed to execute pmr");
     assert!(!output.status.success());
@@ -179,7 +322,7 @@ fn test_pmr_status_nonexistent() {
 #[test]
 fn test_pmr_stop_nonexistent() {
     let (mut cmd, _temp_dir) = create_test_command();
-    cmd.args(&["stop", "nonexistent"]);
+    cmd.args(["stop", "nonexistent"]);
     
     let output = cmd.output().expect("Failed to execute pmr");
     assert!(!output.status.success());
@@ -191,7 +334,7 @@ fn test_pmr_stop_nonexistent() {
 #[test]
 fn test_pmr_delete_nonexistent() {
     let (mut cmd, _temp_dir) = create_test_command();
-    cmd.args(&["delete", "nonexistent"]);
+    cmd.args(["delete", "nonexistent"]);
     
     let output = cmd.output().expect("Failed to execute pmr");
     assert!(!output.status.success());
@@ -203,7 +346,7 @@ fn test_pmr_delete_nonexistent() {
 #[test]
 fn test_pmr_logs_nonexistent() {
     let (mut cmd, _temp_dir) = create_test_command();
-    cmd.args(&["logs", "nonexistent"]);
+    cmd.args(["logs", "nonexistent"]);
     
     let output = cmd.output().expect("Failed to execute pmr");
     assert!(!output.status.success());
@@ -215,7 +358,7 @@ fn test_pmr_logs_nonexistent() {
 #[test]
 fn test_pmr_full_workflow() {
     let (mut start_cmd, temp_dir) = create_test_command();
-    start_cmd.args(&["start", "workflow_test", "echo", "workflow test"]);
+    start_cmd.args(["start", "workflow_test", "echo", "workflow test"]);
     
     // Start process
     let output = start_cmd.output().expect("Failed to start process");
@@ -223,7 +366,7 @@ fn test_pmr_full_workflow() {
     
     // List processes
     let (mut list_cmd, _) = create_test_command();
-    list_cmd.env("HOME", temp_dir.path());
+    list_cmd.env("HOME", temp_dir.path()).current_dir(temp_dir.path());
     list_cmd.arg("list");
     
     let output = list_cmd.output().expect("Failed to list processes");
@@ -234,16 +377,16 @@ fn test_pmr_full_workflow() {
     
     // Get status
     let (mut status_cmd, _) = create_test_command();
-    status_cmd.env("HOME", temp_dir.path());
-    status_cmd.args(&["status", "workflow_test"]);
+    status_cmd.env("HOME", temp_dir.path()).current_dir(temp_dir.path());
+    status_cmd.args(["status", "workflow_test"]);
     
     let output = status_cmd.output().expect("Failed to get status");
     assert!(output.status.success());
     
     // Get logs
     let (mut logs_cmd, _) = create_test_command();
-    logs_cmd.env("HOME", temp_dir.path());
-    logs_cmd.args(&["logs", "workflow_test"]);
+    logs_cmd.env("HOME", temp_dir.path()).current_dir(temp_dir.path());
+    logs_cmd.args(["logs", "workflow_test"]);
     
     let output = logs_cmd.output().expect("Failed to get logs");
     assert!(output.status.success());
@@ -253,15 +396,15 @@ fn test_pmr_full_workflow() {
     
     // Delete process
     let (mut delete_cmd, _) = create_test_command();
-    delete_cmd.env("HOME", temp_dir.path());
-    delete_cmd.args(&["delete", "workflow_test"]);
+    delete_cmd.env("HOME", temp_dir.path()).current_dir(temp_dir.path());
+    delete_cmd.args(["delete", "workflow_test"]);
     
     let output = delete_cmd.output().expect("Failed to delete process");
     assert!(output.status.success());
     
     // Verify deletion
     let (mut final_list_cmd, _) = create_test_command();
-    final_list_cmd.env("HOME", temp_dir.path());
+    final_list_cmd.env("HOME", temp_dir.path()).current_dir(temp_dir.path());
     final_list_cmd.arg("list");
     
     let output = final_list_cmd.output().expect("Failed to list processes");
@@ -271,10 +414,110 @@ fn test_pmr_full_workflow() {
     assert!(stdout.contains("No processes found"));
 }
 
+#[test]
+fn test_pmr_list_quiet_and_no_header() {
+    let (mut start_cmd, temp_dir) = create_test_command();
+    start_cmd.args(["start", "quiet_test", "echo", "hello"]);
+    assert!(start_cmd.output().expect("Failed to start process").status.success());
+
+    let list = |args: &[&str]| {
+        let (mut cmd, _) = create_test_command();
+        cmd.env("HOME", temp_dir.path()).current_dir(temp_dir.path());
+        cmd.args(args);
+        let output = cmd.output().expect("Failed to list processes");
+        assert!(output.status.success());
+        String::from_utf8_lossy(&output.stdout).to_string()
+    };
+
+    let table = list(&["list"]);
+    assert!(table.contains("NAME"));
+    assert!(table.contains("quiet_test"));
+
+    assert_eq!(list(&["list", "-q"]), "quiet_test\n");
+    assert_eq!(list(&["--quiet", "list"]), "quiet_test\n");
+
+    let rows = list(&["list", "--no-header"]);
+    assert!(!rows.contains("NAME"));
+    assert!(!rows.contains("---"));
+    assert_eq!(rows.lines().count(), 1);
+    assert!(rows.starts_with("quiet_test "));
+
+    // JSON ignores both flags
+    let json = list(&["--format", "json", "list", "-q", "--no-header"]);
+    let parsed: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert_eq!(parsed["processes"][0]["name"], "quiet_test");
+}
+
+#[test]
+fn test_pmr_list_quiet_empty_prints_nothing() {
+    let (mut cmd, _temp_dir) = create_test_command();
+    cmd.args(["list", "-q"]);
+
+    let output = cmd.output().expect("Failed to execute pmr");
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn test_pmr_porcelain_list_and_status() {
+    let (mut start_cmd, temp_dir) = create_test_command();
+    start_cmd.args(["start", "porcelain test", "sleep", "30"]);
+    assert!(start_cmd.output().expect("Failed to start process").status.success());
+
+    let run = |args: &[&str]| {
+        let (mut cmd, _) = create_test_command();
+        cmd.env("HOME", temp_dir.path()).current_dir(temp_dir.path());
+        cmd.args(args);
+        let output = cmd.output().expect("Failed to run pmr");
+        assert!(output.status.success());
+        String::from_utf8_lossy(&output.stdout).to_string()
+    };
+
+    let list = run(&["--format", "porcelain", "list"]);
+    let fields: Vec<&str> = list.strip_suffix('\n').unwrap().split('\t').collect();
+    assert_eq!(fields.len(), 6);
+    assert_eq!(&fields[..2], &["porcelain test", "running"]);
+    assert!(fields[2].parse::<u32>().is_ok());
+    assert!(fields[4].parse::<i64>().is_ok());
+
+    let status = run(&["--format", "porcelain", "status", "porcelain test"]);
+    let keys: Vec<&str> = status.lines().map(|line| line.split_once('\t').unwrap().0).collect();
+    assert_eq!(keys, pmr::porcelain::STATUS_FIELDS);
+
+    run(&["delete", "porcelain test"]);
+    assert_eq!(run(&["--format", "porcelain", "list"]), "");
+}
+
+#[test]
+fn test_pmr_inspect_prints_json_in_every_format() {
+    let (mut start_cmd, temp_dir) = create_test_command();
+    start_cmd.args(["start", "inspected", "-e", "API_KEY=abc", "sleep", "30"]);
+    assert!(start_cmd.output().expect("Failed to start process").status.success());
+
+    let run = |args: &[&str]| {
+        let (mut cmd, _) = create_test_command();
+        cmd.env("HOME", temp_dir.path()).current_dir(temp_dir.path());
+        cmd.args(args);
+        cmd.output().expect("Failed to run pmr")
+    };
+
+    let output = run(&["inspect", "inspected"]);
+    assert!(output.status.success());
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["record"]["env_vars"]["API_KEY"], "abc");
+    assert_eq!(report["liveness"]["status"], "Running");
+    assert_eq!(report["logs"]["log_path"], report["record"]["log_path"]);
+    let json: serde_json::Value = serde_json::from_slice(&run(&["--format", "json", "inspect", "inspected"]).stdout).unwrap();
+    assert_eq!(json["record"]["id"], report["record"]["id"]);
+
+    assert!(!run(&["inspect", "missing"]).status.success());
+    run(&["delete", "inspected"]);
+}
+
 #[test]
 fn test_pmr_invalid_command() {
     let (mut cmd, _temp_dir) = create_test_command();
-    cmd.args(&["invalid_command"]);
+    cmd.args(["invalid_command"]);
     
     let output = cmd.output().expect("Failed to execute pmr");
     assert!(!output.status.success());
@@ -283,7 +526,7 @@ fn test_pmr_invalid_command() {
 #[test]
 fn test_pmr_logs_with_lines_limit() {
     let (mut start_cmd, temp_dir) = create_test_command();
-    start_cmd.args(&[
+    start_cmd.args([
         "start", 
         "lines_test", 
         "sh", "-c", 
@@ -299,8 +542,8 @@ fn test_pmr_logs_with_lines_limit() {
     
     // Get logs with line limit
     let (mut logs_cmd, _) = create_test_command();
-    logs_cmd.env("HOME", temp_dir.path());
-    logs_cmd.args(&["logs", "lines_test", "-n", "3"]);
+    logs_cmd.env("HOME", temp_dir.path()).current_dir(temp_dir.path());
+    logs_cmd.args(["logs", "lines_test", "-n", "3"]);
     
     let output = logs_cmd.output().expect("Failed to get logs");
     assert!(output.status.success());
@@ -313,15 +556,15 @@ fn test_pmr_logs_with_lines_limit() {
     
     // Clean up
     let (mut cleanup_cmd, _) = create_test_command();
-    cleanup_cmd.env("HOME", temp_dir.path());
-    cleanup_cmd.args(&["delete", "lines_test"]);
+    cleanup_cmd.env("HOME", temp_dir.path()).current_dir(temp_dir.path());
+    cleanup_cmd.args(["delete", "lines_test"]);
     let _ = cleanup_cmd.output();
 }
 
 #[test]
 fn test_pmr_clear_command() {
     let (mut cmd, _temp_dir) = create_test_command();
-    cmd.args(&["clear"]);
+    cmd.args(["clear"]);
 
     let output = cmd.output().expect("Failed to execute pmr clear");
     assert!(output.status.success());
@@ -333,7 +576,7 @@ fn test_pmr_clear_command() {
 #[test]
 fn test_pmr_clear_all_command() {
     let (mut cmd, _temp_dir) = create_test_command();
-    cmd.args(&["clear", "--all"]);
+    cmd.args(["clear", "--all"]);
 
     let output = cmd.output().expect("Failed to execute pmr clear --all");
     assert!(output.status.success());
@@ -342,10 +585,46 @@ fn test_pmr_clear_all_command() {
     assert!(stdout.contains("No all processes to clear") || stdout.contains("Cleared"));
 }
 
+#[test]
+fn test_pmr_clear_sigterm_finishes_current_item() {
+    let (_, temp_dir) = create_test_command();
+
+    // These ignore SIGTERM, so stopping each one waits out the grace period
+    for name in ["term_a", "term_b", "term_c"] {
+        let (mut start_cmd, _) = create_test_command();
+        start_cmd.env("HOME", temp_dir.path()).current_dir(temp_dir.path());
+        start_cmd.args(["start", name, "sh", "-c", "trap '' TERM; sleep 30"]);
+        let output = start_cmd.output().expect("Failed to start process");
+        assert!(output.status.success());
+    }
+
+    let (mut clear_cmd, _) = create_test_command();
+    clear_cmd.env("HOME", temp_dir.path()).current_dir(temp_dir.path());
+    clear_cmd.args(["clear", "--all"]);
+    clear_cmd.stdout(std::process::Stdio::piped());
+    let child = clear_cmd.spawn().expect("Failed to run pmr clear");
+
+    std::thread::sleep(std::time::Duration::from_secs(1));
+    unsafe { libc::kill(child.id() as i32, libc::SIGTERM) };
+    let output = child.wait_with_output().expect("Failed to wait for pmr clear");
+
+    assert_eq!(output.status.code(), Some(130));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Cleared 1 all processes"), "{}", stdout);
+    assert!(stdout.contains("Skipped 2 processes (cancelled):"), "{}", stdout);
+
+    // The skipped processes are still there
+    let (mut list_cmd, _) = create_test_command();
+    list_cmd.env("HOME", temp_dir.path()).current_dir(temp_dir.path());
+    list_cmd.args(["--quiet", "list"]);
+    let output = list_cmd.output().expect("Failed to list processes");
+    assert_eq!(String::from_utf8_lossy(&output.stdout).lines().count(), 2);
+}
+
 #[test]
 fn test_pmr_clear_json_format() {
     let (mut cmd, _temp_dir) = create_test_command();
-    cmd.args(&["--format", "json", "clear"]);
+    cmd.args(["--format", "json", "clear"]);
 
     let output = cmd.output().expect("Failed to execute pmr clear with JSON format");
     assert!(output.status.success());
@@ -362,3 +641,814 @@ fn test_pmr_clear_json_format() {
     assert!(json.get("cleared_processes").is_some());
     assert!(json.get("failed_processes").is_some());
 }
+
+#[test]
+fn test_config_validate_reports_findings() {
+    let (mut cmd, temp_dir) = create_test_command();
+    let config_path = temp_dir.path().join("pmr.toml");
+    std::fs::write(&config_path, "[env]\n\"9LIVES\" = \"x\"\n").unwrap();
+
+    let output = cmd
+        .args(["--format", "json", "config", "validate", "--config"])
+        .arg(&config_path)
+        .output()
+        .expect("Failed to execute pmr");
+    assert_eq!(output.status.code(), Some(1));
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["valid"], false);
+    assert_eq!(report["issues"][0]["key"], "env.9LIVES");
+    assert_eq!(report["issues"][0]["severity"], "error");
+
+    // No config file at the default location is a valid configuration
+    let (mut cmd, _temp_dir) = create_test_command();
+    let output = cmd.args(["config", "validate"]).output().expect("Failed to execute pmr");
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("configuration is valid"));
+}
+
+/// Run `pmr batch <args>` with `input` on stdin, returning its exit code and result lines
+fn run_batch(home: &std::path::Path, args: &[&str], input: &str) -> (Option<i32>, Vec<pmr::batch::BatchResult>) {
+    use std::io::Write;
+    use std::process::Stdio;
+
+    let mut child = Command::new(get_pmr_binary())
+        .env("HOME", home)
+        .current_dir(home)
+        .arg("batch")
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("Failed to execute pmr");
+    child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    let results = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    (output.status.code(), results)
+}
+
+#[test]
+fn test_pmr_batch_mixed_commands() {
+    let temp_dir = TempDir::new().unwrap();
+    let input = "\
+# one process through its whole life
+start batch_web sh -c 'echo \"hello from batch\"; sleep 30'
+--format json status batch_web
+logs batch_web
+status no_such_process
+delete batch_web
+list -q
+";
+    let (code, results) = run_batch(temp_dir.path(), &[], input);
+    assert_eq!(code, Some(1));
+    assert_eq!(results.iter().map(|r| r.index).collect::<Vec<_>>(), [2, 3, 4, 5, 6, 7]);
+
+    let [start, status, logs, missing, delete, list] = &results[..] else { unreachable!() };
+    assert!(start.success, "{:?}", start);
+    assert_eq!(start.command, ["start", "batch_web", "sh", "-c", "echo \"hello from batch\"; sleep 30"]);
+    assert!(start.output.contains("started with PID"));
+
+    // Later commands see what earlier ones did
+    let status_json: serde_json::Value = serde_json::from_str(&status.output).unwrap();
+    assert_eq!(status_json["name"], "batch_web");
+    assert_eq!(status_json["status"], "Running");
+    assert!(logs.success);
+    assert!(logs.output.contains("hello from batch"));
+
+    // A failure is reported and does not stop the batch
+    assert!(!missing.success);
+    assert_eq!(missing.exit_code, 1);
+    assert!(missing.stderr.contains("Process 'no_such_process' not found"));
+
+    assert!(delete.success);
+    assert!(list.success);
+    assert_eq!(list.output, "");
+}
+
+#[test]
+fn test_pmr_batch_json_input_and_fail_fast() {
+    let temp_dir = TempDir::new().unwrap();
+    let input = r#"[["pmr", "stop", "ghost"], ["list"]]"#;
+
+    let (code, results) = run_batch(temp_dir.path(), &[], input);
+    assert_eq!(code, Some(1));
+    assert_eq!(results.len(), 2);
+    assert!(!results[0].success);
+    assert!(results[1].success);
+
+    let (code, results) = run_batch(temp_dir.path(), &[], &input.replace("[\"list\"]", "[\"list\", \"--bogus\"]"));
+    assert_eq!(code, Some(1));
+    assert_eq!(results[1].exit_code, 2);
+    assert!(results[1].stderr.contains("--bogus"));
+
+    let (code, results) = run_batch(temp_dir.path(), &["--fail-fast"], input);
+    assert_eq!(code, Some(1));
+    assert_eq!(results.len(), 1);
+}
+
+#[test]
+fn test_pmr_batch_rejects_commands_that_do_not_finish() {
+    let temp_dir = TempDir::new().unwrap();
+    let (code, results) = run_batch(temp_dir.path(), &[], "status x --watch 1
+batch
+list 'x
+list
+");
+    assert_eq!(code, Some(1));
+    assert_eq!(results.iter().map(|r| r.exit_code).collect::<Vec<_>>(), [2, 2, 2, 0]);
+    assert!(results[0].stderr.contains("does not finish"));
+    assert!(results[1].stderr.contains("cannot be nested"));
+    assert_eq!(results[2].stderr, "Cannot parse line 3: unterminated single quote");
+
+    let (code, results) = run_batch(temp_dir.path(), &[], "list
+");
+    assert_eq!(code, Some(0));
+    assert_eq!(results[0].output, "No processes found.");
+}
+
+#[test]
+fn test_pmr_home_overrides_all_pmr_paths() {
+    let (mut cmd, temp_dir) = create_test_command();
+    let pmr_home = temp_dir.path().join("custom-pmr");
+    std::fs::create_dir_all(&pmr_home).unwrap();
+    std::fs::write(pmr_home.join("config.toml"), "max_processes = 7\n").unwrap();
+
+    let output = cmd
+        .env("PMR_HOME", &pmr_home)
+        .args(["--format", "json", "stats"])
+        .output()
+        .expect("Failed to execute pmr");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let stats: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(stats["max_processes"], 7);
+    assert_eq!(stats["paths"]["database"], pmr_home.join("processes.db").to_str().unwrap());
+    assert_eq!(stats["paths"]["config_file"], pmr_home.join("config.toml").to_str().unwrap());
+    assert!(pmr_home.join("processes.db").exists());
+    // Nothing is created under HOME
+    assert!(!temp_dir.path().join(".pmr").exists());
+
+    let (mut cmd, _) = create_test_command();
+    let output = cmd
+        .env("HOME", temp_dir.path())
+        .current_dir(temp_dir.path())
+        .env("PMR_HOME", &pmr_home)
+        .args(["config", "show"])
+        .output()
+        .expect("Failed to execute pmr");
+    let text = String::from_utf8_lossy(&output.stdout);
+    assert!(text.contains(&format!("Database: {}", pmr_home.join("processes.db").display())), "{}", text);
+    assert!(text.contains(&format!("Config file: {}", pmr_home.join("config.toml").display())), "{}", text);
+}
+
+#[test]
+fn test_pmr_unwritable_database_directory_names_the_path() {
+    let (mut cmd, temp_dir) = create_test_command();
+    let plain_file = temp_dir.path().join("not-a-dir");
+    std::fs::write(&plain_file, "").unwrap();
+    let pmr_home = plain_file.join("pmr");
+
+    let output = cmd.env("PMR_HOME", &pmr_home).arg("list").output().expect("Failed to execute pmr");
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains(&format!("cannot create the database {}", pmr_home.join("processes.db").display())),
+        "{}",
+        stderr
+    );
+    assert!(stderr.contains(&format!("{} is not a directory", plain_file.display())), "{}", stderr);
+    assert!(stderr.contains("set PMR_HOME"), "{}", stderr);
+
+    // The paths can still be shown, since that needs no database
+    let (mut cmd, _) = create_test_command();
+    let output = cmd.current_dir(temp_dir.path()).env("PMR_HOME", &pmr_home).args(["config", "show"]).output().expect("Failed to execute pmr");
+    assert!(output.status.success());
+}
+
+#[test]
+fn test_pmr_sizes_accept_units_and_show_humanized() {
+    let (_, temp_dir) = create_test_command();
+    let pmr_dir = temp_dir.path().join(".pmr");
+    std::fs::create_dir_all(&pmr_dir).unwrap();
+    std::fs::write(pmr_dir.join("config.toml"), "logs_quota_bytes = \"1GiB\"\n").unwrap();
+    let pmr = |args: &[&str]| {
+        let (mut cmd, _) = create_test_command();
+        cmd.env("HOME", temp_dir.path()).current_dir(temp_dir.path()).args(args).output().expect("Failed to execute pmr")
+    };
+
+    let stats = String::from_utf8_lossy(&pmr(&["stats"]).stdout).to_string();
+    assert!(stats.contains("; quota 1.0 GiB)"), "{}", stats);
+    let json: serde_json::Value = serde_json::from_slice(&pmr(&["--format", "json", "stats"]).stdout).unwrap();
+    assert_eq!(json["logs"]["quota_bytes"], 1u64 << 30);
+
+    let rejected = pmr(&["start", "--cgroup-memory-max", "5parsecs", "sized", "true"]);
+    assert!(!rejected.status.success());
+    let stderr = String::from_utf8_lossy(&rejected.stderr);
+    assert!(stderr.contains("Invalid size '5parsecs'"), "{}", stderr);
+}
+
+#[test]
+fn test_pmr_stats_oneline() {
+    let (_, temp_dir) = create_test_command();
+    let log_dir = temp_dir.path().join("logs");
+    let pmr = |args: &[&str]| {
+        let (mut cmd, _) = create_test_command();
+        cmd.env("HOME", temp_dir.path()).current_dir(temp_dir.path()).args(args).output().expect("Failed to execute pmr")
+    };
+    let oneline = |args: &[&str]| {
+        let output = pmr(&[&["stats", "--oneline"], args].concat());
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    // stdout is a pipe here, so ASCII unless asked otherwise
+    assert_eq!(oneline(&[]), "pmr: 0R 0S 0F\n");
+    assert!(pmr(&["start", "--log-dir", log_dir.to_str().unwrap(), "quick", "true"]).status.success());
+    std::thread::sleep(std::time::Duration::from_millis(500));
+    pmr(&["list"]);
+    assert_eq!(oneline(&[]), "pmr: 0R 1S 0F\n");
+    assert_eq!(oneline(&["--unicode"]), "pmr: 0▲ 1■ 0✖\n");
+    // --format does not change the summary
+    assert_eq!(String::from_utf8(pmr(&["--format", "json", "stats", "--oneline", "--ascii"]).stdout).unwrap(), "pmr: 0R 1S 0F\n");
+}
+
+#[test]
+fn test_pmr_long_arguments_are_limited_and_cut_off_unless_full() {
+    let (_, temp_dir) = create_test_command();
+    let log_dir = temp_dir.path().join("logs");
+    let pmr = |args: &[&str]| {
+        let (mut cmd, _) = create_test_command();
+        cmd.env("HOME", temp_dir.path()).current_dir(temp_dir.path()).args(args).output().expect("Failed to execute pmr")
+    };
+
+    let huge = "x".repeat(70_000);
+    let refused = pmr(&["start", "--log-dir", log_dir.to_str().unwrap(), "huge", "echo", &huge]);
+    assert!(!refused.status.success());
+    let stderr = String::from_utf8_lossy(&refused.stderr);
+    assert!(stderr.contains("over the 64.0 KiB limit (start_limits.max_args_bytes)"), "{}", stderr);
+    assert!(stderr.contains("put large data in a file"), "{}", stderr);
+
+    let script = format!("sleep 30 # {}", "y".repeat(1000));
+    assert!(pmr(&["start", "--log-dir", log_dir.to_str().unwrap(), "long", "sh", "-c", &script]).status.success());
+
+    let list = String::from_utf8_lossy(&pmr(&["list"]).stdout).to_string();
+    assert!(list.contains("… (1011 B in full)") && !list.contains(&script), "{}", list);
+    assert!(String::from_utf8_lossy(&pmr(&["list", "--full"]).stdout).contains(&script));
+    let status = String::from_utf8_lossy(&pmr(&["status", "long"]).stdout).to_string();
+    assert!(status.contains("Truncated: args[1] (use --full to show everything)"), "{}", status);
+    let status = String::from_utf8_lossy(&pmr(&["status", "long", "--full"]).stdout).to_string();
+    assert!(status.contains(&script) && !status.contains("Truncated:"), "{}", status);
+
+    let json: serde_json::Value = serde_json::from_slice(&pmr(&["--format", "json", "list"]).stdout).unwrap();
+    assert_eq!(json["processes"][0]["truncated_fields"], serde_json::json!(["args[1]"]));
+    let json: serde_json::Value = serde_json::from_slice(&pmr(&["--format", "json", "list", "--full"]).stdout).unwrap();
+    assert_eq!(json["processes"][0]["args"][1], script.as_str());
+    assert!(json["processes"][0].get("truncated_fields").is_none());
+
+    pmr(&["delete", "long"]);
+}
+
+#[test]
+fn test_pmr_logs_mention_invalid_utf8_and_say_why_they_are_unreadable() {
+    let (_, temp_dir) = create_test_command();
+    let log_dir = temp_dir.path().join("logs");
+    let pmr = |args: &[&str]| {
+        let (mut cmd, _) = create_test_command();
+        cmd.env("HOME", temp_dir.path()).current_dir(temp_dir.path()).args(args).output().expect("Failed to execute pmr")
+    };
+
+    assert!(pmr(&["start", "--log-dir", log_dir.to_str().unwrap(), "binary", "printf", "ok\\n\\377\\n"]).status.success());
+    std::thread::sleep(std::time::Duration::from_millis(300));
+
+    let logs = pmr(&["logs", "binary"]);
+    assert!(logs.status.success());
+    assert!(String::from_utf8_lossy(&logs.stdout).starts_with("ok\n\u{FFFD}\n"));
+    assert!(String::from_utf8_lossy(&logs.stderr).contains("bytes that are not valid UTF-8"));
+    let json: serde_json::Value = serde_json::from_slice(&pmr(&["--format", "json", "logs", "binary"]).stdout).unwrap();
+    assert_eq!(json["contains_invalid_utf8"], true);
+    let tail = pmr(&["logs", "binary", "-n", "1"]);
+    assert!(String::from_utf8_lossy(&tail.stdout).contains('\u{FFFD}'));
+    let json: serde_json::Value = serde_json::from_slice(&pmr(&["--format", "json", "logs", "binary", "-n", "0"]).stdout).unwrap();
+    assert_eq!(json["contains_invalid_utf8"], false);
+
+    std::fs::remove_file(log_dir.join("binary.log")).unwrap();
+    let missing = pmr(&["logs", "binary"]);
+    assert_eq!(missing.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&missing.stderr);
+    assert!(stderr.contains("binary.log' does not exist; it was removed or the process never started"), "{}", stderr);
+
+    pmr(&["delete", "binary"]);
+}
+
+#[test]
+fn test_pmr_destructive_commands_ask_on_a_terminal_unless_told_yes() {
+    let (_, temp_dir) = create_test_command();
+    let log_dir = temp_dir.path().join("logs");
+    let command = |args: &[&str]| {
+        let (mut cmd, _) = create_test_command();
+        cmd.env("HOME", temp_dir.path()).current_dir(temp_dir.path()).env_remove("PMR_ASSUME_YES").args(args);
+        cmd
+    };
+    let start = |name: &str| assert!(command(&["start", "--log-dir", log_dir.to_str().unwrap(), name, "sleep", "30"]).output().unwrap().status.success());
+    let names = || String::from_utf8_lossy(&command(&["list", "--names-only"]).output().unwrap().stdout).to_string();
+
+    // A running process needs its name typed; y is not enough
+    start("web");
+    let refused = output_with_terminal_stdin(&mut command(&["delete", "web"]), "y\n");
+    assert_eq!(refused.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&refused.stderr);
+    assert!(stderr.contains("Process 'web' (PID "), "{}", stderr);
+    assert!(stderr.contains("Delete process 'web'? Type 'web' to confirm: "), "{}", stderr);
+    assert!(stderr.contains("Cancelled; process 'web' was not deleted"), "{}", stderr);
+    assert!(names().contains("web"));
+
+    let accepted = output_with_terminal_stdin(&mut command(&["delete", "web"]), "web\n");
+    assert!(accepted.status.success(), "{}", String::from_utf8_lossy(&accepted.stderr));
+    assert!(!names().contains("web"));
+
+    // --yes, PMR_ASSUME_YES=1 and a stdin that is not a terminal go ahead without asking;
+    // a stray "n" would decline if anything asked
+    start("api");
+    let yes = output_with_terminal_stdin(&mut command(&["--yes", "delete", "api"]), "n\n");
+    assert!(yes.status.success(), "{}", String::from_utf8_lossy(&yes.stderr));
+    assert!(!String::from_utf8_lossy(&yes.stderr).contains("confirm"));
+    start("api");
+    let env = output_with_terminal_stdin(command(&["delete", "api"]).env("PMR_ASSUME_YES", "1"), "n\n");
+    assert!(env.status.success(), "{}", String::from_utf8_lossy(&env.stderr));
+    start("api");
+    assert!(command(&["delete", "api"]).output().unwrap().status.success());
+
+    // clear --all lists what goes and takes no for an answer
+    start("one");
+    start("two");
+    let refused = output_with_terminal_stdin(&mut command(&["clear", "--all"]), "n\n");
+    assert_eq!(refused.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&refused.stderr);
+    assert!(stderr.contains("This removes 2 process(es)") && stderr.contains("  one (running)"), "{}", stderr);
+    assert!(stderr.contains("Clear these processes? [y/N] "), "{}", stderr);
+    assert!(names().contains("one") && names().contains("two"));
+    let accepted = output_with_terminal_stdin(&mut command(&["-y", "clear", "--all"]), "");
+    assert!(accepted.status.success(), "{}", String::from_utf8_lossy(&accepted.stderr));
+    assert!(names().trim().is_empty(), "{}", names());
+}
+
+#[test]
+fn test_pmr_flapping_process_needs_force_to_restart() {
+    let (_, temp_dir) = create_test_command();
+    let pmr_dir = temp_dir.path().join(".pmr");
+    std::fs::create_dir_all(&pmr_dir).unwrap();
+    std::fs::write(pmr_dir.join("config.toml"), "[flapping]\nfailures = 2\n").unwrap();
+    set_mode(&pmr_dir, 0o700);
+    set_mode(&pmr_dir.join("config.toml"), 0o600);
+    let log_dir = temp_dir.path().join("logs");
+    let pmr = |args: &[&str]| {
+        let (mut cmd, _) = create_test_command();
+        cmd.env("HOME", temp_dir.path()).current_dir(temp_dir.path()).args(args).output().expect("Failed to execute pmr")
+    };
+
+    pmr(&["start", "--log-dir", log_dir.to_str().unwrap(), "crashy", "sh", "-c", "exit 3"]);
+    let doctor = pmr(&["doctor"]);
+    assert_eq!(doctor.status.code(), Some(0));
+    assert_eq!(String::from_utf8_lossy(&doctor.stdout).trim(), "No problems found.");
+
+    // The second failure within ten minutes makes it flapping
+    pmr(&["restart", "crashy"]);
+    let list = String::from_utf8_lossy(&pmr(&["list"]).stdout).to_string();
+    assert!(list.lines().any(|line| line.starts_with("crashy") && line.ends_with(" FLAPPING")), "{}", list);
+    let status = pmr(&["--format", "json", "status", "crashy"]);
+    let status: serde_json::Value = serde_json::from_slice(&status.stdout).unwrap();
+    assert_eq!(status["flapping"], true);
+
+    let doctor = pmr(&["doctor"]);
+    assert_eq!(doctor.status.code(), Some(1));
+    let report = String::from_utf8_lossy(&doctor.stdout);
+    assert!(report.contains("crashy (failed): 2 failures in the last 10m"), "{}", report);
+
+    let refused = pmr(&["restart", "crashy"]);
+    assert_eq!(refused.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&refused.stderr);
+    assert!(stderr.contains("is flapping") || stderr.contains("Flapping"), "{}", stderr);
+    let forced = pmr(&["restart", "--force", "crashy"]);
+    assert!(!String::from_utf8_lossy(&forced.stderr).contains("Flapping"));
+}
+
+#[test]
+fn test_pmr_job_runs_once_until_rerun() {
+    let (_, temp_dir) = create_test_command();
+    let log_dir = temp_dir.path().join("logs");
+    let pmr = |args: &[&str]| {
+        let (mut cmd, _) = create_test_command();
+        cmd.env("HOME", temp_dir.path()).current_dir(temp_dir.path()).args(args).output().expect("Failed to execute pmr")
+    };
+    let start = |extra: &[&str]| {
+        let mut args = vec!["start", "--kind", "job", "--log-dir", log_dir.to_str().unwrap()];
+        args.extend_from_slice(extra);
+        args.extend_from_slice(&["backup", "echo", "done"]);
+        pmr(&args)
+    };
+
+    assert_eq!(start(&[]).status.code(), Some(0));
+    let again = start(&[]);
+    assert_eq!(again.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&again.stderr).contains("JobCompleted"));
+    assert_eq!(start(&["--rerun"]).status.code(), Some(0));
+
+    let jobs = pmr(&["--format", "json", "list", "--jobs"]);
+    let jobs: serde_json::Value = serde_json::from_slice(&jobs.stdout).unwrap();
+    assert_eq!(jobs["jobs"][0]["name"], "backup");
+    assert_eq!(jobs["jobs"][0]["runs"], 2);
+    assert_eq!(jobs["jobs"][0]["result"], "succeeded");
+    assert!(log_dir.join("backup.1.log").is_file());
+
+    // Recently succeeded jobs survive the default clear
+    pmr(&["clear"]);
+    assert!(String::from_utf8_lossy(&pmr(&["list", "--jobs", "--quiet"]).stdout).contains("backup"));
+}
+
+#[test]
+fn test_pmr_stop_uses_stop_signal_from_start() {
+    let (_, temp_dir) = create_test_command();
+    let log_dir = temp_dir.path().join("logs");
+    let pmr = |args: &[&str]| {
+        let (mut cmd, _) = create_test_command();
+        cmd.env("HOME", temp_dir.path()).current_dir(temp_dir.path()).args(args).output().expect("Failed to execute pmr")
+    };
+
+    let refused = pmr(&["start", "--stop-signal", "BOGUS", "--log-dir", log_dir.to_str().unwrap(), "quitter", "sleep", "30"]);
+    assert_eq!(refused.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&refused.stderr).contains("Unknown signal"));
+
+    let script = "trap 'exit 0' QUIT; trap '' TERM; while :; do sleep 0.1; done";
+    let started = pmr(&["start", "--stop-signal", "QUIT", "--log-dir", log_dir.to_str().unwrap(), "quitter", "sh", "-c", script]);
+    assert_eq!(started.status.code(), Some(0), "{}", String::from_utf8_lossy(&started.stderr));
+    let status = String::from_utf8_lossy(&pmr(&["status", "quitter"]).stdout).to_string();
+    assert!(status.contains("Stop Signal: SIGQUIT"), "{}", status);
+
+    let stopped = pmr(&["stop", "quitter"]);
+    assert_eq!(stopped.status.code(), Some(0), "{}", String::from_utf8_lossy(&stopped.stderr));
+    pmr(&["delete", "quitter"]);
+}
+
+#[test]
+fn test_pmr_log_dir_mode_workdir_follows_update() {
+    let (_, temp_dir) = create_test_command();
+    let pmr = |args: &[&str]| {
+        let (mut cmd, _) = create_test_command();
+        cmd.env("HOME", temp_dir.path()).current_dir(temp_dir.path()).args(args).output().expect("Failed to execute pmr")
+    };
+    let first = temp_dir.path().join("first");
+    let second = temp_dir.path().join("second");
+    std::fs::create_dir(&first).unwrap();
+    std::fs::create_dir(&second).unwrap();
+
+    let refused = pmr(&["start", "--log-dir-mode", "cwd", "project", "sleep", "30"]);
+    assert_eq!(refused.status.code(), Some(2));
+
+    let started = pmr(&["start", "--log-dir-mode", "workdir", "-w", first.to_str().unwrap(), "project", "sleep", "30"]);
+    assert_eq!(started.status.code(), Some(0), "{}", String::from_utf8_lossy(&started.stderr));
+    let status = String::from_utf8_lossy(&pmr(&["status", "project"]).stdout).to_string();
+    let log_line = format!("Log File: {}/logs/project.log (workdir mode", first.display());
+    assert!(status.contains(&log_line), "{}", status);
+
+    let updated = pmr(&["update", "project", "--workdir", second.to_str().unwrap()]);
+    assert_eq!(updated.status.code(), Some(0), "{}", String::from_utf8_lossy(&updated.stderr));
+    pmr(&["restart", "project"]);
+    let status = String::from_utf8_lossy(&pmr(&["status", "project"]).stdout).to_string();
+    let log_line = format!("Log File: {}/logs/project.log (workdir mode", second.display());
+    assert!(status.contains(&log_line), "{}", status);
+
+    pmr(&["delete", "--hard", "project"]);
+    assert!(!second.join("logs").join("project.log").exists());
+}
+
+#[test]
+fn test_pmr_logs_rotated_pages_archives_and_lists_json() {
+    let (_, temp_dir) = create_test_command();
+    let log_dir = temp_dir.path().join("logs");
+    let pmr = |args: &[&str]| {
+        let (mut cmd, _) = create_test_command();
+        cmd.env("HOME", temp_dir.path()).current_dir(temp_dir.path()).args(args).output().expect("Failed to execute pmr")
+    };
+
+    pmr(&["start", "--log-dir", log_dir.to_str().unwrap(), "paged", "true"]);
+    std::fs::write(log_dir.join("paged.1.log"), "newer 1\nnewer 2\n").unwrap();
+    std::fs::write(log_dir.join("paged.2.log"), "older\n").unwrap();
+
+    let text = String::from_utf8_lossy(&pmr(&["logs", "paged", "--rotated"]).stdout).to_string();
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(lines.len(), 5, "{}", text);
+    assert!(lines[0].starts_with("=== ") && lines[0].contains("paged.1.log (16 B,"), "{}", text);
+    assert_eq!(&lines[1..3], ["newer 1", "newer 2"]);
+    assert!(lines[3].contains("paged.2.log (6 B,"), "{}", text);
+    assert_eq!(lines[4], "older");
+
+    let json = pmr(&["--format", "json", "logs", "paged", "--rotated"]);
+    let json: serde_json::Value = serde_json::from_slice(&json.stdout).unwrap();
+    assert_eq!(json["rotated_logs"][0]["index"], 1);
+    assert_eq!(json["rotated_logs"][1]["size_bytes"], 6);
+    assert!(json["rotated_logs"][0].get("content").is_none());
+}
+
+/// Run SQL statements against the database pmr keeps under `home`, as another release would
+fn write_database(home: &std::path::Path, statements: &[&str]) {
+    let database_url = format!("sqlite:{}?mode=rwc", home.join(".pmr").join("processes.db").display());
+    tokio::runtime::Runtime::new().unwrap().block_on(async {
+        let pool = sqlx::SqlitePool::connect(&database_url).await.unwrap();
+        for statement in statements {
+            sqlx::query(statement).execute(&pool).await.unwrap();
+        }
+        pool.close().await;
+    });
+}
+
+#[test]
+fn test_pmr_refuses_database_from_newer_release() {
+    let (_, temp_dir) = create_test_command();
+    let pmr = |args: &[&str]| {
+        let (mut cmd, _) = create_test_command();
+        cmd.env("HOME", temp_dir.path()).current_dir(temp_dir.path()).env_remove("PMR_AUTO_MIGRATE").args(args).output().expect("Failed to execute pmr")
+    };
+
+    assert_eq!(pmr(&["list"]).status.code(), Some(0));
+    let compat = pmr(&["db", "info", "--check-compat"]);
+    assert_eq!(compat.status.code(), Some(0));
+    assert!(String::from_utf8_lossy(&compat.stdout).contains("Compatible: yes"));
+
+    write_database(temp_dir.path(), &["UPDATE schema_version SET version = 99, min_binary_version = '9.1.0'"]);
+
+    let refused = pmr(&["list"]);
+    assert_eq!(refused.status.code(), Some(5));
+    let stderr = String::from_utf8_lossy(&refused.stderr);
+    assert!(stderr.contains("Database schema version 99 is newer than this pmr 0.3.0 supports"), "{}", stderr);
+    assert!(stderr.contains("install pmr 9.1.0 or later"), "{}", stderr);
+
+    let compat = pmr(&["--format", "json", "db", "info", "--check-compat"]);
+    assert_eq!(compat.status.code(), Some(1));
+    let info: serde_json::Value = serde_json::from_slice(&compat.stdout).unwrap();
+    assert_eq!(info["schema_version"], 99);
+    assert_eq!(info["min_binary_version"], "9.1.0");
+    assert_eq!(info["compatible"], false);
+    // Without --check-compat the report is informational
+    assert_eq!(pmr(&["db", "info"]).status.code(), Some(0));
+}
+
+#[test]
+fn test_pmr_destructive_migration_needs_migrate_flag() {
+    let (_, temp_dir) = create_test_command();
+    let pmr = |args: &[&str], auto_migrate: bool| {
+        let (mut cmd, _) = create_test_command();
+        cmd.env("HOME", temp_dir.path()).current_dir(temp_dir.path()).env_remove("PMR_AUTO_MIGRATE");
+        if auto_migrate {
+            cmd.env("PMR_AUTO_MIGRATE", "1");
+        }
+        cmd.args(args).output().expect("Failed to execute pmr")
+    };
+    let legacy_schema = "CREATE TABLE processes (id TEXT PRIMARY KEY, name TEXT UNIQUE NOT NULL, command TEXT NOT NULL, \
+        args TEXT NOT NULL, env_vars TEXT NOT NULL, working_dir TEXT NOT NULL, pid INTEGER, \
+        status TEXT NOT NULL, created_at TEXT NOT NULL, updated_at TEXT NOT NULL, log_path TEXT NOT NULL)";
+    std::fs::create_dir_all(temp_dir.path().join(".pmr")).unwrap();
+    write_database(temp_dir.path(), &[legacy_schema]);
+
+    let info = String::from_utf8_lossy(&pmr(&["db", "info"], false).stdout).to_string();
+    assert!(info.contains("Schema version: 0 (none recorded)"), "{}", info);
+    assert!(info.contains("needs --migrate"), "{}", info);
+
+    // Without a terminal to ask on, nothing is migrated
+    let refused = pmr(&["list"], false);
+    assert_eq!(refused.status.code(), Some(6));
+    let stderr = String::from_utf8_lossy(&refused.stderr);
+    assert!(stderr.contains("Database schema version 0 must be migrated to version 1"), "{}", stderr);
+    assert!(stderr.contains("rerun with --migrate or set PMR_AUTO_MIGRATE=1"), "{}", stderr);
+    assert_eq!(pmr(&["db", "info", "--check-compat"], false).status.code(), Some(0));
+
+    let migrated = pmr(&["list"], true);
+    assert_eq!(migrated.status.code(), Some(0), "{}", String::from_utf8_lossy(&migrated.stderr));
+    let info = String::from_utf8_lossy(&pmr(&["db", "info"], false).stdout).to_string();
+    assert!(info.contains("Schema version: 1\n"), "{}", info);
+    assert!(info.contains("Pending migrations: none"), "{}", info);
+}
+
+#[test]
+fn test_pmr_migrate_flag_allows_destructive_migration() {
+    let (_, temp_dir) = create_test_command();
+    std::fs::create_dir_all(temp_dir.path().join(".pmr")).unwrap();
+    write_database(temp_dir.path(), &["CREATE TABLE processes (id TEXT PRIMARY KEY, name TEXT NOT NULL, command TEXT NOT NULL, \
+        args TEXT NOT NULL, env_vars TEXT NOT NULL, working_dir TEXT NOT NULL, pid INTEGER, status TEXT NOT NULL, \
+        created_at TEXT NOT NULL, updated_at TEXT NOT NULL, stdout_path TEXT NOT NULL, stderr_path TEXT NOT NULL)"]);
+
+    let (mut cmd, _) = create_test_command();
+    let output = cmd.env("HOME", temp_dir.path()).current_dir(temp_dir.path()).env_remove("PMR_AUTO_MIGRATE").args(["list", "--migrate"]).output().unwrap();
+    assert_eq!(output.status.code(), Some(0), "{}", String::from_utf8_lossy(&output.stderr));
+}
+
+#[test]
+fn test_pmr_refresh_modes() {
+    let (_, temp_dir) = create_test_command();
+    let log_dir = temp_dir.path().join("logs");
+    let pmr = |args: &[&str]| {
+        let (mut cmd, _) = create_test_command();
+        cmd.env("HOME", temp_dir.path()).current_dir(temp_dir.path()).args(args).output().expect("Failed to execute pmr")
+    };
+
+    pmr(&["start", "--log-dir", log_dir.to_str().unwrap(), "fresh", "sleep", "30"]);
+    for mode in ["true", "false", "stale"] {
+        let listed = pmr(&["list", "--refresh", mode]);
+        assert_eq!(listed.status.code(), Some(0), "{}", String::from_utf8_lossy(&listed.stderr));
+        assert!(String::from_utf8_lossy(&listed.stdout).contains("fresh"));
+    }
+    assert_eq!(pmr(&["list", "--refresh", "sometimes"]).status.code(), Some(2));
+
+    let status = String::from_utf8_lossy(&pmr(&["status", "fresh", "--refresh", "false"]).stdout).to_string();
+    assert!(status.contains("Status Verified: "), "{}", status);
+    let json = pmr(&["--format", "json", "status", "fresh", "--refresh", "stale"]);
+    let parsed: serde_json::Value = serde_json::from_slice(&json.stdout).unwrap();
+    assert!(parsed["status_verified_at"].is_string(), "{}", parsed);
+
+    pmr(&["stop", "fresh"]);
+    pmr(&["delete", "fresh"]);
+}
+
+#[test]
+fn test_pmr_salvages_a_damaged_database() {
+    let (_, temp_dir) = create_test_command();
+    let pmr = |args: &[&str]| {
+        let (mut cmd, _) = create_test_command();
+        cmd.env("HOME", temp_dir.path()).current_dir(temp_dir.path()).args(args).output().expect("Failed to execute pmr")
+    };
+    assert_eq!(pmr(&["list"]).status.code(), Some(0));
+    let database = temp_dir.path().join(".pmr").join("processes.db");
+    let mut bytes = std::fs::read(&database).unwrap();
+    bytes[..100].fill(0xa5);
+    std::fs::write(&database, &bytes).unwrap();
+    let copy = temp_dir.path().join("copy.db");
+    std::fs::write(&copy, &bytes).unwrap();
+
+    // The command still runs, on a rebuilt database, after saying loudly what happened
+    let listed = pmr(&["list"]);
+    assert_eq!(listed.status.code(), Some(0), "{}", String::from_utf8_lossy(&listed.stderr));
+    let stderr = String::from_utf8_lossy(&listed.stderr);
+    assert!(stderr.contains("DATABASE DAMAGED"), "{}", stderr);
+    assert!(stderr.contains("Damaged file kept at:"), "{}", stderr);
+    let backups: Vec<_> = std::fs::read_dir(temp_dir.path().join(".pmr"))
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+        // A WAL left by the previous run is moved aside next to it
+        .filter(|name| name.starts_with("processes.db.corrupt-") && !name.ends_with("-wal") && !name.ends_with("-shm"))
+        .collect();
+    assert_eq!(backups.len(), 1, "{:?}", backups);
+    assert!(!String::from_utf8_lossy(&pmr(&["list"]).stderr).contains("DATABASE DAMAGED"));
+
+    let salvaged = pmr(&["--format", "json", "db", "salvage", copy.to_str().unwrap()]);
+    assert_eq!(salvaged.status.code(), Some(0), "{}", String::from_utf8_lossy(&salvaged.stderr));
+    let report: serde_json::Value = serde_json::from_slice(&salvaged.stdout).unwrap();
+    assert_eq!(report["database"], copy.to_str().unwrap());
+    assert_eq!(report["recovered"], 0);
+    assert_eq!(report["unreadable_tables"], true);
+    assert!(std::path::Path::new(report["backup"].as_str().unwrap()).exists());
+}
+
+#[test]
+fn test_pmr_list_names_only() {
+    let (_, temp_dir) = create_test_command();
+    let pmr = |args: &[&str]| {
+        let (mut cmd, _) = create_test_command();
+        cmd.env("HOME", temp_dir.path()).current_dir(temp_dir.path()).args(args).output().expect("Failed to execute pmr")
+    };
+    for name in ["web-b", "api", "web-a"] {
+        assert!(pmr(&["start", name, "sleep", "30"]).status.success());
+    }
+
+    let all = pmr(&["list", "--names-only"]);
+    assert!(all.status.success());
+    assert_eq!(String::from_utf8_lossy(&all.stdout), "api\nweb-a\nweb-b\n");
+    let web = pmr(&["list", "--names-only", "--prefix", "web-"]);
+    assert_eq!(String::from_utf8_lossy(&web.stdout), "web-a\nweb-b\n");
+    let json = pmr(&["--format", "json", "list", "--names-only", "--prefix", "a"]);
+    let names: Vec<String> = serde_json::from_slice(&json.stdout).unwrap();
+    assert_eq!(names, vec!["api".to_string()]);
+    let none = pmr(&["list", "--names-only", "--prefix", "zzz"]);
+    assert!(none.status.success());
+    assert!(none.stdout.is_empty());
+    // A prefix only makes sense for names
+    assert_eq!(pmr(&["list", "--prefix", "web"]).status.code(), Some(2));
+
+    pmr(&["clear", "--all"]);
+}
+
+#[test]
+fn test_pmr_history_lists_job_runs_with_durations() {
+    let (_, temp_dir) = create_test_command();
+    let run = |args: &[&str]| {
+        let (mut cmd, _) = create_test_command();
+        cmd.env("HOME", temp_dir.path()).current_dir(temp_dir.path());
+        cmd.args(args);
+        let output = cmd.output().expect("Failed to run pmr");
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        String::from_utf8_lossy(&output.stdout).to_string()
+    };
+
+    // Each run ends within the start window, so the start that spawned it sees it exit
+    run(&["start", "--kind", "job", "backup", "sh", "-c", "sleep 0.1"]);
+    for _ in 0..2 {
+        run(&["start", "--kind", "job", "--rerun", "backup", "sh", "-c", "sleep 0.1"]);
+    }
+    let status = run(&["status", "backup"]);
+    assert!(status.contains("Last Run: "), "{}", status);
+    assert!(status.contains("over the last 3 run(s)"), "{}", status);
+
+    let history: serde_json::Value = serde_json::from_str(&run(&["--format", "json", "history", "backup", "--runs"])).unwrap();
+    let runs = history["runs"].as_array().unwrap();
+    assert_eq!(runs.len(), 3, "{}", history);
+    for entry in runs {
+        let duration = entry["duration_ms"].as_u64().unwrap();
+        assert!((80..10_000).contains(&duration), "{}", entry);
+        assert_eq!(entry["status"], "Succeeded");
+    }
+    assert!(runs[0]["started_at"].as_str() < runs[1]["started_at"].as_str());
+    assert!(runs[1]["started_at"].as_str() < runs[2]["started_at"].as_str());
+
+    let text = run(&["history", "backup"]);
+    assert!(text.starts_with("STARTED AT"), "{}", text);
+    assert!(text.contains("Average: "), "{}", text);
+}
+
+#[test]
+fn test_pmr_generate_systemd_units() {
+    let (_, temp_dir) = create_test_command();
+    let pmr = |args: &[&str]| {
+        let (mut cmd, _) = create_test_command();
+        cmd.env("HOME", temp_dir.path()).current_dir(temp_dir.path()).args(args);
+        cmd.output().expect("Failed to run pmr")
+    };
+
+    let output = pmr(&["generate", "systemd", "--serve", "--port", "9191", "--user"]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let unit = String::from_utf8_lossy(&output.stdout);
+    assert!(unit.contains(&format!("ExecStart={} serve --port 9191\n", get_pmr_binary().display())), "{}", unit);
+    assert!(unit.contains(&format!("Environment=\"PMR_HOME={}\"", temp_dir.path().join(".pmr").display())), "{}", unit);
+    assert!(!unit.contains("User=") && unit.ends_with("WantedBy=default.target\n"), "{}", unit);
+
+    assert!(!pmr(&["generate", "systemd", "--process", "missing"]).status.success());
+    assert!(!pmr(&["generate", "systemd"]).status.success());
+
+    assert!(pmr(&["start", "sleeper", "sleep", "30"]).status.success());
+    let path = temp_dir.path().join("pmr-sleeper.service");
+    let output = pmr(&["generate", "systemd", "--process", "sleeper", "--output", path.to_str().unwrap()]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let unit = std::fs::read_to_string(&path).unwrap();
+    assert!(unit.contains(" start --idempotent --workdir "), "{}", unit);
+    assert!(unit.contains(" sleeper sleep 30\n") && unit.contains(" stop sleeper\n"), "{}", unit);
+    assert!(pmr(&["delete", "--yes", "sleeper"]).status.success());
+}
+
+#[test]
+fn test_pmr_points_at_the_profile_holding_a_missing_process() {
+    let (_, temp_dir) = create_test_command();
+    let work_home = temp_dir.path().join(".pmr").join("profiles").join("work");
+    let run = |profile: Option<&std::path::Path>, args: &[&str]| {
+        let (mut cmd, _) = create_test_command();
+        cmd.env("HOME", temp_dir.path()).current_dir(temp_dir.path());
+        cmd.env_remove("PMR_HOME");
+        if let Some(home) = profile {
+            cmd.env("PMR_HOME", home);
+        }
+        cmd.args(args);
+        cmd.output().expect("Failed to run pmr")
+    };
+
+    assert!(run(Some(&work_home), &["start", "--kind", "job", "web", "true"]).status.success());
+    assert!(run(None, &["start", "--kind", "job", "db", "true"]).status.success());
+
+    let default_db = temp_dir.path().join(".pmr").join("processes.db");
+    for command in ["status", "stop"] {
+        let output = run(None, &[command, "web"]);
+        assert!(!output.status.success());
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains(&format!("Error: Process 'web' not found in database {} (id ", default_db.display())), "{}", stderr);
+        assert!(stderr.contains(&format!("Hint: profile 'work' has a process 'web'; run with PMR_HOME={} to use it", work_home.display())), "{}", stderr);
+    }
+    // Only status and stop look into the other profiles
+    let stderr = String::from_utf8_lossy(&run(None, &["history", "web"]).stderr).to_string();
+    assert!(stderr.contains("not found in database"), "{}", stderr);
+    assert!(!stderr.contains("Hint:"), "{}", stderr);
+
+    let output = run(Some(&work_home), &["list", "--all-profiles"]);
+    let text = String::from_utf8_lossy(&output.stdout);
+    assert!(text.starts_with("PROFILE"), "{}", text);
+    assert!(text.lines().any(|line| line.starts_with("default") && line.contains(" db ")), "{}", text);
+    assert!(text.lines().any(|line| line.starts_with("work") && line.contains(" web ")), "{}", text);
+    assert!(text.contains(&format!("work: {}", work_home.join("processes.db").display())), "{}", text);
+    assert!(text.contains(", 1 process(es), current"), "{}", text);
+
+    let output = run(None, &["--format", "json", "list", "--all-profiles"]);
+    let listing: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let profiles = listing["profiles"].as_array().unwrap();
+    assert_eq!(profiles.len(), 2, "{}", listing);
+    assert_eq!(profiles[0]["profile"], "default");
+    assert_eq!(profiles[0]["current"], true);
+    assert_eq!(profiles[1]["profile"], "work");
+    assert_eq!(profiles[1]["current"], false);
+    assert_eq!(profiles[1]["processes"][0]["name"], "web");
+    assert_ne!(profiles[0]["instance_id"], profiles[1]["instance_id"]);
+}
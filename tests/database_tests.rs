@@ -1,10 +1,15 @@
+mod support;
+
 use pmr::{
-    database::{Database, ProcessRecord, ProcessStatus},
+    database::{Database, ProcessRecord, ProcessStatus, HTTP_SERVER_PROCESS_NAME, MIN_BINARY_VERSION, SCHEMA_VERSION},
+    name::ProcessName,
+    Error,
 };
 use chrono::Utc;
 use std::collections::HashMap;
 use tempfile::TempDir;
 use uuid::Uuid;
+use support::named;
 
 /// Helper function to create a test database
 async fn create_test_database() -> (Database, TempDir) {
@@ -23,7 +28,7 @@ fn create_test_process_record(name: &str) -> ProcessRecord {
     
     ProcessRecord {
         id: Uuid::new_v4().to_string(),
-        name: name.to_string(),
+        name: name.parse().unwrap(),
         command: "echo".to_string(),
         args: vec!["hello".to_string()],
         env_vars,
@@ -33,6 +38,40 @@ fn create_test_process_record(name: &str) -> ProcessRecord {
         created_at: Utc::now(),
         updated_at: Utc::now(),
         log_path: "/tmp/test.log".to_string(),
+        log_dir: None,
+        cgroup_memory_max: None,
+        cgroup_cpu_max: None,
+        cgroup: None,
+        deleted_at: None,
+        binary_path: None,
+        binary_sha256: None,
+        canonical_workdir: None,
+        exit_code: None,
+        exit_reason: None,
+        cpu_affinity: None,
+        umask: None,
+        description: None,
+        start_snapshot: None,
+        default_env_keys: Vec::new(),
+        drift: None,
+        notes: Vec::new(),
+        recent_runs: Vec::new(),
+        flapping: false,
+        degraded: false,
+        inferred: false,
+        truncated_fields: Vec::new(),
+        kind: pmr::database::ProcessKind::Service,
+        runs: 1,
+        stop_signal: libc::SIGTERM,
+        log_dir_mode: pmr::database::LogDirMode::Default,
+        status_verified_at: None,
+        internal: false,
+        ephemeral_keep_secs: None,
+        delete_after: None,
+        last_output: Vec::new(),
+        last_output_lossy: false,
+        watch: None,
+        rotate_on_start: None,
     }
 }
 
@@ -42,6 +81,23 @@ async fn test_database_creation() {
     // If we get here without panicking, the database was created successfully
 }
 
+#[tokio::test]
+async fn test_instance_id_is_drawn_once_per_database() {
+    let (db, temp_dir) = create_test_database().await;
+    let instance_id = db.instance_id().await.unwrap().expect("drawn when the database is created");
+    assert!(Uuid::parse_str(&instance_id).is_ok(), "{}", instance_id);
+    db.close().await;
+
+    let path = temp_dir.path().join("test.db");
+    let reopened = Database::new(&format!("sqlite:{}?mode=rwc", path.display())).await.unwrap();
+    assert_eq!(reopened.instance_id().await.unwrap().as_deref(), Some(instance_id.as_str()));
+    let read_only = Database::new_read_only(&path).await.unwrap();
+    assert_eq!(read_only.instance_id().await.unwrap().as_deref(), Some(instance_id.as_str()));
+
+    let (other, _other_dir) = create_test_database().await;
+    assert_ne!(other.instance_id().await.unwrap().as_deref(), Some(instance_id.as_str()));
+}
+
 #[tokio::test]
 async fn test_insert_and_get_process() {
     let (db, _temp_dir) = create_test_database().await;
@@ -52,7 +108,7 @@ async fn test_insert_and_get_process() {
     db.insert_process(&process).await.unwrap();
     
     // Get process by name
-    let retrieved = db.get_process_by_name("test_process").await.unwrap();
+    let retrieved = db.get_process_by_name(&named("test_process")).await.unwrap();
     assert!(retrieved.is_some());
     
     let retrieved = retrieved.unwrap();
@@ -70,7 +126,7 @@ async fn test_insert_and_get_process() {
 async fn test_get_nonexistent_process() {
     let (db, _temp_dir) = create_test_database().await;
     
-    let result = db.get_process_by_name("nonexistent").await.unwrap();
+    let result = db.get_process_by_name(&named("nonexistent")).await.unwrap();
     assert!(result.is_none());
 }
 
@@ -96,7 +152,7 @@ async fn test_get_all_processes() {
     assert_eq!(processes.len(), 3);
     
     // Check that all processes are present
-    let names: Vec<String> = processes.iter().map(|p| p.name.clone()).collect();
+    let names: Vec<String> = processes.iter().map(|p| p.name.to_string()).collect();
     assert!(names.contains(&"process1".to_string()));
     assert!(names.contains(&"process2".to_string()));
     assert!(names.contains(&"process3".to_string()));
@@ -110,12 +166,12 @@ async fn test_update_process_status() {
     db.insert_process(&process).await.unwrap();
     
     // Update status
-    db.update_process_status("test_process", ProcessStatus::Stopped, Some(54321))
+    db.update_process_status(&named("test_process"), ProcessStatus::Stopped, Some(54321), Utc::now())
         .await
         .unwrap();
     
     // Verify update
-    let updated = db.get_process_by_name("test_process").await.unwrap().unwrap();
+    let updated = db.get_process_by_name(&named("test_process")).await.unwrap().unwrap();
     assert_eq!(updated.status, ProcessStatus::Stopped);
     assert_eq!(updated.pid, Some(54321));
     assert!(updated.updated_at > process.updated_at);
@@ -129,15 +185,15 @@ async fn test_delete_process() {
     db.insert_process(&process).await.unwrap();
     
     // Verify process exists
-    let retrieved = db.get_process_by_name("test_process").await.unwrap();
+    let retrieved = db.get_process_by_name(&named("test_process")).await.unwrap();
     assert!(retrieved.is_some());
     
     // Delete process
-    let deleted = db.delete_process("test_process").await.unwrap();
+    let deleted = db.delete_process(&named("test_process")).await.unwrap();
     assert!(deleted);
     
     // Verify process is gone
-    let retrieved = db.get_process_by_name("test_process").await.unwrap();
+    let retrieved = db.get_process_by_name(&named("test_process")).await.unwrap();
     assert!(retrieved.is_none());
 }
 
@@ -145,7 +201,7 @@ async fn test_delete_process() {
 async fn test_delete_nonexistent_process() {
     let (db, _temp_dir) = create_test_database().await;
     
-    let deleted = db.delete_process("nonexistent").await.unwrap();
+    let deleted = db.delete_process(&named("nonexistent")).await.unwrap();
     assert!(!deleted);
 }
 
@@ -168,7 +224,7 @@ async fn test_duplicate_process_name() {
 async fn test_process_status_serialization() {
     let (db, _temp_dir) = create_test_database().await;
     
-    let statuses = vec![
+    let statuses = [
         ProcessStatus::Running,
         ProcessStatus::Stopped,
         ProcessStatus::Failed,
@@ -195,7 +251,7 @@ async fn test_process_with_empty_args() {
     
     db.insert_process(&process).await.unwrap();
     
-    let retrieved = db.get_process_by_name("empty_args").await.unwrap().unwrap();
+    let retrieved = db.get_process_by_name(&named("empty_args")).await.unwrap().unwrap();
     assert_eq!(retrieved.args, vec![] as Vec<String>);
 }
 
@@ -208,7 +264,7 @@ async fn test_process_with_empty_env_vars() {
     
     db.insert_process(&process).await.unwrap();
     
-    let retrieved = db.get_process_by_name("empty_env").await.unwrap().unwrap();
+    let retrieved = db.get_process_by_name(&named("empty_env")).await.unwrap().unwrap();
     assert_eq!(retrieved.env_vars, HashMap::new());
 }
 
@@ -221,7 +277,7 @@ async fn test_process_with_no_pid() {
     
     db.insert_process(&process).await.unwrap();
     
-    let retrieved = db.get_process_by_name("no_pid").await.unwrap().unwrap();
+    let retrieved = db.get_process_by_name(&named("no_pid")).await.unwrap().unwrap();
     assert_eq!(retrieved.pid, None);
 }
 
@@ -253,3 +309,414 @@ async fn test_process_ordering() {
     assert_eq!(processes[1].name, "second");
     assert_eq!(processes[2].name, "first");
 }
+
+#[tokio::test]
+async fn test_soft_deleted_name_can_be_reused() {
+    let (db, _temp_dir) = create_test_database().await;
+
+    let original = create_test_process_record("reused");
+    db.insert_process(&original).await.unwrap();
+    assert!(db.soft_delete_process(&named("reused")).await.unwrap());
+
+    // Hidden from every lookup once soft-deleted
+    assert!(db.get_process_by_name(&named("reused")).await.unwrap().is_none());
+    assert!(db.get_all_processes().await.unwrap().is_empty());
+    assert!(db.get_processes_by_status(&[ProcessStatus::Running]).await.unwrap().is_empty());
+    assert!(!db.soft_delete_process(&named("reused")).await.unwrap());
+
+    // The name is free for a new live record
+    let replacement = create_test_process_record("reused");
+    db.insert_process(&replacement).await.unwrap();
+    assert_eq!(db.get_process_by_name(&named("reused")).await.unwrap().unwrap().id, replacement.id);
+
+    // But only one live record may hold it
+    assert!(db.insert_process(&create_test_process_record("reused")).await.is_err());
+
+    let deleted = db.get_deleted_process_by_name(&named("reused")).await.unwrap().unwrap();
+    assert_eq!(deleted.id, original.id);
+    assert!(deleted.deleted_at.is_some());
+}
+
+#[tokio::test]
+async fn test_restore_and_expired_deleted_processes() {
+    let (db, _temp_dir) = create_test_database().await;
+
+    let process = create_test_process_record("restorable");
+    db.insert_process(&process).await.unwrap();
+    db.soft_delete_process(&named("restorable")).await.unwrap();
+
+    let expired = db.get_deleted_processes_before(Utc::now()).await.unwrap();
+    assert_eq!(expired.len(), 1);
+    let not_expired = db
+        .get_deleted_processes_before(Utc::now() - chrono::Duration::hours(1))
+        .await
+        .unwrap();
+    assert!(not_expired.is_empty());
+
+    assert!(db.restore_process(&process.id).await.unwrap());
+    let restored = db.get_process_by_name(&named("restorable")).await.unwrap().unwrap();
+    assert!(restored.deleted_at.is_none());
+    assert!(db.get_deleted_process_by_name(&named("restorable")).await.unwrap().is_none());
+}
+
+/// Create a database with the schema used before soft delete, where names were globally
+/// unique, holding one stopped process named `legacy`
+async fn create_legacy_database(database_url: &str) {
+    let pool = sqlx::SqlitePool::connect(database_url).await.unwrap();
+    sqlx::query(
+        "CREATE TABLE processes (id TEXT PRIMARY KEY, name TEXT UNIQUE NOT NULL, command TEXT NOT NULL, \
+         args TEXT NOT NULL, env_vars TEXT NOT NULL, working_dir TEXT NOT NULL, pid INTEGER, \
+         status TEXT NOT NULL, created_at TEXT NOT NULL, updated_at TEXT NOT NULL, log_path TEXT NOT NULL)",
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+    let now = Utc::now().to_rfc3339();
+    sqlx::query("INSERT INTO processes VALUES ('legacy-id', 'legacy', 'echo', '[]', '{}', '/tmp', NULL, 'stopped', ?, ?, '/tmp/logs/legacy.log')")
+        .bind(&now)
+        .bind(&now)
+        .execute(&pool)
+        .await
+        .unwrap();
+    pool.close().await;
+}
+
+/// Overwrite the schema version row, as another pmr release would have written it
+async fn write_schema_version(database_url: &str, version: i64, min_binary_version: &str) {
+    let pool = sqlx::SqlitePool::connect(database_url).await.unwrap();
+    sqlx::query("UPDATE schema_version SET version = ?, min_binary_version = ? WHERE id = 1")
+        .bind(version)
+        .bind(min_binary_version)
+        .execute(&pool)
+        .await
+        .unwrap();
+    pool.close().await;
+}
+
+#[tokio::test]
+async fn test_migration_from_unique_name_schema() {
+    let temp_dir = TempDir::new().unwrap();
+    let database_url = format!("sqlite:{}?mode=rwc", temp_dir.path().join("legacy.db").display());
+    create_legacy_database(&database_url).await;
+
+    let db = Database::new(&database_url).await.unwrap();
+    let legacy = db.get_process_by_name(&named("legacy")).await.unwrap().unwrap();
+    assert_eq!(legacy.id, "legacy-id");
+    assert_eq!(legacy.log_dir.as_deref(), Some("/tmp/logs"));
+
+    db.soft_delete_process(&named("legacy")).await.unwrap();
+    db.insert_process(&create_test_process_record("legacy")).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_migration_marks_http_server_sentinel_internal() {
+    let temp_dir = TempDir::new().unwrap();
+    let database_url = format!("sqlite:{}?mode=rwc", temp_dir.path().join("sentinel.db").display());
+    let db = Database::new(&database_url).await.unwrap();
+    db.insert_process(&create_test_process_record(HTTP_SERVER_PROCESS_NAME)).await.unwrap();
+    db.insert_process(&create_test_process_record("web")).await.unwrap();
+    drop(db);
+
+    // As a release from before the column left it
+    let pool = sqlx::SqlitePool::connect(&database_url).await.unwrap();
+    sqlx::query("ALTER TABLE processes DROP COLUMN internal").execute(&pool).await.unwrap();
+    pool.close().await;
+
+    let db = Database::new(&database_url).await.unwrap();
+    assert!(db.get_process_by_name(&named(HTTP_SERVER_PROCESS_NAME)).await.unwrap().unwrap().internal);
+    assert!(!db.get_process_by_name(&named("web")).await.unwrap().unwrap().internal);
+    assert_eq!(db.count_processes().await.unwrap(), 1);
+    assert_eq!(db.count_processes_by_status().await.unwrap().total, 1);
+    drop(db);
+
+    // The name is matched only while adding the column, not on every open
+    let pool = sqlx::SqlitePool::connect(&database_url).await.unwrap();
+    sqlx::query("UPDATE processes SET internal = 0").execute(&pool).await.unwrap();
+    pool.close().await;
+    let db = Database::new(&database_url).await.unwrap();
+    assert!(!db.get_process_by_name(&named(HTTP_SERVER_PROCESS_NAME)).await.unwrap().unwrap().internal);
+}
+
+#[tokio::test]
+async fn test_process_record_name_round_trips() {
+    let record = create_test_process_record("web worker");
+    let json = serde_json::to_value(&record).unwrap();
+    assert_eq!(json["name"], "web worker");
+    let back: ProcessRecord = serde_json::from_value(json.clone()).unwrap();
+    assert_eq!(back.name, record.name);
+
+    let mut invalid = json;
+    invalid["name"] = "../web".into();
+    let err = serde_json::from_value::<ProcessRecord>(invalid).unwrap_err();
+    assert!(err.to_string().contains("Invalid process name '../web'"), "{}", err);
+
+    // A row stored before the naming rules is read back as it is
+    let (db, temp_dir) = create_test_database().await;
+    db.insert_process(&record).await.unwrap();
+    let pool = sqlx::SqlitePool::connect(&format!("sqlite:{}", temp_dir.path().join("test.db").display())).await.unwrap();
+    sqlx::query("UPDATE processes SET name = 'old/web'").execute(&pool).await.unwrap();
+    pool.close().await;
+    let stored = db.get_all_processes().await.unwrap();
+    assert_eq!(stored[0].name, "old/web");
+}
+
+#[tokio::test]
+async fn test_schema_version_is_recorded() {
+    let (_db, temp_dir) = create_test_database().await;
+    let info = Database::inspect(&temp_dir.path().join("test.db")).await.unwrap();
+    assert_eq!(info.schema_version, SCHEMA_VERSION);
+    assert_eq!(info.min_binary_version.as_deref(), Some(MIN_BINARY_VERSION));
+    assert!(info.is_compatible());
+    assert!(!info.migrations_pending());
+
+    // A database that does not exist yet needs nothing destructive
+    let missing = Database::inspect(&temp_dir.path().join("missing.db")).await.unwrap();
+    assert_eq!(missing.schema_version, 0);
+    assert!(missing.destructive_migrations.is_empty());
+    assert!(!temp_dir.path().join("missing.db").exists());
+}
+
+#[tokio::test]
+async fn test_newer_schema_is_refused() {
+    let (db, temp_dir) = create_test_database().await;
+    drop(db);
+    let path = temp_dir.path().join("test.db");
+    let database_url = format!("sqlite:{}?mode=rwc", path.display());
+    write_schema_version(&database_url, SCHEMA_VERSION + 1, "9.1.0").await;
+
+    match Database::new(&database_url).await {
+        Err(Error::SchemaTooNew(version, supported, min_binary_version)) => {
+            assert_eq!((version, supported), (SCHEMA_VERSION + 1, SCHEMA_VERSION));
+            assert_eq!(min_binary_version, "9.1.0");
+        }
+        other => panic!("expected SchemaTooNew, got {:?}", other.map(|_| ())),
+    }
+    let error = Database::new_read_only(&path).await.map(|_| ()).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        format!(
+            "Database schema version {} is newer than this pmr {} supports (schema version {}); install pmr 9.1.0 or later",
+            SCHEMA_VERSION + 1,
+            env!("CARGO_PKG_VERSION"),
+            SCHEMA_VERSION
+        )
+    );
+
+    let info = Database::inspect(&path).await.unwrap();
+    assert!(!info.is_compatible());
+    assert_eq!(info.min_binary_version.as_deref(), Some("9.1.0"));
+}
+
+#[tokio::test]
+async fn test_destructive_migration_needs_consent() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("legacy.db");
+    let database_url = format!("sqlite:{}?mode=rwc", path.display());
+    create_legacy_database(&database_url).await;
+
+    let info = Database::inspect(&path).await.unwrap();
+    assert_eq!(info.schema_version, 0);
+    assert!(info.is_compatible() && info.migrations_pending());
+    assert_eq!(info.destructive_migrations.len(), 1);
+
+    match Database::open(&database_url, false).await {
+        Err(Error::MigrationRequired(version, migrations)) => {
+            assert_eq!(version, 0);
+            assert_eq!(migrations, info.destructive_migrations);
+        }
+        other => panic!("expected MigrationRequired, got {:?}", other.map(|_| ())),
+    }
+    // Refusing left the database as it was
+    assert_eq!(Database::inspect(&path).await.unwrap(), info);
+
+    let db = Database::open(&database_url, true).await.unwrap();
+    assert!(db.get_process_by_name(&named("legacy")).await.unwrap().is_some());
+    assert_eq!(Database::inspect(&path).await.unwrap().schema_version, SCHEMA_VERSION);
+}
+
+#[tokio::test]
+async fn test_additive_migrations_run_without_consent() {
+    let (db, temp_dir) = create_test_database().await;
+    drop(db);
+    let path = temp_dir.path().join("test.db");
+    let database_url = format!("sqlite:{}?mode=rwc", path.display());
+    // An older, versioned database whose upgrade only adds columns
+    write_schema_version(&database_url, 0, "0.1.0").await;
+
+    let info = Database::inspect(&path).await.unwrap();
+    assert!(info.migrations_pending());
+    assert!(info.destructive_migrations.is_empty());
+
+    Database::open(&database_url, false).await.unwrap();
+    let info = Database::inspect(&path).await.unwrap();
+    assert_eq!(info.schema_version, SCHEMA_VERSION);
+    assert_eq!(info.min_binary_version.as_deref(), Some(MIN_BINARY_VERSION));
+}
+
+#[tokio::test]
+async fn test_count_processes_by_status() {
+    let (db, _temp_dir) = create_test_database().await;
+
+    for (name, status) in [
+        ("counted_1", ProcessStatus::Running),
+        ("counted_2", ProcessStatus::Running),
+        ("counted_3", ProcessStatus::Failed),
+        ("counted_4", ProcessStatus::Stopped),
+    ] {
+        let mut process = create_test_process_record(name);
+        process.status = status;
+        db.insert_process(&process).await.unwrap();
+    }
+    db.soft_delete_process(&named("counted_4")).await.unwrap();
+
+    let counts = db.count_processes_by_status().await.unwrap();
+    assert_eq!(counts.to_string(), "2 running, 0 stopped, 1 failed (3 total)");
+}
+
+#[tokio::test]
+async fn test_filtered_queries_use_indexes() {
+    let (_db, temp_dir) = create_test_database().await;
+    let database_url = format!("sqlite:{}", temp_dir.path().join("test.db").display());
+    let pool = sqlx::SqlitePool::connect(&database_url).await.unwrap();
+
+    let plan = |sql: &'static str| {
+        let pool = pool.clone();
+        async move {
+            let rows = sqlx::query(&format!("EXPLAIN QUERY PLAN {}", sql)).fetch_all(&pool).await.unwrap();
+            rows.iter().map(|row| sqlx::Row::get::<String, _>(row, "detail")).collect::<Vec<_>>().join("\n")
+        }
+    };
+
+    // The statements issued by get_processes_by_status, count_processes_by_status,
+    // record_exit and get_deleted_processes_before
+    let by_status = plan("SELECT * FROM processes WHERE status IN (?,?) AND deleted_at IS NULL ORDER BY created_at DESC").await;
+    assert!(by_status.contains("USING INDEX idx_processes_live_status"), "{}", by_status);
+    let counts = plan("SELECT status, COUNT(*) AS count FROM processes WHERE deleted_at IS NULL AND internal = 0 GROUP BY status").await;
+    assert!(counts.contains("USING INDEX idx_processes_live_status"), "{}", counts);
+    let update = plan("UPDATE processes SET status = ?, exit_code = ?, exit_reason = ?, updated_at = ? WHERE name = ? AND deleted_at IS NULL").await;
+    assert!(update.contains("USING INDEX idx_processes_live_name"), "{}", update);
+    let expired = plan("SELECT * FROM processes WHERE deleted_at IS NOT NULL AND deleted_at <= ? ORDER BY deleted_at ASC").await;
+    assert!(expired.contains("USING INDEX idx_processes_deleted_at"), "{}", expired);
+    for detail in [by_status, counts, update, expired] {
+        assert!(detail.lines().all(|line| line != "SCAN processes"), "full table scan: {}", detail);
+    }
+}
+
+#[tokio::test]
+async fn test_delete_by_names_beyond_parameter_limit() {
+    let (db, _temp_dir) = create_test_database().await;
+    for name in ["bulk_a", "bulk_b", "bulk_c"] {
+        db.insert_process(&create_test_process_record(name)).await.unwrap();
+    }
+
+    // More names than any SQLite build accepts as bound parameters in one statement
+    let mut names: Vec<ProcessName> = (0..40_000).map(|i| named(&format!("missing_{}", i))).collect();
+    names.extend(["bulk_a", "bulk_c"].map(named));
+
+    assert_eq!(db.delete_processes_by_names(&names).await.unwrap(), 2);
+    let remaining: Vec<String> = db.get_all_processes().await.unwrap().into_iter().map(|p| p.name.into_string()).collect();
+    assert_eq!(remaining, vec!["bulk_b".to_string()]);
+}
+
+/// Overwrite `len` bytes of the file at `offset` with garbage
+fn garble(path: &std::path::Path, offset: usize, len: usize) {
+    let mut bytes = std::fs::read(path).unwrap();
+    bytes[offset..offset + len].fill(0xa5);
+    std::fs::write(path, bytes).unwrap();
+}
+
+#[tokio::test]
+async fn test_garbled_header_is_reported_and_salvaged() {
+    let (db, temp_dir) = create_test_database().await;
+    db.insert_process(&create_test_process_record("web")).await.unwrap();
+    db.close().await;
+    let path = temp_dir.path().join("test.db");
+    let database_url = format!("sqlite:{}?mode=rwc", path.display());
+    garble(&path, 0, 100);
+
+    match Database::new(&database_url).await {
+        Err(Error::DatabaseCorrupt(_)) => {}
+        other => panic!("expected DatabaseCorrupt, got {:?}", other.map(|_| ())),
+    }
+    assert!(matches!(Database::inspect(&path).await, Err(Error::DatabaseCorrupt(_))));
+
+    let report = Database::salvage(&path).await.unwrap();
+    assert!(report.backup.exists());
+    assert!(report.backup.file_name().unwrap().to_string_lossy().starts_with("test.db.corrupt-"));
+    assert_eq!(report.recovered(), 0);
+    assert!(report.has_unreadable_tables());
+    assert!(!report.integrity_check.is_empty());
+
+    // What is left is an empty but usable database
+    let db = Database::new(&database_url).await.unwrap();
+    assert!(db.get_all_processes().await.unwrap().is_empty());
+    db.insert_process(&create_test_process_record("web")).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_salvage_recovers_rows_around_a_garbled_page() {
+    let (db, temp_dir) = create_test_database().await;
+    const ROWS: usize = 200;
+    for i in 0..ROWS {
+        let mut record = create_test_process_record(&format!("proc-{:03}", i));
+        record.args = vec![format!("marker-{:03}-{}", i, "x".repeat(300))];
+        db.insert_process(&record).await.unwrap();
+    }
+    let instance_id = db.instance_id().await.unwrap();
+    db.close().await;
+    let path = temp_dir.path().join("test.db");
+    let database_url = format!("sqlite:{}?mode=rwc", path.display());
+
+    // Wreck the page holding a row from the middle of the table
+    let bytes = std::fs::read(&path).unwrap();
+    let marker = b"marker-100-";
+    let offset = bytes.windows(marker.len()).position(|window| window == marker).unwrap();
+    const PAGE_SIZE: usize = 4096;
+    garble(&path, offset / PAGE_SIZE * PAGE_SIZE, PAGE_SIZE);
+
+    let report = Database::salvage(&path).await.unwrap();
+    let processes = report.tables.iter().find(|table| table.table == "processes").unwrap();
+    let lost = processes.lost.expect("the table is read row by row");
+    assert!(lost > 0 && lost < ROWS / 2, "lost {} of {} rows", lost, ROWS);
+    assert_eq!(processes.recovered + lost, ROWS);
+    assert_ne!(report.integrity_check, vec!["ok".to_string()]);
+
+    let db = Database::new(&database_url).await.unwrap();
+    let names: Vec<String> = db.get_all_processes().await.unwrap().into_iter().map(|process| process.name.into_string()).collect();
+    assert_eq!(names.len(), processes.recovered);
+    assert!(names.contains(&"proc-000".to_string()));
+    assert!(names.contains(&format!("proc-{:03}", ROWS - 1)));
+    assert!(!names.contains(&"proc-100".to_string()));
+    // The rebuilt file is still the same database
+    assert_eq!(db.instance_id().await.unwrap(), instance_id);
+}
+
+#[tokio::test]
+async fn test_search_matches_name_or_description_literally() {
+    let (db, _temp_dir) = create_test_database().await;
+    let mut export = create_test_process_record("nightly-export");
+    export.description = Some("Orders to the 100% complete CSV".to_string());
+    db.insert_process(&export).await.unwrap();
+    let mut web = create_test_process_record("web");
+    web.description = Some("Public EXPORT page".to_string());
+    db.insert_process(&web).await.unwrap();
+    db.insert_process(&create_test_process_record("worker")).await.unwrap();
+
+    let names = |records: Vec<ProcessRecord>| {
+        let mut names: Vec<String> = records.into_iter().map(|process| process.name.into_string()).collect();
+        names.sort();
+        names
+    };
+    assert_eq!(names(db.search_processes("Export").await.unwrap()), vec!["nightly-export", "web"]);
+    assert_eq!(names(db.search_processes("csv").await.unwrap()), vec!["nightly-export"]);
+    // LIKE wildcards are plain text
+    assert_eq!(names(db.search_processes("100%").await.unwrap()), vec!["nightly-export"]);
+    assert_eq!(db.search_processes("%").await.unwrap().len(), 1);
+    assert!(db.search_processes("w_b").await.unwrap().is_empty());
+
+    assert!(db.update_description(&named("worker"), Some("Queue consumer")).await.unwrap());
+    assert_eq!(names(db.search_processes("queue").await.unwrap()), vec!["worker"]);
+    assert!(db.update_description(&named("worker"), None).await.unwrap());
+    assert!(db.get_process_by_name(&named("worker")).await.unwrap().unwrap().description.is_none());
+    assert!(!db.update_description(&named("missing"), None).await.unwrap());
+}
@@ -1,3 +1,5 @@
+mod support;
+
 use pmr::{
     config::{Config, LogRotationConfig},
     process::ProcessManager,
@@ -7,6 +9,7 @@ use std::collections::HashMap;
 use std::time::Duration;
 use tempfile::TempDir;
 use tokio::time::sleep;
+use support::named;
 
 /// Helper function to create a test ProcessManager with temporary directories
 async fn create_test_process_manager() -> (ProcessManager, TempDir) {
@@ -49,7 +52,7 @@ async fn test_complete_process_management_workflow() {
         env_vars.insert("PROCESS_NAME".to_string(), name.to_string());
         env_vars.insert("ENVIRONMENT".to_string(), "test".to_string());
         
-        pm.start_process(name, command, args.clone(), env_vars, None, None)
+        pm.start(&named(name), command, args.clone(), env_vars, None, None)
             .await
             .unwrap();
         
@@ -62,7 +65,7 @@ async fn test_complete_process_management_workflow() {
     
     // Step 4: Check individual process status
     for (name, _, _) in &test_processes {
-        let status = pm.get_process_status(name).await.unwrap();
+        let status = pm.get_process_status(&named(name)).await.unwrap();
         assert_eq!(status.name, *name);
         assert!(matches!(status.status, ProcessStatus::Running | ProcessStatus::Stopped));
         println!("Process {} status: {:?}", name, status.status);
@@ -72,32 +75,32 @@ async fn test_complete_process_management_workflow() {
     sleep(Duration::from_millis(500)).await;
     
     for (name, _, args) in &test_processes {
-        let logs = pm.get_process_logs(name, None).await.unwrap();
+        let logs = pm.get_process_logs(&named(name), None).await.unwrap();
         assert!(logs.contains(&args[0]), "Logs should contain expected output");
         println!("Process {} logs verified", name);
     }
     
     // Step 6: Test restart functionality
     let restart_process = "web_server";
-    pm.restart_process(restart_process).await.unwrap();
+    pm.restart(&named(restart_process)).await.unwrap();
     println!("Restarted process: {}", restart_process);
     
     // Verify restart worked
-    let status = pm.get_process_status(restart_process).await.unwrap();
+    let status = pm.get_process_status(&named(restart_process)).await.unwrap();
     assert_eq!(status.name, restart_process);
     
     // Step 7: Test stop functionality (if process is still running)
     for (name, _, _) in &test_processes {
-        let status = pm.get_process_status(name).await.unwrap();
+        let status = pm.get_process_status(&named(name)).await.unwrap();
         if status.status == ProcessStatus::Running {
-            pm.stop_process(name).await.unwrap();
+            pm.stop(&named(name)).await.unwrap();
             println!("Stopped process: {}", name);
         }
     }
     
     // Step 8: Clean up all processes
     for (name, _, _) in &test_processes {
-        pm.delete_process(name).await.unwrap();
+        pm.delete(&named(name)).await.unwrap();
         println!("Deleted process: {}", name);
     }
     
@@ -115,8 +118,8 @@ async fn test_error_scenarios_and_recovery() {
     println!("Starting error scenarios and recovery test");
     
     // Test 1: Try to start process with invalid command
-    let result = pm.start_process(
-        "invalid_cmd",
+    let result = pm.start(
+        &named("invalid_cmd"),
         "nonexistent_command_12345",
         vec![],
         HashMap::new(),
@@ -127,43 +130,41 @@ async fn test_error_scenarios_and_recovery() {
     // Should succeed in creating the process record, but process will fail
     assert!(result.is_ok(), "Should create process record even for invalid commands");
     
-    // Wait and check status
-    sleep(Duration::from_millis(300)).await;
-    let status = pm.get_process_status("invalid_cmd").await.unwrap();
-    // Status should be Failed or Stopped (depending on how setsid handles it)
-    assert!(matches!(status.status, ProcessStatus::Failed | ProcessStatus::Stopped | ProcessStatus::Running));
+    // setsid cannot exec the command, so the start window sees a non-zero exit
+    let status = pm.get_process_status(&named("invalid_cmd")).await.unwrap();
+    assert_eq!(status.status, ProcessStatus::Failed);
     
     // Test 2: Try to operate on non-existent process
-    let result = pm.get_process_status("nonexistent").await;
+    let result = pm.get_process_status(&named("nonexistent")).await;
     assert!(result.is_err(), "Should fail for non-existent process");
     
-    let result = pm.stop_process("nonexistent").await;
+    let result = pm.stop(&named("nonexistent")).await;
     assert!(result.is_err(), "Should fail to stop non-existent process");
     
-    let result = pm.delete_process("nonexistent").await;
+    let result = pm.delete(&named("nonexistent")).await;
     assert!(result.is_err(), "Should fail to delete non-existent process");
     
     // Test 3: Try to create duplicate process
-    pm.start_process("duplicate", "echo", vec!["first".to_string()], HashMap::new(), None, None)
+    pm.start(&named("duplicate"), "echo", vec!["first".to_string()], HashMap::new(), None, None)
         .await
         .unwrap();
     
-    let result = pm.start_process("duplicate", "echo", vec!["second".to_string()], HashMap::new(), None, None)
+    let result = pm.start(&named("duplicate"), "echo", vec!["second".to_string()], HashMap::new(), None, None)
         .await;
     assert!(result.is_err(), "Should fail to create duplicate process");
     
     // Test 4: Recovery - system should still be functional
-    pm.start_process("recovery_test", "echo", vec!["recovery".to_string()], HashMap::new(), None, None)
+    pm.start(&named("recovery_test"), "echo", vec!["recovery".to_string()], HashMap::new(), None, None)
         .await
         .unwrap();
     
-    let status = pm.get_process_status("recovery_test").await.unwrap();
+    let status = pm.get_process_status(&named("recovery_test")).await.unwrap();
     assert_eq!(status.name, "recovery_test");
     
     // Clean up
-    pm.delete_process("invalid_cmd").await.unwrap();
-    pm.delete_process("duplicate").await.unwrap();
-    pm.delete_process("recovery_test").await.unwrap();
+    pm.delete(&named("invalid_cmd")).await.unwrap();
+    pm.delete(&named("duplicate")).await.unwrap();
+    pm.delete(&named("recovery_test")).await.unwrap();
     
     println!("Error scenarios and recovery test passed!");
 }
@@ -175,8 +176,8 @@ async fn test_log_management_features() {
     println!("Starting log management features test");
     
     // Create a process that generates multiple log lines
-    pm.start_process(
-        "log_test",
+    pm.start(
+        &named("log_test"),
         "sh",
         vec![
             "-c".to_string(),
@@ -193,7 +194,7 @@ async fn test_log_management_features() {
     sleep(Duration::from_millis(1000)).await;
     
     // Test 1: Get all logs
-    let all_logs = pm.get_process_logs("log_test", None).await.unwrap();
+    let all_logs = pm.get_process_logs(&named("log_test"), None).await.unwrap();
     assert!(all_logs.contains("Log line 1"), "Should contain first log line");
     assert!(all_logs.contains("Log line 20"), "Should contain last log line");
     
@@ -201,25 +202,25 @@ async fn test_log_management_features() {
     assert!(line_count >= 20, "Should have at least 20 log lines");
     
     // Test 2: Get limited logs
-    let limited_logs = pm.get_process_logs("log_test", Some(5)).await.unwrap();
+    let limited_logs = pm.get_process_logs(&named("log_test"), Some(5)).await.unwrap();
     let limited_line_count = limited_logs.lines().count();
     assert!(limited_line_count <= 5, "Should have at most 5 lines");
     
     // Test 3: Check log rotation status
-    let rotation_status = pm.get_log_rotation_status("log_test").await.unwrap();
+    let rotation_status = pm.get_log_rotation_status(&named("log_test")).await.unwrap();
     assert!(rotation_status.contains("Log file:"), "Should contain log file info");
     assert!(rotation_status.contains("Current size:"), "Should contain size info");
     
     // Test 4: Manual log rotation
-    pm.rotate_process_logs("log_test").await.unwrap();
+    pm.rotate_process_logs(&named("log_test")).await.unwrap();
     
     // Test 5: Get rotated logs
-    let rotated_logs = pm.get_rotated_logs("log_test").await.unwrap();
+    let rotated_logs = pm.rotated_logs(&named("log_test")).await.unwrap();
     // May or may not have rotated logs depending on file size
     println!("Rotated logs count: {}", rotated_logs.len());
     
     // Clean up
-    pm.delete_process("log_test").await.unwrap();
+    pm.delete(&named("log_test")).await.unwrap();
     
     println!("Log management features test passed!");
 }
@@ -236,8 +237,8 @@ async fn test_environment_and_working_directory() {
     env_vars.insert("TEST_VAR2".to_string(), "value2".to_string());
     env_vars.insert("CUSTOM_MESSAGE".to_string(), "Hello from PMR!".to_string());
     
-    pm.start_process(
-        "env_test",
+    pm.start(
+        &named("env_test"),
         "sh",
         vec![
             "-c".to_string(),
@@ -252,8 +253,8 @@ async fn test_environment_and_working_directory() {
     
     // Test 2: Working directory
     let work_dir = temp_dir.path().to_string_lossy().to_string();
-    pm.start_process(
-        "workdir_test",
+    pm.start(
+        &named("workdir_test"),
         "pwd",
         vec![],
         HashMap::new(),
@@ -267,18 +268,18 @@ async fn test_environment_and_working_directory() {
     sleep(Duration::from_millis(500)).await;
     
     // Verify environment variables
-    let env_logs = pm.get_process_logs("env_test", None).await.unwrap();
+    let env_logs = pm.get_process_logs(&named("env_test"), None).await.unwrap();
     assert!(env_logs.contains("VAR1: value1"), "Should contain TEST_VAR1");
     assert!(env_logs.contains("VAR2: value2"), "Should contain TEST_VAR2");
     assert!(env_logs.contains("MSG: Hello from PMR!"), "Should contain CUSTOM_MESSAGE");
     
     // Verify working directory
-    let workdir_logs = pm.get_process_logs("workdir_test", None).await.unwrap();
+    let workdir_logs = pm.get_process_logs(&named("workdir_test"), None).await.unwrap();
     assert!(workdir_logs.contains(&work_dir), "Should contain working directory path");
     
     // Clean up
-    pm.delete_process("env_test").await.unwrap();
-    pm.delete_process("workdir_test").await.unwrap();
+    pm.delete(&named("env_test")).await.unwrap();
+    pm.delete(&named("workdir_test")).await.unwrap();
     
     println!("Environment and working directory test passed!");
 }
@@ -293,8 +294,8 @@ async fn test_system_stability_under_load() {
     
     // Create multiple processes
     for i in 0..num_processes {
-        let name = format!("stability_test_{}", i);
-        pm.start_process(
+        let name = named(&format!("stability_test_{}", i));
+        pm.start(
             &name,
             "echo",
             vec![format!("Stability test {}", i)],
@@ -314,7 +315,7 @@ async fn test_system_stability_under_load() {
         
         // Check status of random processes
         for i in (0..num_processes).step_by(5) {
-            let name = format!("stability_test_{}", i);
+            let name = named(&format!("stability_test_{}", i));
             let _status = pm.get_process_status(&name).await.unwrap();
         }
         
@@ -323,8 +324,8 @@ async fn test_system_stability_under_load() {
     
     // Clean up all processes
     for i in 0..num_processes {
-        let name = format!("stability_test_{}", i);
-        pm.delete_process(&name).await.unwrap();
+        let name = named(&format!("stability_test_{}", i));
+        pm.delete(&name).await.unwrap();
     }
     
     // Verify cleanup
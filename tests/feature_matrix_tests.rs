@@ -0,0 +1,39 @@
+//! The crate must build in each of its supported feature combinations: the library alone,
+//! the default `pmr` binary, and the binary with the HTTP API. Checking them runs cargo
+//! three times, so the test is ignored by default; CI runs it with `--ignored`.
+
+use std::path::Path;
+use std::process::Command;
+
+/// Arguments to `cargo check` for each combination, with what it stands for
+const COMBINATIONS: [(&str, &[&str]); 3] = [
+    ("library only", &["--no-default-features"]),
+    ("cli (default)", &[]),
+    ("cli and http-api", &["--features", "http-api"]),
+];
+
+#[test]
+#[ignore = "runs cargo check once per feature combination"]
+fn test_feature_combinations_build() {
+    let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let cargo = std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+    // Its own target directory, since the one of the running test may be locked
+    let target_dir = manifest_dir.join("target").join("feature-matrix");
+
+    let mut failures = Vec::new();
+    for (name, features) in COMBINATIONS {
+        let output = Command::new(&cargo)
+            .current_dir(manifest_dir)
+            .args(["check", "--quiet", "--all-targets"])
+            .args(features)
+            .arg("--target-dir")
+            .arg(&target_dir)
+            .env("RUSTFLAGS", "-D warnings")
+            .output()
+            .expect("failed to run cargo");
+        if !output.status.success() {
+            failures.push(format!("{}:\n{}", name, String::from_utf8_lossy(&output.stderr)));
+        }
+    }
+    assert!(failures.is_empty(), "feature combinations that do not build:\n{}", failures.join("\n"));
+}
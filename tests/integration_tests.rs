@@ -1,12 +1,28 @@
+mod support;
+
+#[cfg(feature = "cli")]
+use pmr::{cli::Commands, env::EnvVarProblem};
 use pmr::{
-    config::{Config, LogRotationConfig},
-    process::ProcessManager,
-    database::ProcessStatus,
+    affinity::{available_cpus, CpuSet},
+    cancel::Cancellation,
+    cgroup::{Cgroup, CgroupLimits},
+    config::{Config, LogRotationConfig, StartLimitsConfig},
+    env::ReservedEnvPolicy,
+    process::{
+        parse_signal, signal_name, DeleteOptions, LogOptions, ProcessManager, RestartOptions, StartOptions, StatusRefresh, StopOptions,
+        MAX_NOTES_PER_PROCESS, MAX_NOTE_BYTES,
+    },
+    timings::TimingCollector,
+    database::{Database, LogDirMode, ProcessStatus, HTTP_SERVER_PROCESS_NAME},
+    drift::DriftIssue,
+    file_watch::{watch_process, WatchConfig},
+    outcome::{RestartSkip, StartExit},
     Error,
 };
 use std::collections::HashMap;
 use tempfile::TempDir;
 use tokio::time::{sleep, Duration};
+use support::named;
 
 /// Helper function to create a test ProcessManager with temporary directories
 async fn create_test_process_manager() -> (ProcessManager, TempDir) {
@@ -37,7 +53,7 @@ async fn test_process_lifecycle() {
     let env_vars = HashMap::new();
     
     // Test starting a process
-    pm.start_process(name, command, args.clone(), env_vars.clone(), None, None)
+    pm.start(&named(name), command, args.clone(), env_vars.clone(), None, None)
         .await
         .unwrap();
     
@@ -45,7 +61,7 @@ async fn test_process_lifecycle() {
     sleep(Duration::from_millis(300)).await;
     
     // Test getting process status
-    let status = pm.get_process_status(name).await.unwrap();
+    let status = pm.get_process_status(&named(name)).await.unwrap();
     assert_eq!(status.name, name);
     assert_eq!(status.command, command);
     assert_eq!(status.args, args);
@@ -56,14 +72,14 @@ async fn test_process_lifecycle() {
     assert_eq!(processes[0].name, name);
     
     // Test getting logs
-    let logs = pm.get_process_logs(name, None).await.unwrap();
+    let logs = pm.get_process_logs(&named(name), None).await.unwrap();
     assert!(logs.contains("Hello, World!"));
     
     // Test deleting process
-    pm.delete_process(name).await.unwrap();
+    pm.delete(&named(name)).await.unwrap();
     
     // Verify process is deleted
-    let result = pm.get_process_status(name).await;
+    let result = pm.get_process_status(&named(name)).await;
     assert!(matches!(result, Err(Error::ProcessNotFound(_))));
     
     let processes = pm.list_processes().await.unwrap();
@@ -80,16 +96,16 @@ async fn test_process_already_exists() {
     let env_vars = HashMap::new();
     
     // Start first process
-    pm.start_process(name, command, args.clone(), env_vars.clone(), None, None)
+    pm.start(&named(name), command, args.clone(), env_vars.clone(), None, None)
         .await
         .unwrap();
     
     // Try to start duplicate process
-    let result = pm.start_process(name, command, args, env_vars, None, None).await;
+    let result = pm.start(&named(name), command, args, env_vars, None, None).await;
     assert!(matches!(result, Err(Error::ProcessAlreadyExists(_))));
     
     // Cleanup
-    pm.delete_process(name).await.unwrap();
+    pm.delete(&named(name)).await.unwrap();
 }
 
 #[tokio::test]
@@ -99,19 +115,19 @@ async fn test_process_not_found() {
     let name = "nonexistent_process";
     
     // Test getting status of non-existent process
-    let result = pm.get_process_status(name).await;
+    let result = pm.get_process_status(&named(name)).await;
     assert!(matches!(result, Err(Error::ProcessNotFound(_))));
     
     // Test stopping non-existent process
-    let result = pm.stop_process(name).await;
+    let result = pm.stop(&named(name)).await;
     assert!(matches!(result, Err(Error::ProcessNotFound(_))));
     
     // Test restarting non-existent process
-    let result = pm.restart_process(name).await;
+    let result = pm.restart(&named(name)).await;
     assert!(matches!(result, Err(Error::ProcessNotFound(_))));
     
     // Test deleting non-existent process
-    let result = pm.delete_process(name).await;
+    let result = pm.delete(&named(name)).await;
     assert!(matches!(result, Err(Error::ProcessNotFound(_))));
 }
 
@@ -125,24 +141,24 @@ async fn test_long_running_process() {
     let env_vars = HashMap::new();
     
     // Start long-running process
-    pm.start_process(name, command, args, env_vars, None, None)
+    pm.start(&named(name), command, args, env_vars, None, None)
         .await
         .unwrap();
     
     // Check initial status - might be running or stopped depending on timing
-    let status = pm.get_process_status(name).await.unwrap();
+    let status = pm.get_process_status(&named(name)).await.unwrap();
     assert!(status.pid.is_some());
 
     // Wait for process to complete
     sleep(Duration::from_millis(2500)).await;
 
     // Check final status - should be stopped after sleep completes
-    let status = pm.get_process_status(name).await.unwrap();
+    let status = pm.get_process_status(&named(name)).await.unwrap();
     // Process should be stopped after the sleep duration
     assert!(matches!(status.status, ProcessStatus::Stopped | ProcessStatus::Running));
     
     // Cleanup
-    pm.delete_process(name).await.unwrap();
+    pm.delete(&named(name)).await.unwrap();
 }
 
 #[tokio::test]
@@ -156,7 +172,7 @@ async fn test_process_with_environment_variables() {
     env_vars.insert("TEST_VAR".to_string(), "test_value".to_string());
     
     // Start process with environment variables
-    pm.start_process(name, command, args, env_vars, None, None)
+    pm.start(&named(name), command, args, env_vars, None, None)
         .await
         .unwrap();
     
@@ -164,11 +180,11 @@ async fn test_process_with_environment_variables() {
     sleep(Duration::from_millis(300)).await;
     
     // Check logs contain the environment variable value
-    let logs = pm.get_process_logs(name, None).await.unwrap();
+    let logs = pm.get_process_logs(&named(name), None).await.unwrap();
     assert!(logs.contains("test_value"));
     
     // Cleanup
-    pm.delete_process(name).await.unwrap();
+    pm.delete(&named(name)).await.unwrap();
 }
 
 #[tokio::test]
@@ -182,7 +198,7 @@ async fn test_process_with_working_directory() {
     let working_dir = Some(temp_dir.path().to_string_lossy().to_string());
     
     // Start process with custom working directory
-    pm.start_process(name, command, args, env_vars, working_dir.clone(), None)
+    pm.start(&named(name), command, args, env_vars, working_dir.clone(), None)
         .await
         .unwrap();
     
@@ -190,39 +206,174 @@ async fn test_process_with_working_directory() {
     sleep(Duration::from_millis(300)).await;
     
     // Check logs contain the working directory
-    let logs = pm.get_process_logs(name, None).await.unwrap();
+    let logs = pm.get_process_logs(&named(name), None).await.unwrap();
     assert!(logs.contains(&*temp_dir.path().to_string_lossy()));
     
     // Cleanup
-    pm.delete_process(name).await.unwrap();
+    pm.delete(&named(name)).await.unwrap();
 }
 
 #[tokio::test]
 async fn test_failed_process() {
-    let (pm, _temp_dir) = create_test_process_manager().await;
+    let temp_dir = TempDir::new().unwrap();
+    // A generous window keeps the early exit detectable when the suite runs on a loaded machine
+    let config = Config::in_directory(temp_dir.path()).with_start_check_window(Duration::from_secs(5));
+    let pm = ProcessManager::new(config).await.unwrap();
     
     let name = "failed_process";
     let command = "nonexistent_command_12345";
     let args = vec![];
     let env_vars = HashMap::new();
     
-    // Start process that will fail
-    pm.start_process(name, command, args, env_vars, None, None)
+    // Start process that will fail: setsid cannot exec the command and exits non-zero
+    let outcome = pm.start(&named(name), command, args, env_vars, None, None)
         .await
         .unwrap();
-    
-    // Wait a bit
-    sleep(Duration::from_millis(300)).await;
+    assert_eq!(outcome.status, ProcessStatus::Failed, "{:?}", outcome);
+    assert!(matches!(outcome.early_exit, Some(StartExit { exit_code: Some(code), .. }) if code != 0), "{:?}", outcome);
 
-    // Check status - should be failed or stopped since command doesn't exist
-    let status = pm.get_process_status(name).await.unwrap();
-    // The process might start and then fail, or fail to start entirely, or be running (setsid process)
-    // Since we're using setsid, the setsid process itself might be running even if the target command fails
-    println!("Process status: {:?}", status.status);
-    assert!(matches!(status.status, ProcessStatus::Failed | ProcessStatus::Stopped | ProcessStatus::Running));
+    let status = pm.get_process_status(&named(name)).await.unwrap();
+    assert_eq!(status.status, ProcessStatus::Failed);
+    assert!(matches!(status.exit_code, Some(code) if code != 0));
     
     // Cleanup
-    pm.delete_process(name).await.unwrap();
+    pm.delete(&named(name)).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_quick_exit_success_and_failure_are_told_apart() {
+    let (pm, _temp_dir) = create_test_process_manager().await;
+
+    let outcome = pm
+        .start(&named("quick_ok"), "true", vec![], HashMap::new(), None, None)
+        .await
+        .unwrap();
+    assert_eq!(outcome.status, ProcessStatus::Stopped);
+    let exit = outcome.early_exit.unwrap();
+    assert!(exit.succeeded());
+    assert!(exit.log_tail.is_empty());
+    let status = pm.get_process_status(&named("quick_ok")).await.unwrap();
+    assert_eq!(status.status, ProcessStatus::Stopped);
+    assert_eq!(status.exit_code, Some(0));
+
+    let script = "echo starting; echo 'config missing' >&2; exit 3".to_string();
+    let outcome = pm
+        .start(&named("quick_fail"), "sh", vec!["-c".to_string(), script], HashMap::new(), None, None)
+        .await
+        .unwrap();
+    assert_eq!(outcome.status, ProcessStatus::Failed);
+    let exit = outcome.early_exit.unwrap();
+    assert_eq!(exit.exit_code, Some(3));
+    assert_eq!(exit.log_tail, vec!["starting", "config missing"]);
+    let status = pm.get_process_status(&named("quick_fail")).await.unwrap();
+    assert_eq!(status.status, ProcessStatus::Failed);
+    assert_eq!(status.exit_code, Some(3));
+}
+
+#[tokio::test]
+async fn test_strict_start_rolls_back_quick_exit() {
+    let (pm, temp_dir) = create_test_process_manager().await;
+    let log_dir = temp_dir.path().join("strict/logs");
+    let pid_file = temp_dir.path().join("leftover.pid");
+    let strict = || StartOptions {
+        log_dir: Some(log_dir.to_string_lossy().to_string()),
+        strict: true,
+        ..Default::default()
+    };
+
+    // Leaves a background child behind in its session, then fails
+    let script = format!("sleep 30 & echo $! > {}; echo 'config missing' >&2; exit 3", pid_file.display());
+    let error = pm
+        .start_with_options(&named("svc"), "sh", vec!["-c".to_string(), script], HashMap::new(), strict())
+        .await
+        .unwrap_err();
+    assert!(matches!(error, Error::StartExited(_, Some(3), _)), "{}", error);
+    let message = error.to_string();
+    assert!(message.starts_with("Process 'svc' exited with code 3 during the start window"), "{}", message);
+    assert!(message.contains("Last log lines:\n  config missing"), "{}", message);
+
+    // Nothing left in the database or on disk
+    assert!(pm.list_processes().await.unwrap().is_empty());
+    assert!(matches!(pm.undelete(&named("svc")).await, Err(Error::ProcessNotFound(_))));
+    assert!(!temp_dir.path().join("strict").exists());
+
+    let leftover: u32 = std::fs::read_to_string(&pid_file).unwrap().trim().parse().unwrap();
+    let mut gone = false;
+    for _ in 0..50 {
+        let stat = std::fs::read_to_string(format!("/proc/{}/stat", leftover)).unwrap_or_default();
+        if stat.is_empty() || stat.contains(") Z ") {
+            gone = true;
+            break;
+        }
+        sleep(Duration::from_millis(100)).await;
+    }
+    assert!(gone, "background child {} survived the rollback", leftover);
+
+    // A process that keeps running is unaffected by strict
+    let outcome = pm
+        .start_with_options(&named("svc"), "sleep", vec!["30".to_string()], HashMap::new(), strict())
+        .await
+        .unwrap();
+    assert_eq!(outcome.status, ProcessStatus::Running);
+    assert!(outcome.pid.is_some() && outcome.early_exit.is_none(), "{:?}", outcome);
+    pm.delete_with_options(&named("svc"), DeleteOptions { hard: true }).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_invalid_config_stops_manager_creation() {
+    let temp_dir = TempDir::new().unwrap();
+    let config = Config::in_directory(temp_dir.path()).with_log_rotation(LogRotationConfig {
+        enabled: true,
+        max_file_size: 0,
+        max_files: 0,
+    });
+
+    let Err(error) = ProcessManager::new(config).await else {
+        panic!("manager started with an invalid config");
+    };
+    assert!(matches!(error, Error::InvalidConfig(ref issues) if issues.len() == 2));
+    assert_eq!(
+        error.to_string(),
+        "Invalid configuration:\n  - log_rotation.max_files: rotation is enabled but keeps no rotated files; set at least 1 or disable rotation\n  - log_rotation.max_file_size: rotation is enabled with a 0 byte limit, so every non-empty log would rotate"
+    );
+    // Nothing was created before the check
+    assert!(!temp_dir.path().join("processes.db").exists());
+}
+
+#[tokio::test]
+async fn test_start_check_window_is_configurable() {
+    let temp_dir = TempDir::new().unwrap();
+    let config = Config::in_directory(temp_dir.path()).with_start_check_window(Duration::from_millis(1500));
+    let pm = ProcessManager::new(config).await.unwrap();
+
+    // Exits after the default 200ms window but inside the widened one
+    let outcome = pm
+        .start(&named("slow_fail"), "sh", vec!["-c".to_string(), "sleep 0.5; exit 1".to_string()], HashMap::new(), None, None)
+        .await
+        .unwrap();
+    assert_eq!(outcome.early_exit.and_then(|exit| exit.exit_code), Some(1));
+    assert_eq!(pm.get_process_status(&named("slow_fail")).await.unwrap().status, ProcessStatus::Failed);
+}
+
+#[tokio::test]
+async fn test_start_limits_refuse_new_definitions_but_not_restarts() {
+    let temp_dir = TempDir::new().unwrap();
+    let unlimited = StartLimitsConfig { max_args_bytes: 0, max_env_value_bytes: 0, max_env_bytes: 0 };
+    let before = ProcessManager::new(Config::in_directory(temp_dir.path()).with_start_limits(unlimited)).await.unwrap();
+    let env_vars = HashMap::from([("BLOB".to_string(), "b".repeat(64 * 1024))]);
+    before.start(&named("stored"), "true", vec![], env_vars.clone(), None, None).await.unwrap();
+    drop(before);
+
+    let pm = ProcessManager::new(Config::in_directory(temp_dir.path())).await.unwrap();
+    let err = pm.start(&named("new"), "true", vec![], env_vars, None, None).await.unwrap_err();
+    assert!(matches!(err, Error::StartTooLarge(_)), "{}", err);
+    assert!(err.to_string().contains("environment variable 'BLOB' is 64.0 KiB, over the 32.0 KiB limit"), "{}", err);
+    assert!(pm.get_process_status(&named("new")).await.is_err());
+
+    // A record stored before the limits keeps working
+    sleep(Duration::from_millis(300)).await;
+    pm.restart(&named("stored")).await.unwrap();
+    pm.delete(&named("stored")).await.unwrap();
 }
 
 #[tokio::test]
@@ -235,7 +386,7 @@ async fn test_restart_process() {
     let env_vars = HashMap::new();
     
     // Start process
-    pm.start_process(name, command, args, env_vars, None, None)
+    pm.start(&named(name), command, args, env_vars, None, None)
         .await
         .unwrap();
     
@@ -243,21 +394,21 @@ async fn test_restart_process() {
     sleep(Duration::from_millis(300)).await;
     
     // Get initial logs
-    let initial_logs = pm.get_process_logs(name, None).await.unwrap();
+    let initial_logs = pm.get_process_logs(&named(name), None).await.unwrap();
     assert!(initial_logs.contains("first_run"));
     
     // Restart process
-    pm.restart_process(name).await.unwrap();
+    pm.restart(&named(name)).await.unwrap();
     
     // Wait for completion
     sleep(Duration::from_millis(300)).await;
     
     // Check that process still exists and has new logs
-    let status = pm.get_process_status(name).await.unwrap();
+    let status = pm.get_process_status(&named(name)).await.unwrap();
     assert_eq!(status.name, name);
     
     // Cleanup
-    pm.delete_process(name).await.unwrap();
+    pm.delete(&named(name)).await.unwrap();
 }
 
 #[tokio::test]
@@ -270,7 +421,7 @@ async fn test_log_lines_limit() {
     let env_vars = HashMap::new();
     
     // Start process that outputs multiple lines
-    pm.start_process(name, command, args, env_vars, None, None)
+    pm.start(&named(name), command, args, env_vars, None, None)
         .await
         .unwrap();
     
@@ -278,12 +429,12 @@ async fn test_log_lines_limit() {
     sleep(Duration::from_millis(500)).await;
     
     // Get all logs
-    let all_logs = pm.get_process_logs(name, None).await.unwrap();
+    let all_logs = pm.get_process_logs(&named(name), None).await.unwrap();
     let all_lines: Vec<&str> = all_logs.lines().collect();
     assert!(all_lines.len() >= 10);
     
     // Get limited logs
-    let limited_logs = pm.get_process_logs(name, Some(3)).await.unwrap();
+    let limited_logs = pm.get_process_logs(&named(name), Some(3)).await.unwrap();
     let limited_lines: Vec<&str> = limited_logs.lines().collect();
     assert_eq!(limited_lines.len(), 3);
     
@@ -291,7 +442,7 @@ async fn test_log_lines_limit() {
     assert!(limited_logs.contains("Line 8") || limited_logs.contains("Line 9") || limited_logs.contains("Line 10"));
     
     // Cleanup
-    pm.delete_process(name).await.unwrap();
+    pm.delete(&named(name)).await.unwrap();
 }
 
 #[tokio::test]
@@ -301,8 +452,8 @@ async fn test_start_process_rollback() {
     println!("Starting process rollback test");
 
     // Test 1: Try to start a process with an invalid working directory that will fail to spawn
-    let result = pm.start_process(
-        "invalid_process",
+    let result = pm.start(
+        &named("invalid_process"),
         "echo",
         vec!["test".to_string()],
         HashMap::new(),
@@ -314,7 +465,7 @@ async fn test_start_process_rollback() {
     assert!(result.is_err());
 
     // Verify no database record was created
-    let process_result = pm.get_process_status("invalid_process").await;
+    let process_result = pm.get_process_status(&named("invalid_process")).await;
     assert!(process_result.is_err(), "Database record should have been rolled back");
 
     // Verify log file was not left behind
@@ -322,8 +473,8 @@ async fn test_start_process_rollback() {
     assert!(!log_path.exists(), "Log file should have been rolled back");
 
     // Test 2: Try to start a process with another invalid working directory
-    let result = pm.start_process(
-        "invalid_process2",
+    let result = pm.start(
+        &named("invalid_process2"),
         "echo",
         vec!["test2".to_string()],
         HashMap::new(),
@@ -335,7 +486,7 @@ async fn test_start_process_rollback() {
     assert!(result.is_err());
 
     // Verify no database record was created
-    let process_result = pm.get_process_status("invalid_process2").await;
+    let process_result = pm.get_process_status(&named("invalid_process2")).await;
     assert!(process_result.is_err(), "Database record should have been rolled back");
 
     // Verify log file was not left behind
@@ -343,8 +494,8 @@ async fn test_start_process_rollback() {
     assert!(!log_path2.exists(), "Log file should have been rolled back");
 
     // Test 3: Verify that a successful process start still works
-    let result = pm.start_process(
-        "valid_process",
+    let result = pm.start(
+        &named("valid_process"),
         "echo",
         vec!["hello".to_string()],
         HashMap::new(),
@@ -356,11 +507,1873 @@ async fn test_start_process_rollback() {
     assert!(result.is_ok());
 
     // Verify database record exists
-    let process_result = pm.get_process_status("valid_process").await;
+    let process_result = pm.get_process_status(&named("valid_process")).await;
     assert!(process_result.is_ok(), "Valid process should have database record");
 
     // Clean up
-    pm.delete_process("valid_process").await.unwrap();
+    pm.delete(&named("valid_process")).await.unwrap();
 
     println!("Process rollback test passed!");
 }
+
+#[tokio::test]
+async fn test_cgroup_limits_require_cgroup_v2() {
+    let temp_dir = TempDir::new().unwrap();
+    let config = Config::new()
+        .with_database_path(temp_dir.path().join("test.db"))
+        .with_log_dir(temp_dir.path().join("logs"))
+        .with_cgroup_root(temp_dir.path().join("not-a-cgroup"));
+    let pm = ProcessManager::new(config).await.unwrap();
+
+    let options = StartOptions {
+        cgroup_limits: CgroupLimits {
+            memory_max: Some(64 * 1024 * 1024),
+            cpu_max_percent: None,
+        },
+        ..Default::default()
+    };
+    let result = pm
+        .start_with_options(&named("capped"), "sleep", vec!["5".to_string()], HashMap::new(), options)
+        .await;
+
+    // Missing cgroup support is an explicit error, not a silently unconstrained process
+    let err = result.unwrap_err();
+    assert!(err.to_string().contains("cgroup v2 is not available"));
+    assert!(matches!(pm.get_process_status(&named("capped")).await, Err(Error::ProcessNotFound(_))));
+}
+
+#[tokio::test]
+async fn test_cgroup_limits_applied_when_available() {
+    let cgroup_root = pmr::cgroup::default_root();
+    if !Cgroup::is_available(&cgroup_root) || unsafe { libc::geteuid() } != 0 {
+        println!("Skipping cgroup test: no writable cgroup v2 hierarchy");
+        return;
+    }
+
+    let (pm, _temp_dir) = create_test_process_manager().await;
+    let options = StartOptions {
+        cgroup_limits: CgroupLimits {
+            memory_max: Some(64 * 1024 * 1024),
+            cpu_max_percent: Some(50),
+        },
+        ..Default::default()
+    };
+    pm.start_with_options(&named("pmr_cgroup_test"), "sleep", vec!["30".to_string()], HashMap::new(), options)
+        .await
+        .unwrap();
+
+    let status = pm.get_process_status(&named("pmr_cgroup_test")).await.unwrap();
+    let cgroup = status.cgroup.expect("status should report cgroup usage");
+    let cgroup_path = std::path::PathBuf::from(&cgroup.path);
+    assert!(cgroup_path.exists());
+
+    pm.stop(&named("pmr_cgroup_test")).await.unwrap();
+    assert!(!cgroup_path.exists(), "stop should remove the cgroup");
+    pm.delete(&named("pmr_cgroup_test")).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_idempotent_start_branches() {
+    let (pm, temp_dir) = create_test_process_manager().await;
+    let workdir = temp_dir.path().to_string_lossy().to_string();
+    let idempotent = || StartOptions {
+        working_dir: Some(workdir.clone()),
+        idempotent: true,
+        ..Default::default()
+    };
+    let args = vec!["30".to_string()];
+
+    // Nothing there yet: a plain start
+    let outcome = pm
+        .start_with_options(&named("steady"), "sleep", args.clone(), HashMap::new(), idempotent())
+        .await
+        .unwrap();
+    assert!(!outcome.unchanged && outcome.pid.is_some(), "{:?}", outcome);
+    let first_pid = pm.get_process_status(&named("steady")).await.unwrap().pid;
+    assert_eq!(outcome.pid, first_pid);
+
+    // Running with the same definition: nothing changes
+    let outcome = pm
+        .start_with_options(&named("steady"), "sleep", args.clone(), HashMap::new(), idempotent())
+        .await
+        .unwrap();
+    assert!(outcome.unchanged);
+    assert_eq!(outcome.pid, first_pid);
+    assert_eq!(pm.get_process_status(&named("steady")).await.unwrap().pid, first_pid);
+
+    // Different definition: refused, naming what differs
+    let mut env_vars = HashMap::new();
+    env_vars.insert("MODE".to_string(), "fast".to_string());
+    let err = pm
+        .start_with_options(&named("steady"), "sleep", vec!["60".to_string()], env_vars, idempotent())
+        .await
+        .unwrap_err();
+    match &err {
+        Error::DefinitionMismatch(name, fields) => {
+            assert_eq!(name, "steady");
+            assert_eq!(fields, &vec!["args".to_string(), "env".to_string()]);
+        }
+        other => panic!("expected DefinitionMismatch, got {:?}", other),
+    }
+    assert!(err.to_string().contains("differs in: args, env"));
+
+    // Stopped with the same definition: started again
+    pm.stop(&named("steady")).await.unwrap();
+    let outcome = pm
+        .start_with_options(&named("steady"), "sleep", args.clone(), HashMap::new(), idempotent())
+        .await
+        .unwrap();
+    assert!(!outcome.unchanged && outcome.pid.is_some(), "{:?}", outcome);
+    let status = pm.get_process_status(&named("steady")).await.unwrap();
+    assert_eq!(status.status, ProcessStatus::Running);
+    assert_ne!(status.pid, first_pid);
+
+    // Without the flag an existing process is still an error
+    let options = StartOptions { working_dir: Some(workdir.clone()), ..Default::default() };
+    let err = pm
+        .start_with_options(&named("steady"), "sleep", args, HashMap::new(), options)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, Error::ProcessAlreadyExists(_)));
+
+    pm.delete_with_options(&named("steady"), DeleteOptions { hard: true }).await.unwrap();
+}
+
+/// CPUs a process may run on, from the Cpus_allowed_list line of /proc/<pid>/status
+#[cfg(target_os = "linux")]
+fn cpus_allowed(pid: &str) -> CpuSet {
+    let status = std::fs::read_to_string(format!("/proc/{}/status", pid)).unwrap();
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("Cpus_allowed_list:"))
+        .unwrap()
+        .trim()
+        .parse()
+        .unwrap()
+}
+
+#[cfg(target_os = "linux")]
+#[tokio::test]
+async fn test_cpu_affinity_applied_and_kept_on_restart() {
+    let (pm, _temp_dir) = create_test_process_manager().await;
+
+    // Pin to the first CPU this test itself may use, so restricted cpusets still work
+    let first_cpu = cpus_allowed("self").cpus().next().unwrap();
+    let requested: CpuSet = first_cpu.to_string().parse().unwrap();
+    let options = StartOptions {
+        cpu_affinity: Some(requested.clone()),
+        ..Default::default()
+    };
+    pm.start_with_options(&named("pinned"), "sleep", vec!["30".to_string()], HashMap::new(), options)
+        .await
+        .unwrap();
+
+    let status = pm.get_process_status(&named("pinned")).await.unwrap();
+    assert_eq!(status.cpu_affinity, Some(requested.to_string()));
+    assert_eq!(cpus_allowed(&status.pid.unwrap().to_string()), requested);
+
+    pm.restart(&named("pinned")).await.unwrap();
+    let status = pm.get_process_status(&named("pinned")).await.unwrap();
+    assert_eq!(cpus_allowed(&status.pid.unwrap().to_string()), requested);
+
+    pm.delete_with_options(&named("pinned"), DeleteOptions { hard: true }).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_cpu_affinity_rejects_missing_cpus() {
+    let (pm, _temp_dir) = create_test_process_manager().await;
+    let options = StartOptions {
+        cpu_affinity: Some(format!("0,{}", available_cpus()).parse().unwrap()),
+        ..Default::default()
+    };
+    let err = pm
+        .start_with_options(&named("overpinned"), "sleep", vec!["30".to_string()], HashMap::new(), options)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, Error::InvalidCpuAffinity(_)), "{:?}", err);
+    assert!(matches!(pm.get_process_status(&named("overpinned")).await, Err(Error::ProcessNotFound(_))));
+}
+
+/// The umask of a running process, as the kernel reports it
+#[cfg(target_os = "linux")]
+fn umask_of(pid: &str) -> String {
+    std::fs::read_to_string(format!("/proc/{}/status", pid))
+        .unwrap()
+        .lines()
+        .find_map(|line| line.strip_prefix("Umask:"))
+        .unwrap()
+        .trim()
+        .to_string()
+}
+
+#[cfg(target_os = "linux")]
+#[tokio::test]
+async fn test_umask_applied_and_kept_on_restart() {
+    let (pm, _temp_dir) = create_test_process_manager().await;
+    let options = StartOptions { umask: Some("077".parse().unwrap()), ..Default::default() };
+    pm.start_with_options(&named("private"), "sleep", vec!["30".to_string()], HashMap::new(), options)
+        .await
+        .unwrap();
+
+    let status = pm.get_process_status(&named("private")).await.unwrap();
+    assert_eq!(status.umask.as_deref(), Some("0077"));
+    assert_eq!(umask_of(&status.pid.unwrap().to_string()), "0077");
+
+    pm.restart(&named("private")).await.unwrap();
+    let status = pm.get_process_status(&named("private")).await.unwrap();
+    assert_eq!(umask_of(&status.pid.unwrap().to_string()), "0077");
+
+    // Without one a process keeps the umask of pmr
+    pm.start(&named("inherited"), "sleep", vec!["30".to_string()], HashMap::new(), None, None).await.unwrap();
+    let status = pm.get_process_status(&named("inherited")).await.unwrap();
+    assert_eq!(status.umask, None);
+    assert_eq!(umask_of(&status.pid.unwrap().to_string()), umask_of("self"));
+
+    pm.delete_with_options(&named("private"), DeleteOptions { hard: true }).await.unwrap();
+    pm.delete_with_options(&named("inherited"), DeleteOptions { hard: true }).await.unwrap();
+}
+
+#[cfg(target_os = "linux")]
+#[tokio::test]
+async fn test_start_snapshot_records_environment_and_cwd() {
+    let (pm, temp_dir) = create_test_process_manager().await;
+    let workdir = temp_dir.path().join("work");
+    std::fs::create_dir(&workdir).unwrap();
+
+    let env_vars = HashMap::from([
+        ("APP_MODE".to_string(), "audit".to_string()),
+        ("DB_PASSWORD".to_string(), "hunter2".to_string()),
+    ]);
+    let options = StartOptions {
+        working_dir: Some(workdir.to_string_lossy().to_string()),
+        capture_snapshot: true,
+        ..Default::default()
+    };
+    pm.start_with_options(&named("audited"), "sleep", vec!["30".to_string()], env_vars, options)
+        .await
+        .unwrap();
+
+    let status = pm.get_process_status(&named("audited")).await.unwrap();
+    let snapshot = status.start_snapshot.expect("snapshot recorded");
+    assert_eq!(Some(snapshot.pid), status.pid);
+    assert_eq!(snapshot.cwd.as_deref(), Some(std::fs::canonicalize(&workdir).unwrap().to_str().unwrap()));
+    assert_eq!(snapshot.uid, Some(unsafe { libc::geteuid() }));
+    let environ = snapshot.environ.expect("environ recorded");
+    assert_eq!(environ.get("PMR_PROCESS_NAME").map(String::as_str), Some("audited"));
+    assert_eq!(environ.get("PMR_INSTANCE_ID"), Some(&status.id));
+    assert_eq!(environ.get("APP_MODE").map(String::as_str), Some("audit"));
+    assert_eq!(environ.get("DB_PASSWORD").map(String::as_str), Some(pmr::env::REDACTED));
+
+    // Restarts keep taking snapshots; starts without the option record none
+    pm.restart(&named("audited")).await.unwrap();
+    let status = pm.get_process_status(&named("audited")).await.unwrap();
+    assert_eq!(status.start_snapshot.map(|snapshot| snapshot.pid), status.pid);
+    pm.start(&named("plain"), "sleep", vec!["30".to_string()], HashMap::new(), None, None).await.unwrap();
+    assert!(pm.get_process_status(&named("plain")).await.unwrap().start_snapshot.is_none());
+
+    for name in ["audited", "plain"] {
+        pm.delete(&named(name)).await.unwrap();
+    }
+}
+
+#[tokio::test]
+async fn test_start_snapshot_of_quick_exit_does_not_fail_start() {
+    let (pm, _temp_dir) = create_test_process_manager().await;
+    let options = StartOptions { capture_snapshot: true, ..Default::default() };
+    pm.start_with_options(&named("blink"), "true", vec![], HashMap::new(), options)
+        .await
+        .unwrap();
+
+    let status = pm.get_process_status(&named("blink")).await.unwrap();
+    assert_eq!(status.start_snapshot.map(|snapshot| snapshot.pid), status.pid);
+}
+
+#[tokio::test]
+async fn test_restart_uses_stored_log_dir() {
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("test.db");
+    let custom_log_dir = temp_dir.path().join("custom_logs");
+
+    let config = Config::new()
+        .with_database_path(db_path.clone())
+        .with_log_dir(temp_dir.path().join("logs"));
+    let pm = ProcessManager::new(config).await.unwrap();
+
+    pm.start(&named("default_dir"), "echo", vec!["hi".to_string()], HashMap::new(), None, None)
+        .await
+        .unwrap();
+    pm.start(
+        &named("custom_dir"),
+        "echo",
+        vec!["hi".to_string()],
+        HashMap::new(),
+        None,
+        Some(custom_log_dir.to_string_lossy().to_string()),
+    )
+    .await
+    .unwrap();
+    drop(pm);
+
+    // The default log directory moves; only processes without an explicit log_dir follow it
+    let moved_log_dir = temp_dir.path().join("moved_logs");
+    let config = Config::new()
+        .with_database_path(db_path)
+        .with_log_dir(moved_log_dir.clone());
+    let pm = ProcessManager::new(config).await.unwrap();
+
+    pm.restart(&named("default_dir")).await.unwrap();
+    pm.restart(&named("custom_dir")).await.unwrap();
+
+    let default_status = pm.get_process_status(&named("default_dir")).await.unwrap();
+    assert_eq!(default_status.log_dir, None);
+    assert_eq!(default_status.log_path, moved_log_dir.join("default_dir.log").to_string_lossy());
+
+    let custom_status = pm.get_process_status(&named("custom_dir")).await.unwrap();
+    assert_eq!(custom_status.log_dir.as_deref(), Some(custom_log_dir.to_string_lossy().as_ref()));
+    assert_eq!(custom_status.log_path, custom_log_dir.join("custom_dir.log").to_string_lossy());
+
+    pm.delete(&named("default_dir")).await.unwrap();
+    pm.delete(&named("custom_dir")).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_restart_with_reset_logs() {
+    let (pm, temp_dir) = create_test_process_manager().await;
+
+    pm.start(&named("reset_logs"), "sleep", vec!["5".to_string()], HashMap::new(), None, None)
+        .await
+        .unwrap();
+
+    let log_path = temp_dir.path().join("logs").join("reset_logs.log");
+    std::fs::write(&log_path, "output from the previous run\n").unwrap();
+
+    pm.restart_with_options(&named("reset_logs"), RestartOptions { reset_logs: true, ..Default::default() })
+        .await
+        .unwrap();
+
+    // The live log starts empty and the previous run is preserved in the first archive
+    assert_eq!(std::fs::metadata(&log_path).unwrap().len(), 0);
+    let archived = std::fs::read_to_string(temp_dir.path().join("logs").join("reset_logs.1.log")).unwrap();
+    assert!(archived.contains("output from the previous run"));
+
+    pm.delete(&named("reset_logs")).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_stop_reports_timeout_for_sigterm_trapping_process() {
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("test.db");
+    let config = Config::new()
+        .with_database_path(db_path.clone())
+        .with_log_dir(temp_dir.path().join("logs"))
+        .with_stop_grace_period(Duration::from_millis(500));
+    let pm = ProcessManager::new(config.clone()).await.unwrap();
+
+    let args = vec!["-c".to_string(), "trap '' TERM; sleep 30".to_string()];
+    pm.start(&named("stubborn"), "sh", args, HashMap::new(), None, None)
+        .await
+        .unwrap();
+
+    // Tracked child: SIGTERM is ignored, so stop must not claim success
+    let result = pm.stop(&named("stubborn")).await;
+    assert!(matches!(result, Err(Error::StopTimedOut(ref name, _)) if name == "stubborn"));
+    let status = pm.get_process_status(&named("stubborn")).await.unwrap();
+    assert_eq!(status.status, ProcessStatus::Running);
+
+    // Untracked (another manager instance): the signal fallback must also verify liveness
+    let other_pm = ProcessManager::new(config).await.unwrap();
+    let result = other_pm.stop(&named("stubborn")).await;
+    assert!(matches!(result, Err(Error::StopTimedOut(_, _))));
+    assert!(result.unwrap_err().to_string().contains("--signal KILL"));
+
+    // Escalation finishes the job
+    let options = StopOptions { escalate: true, ..Default::default() };
+    pm.stop_with_options(&named("stubborn"), options).await.unwrap();
+    let status = pm.get_process_status(&named("stubborn")).await.unwrap();
+    assert_eq!(status.status, ProcessStatus::Stopped);
+
+    pm.delete(&named("stubborn")).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_stored_stop_signal_is_sent_first() {
+    let temp_dir = TempDir::new().unwrap();
+    let config = Config::new()
+        .with_database_path(temp_dir.path().join("test.db"))
+        .with_log_dir(temp_dir.path().join("logs"))
+        .with_stop_grace_period(Duration::from_millis(500));
+    let pm = ProcessManager::new(config).await.unwrap();
+
+    // Shuts down cleanly on SIGINT only, like a JVM or Node app with its own handler
+    let args = vec!["-c".to_string(), "trap 'exit 0' INT; trap '' TERM; while :; do sleep 0.1; done".to_string()];
+    let options = StartOptions { stop_signal: Some(libc::SIGINT), ..Default::default() };
+    pm.start_with_options(&named("graceful"), "sh", args, HashMap::new(), options).await.unwrap();
+    assert_eq!(pm.get_process_status(&named("graceful")).await.unwrap().stop_signal, libc::SIGINT);
+
+    // A signal named by the stop itself takes precedence over the stored one
+    let options = StopOptions { signal: Some(libc::SIGTERM), ..Default::default() };
+    assert!(matches!(pm.stop_with_options(&named("graceful"), options).await, Err(Error::StopTimedOut(_, _))));
+
+    // A restart keeps the stop signal, and a plain stop uses it
+    pm.restart(&named("graceful")).await.unwrap();
+    let outcome = pm.stop(&named("graceful")).await.unwrap();
+    assert!(!outcome.escalated);
+    let status = pm.get_process_status(&named("graceful")).await.unwrap();
+    assert_eq!(status.status, ProcessStatus::Stopped);
+    assert_eq!(status.stop_signal, libc::SIGINT);
+
+    // Without one, processes are still stopped with SIGTERM
+    pm.start(&named("plain"), "sleep", vec!["30".to_string()], HashMap::new(), None, None).await.unwrap();
+    assert_eq!(pm.get_process_status(&named("plain")).await.unwrap().stop_signal, libc::SIGTERM);
+    pm.stop(&named("plain")).await.unwrap();
+
+    pm.delete(&named("graceful")).await.unwrap();
+    pm.delete(&named("plain")).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_workdir_log_dir_mode_precedence() {
+    let temp_dir = TempDir::new().unwrap();
+    let project = temp_dir.path().join("project");
+    std::fs::create_dir(&project).unwrap();
+    let config = Config::new()
+        .with_database_path(temp_dir.path().join("test.db"))
+        .with_log_dir(temp_dir.path().join("logs"));
+    let pm = ProcessManager::new(config).await.unwrap();
+    let sleep_args = || vec!["30".to_string()];
+    let workdir = |log_dir: Option<&str>| StartOptions {
+        working_dir: Some(project.to_string_lossy().to_string()),
+        log_dir: log_dir.map(str::to_string),
+        log_dir_mode: Some(LogDirMode::Workdir),
+        ..Default::default()
+    };
+
+    // Workdir mode puts the log in <working_dir>/logs and records it
+    pm.start_with_options(&named("in-project"), "sleep", sleep_args(), HashMap::new(), workdir(None)).await.unwrap();
+    let status = pm.get_process_status(&named("in-project")).await.unwrap();
+    assert_eq!(std::path::PathBuf::from(&status.log_path), project.join("logs").join("in-project.log"));
+    assert_eq!(status.log_dir_mode, LogDirMode::Workdir);
+
+    // An explicit log directory wins
+    let explicit = temp_dir.path().join("explicit");
+    pm.start_with_options(&named("explicit"), "sleep", sleep_args(), HashMap::new(), workdir(Some(explicit.to_str().unwrap()))).await.unwrap();
+    let status = pm.get_process_status(&named("explicit")).await.unwrap();
+    assert_eq!(std::path::PathBuf::from(&status.log_path), explicit.join("explicit.log"));
+    assert_eq!(status.log_dir_mode, LogDirMode::Default);
+
+    // Without a working directory, workdir mode behaves like the default
+    let options = StartOptions { log_dir_mode: Some(LogDirMode::Workdir), ..Default::default() };
+    pm.start_with_options(&named("no-workdir"), "sleep", sleep_args(), HashMap::new(), options).await.unwrap();
+    let status = pm.get_process_status(&named("no-workdir")).await.unwrap();
+    assert_eq!(std::path::PathBuf::from(&status.log_path), temp_dir.path().join("logs").join("no-workdir.log"));
+    assert_eq!(status.log_dir_mode, LogDirMode::Default);
+
+    for name in ["in-project", "explicit", "no-workdir"] {
+        pm.delete_with_options(&named(name), DeleteOptions { hard: true }).await.unwrap();
+    }
+
+    // The configured mode applies when a start does not choose one
+    let config = Config::new()
+        .with_database_path(temp_dir.path().join("test.db"))
+        .with_log_dir(temp_dir.path().join("logs"))
+        .with_log_dir_mode(LogDirMode::Workdir);
+    let pm = ProcessManager::new(config).await.unwrap();
+    let options = StartOptions { working_dir: Some(project.to_string_lossy().to_string()), ..Default::default() };
+    pm.start_with_options(&named("configured"), "sleep", sleep_args(), HashMap::new(), options).await.unwrap();
+    let status = pm.get_process_status(&named("configured")).await.unwrap();
+    assert_eq!(std::path::PathBuf::from(&status.log_path), project.join("logs").join("configured.log"));
+    let options = StartOptions {
+        working_dir: Some(project.to_string_lossy().to_string()),
+        log_dir_mode: Some(LogDirMode::Default),
+        ..Default::default()
+    };
+    pm.start_with_options(&named("opted-out"), "sleep", sleep_args(), HashMap::new(), options).await.unwrap();
+    let status = pm.get_process_status(&named("opted-out")).await.unwrap();
+    assert_eq!(std::path::PathBuf::from(&status.log_path), temp_dir.path().join("logs").join("opted-out.log"));
+
+    pm.delete(&named("configured")).await.unwrap();
+    pm.delete(&named("opted-out")).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_update_workdir_then_restart_relocates_log() {
+    let temp_dir = TempDir::new().unwrap();
+    let old_project = temp_dir.path().join("old");
+    let new_project = temp_dir.path().join("new");
+    std::fs::create_dir(&old_project).unwrap();
+    std::fs::create_dir(&new_project).unwrap();
+    let config = Config::new()
+        .with_database_path(temp_dir.path().join("test.db"))
+        .with_log_dir(temp_dir.path().join("logs"));
+    let pm = ProcessManager::new(config).await.unwrap();
+
+    let options = StartOptions {
+        working_dir: Some(old_project.to_string_lossy().to_string()),
+        log_dir_mode: Some(LogDirMode::Workdir),
+        ..Default::default()
+    };
+    let args = vec!["-c".to_string(), "pwd; sleep 30".to_string()];
+    pm.start_with_options(&named("svc"), "sh", args, HashMap::new(), options).await.unwrap();
+    let old_log = old_project.join("logs").join("svc.log");
+
+    assert!(matches!(pm.update_working_dir(&named("svc"), "/does/not/exist").await, Err(Error::Other(_))));
+    assert!(matches!(pm.update_working_dir(&named("missing"), new_project.to_str().unwrap()).await, Err(Error::ProcessNotFound(_))));
+
+    // The update only takes effect on the next start
+    let updated = pm.update_working_dir(&named("svc"), new_project.to_str().unwrap()).await.unwrap();
+    assert_eq!(updated.working_dir, new_project.to_string_lossy());
+    assert_eq!(std::path::PathBuf::from(&updated.log_path), old_log);
+
+    pm.restart(&named("svc")).await.unwrap();
+    sleep(Duration::from_millis(300)).await;
+    let new_log = new_project.join("logs").join("svc.log");
+    let status = pm.get_process_status(&named("svc")).await.unwrap();
+    assert_eq!(status.status, ProcessStatus::Running);
+    assert_eq!(status.log_dir_mode, LogDirMode::Workdir);
+    assert_eq!(std::path::PathBuf::from(&status.log_path), new_log);
+    assert!(std::fs::read_to_string(&new_log).unwrap().contains(new_project.to_str().unwrap()));
+
+    // A hard delete removes the current log and leaves the old project's alone
+    pm.delete_with_options(&named("svc"), DeleteOptions { hard: true }).await.unwrap();
+    assert!(!new_log.exists());
+    assert!(old_log.exists());
+}
+
+#[tokio::test]
+async fn test_description_is_kept_across_restart_and_updated() {
+    let temp_dir = TempDir::new().unwrap();
+    let config = Config::new()
+        .with_database_path(temp_dir.path().join("test.db"))
+        .with_log_dir(temp_dir.path().join("logs"));
+    let pm = ProcessManager::new(config).await.unwrap();
+
+    let too_long = StartOptions { description: Some("x".repeat(257)), ..Default::default() };
+    let result = pm.start_with_options(&named("svc"), "sleep", vec!["30".to_string()], HashMap::new(), too_long).await;
+    assert!(matches!(result, Err(Error::InvalidDescription(_))), "{:?}", result);
+    assert!(pm.list_processes().await.unwrap().is_empty());
+
+    let options = StartOptions { description: Some("  Billing webhook receiver ".to_string()), ..Default::default() };
+    pm.start_with_options(&named("svc"), "sleep", vec!["30".to_string()], HashMap::new(), options).await.unwrap();
+    let status = pm.get_process_status(&named("svc")).await.unwrap();
+    assert_eq!(status.description.as_deref(), Some("Billing webhook receiver"));
+
+    pm.restart(&named("svc")).await.unwrap();
+    let status = pm.get_process_status(&named("svc")).await.unwrap();
+    assert_eq!(status.description.as_deref(), Some("Billing webhook receiver"));
+    let found = pm.search_processes("WEBHOOK", StatusRefresh::Stored).await.unwrap();
+    assert_eq!(found.len(), 1);
+
+    assert!(matches!(pm.update_description(&named("svc"), "line\nbreak").await, Err(Error::InvalidDescription(_))));
+    assert!(matches!(pm.update_description(&named("missing"), "text").await, Err(Error::ProcessNotFound(_))));
+    let updated = pm.update_description(&named("svc"), "Billing v2").await.unwrap();
+    assert_eq!(updated.description.as_deref(), Some("Billing v2"));
+    let cleared = pm.update_description(&named("svc"), "   ").await.unwrap();
+    assert!(cleared.description.is_none());
+    assert!(pm.search_processes("billing", StatusRefresh::Stored).await.unwrap().is_empty());
+
+    pm.delete(&named("svc")).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_config_env_defaults_are_layered_and_recorded() {
+    let temp_dir = TempDir::new().unwrap();
+    let config_path = temp_dir.path().join("config.toml");
+    std::fs::write(
+        &config_path,
+        "[env]\nTZ = \"UTC\"\nHTTP_PROXY = \"http://proxy:3128\"\n\n[env_overrides.\"web-*\"]\nPORT = \"8080\"\n",
+    )
+    .unwrap();
+    let config = || {
+        Config::new()
+            .with_database_path(temp_dir.path().join("test.db"))
+            .with_log_dir(temp_dir.path().join("logs"))
+            .with_file(&config_path)
+            .unwrap()
+    };
+    let pm = ProcessManager::new(config()).await.unwrap();
+
+    let explicit: HashMap<String, String> = [("TZ".to_string(), "Asia/Shanghai".to_string())].into_iter().collect();
+    pm.start(&named("web-api"), "sleep", vec!["30".to_string()], explicit.clone(), None, None)
+        .await
+        .unwrap();
+
+    let status = pm.get_process_status(&named("web-api")).await.unwrap();
+    assert_eq!(status.env_vars.get("TZ").map(String::as_str), Some("Asia/Shanghai"));
+    assert_eq!(status.env_vars.get("PORT").map(String::as_str), Some("8080"));
+    assert_eq!(status.default_env_keys, ["HTTP_PROXY", "PORT"]);
+
+    // Same explicit values: the defaults are part of the definition, not a difference
+    let options = StartOptions { idempotent: true, ..Default::default() };
+    let outcome = pm
+        .start_with_options(&named("web-api"), "sleep", vec!["30".to_string()], explicit, options)
+        .await
+        .unwrap();
+    assert!(outcome.unchanged, "{:?}", outcome);
+
+    // A restart picks up changed defaults and keeps the explicit value
+    std::fs::write(&config_path, "[env]\nTZ = \"UTC\"\nHTTP_PROXY = \"http://other:3128\"\n").unwrap();
+    let pm = ProcessManager::new(config()).await.unwrap();
+    pm.restart(&named("web-api")).await.unwrap();
+    let status = pm.get_process_status(&named("web-api")).await.unwrap();
+    assert_eq!(status.env_vars.get("TZ").map(String::as_str), Some("Asia/Shanghai"));
+    assert_eq!(status.env_vars.get("HTTP_PROXY").map(String::as_str), Some("http://other:3128"));
+    assert!(!status.env_vars.contains_key("PORT"));
+    assert_eq!(status.default_env_keys, ["HTTP_PROXY"]);
+
+    pm.delete(&named("web-api")).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_stop_after_natural_exit_reports_not_running() {
+    let (pm, _temp_dir) = create_test_process_manager().await;
+
+    // Outlives the start window, then exits on its own
+    let args = vec!["-c".to_string(), "sleep 0.4; exit 3".to_string()];
+    pm.start(&named("brief"), "sh", args, HashMap::new(), None, None)
+        .await
+        .unwrap();
+    sleep(Duration::from_millis(700)).await;
+
+    let outcome = pm.stop(&named("brief")).await.unwrap();
+    assert_eq!(outcome.pid, None);
+    let not_running = outcome.not_running.expect("the process had already exited");
+    assert_eq!(not_running.exit_code, Some(3));
+    assert!(not_running.since.is_some());
+    let status = pm.get_process_status(&named("brief")).await.unwrap();
+    assert_eq!(status.status, ProcessStatus::Stopped);
+    assert_eq!(status.exit_code, Some(3));
+
+    pm.delete(&named("brief")).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_stop_does_not_signal_recycled_pid() {
+    let (pm, temp_dir) = create_test_process_manager().await;
+
+    pm.start(&named("recycled"), "true", vec![], HashMap::new(), None, None)
+        .await
+        .unwrap();
+
+    // Point the record at an unrelated process that started long after the record was written
+    let mut bystander = std::process::Command::new("sleep").arg("30").spawn().unwrap();
+    let database_url = format!("sqlite:{}", temp_dir.path().join("test.db").display());
+    let db = Database::new(&database_url).await.unwrap();
+    let mut record = db.get_process_by_name(&named("recycled")).await.unwrap().unwrap();
+    db.delete_process(&named("recycled")).await.unwrap();
+    record.pid = Some(bystander.id());
+    record.status = ProcessStatus::Running;
+    record.created_at = chrono::Utc::now() - chrono::Duration::hours(1);
+    db.insert_process(&record).await.unwrap();
+
+    let outcome = pm.stop(&named("recycled")).await.unwrap();
+    assert!(outcome.not_running.is_some(), "{:?}", outcome);
+    assert!(bystander.try_wait().unwrap().is_none(), "the unrelated process was signalled");
+    assert_eq!(pm.get_process_status(&named("recycled")).await.unwrap().status, ProcessStatus::Stopped);
+
+    bystander.kill().unwrap();
+    bystander.wait().unwrap();
+    pm.delete(&named("recycled")).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_zombie_is_stopped_and_reported() {
+    use pmr::process::ZOMBIE_EXIT_REASON;
+    use std::io::BufRead;
+
+    let (pm, temp_dir) = create_test_process_manager().await;
+    pm.start(&named("zombie"), "true", vec![], HashMap::new(), None, None)
+        .await
+        .unwrap();
+
+    // `sleep 30` takes over the shell's PID and never waits for the backgrounded child,
+    // which exits only after the shell is gone so the shell cannot reap it either
+    let mut parent = std::process::Command::new("sh")
+        .args(["-c", "sleep 0.2 & echo $!; exec sleep 30"])
+        .stdout(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut line = String::new();
+    std::io::BufReader::new(parent.stdout.take().unwrap()).read_line(&mut line).unwrap();
+    let zombie: u32 = line.trim().parse().unwrap();
+    let stat = || std::fs::read_to_string(format!("/proc/{}/stat", zombie)).unwrap_or_default();
+    for _ in 0..100 {
+        if stat().rsplit_once(')').is_some_and(|(_, rest)| rest.trim_start().starts_with('Z')) {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+
+    let database_url = format!("sqlite:{}", temp_dir.path().join("test.db").display());
+    let db = Database::new(&database_url).await.unwrap();
+    let mut record = db.get_process_by_name(&named("zombie")).await.unwrap().unwrap();
+    db.delete_process(&named("zombie")).await.unwrap();
+    record.pid = Some(zombie);
+    record.status = ProcessStatus::Running;
+    record.exit_code = None;
+    record.exit_reason = None;
+    record.created_at = chrono::Utc::now();
+    db.insert_process(&record).await.unwrap();
+
+    assert_eq!(pm.get_process_status(&named("zombie")).await.unwrap().status, ProcessStatus::Stopped);
+    let record = db.get_process_by_name(&named("zombie")).await.unwrap().unwrap();
+    assert_eq!(record.exit_code, None);
+    assert_eq!(record.exit_reason.as_deref(), Some(ZOMBIE_EXIT_REASON));
+    let zombies = pm.zombie_processes().await.unwrap();
+    assert_eq!(zombies.len(), 1, "{:?}", zombies);
+    assert_eq!((zombies[0].pid, zombies[0].parent_pid, zombies[0].reparented), (zombie, parent.id(), false));
+
+    // Once its parent is gone the zombie belongs to init, if it is not reaped at once
+    parent.kill().unwrap();
+    parent.wait().unwrap();
+    assert!(pm.zombie_processes().await.unwrap().iter().all(|zombie| zombie.reparented));
+
+    pm.delete(&named("zombie")).await.unwrap();
+}
+
+#[test]
+fn test_parse_signal() {
+    assert_eq!(parse_signal("TERM").unwrap(), libc::SIGTERM);
+    assert_eq!(parse_signal("sigint").unwrap(), libc::SIGINT);
+    assert_eq!(parse_signal("9").unwrap(), libc::SIGKILL);
+    assert!(parse_signal("BOGUS").is_err());
+    assert!(parse_signal("0").is_err());
+    assert_eq!(signal_name(libc::SIGQUIT), "SIGQUIT");
+    assert_eq!(signal_name(parse_signal("SIGUSR2").unwrap()), "SIGUSR2");
+    assert_eq!(signal_name(28), "28");
+}
+
+#[tokio::test]
+async fn test_injected_env_vars_take_precedence() {
+    let (pm, _temp_dir) = create_test_process_manager().await;
+
+    let mut env_vars = HashMap::new();
+    env_vars.insert("PMR_PROCESS_NAME".to_string(), "spoofed".to_string());
+    env_vars.insert("APP_MODE".to_string(), "test".to_string());
+    let args = vec!["-c".to_string(), "echo name=$PMR_PROCESS_NAME mode=$APP_MODE".to_string()];
+
+    pm.start(&named("env_precedence"), "sh", args, env_vars, None, None)
+        .await
+        .unwrap();
+    sleep(Duration::from_millis(300)).await;
+
+    let logs = pm.get_process_logs(&named("env_precedence"), None).await.unwrap();
+    assert!(logs.contains("name=env_precedence mode=test"), "unexpected logs: {}", logs);
+
+    pm.delete(&named("env_precedence")).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_invalid_env_vars_rejected_before_start() {
+    let temp_dir = TempDir::new().unwrap();
+    let config = Config::new()
+        .with_database_path(temp_dir.path().join("test.db"))
+        .with_log_dir(temp_dir.path().join("logs"))
+        .with_reserved_env_policy(ReservedEnvPolicy::Reject);
+    let pm = ProcessManager::new(config).await.unwrap();
+
+    let mut env_vars = HashMap::new();
+    env_vars.insert("1BAD".to_string(), "x".to_string());
+    env_vars.insert("PMR_INSTANCE_ID".to_string(), "x".to_string());
+
+    let result = pm.start(&named("bad_env"), "echo", vec![], env_vars, None, None).await;
+    match result {
+        Err(Error::InvalidEnvVars(errors)) => assert_eq!(errors.len(), 2),
+        other => panic!("expected InvalidEnvVars, got {:?}", other),
+    }
+    assert!(matches!(pm.get_process_status(&named("bad_env")).await, Err(Error::ProcessNotFound(_))));
+}
+
+#[cfg(feature = "cli")]
+#[test]
+fn test_parse_env_vars_reports_all_problems() {
+    let parsed = Commands::parse_env_vars(vec!["A=1".to_string(), "B=x=y".to_string()], false).unwrap();
+    assert_eq!(parsed.get("B").map(String::as_str), Some("x=y"));
+
+    let errors = Commands::parse_env_vars(vec![
+        "NOVALUE".to_string(),
+        "=empty".to_string(),
+        "2X=1".to_string(),
+        "GOOD=1".to_string(),
+    ], false)
+    .unwrap_err();
+    let problems: Vec<EnvVarProblem> = errors.into_iter().map(|e| e.problem).collect();
+    assert_eq!(
+        problems,
+        vec![EnvVarProblem::MissingSeparator, EnvVarProblem::EmptyKey, EnvVarProblem::LeadingDigit]
+    );
+}
+
+#[cfg(feature = "cli")]
+#[test]
+fn test_duplicate_env_keys_are_refused_within_one_source() {
+    let env = |pairs: &[&str]| pairs.iter().map(|pair| pair.to_string()).collect::<Vec<_>>();
+
+    // Within the -e flags: every repeat is reported with the value it would have replaced
+    let errors = Commands::parse_env_vars(env(&["DATABASE_URL=postgres://a", "PORT=1", "DATABASE_URL=postgres://b", "DATABASE_URL=postgres://c"]), false)
+        .unwrap_err();
+    let problems: Vec<(String, EnvVarProblem)> = errors.iter().map(|e| (e.key.clone(), e.problem.clone())).collect();
+    let duplicate = |first: &str, second: &str| EnvVarProblem::Duplicate { first: first.to_string(), second: second.to_string() };
+    assert_eq!(
+        problems,
+        vec![
+            ("DATABASE_URL".to_string(), duplicate("postgres://a", "postgres://b")),
+            ("DATABASE_URL".to_string(), duplicate("postgres://a", "postgres://c")),
+        ]
+    );
+    assert_eq!(
+        errors[0].to_string(),
+        "key 'DATABASE_URL' is given more than once ('postgres://a' and 'postgres://b')"
+    );
+
+    // The escape hatch keeps the last value
+    let parsed = Commands::parse_env_vars(env(&["DATABASE_URL=postgres://a", "DATABASE_URL=postgres://b"]), true).unwrap();
+    assert_eq!(parsed.get("DATABASE_URL").map(String::as_str), Some("postgres://b"));
+
+    // Within the config file the TOML parser already refuses a repeated key
+    let dir = TempDir::new().unwrap();
+    let config_path = dir.path().join("config.toml");
+    std::fs::write(&config_path, "[env]\nTZ = \"UTC\"\nTZ = \"CET\"\n").unwrap();
+    assert!(Config::new().with_file(&config_path).is_err());
+
+    // Across sources -e still overrides [env], as documented
+    std::fs::write(&config_path, "[env]\nTZ = \"UTC\"\n").unwrap();
+    let config = Config::new().with_file(&config_path).unwrap();
+    let explicit = Commands::parse_env_vars(env(&["TZ=CET"]), false).unwrap();
+    let layered = pmr::env::layer_env(&config.env_defaults, "web", explicit);
+    assert_eq!(layered.vars.get("TZ").map(String::as_str), Some("CET"));
+    assert!(layered.default_keys.is_empty());
+}
+
+#[tokio::test]
+async fn test_archive_process_logs_to_file() {
+    let (pm, temp_dir) = create_test_process_manager().await;
+
+    pm.start(&named("bundle"), "echo", vec!["bundled line".to_string()], HashMap::new(), None, None)
+        .await
+        .unwrap();
+    sleep(Duration::from_millis(200)).await;
+
+    let archive_path = temp_dir.path().join("bundle.tar");
+    let message = pm.archive_process_logs(&named("bundle"), &archive_path).await.unwrap();
+    assert!(message.contains("bundle.tar"));
+
+    let bytes = std::fs::read(&archive_path).unwrap();
+    let text = String::from_utf8_lossy(&bytes);
+    assert!(text.contains("bundle/bundle.log"));
+    assert!(text.contains("bundled line"));
+    assert!(text.contains("included bundle/bundle.log"));
+
+    assert!(pm.archive_process_logs(&named("missing"), &archive_path).await.is_err());
+}
+
+#[tokio::test]
+async fn test_merged_logs_span_rotations() {
+    let (pm, _temp_dir) = create_test_process_manager().await;
+
+    pm.start(&named("stitched"), "true", vec![], HashMap::new(), None, None)
+        .await
+        .unwrap();
+    sleep(Duration::from_millis(200)).await;
+    let log_path = pm.get_process_status(&named("stitched")).await.unwrap().log_path;
+
+    // Rotate twice so the lines live in .2.log, .1.log and the live log
+    std::fs::write(&log_path, "1\n2\n3\n").unwrap();
+    pm.rotate_process_logs(&named("stitched")).await.unwrap();
+    std::fs::write(&log_path, "4\n5\n6\n").unwrap();
+    pm.rotate_process_logs(&named("stitched")).await.unwrap();
+    std::fs::write(&log_path, "7\n8\n9\n").unwrap();
+
+    let merged = pm.get_merged_process_logs(&named("stitched"), Some(5)).await.unwrap();
+    assert_eq!(merged, "5\n6\n7\n8\n9");
+
+    let all = pm.get_merged_process_logs(&named("stitched"), None).await.unwrap();
+    assert_eq!(all, "1\n2\n3\n4\n5\n6\n7\n8\n9");
+
+    // Without merging only the live log is read
+    assert_eq!(pm.get_process_logs(&named("stitched"), Some(5)).await.unwrap(), "7\n8\n9");
+    assert_eq!(pm.get_merged_process_logs(&named("stitched"), Some(0)).await.unwrap(), "");
+}
+
+#[tokio::test]
+async fn test_collapse_repeated_log_lines() {
+    let (pm, _temp_dir) = create_test_process_manager().await;
+
+    pm.start(&named("looping"), "true", vec![], HashMap::new(), None, None)
+        .await
+        .unwrap();
+    sleep(Duration::from_millis(200)).await;
+    let log_path = pm.get_process_status(&named("looping")).await.unwrap().log_path;
+
+    // The run of "crash" lines straddles a rotation
+    std::fs::write(&log_path, "boot\ncrash\ncrash\n").unwrap();
+    pm.rotate_process_logs(&named("looping")).await.unwrap();
+    std::fs::write(&log_path, "crash\ncrash\nboot\n").unwrap();
+
+    let collapse = |lines| LogOptions { lines, collapse_repeats: true, ..Default::default() };
+    assert_eq!(
+        pm.get_merged_process_logs_with_options(&named("looping"), &collapse(None)).await.unwrap(),
+        "boot\ncrash  [repeated 4 times]\nboot"
+    );
+    assert_eq!(
+        pm.get_merged_process_logs_with_options(&named("looping"), &collapse(Some(2))).await.unwrap(),
+        "crash  [repeated 4 times]\nboot"
+    );
+    assert_eq!(
+        pm.get_process_logs_with_options(&named("looping"), &collapse(Some(5))).await.unwrap(),
+        "crash  [repeated 2 times]\nboot"
+    );
+    // The file and the plain read keep every line
+    assert_eq!(pm.get_process_logs(&named("looping"), None).await.unwrap(), "crash\ncrash\nboot\n");
+}
+
+#[tokio::test]
+async fn test_start_appends_both_streams_to_existing_log() {
+    let (pm, temp_dir) = create_test_process_manager().await;
+    let log_dir = temp_dir.path().join("logs");
+    std::fs::create_dir_all(&log_dir).unwrap();
+    std::fs::write(log_dir.join("chatty.log"), "previous run\n").unwrap();
+
+    // Both streams write in small, interleaved pieces
+    let script = "for i in $(seq 1 200); do echo \"out line $i\"; echo \"err line $i\" >&2; done";
+    pm.start(&named("chatty"), "sh", vec!["-c".to_string(), script.to_string()], HashMap::new(), None, None)
+        .await
+        .unwrap();
+    for _ in 0..50 {
+        if pm.get_process_status(&named("chatty")).await.unwrap().status != ProcessStatus::Running {
+            break;
+        }
+        sleep(Duration::from_millis(100)).await;
+    }
+
+    let content = std::fs::read_to_string(log_dir.join("chatty.log")).unwrap();
+    let lines: Vec<&str> = content.lines().collect();
+    assert_eq!(lines.first(), Some(&"previous run"), "start truncated the existing log");
+    assert_eq!(lines.len(), 401, "lost or merged lines:\n{}", content);
+    for i in 1..=200 {
+        for stream in ["out", "err"] {
+            let line = format!("{} line {}", stream, i);
+            assert!(lines.contains(&line.as_str()), "missing {:?}", line);
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_wait_for_running_processes() {
+    let (pm, temp_dir) = create_test_process_manager().await;
+
+    pm.start(&named("waiter"), "sleep", vec!["30".to_string()], HashMap::new(), None, None)
+        .await
+        .unwrap();
+    pm.start(&named("quitter"), "sleep", vec!["30".to_string()], HashMap::new(), None, None)
+        .await
+        .unwrap();
+    sleep(Duration::from_millis(300)).await;
+    // A failed process never becomes ready on its own
+    let quitter_pid = pm.get_process_status(&named("quitter")).await.unwrap().pid;
+    let database_url = format!("sqlite:{}", temp_dir.path().join("test.db").display());
+    Database::new(&database_url)
+        .await
+        .unwrap()
+        .update_process_status(&named("quitter"), ProcessStatus::Failed, quitter_pid, chrono::Utc::now())
+        .await
+        .unwrap();
+
+    let names = vec![named("waiter"), named("quitter")];
+    let mut seen = Vec::new();
+    let started = std::time::Instant::now();
+    let report = pm
+        .wait_for_status(&names, ProcessStatus::Running, Duration::from_secs(10), |process| {
+            seen.push(process.name.clone())
+        })
+        .await
+        .unwrap();
+
+    // The failed process is reported straight away instead of waiting for the timeout
+    assert!(started.elapsed() < Duration::from_secs(5));
+    assert_eq!(seen, vec!["waiter"]);
+    assert_eq!(report.ready, vec!["waiter"]);
+    assert!(!report.all_ready());
+    assert_eq!(report.not_ready.len(), 1);
+    assert_eq!(report.not_ready[0].name, "quitter");
+
+    let missing = pm
+        .wait_for_status(&[named("missing")], ProcessStatus::Running, Duration::from_secs(1), |_| {})
+        .await;
+    assert!(matches!(missing, Err(Error::ProcessNotFound(_))));
+
+    // Waiting leaves the processes alone
+    assert_eq!(pm.get_process_status(&named("waiter")).await.unwrap().status, ProcessStatus::Running);
+    pm.stop(&named("waiter")).await.unwrap();
+    pm.stop(&named("quitter")).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_drift_detects_swapped_binary() {
+    let (pm, temp_dir) = create_test_process_manager().await;
+
+    // Run a private copy of sleep so it can be swapped without touching the system binary
+    let sleep_path = pmr::drift::resolve_executable("sleep", temp_dir.path(), None).unwrap();
+    let app_dir = temp_dir.path().join("app");
+    std::fs::create_dir(&app_dir).unwrap();
+    let binary = app_dir.join("fake-server");
+    std::fs::copy(&sleep_path, &binary).unwrap();
+
+    let options = StartOptions {
+        working_dir: Some(app_dir.to_string_lossy().to_string()),
+        ..Default::default()
+    };
+    pm.start_with_options(&named("drifty"), "./fake-server", vec!["30".to_string()], HashMap::new(), options)
+        .await
+        .unwrap();
+    sleep(Duration::from_millis(200)).await;
+
+    let process = pm.get_process_status(&named("drifty")).await.unwrap();
+    assert_eq!(process.binary_path.as_deref(), Some(std::fs::canonicalize(&binary).unwrap().to_str().unwrap()));
+    assert_eq!(process.binary_sha256.as_ref().map(|h| h.len()), Some(64));
+    let reports = pm.check_drift(Some(&named("drifty"))).await.unwrap();
+    assert!(reports[0].fingerprinted);
+    assert!(!reports[0].has_drift(), "unexpected drift: {:?}", reports[0].issues);
+
+    let outcome = pm
+        .restart_with_options(&named("drifty"), RestartOptions { only_if_changed: true, ..Default::default() })
+        .await
+        .unwrap();
+    assert_eq!(outcome.skipped, Some(RestartSkip::BinaryUnchanged));
+    assert!(outcome.stop.is_none() && outcome.start.is_none());
+
+    // Deploy a different binary the way installers do: unlink and write a new file
+    std::fs::remove_file(&binary).unwrap();
+    std::fs::write(&binary, "#!/bin/sh\nexec sleep \"$@\"\n").unwrap();
+
+    let issues = pm.check_drift(Some(&named("drifty"))).await.unwrap().remove(0).issues;
+    assert!(issues.contains(&DriftIssue::BinaryChanged { path: process.binary_path.clone().unwrap() }));
+    assert!(issues.iter().any(|issue| matches!(issue, DriftIssue::RunningBinaryReplaced { exe } if exe.ends_with("(deleted)"))));
+
+    std::fs::remove_dir_all(&app_dir).unwrap();
+    let issues = pm.check_drift(None).await.unwrap().remove(0).issues;
+    assert!(issues.iter().any(|issue| matches!(issue, DriftIssue::BinaryMissing { .. })));
+    assert!(issues.iter().any(|issue| matches!(issue, DriftIssue::WorkdirMissing { .. })));
+
+    pm.stop(&named("drifty")).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_timings_cover_start_and_list() {
+    let collector = TimingCollector::new();
+    let _guard = collector.set_default();
+
+    let (pm, _temp_dir) = create_test_process_manager().await;
+    pm.start(&named("timed"), "echo", vec!["hi".to_string()], HashMap::new(), None, None)
+        .await
+        .unwrap();
+    pm.list_processes().await.unwrap();
+
+    let names: Vec<&str> = collector.report().iter().map(|timing| timing.name).collect();
+    for expected in ["manager.init", "db.open", "db.query", "spawn", "proc.probe", "process.start", "process.list"] {
+        assert!(names.contains(&expected), "missing {} in {:?}", expected, names);
+    }
+    let table = collector.format_table();
+    assert!(table.lines().next().unwrap().starts_with("OPERATION"));
+    assert!(table.contains("process.start"));
+}
+
+#[tokio::test]
+async fn test_soft_delete_and_undelete() {
+    let (pm, _temp_dir) = create_test_process_manager().await;
+
+    pm.start(&named("undo_me"), "echo", vec!["kept".to_string()], HashMap::new(), None, None)
+        .await
+        .unwrap();
+    sleep(Duration::from_millis(200)).await;
+    let original = pm.get_process_status(&named("undo_me")).await.unwrap();
+
+    let outcome = pm.delete(&named("undo_me")).await.unwrap();
+    assert!(!outcome.hard);
+    assert_eq!(outcome.previous_status, ProcessStatus::Stopped);
+    assert!(pm.list_processes().await.unwrap().is_empty());
+    assert!(matches!(pm.get_process_status(&named("undo_me")).await, Err(Error::ProcessNotFound(_))));
+    assert!(std::path::Path::new(&original.log_path).exists(), "soft delete keeps logs");
+
+    pm.undelete(&named("undo_me")).await.unwrap();
+    let restored = pm.get_process_status(&named("undo_me")).await.unwrap();
+    assert_eq!(restored.id, original.id);
+    assert!(matches!(pm.undelete(&named("undo_me")).await, Err(Error::ProcessAlreadyExists(_))));
+}
+
+#[tokio::test]
+async fn test_notes_survive_restart_and_soft_delete() {
+    let (pm, _temp_dir) = create_test_process_manager().await;
+    pm.start(&named("noted"), "sleep", vec!["30".to_string()], HashMap::new(), None, None)
+        .await
+        .unwrap();
+
+    let first = pm.annotate_process(&named("noted"), "alice", "  raised ulimit  ").await.unwrap();
+    assert_eq!(first.text, "raised ulimit");
+    pm.annotate_process(&named("noted"), "bob", "watch memory").await.unwrap();
+
+    // Restart replaces the record, but notes follow the name
+    pm.restart(&named("noted")).await.unwrap();
+    let notes = pm.get_process_notes(&named("noted")).await.unwrap();
+    assert_eq!(notes.iter().map(|n| n.author.as_str()).collect::<Vec<_>>(), ["alice", "bob"]);
+
+    pm.delete_process_note(&named("noted"), first.id).await.unwrap();
+    assert!(matches!(pm.delete_process_note(&named("noted"), first.id).await, Err(Error::NoteNotFound(_, _))));
+
+    pm.delete(&named("noted")).await.unwrap();
+    pm.undelete(&named("noted")).await.unwrap();
+    assert_eq!(pm.get_process_notes(&named("noted")).await.unwrap().len(), 1);
+
+    pm.delete_with_options(&named("noted"), DeleteOptions { hard: true }).await.unwrap();
+    pm.start(&named("noted"), "sleep", vec!["30".to_string()], HashMap::new(), None, None)
+        .await
+        .unwrap();
+    assert!(pm.get_process_notes(&named("noted")).await.unwrap().is_empty(), "hard delete removes notes");
+    pm.delete(&named("noted")).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_note_limits() {
+    let (pm, _temp_dir) = create_test_process_manager().await;
+    pm.start(&named("capped"), "sleep", vec!["30".to_string()], HashMap::new(), None, None)
+        .await
+        .unwrap();
+
+    assert!(matches!(pm.annotate_process(&named("capped"), "alice", " \n").await, Err(Error::InvalidNote(_))));
+    assert!(matches!(pm.annotate_process(&named("missing"), "alice", "hi").await, Err(Error::ProcessNotFound(_))));
+    pm.annotate_process(&named("capped"), "alice", &"x".repeat(MAX_NOTE_BYTES)).await.unwrap();
+    let error = pm.annotate_process(&named("capped"), "alice", &"x".repeat(MAX_NOTE_BYTES + 1)).await.unwrap_err();
+    assert_eq!(error.to_string(), "Invalid note: note is 4097 bytes, the limit is 4096");
+
+    for i in 1..MAX_NOTES_PER_PROCESS {
+        pm.annotate_process(&named("capped"), "alice", &format!("note {}", i)).await.unwrap();
+    }
+    let error = pm.annotate_process(&named("capped"), "alice", "one too many").await.unwrap_err();
+    assert!(matches!(error, Error::NoteLimitReached(_, 100)), "{}", error);
+    assert!(error.to_string().contains("pmr notes capped --delete <id>"));
+    pm.delete(&named("capped")).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_undelete_conflicts_with_live_process() {
+    let (pm, _temp_dir) = create_test_process_manager().await;
+
+    pm.start(&named("reused_name"), "echo", vec!["first".to_string()], HashMap::new(), None, None)
+        .await
+        .unwrap();
+    pm.delete(&named("reused_name")).await.unwrap();
+
+    // The name is free again after a soft delete
+    pm.start(&named("reused_name"), "echo", vec!["second".to_string()], HashMap::new(), None, None)
+        .await
+        .unwrap();
+    assert!(matches!(
+        pm.undelete(&named("reused_name")).await,
+        Err(Error::ProcessAlreadyExists(_))
+    ));
+
+    pm.delete_with_options(&named("reused_name"), DeleteOptions { hard: true }).await.unwrap();
+    pm.undelete(&named("reused_name")).await.unwrap();
+    assert_eq!(pm.get_process_status(&named("reused_name")).await.unwrap().args, vec!["first".to_string()]);
+    assert!(matches!(pm.undelete(&named("missing")).await, Err(Error::ProcessNotFound(_))));
+}
+
+#[tokio::test]
+async fn test_read_only_manager_rejects_mutations() {
+    let temp_dir = TempDir::new().unwrap();
+    let pm = ProcessManager::in_directory(temp_dir.path()).await.unwrap();
+    pm.start(&named("observed"), "sleep", vec!["30".to_string()], HashMap::new(), None, None)
+        .await
+        .unwrap();
+
+    let ro = ProcessManager::read_only(Config::in_directory(temp_dir.path())).await.unwrap();
+    assert!(ro.is_read_only());
+    assert_eq!(ro.list_processes().await.unwrap().len(), 1);
+    assert_eq!(ro.get_process_status(&named("observed")).await.unwrap().status, ProcessStatus::Running);
+
+    let refused = [
+        ro.start(&named("other"), "sleep", vec!["30".to_string()], HashMap::new(), None, None).await.map(drop),
+        ro.stop(&named("observed")).await.map(drop),
+        ro.restart(&named("observed")).await.map(drop),
+        ro.delete(&named("observed")).await.map(drop),
+        ro.undelete(&named("observed")).await.map(drop),
+        ro.rotate_process_logs(&named("observed")).await.map(drop),
+    ];
+    for result in refused {
+        assert!(matches!(result, Err(Error::ReadOnly(_))), "{:?}", result);
+    }
+    assert!(matches!(ro.clear_processes(true).await, Err(Error::ReadOnly(_))));
+    assert!(matches!(ro.purge_deleted_processes().await, Err(Error::ReadOnly(_))));
+
+    pm.delete(&named("observed")).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_max_processes_boundary() {
+    let temp_dir = TempDir::new().unwrap();
+    let pm = ProcessManager::new(Config::in_directory(temp_dir.path()).with_max_processes(2)).await.unwrap();
+    let start = |name: &'static str| {
+        let (pm, name) = (&pm, named(name));
+        async move { pm.start(&name, "sleep", vec!["30".to_string()], HashMap::new(), None, None).await }
+    };
+
+    // Exactly at the limit is allowed
+    start("first").await.unwrap();
+    start("second").await.unwrap();
+    let stats = pm.stats().await.unwrap();
+    assert_eq!((stats.managed, stats.max_processes), (2, 2));
+
+    // One over is refused without creating a record
+    assert!(matches!(start("third").await, Err(Error::ProcessLimitReached(2, 2))));
+    assert!(matches!(pm.get_process_status(&named("third")).await, Err(Error::ProcessNotFound(_))));
+    assert!(matches!(pm.check_capacity(1).await, Err(Error::ProcessLimitReached(2, 2))));
+
+    // A delete frees capacity immediately, and undelete counts against the limit again
+    pm.delete(&named("second")).await.unwrap();
+    start("third").await.unwrap();
+    assert!(matches!(pm.undelete(&named("second")).await, Err(Error::ProcessLimitReached(2, 2))));
+
+    for name in ["first", "third"] {
+        pm.delete(&named(name)).await.unwrap();
+    }
+}
+
+#[tokio::test]
+async fn test_internal_processes_are_left_out() {
+    let temp_dir = TempDir::new().unwrap();
+    let pm = ProcessManager::new(Config::in_directory(temp_dir.path()).with_max_processes(1)).await.unwrap();
+    let sleep_args = || vec!["30".to_string()];
+    let internal = StartOptions { internal: true, ..Default::default() };
+    pm.start_with_options(&named(HTTP_SERVER_PROCESS_NAME), "sleep", sleep_args(), HashMap::new(), internal).await.unwrap();
+
+    // The reserved name is only for internal starts, and internal processes take no capacity
+    let taken = pm.start(&named("__pmr_http_server__"), "sleep", sleep_args(), HashMap::new(), None, None).await;
+    assert!(matches!(taken, Err(Error::ReservedName(_))));
+    pm.start(&named("web"), "sleep", sleep_args(), HashMap::new(), None, None).await.unwrap();
+
+    let listed: Vec<String> = pm.list_processes().await.unwrap().into_iter().map(|p| p.name.into_string()).collect();
+    assert_eq!(listed, vec!["web".to_string()]);
+    assert_eq!(pm.stats().await.unwrap().managed, 1);
+    assert_eq!(pm.process_counts().await.unwrap().running, 1);
+
+    // Looked up by name it is still there, and a restart keeps it internal
+    pm.restart(&named(HTTP_SERVER_PROCESS_NAME)).await.unwrap();
+    let server = pm.get_process_status(&named(HTTP_SERVER_PROCESS_NAME)).await.unwrap();
+    assert!(server.internal);
+    assert_eq!(server.status, ProcessStatus::Running);
+
+    let cleared = pm.clear_processes(true).await.unwrap();
+    assert_eq!(cleared.cleared_processes, vec!["web".to_string()]);
+    assert_eq!(pm.get_process_status(&named(HTTP_SERVER_PROCESS_NAME)).await.unwrap().status, ProcessStatus::Running);
+
+    pm.delete(&named(HTTP_SERVER_PROCESS_NAME)).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_names_only_is_much_faster_than_listing() {
+    let (pm, _temp_dir) = create_test_process_manager().await;
+    pm.start(&named("template"), "true", vec![], HashMap::new(), None, None).await.unwrap();
+    let template = pm.get_process_status(&named("template")).await.unwrap();
+    const ROWS: usize = 2000;
+    let db = Database::new(&format!("sqlite:{}?mode=rwc", pm.config().database_path.display())).await.unwrap();
+    for i in 0..ROWS {
+        let mut record = template.clone();
+        record.id = format!("row-{}", i);
+        record.name = named(&format!("row-{:04}", i));
+        record.status = ProcessStatus::Running;
+        db.insert_process(&record).await.unwrap();
+    }
+    // Best of three runs each, so a scheduling hiccup does not decide the comparison
+    let mut listing = std::time::Duration::MAX;
+    let mut names_only = std::time::Duration::MAX;
+    let mut names = Vec::new();
+    for _ in 0..3 {
+        let started = std::time::Instant::now();
+        assert_eq!(pm.list_processes().await.unwrap().len(), ROWS + 1);
+        listing = listing.min(started.elapsed());
+        let started = std::time::Instant::now();
+        names = pm.process_names(None).await.unwrap();
+        names_only = names_only.min(started.elapsed());
+    }
+
+    assert_eq!(names.len(), ROWS + 1);
+    assert_eq!(names.first().map(String::as_str), Some("row-0000"));
+    assert_eq!(pm.process_names(Some("row-19")).await.unwrap().len(), 100);
+    assert!(names_only * 10 < listing, "names took {:?}, the listing {:?}", names_only, listing);
+}
+
+#[tokio::test]
+async fn test_restart_cancels_pending_ephemeral_deletion() {
+    let (pm, _temp_dir) = create_test_process_manager().await;
+    let ephemeral = StartOptions { ephemeral_keep: Some(std::time::Duration::from_secs(3600)), ..Default::default() };
+    pm.start_with_options(&named("scratch"), "sleep", vec!["30".to_string()], HashMap::new(), ephemeral).await.unwrap();
+    let started = pm.get_process_status(&named("scratch")).await.unwrap();
+    assert_eq!(started.ephemeral_keep_secs, Some(3600));
+    assert!(started.delete_after.is_none());
+
+    pm.stop(&named("scratch")).await.unwrap();
+    let stopped = pm.get_process_status(&named("scratch")).await.unwrap();
+    let deadline = stopped.delete_after.expect("deletion is scheduled when it stops");
+    assert!(deadline - stopped.updated_at >= chrono::Duration::seconds(3599));
+
+    pm.restart(&named("scratch")).await.unwrap();
+    let restarted = pm.get_process_status(&named("scratch")).await.unwrap();
+    assert_eq!(restarted.status, ProcessStatus::Running);
+    assert_eq!(restarted.ephemeral_keep_secs, Some(3600));
+    assert!(restarted.delete_after.is_none());
+
+    // Clearing does not wait for the grace period
+    pm.stop(&named("scratch")).await.unwrap();
+    assert_eq!(pm.clear_processes(false).await.unwrap().cleared_processes, vec!["scratch".to_string()]);
+}
+
+#[tokio::test]
+async fn test_background_start_checks_do_not_serialize_starts() {
+    let (pm, _temp_dir) = create_test_process_manager().await;
+    let background = || StartOptions { check_in_background: true, ..Default::default() };
+
+    // Waiting out the 200ms start window each time would take 20 seconds
+    let started = std::time::Instant::now();
+    let mut outcomes = Vec::new();
+    for index in 0..100 {
+        let name = named(&format!("bench_{}", index));
+        outcomes.push(pm.start_with_options(&name, "sleep", vec!["30".to_string()], HashMap::new(), background()).await.unwrap());
+    }
+    let elapsed = started.elapsed();
+    assert!(elapsed < Duration::from_secs(5), "100 starts took {:?}", elapsed);
+
+    for outcome in outcomes {
+        let outcome = pm.finish_start_check(outcome).await;
+        assert_eq!(outcome.status, ProcessStatus::Running);
+        assert!(outcome.early_exit.is_none());
+    }
+    for index in 0..100 {
+        pm.stop(&named(&format!("bench_{}", index))).await.unwrap();
+    }
+}
+
+#[tokio::test]
+async fn test_background_start_check_records_early_exit() {
+    let (pm, _temp_dir) = create_test_process_manager().await;
+    let background = || StartOptions { check_in_background: true, ..Default::default() };
+
+    let outcome = pm
+        .start_with_options(&named("crasher"), "sh", vec!["-c".to_string(), "echo boom; exit 3".to_string()], HashMap::new(), background())
+        .await
+        .unwrap();
+    assert_eq!(outcome.status, ProcessStatus::Running);
+    assert!(outcome.early_exit.is_none());
+
+    // The final answer matches what a waiting start reports
+    let outcome = pm.finish_start_check(outcome).await;
+    assert_eq!(outcome.status, ProcessStatus::Failed);
+    let exit = outcome.early_exit.expect("exited within the start window");
+    assert_eq!(exit.exit_code, Some(3));
+    assert_eq!(exit.log_tail, vec!["boom".to_string()]);
+    let recorded = pm.get_process_status(&named("crasher")).await.unwrap();
+    assert_eq!(recorded.status, ProcessStatus::Failed);
+    assert_eq!(recorded.exit_code, Some(3));
+
+    let outcome = pm.start_with_options(&named("quick"), "true", vec![], HashMap::new(), background()).await.unwrap();
+    assert_eq!(pm.finish_start_check(outcome).await.status, ProcessStatus::Stopped);
+    assert_eq!(pm.get_process_status(&named("quick")).await.unwrap().status, ProcessStatus::Stopped);
+
+    // Strict starts still wait, so the failure is an error rather than a record
+    let strict = StartOptions { strict: true, ..background() };
+    let error = pm.start_with_options(&named("strict"), "false", vec![], HashMap::new(), strict).await.unwrap_err();
+    assert!(matches!(error, Error::StartExited(_, Some(1), _)));
+    assert!(pm.get_process_status(&named("strict")).await.is_err());
+}
+
+#[tokio::test]
+async fn test_failure_keeps_last_output_until_next_start() {
+    let (pm, temp_dir) = create_test_process_manager().await;
+    let healthy = temp_dir.path().join("healthy");
+    // Fails after the start window until the marker exists
+    let script = format!(
+        "if [ -e '{}' ]; then exec sleep 30; fi; for i in $(seq 1 20); do echo \"line $i\"; done; printf 'bad \\377\\n'; echo 'fatal: boom' >&2; sleep 0.5; exit 2",
+        healthy.display()
+    );
+    pm.start(&named("crashy"), "sh", vec!["-c".to_string(), script], HashMap::new(), None, None).await.unwrap();
+
+    let mut status = pm.get_process_status(&named("crashy")).await.unwrap();
+    for _ in 0..50 {
+        if status.status != ProcessStatus::Running {
+            break;
+        }
+        sleep(Duration::from_millis(100)).await;
+        status = pm.get_process_status(&named("crashy")).await.unwrap();
+    }
+    assert_eq!(status.exit_code, Some(2));
+    assert_eq!(status.last_output.len(), pmr::process::LAST_OUTPUT_LINES);
+    assert_eq!(status.last_output.first().map(String::as_str), Some("line 8"));
+    assert_eq!(&status.last_output[13..], ["bad \u{fffd}".to_string(), "fatal: boom".to_string()]);
+    assert!(status.last_output_lossy);
+
+    // A successful start leaves nothing stale behind, and a clean stop captures nothing
+    std::fs::write(&healthy, "").unwrap();
+    pm.restart(&named("crashy")).await.unwrap();
+    let restarted = pm.get_process_status(&named("crashy")).await.unwrap();
+    assert_eq!(restarted.status, ProcessStatus::Running);
+    assert!(restarted.last_output.is_empty());
+    assert!(!restarted.last_output_lossy);
+    pm.stop(&named("crashy")).await.unwrap();
+    assert!(pm.get_process_status(&named("crashy")).await.unwrap().last_output.is_empty());
+}
+
+#[tokio::test]
+async fn test_watched_changes_restart_once_per_burst() {
+    let (pm, temp_dir) = create_test_process_manager().await;
+    let src = temp_dir.path().join("src");
+    std::fs::create_dir_all(&src).unwrap();
+    let options = StartOptions {
+        working_dir: Some(temp_dir.path().to_string_lossy().to_string()),
+        watch: Some(WatchConfig::new(vec!["src".to_string()], vec!["*.tmp".to_string()], Some(300))),
+        ..Default::default()
+    };
+    pm.start_with_options(&named("watched"), "sleep", vec!["30".to_string()], HashMap::new(), options).await.unwrap();
+    let first_pid = pm.get_process_status(&named("watched")).await.unwrap().pid;
+    let watch = pm.get_process_status(&named("watched")).await.unwrap().watch.unwrap();
+    assert_eq!(watch.paths, vec![src.to_string_lossy().to_string()]);
+
+    // Counted as reported, since the record is briefly gone while a restart runs
+    let restarts = std::cell::RefCell::new(Vec::new());
+    let restart_count = || restarts.borrow().len();
+    let wait_for_restarts = |count: usize| async move {
+        for _ in 0..50 {
+            if restart_count() >= count {
+                break;
+            }
+            sleep(Duration::from_millis(100)).await;
+        }
+    };
+
+    let cancellation = Cancellation::new();
+    let name = named("watched");
+    let watching = watch_process(&pm, &name, &cancellation, |restart| {
+        restarts.borrow_mut().push(restart.map(|restart| restart.changes).map_err(|(_, e)| e.to_string()))
+    });
+    let editing = async {
+        sleep(Duration::from_millis(400)).await;
+        // Ignored files never restart the process
+        std::fs::write(src.join("scratch.tmp"), "x").unwrap();
+        std::fs::write(src.join("debug.log"), "x").unwrap();
+        sleep(Duration::from_millis(1000)).await;
+        assert_eq!(restart_count(), 0);
+
+        // A burst of edits within the debounce window restarts it once
+        for file in ["a.rs", "b.rs", "c.rs"] {
+            std::fs::write(src.join(file), "fn main() {}").unwrap();
+            sleep(Duration::from_millis(50)).await;
+        }
+        wait_for_restarts(1).await;
+        sleep(Duration::from_millis(1000)).await;
+        assert_eq!(restart_count(), 1);
+
+        std::fs::remove_file(src.join("a.rs")).unwrap();
+        wait_for_restarts(2).await;
+        cancellation.cancel();
+    };
+    let (watched, ()) = tokio::join!(watching, editing);
+    watched.unwrap();
+
+    assert_eq!(restarts.into_inner(), vec![Ok(3), Ok(1)]);
+    let history = pm.get_watch_restart_history(&named("watched")).await.unwrap();
+    assert_eq!(history[0].path, src.join("a.rs").to_string_lossy());
+    let restarted = pm.get_process_status(&named("watched")).await.unwrap();
+    assert_eq!(restarted.status, ProcessStatus::Running);
+    assert_ne!(restarted.pid, first_pid);
+    // Restarts keep watching the same paths
+    assert_eq!(restarted.watch, Some(watch));
+}
+
+#[tokio::test]
+async fn test_logs_quota_deletes_oldest_archives_across_processes() {
+    use pmr::events::ProcessEventKind;
+
+    let temp_dir = TempDir::new().unwrap();
+    let config = Config::in_directory(temp_dir.path()).with_logs_quota_bytes(3520);
+    let pm = ProcessManager::new(config.clone()).await.unwrap();
+    for name in ["alpha", "beta"] {
+        pm.start(&named(name), "printf", vec!["0123456789".to_string()], HashMap::new(), None, None).await.unwrap();
+    }
+
+    // Six 1000-byte archives, the two processes' ages interleaved
+    let logs = temp_dir.path().join("logs");
+    let now = std::time::SystemTime::now();
+    let archives = [("alpha.3.log", 600), ("beta.3.log", 500), ("alpha.2.log", 400), ("beta.2.log", 300), ("alpha.1.log", 200), ("beta.1.log", 100)];
+    for (file, age_secs) in archives {
+        let file = std::fs::File::create(logs.join(file)).unwrap();
+        file.set_len(1000).unwrap();
+        file.set_modified(now - Duration::from_secs(age_secs)).unwrap();
+    }
+
+    // The live logs and three archives fit: the three oldest go, whichever process they belong to
+    let enforcement = pm.enforce_logs_quota().await.unwrap().unwrap();
+    let deleted: Vec<&str> = enforcement.deleted.iter().map(|archive| archive.path.rsplit('/').next().unwrap()).collect();
+    assert_eq!(deleted, ["alpha.3.log", "beta.3.log", "alpha.2.log"]);
+    assert_eq!(enforcement.freed_bytes(), 3000);
+    assert!(!enforcement.over_quota());
+    assert_eq!(enforcement.used_before - enforcement.used_after, 3000);
+    for (file, _) in archives {
+        assert_eq!(logs.join(file).exists(), !deleted.contains(&file), "{}", file);
+    }
+    assert!(logs.join("alpha.log").exists() && logs.join("beta.log").exists());
+
+    let history = pm.get_log_rotation_history(&named("alpha")).await.unwrap();
+    let quota_entry = history.iter().find(|entry| entry.trigger == "quota").unwrap();
+    assert_eq!(quota_entry.archives_deleted.len(), 2);
+    let stats = pm.stats().await.unwrap();
+    assert_eq!(stats.logs.last_enforcement, Some(enforcement.clone()));
+    assert_eq!(stats.logs.usage.rotated_bytes(), 3000);
+    assert_eq!(stats.logs.usage.directories.len(), 1);
+    assert_eq!(stats.logs.usage.directories[0].processes, 2);
+
+    // Under the quota nothing more is deleted
+    assert!(pm.enforce_logs_quota().await.unwrap().is_none());
+
+    // Over even with every archive gone: all of them go, and the overrun is announced
+    let strict = ProcessManager::new(config.with_logs_quota_bytes(1)).await.unwrap();
+    let mut events = strict.subscribe();
+    let enforcement = strict.enforce_logs_quota().await.unwrap().unwrap();
+    assert_eq!(enforcement.deleted.len(), 3);
+    assert!(enforcement.over_quota());
+    assert_eq!(strict.stats().await.unwrap().logs.usage.rotated_bytes(), 0);
+    let event = tokio::time::timeout(Duration::from_secs(5), events.recv()).await.unwrap().unwrap();
+    assert_eq!(event.kind, ProcessEventKind::LogsOverQuota { used_bytes: enforcement.used_after, quota_bytes: 1 });
+}
+
+#[tokio::test]
+async fn test_logs_quota_is_not_exhausted_while_archives_remain() {
+    use pmr::events::ProcessEventKind;
+
+    let temp_dir = TempDir::new().unwrap();
+    let config = Config::in_directory(temp_dir.path()).with_logs_quota_bytes(1);
+    let pm = ProcessManager::new(config).await.unwrap();
+    pm.start(&named("alpha"), "printf", vec!["0123456789".to_string()], HashMap::new(), None, None).await.unwrap();
+
+    // An archive that cannot be deleted stays, so the logs are not over the quota with
+    // every archive gone
+    let logs = temp_dir.path().join("logs");
+    std::fs::File::create(logs.join("alpha.2.log")).unwrap().set_len(1000).unwrap();
+    std::fs::create_dir(logs.join("alpha.1.log")).unwrap();
+    let mut events = pm.subscribe();
+    let enforcement = pm.enforce_logs_quota().await.unwrap().unwrap();
+    let deleted: Vec<&str> = enforcement.deleted.iter().map(|archive| archive.path.rsplit('/').next().unwrap()).collect();
+    assert_eq!(deleted, ["alpha.2.log"]);
+    assert!(enforcement.over_quota());
+    assert!(logs.join("alpha.1.log").exists());
+    while let Ok(Ok(event)) = tokio::time::timeout(Duration::from_millis(200), events.recv()).await {
+        assert!(!matches!(event.kind, ProcessEventKind::LogsOverQuota { .. }), "{:?}", event);
+    }
+}
+
+#[tokio::test]
+async fn test_max_processes_zero_is_unlimited() {
+    let temp_dir = TempDir::new().unwrap();
+    let pm = ProcessManager::new(Config::in_directory(temp_dir.path()).with_max_processes(0)).await.unwrap();
+    for index in 0..3 {
+        pm.start(&named(&format!("free_{}", index)), "echo", vec![], HashMap::new(), None, None)
+            .await
+            .unwrap();
+    }
+    assert!(pm.check_capacity(usize::MAX / 2).await.is_ok());
+}
+
+#[tokio::test]
+async fn test_purge_deleted_respects_retention() {
+    let temp_dir = TempDir::new().unwrap();
+    let config = Config::new()
+        .with_database_path(temp_dir.path().join("test.db"))
+        .with_log_dir(temp_dir.path().join("logs"));
+    let pm = ProcessManager::new(config.clone()).await.unwrap();
+
+    pm.start(&named("purged"), "echo", vec!["bye".to_string()], HashMap::new(), None, None)
+        .await
+        .unwrap();
+    sleep(Duration::from_millis(200)).await;
+    let log_path = pm.get_process_status(&named("purged")).await.unwrap().log_path;
+    pm.delete(&named("purged")).await.unwrap();
+
+    // Still within the default retention
+    assert_eq!(pm.purge_deleted_processes().await.unwrap().cleared_count, 0);
+    drop(pm);
+
+    let pm = ProcessManager::new(config.with_deleted_retention(std::time::Duration::ZERO)).await.unwrap();
+    // Expired records are purged automatically when the manager starts
+    assert!(matches!(pm.undelete(&named("purged")).await, Err(Error::ProcessNotFound(_))));
+    assert!(!std::path::Path::new(&log_path).exists());
+}
+
+#[cfg(feature = "test-util")]
+#[tokio::test]
+async fn test_shared_log_file_survives_until_its_last_record_goes() {
+    let temp_dir = TempDir::new().unwrap();
+    let config = Config::new()
+        .with_database_path(temp_dir.path().join("test.db"))
+        .with_log_dir(temp_dir.path().join("logs"));
+    let pm = ProcessManager::new(config.clone()).await.unwrap();
+    for name in ["first", "second"] {
+        pm.start(&named(name), "echo", vec![name.to_string()], HashMap::new(), None, None).await.unwrap();
+    }
+    sleep(Duration::from_millis(200)).await;
+
+    // Forge the collision: both records point at the first one's log
+    let db = pm.get_database();
+    let shared = db.get_process_by_name(&named("first")).await.unwrap().unwrap().log_path;
+    let mut second = db.get_process_by_name(&named("second")).await.unwrap().unwrap();
+    second.log_path = shared.clone();
+    assert!(db.replace_process(&second).await.unwrap());
+    assert_eq!(db.count_processes_with_log_path(&shared).await.unwrap(), 2);
+
+    pm.delete_with_options(&named("first"), DeleteOptions { hard: true }).await.unwrap();
+    assert!(std::path::Path::new(&shared).exists(), "the log of 'second' was removed");
+
+    // The purge of the last record that uses it removes it
+    pm.delete(&named("second")).await.unwrap();
+    drop(pm);
+    let pm = ProcessManager::new(config.with_deleted_retention(std::time::Duration::ZERO)).await.unwrap();
+    assert!(matches!(pm.undelete(&named("second")).await, Err(Error::ProcessNotFound(_))));
+    assert!(!std::path::Path::new(&shared).exists());
+}
+
+#[cfg(feature = "cli")]
+#[test]
+fn test_clamp_log_lines() {
+    assert_eq!(Commands::clamp_log_lines(None, 100), (None, None));
+    assert_eq!(Commands::clamp_log_lines(Some(0), 100), (Some(0), None));
+    assert_eq!(Commands::clamp_log_lines(Some(100), 100), (Some(100), None));
+
+    let (lines, warning) = Commands::clamp_log_lines(Some(usize::MAX), 100);
+    assert_eq!(lines, Some(100));
+    assert!(warning.unwrap().contains("exceeds the maximum of 100"));
+}
+
+#[tokio::test]
+async fn test_event_stream_follows_lifecycle() {
+    use pmr::events::ProcessEventKind;
+
+    let (pm, _temp_dir) = create_test_process_manager().await;
+    let mut events = pm.subscribe();
+
+    pm.start(&named("evented"), "sh", vec!["-c".to_string(), "sleep 0.5; exit 3".to_string()], HashMap::new(), None, None)
+        .await
+        .unwrap();
+    let first_pid = pm.get_process_status(&named("evented")).await.unwrap().pid;
+    for _ in 0..50 {
+        if pm.get_process_status(&named("evented")).await.unwrap().status == ProcessStatus::Stopped {
+            break;
+        }
+        sleep(Duration::from_millis(100)).await;
+    }
+    pm.restart(&named("evented")).await.unwrap();
+    let second_pid = pm.get_process_status(&named("evented")).await.unwrap().pid;
+    pm.delete(&named("evented")).await.unwrap();
+
+    let mut received = Vec::new();
+    while let Ok(event) = events.try_recv() {
+        assert_eq!(event.process, "evented");
+        received.push(event.kind);
+    }
+    assert_eq!(received, vec![
+        ProcessEventKind::Started { pid: first_pid },
+        ProcessEventKind::Stopped { from: Some(ProcessStatus::Running), exit_code: Some(3) },
+        ProcessEventKind::Started { pid: second_pid },
+        ProcessEventKind::Restarted { pid: second_pid, previous_pid: first_pid },
+        ProcessEventKind::Stopped { from: Some(ProcessStatus::Running), exit_code: None },
+        ProcessEventKind::Deleted { from: ProcessStatus::Stopped, hard: false },
+    ]);
+}
+
+/// Start `script` in the background of a shell that exits at once, so the process is
+/// reparented away from the test and only its PID is known, as after a manager restart
+fn spawn_detached(script: &str) -> u32 {
+    let output = std::process::Command::new("sh")
+        .args(["-c", &format!("sh -c '{}' >/dev/null 2>&1 & echo $!", script)])
+        .output()
+        .unwrap();
+    String::from_utf8_lossy(&output.stdout).trim().parse().unwrap()
+}
+
+/// Record `pid` as a running process created at `created_at`, as an earlier manager would have
+async fn insert_running_record(db: &Database, name: &str, pid: u32, created_at: chrono::DateTime<chrono::Utc>) {
+    let record = pmr::database::ProcessRecord {
+        id: uuid::Uuid::new_v4().to_string(),
+        name: name.parse().unwrap(),
+        command: "sh".to_string(),
+        args: Vec::new(),
+        env_vars: HashMap::new(),
+        working_dir: "/tmp".to_string(),
+        pid: Some(pid),
+        status: ProcessStatus::Running,
+        created_at,
+        updated_at: created_at,
+        log_path: "/dev/null".to_string(),
+        log_dir: None,
+        cgroup_memory_max: None,
+        cgroup_cpu_max: None,
+        cgroup: None,
+        deleted_at: None,
+        binary_path: None,
+        binary_sha256: None,
+        canonical_workdir: None,
+        exit_code: None,
+        exit_reason: None,
+        cpu_affinity: None,
+        umask: None,
+        description: None,
+        start_snapshot: None,
+        default_env_keys: Vec::new(),
+        drift: None,
+        notes: Vec::new(),
+        recent_runs: Vec::new(),
+        flapping: false,
+        degraded: false,
+        inferred: false,
+        truncated_fields: Vec::new(),
+        kind: pmr::database::ProcessKind::Service,
+        runs: 1,
+        stop_signal: libc::SIGTERM,
+        log_dir_mode: LogDirMode::Default,
+        status_verified_at: None,
+        internal: false,
+        ephemeral_keep_secs: None,
+        delete_after: None,
+        last_output: Vec::new(),
+        last_output_lossy: false,
+        watch: None,
+        rotate_on_start: None,
+    };
+    db.insert_process(&record).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_new_manager_reattaches_running_processes() {
+    use pmr::events::ProcessEventKind;
+    use pmr::process::EXTERNAL_EXIT_REASON;
+
+    let temp_dir = TempDir::new().unwrap();
+    let config = Config::in_directory(temp_dir.path())
+        .with_reaper_interval(Duration::from_millis(50))
+        .with_stop_grace_period(Duration::from_millis(300));
+    config.ensure_directories().unwrap();
+    let db = Database::new(&format!("sqlite:{}?mode=rwc", config.database_path.display())).await.unwrap();
+    let now = chrono::Utc::now();
+    let short = spawn_detached("sleep 0.5");
+    let long = spawn_detached("sleep 30");
+    insert_running_record(&db, "short", short, now).await;
+    insert_running_record(&db, "long", long, now).await;
+    // A recycled PID: the record is older than whatever runs under the PID now
+    let recycled = spawn_detached("sleep 30");
+    insert_running_record(&db, "recycled", recycled, now - chrono::Duration::days(1)).await;
+
+    let pm = ProcessManager::new(config).await.unwrap();
+    let mut events = pm.subscribe();
+    assert_eq!(pm.external_processes(), vec!["long".to_string(), "short".to_string()]);
+
+    // The reaper notices the exit without anyone asking for the status
+    let event = tokio::time::timeout(Duration::from_secs(5), events.recv()).await.unwrap().unwrap();
+    assert_eq!(event.process, "short");
+    assert_eq!(event.kind, ProcessEventKind::Stopped { from: Some(ProcessStatus::Running), exit_code: None });
+    let record = db.get_process_by_name(&named("short")).await.unwrap().unwrap();
+    assert_eq!(record.status, ProcessStatus::Stopped);
+    assert_eq!(record.exit_code, None);
+    assert_eq!(record.exit_reason.as_deref(), Some(EXTERNAL_EXIT_REASON));
+    assert_eq!(pm.external_processes(), vec!["long".to_string()]);
+
+    // Stopping an adopted process signals its PID and stops watching it
+    pm.stop(&named("long")).await.unwrap();
+    assert!(pm.external_processes().is_empty());
+    assert_eq!(pm.get_process_status(&named("long")).await.unwrap().status, ProcessStatus::Stopped);
+
+    unsafe { libc::kill(recycled as i32, libc::SIGKILL) };
+}
+
+#[cfg(feature = "test-util")]
+#[tokio::test]
+async fn test_status_refresh_survives_failing_status_writes() {
+    let (pm, _temp_dir) = create_test_process_manager().await;
+    pm.start(&named("steady"), "sleep", vec!["30".to_string()], HashMap::new(), None, None).await.unwrap();
+    let doomed = pm.start(&named("doomed"), "sleep", vec!["30".to_string()], HashMap::new(), None, None).await.unwrap();
+    let db = pm.get_database();
+
+    // The disk goes read-only, then a process dies
+    db.fail_status_writes(true);
+    unsafe { libc::kill(doomed.pid.unwrap() as i32, libc::SIGKILL) };
+    sleep(Duration::from_millis(200)).await;
+
+    let processes = pm.list_processes().await.unwrap();
+    let status_of = |name: &str| processes.iter().find(|p| p.name == name).unwrap().status.clone();
+    assert_eq!(status_of("steady"), ProcessStatus::Running);
+    assert_ne!(status_of("doomed"), ProcessStatus::Running);
+    assert!(processes.iter().all(|p| p.degraded));
+    assert!(pm.is_degraded());
+    // The probed status was shown, not stored
+    assert_eq!(db.get_process_by_name(&named("doomed")).await.unwrap().unwrap().status, ProcessStatus::Running);
+
+    let status = pm.get_process_status(&named("doomed")).await.unwrap();
+    assert_ne!(status.status, ProcessStatus::Running);
+    assert!(status.degraded);
+
+    // Mutating commands still fail
+    let error = pm.stop(&named("steady")).await.unwrap_err();
+    assert!(pmr::database::is_storage_failure(&error), "{}", error);
+
+    // Once writes work again, the next refresh stores the status and clears the flag
+    db.fail_status_writes(false);
+    let status = pm.get_process_status(&named("doomed")).await.unwrap();
+    assert!(!status.degraded);
+    assert!(!pm.is_degraded());
+    assert_eq!(db.get_process_by_name(&named("doomed")).await.unwrap().unwrap().status, status.status);
+
+    pm.stop(&named("steady")).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_rotate_on_start_keeps_or_moves_the_followed_log() {
+    use std::os::unix::fs::MetadataExt;
+
+    let temp_dir = TempDir::new().unwrap();
+    let logs = temp_dir.path().join("logs");
+    let config = Config::in_directory(temp_dir.path())
+        .with_log_dir(logs.clone())
+        .with_log_rotation(LogRotationConfig { enabled: true, max_file_size: 1024, max_files: 3 })
+        .with_rotate_logs_on_start(false);
+    let pm = ProcessManager::new(config).await.unwrap();
+    std::fs::create_dir_all(&logs).unwrap();
+
+    for (name, rotate_on_start) in [("kept", None), ("moved", Some(true))] {
+        let log = logs.join(format!("{}.log", name));
+        std::fs::write(&log, "x".repeat(4096)).unwrap();
+        // What a `pmr logs -f` or a log shipper holds on to
+        let followed = std::fs::File::open(&log).unwrap();
+        let inode = followed.metadata().unwrap().ino();
+
+        let options = StartOptions { rotate_on_start, ..Default::default() };
+        let outcome = pm
+            .start_with_options(&named(name), "sh", vec!["-c".to_string(), "echo started; sleep 30".to_string()], HashMap::new(), options)
+            .await
+            .unwrap();
+        sleep(Duration::from_millis(300)).await;
+        let record = pm.get_process_status(&named(name)).await.unwrap();
+        assert_eq!(record.rotate_on_start, rotate_on_start);
+        let history = pm.get_log_rotation_history(&named(name)).await.unwrap();
+        let archive = logs.join(format!("{}.1.log", name));
+
+        if rotate_on_start == Some(true) {
+            // The followed file became the archive and the process writes to a new one
+            assert_eq!(outcome.rotated_log_size, Some(4096));
+            assert_eq!(std::fs::metadata(&archive).unwrap().ino(), inode);
+            assert_ne!(std::fs::metadata(&log).unwrap().ino(), inode);
+            assert_eq!(std::fs::read_to_string(&log).unwrap(), "started\n");
+            assert_eq!(history.iter().map(|entry| entry.trigger.as_str()).collect::<Vec<_>>(), ["size"]);
+        } else {
+            // The followed file is still the live log and sees the new output
+            assert_eq!(outcome.rotated_log_size, None);
+            assert!(!archive.exists());
+            assert_eq!(std::fs::metadata(&log).unwrap().ino(), inode);
+            assert_eq!(followed.metadata().unwrap().len(), 4096 + "started\n".len() as u64);
+            assert!(history.is_empty());
+        }
+        pm.delete_with_options(&named(name), DeleteOptions { hard: true }).await.unwrap();
+    }
+}
@@ -1,3 +1,5 @@
+mod support;
+
 use pmr::{
     config::{Config, LogRotationConfig},
     process::ProcessManager,
@@ -7,6 +9,7 @@ use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tempfile::TempDir;
 use tokio::time::sleep;
+use support::named;
 
 /// Helper function to create a test ProcessManager optimized for large scale tests
 async fn create_large_scale_test_process_manager() -> (ProcessManager, TempDir) {
@@ -21,7 +24,9 @@ async fn create_large_scale_test_process_manager() -> (ProcessManager, TempDir)
             enabled: true,
             max_file_size: 2 * 1024 * 1024, // 2MB for large scale tests
             max_files: 10,
-        });
+        })
+        // These tests deliberately go past the default max_processes ceiling
+        .with_max_processes(0);
     
     let pm = ProcessManager::new(config).await.unwrap();
     (pm, temp_dir)
@@ -40,10 +45,10 @@ async fn test_thousand_process_creation() {
     // Create 1000 processes with timing measurements
     for i in 0..num_processes {
         let process_start = Instant::now();
-        let name = format!("large_scale_test_{:04}", i);
+        let name = named(&format!("large_scale_test_{:04}", i));
         let env_vars = HashMap::new();
         
-        pm.start_process(
+        pm.start(
             &name,
             "echo",
             vec![format!("Large scale process {}", i)],
@@ -90,7 +95,7 @@ async fn test_thousand_process_creation() {
     let mut status_count = 0;
     
     for i in (0..num_processes).step_by(50) { // Check every 50th process
-        let name = format!("large_scale_test_{:04}", i);
+        let name = named(&format!("large_scale_test_{:04}", i));
         let _status = pm.get_process_status(&name).await.unwrap();
         status_count += 1;
     }
@@ -101,8 +106,8 @@ async fn test_thousand_process_creation() {
     // Clean up all processes
     let cleanup_start = Instant::now();
     for i in 0..num_processes {
-        let name = format!("large_scale_test_{:04}", i);
-        pm.delete_process(&name).await.unwrap();
+        let name = named(&format!("large_scale_test_{:04}", i));
+        pm.delete(&name).await.unwrap();
         
         if (i + 1) % 200 == 0 {
             println!("🧹 Cleaned up {}/{} processes", i + 1, num_processes);
@@ -148,11 +153,11 @@ async fn test_concurrent_thousand_process_management() {
             // Each worker creates its processes
             for i in 0..processes_per_worker {
                 let process_start = Instant::now();
-                let name = format!("concurrent_worker_{:02}_process_{:02}", worker_id, i);
+                let name = named(&format!("concurrent_worker_{:02}_process_{:02}", worker_id, i));
                 let env_vars = HashMap::new();
                 
                 pm_clone
-                    .start_process(
+                    .start(
                         &name,
                         "echo",
                         vec![format!("Concurrent Worker {} Process {}", worker_id, i)],
@@ -171,14 +176,14 @@ async fn test_concurrent_thousand_process_management() {
             
             // Worker performs status checks
             for i in 0..processes_per_worker {
-                let name = format!("concurrent_worker_{:02}_process_{:02}", worker_id, i);
+                let name = named(&format!("concurrent_worker_{:02}_process_{:02}", worker_id, i));
                 let _status = pm_clone.get_process_status(&name).await.unwrap();
             }
             
             // Worker cleans up its processes
             for i in 0..processes_per_worker {
-                let name = format!("concurrent_worker_{:02}_process_{:02}", worker_id, i);
-                pm_clone.delete_process(&name).await.unwrap();
+                let name = named(&format!("concurrent_worker_{:02}_process_{:02}", worker_id, i));
+                pm_clone.delete(&name).await.unwrap();
             }
             
             let worker_time = worker_start.elapsed();
@@ -236,10 +241,10 @@ async fn test_database_performance_at_scale() {
     let creation_start = Instant::now();
     
     for i in 0..num_processes {
-        let name = format!("db_scale_test_{:04}", i);
+        let name = named(&format!("db_scale_test_{:04}", i));
         let env_vars = HashMap::new();
         
-        pm.start_process(
+        pm.start(
             &name,
             "echo",
             vec![format!("DB scale test {}", i)],
@@ -289,7 +294,7 @@ async fn test_database_performance_at_scale() {
     for i in 0..random_queries {
         // Query random processes
         let random_id = i * 3 % num_processes; // Pseudo-random pattern
-        let name = format!("db_scale_test_{:04}", random_id);
+        let name = named(&format!("db_scale_test_{:04}", random_id));
         let _status = pm.get_process_status(&name).await.unwrap();
 
         if i % 100 == 0 && i > 0 {
@@ -306,8 +311,8 @@ async fn test_database_performance_at_scale() {
     let delete_start = Instant::now();
 
     for i in 0..num_processes {
-        let name = format!("db_scale_test_{:04}", i);
-        pm.delete_process(&name).await.unwrap();
+        let name = named(&format!("db_scale_test_{:04}", i));
+        pm.delete(&name).await.unwrap();
 
         if (i + 1) % 300 == 0 {
             println!("   Deleted {}/{} processes", i + 1, num_processes);
@@ -354,10 +359,10 @@ async fn test_memory_stability_at_scale() {
         // Create processes
         println!("   Creating {} processes...", processes_per_cycle);
         for i in 0..processes_per_cycle {
-            let name = format!("memory_scale_{}_{:03}", cycle, i);
+            let name = named(&format!("memory_scale_{}_{:03}", cycle, i));
             let env_vars = HashMap::new();
 
-            pm.start_process(
+            pm.start(
                 &name,
                 "echo",
                 vec![format!("Memory scale test cycle {} process {}", cycle, i)],
@@ -382,15 +387,15 @@ async fn test_memory_stability_at_scale() {
         // Perform some operations on the processes
         println!("   Performing operations on processes...");
         for i in (0..processes_per_cycle).step_by(20) {
-            let name = format!("memory_scale_{}_{:03}", cycle, i);
+            let name = named(&format!("memory_scale_{}_{:03}", cycle, i));
             let _status = pm.get_process_status(&name).await.unwrap();
         }
 
         // Delete all processes
         println!("   Cleaning up {} processes...", processes_per_cycle);
         for i in 0..processes_per_cycle {
-            let name = format!("memory_scale_{}_{:03}", cycle, i);
-            pm.delete_process(&name).await.unwrap();
+            let name = named(&format!("memory_scale_{}_{:03}", cycle, i));
+            pm.delete(&name).await.unwrap();
 
             if (i + 1) % 200 == 0 {
                 sleep(Duration::from_millis(5)).await;
@@ -429,10 +434,10 @@ async fn test_log_handling_at_scale() {
     // Create processes that generate substantial logs
     println!("🚀 Creating log-generating processes...");
     for i in 0..num_processes {
-        let name = format!("log_scale_test_{:03}", i);
+        let name = named(&format!("log_scale_test_{:03}", i));
         let env_vars = HashMap::new();
 
-        pm.start_process(
+        pm.start(
             &name,
             "sh",
             vec![
@@ -463,7 +468,7 @@ async fn test_log_handling_at_scale() {
     let log_read_start = Instant::now();
 
     for i in 0..num_processes {
-        let name = format!("log_scale_test_{:03}", i);
+        let name = named(&format!("log_scale_test_{:03}", i));
 
         // Read full logs
         let logs = pm.get_process_logs(&name, None).await.unwrap();
@@ -490,8 +495,8 @@ async fn test_log_handling_at_scale() {
     // Clean up
     println!("🧹 Cleaning up log test processes...");
     for i in 0..num_processes {
-        let name = format!("log_scale_test_{:03}", i);
-        pm.delete_process(&name).await.unwrap();
+        let name = named(&format!("log_scale_test_{:03}", i));
+        pm.delete(&name).await.unwrap();
     }
 
     let total_time = start_time.elapsed();
@@ -519,10 +524,10 @@ async fn test_system_resource_limits() {
     let start_time = Instant::now();
 
     for i in 0..max_attempts {
-        let name = format!("resource_limit_test_{:04}", i);
+        let name = named(&format!("resource_limit_test_{:04}", i));
         let env_vars = HashMap::new();
 
-        let result = pm.start_process(
+        let result = pm.start(
             &name,
             "sleep",
             vec!["30".to_string()], // Long-running process to test resource limits
@@ -584,7 +589,7 @@ async fn test_system_resource_limits() {
 
         for i in (0..sample_size).step_by(std::cmp::max(1, successful_processes / sample_size)) {
             if i < process_names.len() {
-                let _status = pm.get_process_status(&process_names[i]).await.unwrap();
+                let _status = pm.get_process_status(&named(&process_names[i])).await.unwrap();
             }
         }
 
@@ -598,8 +603,8 @@ async fn test_system_resource_limits() {
 
     for (i, name) in process_names.iter().enumerate() {
         // Stop the process first (since they're sleep processes)
-        let _ = pm.stop_process(name).await;
-        pm.delete_process(name).await.unwrap();
+        let _ = pm.stop(&named(name)).await;
+        pm.delete(&named(name)).await.unwrap();
 
         if (i + 1) % 100 == 0 {
             println!("   Cleaned up {}/{} processes", i + 1, process_names.len());
@@ -641,10 +646,10 @@ async fn test_mixed_workload_at_scale() {
         while start_time.elapsed() < duration {
             // Create some processes
             for i in 0..10 {
-                let name = format!("mixed_creator_{}_{}", process_counter, i);
+                let name = named(&format!("mixed_creator_{}_{}", process_counter, i));
                 let env_vars = HashMap::new();
 
-                if pm1.start_process(
+                if pm1.start(
                     &name,
                     "echo",
                     vec![format!("Mixed workload process {}", process_counter)],
@@ -660,7 +665,7 @@ async fn test_mixed_workload_at_scale() {
             if created_processes.len() > 50 {
                 for _ in 0..5 {
                     if let Some(name) = created_processes.pop() {
-                        let _ = pm1.delete_process(&name).await;
+                        let _ = pm1.delete(&name).await;
                     }
                 }
             }
@@ -671,7 +676,7 @@ async fn test_mixed_workload_at_scale() {
 
         // Clean up remaining processes
         for name in created_processes {
-            let _ = pm1.delete_process(&name).await;
+            let _ = pm1.delete(&name).await;
         }
 
         process_counter
@@ -710,10 +715,10 @@ async fn test_mixed_workload_at_scale() {
         while start_time.elapsed() < duration {
             // Create log-generating processes
             for i in 0..5 {
-                let name = format!("mixed_logger_{}_{}", batch_counter, i);
+                let name = named(&format!("mixed_logger_{}_{}", batch_counter, i));
                 let env_vars = HashMap::new();
 
-                if pm3.start_process(
+                if pm3.start(
                     &name,
                     "sh",
                     vec![
@@ -733,7 +738,7 @@ async fn test_mixed_workload_at_scale() {
                 for _ in 0..5 {
                     if let Some(name) = log_processes.pop() {
                         let _ = pm3.get_process_logs(&name, Some(20)).await;
-                        let _ = pm3.delete_process(&name).await;
+                        let _ = pm3.delete(&name).await;
                     }
                 }
             }
@@ -744,7 +749,7 @@ async fn test_mixed_workload_at_scale() {
 
         // Clean up remaining log processes
         for name in log_processes {
-            let _ = pm3.delete_process(&name).await;
+            let _ = pm3.delete(&name).await;
         }
 
         batch_counter
@@ -770,7 +775,7 @@ async fn test_mixed_workload_at_scale() {
 
     // Clean up any remaining processes
     for process in final_processes {
-        let _ = pm.delete_process(&process.name).await;
+        let _ = pm.delete(&process.name).await;
     }
 
     println!("✅ Mixed workload test completed successfully");
@@ -0,0 +1,63 @@
+//! Memory use and first-output latency of streamed process listings, measured with a
+//! counting allocator. This binary holds a single test so no other test allocates while
+//! it measures.
+
+mod common;
+mod support;
+
+use common::peak_allocation;
+use futures_util::StreamExt;
+use pmr::{
+    database::Database,
+    process::{ProcessManager, StatusRefresh},
+};
+use std::collections::HashMap;
+use std::time::Instant;
+use tempfile::TempDir;
+use support::named;
+
+#[tokio::test]
+async fn test_streamed_listing_does_not_scale_with_table_size() {
+    let temp_dir = TempDir::new().unwrap();
+    let pm = ProcessManager::in_directory(temp_dir.path()).await.unwrap();
+    pm.start(&named("template"), "true", vec![], HashMap::new(), None, None).await.unwrap();
+    let template = pm.get_process_status(&named("template")).await.unwrap();
+
+    const ROWS: usize = 3000;
+    let db = Database::new(&format!("sqlite:{}?mode=rwc", pm.config().database_path.display())).await.unwrap();
+    for i in 0..ROWS {
+        let mut record = template.clone();
+        record.id = format!("synthetic-{}", i);
+        record.name = named(&format!("synthetic-{:05}", i));
+        record.args = vec!["x".repeat(200)];
+        db.insert_process(&record).await.unwrap();
+    }
+
+    // Reading everything at once holds every record
+    let started = Instant::now();
+    let (all, all_peak) = peak_allocation(pm.list_processes_with_refresh(StatusRefresh::Stored)).await;
+    let all_elapsed = started.elapsed();
+    assert_eq!(all.unwrap().len(), ROWS + 1);
+
+    // The stream has its first record long before the whole table was read
+    let started = Instant::now();
+    let mut stream = pm.stream_processes(StatusRefresh::Stored);
+    let first = stream.next().await.unwrap().unwrap();
+    let first_elapsed = started.elapsed();
+    assert!(first.name.starts_with("synthetic-"));
+    assert!(first_elapsed * 5 < all_elapsed, "first record after {:?}, the full list took {:?}", first_elapsed, all_elapsed);
+
+    // Consuming it record by record keeps only a bounded window of rows in memory
+    let (count, stream_peak) = peak_allocation(async {
+        let mut count = 1;
+        while let Some(process) = stream.next().await {
+            process.unwrap();
+            count += 1;
+        }
+        count
+    })
+    .await;
+    assert_eq!(count, ROWS + 1);
+    assert!(stream_peak * 4 < all_peak, "streaming peaked at {} bytes, listing at {}", stream_peak, all_peak);
+    assert!(stream_peak < 512 * 1024, "streaming {} rows allocated {} bytes at peak", ROWS, stream_peak);
+}
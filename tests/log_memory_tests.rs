@@ -0,0 +1,40 @@
+//! Memory use of reading rotated logs, measured with a counting allocator. This binary
+//! holds a single test so no other test allocates while it measures.
+
+mod common;
+mod support;
+
+use common::peak_allocation;
+use pmr::process::ProcessManager;
+use std::collections::HashMap;
+use tempfile::TempDir;
+use support::named;
+
+#[tokio::test]
+async fn test_rotated_log_reads_do_not_scale_with_archive_size() {
+    let temp_dir = TempDir::new().unwrap();
+    let pm = ProcessManager::in_directory(temp_dir.path()).await.unwrap();
+    pm.start(&named("big"), "true", vec![], HashMap::new(), None, None).await.unwrap();
+
+    const ARCHIVE_BYTES: usize = 16 * 1024 * 1024;
+    const LIMIT: usize = 1024 * 1024;
+    let line = "x".repeat(1023) + "\n";
+    let archive = line.repeat(ARCHIVE_BYTES / line.len());
+    for i in 1..=3 {
+        std::fs::write(temp_dir.path().join("logs").join(format!("big.{}.log", i)), &archive).unwrap();
+    }
+    drop(archive);
+
+    let (content, peak) = peak_allocation(pm.read_rotated_logs(&named("big"), LIMIT)).await;
+    let content = content.unwrap();
+    assert!(content.truncated);
+    assert_eq!(content.bytes, LIMIT as u64);
+    assert_eq!(content.total_bytes, 3 * ARCHIVE_BYTES as u64);
+    // The content itself, plus a read block and bookkeeping, but not 48MB of archives
+    assert!(peak >= LIMIT, "the allocator saw only {} bytes", peak);
+    assert!(peak < LIMIT + 512 * 1024, "reading 48MB of archives allocated {} bytes at peak", peak);
+
+    let (listed, peak) = peak_allocation(pm.rotated_logs(&named("big"))).await;
+    assert_eq!(listed.unwrap().len(), 3);
+    assert!(peak < 256 * 1024, "listing rotated logs allocated {} bytes at peak", peak);
+}
@@ -1,3 +1,5 @@
+mod support;
+
 use pmr::{
     config::{Config, LogRotationConfig},
     process::ProcessManager,
@@ -7,6 +9,7 @@ use std::collections::HashMap;
 use std::time::{Duration, Instant};
 use tempfile::TempDir;
 use tokio::time::sleep;
+use support::named;
 
 /// Helper function to create a test ProcessManager with temporary directories
 async fn create_test_process_manager() -> (ProcessManager, TempDir) {
@@ -36,10 +39,10 @@ async fn test_multiple_process_creation_performance() {
     
     // Create multiple processes
     for i in 0..num_processes {
-        let name = format!("perf_test_{}", i);
+        let name = named(&format!("perf_test_{}", i));
         let env_vars = HashMap::new();
         
-        pm.start_process(
+        pm.start(
             &name,
             "echo",
             vec![format!("Process {}", i)],
@@ -66,8 +69,8 @@ async fn test_multiple_process_creation_performance() {
     
     // Clean up
     for i in 0..num_processes {
-        let name = format!("perf_test_{}", i);
-        pm.delete_process(&name).await.unwrap();
+        let name = named(&format!("perf_test_{}", i));
+        pm.delete(&name).await.unwrap();
     }
 }
 
@@ -78,10 +81,10 @@ async fn test_database_query_performance() {
     // Create some test processes
     let num_processes = 20;
     for i in 0..num_processes {
-        let name = format!("db_perf_test_{}", i);
+        let name = named(&format!("db_perf_test_{}", i));
         let env_vars = HashMap::new();
         
-        pm.start_process(
+        pm.start(
             &name,
             "echo",
             vec![format!("Process {}", i)],
@@ -114,7 +117,7 @@ async fn test_database_query_performance() {
     let start_time = Instant::now();
     
     for i in 0..num_processes {
-        let name = format!("db_perf_test_{}", i);
+        let name = named(&format!("db_perf_test_{}", i));
         let _status = pm.get_process_status(&name).await.unwrap();
     }
     
@@ -129,8 +132,8 @@ async fn test_database_query_performance() {
     
     // Clean up
     for i in 0..num_processes {
-        let name = format!("db_perf_test_{}", i);
-        pm.delete_process(&name).await.unwrap();
+        let name = named(&format!("db_perf_test_{}", i));
+        pm.delete(&name).await.unwrap();
     }
 }
 
@@ -145,11 +148,11 @@ async fn test_concurrent_operations_performance() {
 
     // Simulate concurrent-like operations by rapidly creating and managing processes
     for i in 0..num_operations {
-        let name = format!("concurrent_test_{}", i);
+        let name = named(&format!("concurrent_test_{}", i));
         let env_vars = HashMap::new();
 
         // Start process
-        pm.start_process(
+        pm.start(
             &name,
             "echo",
             vec![format!("Concurrent {}", i)],
@@ -166,14 +169,14 @@ async fn test_concurrent_operations_performance() {
 
     // Get status for all processes
     for i in 0..num_operations {
-        let name = format!("concurrent_test_{}", i);
+        let name = named(&format!("concurrent_test_{}", i));
         let _status = pm.get_process_status(&name).await.unwrap();
     }
 
     // Delete all processes
     for i in 0..num_operations {
-        let name = format!("concurrent_test_{}", i);
-        pm.delete_process(&name).await.unwrap();
+        let name = named(&format!("concurrent_test_{}", i));
+        pm.delete(&name).await.unwrap();
     }
 
     let concurrent_time = start_time.elapsed();
@@ -196,8 +199,8 @@ async fn test_log_file_operations_performance() {
     let name = "log_perf_test";
     let env_vars = HashMap::new();
     
-    pm.start_process(
-        name,
+    pm.start(
+        &named(name),
         "sh",
         vec![
             "-c".to_string(),
@@ -218,7 +221,7 @@ async fn test_log_file_operations_performance() {
     let iterations = 50;
     
     for _ in 0..iterations {
-        let _logs = pm.get_process_logs(name, None).await.unwrap();
+        let _logs = pm.get_process_logs(&named(name), None).await.unwrap();
     }
     
     let log_read_time = start_time.elapsed();
@@ -234,7 +237,7 @@ async fn test_log_file_operations_performance() {
     let start_time = Instant::now();
     
     for _ in 0..iterations {
-        let _logs = pm.get_process_logs(name, Some(10)).await.unwrap();
+        let _logs = pm.get_process_logs(&named(name), Some(10)).await.unwrap();
     }
     
     let limited_log_read_time = start_time.elapsed();
@@ -246,7 +249,7 @@ async fn test_log_file_operations_performance() {
     assert!(avg_limited_log_read_time <= avg_log_read_time);
     
     // Clean up
-    pm.delete_process(name).await.unwrap();
+    pm.delete(&named(name)).await.unwrap();
 }
 
 #[tokio::test]
@@ -262,10 +265,10 @@ async fn test_memory_usage_stability() {
         
         // Create processes
         for i in 0..processes_per_cycle {
-            let name = format!("memory_test_{}_{}", cycle, i);
+            let name = named(&format!("memory_test_{}_{}", cycle, i));
             let env_vars = HashMap::new();
             
-            pm.start_process(
+            pm.start(
                 &name,
                 "echo",
                 vec![format!("Memory test {} {}", cycle, i)],
@@ -286,8 +289,8 @@ async fn test_memory_usage_stability() {
         
         // Delete all processes
         for i in 0..processes_per_cycle {
-            let name = format!("memory_test_{}_{}", cycle, i);
-            pm.delete_process(&name).await.unwrap();
+            let name = named(&format!("memory_test_{}_{}", cycle, i));
+            pm.delete(&name).await.unwrap();
         }
         
         // Verify cleanup
@@ -305,10 +308,10 @@ async fn test_process_status_update_performance() {
     // Create some long-running processes
     let num_processes = 15;
     for i in 0..num_processes {
-        let name = format!("status_perf_test_{}", i);
+        let name = named(&format!("status_perf_test_{}", i));
         let env_vars = HashMap::new();
         
-        pm.start_process(
+        pm.start(
             &name,
             "sleep",
             vec!["2".to_string()], // Sleep for 2 seconds
@@ -349,7 +352,72 @@ async fn test_process_status_update_performance() {
     
     // Clean up
     for i in 0..num_processes {
-        let name = format!("status_perf_test_{}", i);
-        pm.delete_process(&name).await.unwrap();
+        let name = named(&format!("status_perf_test_{}", i));
+        pm.delete(&name).await.unwrap();
+    }
+}
+
+/// Indexes added for status and time filters; dropping them gives the pre-index baseline
+const FILTER_INDEXES: [&str; 5] = [
+    "idx_processes_live_status",
+    "idx_processes_live_created_at",
+    "idx_processes_updated_at",
+    "idx_processes_name_status",
+    "idx_processes_deleted_at",
+];
+
+/// Time `clear_processes(false)` over a table of `rows` records, half of them stopped
+async fn clear_latency(rows: usize, with_indexes: bool) -> Duration {
+    let temp_dir = TempDir::new().unwrap();
+    let pm = ProcessManager::new(Config::in_directory(temp_dir.path()).with_max_processes(0)).await.unwrap();
+    let database_url = format!("sqlite:{}", pm.config().database_path.display());
+    let pool = sqlx::SqlitePool::connect(&database_url).await.unwrap();
+
+    if !with_indexes {
+        for index in FILTER_INDEXES {
+            sqlx::query(&format!("DROP INDEX {}", index)).execute(&pool).await.unwrap();
+        }
+    }
+
+    // Seed records directly; nothing has to be spawned to measure the queries
+    let now = chrono::Utc::now().to_rfc3339();
+    let mut tx = pool.begin().await.unwrap();
+    for i in 0..rows {
+        let status = if i % 2 == 0 { "stopped" } else { "running" };
+        sqlx::query(
+            "INSERT INTO processes (id, name, command, args, env_vars, working_dir, pid, status, created_at, updated_at, log_path) \
+             VALUES (?, ?, 'echo', '[]', '{}', '/tmp', NULL, ?, ?, ?, '/tmp/bench.log')",
+        )
+        .bind(format!("bench-{}", i))
+        .bind(format!("bench_{}", i))
+        .bind(status)
+        .bind(&now)
+        .bind(&now)
+        .execute(&mut *tx)
+        .await
+        .unwrap();
     }
+    tx.commit().await.unwrap();
+    pool.close().await;
+
+    let start_time = Instant::now();
+    let result = pm.clear_processes(false).await.unwrap();
+    let elapsed = start_time.elapsed();
+    assert_eq!(result.cleared_count, rows / 2);
+    elapsed
+}
+
+#[tokio::test]
+async fn test_clear_latency_with_and_without_indexes() {
+    let rows = 2000;
+    let without_indexes = clear_latency(rows, false).await;
+    let with_indexes = clear_latency(rows, true).await;
+
+    println!(
+        "clear_processes(false) on {} rows: {:?} without filter indexes, {:?} with them",
+        rows, without_indexes, with_indexes
+    );
+
+    // Timings vary too much between machines to compare strictly; guard against pathologies
+    assert!(with_indexes < Duration::from_secs(30), "Clear too slow: {:?}", with_indexes);
 }
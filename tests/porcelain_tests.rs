@@ -0,0 +1,96 @@
+//! Golden-file tests pinning the exact bytes of `--format porcelain`. A difference here
+//! is a break of the scripting contract: bump `PORCELAIN_VERSION` instead of editing
+//! the golden files.
+
+#![cfg(feature = "cli")]
+
+use chrono::{TimeZone, Utc};
+use pmr::{
+    cli::OutputFormat,
+    database::{ProcessRecord, ProcessStatus},
+    formatter::Formatter,
+    name::ProcessName,
+};
+use std::collections::HashMap;
+
+/// Names are taken as stored, since rows from before the naming rules can hold any
+fn record(name: &str, status: ProcessStatus, pid: Option<u32>, exit_code: Option<i32>) -> ProcessRecord {
+    ProcessRecord {
+        id: format!("id-{}", name),
+        name: ProcessName::stored(name.to_string()),
+        command: "sleep".to_string(),
+        args: vec!["60".to_string()],
+        env_vars: HashMap::new(),
+        working_dir: "/srv/app".to_string(),
+        pid,
+        status,
+        created_at: Utc.with_ymd_and_hms(2025, 1, 2, 3, 4, 5).unwrap(),
+        updated_at: Utc.with_ymd_and_hms(2025, 1, 2, 3, 5, 0).unwrap(),
+        log_path: format!("/var/log/pmr/{}.log", name),
+        log_dir: None,
+        cgroup_memory_max: None,
+        cgroup_cpu_max: None,
+        cgroup: None,
+        deleted_at: None,
+        binary_path: None,
+        binary_sha256: None,
+        canonical_workdir: None,
+        exit_code,
+        exit_reason: None,
+        cpu_affinity: None,
+        umask: None,
+        description: None,
+        start_snapshot: None,
+        default_env_keys: Vec::new(),
+        drift: None,
+        notes: Vec::new(),
+        recent_runs: Vec::new(),
+        flapping: false,
+        degraded: false,
+        inferred: false,
+        truncated_fields: Vec::new(),
+        kind: pmr::database::ProcessKind::Service,
+        runs: 1,
+        stop_signal: libc::SIGTERM,
+        log_dir_mode: pmr::database::LogDirMode::Default,
+        status_verified_at: None,
+        internal: false,
+        ephemeral_keep_secs: None,
+        delete_after: None,
+        last_output: Vec::new(),
+        last_output_lossy: false,
+        watch: None,
+        rotate_on_start: None,
+    }
+}
+
+/// Output as the CLI prints it, with println!'s newline
+fn printed(output: String) -> String {
+    format!("{}\n", output)
+}
+
+#[test]
+fn test_porcelain_list_golden() {
+    let mut odd = record("tab\tand\nnewline", ProcessStatus::Failed, None, Some(2));
+    odd.log_path = "C:\\logs\\odd.log".to_string();
+    let processes = vec![
+        record("web", ProcessStatus::Running, Some(4242), None),
+        record("nightly backup", ProcessStatus::Stopped, None, Some(0)),
+        odd,
+    ];
+
+    // --quiet and --no-header do not touch the contract
+    let formatter = Formatter::new(OutputFormat::Porcelain).with_quiet(true).with_no_header(true);
+    assert_eq!(printed(formatter.format_process_list(&processes)), include_str!("golden/porcelain_list.txt"));
+    assert_eq!(formatter.format_empty_list_message("No processes found."), "");
+}
+
+#[test]
+fn test_porcelain_status_golden() {
+    let mut process = record("api server", ProcessStatus::Running, Some(31337), None);
+    process.command = "/usr/bin/env\tpython3".to_string();
+    process.working_dir = "/srv/my app\\v2".to_string();
+
+    let output = Formatter::new(OutputFormat::Porcelain).format_process_status(&process);
+    assert_eq!(printed(output), include_str!("golden/porcelain_status.txt"));
+}
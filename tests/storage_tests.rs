@@ -0,0 +1,355 @@
+//! The same checks against every storage backend. SQLite always runs; Postgres runs with the
+//! `postgres` feature when `PMR_TEST_POSTGRES_URL` points at a database the tests may write
+//! to, e.g. `postgres://postgres@127.0.0.1/postgres`. Each Postgres test uses host names
+//! of its own, so tests sharing the database do not see each other's rows.
+
+mod support;
+
+use pmr::{
+    database::{Database, LogRotationEntry, ProcessKind, ProcessRecord, ProcessRun, ProcessStatus, Reservation, WatchRestartEntry},
+    flapping::FailureHistory,
+    storage::StorageBackend,
+};
+use chrono::{Duration, Utc};
+use futures_util::StreamExt;
+use std::collections::HashMap;
+use tempfile::TempDir;
+use uuid::Uuid;
+use support::named;
+
+fn record(name: &str, status: ProcessStatus) -> ProcessRecord {
+    ProcessRecord {
+        id: Uuid::new_v4().to_string(),
+        name: named(name),
+        command: "sleep".to_string(),
+        args: vec!["60".to_string()],
+        env_vars: HashMap::from([("MODE".to_string(), "test".to_string())]),
+        working_dir: "/tmp".to_string(),
+        pid: Some(4242),
+        status,
+        created_at: Utc::now(),
+        updated_at: Utc::now(),
+        log_path: format!("/tmp/{}.log", name),
+        log_dir: None,
+        cgroup_memory_max: Some(64 * 1024 * 1024),
+        cgroup_cpu_max: None,
+        cgroup: None,
+        deleted_at: None,
+        binary_path: None,
+        binary_sha256: None,
+        canonical_workdir: None,
+        exit_code: None,
+        exit_reason: None,
+        cpu_affinity: None,
+        umask: None,
+        description: Some(format!("the {} service", name)),
+        start_snapshot: None,
+        default_env_keys: Vec::new(),
+        drift: None,
+        notes: Vec::new(),
+        recent_runs: Vec::new(),
+        flapping: false,
+        degraded: false,
+        inferred: false,
+        truncated_fields: Vec::new(),
+        kind: ProcessKind::Service,
+        runs: 1,
+        stop_signal: libc::SIGTERM,
+        log_dir_mode: pmr::database::LogDirMode::Default,
+        status_verified_at: None,
+        internal: false,
+        ephemeral_keep_secs: None,
+        delete_after: None,
+        last_output: Vec::new(),
+        last_output_lossy: false,
+        watch: None,
+        rotate_on_start: None,
+    }
+}
+
+async fn sqlite() -> (Database, TempDir) {
+    let temp_dir = TempDir::new().unwrap();
+    let database_url = format!("sqlite:{}?mode=rwc", temp_dir.path().join("test.db").display());
+    (Database::new(&database_url).await.unwrap(), temp_dir)
+}
+
+async fn check_process_records(db: &dyn StorageBackend) {
+    let web = record("web", ProcessStatus::Running);
+    db.insert_process(&web).await.unwrap();
+    db.insert_process(&record("web-worker", ProcessStatus::Stopped)).await.unwrap();
+    let mut internal = record("internal", ProcessStatus::Running);
+    internal.internal = true;
+    db.insert_process(&internal).await.unwrap();
+    // Names are unique among live records
+    assert!(db.insert_process(&record("web", ProcessStatus::Stopped)).await.is_err());
+
+    let stored = db.get_process_by_name(&named("web")).await.unwrap().unwrap();
+    assert_eq!(stored.id, web.id);
+    assert_eq!(stored.args, web.args);
+    assert_eq!(stored.env_vars, web.env_vars);
+    assert_eq!(stored.pid, Some(4242));
+    assert_eq!(stored.cgroup_memory_max, web.cgroup_memory_max);
+    assert_eq!(stored.created_at.timestamp_micros(), web.created_at.timestamp_micros());
+
+    assert_eq!(db.get_all_processes().await.unwrap().len(), 3);
+    assert_eq!(db.count_processes().await.unwrap(), 2);
+    let counts = db.count_processes_by_status().await.unwrap();
+    assert_eq!((counts.running, counts.stopped, counts.total), (1, 1, 2));
+    assert_eq!(db.get_process_names(Some("web")).await.unwrap(), vec!["web", "web-worker"]);
+    assert_eq!(db.get_process_names(Some("x")).await.unwrap(), Vec::<String>::new());
+    let found = db.search_processes("WORKER").await.unwrap();
+    assert_eq!(found.iter().map(|p| p.name.as_str()).collect::<Vec<_>>(), vec!["web-worker"]);
+    assert!(db.search_processes("%").await.unwrap().is_empty());
+    assert_eq!(db.get_processes_by_status(&[ProcessStatus::Stopped, ProcessStatus::Failed]).await.unwrap().len(), 1);
+    assert_eq!(db.stream_processes().count().await, 3);
+
+    let failed_at = Utc::now() - Duration::hours(3);
+    db.update_process_status(&named("web"), ProcessStatus::Failed, None, failed_at).await.unwrap();
+    db.record_exit(&named("web"), ProcessStatus::Failed, Some(3), Some("exited with 3"), failed_at).await.unwrap();
+    assert!(db.update_description(&named("web"), None).await.unwrap());
+    db.set_last_output(&web.id, &["bye".to_string()], true).await.unwrap();
+    let now = Utc::now();
+    db.mark_status_verified(std::slice::from_ref(&web.id), now).await.unwrap();
+    let stored = db.get_process_by_name(&named("web")).await.unwrap().unwrap();
+    assert_eq!(stored.status, ProcessStatus::Failed);
+    assert_eq!((stored.pid, stored.exit_code, stored.exit_reason.as_deref()), (None, Some(3), Some("exited with 3")));
+    assert_eq!(stored.description, None);
+    assert_eq!((stored.last_output.as_slice(), stored.last_output_lossy), (["bye".to_string()].as_slice(), true));
+    assert_eq!(stored.status_verified_at.map(|at| at.timestamp_micros()), Some(now.timestamp_micros()));
+    // Only status changes move updated_at, so it dates the failure
+    assert_eq!(stored.updated_at.timestamp_micros(), failed_at.timestamp_micros());
+    let summary = db.summarize_processes_by_status().await.unwrap();
+    assert_eq!((summary.counts.failed, summary.counts.stopped, summary.counts.total), (1, 1, 2));
+    assert_eq!(summary.oldest_failed.map(|at| at.timestamp_micros()), Some(failed_at.timestamp_micros()));
+    assert!(summary.oldest_stopped.is_some_and(|at| at > failed_at));
+
+    let mut replaced = stored.clone();
+    replaced.command = "true".to_string();
+    assert!(db.replace_process(&replaced).await.unwrap());
+    let reset = db.reset_for_restart(&named("web"), now).await.unwrap().unwrap();
+    assert_eq!((reset.command.as_str(), reset.status), ("true", ProcessStatus::Stopped));
+
+    #[cfg(feature = "test-util")]
+    {
+        db.fail_status_writes(true);
+        assert!(db.update_process_status(&named("web"), ProcessStatus::Running, Some(1), Utc::now()).await.is_err());
+        db.fail_status_writes(false);
+    }
+
+    let deleted_at = Utc::now() - Duration::days(2);
+    assert!(db.soft_delete_process_at(&named("web"), deleted_at).await.unwrap());
+    assert!(db.get_process_by_name(&named("web")).await.unwrap().is_none());
+    let deleted = db.get_deleted_process_by_name(&named("web")).await.unwrap().unwrap();
+    assert_eq!(db.stream_deleted_processes_before(Utc::now() - Duration::days(1)).count().await, 1);
+    // The name is free again while the old record waits in the trash
+    db.insert_process(&record("web", ProcessStatus::Running)).await.unwrap();
+    assert!(db.delete_process(&named("web")).await.unwrap());
+    assert!(db.restore_process(&deleted.id).await.unwrap());
+    assert_eq!(db.get_process_by_name(&named("web")).await.unwrap().unwrap().id, web.id);
+
+    db.schedule_deletion(&web.id, Utc::now() - Duration::seconds(1)).await.unwrap();
+    assert_eq!(db.stream_expired_ephemeral_processes(Utc::now()).count().await, 1);
+    assert!(db.delete_process_by_id(&web.id).await.unwrap());
+    assert!(!db.delete_process_by_id(&web.id).await.unwrap());
+}
+
+async fn check_history(db: &dyn StorageBackend) {
+    let web = named("web");
+    for changes in 1..=4 {
+        let entry = WatchRestartEntry { process_name: "web".to_string(), restarted_at: Utc::now(), path: "src/main.rs".to_string(), changes };
+        db.insert_watch_restart(&entry, 3).await.unwrap();
+    }
+    let restarts = db.get_watch_restarts(&web).await.unwrap();
+    assert_eq!(restarts.iter().map(|r| r.changes).collect::<Vec<_>>(), vec![2, 3, 4]);
+    assert_eq!(db.delete_watch_restarts(&web).await.unwrap(), 3);
+
+    let started_at = Utc::now() - Duration::minutes(10);
+    for minutes in 0..3 {
+        let run = ProcessRun {
+            process_name: "web".to_string(),
+            started_at: started_at + Duration::minutes(minutes),
+            ended_at: started_at + Duration::minutes(minutes + 1),
+            status: ProcessStatus::Succeeded,
+            exit_code: Some(0),
+            duration_ms: 60_000,
+        };
+        db.insert_process_run(&run, 10).await.unwrap();
+        // The same run recorded twice is kept once
+        db.insert_process_run(&run, 10).await.unwrap();
+    }
+    let runs = db.get_process_runs(&web, 2).await.unwrap();
+    assert_eq!(runs.len(), 2);
+    assert!(runs[0].started_at < runs[1].started_at);
+    assert_eq!(db.delete_process_runs(&web).await.unwrap(), 3);
+
+    let rotation = LogRotationEntry {
+        process_name: "web".to_string(),
+        rotated_at: Utc::now(),
+        trigger: "manual".to_string(),
+        rotated_from_size: 2048,
+        archives_shifted: 1,
+        archives_deleted: vec!["/tmp/web.log.5".to_string()],
+    };
+    db.insert_log_rotation(&rotation).await.unwrap();
+    assert_eq!(db.get_log_rotations(&web).await.unwrap()[0].archives_deleted, rotation.archives_deleted);
+    assert_eq!(db.delete_log_rotations(&web).await.unwrap(), 1);
+
+    let first = db.insert_process_note(&web, "ops", "deployed").await.unwrap();
+    let second = db.insert_process_note(&web, "ops", "rolled back").await.unwrap();
+    assert!(second.id > first.id);
+    assert!(db.delete_process_note(&web, first.id).await.unwrap());
+    assert_eq!(db.get_process_notes(&web).await.unwrap()[0].text, "rolled back");
+    assert_eq!(db.delete_process_notes(&web).await.unwrap(), 1);
+
+    assert_eq!(db.get_failure_history(&web).await.unwrap(), FailureHistory::default());
+    let history = FailureHistory { failures: vec![Utc::now()], flapping_since: Some(Utc::now()) };
+    db.save_failure_history(&web, &history).await.unwrap();
+    db.save_failure_history(&web, &history).await.unwrap();
+    assert_eq!(db.get_flapping_histories().await.unwrap().len(), 1);
+    assert!(db.delete_failure_history(&web).await.unwrap());
+    assert!(db.get_flapping_histories().await.unwrap().is_empty());
+}
+
+async fn check_reservations(db: &dyn StorageBackend) {
+    let now = Utc::now();
+    let reservation = |token: &str, minutes: i64| Reservation {
+        name: "web".to_string(),
+        token: token.to_string(),
+        reserved_at: now,
+        expires_at: now + Duration::minutes(minutes),
+    };
+    assert!(db.claim_reservation(&reservation("pmr_aaaaaaaa", 5), now).await.unwrap());
+    // Another token waits for it to expire; the holder may renew it
+    assert!(!db.claim_reservation(&reservation("pmr_bbbbbbbb", 5), now).await.unwrap());
+    assert!(db.claim_reservation(&reservation("pmr_aaaaaaaa", 10), now).await.unwrap());
+    assert_eq!(db.get_reservation(&named("web"), now).await.unwrap(), Some(reservation("pmr_aaaaaaaa", 10)));
+
+    let later = now + Duration::minutes(10);
+    assert_eq!(db.get_reservation(&named("web"), later).await.unwrap(), None);
+    assert!(db.claim_reservation(&reservation("pmr_bbbbbbbb", 20), later).await.unwrap());
+    assert_eq!(db.get_reservation(&named("web"), later).await.unwrap().unwrap().token, "pmr_bbbbbbbb");
+    assert_eq!(db.delete_expired_reservations(later).await.unwrap(), 0);
+    assert_eq!(db.delete_expired_reservations(now + Duration::minutes(20)).await.unwrap(), 1);
+    assert!(!db.delete_reservation(&named("web")).await.unwrap());
+}
+
+#[tokio::test]
+async fn test_sqlite_process_records() {
+    let (db, _temp_dir) = sqlite().await;
+    check_process_records(&db).await;
+}
+
+#[tokio::test]
+async fn test_sqlite_history() {
+    let (db, _temp_dir) = sqlite().await;
+    check_history(&db).await;
+}
+
+#[tokio::test]
+async fn test_sqlite_reservations() {
+    let (db, _temp_dir) = sqlite().await;
+    check_reservations(&db).await;
+}
+
+#[tokio::test]
+async fn test_sqlite_lists_itself_as_the_only_host() {
+    let (db, _temp_dir) = sqlite().await;
+    db.insert_process(&record("web", ProcessStatus::Running)).await.unwrap();
+    let listings = db.list_all_hosts().await.unwrap();
+    assert_eq!(listings.len(), 1);
+    assert_eq!(listings[0].host, StorageBackend::host(&db));
+    assert!(listings[0].current);
+    assert_eq!(listings[0].processes.len(), 1);
+}
+
+#[cfg(feature = "postgres")]
+mod postgres {
+    use super::*;
+    use pmr::config::{Config, StorageConfig, StorageKind};
+    use pmr::process::{ProcessManager, StatusRefresh};
+    use pmr::storage::postgres::PostgresStorage;
+
+    /// The test database, or None to skip
+    fn url() -> Option<String> {
+        std::env::var("PMR_TEST_POSTGRES_URL").ok().filter(|url| !url.is_empty())
+    }
+
+    fn unique_host(label: &str) -> String {
+        format!("{}-{}", label, Uuid::new_v4())
+    }
+
+    async fn connect(url: &str, host: &str) -> PostgresStorage {
+        PostgresStorage::connect(url, host, true).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_postgres_process_records() {
+        let Some(url) = url() else { return };
+        check_process_records(&connect(&url, &unique_host("records")).await).await;
+    }
+
+    #[tokio::test]
+    async fn test_postgres_history() {
+        let Some(url) = url() else { return };
+        check_history(&connect(&url, &unique_host("history")).await).await;
+    }
+
+    #[tokio::test]
+    async fn test_postgres_reservations() {
+        let Some(url) = url() else { return };
+        check_reservations(&connect(&url, &unique_host("reservations")).await).await;
+    }
+
+    #[tokio::test]
+    async fn test_postgres_hosts_see_only_their_own_rows() {
+        let Some(url) = url() else { return };
+        let (host_a, host_b) = (unique_host("a"), unique_host("b"));
+        let a = connect(&url, &host_a).await;
+        let b = connect(&url, &host_b).await;
+        assert_ne!(a.instance_id().await.unwrap(), b.instance_id().await.unwrap());
+
+        // Both hosts may run a `web`
+        a.insert_process(&record("web", ProcessStatus::Running)).await.unwrap();
+        b.insert_process(&record("web", ProcessStatus::Stopped)).await.unwrap();
+        assert_eq!(a.get_process_by_name(&named("web")).await.unwrap().unwrap().status, ProcessStatus::Running);
+        assert_eq!(b.get_process_by_name(&named("web")).await.unwrap().unwrap().status, ProcessStatus::Stopped);
+        assert_eq!(a.count_processes().await.unwrap(), 1);
+
+        assert!(b.delete_process(&named("web")).await.unwrap());
+        assert!(a.get_process_by_name(&named("web")).await.unwrap().is_some());
+        b.insert_process(&record("worker", ProcessStatus::Running)).await.unwrap();
+
+        let listings = a.list_all_hosts().await.unwrap();
+        let mine = listings.iter().find(|listing| listing.host == host_a).unwrap();
+        let theirs = listings.iter().find(|listing| listing.host == host_b).unwrap();
+        assert!(mine.current && !theirs.current);
+        assert_eq!(mine.processes[0].name, "web");
+        assert_eq!(theirs.processes[0].name, "worker");
+        assert!(listings.windows(2).all(|pair| pair[0].host < pair[1].host));
+    }
+
+    #[tokio::test]
+    async fn test_postgres_manager_starts_and_lists_across_hosts() {
+        let Some(url) = url() else { return };
+        let temp_dir = TempDir::new().unwrap();
+        let host = unique_host("manager");
+        let storage = StorageConfig { backend: StorageKind::Postgres, url: Some(url.clone()), host: Some(host.clone()) };
+        let pm = ProcessManager::new(Config::in_directory(temp_dir.path()).with_storage(storage)).await.unwrap();
+        let other = connect(&url, &unique_host("other")).await;
+        other.insert_process(&record("web", ProcessStatus::Running)).await.unwrap();
+
+        pm.start(&named("web"), "sleep", vec!["30".to_string()], HashMap::new(), None, None).await.unwrap();
+        let status = pm.get_process_status(&named("web")).await.unwrap();
+        assert_eq!(status.status, ProcessStatus::Running);
+
+        let listings = pm.list_all_hosts(StatusRefresh::All).await.unwrap();
+        let mine = listings.iter().find(|listing| listing.current).unwrap();
+        assert_eq!(mine.host, host);
+        assert_eq!(mine.processes[0].name, "web");
+        assert!(listings.iter().any(|listing| !listing.current && listing.processes.iter().any(|p| p.name == "web")));
+
+        pm.stop(&named("web")).await.unwrap();
+        pm.delete(&named("web")).await.unwrap();
+        assert!(other.get_process_by_name(&named("web")).await.unwrap().is_some());
+    }
+}
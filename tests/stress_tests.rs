@@ -1,13 +1,15 @@
+mod support;
+
 use pmr::{
     config::{Config, LogRotationConfig},
     process::ProcessManager,
-    database::ProcessStatus,
 };
 use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tempfile::TempDir;
 use tokio::time::sleep;
+use support::named;
 
 /// Helper function to create a test ProcessManager with temporary directories
 async fn create_test_process_manager() -> (ProcessManager, TempDir) {
@@ -39,10 +41,10 @@ async fn test_high_volume_process_creation() {
     
     // Create many processes rapidly
     for i in 0..num_processes {
-        let name = format!("stress_test_{}", i);
+        let name = named(&format!("stress_test_{}", i));
         let env_vars = HashMap::new();
         
-        pm.start_process(
+        pm.start(
             &name,
             "echo",
             vec![format!("Stress test process {}", i)],
@@ -69,7 +71,7 @@ async fn test_high_volume_process_creation() {
     // Test rapid status queries
     let query_start = Instant::now();
     for i in 0..num_processes {
-        let name = format!("stress_test_{}", i);
+        let name = named(&format!("stress_test_{}", i));
         let _status = pm.get_process_status(&name).await.unwrap();
     }
     let query_time = query_start.elapsed();
@@ -78,8 +80,8 @@ async fn test_high_volume_process_creation() {
     // Clean up all processes
     let cleanup_start = Instant::now();
     for i in 0..num_processes {
-        let name = format!("stress_test_{}", i);
-        pm.delete_process(&name).await.unwrap();
+        let name = named(&format!("stress_test_{}", i));
+        pm.delete(&name).await.unwrap();
     }
     let cleanup_time = cleanup_start.elapsed();
     println!("Cleaned up {} processes in {:?}", num_processes, cleanup_time);
@@ -115,11 +117,11 @@ async fn test_concurrent_heavy_load() {
             
             // Each worker creates multiple processes
             for i in 0..processes_per_worker {
-                let name = format!("worker_{}_process_{}", worker_id, i);
+                let name = named(&format!("worker_{}_process_{}", worker_id, i));
                 let env_vars = HashMap::new();
                 
                 pm_clone
-                    .start_process(
+                    .start(
                         &name,
                         "echo",
                         vec![format!("Worker {} Process {}", worker_id, i)],
@@ -136,14 +138,14 @@ async fn test_concurrent_heavy_load() {
             
             // Worker performs some operations
             for i in 0..processes_per_worker {
-                let name = format!("worker_{}_process_{}", worker_id, i);
+                let name = named(&format!("worker_{}_process_{}", worker_id, i));
                 let _status = pm_clone.get_process_status(&name).await.unwrap();
             }
             
             // Worker cleans up its processes
             for i in 0..processes_per_worker {
-                let name = format!("worker_{}_process_{}", worker_id, i);
-                pm_clone.delete_process(&name).await.unwrap();
+                let name = named(&format!("worker_{}_process_{}", worker_id, i));
+                pm_clone.delete(&name).await.unwrap();
             }
             
             let worker_time = worker_start.elapsed();
@@ -182,10 +184,10 @@ async fn test_rapid_start_stop_cycles() {
     for cycle in 0..cycles {
         // Start processes
         for i in 0..processes_per_cycle {
-            let name = format!("cycle_{}_process_{}", cycle, i);
+            let name = named(&format!("cycle_{}_process_{}", cycle, i));
             let env_vars = HashMap::new();
             
-            pm.start_process(
+            pm.start(
                 &name,
                 "sleep",
                 vec!["0.5".to_string()], // Short-lived process
@@ -206,13 +208,13 @@ async fn test_rapid_start_stop_cycles() {
         
         // Stop and delete processes
         for i in 0..processes_per_cycle {
-            let name = format!("cycle_{}_process_{}", cycle, i);
+            let name = named(&format!("cycle_{}_process_{}", cycle, i));
             
             // Try to stop (might already be stopped)
-            let _ = pm.stop_process(&name).await;
+            let _ = pm.stop(&name).await;
             
             // Delete
-            pm.delete_process(&name).await.unwrap();
+            pm.delete(&name).await.unwrap();
         }
         
         // Brief pause between cycles
@@ -241,11 +243,11 @@ async fn test_database_stress() {
     
     // Perform many database operations rapidly
     for i in 0..operations {
-        let name = format!("db_stress_{}", i);
+        let name = named(&format!("db_stress_{}", i));
         let env_vars = HashMap::new();
         
         // Create process
-        pm.start_process(
+        pm.start(
             &name,
             "echo",
             vec![format!("DB stress {}", i)],
@@ -265,7 +267,7 @@ async fn test_database_stress() {
         }
         
         // Delete process
-        pm.delete_process(&name).await.unwrap();
+        pm.delete(&name).await.unwrap();
         
         // Progress indicator
         if i % 100 == 0 && i > 0 {
@@ -296,10 +298,10 @@ async fn test_log_generation_stress() {
     
     // Create processes that generate lots of logs
     for i in 0..num_processes {
-        let name = format!("log_stress_{}", i);
+        let name = named(&format!("log_stress_{}", i));
         let env_vars = HashMap::new();
         
-        pm.start_process(
+        pm.start(
             &name,
             "sh",
             vec![
@@ -321,7 +323,7 @@ async fn test_log_generation_stress() {
     // Read logs from all processes
     let log_read_start = Instant::now();
     for i in 0..num_processes {
-        let name = format!("log_stress_{}", i);
+        let name = named(&format!("log_stress_{}", i));
         let logs = pm.get_process_logs(&name, None).await.unwrap();
         
         // Verify logs contain expected content
@@ -338,8 +340,8 @@ async fn test_log_generation_stress() {
     
     // Clean up
     for i in 0..num_processes {
-        let name = format!("log_stress_{}", i);
-        pm.delete_process(&name).await.unwrap();
+        let name = named(&format!("log_stress_{}", i));
+        pm.delete(&name).await.unwrap();
     }
     
     let total_time = start_time.elapsed();
@@ -360,16 +362,16 @@ async fn test_error_handling_under_stress() {
         // Mix of valid and invalid operations
         if i % 3 == 0 {
             // Try to operate on non-existent process
-            let result = pm.get_process_status("nonexistent_process").await;
+            let result = pm.get_process_status(&named("nonexistent_process")).await;
             if result.is_err() {
                 error_count += 1;
             }
         } else if i % 3 == 1 {
             // Try to create process with invalid command
-            let name = format!("invalid_cmd_{}", i);
+            let name = named(&format!("invalid_cmd_{}", i));
             let env_vars = HashMap::new();
             
-            let result = pm.start_process(
+            let result = pm.start(
                 &name,
                 "nonexistent_command_12345",
                 vec![],
@@ -380,14 +382,14 @@ async fn test_error_handling_under_stress() {
             
             if result.is_ok() {
                 // Clean up if somehow succeeded
-                let _ = pm.delete_process(&name).await;
+                let _ = pm.delete(&name).await;
             }
         } else {
             // Valid operation
-            let name = format!("valid_process_{}", i);
+            let name = named(&format!("valid_process_{}", i));
             let env_vars = HashMap::new();
             
-            pm.start_process(
+            pm.start(
                 &name,
                 "echo",
                 vec!["valid".to_string()],
@@ -398,7 +400,7 @@ async fn test_error_handling_under_stress() {
             .await
             .unwrap();
             
-            pm.delete_process(&name).await.unwrap();
+            pm.delete(&name).await.unwrap();
         }
         
         if i % 50 == 0 && i > 0 {
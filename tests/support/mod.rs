@@ -0,0 +1,9 @@
+//! Helpers shared by the integration test binaries. Kept apart from `common`, which
+//! installs a counting global allocator that only the memory tests want.
+
+use pmr::name::ProcessName;
+
+/// `name` as a [`ProcessName`], for names the tests know to be valid
+pub fn named(name: &str) -> ProcessName {
+    name.parse().unwrap()
+}
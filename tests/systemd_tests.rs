@@ -0,0 +1,92 @@
+//! Golden-file tests pinning the units of `pmr generate systemd` for representative
+//! configurations. Check a changed unit with `systemd-analyze verify` before updating
+//! the golden file.
+
+use pmr::{
+    config::ConfigPaths,
+    database::ProcessRecord,
+    systemd::{process_unit, serve_unit, UnitContext, UnitScope},
+};
+use std::path::PathBuf;
+
+fn context(scope: UnitScope, config_file: Option<&str>) -> UnitContext {
+    UnitContext {
+        pmr_binary: PathBuf::from("/usr/local/bin/pmr"),
+        working_dir: PathBuf::from("/srv/pmr"),
+        paths: ConfigPaths {
+            database: PathBuf::from("/home/deploy/.pmr/processes.db"),
+            default_log_dir: PathBuf::from("/srv/pmr/logs"),
+            config_file: config_file.map(PathBuf::from),
+        },
+        scope,
+    }
+}
+
+fn deploy() -> UnitScope {
+    UnitScope::System { user: "deploy".to_string() }
+}
+
+fn record(value: serde_json::Value) -> ProcessRecord {
+    let mut record = serde_json::json!({
+        "id": "id-web",
+        "env_vars": {},
+        "working_dir": "/srv/app",
+        "pid": null,
+        "status": "Stopped",
+        "created_at": "2025-01-02T03:04:05Z",
+        "updated_at": "2025-01-02T03:05:00Z",
+        "log_path": "/srv/pmr/logs/web.log",
+    });
+    record.as_object_mut().unwrap().extend(value.as_object().unwrap().clone());
+    serde_json::from_value(record).unwrap()
+}
+
+#[test]
+fn test_serve_unit_golden() {
+    let unit = serve_unit(&context(deploy(), Some("/etc/pmr/config.toml")), 9090).unwrap();
+    assert_eq!(unit, include_str!("golden/systemd_serve.service"));
+}
+
+#[test]
+fn test_serve_user_unit_golden() {
+    let unit = serve_unit(&context(UnitScope::User, None), 8080).unwrap();
+    assert_eq!(unit, include_str!("golden/systemd_serve_user.service"));
+}
+
+#[test]
+fn test_process_unit_golden() {
+    let process = record(serde_json::json!({
+        "name": "web",
+        "command": "/usr/bin/python3",
+        "args": ["-m", "http.server", "--bind", "", "8000; echo $HOME 100%", "say \"hi\""],
+        "env_vars": {"TZ": "UTC", "GREETING": "hello world", "QUOTA": "50%", "FROM_DEFAULTS": "x"},
+        "default_env_keys": ["FROM_DEFAULTS"],
+        "working_dir": "/srv/my app",
+        "log_dir": "/var/log/web",
+        "cgroup_memory_max": 536870912,
+        "cpu_affinity": "0-1",
+        "stop_signal": 2,
+    }));
+    let unit = process_unit(&context(deploy(), None), &process).unwrap();
+    assert_eq!(unit, include_str!("golden/systemd_process.service"));
+}
+
+#[test]
+fn test_job_user_unit_golden() {
+    let process = record(serde_json::json!({
+        "name": "nightly-backup",
+        "command": "./backup.sh",
+        "args": [],
+        "kind": "job",
+        "log_dir_mode": "workdir",
+    }));
+    let unit = process_unit(&context(UnitScope::User, None), &process).unwrap();
+    assert_eq!(unit, include_str!("golden/systemd_job_user.service"));
+}
+
+#[test]
+fn test_control_characters_in_plain_values_are_refused() {
+    let mut context = context(deploy(), None);
+    context.working_dir = PathBuf::from("/srv/a\nb");
+    assert!(serve_unit(&context, 8080).is_err());
+}